@@ -1,4 +1,5 @@
 use wsts::common::test_helpers::gen_signer_ids;
+use wsts::traits::Aggregator;
 use wsts::v1;
 use wsts::v1::test_helpers::{dkg, sign};
 
@@ -11,7 +12,7 @@ const K: u32 = 4;
 
 #[allow(non_snake_case)]
 pub fn bench_dkg(c: &mut Criterion) {
-    let mut rng = OsRng::default();
+    let mut rng = OsRng;
     let signer_ids = gen_signer_ids(N, K);
     let mut signers: Vec<v1::Signer> = signer_ids
         .iter()
@@ -25,7 +26,7 @@ pub fn bench_dkg(c: &mut Criterion) {
 
 #[allow(non_snake_case)]
 pub fn bench_party_sign(c: &mut Criterion) {
-    let mut rng = OsRng::default();
+    let mut rng = OsRng;
     let msg = "It was many and many a year ago".as_bytes();
     let signer_ids = gen_signer_ids(N, K);
     let mut signers: Vec<v1::Signer> = signer_ids
@@ -44,12 +45,12 @@ pub fn bench_party_sign(c: &mut Criterion) {
     let mut signers = signers[..(K * 3 / 4).try_into().unwrap()].to_vec();
 
     let s = format!("v1 party sign N={} T={} K={}", N, T, K);
-    c.bench_function(&s, |b| b.iter(|| sign(&msg, &mut signers, &mut rng)));
+    c.bench_function(&s, |b| b.iter(|| sign(msg, &mut signers, &mut rng)));
 }
 
 #[allow(non_snake_case)]
 pub fn bench_aggregator_sign(c: &mut Criterion) {
-    let mut rng = OsRng::default();
+    let mut rng = OsRng;
     let msg = "It was many and many a year ago".as_bytes();
     let signer_ids = gen_signer_ids(N, K);
     let mut signers: Vec<v1::Signer> = signer_ids
@@ -67,14 +68,14 @@ pub fn bench_aggregator_sign(c: &mut Criterion) {
 
     let mut signers = signers[..(K * 3 / 4).try_into().unwrap()].to_vec();
 
-    let mut aggregator =
-        v1::SignatureAggregator::new(N, T, A.clone()).expect("aggregator ctor failed");
+    let mut aggregator = v1::Aggregator::new(N, T);
+    aggregator.init(A.clone()).expect("aggregator init failed");
 
-    let (nonces, sig_shares) = sign(&msg, &mut signers, &mut rng);
+    let (nonces, sig_shares) = sign(msg, &mut signers, &mut rng);
 
     let s = format!("v1 group sign N={} T={} K={}", N, T, K);
     c.bench_function(&s, |b| {
-        b.iter(|| aggregator.sign(&msg, &nonces, &sig_shares))
+        b.iter(|| aggregator.sign(msg, &nonces, &sig_shares, &[]))
     });
 }
 
@@ -1,4 +1,5 @@
 use wsts::common::test_helpers::gen_signer_ids;
+use wsts::traits::Aggregator;
 use wsts::v2;
 use wsts::v2::test_helpers::{dkg, sign};
 
@@ -11,7 +12,7 @@ const K: u32 = 4;
 
 #[allow(non_snake_case)]
 pub fn bench_dkg(c: &mut Criterion) {
-    let mut rng = OsRng::default();
+    let mut rng = OsRng;
     let party_key_ids = gen_signer_ids(N, K);
     let mut signers: Vec<v2::Party> = party_key_ids
         .iter()
@@ -34,9 +35,9 @@ pub fn bench_dkg(c: &mut Criterion) {
 
 #[allow(non_snake_case)]
 pub fn bench_party_sign(c: &mut Criterion) {
-    let mut rng = OsRng::default();
+    let mut rng = OsRng;
     let msg = "It was many and many a year ago".as_bytes();
-    let party_key_ids = gen_signer_ids(N.try_into().unwrap(), K.try_into().unwrap());
+    let party_key_ids = gen_signer_ids(N, K);
     let mut signers: Vec<v2::Party> = party_key_ids
         .iter()
         .enumerate()
@@ -62,12 +63,12 @@ pub fn bench_party_sign(c: &mut Criterion) {
     let mut signers = signers[..(K * 3 / 4).try_into().unwrap()].to_vec();
 
     let s = format!("v2 party sign N={} T={} K={}", N, T, K);
-    c.bench_function(&s, |b| b.iter(|| sign(&msg, &mut signers, &mut rng)));
+    c.bench_function(&s, |b| b.iter(|| sign(msg, &mut signers, &mut rng)));
 }
 
 #[allow(non_snake_case)]
 pub fn bench_aggregator_sign(c: &mut Criterion) {
-    let mut rng = OsRng::default();
+    let mut rng = OsRng;
     let msg = "It was many and many a year ago".as_bytes();
     let party_key_ids = gen_signer_ids(N, K);
     let mut signers: Vec<v2::Party> = party_key_ids
@@ -93,14 +94,14 @@ pub fn bench_aggregator_sign(c: &mut Criterion) {
     };
 
     let mut signers = signers[..(K * 3 / 4).try_into().unwrap()].to_vec();
-    let mut aggregator =
-        v2::SignatureAggregator::new(N, T, A.clone()).expect("aggregator ctor failed");
+    let mut aggregator = v2::Aggregator::new(N, T);
+    aggregator.init(A.clone()).expect("aggregator init failed");
 
-    let (nonces, sig_shares, key_ids) = sign(&msg, &mut signers, &mut rng);
+    let (nonces, sig_shares, key_ids) = sign(msg, &mut signers, &mut rng);
 
     let s = format!("v2 group sign N={} T={} K={}", N, T, K);
     c.bench_function(&s, |b| {
-        b.iter(|| aggregator.sign(&msg, &nonces, &sig_shares, &key_ids))
+        b.iter(|| aggregator.sign(msg, &nonces, &sig_shares, &key_ids))
     });
 }
 
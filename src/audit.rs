@@ -0,0 +1,340 @@
+use p256k1::{ecdsa, point::Point};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    errors::{AuditError, TranscriptError},
+    net::{initial_seq, DkgFailure, DkgPublicShares, Packet, Signable},
+    state_machine::PublicKeys,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// Whether a packet was received or sent by the party keeping the log
+pub enum Direction {
+    /// The packet was received
+    Inbound,
+    /// The packet was sent
+    Outbound,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// One packet in an audit log, chained to the entry before it by hash
+pub struct AuditEntry {
+    /// The position of this entry in the log, starting from 0
+    pub index: u64,
+    /// The hash of the previous entry, or all zeros for the first entry
+    pub prev_hash: [u8; 32],
+    /// When the packet was recorded, in microseconds since the unix epoch
+    pub time: u64,
+    /// Whether the packet was received or sent
+    pub direction: Direction,
+    /// DKG round ID of the packet
+    pub dkg_id: u64,
+    /// Signing round ID of the packet, or zero for DKG packets
+    pub sign_id: u64,
+    /// Signing round iteration ID of the packet, or zero for DKG packets
+    pub sign_iter_id: u64,
+    /// The packet, with its signature
+    pub packet: Packet,
+    /// The hash of this entry, which the next entry links to
+    pub hash: [u8; 32],
+}
+
+impl AuditEntry {
+    /// Hash everything in this entry except the hash itself
+    pub fn compute_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update("AUDIT_ENTRY".as_bytes());
+        hasher.update(self.index.to_be_bytes());
+        hasher.update(self.prev_hash);
+        hasher.update(self.time.to_be_bytes());
+        hasher.update([self.direction as u8]);
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.sign_iter_id.to_be_bytes());
+        self.packet.hash(&mut hasher);
+        hasher.update((self.packet.sig.len() as u32).to_be_bytes());
+        hasher.update(&self.packet.sig);
+        hasher.finalize().into()
+    }
+
+    /// Was the packet signed under `domain` by the party it claims to be from, i.e. the signer
+    /// named in the message, or the coordinator if the message doesn't name a signer?
+    pub fn is_signed_by_sender(
+        &self,
+        domain: &[u8],
+        public_keys: &PublicKeys,
+        coordinator_public_key: &ecdsa::PublicKey,
+    ) -> bool {
+        let public_key = match self.packet.msg.id().signer_id {
+            Some(signer_id) => match public_keys.signers.get(&signer_id) {
+                Some(public_key) => public_key,
+                None => return false,
+            },
+            None => coordinator_public_key,
+        };
+        self.packet
+            .verify_with_domain(domain, &self.packet.sig, public_key)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// A tamper-evident log of every packet a signer or coordinator sends and receives, so disputes
+/// about who sent what can be settled from the entries afterwards
+pub struct AuditLog {
+    /// The entries which haven't been taken yet
+    entries: Vec<AuditEntry>,
+    /// The index of the next entry
+    next_index: u64,
+    /// The hash of the last entry recorded
+    last_hash: [u8; 32],
+}
+
+impl AuditLog {
+    /// Construct an empty AuditLog
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append `packet` to the log
+    pub fn record(&mut self, direction: Direction, packet: &Packet) {
+        let id = packet.msg.id();
+        let mut entry = AuditEntry {
+            index: self.next_index,
+            prev_hash: self.last_hash,
+            time: initial_seq(),
+            direction,
+            dkg_id: id.dkg_id,
+            sign_id: id.sign_id,
+            sign_iter_id: id.sign_iter_id,
+            packet: packet.clone(),
+            hash: [0; 32],
+        };
+        entry.hash = entry.compute_hash();
+
+        self.next_index += 1;
+        self.last_hash = entry.hash;
+        self.entries.push(entry);
+    }
+
+    /// The entries which haven't been taken yet
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// Take the entries recorded since the last call, e.g. to persist them; later entries still
+    /// chain to the taken ones
+    pub fn take_entries(&mut self) -> Vec<AuditEntry> {
+        std::mem::take(&mut self.entries)
+    }
+
+    /// Check that `entries` is a whole log from its first entry, in which no entry has been
+    /// changed, removed, or reordered
+    pub fn verify(entries: &[AuditEntry]) -> Result<(), AuditError> {
+        let mut prev_hash = [0; 32];
+        for (index, entry) in (0u64..).zip(entries) {
+            if entry.index != index {
+                return Err(if index == 0 {
+                    AuditError::MissingStart
+                } else {
+                    AuditError::BadIndex(entry.index, index)
+                });
+            }
+            if entry.prev_hash != prev_hash {
+                return Err(AuditError::BrokenChain(index));
+            }
+            if entry.hash != entry.compute_hash() {
+                return Err(AuditError::BadHash(index));
+            }
+            let id = entry.packet.msg.id();
+            if (entry.dkg_id, entry.sign_id, entry.sign_iter_id)
+                != (id.dkg_id, id.sign_id, id.sign_iter_id)
+            {
+                return Err(AuditError::BadRoundIds(index));
+            }
+            prev_hash = entry.hash;
+        }
+        Ok(())
+    }
+
+    /// The indices of the entries whose packets weren't signed by the party they claim to be from
+    pub fn unattributable(
+        entries: &[AuditEntry],
+        domain: &[u8],
+        public_keys: &PublicKeys,
+        coordinator_public_key: &ecdsa::PublicKey,
+    ) -> Vec<u64> {
+        entries
+            .iter()
+            .filter(|entry| !entry.is_signed_by_sender(domain, public_keys, coordinator_public_key))
+            .map(|entry| entry.index)
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// The public record of a finished DKG round, from which anyone can check that the group key
+/// came from the signers' polynomial commitments without trusting the coordinator
+pub struct DkgTranscript {
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// The threshold the polynomials were generated for
+    pub threshold: u32,
+    /// The public shares each signer sent, indexed by signer ID
+    pub public_shares: BTreeMap<u32, DkgPublicShares>,
+    /// The complaints of the signers which failed to finish DKG, indexed by signer ID
+    pub failures: BTreeMap<u32, DkgFailure>,
+    /// The group key which resulted from the round
+    pub aggregate_public_key: Point,
+}
+
+impl DkgTranscript {
+    /// The signers which finished DKG without complaint
+    pub fn succeeded(&self) -> Vec<u32> {
+        self.public_shares
+            .keys()
+            .filter(|signer_id| !self.failures.contains_key(signer_id))
+            .copied()
+            .collect()
+    }
+}
+
+/// Check that `transcript` is internally consistent: every polynomial commitment is well formed
+/// and proves ownership for its party, no party was dealt twice, every complaint came from a
+/// signer in the round, and the group key is the sum of the polynomials' constant terms
+pub fn verify_transcript(transcript: &DkgTranscript) -> Result<(), TranscriptError> {
+    let mut party_ids = BTreeSet::new();
+    let mut key = Point::default();
+    for (signer_id, shares) in &transcript.public_shares {
+        if shares.signer_id != *signer_id {
+            return Err(TranscriptError::SignerMismatch(
+                *signer_id,
+                shares.signer_id,
+            ));
+        }
+        if shares.dkg_id != transcript.dkg_id {
+            return Err(TranscriptError::BadDkgId(shares.dkg_id, transcript.dkg_id));
+        }
+        let invalid_party_ids = shares.invalid_party_ids(transcript.threshold);
+        if !invalid_party_ids.is_empty() {
+            return Err(TranscriptError::InvalidPolyCommitments(
+                *signer_id,
+                invalid_party_ids,
+            ));
+        }
+        for (party_id, comm) in &shares.comms {
+            if !party_ids.insert(*party_id) {
+                return Err(TranscriptError::DuplicateParty(*party_id));
+            }
+            key += comm.poly[0];
+        }
+    }
+
+    if let Some(signer_id) = transcript
+        .failures
+        .keys()
+        .find(|signer_id| !transcript.public_shares.contains_key(signer_id))
+    {
+        return Err(TranscriptError::UnknownComplainant(*signer_id));
+    }
+
+    if key != transcript.aggregate_public_key {
+        return Err(TranscriptError::KeyMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use p256k1::{ecdsa, scalar::Scalar};
+    use rand_core::OsRng;
+
+    use crate::{
+        audit::{AuditEntry, AuditLog, Direction},
+        errors::AuditError,
+        net::{DkgBegin, DkgEnd, DkgFailure, DkgStatus, Message, Packet},
+        state_machine::PublicKeys,
+    };
+
+    #[test]
+    fn audit_log() {
+        let mut rng = OsRng;
+        let domain = b"audit";
+        let coordinator_key = Scalar::random(&mut rng);
+        let signer_key = Scalar::random(&mut rng);
+        let coordinator_public_key = ecdsa::PublicKey::new(&coordinator_key).unwrap();
+        let mut public_keys = PublicKeys::default();
+        public_keys
+            .signers
+            .insert(0, ecdsa::PublicKey::new(&signer_key).unwrap());
+
+        let dkg_begin = Message::DkgBegin(DkgBegin {
+            dkg_id: 7,
+            epoch: 0,
+            threshold: 0,
+            num_signers: 0,
+            num_keys: 0,
+            pedersen: false,
+        });
+        let dkg_end = |status| {
+            Message::DkgEnd(DkgEnd {
+                dkg_id: 7,
+                signer_id: 0,
+                status,
+            })
+        };
+
+        let mut log = AuditLog::new();
+        log.record(
+            Direction::Inbound,
+            &Packet::new(dkg_begin, 1, domain, &coordinator_key).unwrap(),
+        );
+        log.record(
+            Direction::Outbound,
+            &Packet::new(dkg_end(DkgStatus::Success), 2, domain, &signer_key).unwrap(),
+        );
+        let mut entries = log.take_entries();
+        assert!(log.entries().is_empty());
+
+        // a packet claiming to be from signer 0, but signed by someone else
+        log.record(
+            Direction::Inbound,
+            &Packet::new(
+                dkg_end(DkgStatus::Failure(DkgFailure::Timeout)),
+                3,
+                domain,
+                &coordinator_key,
+            )
+            .unwrap(),
+        );
+        entries.extend(log.take_entries());
+
+        let json = serde_json::to_string(&entries).unwrap();
+        let entries: Vec<AuditEntry> = serde_json::from_str(&json).unwrap();
+        assert_eq!(AuditLog::verify(&entries), Ok(()));
+        assert_eq!(entries[0].dkg_id, 7);
+        assert_eq!(
+            AuditLog::unattributable(&entries, domain, &public_keys, &coordinator_public_key),
+            vec![2]
+        );
+
+        let mut tampered = entries.clone();
+        tampered[1].packet.msg = dkg_end(DkgStatus::Failure(DkgFailure::EchoMismatch(vec![1])));
+        assert_eq!(AuditLog::verify(&tampered), Err(AuditError::BadHash(1)));
+
+        let mut tampered = entries.clone();
+        tampered[1].sign_id = 1;
+        tampered[1].hash = tampered[1].compute_hash();
+        assert_eq!(AuditLog::verify(&tampered), Err(AuditError::BadRoundIds(1)));
+
+        let mut removed = entries.clone();
+        removed.remove(1);
+        assert_eq!(AuditLog::verify(&removed), Err(AuditError::BadIndex(2, 1)));
+
+        assert_eq!(
+            AuditLog::verify(&entries[1..]),
+            Err(AuditError::MissingStart)
+        );
+    }
+}
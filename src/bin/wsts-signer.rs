@@ -0,0 +1,265 @@
+use serde::Deserialize;
+use std::{
+    env, fs,
+    net::TcpStream,
+    path::{Path, PathBuf},
+    process, thread,
+    time::Duration,
+};
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+
+use wsts::{
+    net::{DkgEnd, DkgStatus, Message, Packet, ReplayGuard, Signable},
+    state_machine::signer::{SignerConfig, SigningRound},
+    store::FileStore,
+    traits::{Signer, Version},
+    v1, v2, v3,
+};
+
+/// The environment variable holding the passphrase which encrypts the state file
+const PASSPHRASE_VAR: &str = "WSTS_SIGNER_PASSPHRASE";
+
+/// The key which the signing round is saved under in the state file
+const STATE_KEY: &str = "signing_round";
+
+#[derive(Deserialize)]
+/// The signer's config file
+struct Config {
+    /// the address of the coordinator, e.g. `127.0.0.1:4000`
+    coordinator: String,
+    /// the WSTS version which the coordinator runs
+    #[serde(default = "default_version")]
+    version: Version,
+    /// the signer's keys and the network's public keys
+    signer: SignerConfig,
+    /// the `FileStore` where the signer's state is saved after each successful DKG, encrypted with
+    /// the passphrase in `WSTS_SIGNER_PASSPHRASE`; DKG has to be rerun after a restart if this is
+    /// unset
+    state_file: Option<PathBuf>,
+    /// whether to compress outbound DKG shares
+    #[serde(default)]
+    compress: bool,
+    /// how long to wait before reconnecting to the coordinator
+    #[serde(default = "default_reconnect_secs")]
+    reconnect_secs: u64,
+    /// whether to lock the signer's memory into RAM so key material is never swapped to disk,
+    /// which needs the mlock feature
+    #[serde(default)]
+    lock_memory: bool,
+}
+
+fn default_version() -> Version {
+    Version::V2
+}
+
+fn default_reconnect_secs() -> u64 {
+    5
+}
+
+fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() != 2 {
+        eprintln!("usage: {} <config.json>", args[0]);
+        process::exit(2);
+    }
+
+    if let Err(e) = load_config(Path::new(&args[1])).and_then(|config| match config.version {
+        Version::V1 => run::<v1::Signer>(config),
+        Version::V2 => run::<v2::Signer>(config),
+        Version::V3 => run::<v3::Signer>(config),
+    }) {
+        eprintln!("{e}");
+        process::exit(1);
+    }
+}
+
+fn load_config(path: &Path) -> Result<Config, String> {
+    let bytes = fs::read(path).map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+    serde_json::from_slice(&bytes).map_err(|e| format!("failed to parse {}: {e}", path.display()))
+}
+
+/// Load the signing round from the state file if there is one, otherwise create a new one from
+/// the config
+fn load_round<S: Signer>(
+    config: &Config,
+    passphrase: Option<&[u8]>,
+) -> Result<SigningRound<S>, String> {
+    let (Some(state_file), Some(passphrase)) = (&config.state_file, passphrase) else {
+        return SigningRound::from_config(config.signer.clone()).map_err(|e| e.to_string());
+    };
+    let store = FileStore::open(state_file)
+        .map_err(|e| format!("failed to open {}: {e}", state_file.display()))?;
+    let Some(round) = SigningRound::load_from_store(
+        &store,
+        STATE_KEY,
+        passphrase,
+        config.signer.network_private_key,
+        config.signer.public_keys.clone(),
+    )
+    .map_err(|e| format!("failed to load {}: {e}", state_file.display()))?
+    else {
+        info!("No saved state, starting without DKG shares");
+        return SigningRound::from_config(config.signer.clone()).map_err(|e| e.to_string());
+    };
+    info!(dkg_id = round.dkg_id, "Loaded state file");
+    Ok(round
+        .with_domain(&config.signer.domain)
+        .with_ban_list(config.signer.ban_list.clone()))
+}
+
+fn save_round<S: Signer>(
+    round: &SigningRound<S>,
+    state_file: &Path,
+    passphrase: &[u8],
+) -> Result<(), String> {
+    let mut store = FileStore::open(state_file)
+        .map_err(|e| format!("failed to open {}: {e}", state_file.display()))?;
+    round
+        .save_to_store(&mut store, STATE_KEY, passphrase)
+        .map_err(|e| format!("failed to save {}: {e}", state_file.display()))?;
+    info!(dkg_id = round.dkg_id, "Saved state file");
+    Ok(())
+}
+
+/// Lock the signer's memory into RAM if the config asks for it, before any key material is loaded
+fn lock_memory(config: &Config) -> Result<(), String> {
+    if !config.lock_memory {
+        return Ok(());
+    }
+    #[cfg(feature = "mlock")]
+    {
+        wsts::memlock::lock_all().map_err(|e| format!("failed to lock memory: {e}"))?;
+        info!("Locked memory");
+        Ok(())
+    }
+    #[cfg(not(feature = "mlock"))]
+    Err("lock_memory needs wsts-signer to be built with the mlock feature".to_string())
+}
+
+/// Authenticates the packets on the coordinator's connection, which carries both the
+/// coordinator's requests and the messages of the other signers it relays
+#[derive(Default)]
+struct Authenticator {
+    /// sequence numbers accepted from each signer
+    signers: ReplayGuard,
+    /// sequence numbers accepted from each coordinator, by its index in the public keys
+    coordinators: ReplayGuard,
+}
+
+impl Authenticator {
+    /// Check that `packet` was signed by the signer its message names, or by one of the
+    /// coordinators registered in `round`'s public keys if it names none, and that it isn't a
+    /// replay
+    fn check<S: Signer>(&mut self, round: &SigningRound<S>, packet: &Packet) -> Result<(), String> {
+        let public_keys = &round.public_keys;
+        if let Some(signer_id) = packet.msg.id().signer_id {
+            let public_key = public_keys
+                .signers
+                .get(&signer_id)
+                .ok_or_else(|| format!("packet from unknown signer {signer_id}"))?;
+            return self
+                .signers
+                .check(signer_id, packet, &round.domain, public_key)
+                .map_err(|e| e.to_string());
+        }
+
+        let (index, public_key) = public_keys
+            .coordinators
+            .iter()
+            .enumerate()
+            .find(|(_, key)| packet.verify_with_domain(&round.domain, &packet.sig, key))
+            .ok_or_else(|| "packet isn't signed by a signer or coordinator".to_string())?;
+        self.coordinators
+            .check(index as u32, packet, &round.domain, public_key)
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn run<S: Signer>(config: Config) -> Result<(), String> {
+    lock_memory(&config)?;
+    if config.signer.public_keys.coordinators.is_empty() {
+        return Err(
+            "the signer's public_keys must list the coordinators, to authenticate their packets"
+                .to_string(),
+        );
+    }
+    let passphrase = env::var(PASSPHRASE_VAR).ok().map(String::into_bytes);
+    if config.state_file.is_some() && passphrase.is_none() {
+        return Err(format!("{PASSPHRASE_VAR} must be set to use a state file"));
+    }
+    let mut round = load_round::<S>(&config, passphrase.as_deref())?;
+    // replays are tracked across reconnections, since a packet could be replayed on a new one
+    let mut authenticator = Authenticator::default();
+
+    loop {
+        match TcpStream::connect(&config.coordinator) {
+            Ok(mut stream) => {
+                info!(coordinator = config.coordinator, "Connected to coordinator");
+                if let Err(e) = serve(
+                    &config,
+                    passphrase.as_deref(),
+                    &mut round,
+                    &mut authenticator,
+                    &mut stream,
+                ) {
+                    warn!("Connection to coordinator closed: {e}");
+                }
+            }
+            Err(e) => warn!("Failed to connect to {}: {e}", config.coordinator),
+        }
+        thread::sleep(Duration::from_secs(config.reconnect_secs));
+    }
+}
+
+/// Process packets from the coordinator until the connection fails, dropping any which aren't
+/// authentic
+fn serve<S: Signer>(
+    config: &Config,
+    passphrase: Option<&[u8]>,
+    round: &mut SigningRound<S>,
+    authenticator: &mut Authenticator,
+    stream: &mut TcpStream,
+) -> Result<(), String> {
+    loop {
+        let packet = Packet::read_frame(stream).map_err(|e| e.to_string())?;
+        if let Err(e) = authenticator.check(round, &packet) {
+            warn!("Dropping {:?} packet: {e}", packet.msg.message_type());
+            continue;
+        }
+        let outbounds = match round.process_inbound_messages_with_context(&[packet]) {
+            Ok(outbounds) => outbounds,
+            Err(e) => {
+                warn!("Failed to process packet: {e}");
+                continue;
+            }
+        };
+
+        for outbound in &outbounds {
+            outbound
+                .write_frame(stream, config.compress)
+                .map_err(|e| e.to_string())?;
+        }
+
+        let dkg_succeeded = outbounds.iter().any(|outbound| {
+            matches!(
+                outbound.msg,
+                Message::DkgEnd(DkgEnd {
+                    status: DkgStatus::Success,
+                    ..
+                })
+            )
+        });
+        if let (true, Some(state_file), Some(passphrase)) =
+            (dkg_succeeded, &config.state_file, passphrase)
+        {
+            if let Err(e) = save_round(round, state_file, passphrase) {
+                warn!("{e}");
+            }
+        }
+    }
+}
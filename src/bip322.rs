@@ -0,0 +1,233 @@
+use p256k1::point::Point;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    common::TaprootTweak,
+    compute,
+    net::Packet,
+    state_machine::coordinator::{Coordinatable, Error as CoordinatorError},
+    taproot::{
+        compact_size, key_spend_sighash, OutPoint, SchnorrProof, Transaction, TxIn, TxOut,
+        SIGHASH_DEFAULT,
+    },
+};
+
+/// OP_1 followed by a 32 byte push, which starts every P2TR output script
+const P2TR_PREFIX: [u8; 2] = [0x51, 0x20];
+/// The output script of the to_sign transaction, a bare OP_RETURN
+const OP_RETURN: u8 = 0x6a;
+
+#[derive(thiserror::Error, Debug)]
+/// Errors from signing BIP-322 messages
+pub enum Error {
+    #[error("coordinator error {0}")]
+    /// The signing round couldn't be started
+    Coordinator(#[from] CoordinatorError),
+}
+
+/// Compute the BIP-322 tagged hash of `message`, which the to_spend transaction commits to
+pub fn message_hash(message: &[u8]) -> [u8; 32] {
+    let mut hasher = compute::tagged_hash("BIP0322-signed-message");
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// The P2TR output script of `group_key` tweaked by `tweak`, which is the message challenge
+/// when proving ownership of the group's taproot address
+pub fn message_challenge(group_key: &Point, tweak: TaprootTweak) -> Vec<u8> {
+    let output_key = compute::tweaked_public_key(group_key, tweak);
+    let mut script = P2TR_PREFIX.to_vec();
+    script.extend(output_key.x().to_bytes());
+    script
+}
+
+/// The ID of the virtual to_spend transaction for `message_challenge` and `message`, in the
+/// byte order it is serialized in
+pub fn to_spend_txid(message_challenge: &[u8], message: &[u8]) -> [u8; 32] {
+    // OP_0 followed by a push of the message hash
+    let mut script_sig = vec![0x00, 0x20];
+    script_sig.extend(message_hash(message));
+
+    let mut tx = Vec::new();
+    // version
+    tx.extend(0u32.to_le_bytes());
+    tx.extend(compact_size(1));
+    tx.extend([0; 32]);
+    tx.extend(u32::MAX.to_le_bytes());
+    tx.extend(compact_size(script_sig.len()));
+    tx.extend(script_sig);
+    // sequence
+    tx.extend(0u32.to_le_bytes());
+    tx.extend(compact_size(1));
+    // value
+    tx.extend(0u64.to_le_bytes());
+    tx.extend(compact_size(message_challenge.len()));
+    tx.extend(message_challenge);
+    // lock time
+    tx.extend(0u32.to_le_bytes());
+
+    Sha256::digest(Sha256::digest(tx)).into()
+}
+
+/// The virtual to_sign transaction, which spends the to_spend transaction for
+/// `message_challenge` and `message`
+pub fn to_sign(message_challenge: &[u8], message: &[u8]) -> Transaction {
+    Transaction {
+        version: 0,
+        lock_time: 0,
+        inputs: vec![TxIn {
+            previous_output: OutPoint {
+                txid: to_spend_txid(message_challenge, message),
+                vout: 0,
+            },
+            sequence: 0,
+        }],
+        outputs: vec![TxOut {
+            value: 0,
+            script_pubkey: vec![OP_RETURN],
+        }],
+    }
+}
+
+/// Compute the BIP-341 sighash which the group signs, with `sign_taproot` and `tweak`, to prove
+/// ownership of its taproot address for `message`
+pub fn sighash(group_key: &Point, tweak: TaprootTweak, message: &[u8]) -> [u8; 32] {
+    let message_challenge = message_challenge(group_key, tweak);
+    let to_sign = to_sign(&message_challenge, message);
+    let spent_output = TxOut {
+        value: 0,
+        script_pubkey: message_challenge,
+    };
+    key_spend_sighash(&to_sign, 0, &[spent_output], SIGHASH_DEFAULT)
+        .expect("to_sign has one input, which spends one output")
+}
+
+/// Start a taproot signing round over the BIP-322 sighash for `message`, under `group_key`
+/// tweaked by `tweak`
+pub fn start_signing_round<C: Coordinatable + ?Sized>(
+    coordinator: &mut C,
+    group_key: &Point,
+    tweak: TaprootTweak,
+    message: &[u8],
+) -> Result<Packet, Error> {
+    let sighash = sighash(group_key, tweak, message);
+    Ok(coordinator.start_signing_digest(&sighash, true, tweak)?)
+}
+
+/// Encode `proof` as the witness of the to_sign transaction, which is the BIP-322 simple
+/// signature before base64 encoding
+pub fn simple_signature(proof: &SchnorrProof) -> Vec<u8> {
+    let sig = proof.to_bytes();
+    let mut witness = compact_size(1);
+    witness.extend(compact_size(sig.len()));
+    witness.extend(sig);
+    witness
+}
+
+#[cfg(test)]
+mod test {
+    use p256k1::scalar::Scalar;
+    use rand_core::OsRng;
+    use sha2::{Digest, Sha256};
+
+    use super::{message_hash, sighash, simple_signature, to_sign, to_spend_txid};
+    use crate::{
+        common::TaprootTweak,
+        compute,
+        taproot::{test_helpers, SchnorrProof},
+        traits::{Aggregator, Signer},
+        v2,
+    };
+
+    /// A transaction ID in the byte order block explorers show it in
+    fn display_txid(txid: &[u8; 32]) -> String {
+        let mut txid = *txid;
+        txid.reverse();
+        hex::encode(txid)
+    }
+
+    #[test]
+    fn vectors() {
+        // BIP-322 test vectors, for the P2WPKH address bc1q9vza2e8x573nczrlzms0wvx3gsqjx7vavgkx0l
+        assert_eq!(
+            hex::encode(message_hash(b"")),
+            "c90c269c4f8fcbe6880f72a721ddfbf1914268a794cbb21cfafee13770ae19f1"
+        );
+        assert_eq!(
+            hex::encode(message_hash(b"Hello World")),
+            "f0eb03b1a75ac6d9847f55c624a99169b5dccba2a31f5b23bea77ba270de0a7a"
+        );
+
+        let challenge = hex::decode("00142b05d564e6a7a33c087f16e0f730d1440123799d").unwrap();
+        for (message, to_spend, to_sign_id) in [
+            (
+                &b""[..],
+                "c5680aa69bb8d860bf82d4e9cd3504b55dde018de765a91bb566283c545a99a7",
+                "1e9654e951a5ba44c8604c4de6c67fd78a27e81dcadcfe1edf638ba3aaebaed6",
+            ),
+            (
+                b"Hello World",
+                "b79d196740ad5217771c1098fc4a4b51e0535c32236c71f1ea4d61a2d603352b",
+                "88737ae86f2077145f93cc4b153ae9a1cb8d56afa511988c149c5c8c9d93bddf",
+            ),
+        ] {
+            let txid = to_spend_txid(&challenge, message);
+            assert_eq!(display_txid(&txid), to_spend);
+
+            // the to_sign transaction has no witness yet, so its ID covers all of it
+            let tx = to_sign(&challenge, message);
+            let mut bytes = Vec::new();
+            bytes.extend(tx.version.to_le_bytes());
+            bytes.push(1);
+            bytes.extend(tx.inputs[0].previous_output.txid);
+            bytes.extend(tx.inputs[0].previous_output.vout.to_le_bytes());
+            bytes.push(0);
+            bytes.extend(tx.inputs[0].sequence.to_le_bytes());
+            bytes.push(1);
+            bytes.extend(tx.outputs[0].value.to_le_bytes());
+            bytes.push(1);
+            bytes.extend(&tx.outputs[0].script_pubkey);
+            bytes.extend(tx.lock_time.to_le_bytes());
+            let txid: [u8; 32] = Sha256::digest(Sha256::digest(bytes)).into();
+            assert_eq!(display_txid(&txid), to_sign_id);
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn sign() {
+        let mut rng = OsRng;
+        let (Nk, Np, T) = (10, 4, 7);
+        let mut signers: Vec<v2::Signer> = [vec![0, 1, 2], vec![3, 4], vec![5, 6, 7], vec![8, 9]]
+            .iter()
+            .enumerate()
+            .map(|(id, ids)| v2::Signer::new(id.try_into().unwrap(), ids, Np, Nk, T, &mut rng))
+            .collect();
+        let A = test_helpers::dkg(&mut signers, &mut rng).unwrap();
+        let mut sig_agg = v2::Aggregator::new(Nk, T);
+        sig_agg.init(A).unwrap();
+        let group_key = sig_agg.poly[0];
+
+        for tweak in [
+            TaprootTweak::KeySpendNoScripts,
+            TaprootTweak::RawTweak(Scalar::random(&mut rng)),
+        ] {
+            let message = b"I own this address";
+            let sighash = sighash(&group_key, tweak, message);
+            let mut S = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
+            let key_ids = S.iter().flat_map(|s| s.get_key_ids()).collect::<Vec<u32>>();
+            let (nonces, sig_shares) = test_helpers::sign(&sighash, &mut S, &mut rng, tweak);
+            let proof = sig_agg
+                .sign_taproot(&sighash, &nonces, &sig_shares, &key_ids, tweak)
+                .unwrap();
+
+            let output_key = compute::tweaked_public_key(&group_key, tweak).x();
+            assert!(proof.verify(&output_key, &sighash));
+            assert_ne!(sighash, super::sighash(&group_key, tweak, b"other"));
+
+            let witness = simple_signature(&proof);
+            assert_eq!(witness[..2], [1, 64]);
+            assert_eq!(SchnorrProof::try_from(&witness[2..]).unwrap(), proof);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use p256k1::{point::Point, scalar::Scalar};
+use rand_core::{CryptoRng, RngCore};
+
+use crate::{
+    common::{PublicNonce, Signature},
+    compute, ct,
+};
+
+/// The message which nonces are bound to in blind signing rounds, since signers never see the
+/// real message
+pub const BINDING_MSG: &[u8] = b"WSTS/blind";
+
+/// Compute the aggregate nonce for a blind signing round from the participating `party_ids`
+/// and their `nonces`
+#[allow(non_snake_case)]
+pub fn aggregate_nonce(party_ids: &[u32], nonces: &[PublicNonce]) -> Point {
+    let (_, R) = compute::intermediate(BINDING_MSG, party_ids, nonces);
+    R
+}
+
+#[derive(Clone, Debug)]
+/// The secret blinding factors chosen by the requester of a blind signature.  A requester must
+/// not run concurrent blind signing rounds with the same signers, since the ROS attack can use
+/// concurrent rounds to forge an extra signature
+pub struct Blinding {
+    /// The factor which blinds the nonce and unblinds the response
+    pub alpha: Scalar,
+    /// The factor which blinds the challenge
+    pub beta: Scalar,
+}
+
+impl Blinding {
+    /// Choose random blinding factors
+    pub fn new<RNG: RngCore + CryptoRng>(rng: &mut RNG) -> Self {
+        Self {
+            alpha: Scalar::random(rng),
+            beta: Scalar::random(rng),
+        }
+    }
+
+    /// Blind the `aggregate_nonce`, returning the blinded nonce which goes in the final
+    /// signature, and the blinded challenge which is sent to the signers in place of `msg`
+    #[allow(non_snake_case)]
+    pub fn challenge(
+        &self,
+        aggregate_nonce: &Point,
+        group_key: &Point,
+        msg: &[u8],
+    ) -> (Point, Scalar) {
+        let R = aggregate_nonce + ct::mul_base(&self.alpha) + ct::mul(&self.beta, group_key);
+        let c = compute::challenge(group_key, &R, msg) + self.beta;
+
+        (R, c)
+    }
+
+    /// Unblind the aggregated response `z` into a signature over `msg` for the group key
+    pub fn unblind(&self, blinded_nonce: &Point, z: &Scalar) -> Signature {
+        Signature {
+            R: *blinded_nonce,
+            z: z + self.alpha,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{aggregate_nonce, Blinding};
+
+    use crate::{
+        common::PublicNonce,
+        errors::AggregatorError,
+        taproot::test_helpers,
+        traits::{Aggregator, Signer},
+        v1, v2,
+    };
+    use p256k1::scalar::Scalar;
+    use rand_core::OsRng;
+
+    #[test]
+    fn blind_sign_v1() {
+        let mut rng = OsRng;
+        let signer_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let signers: Vec<v1::Signer> = signer_ids
+            .iter()
+            .enumerate()
+            .map(|(id, ids)| v1::Signer::new(id.try_into().unwrap(), ids, 10, 7, &mut rng))
+            .collect();
+
+        // v1 binds a nonce to each key
+        blind_sign::<v1::Aggregator, v1::Signer>(signers, |signer| signer.get_key_ids());
+    }
+
+    #[test]
+    fn blind_sign_v2() {
+        let mut rng = OsRng;
+        let signer_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let signers: Vec<v2::Signer> = signer_ids
+            .iter()
+            .enumerate()
+            .map(|(id, ids)| v2::Signer::new(id.try_into().unwrap(), ids, 4, 10, 7, &mut rng))
+            .collect();
+
+        // v2 binds a nonce to each signer
+        blind_sign::<v2::Aggregator, v2::Signer>(signers, |signer| vec![signer.get_id()]);
+    }
+
+    #[allow(non_snake_case)]
+    fn blind_sign<Agg: Aggregator, S: Signer + Clone>(
+        mut signers: Vec<S>,
+        nonce_ids: fn(&S) -> Vec<u32>,
+    ) {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+
+        let A = match test_helpers::dkg(&mut signers, &mut rng) {
+            Ok(A) => A,
+            Err(secret_errors) => {
+                panic!("Got secret errors from DKG: {:?}", secret_errors);
+            }
+        };
+        let group_key = signers[0].get_group_key();
+
+        let mut S = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
+        let signer_ids: Vec<u32> = S.iter().map(|s| s.get_id()).collect();
+        let key_ids: Vec<u32> = S.iter().flat_map(|s| s.get_key_ids()).collect();
+        let party_ids: Vec<u32> = S.iter().flat_map(nonce_ids).collect();
+        let mut sig_agg = Agg::new(10, 7);
+        sig_agg.init(A).expect("aggregator init failed");
+
+        // the requester blinds the challenge, so the signers never see msg
+        let nonces: Vec<PublicNonce> = S.iter_mut().flat_map(|s| s.gen_nonces(&mut rng)).collect();
+        let blinding = Blinding::new(&mut rng);
+        let (R, c) = blinding.challenge(&aggregate_nonce(&party_ids, &nonces), &group_key, msg);
+
+        let sig_shares: Vec<_> = S
+            .iter()
+            .flat_map(|s| s.sign_blinded(&c, &signer_ids, &key_ids, &nonces))
+            .collect();
+        let z = sig_agg
+            .sign_blinded(&c, &nonces, &sig_shares, &key_ids)
+            .expect("aggregator sign_blinded failed");
+
+        let sig = blinding.unblind(&R, &z);
+        assert!(sig.verify(&group_key, msg));
+        assert!(!sig.verify(&group_key, "wrong message".as_bytes()));
+
+        // the blinded response is not itself a valid signature over the blinded nonce
+        assert!(!Blinding {
+            alpha: Scalar::from(0),
+            beta: blinding.beta,
+        }
+        .unblind(&R, &z)
+        .verify(&group_key, msg));
+
+        let mut bad_shares = sig_shares.clone();
+        bad_shares[0].z_i += Scalar::from(1);
+        assert!(matches!(
+            sig_agg.sign_blinded(&c, &nonces, &bad_shares, &key_ids),
+            Err(AggregatorError::BadPartySigs(_))
+        ));
+    }
+}
@@ -1,6 +1,9 @@
 use core::{
     fmt::{Debug, Display, Formatter, Result as FmtResult},
-    ops::Add,
+    iter::FlatMap,
+    marker::PhantomData,
+    ops::{Add, RangeInclusive},
+    slice,
 };
 use hashbrown::HashMap;
 use num_traits::{One, Zero};
@@ -11,13 +14,53 @@ use p256k1::{
 };
 use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::compute::challenge;
+use crate::compute::{self, Bip340Challenge};
+use crate::ct;
+use crate::errors::AggregatorError;
 use crate::schnorr::ID;
+use crate::taproot::SchnorrProof;
+use crate::traits::ChallengeHash;
 
 /// A merkle root is a 256 bit hash
 pub type MerkleRoot = [u8; 32];
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// How a taproot signature's group key is tweaked into the output key it verifies under
+pub enum TaprootTweak {
+    /// BIP341 key spend of an output with no script tree, tweaked by the hash of the key alone
+    KeySpendNoScripts,
+    /// BIP341 key spend of an output which commits to the script tree with this merkle root
+    KeySpend {
+        /// the merkle root of the script tree
+        merkle_root: MerkleRoot,
+    },
+    /// Use this scalar in place of the BIP341 tweak hash, e.g. for a tweak computed elsewhere
+    RawTweak(Scalar),
+}
+
+impl TaprootTweak {
+    /// The scalar which is multiplied by G and added to the even-y `public_key`
+    pub fn scalar(&self, public_key: &Point) -> Scalar {
+        match self {
+            Self::KeySpendNoScripts => compute::tweak(public_key, None),
+            Self::KeySpend { merkle_root } => compute::tweak(public_key, Some(*merkle_root)),
+            Self::RawTweak(tweak) => *tweak,
+        }
+    }
+}
+
+impl From<Option<MerkleRoot>> for TaprootTweak {
+    /// A BIP341 key spend, of an output with no script tree if `merkle_root` is None
+    fn from(merkle_root: Option<MerkleRoot>) -> Self {
+        match merkle_root {
+            Some(merkle_root) => Self::KeySpend { merkle_root },
+            None => Self::KeySpendNoScripts,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 /// A commitment to a polynonial, with a Schnorr proof of ownership bound to the ID
 pub struct PolyCommitment {
@@ -30,7 +73,14 @@ pub struct PolyCommitment {
 impl PolyCommitment {
     /// Verify the wrapped schnorr ID
     pub fn verify(&self) -> bool {
-        self.id.verify(&self.poly[0])
+        !self.poly.is_empty() && self.id.verify(&self.poly[0])
+    }
+
+    /// Verify the wrapped schnorr ID is for `party_id`, and the polynomial has `threshold` coefficients
+    pub fn verify_for(&self, party_id: u32, threshold: u32) -> bool {
+        self.poly.len() == usize::try_from(threshold).unwrap()
+            && self.id.id == Scalar::from(party_id)
+            && self.verify()
     }
 }
 
@@ -61,6 +111,34 @@ impl Nonce {
             e: Scalar::random(rng),
         }
     }
+
+    /// Construct a synthetic nonce, as BIP-340 does with `aux_rand`: each value hashes `secret`,
+    /// 32 bytes from `rng`, `aux_rand` and the round `context` together.  The nonce is
+    /// unpredictable as long as either `rng` or `aux_rand` is good, and even if both are weak it
+    /// can't be predicted without `secret`, so a bad RNG on one host doesn't leak its keys.
+    pub fn synthetic<RNG: RngCore + CryptoRng>(
+        secret: &[u8],
+        aux_rand: &[u8],
+        context: &[u8],
+        rng: &mut RNG,
+    ) -> Self {
+        let mut rand = [0u8; 32];
+        rng.fill_bytes(&mut rand);
+        let hash = |tag: &str| {
+            let mut hasher = Sha256::new();
+            hasher.update(tag.as_bytes());
+            for input in [secret, &rand, aux_rand, context] {
+                hasher.update((input.len() as u64).to_be_bytes());
+                hasher.update(input);
+            }
+            crate::util::hash_to_scalar(&mut hasher)
+        };
+
+        Self {
+            d: hash("WSTS/SYNTHETIC_NONCE/D"),
+            e: hash("WSTS/SYNTHETIC_NONCE/E"),
+        }
+    }
 }
 
 impl Zero for Nonce {
@@ -101,8 +179,8 @@ impl PublicNonce {
     /// Construct a public nonce from a private nonce
     pub fn from(n: &Nonce) -> Self {
         Self {
-            D: &n.d * G,
-            E: &n.e * G,
+            D: ct::mul_base(&n.d),
+            E: ct::mul_base(&n.e),
         }
     }
 }
@@ -121,10 +199,136 @@ pub struct SignatureShare {
     /// The party signature
     pub z_i: Scalar,
     /// The key IDs of the party
-    pub key_ids: Vec<u32>,
+    pub key_ids: KeyIdRanges,
+}
+
+/// The most key IDs a `KeyIdRanges` may hold, so a peer can't make us expand a few bytes of runs
+/// into gigabytes of key IDs
+pub const MAX_KEY_IDS: usize = u16::MAX as usize;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "Vec<(u32, u32)>", into = "Vec<(u32, u32)>")]
+/// Key IDs stored as runs of consecutive IDs, in the order they were given, so a signer holding a
+/// contiguous block of tens of thousands of keys costs the same as one holding a single key, both
+/// in memory and on the wire
+pub struct KeyIdRanges {
+    /// inclusive (first, last) key ID of each run
+    runs: Vec<(u32, u32)>,
+}
+
+impl KeyIdRanges {
+    /// The inclusive (first, last) key ID of each run
+    pub fn runs(&self) -> &[(u32, u32)] {
+        &self.runs
+    }
+
+    /// The number of key IDs
+    pub fn len(&self) -> usize {
+        self.runs
+            .iter()
+            .map(|(first, last)| (last - first) as usize + 1)
+            .sum()
+    }
+
+    /// The key IDs as a list, in their original order
+    pub fn to_vec(&self) -> Vec<u32> {
+        self.iter().collect()
+    }
+
+    /// Are there no key IDs?
+    pub fn is_empty(&self) -> bool {
+        self.runs.is_empty()
+    }
+
+    /// Is `key_id` one of the key IDs?
+    pub fn contains(&self, key_id: u32) -> bool {
+        self.runs
+            .iter()
+            .any(|(first, last)| (*first..=*last).contains(&key_id))
+    }
+
+    /// Iterate over the key IDs in their original order
+    pub fn iter(&self) -> KeyIdIter<'_> {
+        self.runs.iter().flat_map(|(first, last)| *first..=*last)
+    }
+}
+
+impl TryFrom<Vec<(u32, u32)>> for KeyIdRanges {
+    type Error = String;
+
+    /// Check that the runs are well formed, don't overlap, and hold at most `MAX_KEY_IDS` key IDs,
+    /// before anything is expanded
+    fn try_from(runs: Vec<(u32, u32)>) -> Result<Self, Self::Error> {
+        if let Some((first, last)) = runs.iter().find(|(first, last)| first > last) {
+            return Err(format!("bad key ID run {first}..={last}"));
+        }
+
+        let total = runs
+            .iter()
+            .map(|(first, last)| u64::from(last - first) + 1)
+            .sum::<u64>();
+        if total > MAX_KEY_IDS as u64 {
+            return Err(format!(
+                "{total} key IDs is more than the {MAX_KEY_IDS} allowed"
+            ));
+        }
+
+        let mut sorted = runs.clone();
+        sorted.sort();
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[1].0 <= pair[0].1) {
+            return Err(format!(
+                "key ID runs {}..={} and {}..={} overlap",
+                pair[0].0, pair[0].1, pair[1].0, pair[1].1
+            ));
+        }
+
+        Ok(Self { runs })
+    }
+}
+
+impl From<KeyIdRanges> for Vec<(u32, u32)> {
+    fn from(ranges: KeyIdRanges) -> Self {
+        ranges.runs
+    }
+}
+
+impl From<Vec<u32>> for KeyIdRanges {
+    fn from(key_ids: Vec<u32>) -> Self {
+        key_ids.into_iter().collect()
+    }
+}
+
+/// Iterator over the key IDs in a `KeyIdRanges`
+pub type KeyIdIter<'a> = FlatMap<
+    slice::Iter<'a, (u32, u32)>,
+    RangeInclusive<u32>,
+    fn(&(u32, u32)) -> RangeInclusive<u32>,
+>;
+
+impl<'a> IntoIterator for &'a KeyIdRanges {
+    type Item = u32;
+    type IntoIter = KeyIdIter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<u32> for KeyIdRanges {
+    fn from_iter<I: IntoIterator<Item = u32>>(key_ids: I) -> Self {
+        let mut runs: Vec<(u32, u32)> = Vec::new();
+        for key_id in key_ids {
+            match runs.last_mut() {
+                Some((_, last)) if last.checked_add(1) == Some(key_id) => *last = key_id,
+                _ => runs.push((key_id, key_id)),
+            }
+        }
+        Self { runs }
+    }
 }
 
 #[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq)]
 /// An aggregated group signature
 pub struct Signature {
     /// The sum of the public nonces with commitments to the signed message
@@ -137,13 +341,152 @@ impl Signature {
     #[allow(non_snake_case)]
     /// Verify the aggregated group signature
     pub fn verify(&self, public_key: &Point, msg: &[u8]) -> bool {
-        let c = challenge(public_key, &self.R, msg);
+        self.verify_with::<Bip340Challenge>(public_key, msg)
+    }
+
+    #[allow(non_snake_case)]
+    /// Verify the aggregated group signature, using the challenge hash `H`
+    pub fn verify_with<H: ChallengeHash>(&self, public_key: &Point, msg: &[u8]) -> bool {
+        let c = H::challenge(public_key, &self.R, msg);
         let R = &self.z * G + (-c) * public_key;
 
         R == self.R
     }
 }
 
+/// Verify a plain (non-taproot) wsts `sig` over `msg` for the group `public_key`, without
+/// needing an `Aggregator` or the DKG commitments
+pub fn verify(msg: &[u8], sig: &Signature, public_key: &Point) -> bool {
+    sig.verify(public_key, msg)
+}
+
+#[derive(Clone, Debug)]
+#[allow(non_snake_case)]
+/// A group signature whose shares are checked and added one at a time as they arrive, so bad
+/// shares are found right away and the signature is ready as soon as the last share comes in
+pub struct PendingSignature<H: ChallengeHash = Bip340Challenge> {
+    msg: Vec<u8>,
+    key_ids: Vec<u32>,
+    tweak: Scalar,
+    tweaked_public_key: Point,
+    R: Point,
+    nonce_sign: Scalar,
+    challenge: Scalar,
+    /// The binding nonce commitment of each party, indexed by party ID
+    Rs: HashMap<u32, Point>,
+    shares: HashMap<u32, SignatureShare>,
+    challenge_hash: PhantomData<H>,
+}
+
+impl<H: ChallengeHash> PendingSignature<H> {
+    #[allow(non_snake_case)]
+    /// Start a signature over `msg` by the parties `party_ids` which sent `nonces`, signing for
+    /// `key_ids` with the `group_key` tweaked by `tweak` (zero for no tweak)
+    pub fn new(
+        msg: &[u8],
+        party_ids: &[u32],
+        nonces: &[PublicNonce],
+        key_ids: &[u32],
+        group_key: &Point,
+        tweak: &Scalar,
+    ) -> Result<Self, AggregatorError> {
+        if nonces.len() != party_ids.len() {
+            return Err(AggregatorError::BadNonceLen(nonces.len(), party_ids.len()));
+        }
+
+        let (R_vec, R) = compute::intermediate(msg, party_ids, nonces);
+        let tweaked_public_key = group_key + tweak * G;
+        let mut nonce_sign = Scalar::one();
+        let mut challenge = H::challenge(&tweaked_public_key, &R, msg);
+        if tweak != &Scalar::zero() {
+            if !R.has_even_y() {
+                nonce_sign = -Scalar::one();
+            }
+            if !tweaked_public_key.has_even_y() {
+                challenge = -challenge;
+            }
+        }
+
+        Ok(Self {
+            msg: msg.to_vec(),
+            key_ids: key_ids.to_vec(),
+            tweak: *tweak,
+            tweaked_public_key,
+            R,
+            nonce_sign,
+            challenge,
+            Rs: party_ids.iter().copied().zip(R_vec).collect(),
+            shares: HashMap::new(),
+            challenge_hash: PhantomData,
+        })
+    }
+
+    #[allow(non_snake_case)]
+    /// Check `share` against its party's nonce and the `public_keys` of each key ID, and keep it
+    /// if it's valid; a retransmission of a share which was already added is ignored
+    pub fn add_share(
+        &mut self,
+        share: SignatureShare,
+        public_keys: &HashMap<u32, Point>,
+    ) -> Result<(), AggregatorError> {
+        let Some(R_i) = self.Rs.get(&share.id) else {
+            return Err(AggregatorError::BadPartySigs(vec![share.id]));
+        };
+        if let Some(added) = self.shares.get(&share.id) {
+            if added.z_i == share.z_i && added.key_ids == share.key_ids {
+                return Ok(());
+            }
+            return Err(AggregatorError::BadPartySigs(vec![share.id]));
+        }
+
+        match compute::verify_share_with_public_keys(
+            &share,
+            &(self.nonce_sign * R_i),
+            public_keys,
+            &self.challenge,
+            &self.key_ids,
+        ) {
+            Some(true) => {
+                self.shares.insert(share.id, share);
+                Ok(())
+            }
+            Some(false) => Err(AggregatorError::BadPartySigs(vec![share.id])),
+            None => Err(AggregatorError::BadPartyKeys(vec![share.id])),
+        }
+    }
+
+    /// Have all of the parties sent valid shares?
+    pub fn is_complete(&self) -> bool {
+        self.shares.len() == self.Rs.len()
+    }
+
+    /// Aggregate the shares into the group signature, which is BIP-340 style if there's a tweak,
+    /// or None if some parties haven't sent their shares yet
+    pub fn finalize(&self) -> Result<Option<Signature>, AggregatorError> {
+        if !self.is_complete() {
+            return Ok(None);
+        }
+
+        let z = self
+            .shares
+            .values()
+            .fold(Scalar::zero(), |z, share| z + share.z_i)
+            + self.challenge * self.tweak;
+        let sig = Signature { R: self.R, z };
+
+        let valid = if self.tweak == Scalar::zero() {
+            sig.verify_with::<H>(&self.tweaked_public_key, &self.msg)
+        } else {
+            SchnorrProof::new(&sig).verify_with::<H>(&self.tweaked_public_key.x(), &self.msg)
+        };
+        if valid {
+            Ok(Some(sig))
+        } else {
+            Err(AggregatorError::BadGroupSig)
+        }
+    }
+}
+
 /// Helper functions for tests
 pub mod test_helpers {
     /// Generate a set of `k` vectors which divide `n` IDs evenly
@@ -177,13 +520,17 @@ pub struct CheckPrivateShares<'a> {
     powers: Vec<Scalar>,
     /// Negated DKG private shares for the receiving key ID, indexed by sending key ID
     pub neg_shares: HashMap<u32, Scalar>,
+    /// With the constant-time feature, random scalars which were subtracted from the negated
+    /// shares and are multiplied separately, so the multimult's running time doesn't depend on
+    /// the shares; empty otherwise
+    masks: HashMap<u32, Scalar>,
     /// Polynomial commitments for each key ID
-    polys: &'a [PolyCommitment],
+    polys: &'a [&'a PolyCommitment],
 }
 
 impl<'a> CheckPrivateShares<'a> {
     /// Construct a new CheckPrivateShares object
-    pub fn new(id: Scalar, shares: &HashMap<u32, Scalar>, polys: &'a [PolyCommitment]) -> Self {
+    pub fn new(id: Scalar, shares: &HashMap<u32, Scalar>, polys: &'a [&'a PolyCommitment]) -> Self {
         let n: u32 = shares.len().try_into().unwrap();
         let t: u32 = polys[0].poly.len().try_into().unwrap();
         let x = id;
@@ -196,7 +543,16 @@ impl<'a> CheckPrivateShares<'a> {
         }
 
         let mut neg_shares = HashMap::with_capacity(polys.len());
+        #[allow(unused_mut)]
+        let mut masks = HashMap::new();
         for (i, s) in shares.iter() {
+            #[cfg(feature = "constant-time")]
+            {
+                let r = Scalar::random(&mut rand_core::OsRng);
+                neg_shares.insert(*i, -s - r);
+                masks.insert(*i, r);
+            }
+            #[cfg(not(feature = "constant-time"))]
             neg_shares.insert(*i, -s);
         }
 
@@ -205,24 +561,28 @@ impl<'a> CheckPrivateShares<'a> {
             t,
             powers,
             neg_shares,
+            masks,
             polys,
         }
     }
 }
 
 impl<'a> MultiMult for CheckPrivateShares<'a> {
-    /// The first n*t scalars will be powers, the last n will be the negation of shares
+    /// The first n*t scalars will be powers, the next n will be the negation of shares, and the
+    /// last n will be the masks if there are any
     fn get_scalar(&self, i: usize) -> &Scalar {
         let h: u32 = i.try_into().unwrap();
         let u: usize = self.t.try_into().unwrap();
         if h < self.n * self.t {
             &self.powers[i % u]
-        } else {
+        } else if h < self.n * (self.t + 1) {
             &self.neg_shares[&(h - (self.t * self.n))]
+        } else {
+            &self.masks[&(h - ((self.t + 1) * self.n))]
         }
     }
 
-    /// The first n*t points will be poly coeffs, the last n will be G
+    /// The first n*t points will be poly coeffs, the rest will be G
     fn get_point(&self, i: usize) -> &Point {
         let h: u32 = i.try_into().unwrap();
         let u: usize = self.t.try_into().unwrap();
@@ -237,6 +597,7 @@ impl<'a> MultiMult for CheckPrivateShares<'a> {
     }
 
     fn get_size(&self) -> usize {
-        ((self.t + 1) * self.n).try_into().unwrap()
+        let size: usize = ((self.t + 1) * self.n).try_into().unwrap();
+        size + self.masks.len()
     }
 }
@@ -1,20 +1,30 @@
 use core::iter::zip;
+use hashbrown::HashMap;
 use num_traits::{One, Zero};
 use p256k1::{
     point::Compressed, point::Error as PointError, point::Point, point::G, scalar::Scalar,
 };
 use sha2::{Digest, Sha256};
+use std::sync::Mutex;
 
-use crate::common::PublicNonce;
+use crate::common::{PublicNonce, Signature, SignatureShare, TaprootTweak};
+use crate::errors::DkgError;
+use crate::traits::{ChallengeHash, Signer};
 use crate::util::hash_to_scalar;
+use crate::vss::VSS;
+
+/// The prefix hashed into every binding value
+pub const BINDING_PREFIX: &str = "WSTS/binding";
+
+/// The BIP-340 tag of the schnorr challenge hash
+pub const CHALLENGE_TAG: &str = "BIP0340/challenge";
 
 #[allow(non_snake_case)]
 /// Compute a binding value from the party ID, public nonces, and signed message
 pub fn binding(id: &Scalar, B: &[PublicNonce], msg: &[u8]) -> Scalar {
     let mut hasher = Sha256::new();
-    let prefix = "WSTS/binding";
 
-    hasher.update(prefix.as_bytes());
+    hasher.update(BINDING_PREFIX.as_bytes());
     hasher.update(id.to_bytes());
     for b in B {
         hasher.update(b.D.compress().as_bytes());
@@ -29,9 +39,8 @@ pub fn binding(id: &Scalar, B: &[PublicNonce], msg: &[u8]) -> Scalar {
 /// Compute a binding value from the party ID, public nonces, and signed message
 pub fn binding_compressed(id: &Scalar, B: &[(Compressed, Compressed)], msg: &[u8]) -> Scalar {
     let mut hasher = Sha256::new();
-    let prefix = "WSTS/binding";
 
-    hasher.update(prefix.as_bytes());
+    hasher.update(BINDING_PREFIX.as_bytes());
     hasher.update(id.to_bytes());
     for (D, E) in B {
         hasher.update(D.as_bytes());
@@ -45,8 +54,7 @@ pub fn binding_compressed(id: &Scalar, B: &[(Compressed, Compressed)], msg: &[u8
 #[allow(non_snake_case)]
 /// Compute the schnorr challenge from the public key, aggregated commitments, and the signed message
 pub fn challenge(publicKey: &Point, R: &Point, msg: &[u8]) -> Scalar {
-    let tag = "BIP0340/challenge";
-    let mut hasher = tagged_hash(tag);
+    let mut hasher = tagged_hash(CHALLENGE_TAG);
 
     hasher.update(R.x().to_bytes());
     hasher.update(publicKey.x().to_bytes());
@@ -55,6 +63,17 @@ pub fn challenge(publicKey: &Point, R: &Point, msg: &[u8]) -> Scalar {
     hash_to_scalar(&mut hasher)
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// The BIP-340 challenge hash, which signers and aggregators use unless told otherwise
+pub struct Bip340Challenge;
+
+impl ChallengeHash for Bip340Challenge {
+    #[allow(non_snake_case)]
+    fn challenge(public_key: &Point, R: &Point, msg: &[u8]) -> Scalar {
+        challenge(public_key, R, msg)
+    }
+}
+
 /// Compute the Lagrange interpolation value
 pub fn lambda(i: u32, key_ids: &[u32]) -> Scalar {
     let mut lambda = Scalar::one();
@@ -68,14 +87,76 @@ pub fn lambda(i: u32, key_ids: &[u32]) -> Scalar {
     lambda
 }
 
+/// The most key ID sets a LagrangeCache will hold before it's cleared
+pub const LAGRANGE_CACHE_SIZE: usize = 64;
+
+#[derive(Debug, Default)]
+/// Cache of Lagrange interpolation values, keyed by the set of key IDs being interpolated over,
+/// so a stable set of signers doesn't recompute them every round
+pub struct LagrangeCache {
+    lambdas: Mutex<HashMap<Vec<u32>, HashMap<u32, Scalar>>>,
+}
+
+impl LagrangeCache {
+    /// Compute the Lagrange interpolation value, or return the cached one if `key_ids` was seen before
+    pub fn lambda(&self, i: u32, key_ids: &[u32]) -> Scalar {
+        let Ok(mut lambdas) = self.lambdas.lock() else {
+            return lambda(i, key_ids);
+        };
+
+        let mut sorted_key_ids = key_ids.to_vec();
+        sorted_key_ids.sort();
+        if !lambdas.contains_key(&sorted_key_ids) && lambdas.len() >= LAGRANGE_CACHE_SIZE {
+            lambdas.clear();
+        }
+
+        *lambdas
+            .entry(sorted_key_ids)
+            .or_default()
+            .entry(i)
+            .or_insert_with(|| lambda(i, key_ids))
+    }
+}
+
+impl Clone for LagrangeCache {
+    fn clone(&self) -> Self {
+        let lambdas = match self.lambdas.lock() {
+            Ok(lambdas) => lambdas.clone(),
+            Err(_) => HashMap::new(),
+        };
+        Self {
+            lambdas: Mutex::new(lambdas),
+        }
+    }
+}
+
+// the cache only holds values derived from its arguments, so it never makes two owners differ
+impl PartialEq for LagrangeCache {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for LagrangeCache {}
+
+/// Compute the binding value of each of the `party_ids`, i.e. the weight of the `E` part of its
+/// nonce in the aggregate nonce
+pub fn bindings(msg: &[u8], party_ids: &[u32], nonces: &[PublicNonce]) -> Vec<Scalar> {
+    let compressed_nonces: Vec<(Compressed, Compressed)> = nonces
+        .iter()
+        .map(|nonce| (nonce.D.compress(), nonce.E.compress()))
+        .collect();
+    party_ids
+        .iter()
+        .map(|&i| binding_compressed(&id(i), &compressed_nonces, msg))
+        .collect()
+}
+
 // Is this the best way to return these values?
 #[allow(non_snake_case)]
 /// Compute the intermediate values used in both the parties and the aggregator
 pub fn intermediate(msg: &[u8], party_ids: &[u32], nonces: &[PublicNonce]) -> (Vec<Point>, Point) {
-    let rhos: Vec<Scalar> = party_ids
-        .iter()
-        .map(|&i| binding(&id(i), nonces, msg))
-        .collect();
+    let rhos = bindings(msg, party_ids, nonces);
     let R_vec: Vec<Point> = zip(nonces, rhos)
         .map(|(nonce, rho)| nonce.D + rho * nonce.E)
         .collect();
@@ -91,18 +172,9 @@ pub fn aggregate_nonce(
     party_ids: &[u32],
     nonces: &[PublicNonce],
 ) -> Result<Point, PointError> {
-    let compressed_nonces: Vec<(Compressed, Compressed)> = nonces
-        .iter()
-        .map(|nonce| (nonce.D.compress(), nonce.E.compress()))
-        .collect();
-    let scalars: Vec<Scalar> = party_ids
-        .iter()
-        .flat_map(|&i| {
-            [
-                Scalar::from(1),
-                binding_compressed(&id(i), &compressed_nonces, msg),
-            ]
-        })
+    let scalars: Vec<Scalar> = bindings(msg, party_ids, nonces)
+        .into_iter()
+        .flat_map(|rho| [Scalar::from(1), rho])
         .collect();
     let points: Vec<Point> = nonces.iter().flat_map(|nonce| [nonce.D, nonce.E]).collect();
 
@@ -115,7 +187,7 @@ pub fn id(i: u32) -> Scalar {
 }
 
 /// Evaluate the public polynomial `f` at scalar `x` using multi-exponentiation
-pub fn poly(x: &Scalar, f: &Vec<Point>) -> Result<Point, PointError> {
+pub fn poly(x: &Scalar, f: &[Point]) -> Result<Point, PointError> {
     let mut s = Vec::with_capacity(f.len());
     let mut pow = Scalar::one();
     for _ in 0..f.len() {
@@ -123,7 +195,140 @@ pub fn poly(x: &Scalar, f: &Vec<Point>) -> Result<Point, PointError> {
         pow *= x;
     }
 
-    Point::multimult(s, f.clone())
+    Point::multimult(s, f.to_vec())
+}
+
+#[allow(non_snake_case)]
+/// Check a single signature share for `msg` without waiting for the rest, where `nonce` is the
+/// share's binding nonce commitment and `aggregate_nonce` the group nonce (both from
+/// `intermediate`), `commitments` the group polynomial whose constant term is the group key, and
+/// `key_ids` the IDs of every key taking part in the signature
+pub fn verify_share(
+    share: &SignatureShare,
+    nonce: &Point,
+    aggregate_nonce: &Point,
+    commitments: &[Point],
+    msg: &[u8],
+    key_ids: &[u32],
+) -> Result<bool, PointError> {
+    let Some(group_key) = commitments.first() else {
+        return Ok(false);
+    };
+    let c = challenge(group_key, aggregate_nonce, msg);
+
+    verify_share_with_challenge(share, nonce, commitments, &c, key_ids)
+}
+
+/// Check a single signature share against an explicit `challenge`, i.e. that `z_i * G` is `nonce`
+/// plus the challenge times the Lagrange weighted public keys of the share's keys
+pub fn verify_share_with_challenge(
+    share: &SignatureShare,
+    nonce: &Point,
+    commitments: &[Point],
+    challenge: &Scalar,
+    key_ids: &[u32],
+) -> Result<bool, PointError> {
+    let mut cx = Point::zero();
+    for key_id in &share.key_ids {
+        let public_key = poly(&id(key_id), commitments)?;
+        cx += lambda(key_id, key_ids) * challenge * public_key;
+    }
+
+    Ok(share.z_i * G == *nonce + cx)
+}
+
+/// Check a single signature share against an explicit `challenge`, using the already computed
+/// `public_keys` of each key ID rather than the group polynomial; returns None if any of the
+/// share's keys has no public key
+pub fn verify_share_with_public_keys(
+    share: &SignatureShare,
+    nonce: &Point,
+    public_keys: &HashMap<u32, Point>,
+    challenge: &Scalar,
+    key_ids: &[u32],
+) -> Option<bool> {
+    let mut cx = Point::zero();
+    for key_id in &share.key_ids {
+        cx += lambda(key_id, key_ids) * challenge * public_keys.get(&key_id)?;
+    }
+
+    Some(share.z_i * G == *nonce + cx)
+}
+
+/// Evaluate the group polynomial at the ID of each of the `num_keys` keys, to get their public keys
+pub fn public_keys(num_keys: u32, poly: &[Point]) -> Result<HashMap<u32, Point>, PointError> {
+    (0..num_keys)
+        .map(|key_id| Ok((key_id, self::poly(&id(key_id), poly)?)))
+        .collect()
+}
+
+/// Interpolate the group public key from the public keys of `key_ids`
+pub fn interpolate_public_key(
+    public_keys: &HashMap<u32, Point>,
+    key_ids: &[u32],
+) -> Result<Point, PointError> {
+    let scalars = key_ids.iter().map(|k| lambda(*k, key_ids)).collect();
+    let points = key_ids
+        .iter()
+        .map(|k| public_keys.get(k).copied().unwrap_or_default())
+        .collect();
+
+    Point::multimult(scalars, points)
+}
+
+/// Check many signatures by `group_key` at once, with a single multi-exponentiation over a
+/// random linear combination of them; this only says whether all of them are valid, so check
+/// them one at a time to find the bad ones
+pub fn verify_batch(sigs: &[(&[u8], Signature)], group_key: &Point) -> bool {
+    // derive the weights from everything being checked, so bad signatures can't be chosen to
+    // cancel each other out
+    let mut hasher = Sha256::new();
+    hasher.update("WSTS/verify_batch".as_bytes());
+    hasher.update(group_key.compress().as_bytes());
+    for (msg, sig) in sigs {
+        hasher.update(sig.R.compress().as_bytes());
+        hasher.update(sig.z.to_bytes());
+        hasher.update((msg.len() as u64).to_be_bytes());
+        hasher.update(msg);
+    }
+    let seed = hasher.finalize();
+
+    let mut z = Scalar::zero();
+    let mut cx = Scalar::zero();
+    let mut scalars = Vec::with_capacity(sigs.len() + 2);
+    let mut points = Vec::with_capacity(sigs.len() + 2);
+    for (i, (msg, sig)) in sigs.iter().enumerate() {
+        let mut hasher = Sha256::new();
+        hasher.update(seed);
+        hasher.update((i as u64).to_be_bytes());
+        let a = hash_to_scalar(&mut hasher);
+
+        z += a * sig.z;
+        cx += a * challenge(group_key, &sig.R, msg);
+        scalars.push(-a);
+        points.push(sig.R);
+    }
+    scalars.push(z);
+    points.push(G);
+    scalars.push(-cx);
+    points.push(*group_key);
+
+    matches!(Point::multimult(scalars, points), Ok(sum) if sum == Point::zero())
+}
+
+#[allow(non_snake_case)]
+/// Check the sum `z` of a set of signature shares with a single multi-exponentiation, i.e. that
+/// `z * G` is `nonce_sign * R` plus `challenge` times the group key, where `R` is the aggregate nonce
+pub fn check_aggregate(
+    z: &Scalar,
+    R: &Point,
+    nonce_sign: &Scalar,
+    group_key: &Point,
+    challenge: &Scalar,
+) -> Result<bool, PointError> {
+    let sum = Point::multimult(vec![*z, -nonce_sign, -challenge], vec![G, *R, *group_key])?;
+
+    Ok(sum == Point::zero())
 }
 
 /// Create a BIP340 compliant tagged hash by double hashing the tag
@@ -152,9 +357,27 @@ pub fn tweak(public_key: &Point, merkle_root: Option<[u8; 32]>) -> Scalar {
     hash_to_scalar(&mut hasher)
 }
 
-/// Create a BIP341 compliant taproot tweak from a public key and merkle root
-pub fn tweaked_public_key(public_key: &Point, merkle_root: Option<[u8; 32]>) -> Point {
-    public_key + tweak(public_key, merkle_root) * G
+/// Create the tweak to sign with so that signatures verify under the BIP341 output key of
+/// `public_key`.  BIP341 tweaks the even-y internal key, so the tweak is negated when
+/// `public_key` has odd y: `P - tG` is the negation of `-P + tG`, and x-only signatures can't
+/// tell the two apart
+pub fn taproot_tweak(public_key: &Point, tweak: TaprootTweak) -> Scalar {
+    let tweak = tweak.scalar(public_key);
+    if public_key.has_even_y() {
+        tweak
+    } else {
+        -tweak
+    }
+}
+
+/// Create a BIP341 compliant taproot output key from an internal public key and `tweak`
+pub fn tweaked_public_key(public_key: &Point, tweak: TaprootTweak) -> Point {
+    let internal_key = if public_key.has_even_y() {
+        *public_key
+    } else {
+        -*public_key
+    };
+    internal_key + tweak.scalar(public_key) * G
 }
 
 /// Create a taproot style merkle root from the serialized script data
@@ -165,3 +388,110 @@ pub fn merkle_root(data: &[u8]) -> [u8; 32] {
 
     hasher.finalize().into()
 }
+
+/// Reconstruct the group private key from the private key shares held by `signers`, which must
+/// hold at least `threshold` distinct keys between them.  This defeats the purpose of threshold
+/// signing, so it should only be used for testing or escrowed recovery
+pub fn group_secret<S: Signer>(signers: &[S], threshold: u32) -> Result<Scalar, DkgError> {
+    let mut shares: Vec<(u32, Scalar)> = signers
+        .iter()
+        .flat_map(|signer| signer.get_private_keys())
+        .collect();
+    shares.sort_by_key(|(key_id, _)| *key_id);
+    shares.dedup_by_key(|(key_id, _)| *key_id);
+
+    if shares.len() < threshold as usize {
+        return Err(DkgError::NotEnoughShares(
+            shares.iter().map(|(key_id, _)| *key_id).collect(),
+        ));
+    }
+
+    Ok(VSS::reconstruct(&shares))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand_core::OsRng;
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn verify_batch() {
+        let mut rng = OsRng;
+        let private_key = Scalar::random(&mut rng);
+        let group_key = private_key * G;
+        let msgs: Vec<Vec<u8>> = (0..10u8).map(|i| vec![i; 32]).collect();
+
+        let sign = |msg: &[u8]| {
+            let k = Scalar::random(&mut OsRng);
+            let R = k * G;
+            let z = k + challenge(&group_key, &R, msg) * private_key;
+            Signature { R, z }
+        };
+        let mut sigs: Vec<(&[u8], Signature)> =
+            msgs.iter().map(|msg| (&msg[..], sign(msg))).collect();
+
+        assert!(super::verify_batch(&sigs, &group_key));
+        assert!(super::verify_batch(&[], &group_key));
+        assert!(!super::verify_batch(&sigs, &(group_key + G)));
+
+        sigs[3].1.z += Scalar::from(1);
+        assert!(!super::verify_batch(&sigs, &group_key));
+
+        // two bad signatures which cancel each other out in a plain sum are still caught
+        sigs[4].1.z = sigs[4].1.z - Scalar::from(1);
+        assert!(!super::verify_batch(&sigs, &group_key));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn intermediate_values() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let party_ids = [0, 3, 7];
+        let nonces: Vec<PublicNonce> = party_ids
+            .iter()
+            .map(|_| PublicNonce {
+                D: Scalar::random(&mut rng) * G,
+                E: Scalar::random(&mut rng) * G,
+            })
+            .collect();
+
+        let rhos = bindings(msg, &party_ids, &nonces);
+        for (i, rho) in party_ids.iter().zip(&rhos) {
+            assert_eq!(*rho, binding(&id(*i), &nonces, msg));
+        }
+
+        let R =
+            zip(&nonces, &rhos).fold(Point::zero(), |R, (nonce, rho)| R + nonce.D + rho * nonce.E);
+        let (_, R_intermediate) = intermediate(msg, &party_ids, &nonces);
+        assert_eq!(R, R_intermediate);
+        assert_eq!(R, aggregate_nonce(msg, &party_ids, &nonces).unwrap());
+
+        let public_key = Scalar::random(&mut rng) * G;
+        let mut hasher = tagged_hash(CHALLENGE_TAG);
+        hasher.update(R.x().to_bytes());
+        hasher.update(public_key.x().to_bytes());
+        hasher.update(msg);
+        assert_eq!(challenge(&public_key, &R, msg), hash_to_scalar(&mut hasher));
+    }
+
+    #[test]
+    fn lagrange_cache() {
+        let cache = LagrangeCache::default();
+        let key_ids = [0, 2, 3, 5];
+        let shuffled = [5, 3, 0, 2];
+
+        for i in key_ids {
+            assert_eq!(cache.lambda(i, &key_ids), lambda(i, &key_ids));
+            assert_eq!(cache.lambda(i, &shuffled), lambda(i, &key_ids));
+        }
+        assert_eq!(cache.lambdas.lock().unwrap().len(), 1);
+
+        for n in 0..LAGRANGE_CACHE_SIZE as u32 + 1 {
+            let key_ids = [0, n + 1];
+            assert_eq!(cache.lambda(0, &key_ids), lambda(0, &key_ids));
+        }
+        assert!(cache.lambdas.lock().unwrap().len() <= LAGRANGE_CACHE_SIZE);
+    }
+}
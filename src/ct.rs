@@ -0,0 +1,106 @@
+use p256k1::{point::Point, point::G, scalar::Scalar};
+#[cfg(feature = "constant-time")]
+use rand_core::OsRng;
+
+/// Multiply the generator by `secret`, e.g. to commit to a private polynomial coefficient or nonce
+///
+/// p256k1 only has variable time scalar multiplication, whose running time depends on the
+/// scalar.  With the `constant-time` feature the secret is split into two random looking halves,
+/// `secret - r` and `r` for a fresh random `r`, which are multiplied separately, so the time taken
+/// doesn't depend on the secret.  This doubles the cost of the multiplication.
+pub fn mul_base(secret: &Scalar) -> Point {
+    #[cfg(feature = "constant-time")]
+    {
+        let r = Scalar::random(&mut OsRng);
+        (*secret - r) * G + r * G
+    }
+    #[cfg(not(feature = "constant-time"))]
+    {
+        secret * G
+    }
+}
+
+/// Multiply `point` by `secret`, e.g. to compute a Diffie-Hellman shared secret, splitting the
+/// secret as `mul_base` does with the `constant-time` feature
+pub fn mul(secret: &Scalar, point: &Point) -> Point {
+    #[cfg(feature = "constant-time")]
+    {
+        let r = Scalar::random(&mut OsRng);
+        (*secret - r) * point + r * point
+    }
+    #[cfg(not(feature = "constant-time"))]
+    {
+        secret * point
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use p256k1::{point::Point, point::G, scalar::Scalar};
+    use rand_core::OsRng;
+    #[cfg(feature = "constant-time")]
+    use {rand_core::RngCore, std::time::Instant};
+
+    #[test]
+    fn mul() {
+        let mut rng = OsRng;
+        let secret = Scalar::random(&mut rng);
+        let point = Scalar::random(&mut rng) * G;
+
+        assert_eq!(super::mul_base(&secret), secret * G);
+        assert_eq!(super::mul(&secret, &point), secret * point);
+        assert_eq!(super::mul_base(&Scalar::from(0)), Point::default());
+    }
+
+    #[cfg(feature = "constant-time")]
+    /// Welch's t statistic for the difference between the mean running times of `f` on a fixed
+    /// secret and on random secrets, in the style of dudect: the two classes are interleaved at
+    /// random, and the slowest tenth of the measurements are dropped as noise from preemption
+    fn timing_t_statistic(f: impl Fn(&Scalar) -> Point, fixed: &Scalar, samples: usize) -> f64 {
+        let mut rng = OsRng;
+        let mut times = [Vec::new(), Vec::new()];
+        for _ in 0..samples {
+            let class = (rng.next_u32() & 1) as usize;
+            let secret = if class == 0 {
+                *fixed
+            } else {
+                Scalar::random(&mut rng)
+            };
+            let start = Instant::now();
+            std::hint::black_box(f(std::hint::black_box(&secret)));
+            times[class].push(start.elapsed().as_nanos() as f64);
+        }
+
+        let mut all = times.concat();
+        all.sort_by(f64::total_cmp);
+        let cutoff = all[all.len() * 9 / 10];
+        let [a, b] = times.map(|class| {
+            let class = class
+                .into_iter()
+                .filter(|t| *t <= cutoff)
+                .collect::<Vec<f64>>();
+            let n = class.len() as f64;
+            let mean = class.iter().sum::<f64>() / n;
+            let var = class.iter().map(|t| (t - mean) * (t - mean)).sum::<f64>() / (n - 1.0);
+            (mean, var, n)
+        });
+        (a.0 - b.0) / (a.1 / a.2 + b.1 / b.2).sqrt()
+    }
+
+    #[cfg(feature = "constant-time")]
+    #[test]
+    // wall clock timings depend on how loaded the machine is, so this only runs on request, with
+    // `cargo test --release --features constant-time -- --ignored ct::test::timing`
+    #[ignore]
+    fn timing() {
+        // a secret of one makes p256k1's variable time multiplication much faster than a random
+        // secret, so the unprotected multiplication must leak for the test to mean anything
+        let fixed = Scalar::from(1);
+        let point = Scalar::random(&mut OsRng) * G;
+        assert!(timing_t_statistic(|secret| secret * G, &fixed, 2000).abs() > 10.0);
+        assert!(timing_t_statistic(|secret| secret * point, &fixed, 2000).abs() > 10.0);
+
+        assert!(timing_t_statistic(super::mul_base, &fixed, 2000).abs() < 10.0);
+        assert!(timing_t_statistic(|secret| super::mul(secret, &point), &fixed, 2000).abs() < 10.0);
+    }
+}
@@ -1,6 +1,15 @@
+use core::fmt;
 use p256k1::{point::Error as PointError, scalar::Scalar};
 use thiserror::Error;
 
+use crate::{
+    net::{Message, MessageType},
+    state_machine::{
+        coordinator::Error as CoordinatorError, signer::Error as SignerError, ConfigError,
+        RoundType,
+    },
+};
+
 #[derive(Error, Debug, Clone)]
 /// Errors which can happen during distributed key generation
 pub enum DkgError {
@@ -45,7 +54,291 @@ pub enum AggregatorError {
     #[error("bad party sigs from {0:?}")]
     /// The party signatures which failed to verify
     BadPartySigs(Vec<u32>),
+    #[error("public keys don't interpolate to the group key")]
+    /// The public keys passed to the aggregator don't match the group public key
+    BadGroupKey,
+    #[error("no signature in progress")]
+    /// A share was added before a signature was started
+    NoPendingSignature,
     #[error("bad group sig")]
     /// The aggregate group signature failed to verify
     BadGroupSig,
+    #[error("point error {0:?}")]
+    /// An error during point operations
+    Point(PointError),
+}
+
+impl From<PointError> for AggregatorError {
+    fn from(e: PointError) -> Self {
+        AggregatorError::Point(e)
+    }
+}
+
+#[derive(Error, Debug, Clone)]
+/// Errors which can happen while splitting packets into fragments or reassembling them
+pub enum FragmentError {
+    #[error("serialization error {0}")]
+    /// The packet could not be serialized or deserialized
+    Serialization(String),
+    #[error("bad fragment size {0}")]
+    /// The maximum fragment size was zero
+    BadFragmentSize(usize),
+    #[error("bad fragment index {0} of {1}")]
+    /// The fragment index was out of range, or the count didn't match the other fragments
+    BadFragmentIndex(u32, u32),
+    #[error("bad fragment hash for fragment {0}")]
+    /// The fragment data didn't match its hash
+    BadFragmentHash(u32),
+    #[error("bad message hash")]
+    /// The reassembled fragments didn't match the message hash
+    BadMessageHash,
+    #[error("too many fragments {0}, at most {1}")]
+    /// The fragment count was more than a packet of the largest allowed size needs
+    TooManyFragments(u32, u32),
+    #[error("packet too large, {0} bytes")]
+    /// The fragments of one packet held more bytes than a packet may have
+    TooLarge(usize),
+}
+
+#[derive(Error, Debug, Clone)]
+/// Errors which can happen while encoding or decoding packets
+pub enum EncodingError {
+    #[error("serialization error {0}")]
+    /// The packet could not be serialized or deserialized
+    Serialization(String),
+    #[error("compression error {0}")]
+    /// The payload could not be compressed or decompressed
+    Compression(String),
+    #[error("missing header")]
+    /// The encoded packet was empty
+    MissingHeader,
+    #[error("unsupported header flags {0:#04x}")]
+    /// The header had flags which this build doesn't understand, e.g. compression without the zstd feature
+    UnsupportedFlags(u8),
+    #[error("frame of {0} bytes is too large")]
+    /// A frame, or a decompressed payload, was longer than `net::MAX_FRAME_LEN`
+    FrameTooLarge(usize),
+    #[error("I/O error {0}")]
+    /// A frame could not be read or written
+    Io(String),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from checking inbound packets
+pub enum PacketError {
+    #[error("bad packet signature from {0}")]
+    /// The packet signature didn't verify against the sender's public key
+    BadSignature(u32),
+    #[error("replayed packet from {0}: sequence number {1}, highest accepted {2}")]
+    /// The packet's sequence number was already accepted from the sender, or is too far behind the
+    /// highest one accepted to tell
+    Replayed(u32, u64, u64),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from verifying an audit log
+pub enum AuditError {
+    #[error("audit log doesn't start at entry 0")]
+    /// The first entry wasn't the start of the log
+    MissingStart,
+    #[error("audit entry {0} is out of order, expected entry {1}")]
+    /// An entry's index didn't follow the previous entry's
+    BadIndex(u64, u64),
+    #[error("audit entry {0} doesn't link to the previous entry")]
+    /// An entry's previous hash didn't match the previous entry's hash
+    BrokenChain(u64),
+    #[error("audit entry {0} doesn't match its hash")]
+    /// An entry's contents didn't hash to its recorded hash
+    BadHash(u64),
+    #[error("audit entry {0} has round IDs which don't match its packet")]
+    /// An entry's round IDs weren't the ones in its packet
+    BadRoundIds(u64),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from verifying a DKG transcript
+pub enum TranscriptError {
+    #[error("public shares indexed under signer {0} are from signer {1}")]
+    /// Public shares were recorded under a different signer than the one which sent them
+    SignerMismatch(u32, u32),
+    #[error("public shares for DKG round {0}, expected round {1}")]
+    /// Public shares were from a different DKG round than the transcript
+    BadDkgId(u64, u64),
+    #[error("signer {0} sent invalid polynomial commitments for parties {1:?}")]
+    /// A signer's polynomial commitments were malformed or failed their proofs of ownership
+    InvalidPolyCommitments(u32, Vec<u32>),
+    #[error("party {0} has more than one polynomial commitment")]
+    /// More than one polynomial was committed to for the same party
+    DuplicateParty(u32),
+    #[error("complaint from signer {0}, which didn't send public shares")]
+    /// A complaint was recorded for a signer which wasn't in the round
+    UnknownComplainant(u32),
+    #[error("group key isn't the sum of the polynomial commitments")]
+    /// The group key didn't come from the recorded polynomial commitments
+    KeyMismatch,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from computing a taproot sighash
+pub enum SighashError {
+    #[error("invalid taproot sighash type {0:#04x}")]
+    /// The sighash type isn't one of the types BIP-341 allows
+    BadSighashType(u8),
+    #[error("input index {0} out of range for {1} inputs")]
+    /// The input being signed isn't in the transaction
+    BadInputIndex(usize, usize),
+    #[error("expected {1} spent outputs, got {0}")]
+    /// There wasn't one spent output per input
+    BadSpentOutputs(usize, usize),
+    #[error("SIGHASH_SINGLE for input {0} has no output at the same index")]
+    /// SIGHASH_SINGLE was used for an input without a matching output
+    NoSingleOutput(usize),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from reading or writing a state store
+pub enum StoreError {
+    #[error("I/O error {0}")]
+    /// The store could not be read or written
+    Io(String),
+    #[error("corrupt store {0}")]
+    /// The store's contents could not be parsed
+    Corrupt(String),
+    #[error("backend error {0}")]
+    /// An error from the database or service behind a store, for stores outside this crate
+    Backend(String),
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from locking memory into RAM
+pub enum MemLockError {
+    #[error("locking memory needs CAP_IPC_LOCK or an RLIMIT_MEMLOCK above {0} bytes")]
+    /// The process isn't allowed to lock that much memory, with the soft RLIMIT_MEMLOCK it has
+    Limit(u64),
+    #[error("I/O error {0}")]
+    /// Any other failure to lock or unlock memory
+    Io(String),
+    #[error("locking memory isn't supported on this platform")]
+    /// The platform has no way to lock memory
+    Unsupported,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// The round and parties an error happened in, so applications can attribute failures without
+/// parsing error strings
+pub struct ErrorContext {
+    /// The DKG round being processed
+    pub dkg_id: Option<u64>,
+    /// The signing round being processed
+    pub sign_id: Option<u64>,
+    /// The iteration of the signing round being processed
+    pub sign_iter_id: Option<u64>,
+    /// The signer which sent the message being processed, or None if it came from a coordinator
+    pub sender_id: Option<u32>,
+    /// The local signer which hit the error, or None if it was a coordinator
+    pub signer_id: Option<u32>,
+}
+
+impl ErrorContext {
+    /// The context of processing `message`, taken from its round IDs and sender
+    pub fn from_message(message: &Message) -> Self {
+        let id = message.id();
+        if id.message_type == MessageType::Unknown {
+            return Self::default();
+        }
+        let is_sign = RoundType::from(id.message_type) == RoundType::Sign;
+        Self {
+            dkg_id: Some(id.dkg_id),
+            sign_id: is_sign.then_some(id.sign_id),
+            sign_iter_id: is_sign.then_some(id.sign_iter_id),
+            sender_id: id.signer_id,
+            signer_id: None,
+        }
+    }
+
+    /// This context, for an error which happened in the local signer `signer_id`
+    pub fn with_signer_id(self, signer_id: u32) -> Self {
+        Self {
+            signer_id: Some(signer_id),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields = [
+            ("dkg_id", self.dkg_id),
+            ("sign_id", self.sign_id),
+            ("sign_iter_id", self.sign_iter_id),
+            ("sender_id", self.sender_id.map(u64::from)),
+            ("signer_id", self.signer_id.map(u64::from)),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| format!("{name} {value}")))
+        .collect::<Vec<String>>();
+        if fields.is_empty() {
+            write!(f, "no context")
+        } else {
+            write!(f, "{}", fields.join(", "))
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+/// The errors from each part of this crate
+pub enum ErrorKind {
+    #[error("signer error: {0}")]
+    /// An error from a signer's state machine
+    Signer(#[from] SignerError),
+    #[error("coordinator error: {0}")]
+    /// An error from a coordinator's state machine
+    Coordinator(#[from] CoordinatorError),
+    #[error("config error: {0}")]
+    /// An invalid signer or coordinator config
+    Config(#[from] ConfigError),
+    #[error("DKG error: {0}")]
+    /// An error during distributed key generation
+    Dkg(#[from] DkgError),
+    #[error("aggregator error: {0}")]
+    /// An error during signature aggregation
+    Aggregator(#[from] AggregatorError),
+    #[error("encoding error: {0}")]
+    /// An error while encoding or decoding packets
+    Encoding(#[from] EncodingError),
+    #[error("fragment error: {0}")]
+    /// An error while fragmenting or reassembling packets
+    Fragment(#[from] FragmentError),
+    #[error("packet error: {0}")]
+    /// An inbound packet which failed its checks
+    Packet(#[from] PacketError),
+    #[error("store error: {0}")]
+    /// An error from a state store
+    Store(#[from] StoreError),
+}
+
+#[derive(Error, Debug)]
+#[error("{kind} ({context})")]
+/// An error from any part of this crate, with the round and parties it happened in
+pub struct Error {
+    /// What went wrong
+    pub kind: ErrorKind,
+    /// Where it went wrong
+    pub context: ErrorContext,
+}
+
+impl Error {
+    /// Wrap `kind` with `context`
+    pub fn new(kind: impl Into<ErrorKind>, context: ErrorContext) -> Self {
+        Self {
+            kind: kind.into(),
+            context,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self::new(kind, ErrorContext::default())
+    }
 }
@@ -0,0 +1,237 @@
+use p256k1::{
+    field,
+    point::{Point, G},
+    scalar::Scalar,
+};
+use sha3::{Digest, Keccak256};
+
+use crate::{common::Signature, traits::ChallengeHash};
+
+/// Half the order of the secp256k1 group, rounded down.  Solidity verifiers pass the group key's
+/// x coordinate to `ecrecover` as a signature's `r`, so it must be no larger than this
+const HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0x5d, 0x57, 0x6e, 0x73, 0x57, 0xa4, 0x50, 0x1d, 0xdf, 0xe9, 0x2f, 0x46, 0x68, 0x1b, 0x20, 0xa0,
+];
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from making signatures for EVM contracts
+pub enum Error {
+    #[error("group key x coordinate is more than half the group order")]
+    /// Solidity verifiers can't check signatures for this group key
+    KeyNotSupported,
+}
+
+/// Compute the Keccak-256 hash of `data`, as used by the EVM.  This is the original Keccak
+/// padding, not the SHA3-256 padding which was standardized later
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    Keccak256::digest(data).into()
+}
+
+/// The EVM address of `point`, which is the last 20 bytes of the Keccak-256 hash of its
+/// uncompressed coordinates
+pub fn address(point: &Point) -> [u8; 20] {
+    let mut coordinates = point.x().to_bytes().to_vec();
+    coordinates.extend(point.y().to_bytes());
+    let hash = keccak256(&coordinates);
+
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// The `v` which `ecrecover` needs to lift `public_key` from its x coordinate
+fn parity(public_key: &Point) -> u8 {
+    if public_key.has_even_y() {
+        27
+    } else {
+        28
+    }
+}
+
+/// Can Solidity verifiers check signatures for `public_key`?
+pub fn is_supported_key(public_key: &Point) -> bool {
+    public_key.x().to_bytes() <= HALF_ORDER
+}
+
+/// The challenge hash which Solidity verifiers recompute, over the address of the nonce
+#[allow(non_snake_case)]
+fn challenge_bytes(public_key: &Point, R: &Point, msg: &[u8]) -> [u8; 32] {
+    let mut data = address(R).to_vec();
+    data.push(parity(public_key));
+    data.extend(public_key.x().to_bytes());
+    data.extend(msg);
+    keccak256(&data)
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+/// The challenge hash of Solidity Schnorr verifiers which use `ecrecover`, i.e.
+/// `keccak256(abi.encodePacked(address(R), parity, px, message))`.  Signers and aggregators
+/// which use it make signatures an `EvmSignature` can be made from
+pub struct EvmChallenge;
+
+impl ChallengeHash for EvmChallenge {
+    #[allow(non_snake_case)]
+    fn challenge(public_key: &Point, R: &Point, msg: &[u8]) -> Scalar {
+        Scalar::from(challenge_bytes(public_key, R, msg))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A signature in the form Solidity Schnorr verifiers take, which check that `e` is the
+/// challenge hash of the address `ecrecover` gives for `s*G - e*P`
+pub struct EvmSignature {
+    /// 27 if the group key has even y, otherwise 28
+    pub parity: u8,
+    /// the x coordinate of the group key
+    pub px: [u8; 32],
+    /// the challenge hash, as the verifier computes it
+    pub e: [u8; 32],
+    /// the signature scalar
+    pub s: [u8; 32],
+}
+
+impl EvmSignature {
+    /// Convert `sig`, made with `EvmChallenge` by `public_key` over the 32 byte `msg`
+    pub fn new(sig: &Signature, public_key: &Point, msg: &[u8; 32]) -> Result<Self, Error> {
+        if !is_supported_key(public_key) {
+            return Err(Error::KeyNotSupported);
+        }
+        Ok(Self {
+            parity: parity(public_key),
+            px: public_key.x().to_bytes(),
+            e: challenge_bytes(public_key, &sig.R, msg),
+            s: sig.z.to_bytes(),
+        })
+    }
+
+    /// Verify the signature over `msg` the way Solidity verifiers do
+    pub fn verify(&self, msg: &[u8; 32]) -> bool {
+        let Ok(public_key) = Point::lift_x(&field::Element::from(self.px)) else {
+            return false;
+        };
+        let public_key = match (public_key.has_even_y(), self.parity) {
+            (true, 27) | (false, 28) => public_key,
+            (false, 27) | (true, 28) => -public_key,
+            _ => return false,
+        };
+        if !is_supported_key(&public_key) {
+            return false;
+        }
+        let s = Scalar::from(self.s);
+        if s == Scalar::from(0) {
+            return false;
+        }
+
+        #[allow(non_snake_case)]
+        let R = s * G - Scalar::from(self.e) * public_key;
+        challenge_bytes(&public_key, &R, msg) == self.e
+    }
+
+    /// The arguments of `verify(uint8 parity, bytes32 px, bytes32 message, bytes32 e, bytes32 s)`,
+    /// ABI encoded
+    pub fn abi_encode(&self, msg: &[u8; 32]) -> Vec<u8> {
+        let mut parity = [0u8; 32];
+        parity[31] = self.parity;
+        [parity, self.px, *msg, self.e, self.s].concat()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use p256k1::{
+        point::{Point, G},
+        scalar::Scalar,
+    };
+    use rand_core::OsRng;
+
+    use super::{address, is_supported_key, keccak256, Error, EvmChallenge, EvmSignature};
+    use crate::{
+        common::{PublicNonce, Signature, SignatureShare},
+        taproot::test_helpers,
+        traits::{Aggregator, Signer},
+        v2,
+    };
+
+    #[test]
+    fn keccak() {
+        assert_eq!(
+            hex::encode(keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+        assert_eq!(
+            hex::encode(keccak256(b"abc")),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        assert_eq!(
+            hex::encode(keccak256(b"The quick brown fox jumps over the lazy dog")),
+            "4d741b6f1eb29cb2a9b9911c82f56fa8d73b04959d3d9d222895df6c0b28aa15"
+        );
+
+        // the address of the private key 1
+        assert_eq!(
+            hex::encode(address(&(Scalar::from(1) * G))),
+            "7e5f4552091a69125d5dfcb7b8c2659029395bdf"
+        );
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn sign() {
+        let mut rng = OsRng;
+        let (Nk, Np, T) = (10, 4, 7);
+        let signer_key_ids = [vec![0, 1, 2], vec![3, 4], vec![5, 6, 7], vec![8, 9]];
+
+        // about half of all group keys can't be used with Solidity verifiers
+        let (mut signers, A, group_key) = loop {
+            let mut signers: Vec<v2::Signer<EvmChallenge>> = signer_key_ids
+                .iter()
+                .enumerate()
+                .map(|(id, ids)| Signer::new(id.try_into().unwrap(), ids, Np, Nk, T, &mut rng))
+                .collect();
+            let A = test_helpers::dkg(&mut signers, &mut rng).unwrap();
+            let group_key = A.iter().fold(Point::default(), |s, a| s + a.poly[0]);
+            if is_supported_key(&group_key) {
+                break (signers, A, group_key);
+            }
+            let sig = Signature {
+                R: G,
+                z: Scalar::from(1),
+            };
+            assert_eq!(
+                EvmSignature::new(&sig, &group_key, &[0; 32]),
+                Err(Error::KeyNotSupported)
+            );
+        };
+
+        let mut sig_agg = v2::Aggregator::<EvmChallenge>::new(Nk, T);
+        sig_agg.init(A).unwrap();
+        let msg = keccak256(b"It was many and many a year ago");
+        let S = &mut signers[..3];
+        let signer_ids = S.iter().map(|s| s.get_id()).collect::<Vec<u32>>();
+        let key_ids = S.iter().flat_map(|s| s.get_key_ids()).collect::<Vec<u32>>();
+        let nonces = S
+            .iter_mut()
+            .flat_map(|s| s.gen_nonces(&mut rng))
+            .collect::<Vec<PublicNonce>>();
+        let sig_shares = S
+            .iter()
+            .flat_map(|s| Signer::sign(s, &msg, &signer_ids, &key_ids, &nonces))
+            .collect::<Vec<SignatureShare>>();
+        let sig = sig_agg.sign(&msg, &nonces, &sig_shares, &key_ids).unwrap();
+        assert!(sig.verify_with::<EvmChallenge>(&group_key, &msg));
+
+        let evm_sig = EvmSignature::new(&sig, &group_key, &msg).unwrap();
+        assert!(evm_sig.verify(&msg));
+        assert!(!evm_sig.verify(&keccak256(b"other message")));
+        // the negated group key gives a different challenge
+        let mut bad_sig = evm_sig.clone();
+        bad_sig.parity = if evm_sig.parity == 27 { 28 } else { 27 };
+        assert!(!bad_sig.verify(&msg));
+
+        let calldata = evm_sig.abi_encode(&msg);
+        assert_eq!(calldata.len(), 5 * 32);
+        assert_eq!(calldata[31], evm_sig.parity);
+        assert_eq!(calldata[64..96], msg);
+    }
+}
@@ -1,24 +1,59 @@
 #![deny(missing_docs)]
 #![doc = include_str!("../README.md")]
 
+/// Tamper-evident logs of the packets sent and received during the protocol
+pub mod audit;
+/// BIP-322 generic message signing, so the group can prove it owns its taproot address
+pub mod bip322;
+/// Blind signing, where signers sign a blinded challenge without learning the message
+pub mod blind;
 /// Types which are common to both v1 and v2
 #[allow(clippy::op_ref)]
 pub mod common;
 /// Functions to perform various computations needed for v1 and v2
 pub mod compute;
+/// Scalar multiplications by secrets, which the constant-time feature hardens against timing
+/// side channels
+pub mod ct;
 /// Errors which are returned from objects and functions
 pub mod errors;
+/// Schnorr signatures which Solidity verifiers can check, so a group key can control EVM
+/// contracts
+pub mod evm;
+/// Locking the process's memory into RAM, so key material is never written to swap
+#[cfg(feature = "mlock")]
+pub mod memlock;
+/// MuSig2 key aggregation and signing for n-of-n groups, compatible with BIP-327
+#[allow(clippy::op_ref)]
+pub mod musig2;
 /// Network messages
 pub mod net;
+/// Signing the taproot key spends in a PSBT with the group key
+#[cfg(feature = "bitcoin")]
+pub mod psbt;
+/// Publicly verifiable encryption of DKG private shares, so anyone can check a dealer's shares
+/// against its commitments
+#[allow(clippy::op_ref)]
+pub mod pvss;
+/// A signer which keeps its private state in a remote keystore or HSM
+pub mod remote;
 /// Schnorr utility types
 #[allow(clippy::op_ref)]
 pub mod schnorr;
+/// SIP-018 structured data hashing and signing, for Stacks applications
+pub mod sip018;
 /// State machines
 pub mod state_machine;
+/// Pluggable storage for signer state
+pub mod store;
 /// Functions for doing BIP-340 schnorr proofs and other taproot actions
 pub mod taproot;
+/// Utilities for testing code which uses WSTS
+pub mod testing;
 /// Traits which are used for v1 and v2
 pub mod traits;
+/// Ways of moving packets between signers and the coordinator
+pub mod transport;
 /// Utilities for hashing and encryption
 pub mod util;
 /// Version 1 of WSTS, which encapsulates a number of parties using vanilla FROST
@@ -27,6 +62,10 @@ pub mod v1;
 /// Version 1 of WSTS, which encapsulates a number of parties using vanilla FROST
 #[allow(clippy::op_ref)]
 pub mod v2;
+/// Version 3 of WSTS, which is specialized for signers which each hold exactly one key
+pub mod v3;
+/// Deterministic JSON test vectors, so other implementations can check interop byte-for-byte
+pub mod vectors;
 /// Shamir secret sharing, using in distributed key generation
 pub mod vss;
 
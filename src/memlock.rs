@@ -0,0 +1,156 @@
+use crate::errors::MemLockError;
+
+/// Lock the pages holding `secret` into RAM, so it's never written to swap.  Every page touched
+/// by `secret` is locked, including whatever else shares them, until `unlock` is called on it.
+///
+/// Locked memory counts against `RLIMIT_MEMLOCK` unless the process has `CAP_IPC_LOCK`, and the
+/// default limit is often only a few pages, so going over it returns `MemLockError::Limit`.
+pub fn lock<T: ?Sized>(secret: &T) -> Result<(), MemLockError> {
+    #[cfg(unix)]
+    {
+        let (addr, len) = region(secret);
+        // SAFETY: mlock only changes how the kernel pages memory the reference keeps alive
+        check(unsafe { libc::mlock(addr, len) })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = secret;
+        Err(MemLockError::Unsupported)
+    }
+}
+
+/// Undo `lock`, letting the pages holding `secret` be swapped again
+pub fn unlock<T: ?Sized>(secret: &T) -> Result<(), MemLockError> {
+    #[cfg(unix)]
+    {
+        let (addr, len) = region(secret);
+        // SAFETY: munlock only changes how the kernel pages memory the reference keeps alive
+        check(unsafe { libc::munlock(addr, len) })
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = secret;
+        Err(MemLockError::Unsupported)
+    }
+}
+
+/// Lock all of the process's memory into RAM, including memory it allocates later, so private
+/// polynomial coefficients, decrypted DKG shares, nonces and key shares are never written to swap.
+///
+/// The secrets live in ordinary heap containers alongside everything else, so where they can't be
+/// passed to `lock` individually the whole process is locked instead.  The whole process counts
+/// against `RLIMIT_MEMLOCK` unless it has `CAP_IPC_LOCK`, so this returns `MemLockError::Limit` if
+/// the limit is too small for what's already mapped; and once it succeeds, any later allocation
+/// which would go over the limit fails, which aborts the process.  Only use it with the
+/// capability, or with a limit set well above the process's peak memory use.
+pub fn lock_all() -> Result<(), MemLockError> {
+    #[cfg(unix)]
+    {
+        // SAFETY: mlockall only changes how the kernel pages the process's memory
+        check(unsafe { libc::mlockall(libc::MCL_CURRENT | libc::MCL_FUTURE) })
+    }
+    #[cfg(not(unix))]
+    {
+        Err(MemLockError::Unsupported)
+    }
+}
+
+/// Undo `lock_all`, letting the process's memory be swapped again
+pub fn unlock_all() -> Result<(), MemLockError> {
+    #[cfg(unix)]
+    {
+        // SAFETY: munlockall only changes how the kernel pages the process's memory
+        check(unsafe { libc::munlockall() })
+    }
+    #[cfg(not(unix))]
+    {
+        Err(MemLockError::Unsupported)
+    }
+}
+
+#[cfg(unix)]
+fn region<T: ?Sized>(secret: &T) -> (*const libc::c_void, usize) {
+    (
+        secret as *const T as *const libc::c_void,
+        std::mem::size_of_val(secret),
+    )
+}
+
+/// Turn the return value of a locking call into an error, explaining the rlimit if that's the
+/// reason it failed
+#[cfg(unix)]
+fn check(ret: libc::c_int) -> Result<(), MemLockError> {
+    if ret == 0 {
+        return Ok(());
+    }
+    let err = std::io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EPERM) | Some(libc::ENOMEM) | Some(libc::EAGAIN) => {
+            Err(MemLockError::Limit(memlock_limit()))
+        }
+        _ => Err(MemLockError::Io(err.to_string())),
+    }
+}
+
+/// The soft RLIMIT_MEMLOCK of the process, in bytes
+#[cfg(unix)]
+// rlim_t is only u64 on some platforms
+#[allow(clippy::unnecessary_cast)]
+fn memlock_limit() -> u64 {
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: getrlimit only writes to the struct it's given
+    if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) } == 0 {
+        limit.rlim_cur as u64
+    } else {
+        0
+    }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+    use crate::errors::MemLockError;
+
+    #[test]
+    fn lock_under_limit() {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: getrlimit and setrlimit only read and write the struct they're given
+        assert_eq!(
+            unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit) },
+            0
+        );
+        let lowered = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: limit.rlim_max,
+        };
+        assert_eq!(
+            unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &lowered) },
+            0
+        );
+
+        // with no allowance only a process with CAP_IPC_LOCK can lock, and the error says why
+        let secret = vec![0x5au8; 4096];
+        let locked = super::lock(secret.as_slice());
+        let locked_all = super::lock_all();
+        let _ = super::unlock_all();
+        assert_eq!(unsafe { libc::setrlimit(libc::RLIMIT_MEMLOCK, &limit) }, 0);
+
+        for result in [&locked, &locked_all] {
+            match result {
+                Ok(()) => {}
+                Err(e) => {
+                    assert_eq!(e, &MemLockError::Limit(0));
+                    assert!(e.to_string().contains("RLIMIT_MEMLOCK"));
+                }
+            }
+        }
+        if locked.is_ok() {
+            super::unlock(secret.as_slice()).unwrap();
+        }
+    }
+}
@@ -0,0 +1,578 @@
+use num_traits::{One, Zero};
+use p256k1::{
+    point::{Compressed, Point, G},
+    scalar::Scalar,
+};
+use sha2::Digest;
+
+use crate::{
+    common::{Nonce, PublicNonce, TaprootTweak},
+    compute, ct,
+    taproot::SchnorrProof,
+    util::hash_to_scalar,
+};
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from MuSig2 key aggregation and signing
+pub enum Error {
+    #[error("no public keys to aggregate")]
+    /// There were no public keys to aggregate
+    NoKeys,
+    #[error("the aggregate public key is the point at infinity")]
+    /// The keys or tweaks cancelled out to the point at infinity
+    InfiniteKey,
+    #[error("the signing key isn't one of the aggregated keys")]
+    /// A partial signature was made with a key which wasn't aggregated
+    UnknownKey,
+    #[error("bad encoding of a point {0:?}")]
+    /// A public key or nonce couldn't be decoded
+    BadPoint(Vec<u8>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(non_snake_case)]
+/// The BIP-327 key aggregation context for an n-of-n group of public keys, with any tweaks
+/// applied to it.  The keys are aggregated in the order they are given, so every signer must
+/// use the same order.
+pub struct KeyAggContext {
+    /// the public keys which were aggregated
+    pub keys: Vec<Point>,
+    /// the aggregate public key, with any tweaks applied
+    pub Q: Point,
+    list_hash: [u8; 32],
+    second_key: Option<Point>,
+    gacc: Scalar,
+    tacc: Scalar,
+}
+
+impl KeyAggContext {
+    /// Aggregate `keys` into a single public key
+    pub fn new(keys: &[Point]) -> Result<Self, Error> {
+        let first_key = keys.first().ok_or(Error::NoKeys)?;
+
+        let mut hasher = compute::tagged_hash("KeyAgg list");
+        for key in keys {
+            hasher.update(key.compress().as_bytes());
+        }
+        let mut ctx = Self {
+            keys: keys.to_vec(),
+            Q: Point::zero(),
+            list_hash: hasher.finalize().into(),
+            second_key: keys.iter().find(|key| *key != first_key).copied(),
+            gacc: Scalar::one(),
+            tacc: Scalar::zero(),
+        };
+
+        let coefficients = keys.iter().map(|key| ctx.coefficient(key)).collect();
+        ctx.Q = Point::multimult(coefficients, keys.to_vec()).map_err(|_| Error::InfiniteKey)?;
+        if ctx.Q.is_zero() {
+            return Err(Error::InfiniteKey);
+        }
+        Ok(ctx)
+    }
+
+    /// The key aggregation coefficient of `key`
+    pub fn coefficient(&self, key: &Point) -> Scalar {
+        if Some(*key) == self.second_key {
+            return Scalar::one();
+        }
+        let mut hasher = compute::tagged_hash("KeyAgg coefficient");
+        hasher.update(self.list_hash);
+        hasher.update(key.compress().as_bytes());
+        hash_to_scalar(&mut hasher)
+    }
+
+    /// Tweak the aggregate public key by `tweak`.  An x-only tweak is applied to the even-y
+    /// version of the key, as taproot does.
+    #[allow(non_snake_case)]
+    pub fn apply_tweak(&mut self, tweak: &Scalar, is_xonly: bool) -> Result<(), Error> {
+        let g = if is_xonly && !self.Q.has_even_y() {
+            -Scalar::one()
+        } else {
+            Scalar::one()
+        };
+        let Q = g * self.Q + tweak * G;
+        if Q.is_zero() {
+            return Err(Error::InfiniteKey);
+        }
+        self.Q = Q;
+        self.gacc = g * self.gacc;
+        self.tacc = tweak + g * self.tacc;
+        Ok(())
+    }
+
+    /// Apply the taproot `tweak`, so signatures verify under the output key
+    pub fn apply_taproot_tweak(&mut self, tweak: TaprootTweak) -> Result<(), Error> {
+        let tweak = tweak.scalar(&self.Q);
+        self.apply_tweak(&tweak, true)
+    }
+
+    fn sign(&self) -> Scalar {
+        if self.Q.has_even_y() {
+            Scalar::one()
+        } else {
+            -Scalar::one()
+        }
+    }
+}
+
+/// Encode a public nonce the way BIP-327 does, as its two compressed points
+pub fn encode_nonce(nonce: &PublicNonce) -> [u8; 66] {
+    let mut bytes = [0; 66];
+    bytes[..33].copy_from_slice(&encode_point(&nonce.D));
+    bytes[33..].copy_from_slice(&encode_point(&nonce.E));
+    bytes
+}
+
+/// Decode a public nonce encoded the way BIP-327 does
+pub fn decode_nonce(bytes: &[u8; 66]) -> Result<PublicNonce, Error> {
+    Ok(PublicNonce {
+        D: decode_point(&bytes[..33])?,
+        E: decode_point(&bytes[33..])?,
+    })
+}
+
+/// Aggregate the public nonces of every signer into the nonce which all partial signatures
+/// are made with.  Either point may be the point at infinity.
+pub fn aggregate_nonces(nonces: &[PublicNonce]) -> PublicNonce {
+    nonces.iter().fold(
+        PublicNonce {
+            D: Point::zero(),
+            E: Point::zero(),
+        },
+        |agg, nonce| PublicNonce {
+            D: agg.D + nonce.D,
+            E: agg.E + nonce.E,
+        },
+    )
+}
+
+/// Encode a point in compressed format, or as 33 zero bytes for the point at infinity
+fn encode_point(point: &Point) -> [u8; 33] {
+    if point.is_zero() {
+        [0; 33]
+    } else {
+        point.compress().data
+    }
+}
+
+fn decode_point(bytes: &[u8]) -> Result<Point, Error> {
+    let bad_point = || Error::BadPoint(bytes.to_vec());
+    if bytes[0] != 0x02 && bytes[0] != 0x03 {
+        return Err(bad_point());
+    }
+    let compressed = Compressed::try_from(bytes).map_err(|_| bad_point())?;
+    Point::try_from(&compressed).map_err(|_| bad_point())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[allow(non_snake_case)]
+/// A BIP-327 signing session over a message, which makes, checks, and aggregates partial
+/// signatures once every signer's public nonce is known
+pub struct Session {
+    /// the key aggregation context of the signers
+    pub key_agg: KeyAggContext,
+    /// the aggregate public nonce
+    pub nonce: PublicNonce,
+    /// the message being signed
+    pub msg: Vec<u8>,
+    /// the nonce which goes in the final signature
+    pub R: Point,
+    b: Scalar,
+    e: Scalar,
+}
+
+impl Session {
+    #[allow(non_snake_case)]
+    /// Start a session signing `msg` with the aggregate public `nonce`
+    pub fn new(key_agg: KeyAggContext, nonce: PublicNonce, msg: &[u8]) -> Self {
+        let mut hasher = compute::tagged_hash("MuSig/noncecoef");
+        hasher.update(encode_point(&nonce.D));
+        hasher.update(encode_point(&nonce.E));
+        hasher.update(key_agg.Q.x().to_bytes());
+        hasher.update(msg);
+        let b = hash_to_scalar(&mut hasher);
+
+        let R = nonce.D + b * nonce.E;
+        let R = if R.is_zero() { G } else { R };
+        let e = compute::challenge(&key_agg.Q, &R, msg);
+
+        Self {
+            key_agg,
+            nonce,
+            msg: msg.to_vec(),
+            R,
+            b,
+            e,
+        }
+    }
+
+    /// Make a partial signature with `secret_key` and the secret `nonce` whose public nonce went
+    /// into the aggregate nonce.  The nonce is consumed, since signing twice with it leaks the key.
+    pub fn sign(&self, nonce: Nonce, secret_key: &Scalar) -> Result<Scalar, Error> {
+        let public_key = ct::mul_base(secret_key);
+        if !self.key_agg.keys.contains(&public_key) {
+            return Err(Error::UnknownKey);
+        }
+        let (d, e) = if self.R.has_even_y() {
+            (nonce.d, nonce.e)
+        } else {
+            (-nonce.d, -nonce.e)
+        };
+        let a = self.key_agg.coefficient(&public_key);
+        let g = self.key_agg.sign() * self.key_agg.gacc;
+
+        Ok(d + self.b * e + self.e * a * g * secret_key)
+    }
+
+    #[allow(non_snake_case)]
+    /// Check the partial signature `sig` of the signer with `public_key` and public `nonce`
+    pub fn verify_partial_sig(
+        &self,
+        sig: &Scalar,
+        nonce: &PublicNonce,
+        public_key: &Point,
+    ) -> bool {
+        if !self.key_agg.keys.contains(public_key) {
+            return false;
+        }
+        let R = nonce.D + self.b * nonce.E;
+        let R = if self.R.has_even_y() { R } else { -R };
+        let a = self.key_agg.coefficient(public_key);
+        let g = self.key_agg.sign() * self.key_agg.gacc;
+
+        sig * G == R + (self.e * a * g) * public_key
+    }
+
+    /// Aggregate the partial signatures of every signer into a BIP-340 signature under the
+    /// x-only aggregate public key
+    pub fn aggregate(&self, sigs: &[Scalar]) -> SchnorrProof {
+        let s = sigs.iter().fold(
+            self.e * self.key_agg.sign() * self.key_agg.tacc,
+            |s, sig| s + sig,
+        );
+        SchnorrProof { r: self.R.x(), s }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use p256k1::{
+        point::{Point, G},
+        scalar::Scalar,
+    };
+    use rand_core::OsRng;
+
+    use super::{aggregate_nonces, decode_nonce, encode_nonce, Error, KeyAggContext, Session};
+    use crate::common::{Nonce, PublicNonce, TaprootTweak};
+
+    fn decode_key(hex: &str) -> Point {
+        let bytes = hex::decode(hex).unwrap();
+        super::decode_point(&bytes).unwrap()
+    }
+
+    fn decode_scalar(hex: &str) -> Scalar {
+        let bytes: [u8; 32] = hex::decode(hex).unwrap().try_into().unwrap();
+        Scalar::from(bytes)
+    }
+
+    fn decode_public_nonce(hex: &str) -> PublicNonce {
+        let bytes: [u8; 66] = hex::decode(hex).unwrap().try_into().unwrap();
+        decode_nonce(&bytes).unwrap()
+    }
+
+    // the secret key and nonce shared by the BIP-327 sign_verify and tweak vectors
+    const SECRET_KEY: &str = "7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671";
+    const SECRET_NONCE: [&str; 2] = [
+        "508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61",
+        "FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F7",
+    ];
+    const PUBLIC_NONCES: [&str; 4] = [
+        "0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+        "0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F817980279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798",
+        "032DE2662628C90B03F5E720284EB52FF7D71F4284F627B68A853D78C78E1FFE9303E4C5524E83FFE1493B9077CF1CA6BEB2090C93D930321071AD40B2F44E599046",
+        "0237C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0387BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+    ];
+    const MSG: &str = "F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF";
+
+    fn secret_nonce() -> Nonce {
+        Nonce {
+            d: decode_scalar(SECRET_NONCE[0]),
+            e: decode_scalar(SECRET_NONCE[1]),
+        }
+    }
+
+    /// Sign `msg` with the vector secret key and nonce, given the public `nonces` of every
+    /// signer, then check the partial signature verifies and return it
+    fn sign_vector(key_agg: KeyAggContext, nonces: &[PublicNonce], msg: &str) -> String {
+        let secret_key = decode_scalar(SECRET_KEY);
+        let msg = hex::decode(msg).unwrap();
+        let session = Session::new(key_agg, aggregate_nonces(nonces), &msg);
+        let sig = session.sign(secret_nonce(), &secret_key).unwrap();
+
+        let nonce = PublicNonce::from(&secret_nonce());
+        assert!(session.verify_partial_sig(&sig, &nonce, &(secret_key * G)));
+        assert!(!session.verify_partial_sig(&-sig, &nonce, &(secret_key * G)));
+
+        hex::encode_upper(sig.to_bytes())
+    }
+
+    #[test]
+    fn key_agg() {
+        // vectors from BIP-327
+        let x1 = decode_key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9");
+        let x2 = decode_key("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659");
+        let x3 = decode_key("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66");
+
+        let ctx = KeyAggContext::new(&[x1, x2, x3]).unwrap();
+        assert_eq!(
+            hex::encode_upper(ctx.Q.x().to_bytes()),
+            "90539EEDE565F5D054F32CC0C220126889ED1E5D193BAF15AEF344FE59D4610C"
+        );
+        let ctx = KeyAggContext::new(&[x3, x2, x1]).unwrap();
+        assert_eq!(
+            hex::encode_upper(ctx.Q.x().to_bytes()),
+            "6204DE8B083426DC6EAF9502D27024D53FC826BF7D2012148A0575435DF54B2B"
+        );
+        let ctx = KeyAggContext::new(&[x1, x1, x1]).unwrap();
+        assert_eq!(
+            hex::encode_upper(ctx.Q.x().to_bytes()),
+            "B436E3BAD62B8CD409969A224731C193D051162D8C5AE8B109306127DA3AA935"
+        );
+
+        assert_eq!(KeyAggContext::new(&[]), Err(Error::NoKeys));
+    }
+
+    #[test]
+    fn nonce_encoding() {
+        let nonce = PublicNonce::from(&Nonce::random(&mut OsRng));
+        assert_eq!(decode_nonce(&encode_nonce(&nonce)).unwrap(), nonce);
+        assert!(matches!(decode_nonce(&[0; 66]), Err(Error::BadPoint(_))));
+    }
+
+    fn sign(tweak: Option<TaprootTweak>) {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let secret_keys: Vec<Scalar> = (0..3).map(|_| Scalar::random(&mut rng)).collect();
+        let public_keys: Vec<Point> = secret_keys.iter().map(|key| key * G).collect();
+
+        let mut key_agg = KeyAggContext::new(&public_keys).unwrap();
+        if let Some(tweak) = tweak {
+            key_agg.apply_taproot_tweak(tweak).unwrap();
+        }
+
+        // round one: every signer sends a public nonce
+        let nonces: Vec<Nonce> = (0..3).map(|_| Nonce::random(&mut rng)).collect();
+        let public_nonces: Vec<PublicNonce> = nonces.iter().map(PublicNonce::from).collect();
+        let session = Session::new(key_agg.clone(), aggregate_nonces(&public_nonces), msg);
+
+        // round two: every signer sends a partial signature
+        let sigs: Vec<Scalar> = nonces
+            .into_iter()
+            .zip(&secret_keys)
+            .map(|(nonce, key)| session.sign(nonce, key).unwrap())
+            .collect();
+        for ((sig, nonce), key) in sigs.iter().zip(&public_nonces).zip(&public_keys) {
+            assert!(session.verify_partial_sig(sig, nonce, key));
+        }
+        let bad_sig = sigs[0] + Scalar::from(1);
+        assert!(!session.verify_partial_sig(&bad_sig, &public_nonces[0], &public_keys[0]));
+        assert!(!session.verify_partial_sig(&sigs[0], &public_nonces[1], &public_keys[0]));
+
+        let proof = session.aggregate(&sigs);
+        assert!(proof.verify(&key_agg.Q.x(), msg));
+        assert!(!proof.verify(&key_agg.Q.x(), "wrong message".as_bytes()));
+
+        let other_key = Scalar::random(&mut rng);
+        assert_eq!(
+            session.sign(Nonce::random(&mut rng), &other_key),
+            Err(Error::UnknownKey)
+        );
+    }
+
+    #[test]
+    fn sign_untweaked() {
+        sign(None);
+    }
+
+    #[test]
+    fn sign_taproot() {
+        sign(Some(TaprootTweak::KeySpendNoScripts));
+        sign(Some(TaprootTweak::KeySpend {
+            merkle_root: [7; 32],
+        }));
+    }
+
+    #[test]
+    fn sign_verify_vectors() {
+        // vectors from BIP-327
+        let keys = [
+            decode_key("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            decode_key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            decode_key("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661"),
+        ];
+        let nonces: Vec<PublicNonce> = PUBLIC_NONCES
+            .iter()
+            .map(|nonce| decode_public_nonce(nonce))
+            .collect();
+
+        assert_eq!(decode_scalar(SECRET_KEY) * G, keys[0]);
+        assert_eq!(PublicNonce::from(&secret_nonce()), nonces[0]);
+        assert_eq!(
+            hex::encode_upper(encode_nonce(&aggregate_nonces(&nonces[..3]))),
+            "028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9"
+        );
+
+        let cases = [
+            (
+                [0, 1, 2],
+                MSG,
+                "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB",
+            ),
+            (
+                [1, 0, 2],
+                MSG,
+                "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52",
+            ),
+            (
+                [1, 2, 0],
+                MSG,
+                "FA23C359F6FAC4E7796BB93BC9F0532A95468C539BA20FF86D7C76ED92227900",
+            ),
+            (
+                [0, 1, 2],
+                "",
+                "D7D63FFD644CCDA4E62BC2BC0B1D02DD32A1DC3030E155195810231D1037D82D",
+            ),
+            (
+                [0, 1, 2],
+                "2626262626262626262626262626262626262626262626262626262626262626262626262626",
+                "E184351828DA5094A97C79CABDAAA0BFB87608C32E8829A4DF5340A6F243B78C",
+            ),
+        ];
+        for (indices, msg, expected) in cases {
+            let keys: Vec<Point> = indices.iter().map(|i| keys[*i]).collect();
+            let nonces: Vec<PublicNonce> = indices.iter().map(|i| nonces[*i].clone()).collect();
+            let key_agg = KeyAggContext::new(&keys).unwrap();
+            assert_eq!(sign_vector(key_agg, &nonces, msg), expected);
+        }
+
+        // the public nonces cancel out, so the aggregate nonce is the point at infinity
+        let infinite_nonces = [nonces[0].clone(), nonces[3].clone()];
+        assert_eq!(encode_nonce(&aggregate_nonces(&infinite_nonces)), [0; 66]);
+        let key_agg = KeyAggContext::new(&keys[..2]).unwrap();
+        assert_eq!(
+            sign_vector(key_agg, &infinite_nonces, MSG),
+            "AE386064B26105404798F75DE2EB9AF5EDA5387B064B83D049CB7C5E08879531"
+        );
+
+        // the signer's key isn't one of the aggregated keys
+        let key_agg = KeyAggContext::new(&keys[1..]).unwrap();
+        let session = Session::new(
+            key_agg,
+            aggregate_nonces(&nonces[..3]),
+            &hex::decode(MSG).unwrap(),
+        );
+        assert_eq!(
+            session.sign(secret_nonce(), &decode_scalar(SECRET_KEY)),
+            Err(Error::UnknownKey)
+        );
+
+        // a public key which isn't on the curve
+        let bad_key =
+            hex::decode("020000000000000000000000000000000000000000000000000000000000000007")
+                .unwrap();
+        assert!(matches!(
+            super::decode_point(&bad_key),
+            Err(Error::BadPoint(_))
+        ));
+    }
+
+    #[test]
+    fn tweak_vectors() {
+        // vectors from BIP-327
+        let keys = [
+            decode_key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            decode_key("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            decode_key("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+        ];
+        let nonces: Vec<PublicNonce> = [PUBLIC_NONCES[1], PUBLIC_NONCES[2], PUBLIC_NONCES[0]]
+            .iter()
+            .map(|nonce| decode_public_nonce(nonce))
+            .collect();
+        let tweaks = [
+            decode_scalar("E8F791FF9225A2AF0102AFFF4A9A723D9612A682A25EBE79802B263CDFCD83BB"),
+            decode_scalar("AE2EA797CC0FE72AC5B97B97F3C6957D7E4199A167A58EB08BCAFFDA70AC0455"),
+            decode_scalar("F52ECBC565B3D8BEA2DFD5B75A4F457E54369809322E4120831626F290FA87E0"),
+            decode_scalar("1969AD73CC177FA0B4FCED6DF1F7BF9907E665FDE9BA196A74FED0A3CF5AEF9D"),
+        ];
+
+        let cases: [(&[(usize, bool)], &str); 5] = [
+            (
+                &[(0, true)],
+                "E28A5C66E61E178C2BA19DB77B6CF9F7E2F0F56C17918CD13135E60CC848FE91",
+            ),
+            (
+                &[(0, false)],
+                "38B0767798252F21BF5702C48028B095428320F73A4B14DB1E25DE58543D2D2D",
+            ),
+            (
+                &[(0, false), (1, true)],
+                "408A0A21C4A0F5DACAF9646AD6EB6FECD7F7A11F03ED1F48DFFF2185BC2C2408",
+            ),
+            (
+                &[(0, false), (1, false), (2, true), (3, true)],
+                "45ABD206E61E3DF2EC9E264A6FEC8292141A633C28586388235541F9ADE75435",
+            ),
+            (
+                &[(0, true), (1, false), (2, true), (3, false)],
+                "B255FDCAC27B40C7CE7848E2D3B7BF5EA0ED756DA81565AC804CCCA3E1D5D239",
+            ),
+        ];
+        for (applied, expected) in cases {
+            let mut key_agg = KeyAggContext::new(&keys).unwrap();
+            for (i, is_xonly) in applied {
+                key_agg.apply_tweak(&tweaks[*i], *is_xonly).unwrap();
+            }
+            assert_eq!(sign_vector(key_agg, &nonces, MSG), expected);
+        }
+    }
+
+    #[test]
+    fn sig_agg_vectors() {
+        // vectors from BIP-327
+        let keys = [
+            decode_key("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            decode_key("02D2DC6F5DF7C56ACF38C7FA0AE7A759AE30E19B37359DFDE015872324C7EF6E05"),
+        ];
+        let nonces = [
+            decode_public_nonce("036E5EE6E28824029FEA3E8A9DDD2C8483F5AF98F7177C3AF3CB6F47CAF8D94AE902DBA67E4A1F3680826172DA15AFB1A8CA85C7C5CC88900905C8DC8C328511B53E"),
+            decode_public_nonce("03E4F798DA48A76EEC1C9CC5AB7A880FFBA201A5F064E627EC9CB0031D1D58FC5103E06180315C5A522B7EC7C08B69DCD721C313C940819296D0A7AB8E8795AC1F00"),
+        ];
+        let sigs = [
+            decode_scalar("B15D2CD3C3D22B04DAE438CE653F6B4ECF042F42CFDED7C41B64AAF9B4AF53FB"),
+            decode_scalar("6193D6AC61B354E9105BBDC8937A3454A6D705B6D57322A5A472A02CE99FCB64"),
+        ];
+        let msg = hex::decode("599C67EA410D005B9DA90817CF03ED3B1C868E4DA4EDF00A5880B0082C237869")
+            .unwrap();
+
+        let nonce = aggregate_nonces(&nonces);
+        assert_eq!(
+            hex::encode_upper(encode_nonce(&nonce)),
+            "0341432722C5CD0268D829C702CF0D1CBCE57033EED201FD335191385227C3210C03D377F2D258B64AADC0E16F26462323D701D286046A2EA93365656AFD9875982B"
+        );
+
+        let key_agg = KeyAggContext::new(&keys).unwrap();
+        let session = Session::new(key_agg.clone(), nonce, &msg);
+        let proof = session.aggregate(&sigs);
+        assert_eq!(
+            format!(
+                "{}{}",
+                hex::encode_upper(proof.r.to_bytes()),
+                hex::encode_upper(proof.s.to_bytes())
+            ),
+            "041DA22223CE65C92C9A0D6C2CAC828AAF1EEE56304FEC371DDF91EBB2B9EF0912F1038025857FEDEB3FF696F8B99FA4BB2C5812F6095A2E0004EC99CE18DE1E"
+        );
+        assert!(proof.verify(&key_agg.Q.x(), &msg));
+    }
+}
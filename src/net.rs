@@ -1,9 +1,22 @@
-use hashbrown::HashMap;
-use p256k1::{ecdsa, scalar::Scalar};
+use hashbrown::{HashMap, HashSet};
+use p256k1::{ecdsa, point::Point, scalar::Scalar};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt::{Formatter, Result as FmtResult},
+    io::{Read, Write},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::warn;
 
-use crate::common::{MerkleRoot, PolyCommitment, PublicNonce, SignatureShare};
+use crate::{
+    common::{KeyIdRanges, PolyCommitment, PublicNonce, SignatureShare, TaprootTweak},
+    compute,
+    errors::{DkgError, EncodingError, FragmentError, PacketError},
+    pvss::{Complaint, EncryptedShare},
+    vss::VSS,
+};
 
 /// Trait to encapsulate sign/verify, users only need to impl hash
 pub trait Signable {
@@ -12,8 +25,23 @@ pub trait Signable {
 
     /// Sign a hash of this object using the passed private key
     fn sign(&self, private_key: &Scalar) -> Result<Vec<u8>, ecdsa::Error> {
+        self.sign_with_domain(&[], private_key)
+    }
+
+    /// Verify a hash of this object using the passed public key
+    fn verify(&self, signature: &[u8], public_key: &ecdsa::PublicKey) -> bool {
+        self.verify_with_domain(&[], signature, public_key)
+    }
+
+    /// Sign a hash of this object, bound to the passed domain separation tag, using the passed private key
+    fn sign_with_domain(
+        &self,
+        domain: &[u8],
+        private_key: &Scalar,
+    ) -> Result<Vec<u8>, ecdsa::Error> {
         let mut hasher = Sha256::new();
 
+        hash_domain(domain, &mut hasher);
         self.hash(&mut hasher);
 
         let hash = hasher.finalize();
@@ -23,10 +51,16 @@ pub trait Signable {
         }
     }
 
-    /// Verify a hash of this object using the passed public key
-    fn verify(&self, signature: &[u8], public_key: &ecdsa::PublicKey) -> bool {
+    /// Verify a hash of this object, bound to the passed domain separation tag, using the passed public key
+    fn verify_with_domain(
+        &self,
+        domain: &[u8],
+        signature: &[u8],
+        public_key: &ecdsa::PublicKey,
+    ) -> bool {
         let mut hasher = Sha256::new();
 
+        hash_domain(domain, &mut hasher);
         self.hash(&mut hasher);
 
         let hash = hasher.finalize();
@@ -39,24 +73,300 @@ pub trait Signable {
     }
 }
 
+/// Prefix the hasher with an application supplied domain (e.g. network magic or chain ID).
+/// An empty domain leaves the hash unchanged, so existing deployments keep verifying.
+fn hash_domain(domain: &[u8], hasher: &mut Sha256) {
+    if !domain.is_empty() {
+        hasher.update("DOMAIN".as_bytes());
+        hasher.update((domain.len() as u32).to_be_bytes());
+        hasher.update(domain);
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
+/// DKG echo message from signer to all signers, so signers can check that they all received
+/// the same DkgPublicShares
+pub struct DkgEcho {
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// Signer ID
+    pub signer_id: u32,
+    /// Hash of every polynomial commitment the signer received
+    pub public_shares_hash: [u8; 32],
+}
+
+impl Signable for DkgEcho {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("DKG_ECHO".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.public_shares_hash);
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 /// Final DKG status after receiving public and private shares
 pub enum DkgStatus {
     /// DKG completed successfully
     Success,
     /// DKG failed with error
-    Failure(String),
+    Failure(DkgFailure),
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-/// Encapsulation of all possible network message types
+#[derive(
+    thiserror::Error, Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord,
+)]
+/// Why a DKG private share couldn't be used
+pub enum BadShareReason {
+    /// The share didn't decrypt under the key shared with the sender
+    #[error("failed to decrypt")]
+    Decrypt,
+    /// The share decrypted to something which isn't a scalar
+    #[error("not a scalar")]
+    InvalidScalar,
+    /// The shares decrypted to the wrong number of bytes for the recipient's keys
+    #[error("wrong length")]
+    BadLength,
+    /// The verifiable encryption of the share didn't match the dealer's commitment
+    #[error("bad encryption proof")]
+    BadProof,
+    /// The share and its blinding share didn't match the dealer's Pedersen commitment
+    #[error("doesn't match the hiding commitment")]
+    BadHidingShare,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// A non-interactive complaint that a verifiably encrypted DKG private share couldn't be
+/// decrypted, which anyone holding the dealer's `DkgPrivateShares` can check
+pub struct ShareComplaint {
+    /// the party which dealt the share, a key ID in v1 or a signer ID in v2
+    pub src_party_id: u32,
+    /// the key ID the share was for
+    pub dst_key_id: u32,
+    /// the proof that the share doesn't decrypt
+    pub complaint: Complaint,
+}
+
+impl ShareComplaint {
+    /// Check this complaint by `dst_signer_id`, which owns `dst_key_ids` and has network key
+    /// `dst_public_key`, against the dealer's `private_shares`
+    pub fn verify(
+        &self,
+        private_shares: &DkgPrivateShares,
+        dst_signer_id: u32,
+        dst_key_ids: &[u32],
+        dst_public_key: &Point,
+    ) -> bool {
+        private_shares
+            .verifiable_share(
+                dst_signer_id,
+                dst_key_ids,
+                self.src_party_id,
+                self.dst_key_id,
+            )
+            .is_some_and(|share| self.complaint.verify(share, dst_public_key))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+/// A DKG private share which a signer couldn't use
+pub struct BadPrivateShare {
+    /// the party which dealt the share, a key ID in v1 or a signer ID in v2
+    pub src_party_id: u32,
+    /// the key ID the share was for
+    pub dst_key_id: u32,
+    /// why the share couldn't be used
+    pub reason: BadShareReason,
+}
+
+#[derive(thiserror::Error, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// Why one of a signer's parties couldn't compute its secret, mirroring `DkgError` in a form which
+/// can be sent over the network
+pub enum DkgPartyError {
+    /// The parties (v1) or keys (v2) whose private shares were missing
+    #[error("missing shares from {0:?}")]
+    MissingShares(Vec<u32>),
+    /// The parties whose polynomial commitments didn't verify
+    #[error("bad commitments from {0:?}")]
+    BadCommitments(Vec<u32>),
+    /// The keys which didn't get enough private shares
+    #[error("not enough shares for {0:?}")]
+    NotEnoughShares(Vec<u32>),
+    /// The parties whose private shares didn't match their commitments
+    #[error("bad shares from {0:?}")]
+    BadShares(Vec<u32>),
+    /// An error during point operations
+    #[error("point error {0}")]
+    Point(String),
+}
+
+impl From<&DkgError> for DkgPartyError {
+    fn from(error: &DkgError) -> Self {
+        match error {
+            DkgError::MissingShares(ids) => DkgPartyError::MissingShares(ids.clone()),
+            DkgError::BadIds(ids) => DkgPartyError::BadCommitments(ids.clone()),
+            DkgError::NotEnoughShares(ids) => DkgPartyError::NotEnoughShares(ids.clone()),
+            DkgError::BadShares(ids) => DkgPartyError::BadShares(ids.clone()),
+            DkgError::Point(e) => DkgPartyError::Point(format!("{:?}", e)),
+        }
+    }
+}
+
+#[derive(thiserror::Error, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// Why DKG failed on a signer, so the coordinator can decide whether to retry and whom to exclude
+pub enum DkgFailure {
+    /// The signers whose echoed DkgPublicShares hash didn't match this signer's
+    #[error("DkgPublicShares echo mismatch from signers {0:?}")]
+    EchoMismatch(Vec<u32>),
+    /// Why each of the signer's parties which failed couldn't compute its secret, indexed by
+    /// party ID
+    #[error("party errors {0:?}")]
+    Parties(BTreeMap<u32, DkgPartyError>),
+    /// The private shares which couldn't be decrypted, indexed by the signer which sent them
+    #[error("invalid private shares from signers {:?}", .0.keys())]
+    InvalidPrivateShares(BTreeMap<u32, BTreeSet<BadPrivateShare>>),
+    /// Proofs that verifiably encrypted private shares couldn't be decrypted, indexed by the signer
+    /// which sent them
+    #[error("undecryptable private shares from signers {:?}", .0.keys())]
+    ShareComplaints(BTreeMap<u32, Vec<ShareComplaint>>),
+    /// The signer gave up waiting for the other signers
+    #[error("timed out")]
+    Timeout,
+    /// Some other error, e.g. a bad point
+    #[error("{0}")]
+    Other(String),
+}
+
+impl DkgFailure {
+    /// The parties, or signers for an echo mismatch, which this failure blames
+    pub fn blamed_ids(&self) -> Vec<u32> {
+        match self {
+            DkgFailure::EchoMismatch(ids) => ids.clone(),
+            DkgFailure::Parties(errors) => errors
+                .values()
+                .flat_map(|error| match error {
+                    DkgPartyError::BadCommitments(ids) | DkgPartyError::BadShares(ids) => &ids[..],
+                    _ => &[],
+                })
+                .copied()
+                .collect::<BTreeSet<u32>>()
+                .into_iter()
+                .collect(),
+            DkgFailure::InvalidPrivateShares(bad_shares) => bad_shares
+                .values()
+                .flatten()
+                .map(|bad_share| bad_share.src_party_id)
+                .collect::<BTreeSet<u32>>()
+                .into_iter()
+                .collect(),
+            DkgFailure::ShareComplaints(complaints) => complaints
+                .values()
+                .flatten()
+                .map(|complaint| complaint.src_party_id)
+                .collect::<BTreeSet<u32>>()
+                .into_iter()
+                .collect(),
+            DkgFailure::Timeout | DkgFailure::Other(_) => vec![],
+        }
+    }
+}
+
+impl From<&HashMap<u32, DkgError>> for DkgFailure {
+    /// Keep the error from each of a signer's parties, so the coordinator can tell which check
+    /// failed for which party
+    fn from(errors: &HashMap<u32, DkgError>) -> Self {
+        DkgFailure::Parties(
+            errors
+                .iter()
+                .map(|(party_id, error)| (*party_id, error.into()))
+                .collect(),
+        )
+    }
+}
+
+impl Signable for DkgFailure {
+    fn hash(&self, hasher: &mut Sha256) {
+        let (tag, ids) = match self {
+            DkgFailure::EchoMismatch(ids) => ("ECHO_MISMATCH", &ids[..]),
+            DkgFailure::Parties(errors) => {
+                hasher.update("PARTIES".as_bytes());
+                for (party_id, error) in errors {
+                    hasher.update(party_id.to_be_bytes());
+                    let (tag, ids) = match error {
+                        DkgPartyError::MissingShares(ids) => ("MISSING_SHARES", &ids[..]),
+                        DkgPartyError::BadCommitments(ids) => ("BAD_COMMITMENTS", &ids[..]),
+                        DkgPartyError::NotEnoughShares(ids) => ("NOT_ENOUGH_SHARES", &ids[..]),
+                        DkgPartyError::BadShares(ids) => ("BAD_SHARES", &ids[..]),
+                        DkgPartyError::Point(reason) => {
+                            hasher.update("POINT".as_bytes());
+                            hasher.update(reason.as_bytes());
+                            continue;
+                        }
+                    };
+                    hasher.update(tag.as_bytes());
+                    hasher.update((ids.len() as u32).to_be_bytes());
+                    for id in ids {
+                        hasher.update(id.to_be_bytes());
+                    }
+                }
+                return;
+            }
+            DkgFailure::InvalidPrivateShares(bad_shares) => {
+                hasher.update("INVALID_PRIVATE_SHARES".as_bytes());
+                for (signer_id, signer_bad_shares) in bad_shares {
+                    hasher.update(signer_id.to_be_bytes());
+                    hasher.update((signer_bad_shares.len() as u32).to_be_bytes());
+                    for bad_share in signer_bad_shares {
+                        hasher.update(bad_share.src_party_id.to_be_bytes());
+                        hasher.update(bad_share.dst_key_id.to_be_bytes());
+                        hasher.update([bad_share.reason as u8]);
+                    }
+                }
+                return;
+            }
+            DkgFailure::ShareComplaints(complaints) => {
+                hasher.update("SHARE_COMPLAINTS".as_bytes());
+                for (signer_id, signer_complaints) in complaints {
+                    hasher.update(signer_id.to_be_bytes());
+                    hasher.update((signer_complaints.len() as u32).to_be_bytes());
+                    for share_complaint in signer_complaints {
+                        let complaint = &share_complaint.complaint;
+                        hasher.update(share_complaint.src_party_id.to_be_bytes());
+                        hasher.update(share_complaint.dst_key_id.to_be_bytes());
+                        hasher.update(complaint.chunk.to_be_bytes());
+                        hasher.update(complaint.dh_key.compress().as_bytes());
+                        hasher.update(complaint.proof.c.to_bytes());
+                        hasher.update(complaint.proof.z.to_bytes());
+                    }
+                }
+                return;
+            }
+            DkgFailure::Timeout => ("TIMEOUT", &[][..]),
+            DkgFailure::Other(reason) => {
+                hasher.update("OTHER".as_bytes());
+                hasher.update(reason.as_bytes());
+                return;
+            }
+        };
+        hasher.update(tag.as_bytes());
+        hasher.update((ids.len() as u32).to_be_bytes());
+        for id in ids {
+            hasher.update(id.to_be_bytes());
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+/// Encapsulation of all possible network message types.  New types may be added, so match on it
+/// with a wildcard arm.
 pub enum Message {
     /// Tell signers to begin DKG by sending DKG public shares
     DkgBegin(DkgBegin),
     /// Send DKG public shares
     DkgPublicShares(DkgPublicShares),
     /// Tell signers to send DKG private shares
-    DkgPrivateBegin(DkgBegin),
+    DkgPrivateBegin(DkgPrivateBegin),
     /// Send DKG private shares
     DkgPrivateShares(DkgPrivateShares),
     /// Tell coordinator that DKG is complete
@@ -69,6 +379,367 @@ pub enum Message {
     SignatureShareRequest(SignatureShareRequest),
     /// Tell coordinator signature shares
     SignatureShareResponse(SignatureShareResponse),
+    /// Tell coordinator the signer refused to sign the requested message
+    SignatureShareReject(SignatureShareReject),
+    /// Tell signers which DKG public shares were received
+    DkgEcho(DkgEcho),
+    /// Tell signers to reshare their keys under a new threshold by sending DKG public shares
+    ThresholdChangeBegin(ThresholdChangeBegin),
+    /// Tell coordinator a commitment to signing nonces, before revealing them
+    NonceCommitment(NonceCommitment),
+    /// Tell signers to reveal the signing nonces they committed to
+    NonceReveal(NonceReveal),
+    /// Tell signers and coordinator the polynomial commitments behind a signer's Pedersen
+    /// commitments
+    DkgDecommit(DkgDecommit),
+    /// A message of a type this version doesn't know, e.g. from a newer peer, which is kept so it
+    /// can be relayed but is otherwise ignored
+    Unknown {
+        /// the type tag the message was serialized with
+        type_id: String,
+        /// the serialized contents of the message
+        bytes: Vec<u8>,
+    },
+}
+
+/// The type tags of the known message types, in declaration order, so a tag's index is the
+/// variant index serde derives for compact formats like bincode
+const MESSAGE_VARIANTS: &[&str] = &[
+    "DkgBegin",
+    "DkgPublicShares",
+    "DkgPrivateBegin",
+    "DkgPrivateShares",
+    "DkgEnd",
+    "NonceRequest",
+    "NonceResponse",
+    "SignatureShareRequest",
+    "SignatureShareResponse",
+    "SignatureShareReject",
+    "DkgEcho",
+    "ThresholdChangeBegin",
+    "NonceCommitment",
+    "NonceReveal",
+    "DkgDecommit",
+];
+
+/// Known messages are serialized as serde derives for enums, e.g. as a map from the type tag to
+/// the contents in JSON.  Unknown messages can only have come from a self-describing format, and
+/// are serialized back as the same map.
+impl Serialize for Message {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeMap};
+
+        fn variant<S: serde::Serializer, T: Serialize>(
+            serializer: S,
+            index: u32,
+            msg: &T,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.serialize_newtype_variant(
+                "Message",
+                index,
+                MESSAGE_VARIANTS[index as usize],
+                msg,
+            )
+        }
+
+        match self {
+            Message::DkgBegin(msg) => variant(serializer, 0, msg),
+            Message::DkgPublicShares(msg) => variant(serializer, 1, msg),
+            Message::DkgPrivateBegin(msg) => variant(serializer, 2, msg),
+            Message::DkgPrivateShares(msg) => variant(serializer, 3, msg),
+            Message::DkgEnd(msg) => variant(serializer, 4, msg),
+            Message::NonceRequest(msg) => variant(serializer, 5, msg),
+            Message::NonceResponse(msg) => variant(serializer, 6, msg),
+            Message::SignatureShareRequest(msg) => variant(serializer, 7, msg),
+            Message::SignatureShareResponse(msg) => variant(serializer, 8, msg),
+            Message::SignatureShareReject(msg) => variant(serializer, 9, msg),
+            Message::DkgEcho(msg) => variant(serializer, 10, msg),
+            Message::ThresholdChangeBegin(msg) => variant(serializer, 11, msg),
+            Message::NonceCommitment(msg) => variant(serializer, 12, msg),
+            Message::NonceReveal(msg) => variant(serializer, 13, msg),
+            Message::DkgDecommit(msg) => variant(serializer, 14, msg),
+            Message::Unknown { type_id, bytes } => {
+                let value = serde_json::from_slice::<serde_json::Value>(bytes)
+                    .map_err(|e| S::Error::custom(e.to_string()))?;
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(type_id, &value)?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// The type tag of a serialized message, which is its name in self-describing formats and its
+/// variant index in compact ones
+struct MessageTag(String);
+
+impl<'de> Deserialize<'de> for MessageTag {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct TagVisitor;
+
+        impl serde::de::Visitor<'_> for TagVisitor {
+            type Value = MessageTag;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+                f.write_str("a message type tag")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, index: u64) -> Result<MessageTag, E> {
+                // a compact format has no name to keep for an unknown index, nor any way to skip
+                // the contents, so only known messages can be decoded from one
+                usize::try_from(index)
+                    .ok()
+                    .and_then(|index| MESSAGE_VARIANTS.get(index))
+                    .map(|name| MessageTag(name.to_string()))
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Unsigned(index), &self))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, name: &str) -> Result<MessageTag, E> {
+                Ok(MessageTag(name.to_string()))
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, name: &[u8]) -> Result<MessageTag, E> {
+                std::str::from_utf8(name)
+                    .map(|name| MessageTag(name.to_string()))
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Bytes(name), &self))
+            }
+        }
+
+        deserializer.deserialize_identifier(TagVisitor)
+    }
+}
+
+/// Decodes known message types as serde derives for enums, so any format works, and unknown ones
+/// as `Message::Unknown` rather than rejecting them, which needs a self-describing format like JSON
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::{EnumAccess, Error, VariantAccess};
+
+        struct MessageVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for MessageVisitor {
+            type Value = Message;
+
+            fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+                f.write_str("a network message")
+            }
+
+            fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Message, A::Error> {
+                let (MessageTag(type_id), variant) = data.variant::<MessageTag>()?;
+                Ok(match type_id.as_str() {
+                    "DkgBegin" => Message::DkgBegin(variant.newtype_variant()?),
+                    "DkgPublicShares" => Message::DkgPublicShares(variant.newtype_variant()?),
+                    "DkgPrivateBegin" => Message::DkgPrivateBegin(variant.newtype_variant()?),
+                    "DkgPrivateShares" => Message::DkgPrivateShares(variant.newtype_variant()?),
+                    "DkgEnd" => Message::DkgEnd(variant.newtype_variant()?),
+                    "NonceRequest" => Message::NonceRequest(variant.newtype_variant()?),
+                    "NonceResponse" => Message::NonceResponse(variant.newtype_variant()?),
+                    "SignatureShareRequest" => {
+                        Message::SignatureShareRequest(variant.newtype_variant()?)
+                    }
+                    "SignatureShareResponse" => {
+                        Message::SignatureShareResponse(variant.newtype_variant()?)
+                    }
+                    "SignatureShareReject" => {
+                        Message::SignatureShareReject(variant.newtype_variant()?)
+                    }
+                    "DkgEcho" => Message::DkgEcho(variant.newtype_variant()?),
+                    "ThresholdChangeBegin" => {
+                        Message::ThresholdChangeBegin(variant.newtype_variant()?)
+                    }
+                    "NonceCommitment" => Message::NonceCommitment(variant.newtype_variant()?),
+                    "NonceReveal" => Message::NonceReveal(variant.newtype_variant()?),
+                    "DkgDecommit" => Message::DkgDecommit(variant.newtype_variant()?),
+                    _ => {
+                        let value = variant.newtype_variant::<serde_json::Value>()?;
+                        let bytes = serde_json::to_vec(&value)
+                            .map_err(|e| A::Error::custom(e.to_string()))?;
+                        Message::Unknown { type_id, bytes }
+                    }
+                })
+            }
+        }
+
+        deserializer.deserialize_enum("Message", MESSAGE_VARIANTS, MessageVisitor)
+    }
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Eq, Hash)]
+/// The type of a network message, without its contents
+pub enum MessageType {
+    /// DkgBegin
+    DkgBegin,
+    /// DkgPublicShares
+    DkgPublicShares,
+    /// DkgPrivateBegin
+    DkgPrivateBegin,
+    /// DkgPrivateShares
+    DkgPrivateShares,
+    /// DkgEnd
+    DkgEnd,
+    /// NonceRequest
+    NonceRequest,
+    /// NonceResponse
+    NonceResponse,
+    /// SignatureShareRequest
+    SignatureShareRequest,
+    /// SignatureShareResponse
+    SignatureShareResponse,
+    /// SignatureShareReject
+    SignatureShareReject,
+    /// DkgEcho
+    DkgEcho,
+    /// ThresholdChangeBegin
+    ThresholdChangeBegin,
+    /// NonceCommitment
+    NonceCommitment,
+    /// NonceReveal
+    NonceReveal,
+    /// DkgDecommit
+    DkgDecommit,
+    /// A message type this version doesn't know
+    Unknown,
+}
+
+impl MessageType {
+    /// A fixed name for this type, which is bound into packet signatures
+    pub fn tag(&self) -> &'static str {
+        match self {
+            MessageType::DkgBegin => "DKG_BEGIN",
+            MessageType::DkgPublicShares => "DKG_PUBLIC_SHARES",
+            MessageType::DkgPrivateBegin => "DKG_PRIVATE_BEGIN",
+            MessageType::DkgPrivateShares => "DKG_PRIVATE_SHARES",
+            MessageType::DkgEnd => "DKG_END",
+            MessageType::NonceRequest => "NONCE_REQUEST",
+            MessageType::NonceResponse => "NONCE_RESPONSE",
+            MessageType::SignatureShareRequest => "SIGNATURE_SHARE_REQUEST",
+            MessageType::SignatureShareResponse => "SIGNATURE_SHARE_RESPONSE",
+            MessageType::SignatureShareReject => "SIGNATURE_SHARE_REJECT",
+            MessageType::DkgEcho => "DKG_ECHO",
+            MessageType::ThresholdChangeBegin => "THRESHOLD_CHANGE_BEGIN",
+            MessageType::NonceCommitment => "NONCE_COMMITMENT",
+            MessageType::NonceReveal => "NONCE_REVEAL",
+            MessageType::DkgDecommit => "DKG_DECOMMIT",
+            MessageType::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// Identifies a message by its type, sender, and round IDs, ignoring its contents
+pub struct MessageId {
+    /// The type of the message
+    pub message_type: MessageType,
+    /// The sending signer, or None if the message came from the coordinator
+    pub signer_id: Option<u32>,
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// Signing round ID, or zero for DKG messages
+    pub sign_id: u64,
+    /// Signing round iteration ID, or zero for DKG messages
+    pub sign_iter_id: u64,
+}
+
+impl Message {
+    /// The type of this message
+    pub fn message_type(&self) -> MessageType {
+        match self {
+            Message::DkgBegin(_) => MessageType::DkgBegin,
+            Message::DkgPublicShares(_) => MessageType::DkgPublicShares,
+            Message::DkgPrivateBegin(_) => MessageType::DkgPrivateBegin,
+            Message::DkgPrivateShares(_) => MessageType::DkgPrivateShares,
+            Message::DkgEnd(_) => MessageType::DkgEnd,
+            Message::NonceRequest(_) => MessageType::NonceRequest,
+            Message::NonceResponse(_) => MessageType::NonceResponse,
+            Message::SignatureShareRequest(_) => MessageType::SignatureShareRequest,
+            Message::SignatureShareResponse(_) => MessageType::SignatureShareResponse,
+            Message::SignatureShareReject(_) => MessageType::SignatureShareReject,
+            Message::DkgEcho(_) => MessageType::DkgEcho,
+            Message::ThresholdChangeBegin(_) => MessageType::ThresholdChangeBegin,
+            Message::NonceCommitment(_) => MessageType::NonceCommitment,
+            Message::NonceReveal(_) => MessageType::NonceReveal,
+            Message::DkgDecommit(_) => MessageType::DkgDecommit,
+            Message::Unknown { .. } => MessageType::Unknown,
+        }
+    }
+
+    /// The type, sender, and round IDs of this message
+    pub fn id(&self) -> MessageId {
+        let (signer_id, dkg_id, sign_id, sign_iter_id) = match self {
+            Message::DkgBegin(msg) => (None, msg.dkg_id, 0, 0),
+            Message::DkgPrivateBegin(msg) => (None, msg.dkg_id, 0, 0),
+            Message::DkgPublicShares(msg) => (Some(msg.signer_id), msg.dkg_id, 0, 0),
+            Message::DkgPrivateShares(msg) => (Some(msg.signer_id), msg.dkg_id, 0, 0),
+            Message::DkgEnd(msg) => (Some(msg.signer_id), msg.dkg_id, 0, 0),
+            Message::DkgEcho(msg) => (Some(msg.signer_id), msg.dkg_id, 0, 0),
+            Message::DkgDecommit(msg) => (Some(msg.signer_id), msg.dkg_id, 0, 0),
+            Message::ThresholdChangeBegin(msg) => (None, msg.dkg_id, 0, 0),
+            Message::Unknown { .. } => (None, 0, 0, 0),
+            Message::NonceRequest(msg) => (None, msg.dkg_id, msg.sign_id, msg.sign_iter_id),
+            Message::NonceCommitment(msg) => (
+                Some(msg.signer_id),
+                msg.dkg_id,
+                msg.sign_id,
+                msg.sign_iter_id,
+            ),
+            Message::NonceReveal(msg) => (None, msg.dkg_id, msg.sign_id, msg.sign_iter_id),
+            Message::NonceResponse(msg) => (
+                Some(msg.signer_id),
+                msg.dkg_id,
+                msg.sign_id,
+                msg.sign_iter_id,
+            ),
+            Message::SignatureShareRequest(msg) => {
+                (None, msg.dkg_id, msg.sign_id, msg.sign_iter_id)
+            }
+            Message::SignatureShareResponse(msg) => (
+                Some(msg.signer_id),
+                msg.dkg_id,
+                msg.sign_id,
+                msg.sign_iter_id,
+            ),
+            Message::SignatureShareReject(msg) => (
+                Some(msg.signer_id),
+                msg.dkg_id,
+                msg.sign_id,
+                msg.sign_iter_id,
+            ),
+        };
+
+        MessageId {
+            message_type: self.message_type(),
+            signer_id,
+            dkg_id,
+            sign_id,
+            sign_iter_id,
+        }
+    }
+}
+
+impl Signable for Message {
+    fn hash(&self, hasher: &mut Sha256) {
+        match self {
+            Message::DkgBegin(msg) => msg.hash(hasher),
+            Message::DkgPrivateBegin(msg) => msg.hash(hasher),
+            Message::DkgPublicShares(msg) => msg.hash(hasher),
+            Message::DkgPrivateShares(msg) => msg.hash(hasher),
+            Message::DkgEnd(msg) => msg.hash(hasher),
+            Message::DkgEcho(msg) => msg.hash(hasher),
+            Message::DkgDecommit(msg) => msg.hash(hasher),
+            Message::ThresholdChangeBegin(msg) => msg.hash(hasher),
+            Message::NonceRequest(msg) => msg.hash(hasher),
+            Message::NonceResponse(msg) => msg.hash(hasher),
+            Message::NonceCommitment(msg) => msg.hash(hasher),
+            Message::NonceReveal(msg) => msg.hash(hasher),
+            Message::SignatureShareRequest(msg) => msg.hash(hasher),
+            Message::SignatureShareResponse(msg) => msg.hash(hasher),
+            Message::SignatureShareReject(msg) => msg.hash(hasher),
+            Message::Unknown { type_id, bytes } => {
+                hasher.update(type_id.as_bytes());
+                hasher.update(bytes);
+            }
+        }
+    }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -76,12 +747,83 @@ pub enum Message {
 pub struct DkgBegin {
     /// DKG round ID
     pub dkg_id: u64,
+    /// the epoch whose signer and key assignments the round, and the signing rounds which use
+    /// its group key, run under
+    #[serde(default)]
+    pub epoch: u64,
+    /// the number of keys needed to sign with the group key
+    #[serde(default)]
+    pub threshold: u32,
+    /// the number of signers taking part
+    #[serde(default)]
+    pub num_signers: u32,
+    /// the number of keys the signers hold between them
+    #[serde(default)]
+    pub num_keys: u32,
+    /// whether signers first send Pedersen commitments, which hide their polynomials until every
+    /// signer is bound to its own, and only reveal their polynomial commitments in a DkgDecommit
+    #[serde(default)]
+    pub pedersen: bool,
 }
 
 impl Signable for DkgBegin {
     fn hash(&self, hasher: &mut Sha256) {
         hasher.update("DKG_BEGIN".as_bytes());
         hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.epoch.to_be_bytes());
+        hasher.update(self.threshold.to_be_bytes());
+        hasher.update(self.num_signers.to_be_bytes());
+        hasher.update(self.num_keys.to_be_bytes());
+        if self.pedersen {
+            hasher.update("PEDERSEN".as_bytes());
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// DKG private begin message from coordinator to signers, naming the signers which should be sent
+/// private shares
+pub struct DkgPrivateBegin {
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// the signers whose keys are sent private shares; the others still send shares, but get none
+    pub signer_ids: Vec<u32>,
+}
+
+impl Signable for DkgPrivateBegin {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("DKG_PRIVATE_BEGIN".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        for signer_id in &self.signer_ids {
+            hasher.update(signer_id.to_be_bytes());
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// Threshold change begin message from coordinator to signers, which starts a DKG round that
+/// reshares the existing group key under a new threshold
+pub struct ThresholdChangeBegin {
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// The new threshold
+    pub threshold: u32,
+    /// The signer whose keys are re-dealt, if this round recovers a lost or compromised signer
+    /// rather than changing the threshold.  Its old key shares aren't reshared, so they're
+    /// useless once the round completes.
+    #[serde(default)]
+    pub recover_signer_id: Option<u32>,
+}
+
+impl Signable for ThresholdChangeBegin {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("THRESHOLD_CHANGE_BEGIN".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.threshold.to_be_bytes());
+        if let Some(recover_signer_id) = self.recover_signer_id {
+            hasher.update("RECOVER".as_bytes());
+            hasher.update(recover_signer_id.to_be_bytes());
+        }
     }
 }
 
@@ -94,6 +836,42 @@ pub struct DkgPublicShares {
     pub signer_id: u32,
     /// List of (party_id, commitment)
     pub comms: Vec<(u32, PolyCommitment)>,
+    /// List of (party_id, Pedersen commitment), sent instead of `comms` in a Pedersen DKG round
+    #[serde(default)]
+    pub hiding_comms: Vec<(u32, Vec<Point>)>,
+}
+
+impl DkgPublicShares {
+    /// The party IDs whose polynomial commitments are malformed or fail to prove ownership
+    pub fn invalid_party_ids(&self, threshold: u32) -> Vec<u32> {
+        self.comms
+            .iter()
+            .filter(|(party_id, comm)| !comm.verify_for(*party_id, threshold))
+            .map(|(party_id, _)| *party_id)
+            .collect()
+    }
+
+    /// The party IDs whose commitments are the wrong kind for a round which does or doesn't use
+    /// Pedersen commitments, or whose Pedersen commitments are the wrong size for `threshold`
+    pub fn invalid_hiding_party_ids(&self, threshold: u32, pedersen: bool) -> Vec<u32> {
+        if !pedersen {
+            return self
+                .hiding_comms
+                .iter()
+                .map(|(party_id, _)| *party_id)
+                .collect();
+        }
+        self.comms
+            .iter()
+            .map(|(party_id, _)| *party_id)
+            .chain(
+                self.hiding_comms
+                    .iter()
+                    .filter(|(_, hiding_comm)| hiding_comm.len() != threshold as usize)
+                    .map(|(party_id, _)| *party_id),
+            )
+            .collect()
+    }
 }
 
 impl Signable for DkgPublicShares {
@@ -107,18 +885,98 @@ impl Signable for DkgPublicShares {
                 hasher.update(a.compress().as_bytes());
             }
         }
+        for (party_id, hiding_comm) in &self.hiding_comms {
+            hasher.update("HIDING".as_bytes());
+            hasher.update(party_id.to_be_bytes());
+            for c in hiding_comm {
+                hasher.update(c.compress().as_bytes());
+            }
+        }
     }
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 /// DKG private shares message from signer to all signers and coordinator
+///
+/// Each recipient signer gets one ciphertext holding all of its shares, laid out positionally
+/// rather than keyed by ID: for each party in `src_party_ids`, the 32-byte share for each of the
+/// recipient's key IDs in `PublicKeys`, in ascending order.  Both ends know the layout, so a
+/// signer with many keys sends one AEAD nonce and tag per recipient instead of one per share.
+/// In a Pedersen DKG round each share is followed by its 32-byte blinding share.
+///
+/// A signer using publicly verifiable shares sends `verifiable_shares` instead, in the same
+/// layout, so anyone can check them against the dealers' polynomial commitments.
 pub struct DkgPrivateShares {
     /// DKG round ID
     pub dkg_id: u64,
     /// Signer ID
     pub signer_id: u32,
-    /// List of (src_key_id, Map(dst_key_id, encrypted_share))
-    pub shares: Vec<(u32, HashMap<u32, Vec<u8>>)>,
+    /// The parties which dealt the shares, in the order their shares appear in each ciphertext
+    pub src_party_ids: Vec<u32>,
+    /// List of (dst_signer_id, encrypted_shares)
+    pub shares: Vec<(u32, Vec<u8>)>,
+    /// List of (dst_signer_id, verifiably encrypted shares), empty unless the signer uses
+    /// publicly verifiable shares
+    #[serde(default)]
+    pub verifiable_shares: Vec<(u32, Vec<EncryptedShare>)>,
+}
+
+impl DkgPrivateShares {
+    /// The verifiably encrypted share dealt by `src_party_id` for `dst_key_id`, which is one of the
+    /// `dst_key_ids` owned by `dst_signer_id`
+    pub fn verifiable_share(
+        &self,
+        dst_signer_id: u32,
+        dst_key_ids: &[u32],
+        src_party_id: u32,
+        dst_key_id: u32,
+    ) -> Option<&EncryptedShare> {
+        let src_index = self
+            .src_party_ids
+            .iter()
+            .position(|id| *id == src_party_id)?;
+        let key_index = dst_key_ids.iter().position(|id| *id == dst_key_id)?;
+        self.verifiable_shares
+            .iter()
+            .find(|(signer_id, _)| *signer_id == dst_signer_id)
+            .and_then(|(_, shares)| shares.get(src_index * dst_key_ids.len() + key_index))
+    }
+
+    /// Check the verifiable shares sent to `dst_signer_id`, which owns `dst_key_ids` and has
+    /// network key `dst_public_key`, against the dealers' `commitments` as indexed in
+    /// `DkgPublicShares`, returning the shares whose proofs fail; this needs no secrets, so any
+    /// observer of the round can run it
+    pub fn invalid_verifiable_shares(
+        &self,
+        dst_signer_id: u32,
+        dst_key_ids: &[u32],
+        dst_public_key: &Point,
+        commitments: &BTreeMap<u32, PolyCommitment>,
+    ) -> Vec<BadPrivateShare> {
+        let positions = self.src_party_ids.iter().flat_map(|src_id| {
+            dst_key_ids
+                .iter()
+                .map(move |dst_key_id| (*src_id, *dst_key_id))
+        });
+        positions
+            .filter(|(src_id, dst_key_id)| {
+                let share = self.verifiable_share(dst_signer_id, dst_key_ids, *src_id, *dst_key_id);
+                // commitments are indexed by the ID they were made for, one past the party ID
+                let commitment = commitments
+                    .get(&compute::id(*src_id).get_u32())
+                    .and_then(|comm| compute::poly(&compute::id(*dst_key_id), &comm.poly).ok());
+                match (share, commitment) {
+                    (Some(share), Some(commitment)) => !share.verify(dst_public_key, &commitment),
+                    _ => true,
+                }
+            })
+            .map(|(src_party_id, dst_key_id)| BadPrivateShare {
+                src_party_id,
+                dst_key_id,
+                reason: BadShareReason::BadProof,
+            })
+            .collect()
+    }
 }
 
 impl Signable for DkgPrivateShares {
@@ -126,12 +984,80 @@ impl Signable for DkgPrivateShares {
         hasher.update("DKG_PRIVATE_SHARES".as_bytes());
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.signer_id.to_be_bytes());
-        // make sure we iterate sequentially
-        for (src_id, share) in &self.shares {
-            hasher.update(src_id.to_be_bytes());
-            for dst_id in 0..share.len() as u32 {
-                hasher.update(dst_id.to_be_bytes());
-                hasher.update(&share[&dst_id]);
+        hasher.update((self.src_party_ids.len() as u32).to_be_bytes());
+        for src_party_id in &self.src_party_ids {
+            hasher.update(src_party_id.to_be_bytes());
+        }
+        for (dst_signer_id, encrypted_shares) in &self.shares {
+            hasher.update(dst_signer_id.to_be_bytes());
+            hasher.update((encrypted_shares.len() as u32).to_be_bytes());
+            hasher.update(encrypted_shares);
+        }
+        for (dst_signer_id, encrypted_shares) in &self.verifiable_shares {
+            hasher.update(dst_signer_id.to_be_bytes());
+            hasher.update((encrypted_shares.len() as u32).to_be_bytes());
+            for share in encrypted_shares {
+                for (r, c) in &share.chunks {
+                    hasher.update(r.compress().as_bytes());
+                    hasher.update(c.compress().as_bytes());
+                }
+                hasher.update(share.proof.c.to_bytes());
+                hasher.update(share.proof.z.to_bytes());
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// DKG decommit message from signer to all signers and coordinator, sent with the private shares
+/// in a Pedersen DKG round to reveal the polynomial commitments behind the signer's Pedersen
+/// commitments
+///
+/// The blinding coefficients are revealed too, so every recipient can check that the polynomial
+/// commitments open the Pedersen commitments it echoed, and a signer can't reveal different
+/// polynomials to different recipients.
+pub struct DkgDecommit {
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// Signer ID
+    pub signer_id: u32,
+    /// List of (party_id, commitment, blinding polynomial coefficients)
+    pub comms: Vec<(u32, PolyCommitment, Vec<Scalar>)>,
+}
+
+impl DkgDecommit {
+    /// The party IDs whose polynomial commitments are malformed, fail to prove ownership, or don't
+    /// open their Pedersen commitments in `hiding_comms`
+    pub fn invalid_party_ids(
+        &self,
+        threshold: u32,
+        hiding_comms: &BTreeMap<u32, Vec<Point>>,
+    ) -> Vec<u32> {
+        self.comms
+            .iter()
+            .filter(|(party_id, comm, blinding)| {
+                !comm.verify_for(*party_id, threshold)
+                    || !hiding_comms.get(party_id).is_some_and(|hiding_comm| {
+                        VSS::verify_opening(hiding_comm, &comm.poly, blinding)
+                    })
+            })
+            .map(|(party_id, _, _)| *party_id)
+            .collect()
+    }
+}
+
+impl Signable for DkgDecommit {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("DKG_DECOMMIT".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        for (party_id, comm, blinding) in &self.comms {
+            hasher.update(party_id.to_be_bytes());
+            for a in &comm.poly {
+                hasher.update(a.compress().as_bytes());
+            }
+            for b in blinding {
+                hasher.update(b.to_bytes());
             }
         }
     }
@@ -153,6 +1079,14 @@ impl Signable for DkgEnd {
         hasher.update("DKG_END".as_bytes());
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.signer_id.to_be_bytes());
+        // sign the failure reason too, so it can be held against the signer which reported it
+        match &self.status {
+            DkgStatus::Success => hasher.update([0]),
+            DkgStatus::Failure(failure) => {
+                hasher.update([1]);
+                failure.hash(hasher);
+            }
+        }
     }
 }
 
@@ -165,6 +1099,12 @@ pub struct NonceRequest {
     pub sign_id: u64,
     /// Signing round iteration ID
     pub sign_iter_id: u64,
+    /// The signers chosen to sign, or None if every signer is asked
+    #[serde(default)]
+    pub signer_ids: Option<Vec<u32>>,
+    /// Whether signers should send a NonceCommitment, and only reveal their nonces once asked
+    #[serde(default)]
+    pub commit: bool,
 }
 
 impl Signable for NonceRequest {
@@ -173,6 +1113,15 @@ impl Signable for NonceRequest {
         hasher.update(self.dkg_id.to_be_bytes());
         hasher.update(self.sign_id.to_be_bytes());
         hasher.update(self.sign_iter_id.to_be_bytes());
+        if let Some(signer_ids) = &self.signer_ids {
+            hasher.update("SIGNER_IDS".as_bytes());
+            for signer_id in signer_ids {
+                hasher.update(signer_id.to_be_bytes());
+            }
+        }
+        if self.commit {
+            hasher.update("COMMIT".as_bytes());
+        }
     }
 }
 
@@ -188,7 +1137,7 @@ pub struct NonceResponse {
     /// Signer ID
     pub signer_id: u32,
     /// Key IDs
-    pub key_ids: Vec<u32>,
+    pub key_ids: KeyIdRanges,
     /// Public nonces
     pub nonces: Vec<PublicNonce>,
 }
@@ -212,6 +1161,68 @@ impl Signable for NonceResponse {
     }
 }
 
+impl NonceResponse {
+    /// The hash a signer commits to before revealing this response
+    pub fn commitment(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        self.hash(&mut hasher);
+        hasher.finalize().into()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// Nonce commitment message from signers to coordinator, which binds a signer to its nonces
+/// before it sees anyone else's, so the last signer to answer can't choose its nonces based on
+/// the others
+pub struct NonceCommitment {
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// Signing round ID
+    pub sign_id: u64,
+    /// Signing round iteration ID
+    pub sign_iter_id: u64,
+    /// Signer ID
+    pub signer_id: u32,
+    /// The `NonceResponse::commitment` of the nonce response the signer will reveal
+    pub commitment: [u8; 32],
+}
+
+impl Signable for NonceCommitment {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("NONCE_COMMITMENT".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.sign_iter_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        hasher.update(self.commitment);
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// Nonce reveal message from coordinator to signers, sent once every signer has committed
+pub struct NonceReveal {
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// Signing round ID
+    pub sign_id: u64,
+    /// Signing round iteration ID
+    pub sign_iter_id: u64,
+    /// The signers whose commitments arrived, which should now send their nonces
+    pub signer_ids: Vec<u32>,
+}
+
+impl Signable for NonceReveal {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("NONCE_REVEAL".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.sign_iter_id.to_be_bytes());
+        for signer_id in &self.signer_ids {
+            hasher.update(signer_id.to_be_bytes());
+        }
+    }
+}
+
 #[derive(Clone, Serialize, Deserialize, Debug)]
 /// Signature share request message from coordinator to signers
 pub struct SignatureShareRequest {
@@ -225,10 +1236,13 @@ pub struct SignatureShareRequest {
     pub nonce_responses: Vec<NonceResponse>,
     /// Bytes to sign
     pub message: Vec<u8>,
+    /// Whether `message` is a 32-byte digest, e.g. a sighash or Stacks block hash, which the
+    /// caller already hashed and which is signed as-is
+    pub is_digest: bool,
     /// Whether to make a taproot signature
     pub is_taproot: bool,
-    /// Taproot merkle root
-    pub merkle_root: Option<MerkleRoot>,
+    /// How the group key is tweaked for a taproot signature
+    pub tweak: TaprootTweak,
 }
 
 impl Signable for SignatureShareRequest {
@@ -242,10 +1256,16 @@ impl Signable for SignatureShareRequest {
         }
 
         hasher.update(self.message.as_slice());
+        hasher.update((self.is_digest as u16).to_be_bytes());
 
         hasher.update((self.is_taproot as u16).to_be_bytes());
-        if let Some(merkle_root) = self.merkle_root {
-            hasher.update(merkle_root);
+        match self.tweak {
+            TaprootTweak::KeySpendNoScripts => {}
+            TaprootTweak::KeySpend { merkle_root } => hasher.update(merkle_root),
+            TaprootTweak::RawTweak(tweak) => {
+                hasher.update("RAW_TWEAK".as_bytes());
+                hasher.update(tweak.to_bytes());
+            }
         }
     }
 }
@@ -279,11 +1299,1351 @@ impl Signable for SignatureShareResponse {
     }
 }
 
+#[derive(thiserror::Error, Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// Why a signer refused to take part in a signing round
+pub enum RejectReason {
+    /// The signer's signing policy refused the message
+    #[error("{0}")]
+    Policy(String),
+    /// The signer has no DKG output for the requested DKG round
+    #[error("missing DKG state")]
+    MissingDkgState,
+    /// The request itself was malformed, e.g. carried bad nonce responses
+    #[error("bad request: {0}")]
+    BadRequest(String),
+}
+
+impl Signable for RejectReason {
+    fn hash(&self, hasher: &mut Sha256) {
+        match self {
+            RejectReason::Policy(reason) => {
+                hasher.update("POLICY".as_bytes());
+                hasher.update(reason.as_bytes());
+            }
+            RejectReason::MissingDkgState => hasher.update("MISSING_DKG_STATE".as_bytes()),
+            RejectReason::BadRequest(reason) => {
+                hasher.update("BAD_REQUEST".as_bytes());
+                hasher.update(reason.as_bytes());
+            }
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// Signing round rejection message from signers to coordinator, sent in reply to a NonceRequest
+/// or SignatureShareRequest the signer won't serve, so the coordinator needn't wait for a timeout
+pub struct SignatureShareReject {
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// Signing round ID
+    pub sign_id: u64,
+    /// Signing round iteration ID
+    pub sign_iter_id: u64,
+    /// Signer ID
+    pub signer_id: u32,
+    /// Why the signer refused to sign
+    pub reason: RejectReason,
+}
+
+impl Signable for SignatureShareReject {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("SIGNATURE_SHARE_REJECT".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        hasher.update(self.sign_id.to_be_bytes());
+        hasher.update(self.sign_iter_id.to_be_bytes());
+        hasher.update(self.signer_id.to_be_bytes());
+        self.reason.hash(hasher);
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 /// Network packets need to be signed so they can be verified
 pub struct Packet {
     /// The message to sign
     pub msg: Message,
+    /// The sender's sequence number, which increases with every packet it signs
+    #[serde(default)]
+    pub seq: u64,
     /// The bytes of the signature
     pub sig: Vec<u8>,
 }
+
+impl Signable for Packet {
+    fn hash(&self, hasher: &mut Sha256) {
+        let tag = self.msg.message_type().tag();
+        hasher.update("PACKET".as_bytes());
+        hasher.update((tag.len() as u32).to_be_bytes());
+        hasher.update(tag.as_bytes());
+        hasher.update(self.seq.to_be_bytes());
+        self.msg.hash(hasher);
+    }
+}
+
+impl Packet {
+    /// Sign `msg` along with its type and the sender's sequence number `seq`, so the packet can't
+    /// be replayed, or passed off as a different type of message with the same contents
+    pub fn new(
+        msg: Message,
+        seq: u64,
+        domain: &[u8],
+        private_key: &Scalar,
+    ) -> Result<Self, ecdsa::Error> {
+        let mut packet = Packet {
+            msg,
+            seq,
+            sig: vec![],
+        };
+        packet.sig = packet.sign_with_domain(domain, private_key)?;
+        Ok(packet)
+    }
+}
+
+/// A starting sequence number for packet signers, taken from the clock so that it keeps
+/// increasing across restarts
+pub fn initial_seq() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+/// How many sequence numbers behind the highest one accepted from a peer a ReplayGuard still
+/// tracks, so packets which arrive out of order within it are accepted
+pub const REPLAY_WINDOW: u64 = 64;
+
+#[derive(Clone, Copy, Debug)]
+/// The highest sequence number accepted from a peer, and which of the ones before it were too
+struct SeqWindow {
+    /// the highest sequence number accepted
+    highest: u64,
+    /// bit `i` is set if `highest - i` was accepted
+    seen: u64,
+}
+
+impl SeqWindow {
+    fn new(seq: u64) -> Self {
+        Self {
+            highest: seq,
+            seen: 1,
+        }
+    }
+
+    /// Accept `seq` unless it was already accepted or is too old to tell
+    fn accept(&mut self, seq: u64) -> bool {
+        if seq > self.highest {
+            let shift = seq - self.highest;
+            self.seen = if shift < REPLAY_WINDOW {
+                self.seen << shift
+            } else {
+                0
+            };
+            self.seen |= 1;
+            self.highest = seq;
+            return true;
+        }
+        let offset = self.highest - seq;
+        if offset >= REPLAY_WINDOW || self.seen & (1 << offset) != 0 {
+            return false;
+        }
+        self.seen |= 1 << offset;
+        true
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// Tracks the sequence numbers accepted from each peer, so replayed packets are rejected.  Packets
+/// may arrive out of order, as long as they're within REPLAY_WINDOW of the highest one accepted
+pub struct ReplayGuard {
+    windows: HashMap<u32, SeqWindow>,
+}
+
+impl ReplayGuard {
+    /// Construct a ReplayGuard which hasn't seen any packets
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check that `packet` was signed by `sender_id`'s `public_key` under `domain`, and that its
+    /// sequence number hasn't been accepted from `sender_id` before; if so, remember it
+    pub fn check(
+        &mut self,
+        sender_id: u32,
+        packet: &Packet,
+        domain: &[u8],
+        public_key: &ecdsa::PublicKey,
+    ) -> Result<(), PacketError> {
+        if !packet.verify_with_domain(domain, &packet.sig, public_key) {
+            return Err(PacketError::BadSignature(sender_id));
+        }
+        match self.windows.get_mut(&sender_id) {
+            Some(window) => {
+                if !window.accept(packet.seq) {
+                    return Err(PacketError::Replayed(sender_id, packet.seq, window.highest));
+                }
+            }
+            None => {
+                self.windows.insert(sender_id, SeqWindow::new(packet.seq));
+            }
+        }
+        Ok(())
+    }
+
+    /// The highest sequence number accepted from `sender_id`
+    pub fn last_seq(&self, sender_id: u32) -> Option<u64> {
+        self.windows.get(&sender_id).map(|window| window.highest)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// A packet tagged with its sender, so receivers can acknowledge it by its signed sequence number
+/// and drop retransmissions
+pub struct SequencedPacket {
+    /// The ID of the sender, which numbers its packets independently of everyone else
+    pub sender_id: u32,
+    /// The packet
+    pub packet: Packet,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// Acknowledgement that a packet was received
+pub struct Ack {
+    /// The ID of the packet's sender
+    pub sender_id: u32,
+    /// The ID of the receiver which is acknowledging the packet
+    pub receiver_id: u32,
+    /// The sequence number of the packet
+    pub seq: u64,
+}
+
+impl Signable for Ack {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("ACK".as_bytes());
+        hasher.update(self.sender_id.to_be_bytes());
+        hasher.update(self.receiver_id.to_be_bytes());
+        hasher.update(self.seq.to_be_bytes());
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+/// How often and for how long unacknowledged packets are retransmitted
+pub struct RetransmitConfig {
+    /// How long to wait for an ack before the first retransmission
+    pub initial_timeout: Duration,
+    /// The longest wait between retransmissions, which the timeout doubles up to
+    pub max_timeout: Duration,
+    /// How many times a packet is sent before giving up on it
+    pub max_attempts: u32,
+}
+
+impl Default for RetransmitConfig {
+    fn default() -> Self {
+        Self {
+            initial_timeout: Duration::from_millis(500),
+            max_timeout: Duration::from_secs(30),
+            max_attempts: 8,
+        }
+    }
+}
+
+/// A sent packet which some of its destinations haven't acknowledged yet
+struct Unacked {
+    packet: SequencedPacket,
+    destinations: HashSet<u32>,
+    attempts: u32,
+    timeout: Duration,
+    next_retry: Instant,
+}
+
+/// The sending side of an optional reliability layer for lossy transports, which retransmits
+/// outbound packets with exponential backoff until every destination acknowledges them
+pub struct ReliableSender {
+    sender_id: u32,
+    config: RetransmitConfig,
+    unacked: BTreeMap<u64, Unacked>,
+}
+
+impl ReliableSender {
+    /// Construct a ReliableSender for `sender_id`
+    pub fn new(sender_id: u32, config: RetransmitConfig) -> Self {
+        Self {
+            sender_id,
+            config,
+            unacked: BTreeMap::new(),
+        }
+    }
+
+    /// Tag `packet` so it can be sent to `destinations`, and keep it until they all ack it.  Acks
+    /// refer to the packet's sequence number, which must be unique among this sender's packets, as
+    /// it is for packets signed by the state machines.
+    pub fn send(&mut self, packet: Packet, destinations: &[u32], now: Instant) -> SequencedPacket {
+        let packet = SequencedPacket {
+            sender_id: self.sender_id,
+            packet,
+        };
+
+        if !destinations.is_empty() {
+            self.unacked.insert(
+                packet.packet.seq,
+                Unacked {
+                    packet: packet.clone(),
+                    destinations: destinations.iter().copied().collect(),
+                    attempts: 1,
+                    timeout: self.config.initial_timeout,
+                    next_retry: now + self.config.initial_timeout,
+                },
+            );
+        }
+
+        packet
+    }
+
+    /// Stop retransmitting the acknowledged packet to the acknowledging receiver
+    pub fn ack(&mut self, ack: &Ack) {
+        if ack.sender_id != self.sender_id {
+            return;
+        }
+        if let Some(unacked) = self.unacked.get_mut(&ack.seq) {
+            unacked.destinations.remove(&ack.receiver_id);
+            if unacked.destinations.is_empty() {
+                self.unacked.remove(&ack.seq);
+            }
+        }
+    }
+
+    /// Get the packets whose acks are overdue at `now`, along with the destinations which need
+    /// them again; packets which have been sent `max_attempts` times are dropped instead
+    pub fn retransmit(&mut self, now: Instant) -> Vec<(SequencedPacket, Vec<u32>)> {
+        let mut packets = Vec::new();
+        let mut expired = Vec::new();
+
+        for (seq, unacked) in self.unacked.iter_mut() {
+            if unacked.next_retry > now {
+                continue;
+            }
+            if unacked.attempts >= self.config.max_attempts {
+                expired.push(*seq);
+                continue;
+            }
+
+            unacked.attempts += 1;
+            unacked.timeout = (unacked.timeout * 2).min(self.config.max_timeout);
+            unacked.next_retry = now + unacked.timeout;
+
+            let mut destinations: Vec<u32> = unacked.destinations.iter().copied().collect();
+            destinations.sort();
+            packets.push((unacked.packet.clone(), destinations));
+        }
+
+        for seq in expired {
+            if let Some(unacked) = self.unacked.remove(&seq) {
+                warn!(
+                    "Sender {} giving up on packet {} after {} attempts, no ack from {:?}",
+                    self.sender_id, seq, unacked.attempts, unacked.destinations
+                );
+            }
+        }
+
+        packets
+    }
+
+    /// The number of packets which haven't been acknowledged by all of their destinations
+    pub fn pending(&self) -> usize {
+        self.unacked.len()
+    }
+}
+
+/// The receiving side of an optional reliability layer for lossy transports, which acknowledges
+/// every packet and drops retransmissions of ones it already received
+pub struct ReliableReceiver {
+    receiver_id: u32,
+    /// map of sender_id to the sequence numbers received from that sender
+    received: HashMap<u32, SeqWindow>,
+}
+
+impl ReliableReceiver {
+    /// Construct a ReliableReceiver for `receiver_id`
+    pub fn new(receiver_id: u32) -> Self {
+        Self {
+            receiver_id,
+            received: HashMap::new(),
+        }
+    }
+
+    /// Acknowledge `packet`, returning the packet inside it unless it was already received.  Only
+    /// the last REPLAY_WINDOW sequence numbers from each sender are remembered, so a packet which
+    /// arrives after that many newer ones is dropped too.
+    pub fn receive(&mut self, packet: SequencedPacket) -> (Ack, Option<Packet>) {
+        let seq = packet.packet.seq;
+        let ack = Ack {
+            sender_id: packet.sender_id,
+            receiver_id: self.receiver_id,
+            seq,
+        };
+
+        let is_new = match self.received.get_mut(&packet.sender_id) {
+            Some(window) => window.accept(seq),
+            None => {
+                self.received.insert(packet.sender_id, SeqWindow::new(seq));
+                true
+            }
+        };
+
+        (ack, is_new.then_some(packet.packet))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+/// One part of a packet which was too big for the transport, e.g. DKG shares for many keys
+pub struct Fragment {
+    /// The hash of the whole serialized packet, which identifies the fragments that belong together
+    pub message_hash: [u8; 32],
+    /// The index of this fragment
+    pub index: u32,
+    /// The total number of fragments
+    pub count: u32,
+    /// This fragment's part of the serialized packet
+    pub data: Vec<u8>,
+    /// The hash of `data`, so a corrupted fragment is caught on its own
+    pub data_hash: [u8; 32],
+}
+
+impl Signable for Fragment {
+    fn hash(&self, hasher: &mut Sha256) {
+        hasher.update("FRAGMENT".as_bytes());
+        hasher.update(self.message_hash);
+        hasher.update(self.index.to_be_bytes());
+        hasher.update(self.count.to_be_bytes());
+        hasher.update(self.data_hash);
+    }
+}
+
+impl Fragment {
+    /// Split `packet` into fragments which each hold at most `max_size` bytes of it
+    pub fn split(packet: &Packet, max_size: usize) -> Result<Vec<Fragment>, FragmentError> {
+        if max_size == 0 {
+            return Err(FragmentError::BadFragmentSize(max_size));
+        }
+        let bytes =
+            serde_json::to_vec(packet).map_err(|e| FragmentError::Serialization(e.to_string()))?;
+        let message_hash: [u8; 32] = Sha256::digest(&bytes).into();
+        let chunks: Vec<&[u8]> = bytes.chunks(max_size).collect();
+        let count =
+            u32::try_from(chunks.len()).map_err(|_| FragmentError::BadFragmentSize(max_size))?;
+
+        Ok(chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, data)| Fragment {
+                message_hash,
+                index: index as u32,
+                count,
+                data: data.to_vec(),
+                data_hash: Sha256::digest(data).into(),
+            })
+            .collect())
+    }
+}
+
+/// The fragments of one packet received so far
+#[derive(Default)]
+struct PendingPacket {
+    fragments: BTreeMap<u32, Fragment>,
+    /// the total size of the fragments' data
+    bytes: usize,
+}
+
+/// Collects fragments until all the parts of a packet are in, then rebuilds the packet
+pub struct Reassembler {
+    max_pending: usize,
+    max_fragment_size: usize,
+    max_bytes: usize,
+    /// the total size of the data in every pending fragment
+    buffered: usize,
+    /// map of message hash to the fragments received so far
+    pending: HashMap<[u8; 32], PendingPacket>,
+    /// message hashes in the order their first fragment arrived, so the oldest can be dropped
+    order: VecDeque<[u8; 32]>,
+}
+
+impl Reassembler {
+    /// Construct a Reassembler which holds the fragments of at most `max_pending` packets at once,
+    /// dropping the oldest incomplete packet to make room, and accepts fragments of at most
+    /// `max_fragment_size` bytes.  At most MAX_FRAME_LEN bytes are buffered across all packets.
+    pub fn new(max_pending: usize, max_fragment_size: usize) -> Self {
+        Self {
+            max_pending,
+            max_fragment_size,
+            max_bytes: MAX_FRAME_LEN,
+            buffered: 0,
+            pending: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Buffer at most `max_bytes` of fragment data across all packets, which also bounds the size
+    /// of any one packet
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// The largest packet this reassembler rebuilds
+    fn max_packet_len(&self) -> usize {
+        self.max_bytes.min(MAX_FRAME_LEN)
+    }
+
+    /// Drop the fragments of the packet with `message_hash`
+    fn remove(&mut self, message_hash: &[u8; 32]) -> Option<PendingPacket> {
+        let packet = self.pending.remove(message_hash)?;
+        self.buffered -= packet.bytes;
+        self.order.retain(|hash| hash != message_hash);
+        Some(packet)
+    }
+
+    /// Check and add `fragment`, returning the packet once all of its fragments are in
+    pub fn add(&mut self, fragment: Fragment) -> Result<Option<Packet>, FragmentError> {
+        if fragment.index >= fragment.count {
+            return Err(FragmentError::BadFragmentIndex(
+                fragment.index,
+                fragment.count,
+            ));
+        }
+        // the count comes from the peer, so only allow as many fragments as the largest packet
+        // needs, and only fragments as large as this reassembler was built for
+        let max_count = u32::try_from(
+            self.max_packet_len()
+                .div_ceil(self.max_fragment_size.max(1)),
+        )
+        .unwrap_or(u32::MAX);
+        if fragment.count > max_count {
+            return Err(FragmentError::TooManyFragments(fragment.count, max_count));
+        }
+        if fragment.data.len() > self.max_fragment_size {
+            return Err(FragmentError::BadFragmentSize(fragment.data.len()));
+        }
+        let data_hash: [u8; 32] = Sha256::digest(&fragment.data).into();
+        if data_hash != fragment.data_hash {
+            return Err(FragmentError::BadFragmentHash(fragment.index));
+        }
+
+        let message_hash = fragment.message_hash;
+        if let Some(packet) = self.pending.get(&message_hash) {
+            if let Some((_, first)) = packet.fragments.first_key_value() {
+                if first.count != fragment.count {
+                    return Err(FragmentError::BadFragmentIndex(
+                        fragment.index,
+                        fragment.count,
+                    ));
+                }
+            }
+            if packet.fragments.contains_key(&fragment.index) {
+                return Ok(None);
+            }
+            let bytes = packet.bytes + fragment.data.len();
+            if bytes > self.max_packet_len() {
+                self.remove(&message_hash);
+                return Err(FragmentError::TooLarge(bytes));
+            }
+        } else {
+            while self.order.len() >= self.max_pending.max(1) {
+                let Some(oldest) = self.order.front().copied() else {
+                    break;
+                };
+                self.remove(&oldest);
+            }
+            self.order.push_back(message_hash);
+            self.pending.insert(message_hash, PendingPacket::default());
+        }
+
+        // make room by dropping the oldest other incomplete packets
+        while self.buffered + fragment.data.len() > self.max_bytes {
+            let Some(oldest) = self
+                .order
+                .iter()
+                .find(|hash| **hash != message_hash)
+                .copied()
+            else {
+                break;
+            };
+            self.remove(&oldest);
+        }
+
+        let count = fragment.count;
+        let Some(packet) = self.pending.get_mut(&message_hash) else {
+            return Ok(None);
+        };
+        packet.bytes += fragment.data.len();
+        self.buffered += fragment.data.len();
+        packet.fragments.insert(fragment.index, fragment);
+        if packet.fragments.len() < count as usize {
+            return Ok(None);
+        }
+
+        let fragments = self
+            .remove(&message_hash)
+            .map(|packet| packet.fragments)
+            .unwrap_or_default();
+        let bytes: Vec<u8> = fragments
+            .into_values()
+            .flat_map(|fragment| fragment.data)
+            .collect();
+        let hash: [u8; 32] = Sha256::digest(&bytes).into();
+        if hash != message_hash {
+            return Err(FragmentError::BadMessageHash);
+        }
+
+        serde_json::from_slice(&bytes)
+            .map(Some)
+            .map_err(|e| FragmentError::Serialization(e.to_string()))
+    }
+}
+
+/// Header flag which marks the payload of an encoded packet as zstd compressed
+pub const COMPRESSED_FLAG: u8 = 0x01;
+
+/// The zstd level used for compressed payloads
+#[cfg(feature = "zstd")]
+pub const COMPRESSION_LEVEL: i32 = 3;
+
+/// Decompress a zstd `payload`, failing rather than expanding it past `limit` bytes, since a few
+/// KB of compressed input can expand to gigabytes
+#[cfg(feature = "zstd")]
+fn decompress(payload: &[u8], limit: usize) -> Result<Vec<u8>, EncodingError> {
+    let decoder = zstd::stream::read::Decoder::new(payload)
+        .map_err(|e| EncodingError::Compression(e.to_string()))?;
+    let mut decompressed = Vec::new();
+    decoder
+        .take(limit as u64 + 1)
+        .read_to_end(&mut decompressed)
+        .map_err(|e| EncodingError::Compression(e.to_string()))?;
+    if decompressed.len() > limit {
+        return Err(EncodingError::FrameTooLarge(decompressed.len()));
+    }
+    Ok(decompressed)
+}
+
+/// The longest frame `Packet::read_frame` accepts, and the most a compressed payload may expand
+/// to in `Packet::decode`, which bounds what a peer can make a reader allocate
+pub const MAX_FRAME_LEN: usize = 64 * 1024 * 1024;
+
+impl Packet {
+    /// Whether this packet carries DKG shares, whose polynomial commitments and private shares
+    /// grow with the number of keys and dominate DKG bandwidth
+    pub fn is_compressible(&self) -> bool {
+        matches!(
+            self.msg,
+            Message::DkgPublicShares(_) | Message::DkgPrivateShares(_)
+        )
+    }
+
+    /// Encode this packet as a header byte followed by the serialized packet.  If `compress` is set,
+    /// the crate was built with the zstd feature, and the packet carries DKG shares, then the payload
+    /// is compressed and `COMPRESSED_FLAG` is set in the header, so receivers can always tell how to
+    /// decode it
+    pub fn encode(&self, compress: bool) -> Result<Vec<u8>, EncodingError> {
+        let payload =
+            serde_json::to_vec(self).map_err(|e| EncodingError::Serialization(e.to_string()))?;
+
+        #[cfg(feature = "zstd")]
+        if compress && self.is_compressible() {
+            let compressed = zstd::encode_all(payload.as_slice(), COMPRESSION_LEVEL)
+                .map_err(|e| EncodingError::Compression(e.to_string()))?;
+            let mut bytes = vec![COMPRESSED_FLAG];
+            bytes.extend(compressed);
+            return Ok(bytes);
+        }
+        #[cfg(not(feature = "zstd"))]
+        let _ = compress;
+
+        let mut bytes = vec![0];
+        bytes.extend(payload);
+        Ok(bytes)
+    }
+
+    /// Decode a packet which was encoded by `Packet::encode`
+    pub fn decode(bytes: &[u8]) -> Result<Packet, EncodingError> {
+        let (header, payload) = bytes.split_first().ok_or(EncodingError::MissingHeader)?;
+        match *header {
+            0 => serde_json::from_slice(payload)
+                .map_err(|e| EncodingError::Serialization(e.to_string())),
+            #[cfg(feature = "zstd")]
+            COMPRESSED_FLAG => {
+                let payload = decompress(payload, MAX_FRAME_LEN)?;
+                serde_json::from_slice(&payload)
+                    .map_err(|e| EncodingError::Serialization(e.to_string()))
+            }
+            flags => Err(EncodingError::UnsupportedFlags(flags)),
+        }
+    }
+
+    /// Write this packet to a byte stream such as a TCP connection, as the length of the encoded
+    /// packet in 4 big-endian bytes followed by the encoded packet
+    pub fn write_frame<W: Write>(
+        &self,
+        writer: &mut W,
+        compress: bool,
+    ) -> Result<(), EncodingError> {
+        let bytes = self.encode(compress)?;
+        if bytes.len() > MAX_FRAME_LEN {
+            return Err(EncodingError::FrameTooLarge(bytes.len()));
+        }
+        writer
+            .write_all(&(bytes.len() as u32).to_be_bytes())
+            .and_then(|_| writer.write_all(&bytes))
+            .and_then(|_| writer.flush())
+            .map_err(|e| EncodingError::Io(e.to_string()))
+    }
+
+    /// Read a packet written by `Packet::write_frame` from a byte stream
+    pub fn read_frame<R: Read>(reader: &mut R) -> Result<Packet, EncodingError> {
+        let mut len = [0; 4];
+        reader
+            .read_exact(&mut len)
+            .map_err(|e| EncodingError::Io(e.to_string()))?;
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_FRAME_LEN {
+            return Err(EncodingError::FrameTooLarge(len));
+        }
+        let mut bytes = vec![0; len];
+        reader
+            .read_exact(&mut bytes)
+            .map_err(|e| EncodingError::Io(e.to_string()))?;
+        Packet::decode(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use p256k1::{ecdsa, scalar::Scalar};
+    use rand_core::OsRng;
+    use sha2::{Digest, Sha256};
+    use std::{
+        collections::BTreeMap,
+        time::{Duration, Instant},
+    };
+
+    use super::{
+        DkgBegin, DkgEnd, DkgFailure, DkgPartyError, DkgPrivateBegin, DkgPrivateShares, DkgStatus,
+        Fragment, Message, MessageType, NonceResponse, Packet, Reassembler, ReliableReceiver,
+        ReliableSender, ReplayGuard, RetransmitConfig, Signable, MAX_FRAME_LEN, REPLAY_WINDOW,
+    };
+    use crate::{
+        common::{KeyIdRanges, MAX_KEY_IDS},
+        errors::{DkgError, EncodingError, FragmentError, PacketError},
+    };
+    use hashbrown::HashMap;
+
+    #[test]
+    fn domain_separation() {
+        let mut rng = OsRng;
+        let private_key = Scalar::random(&mut rng);
+        let public_key = ecdsa::PublicKey::new(&private_key).unwrap();
+        let dkg_begin = DkgBegin {
+            dkg_id: 0,
+            epoch: 0,
+            threshold: 0,
+            num_signers: 0,
+            num_keys: 0,
+            pedersen: false,
+        };
+
+        let sig = dkg_begin.sign(&private_key).unwrap();
+        assert!(dkg_begin.verify(&sig, &public_key));
+        assert!(dkg_begin.verify_with_domain(&[], &sig, &public_key));
+        assert!(!dkg_begin.verify_with_domain(b"mainnet", &sig, &public_key));
+
+        let sig = dkg_begin
+            .sign_with_domain(b"testnet", &private_key)
+            .unwrap();
+        assert!(dkg_begin.verify_with_domain(b"testnet", &sig, &public_key));
+        assert!(!dkg_begin.verify_with_domain(b"mainnet", &sig, &public_key));
+        assert!(!dkg_begin.verify(&sig, &public_key));
+    }
+
+    #[test]
+    fn retransmit() {
+        let packet = |seq| Packet {
+            msg: Message::DkgBegin(DkgBegin {
+                dkg_id: 0,
+                epoch: 0,
+                threshold: 0,
+                num_signers: 0,
+                num_keys: 0,
+                pedersen: false,
+            }),
+            seq,
+            sig: vec![],
+        };
+        let config = RetransmitConfig {
+            initial_timeout: Duration::from_secs(1),
+            max_timeout: Duration::from_secs(3),
+            max_attempts: 4,
+        };
+        let mut sender = ReliableSender::new(7, config);
+        let start = Instant::now();
+
+        let first = sender.send(packet(100), &[1, 2], start);
+        let second = sender.send(packet(101), &[1], start);
+        assert_eq!(sender.pending(), 2);
+
+        // nothing is retransmitted before the timeout
+        assert!(sender.retransmit(start).is_empty());
+
+        // only the destinations which haven't acked get the packet again
+        let mut receiver = ReliableReceiver::new(1);
+        let (ack, _) = receiver.receive(first.clone());
+        sender.ack(&ack);
+        let (ack, _) = receiver.receive(second);
+        sender.ack(&ack);
+        assert_eq!(sender.pending(), 1);
+
+        let now = start + Duration::from_secs(1);
+        match &sender.retransmit(now)[..] {
+            [(packet, destinations)] => {
+                assert_eq!(packet.packet.seq, 100);
+                assert_eq!(destinations, &[2]);
+            }
+            packets => panic!("Unexpected retransmissions {:?}", packets),
+        }
+
+        // the timeout doubles up to the max, then the packet is dropped
+        assert!(sender
+            .retransmit(now + Duration::from_millis(1999))
+            .is_empty());
+        let now = now + Duration::from_secs(2);
+        assert_eq!(sender.retransmit(now).len(), 1);
+        let now = now + Duration::from_secs(3);
+        assert_eq!(sender.retransmit(now).len(), 1);
+        let now = now + Duration::from_secs(3);
+        assert!(sender.retransmit(now).is_empty());
+        assert_eq!(sender.pending(), 0);
+
+        // receivers ack retransmissions but only pass each packet through once
+        let (ack, msg) = receiver.receive(first.clone());
+        assert_eq!(ack.seq, 100);
+        assert!(msg.is_none());
+        let third = sender.send(packet(102), &[1], now);
+        let fourth = sender.send(packet(103), &[1], now);
+        assert!(receiver.receive(fourth.clone()).1.is_some());
+        assert!(receiver.receive(fourth).1.is_none());
+        assert!(receiver.receive(third.clone()).1.is_some());
+        assert!(receiver.receive(third).1.is_none());
+
+        // a gap the sender gave up on doesn't make the receiver keep every later sequence number;
+        // only the window behind the newest one is remembered
+        for seq in 105..105 + 4 * REPLAY_WINDOW {
+            assert!(receiver
+                .receive(sender.send(packet(seq), &[], now))
+                .1
+                .is_some());
+        }
+        assert_eq!(receiver.received[&7].highest, 104 + 4 * REPLAY_WINDOW);
+        assert!(receiver
+            .receive(sender.send(packet(104), &[], now))
+            .1
+            .is_none());
+    }
+
+    #[test]
+    fn fragments() {
+        let shares = (0..64)
+            .map(|signer_id| (signer_id, vec![signer_id as u8; 64]))
+            .collect();
+        let packet = Packet {
+            msg: Message::DkgPrivateShares(DkgPrivateShares {
+                dkg_id: 1,
+                signer_id: 2,
+                src_party_ids: vec![2],
+                shares,
+                verifiable_shares: Vec::new(),
+            }),
+            seq: 0,
+            sig: vec![3; 64],
+        };
+        let bytes = serde_json::to_vec(&packet).unwrap();
+
+        let fragments = Fragment::split(&packet, 1000).unwrap();
+        assert_eq!(fragments.len(), bytes.len().div_ceil(1000));
+        assert!(fragments.iter().all(|fragment| fragment.data.len() <= 1000));
+
+        // fragments can arrive in any order
+        let mut reassembler = Reassembler::new(4, 1000);
+        let (last, rest) = fragments.split_first().unwrap();
+        for fragment in rest.iter().rev() {
+            assert!(reassembler.add(fragment.clone()).unwrap().is_none());
+        }
+        let reassembled = reassembler.add(last.clone()).unwrap().unwrap();
+        assert_eq!(serde_json::to_vec(&reassembled).unwrap(), bytes);
+
+        // a corrupted fragment is caught on its own
+        let mut bad = fragments[1].clone();
+        bad.data[0] ^= 1;
+        assert!(matches!(
+            reassembler.add(bad),
+            Err(FragmentError::BadFragmentHash(1))
+        ));
+
+        // as is a fragment which was swapped for a valid one from another packet
+        let mut other = fragments.clone();
+        other[1].data = other[1].data.iter().rev().copied().collect();
+        other[1].data_hash = Sha256::digest(&other[1].data).into();
+        let mut result = Ok(None);
+        for fragment in other {
+            result = reassembler.add(fragment);
+        }
+        assert!(matches!(result, Err(FragmentError::BadMessageHash)));
+
+        assert!(matches!(
+            Fragment::split(&packet, 0),
+            Err(FragmentError::BadFragmentSize(0))
+        ));
+
+        // the fragment count and size a peer claims are bounded
+        let mut huge = fragments[0].clone();
+        huge.count = u32::MAX;
+        assert!(matches!(
+            reassembler.add(huge),
+            Err(FragmentError::TooManyFragments(u32::MAX, max))
+                if max as usize == MAX_FRAME_LEN / 1000 + 1
+        ));
+        let mut small = Reassembler::new(4, 100);
+        assert!(matches!(
+            small.add(fragments[0].clone()),
+            Err(FragmentError::BadFragmentSize(1000))
+        ));
+
+        // as are the bytes buffered for one packet and across all of them
+        let mut small = Reassembler::new(4, 1000).with_max_bytes(2500);
+        assert!(matches!(
+            small.add(fragments[0].clone()),
+            Err(FragmentError::TooManyFragments(_, 3))
+        ));
+        let fragment = |index| Fragment {
+            message_hash: [9; 32],
+            index,
+            count: 3,
+            data: vec![0; 1000],
+            data_hash: Sha256::digest(vec![0; 1000]).into(),
+        };
+        assert!(small.add(fragment(0)).unwrap().is_none());
+        assert!(small.add(fragment(1)).unwrap().is_none());
+        assert!(matches!(
+            small.add(fragment(2)),
+            Err(FragmentError::TooLarge(3000))
+        ));
+        assert_eq!(small.buffered, 0);
+
+        let other = Packet {
+            seq: 1,
+            ..packet.clone()
+        };
+        let other_fragments = Fragment::split(&other, 1000).unwrap();
+        let mut small = Reassembler::new(4, 1000).with_max_bytes(bytes.len());
+        for fragment in &other_fragments[1..] {
+            assert!(small.add(fragment.clone()).unwrap().is_none());
+        }
+        let (last, rest) = fragments.split_first().unwrap();
+        for fragment in rest {
+            assert!(small.add(fragment.clone()).unwrap().is_none());
+        }
+        let reassembled = small.add(last.clone()).unwrap().unwrap();
+        assert_eq!(serde_json::to_vec(&reassembled).unwrap(), bytes);
+        assert!(small.buffered <= bytes.len());
+    }
+
+    #[test]
+    fn encode() {
+        let shares = (0..64).map(|signer_id| (signer_id, vec![0; 64])).collect();
+        let packet = Packet {
+            msg: Message::DkgPrivateShares(DkgPrivateShares {
+                dkg_id: 1,
+                signer_id: 2,
+                src_party_ids: vec![2],
+                shares,
+                verifiable_shares: Vec::new(),
+            }),
+            seq: 0,
+            sig: vec![3; 64],
+        };
+        let bytes = serde_json::to_vec(&packet).unwrap();
+
+        let plain = packet.encode(false).unwrap();
+        assert_eq!(plain[0], 0);
+        assert_eq!(
+            serde_json::to_vec(&Packet::decode(&plain).unwrap()).unwrap(),
+            bytes
+        );
+
+        let compressed = packet.encode(true).unwrap();
+        #[cfg(feature = "zstd")]
+        {
+            assert_eq!(compressed[0], super::COMPRESSED_FLAG);
+            assert!(compressed.len() < plain.len());
+        }
+        #[cfg(not(feature = "zstd"))]
+        assert_eq!(compressed, plain);
+        #[cfg(feature = "zstd")]
+        {
+            // a small payload which expands past the limit is rejected before it all expands
+            let bomb = zstd::encode_all(vec![0; 1 << 20].as_slice(), 3).unwrap();
+            assert!(bomb.len() < 1024);
+            assert!(matches!(
+                super::decompress(&bomb, 1 << 16),
+                Err(EncodingError::FrameTooLarge(n)) if n == (1 << 16) + 1
+            ));
+            assert_eq!(super::decompress(&bomb, 1 << 20).unwrap().len(), 1 << 20);
+        }
+        assert_eq!(
+            serde_json::to_vec(&Packet::decode(&compressed).unwrap()).unwrap(),
+            bytes
+        );
+
+        // only DKG shares are compressed
+        let packet = Packet {
+            msg: Message::DkgBegin(DkgBegin {
+                dkg_id: 0,
+                epoch: 0,
+                threshold: 0,
+                num_signers: 0,
+                num_keys: 0,
+                pedersen: false,
+            }),
+            seq: 0,
+            sig: vec![],
+        };
+        assert_eq!(packet.encode(true).unwrap()[0], 0);
+
+        // frames carry their length, so several can be read back from one stream
+        let mut stream = Vec::new();
+        packet.write_frame(&mut stream, false).unwrap();
+        packet.write_frame(&mut stream, false).unwrap();
+        let mut reader = stream.as_slice();
+        for _ in 0..2 {
+            let read = Packet::read_frame(&mut reader).unwrap();
+            assert_eq!(read.encode(false).unwrap(), packet.encode(false).unwrap());
+        }
+        assert!(matches!(
+            Packet::read_frame(&mut reader),
+            Err(EncodingError::Io(_))
+        ));
+        let too_long = ((super::MAX_FRAME_LEN + 1) as u32).to_be_bytes();
+        assert!(matches!(
+            Packet::read_frame(&mut too_long.as_slice()),
+            Err(EncodingError::FrameTooLarge(_))
+        ));
+
+        assert!(matches!(
+            Packet::decode(&[]),
+            Err(EncodingError::MissingHeader)
+        ));
+        assert!(matches!(
+            Packet::decode(&[0x80]),
+            Err(EncodingError::UnsupportedFlags(0x80))
+        ));
+    }
+
+    #[test]
+    fn unknown_message() {
+        let bytes = br#"{"msg":{"DkgRetry":{"dkg_id":3,"reason":"timeout"}},"seq":1,"sig":[]}"#;
+        let mut encoded = vec![0];
+        encoded.extend_from_slice(bytes);
+        let packet = Packet::decode(&encoded).unwrap();
+        match &packet.msg {
+            Message::Unknown { type_id, bytes } => {
+                assert_eq!(type_id, "DkgRetry");
+                assert_eq!(bytes, br#"{"dkg_id":3,"reason":"timeout"}"#);
+            }
+            msg => panic!("expected an unknown message, got {msg:?}"),
+        }
+        assert_eq!(packet.msg.message_type(), MessageType::Unknown);
+        assert_eq!(packet.msg.id().signer_id, None);
+
+        // unknown messages are relayed as they arrived
+        assert_eq!(serde_json::to_vec(&packet).unwrap(), bytes);
+
+        // but known messages with bad contents are still rejected
+        let bad = br#"{"msg":{"DkgBegin":{"dkg_id":"three"}},"seq":1,"sig":[]}"#;
+        assert!(serde_json::from_slice::<Packet>(bad).is_err());
+        let ambiguous = br#"{"msg":{"DkgBegin":{"dkg_id":3},"DkgEnd":{}},"seq":1,"sig":[]}"#;
+        assert!(serde_json::from_slice::<Packet>(ambiguous).is_err());
+    }
+
+    #[test]
+    fn bincode_round_trip() {
+        let private_key = Scalar::random(&mut OsRng);
+        let public_key = ecdsa::PublicKey::new(&private_key).unwrap();
+        let msgs = [
+            Message::DkgBegin(DkgBegin {
+                dkg_id: 3,
+                threshold: 7,
+                num_signers: 5,
+                num_keys: 15,
+                pedersen: true,
+                epoch: 1,
+            }),
+            Message::NonceResponse(NonceResponse {
+                dkg_id: 1,
+                sign_id: 2,
+                sign_iter_id: 3,
+                signer_id: 4,
+                key_ids: (10_000..30_000).collect(),
+                nonces: vec![],
+            }),
+            Message::DkgEnd(DkgEnd {
+                dkg_id: 3,
+                signer_id: 2,
+                status: DkgStatus::Failure(DkgFailure::Timeout),
+            }),
+        ];
+        for msg in msgs {
+            let packet = Packet::new(msg, 9, b"testnet", &private_key).unwrap();
+            let bytes = bincode::serialize(&packet).unwrap();
+            let decoded: Packet = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(decoded.msg.message_type(), packet.msg.message_type());
+            assert_eq!(bincode::serialize(&decoded).unwrap(), bytes);
+            assert!(decoded.verify_with_domain(b"testnet", &decoded.sig, &public_key));
+
+            // JSON still tags messages with their names
+            let json = serde_json::to_string(&packet.msg).unwrap();
+            let tag = format!("{{{:?}:", format!("{:?}", packet.msg.message_type()));
+            assert!(json.starts_with(&tag), "{json}");
+        }
+
+        // a compact format can't skip over a message type it doesn't know
+        let mut unknown = bincode::serialize(&Message::DkgBegin(DkgBegin {
+            dkg_id: 0,
+            threshold: 0,
+            num_signers: 0,
+            num_keys: 0,
+            pedersen: false,
+            epoch: 0,
+        }))
+        .unwrap();
+        unknown[..4].copy_from_slice(&99u32.to_le_bytes());
+        assert!(bincode::deserialize::<Message>(&unknown).is_err());
+    }
+
+    #[test]
+    fn key_id_ranges() {
+        let ranges = [7, 8, 9, 3, 4, 11].into_iter().collect::<KeyIdRanges>();
+        assert_eq!(ranges.runs(), &[(7, 9), (3, 4), (11, 11)]);
+        assert_eq!(ranges.len(), 6);
+        assert!(ranges.contains(4) && !ranges.contains(5));
+        assert_eq!(ranges.to_vec(), vec![7, 8, 9, 3, 4, 11]);
+        let json = serde_json::to_string(&ranges).unwrap();
+        assert_eq!(serde_json::from_str::<KeyIdRanges>(&json).unwrap(), ranges);
+
+        // runs which are backwards, overlap, or expand to too many key IDs are rejected before
+        // anything is expanded
+        assert!(serde_json::from_str::<KeyIdRanges>("[[2,1]]").is_err());
+        assert!(serde_json::from_str::<KeyIdRanges>("[[7,9],[3,4],[9,10]]").is_err());
+        assert!(serde_json::from_str::<KeyIdRanges>("[[3,3],[3,3]]").is_err());
+        assert!(serde_json::from_str::<KeyIdRanges>(&format!("[[0,{}]]", u32::MAX)).is_err());
+        let too_many = format!("[[0,{}],[{},{}]]", MAX_KEY_IDS - 1, u32::MAX, u32::MAX);
+        assert!(serde_json::from_str::<KeyIdRanges>(&too_many).is_err());
+        let max = format!("[[0,{}]]", MAX_KEY_IDS - 1);
+        assert_eq!(
+            serde_json::from_str::<KeyIdRanges>(&max).unwrap().len(),
+            MAX_KEY_IDS
+        );
+
+        // a signer holding a contiguous block of keys sends one run, and the key IDs (and so
+        // the signature over them) survive the round trip
+        let private_key = Scalar::random(&mut OsRng);
+        let public_key = ecdsa::PublicKey::new(&private_key).unwrap();
+        let nonce_response = NonceResponse {
+            dkg_id: 1,
+            sign_id: 2,
+            sign_iter_id: 3,
+            signer_id: 4,
+            key_ids: (10_000..30_000).collect(),
+            nonces: vec![],
+        };
+        let sig = nonce_response.sign(&private_key).unwrap();
+        let json = serde_json::to_string(&nonce_response).unwrap();
+        assert!(json.contains(r#""key_ids":[[10000,29999]]"#));
+        let nonce_response: NonceResponse = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            nonce_response.key_ids,
+            (10_000..30_000).collect::<KeyIdRanges>()
+        );
+        assert!(nonce_response.verify(&sig, &public_key));
+    }
+
+    #[test]
+    fn replay_guard() {
+        let private_key = Scalar::random(&mut OsRng);
+        let public_key = ecdsa::PublicKey::new(&private_key).unwrap();
+        let domain = b"testnet";
+        let dkg_begin = DkgBegin {
+            dkg_id: 1,
+            epoch: 0,
+            threshold: 0,
+            num_signers: 0,
+            num_keys: 0,
+            pedersen: false,
+        };
+        let dkg_private_begin = DkgPrivateBegin {
+            dkg_id: 1,
+            signer_ids: vec![],
+        };
+        let packet = Packet::new(
+            Message::DkgBegin(dkg_begin.clone()),
+            5,
+            domain,
+            &private_key,
+        )
+        .unwrap();
+
+        let mut guard = ReplayGuard::new();
+        assert_eq!(guard.check(0, &packet, domain, &public_key), Ok(()));
+        assert_eq!(guard.last_seq(0), Some(5));
+
+        // the same packet is rejected, but an older one which arrives out of order isn't, once
+        assert_eq!(
+            guard.check(0, &packet, domain, &public_key),
+            Err(PacketError::Replayed(0, 5, 5))
+        );
+        let old = Packet::new(
+            Message::DkgBegin(dkg_begin.clone()),
+            4,
+            domain,
+            &private_key,
+        )
+        .unwrap();
+        assert_eq!(guard.check(0, &old, domain, &public_key), Ok(()));
+        assert_eq!(
+            guard.check(0, &old, domain, &public_key),
+            Err(PacketError::Replayed(0, 4, 5))
+        );
+        assert_eq!(guard.last_seq(0), Some(5));
+
+        // sequence numbers are tracked per peer
+        assert_eq!(guard.check(1, &old, domain, &public_key), Ok(()));
+
+        // the sequence number and message type are covered by the signature, so a DkgBegin can't
+        // be bumped to look new, or passed off as a DkgPrivateBegin for the same round
+        let mut bumped = packet.clone();
+        bumped.seq = 6;
+        assert_eq!(
+            guard.check(0, &bumped, domain, &public_key),
+            Err(PacketError::BadSignature(0))
+        );
+        let mut retyped = bumped.clone();
+        retyped.msg = Message::DkgPrivateBegin(dkg_private_begin.clone());
+        retyped.seq = packet.seq;
+        assert_eq!(
+            guard.check(2, &retyped, domain, &public_key),
+            Err(PacketError::BadSignature(2))
+        );
+
+        let new = Packet::new(
+            Message::DkgPrivateBegin(dkg_private_begin),
+            6,
+            domain,
+            &private_key,
+        )
+        .unwrap();
+        assert_eq!(guard.check(0, &new, domain, &public_key), Ok(()));
+        assert_eq!(
+            guard.check(0, &new, b"mainnet", &public_key),
+            Err(PacketError::BadSignature(0))
+        );
+    }
+
+    #[test]
+    fn replay_window() {
+        let mut rng = OsRng;
+        let private_key = Scalar::random(&mut rng);
+        let public_key = ecdsa::PublicKey::new(&private_key).unwrap();
+        let domain = b"testnet";
+        let packet = |seq| {
+            Packet::new(
+                Message::DkgBegin(DkgBegin {
+                    dkg_id: 1,
+                    epoch: 0,
+                    threshold: 0,
+                    num_signers: 0,
+                    num_keys: 0,
+                    pedersen: false,
+                }),
+                seq,
+                domain,
+                &private_key,
+            )
+            .unwrap()
+        };
+        let mut guard = ReplayGuard::new();
+        let mut check = |seq| guard.check(0, &packet(seq), domain, &public_key);
+
+        // reordered packets within the window are each accepted once
+        for seq in [100, 103, 101, 102, 110, 104] {
+            assert_eq!(check(seq), Ok(()));
+        }
+        for seq in [100, 101, 102, 103, 104, 110] {
+            assert_eq!(check(seq), Err(PacketError::Replayed(0, seq, 110)));
+        }
+
+        // the oldest sequence number still in the window is accepted, but one behind it isn't
+        let oldest = 110 - (REPLAY_WINDOW - 1);
+        assert_eq!(check(oldest), Ok(()));
+        assert_eq!(
+            check(oldest - 1),
+            Err(PacketError::Replayed(0, oldest - 1, 110))
+        );
+
+        // a jump past the whole window forgets everything before it
+        let far = 110 + 2 * REPLAY_WINDOW;
+        assert_eq!(check(far), Ok(()));
+        assert_eq!(check(110), Err(PacketError::Replayed(0, 110, far)));
+        assert_eq!(check(far - 1), Ok(()));
+        assert_eq!(check(far), Err(PacketError::Replayed(0, far, far)));
+    }
+
+    #[test]
+    fn dkg_failure() {
+        let errors = HashMap::from([
+            (0, DkgError::MissingShares(vec![3])),
+            (1, DkgError::BadShares(vec![2, 4])),
+            (2, DkgError::BadShares(vec![2])),
+        ]);
+        let failure = DkgFailure::from(&errors);
+        assert_eq!(
+            failure,
+            DkgFailure::Parties(BTreeMap::from([
+                (0, DkgPartyError::MissingShares(vec![3])),
+                (1, DkgPartyError::BadShares(vec![2, 4])),
+                (2, DkgPartyError::BadShares(vec![2])),
+            ]))
+        );
+        assert_eq!(failure.blamed_ids(), &[2, 4]);
+
+        // missing shares may not be the dealer's fault, so they blame nobody
+        let errors = HashMap::from([
+            (0, DkgError::MissingShares(vec![3])),
+            (1, DkgError::NotEnoughShares(vec![1])),
+        ]);
+        assert!(DkgFailure::from(&errors).blamed_ids().is_empty());
+
+        let errors = HashMap::from([(0, DkgError::BadIds(vec![5]))]);
+        assert_eq!(DkgFailure::from(&errors).blamed_ids(), &[5]);
+
+        // the failure is signed along with the rest of the DkgEnd
+        let mut rng = OsRng;
+        let private_key = Scalar::random(&mut rng);
+        let public_key = ecdsa::PublicKey::new(&private_key).unwrap();
+        let mut dkg_end = DkgEnd {
+            dkg_id: 1,
+            signer_id: 0,
+            status: DkgStatus::Failure(failure),
+        };
+        let sig = dkg_end.sign(&private_key).unwrap();
+        dkg_end.status = DkgStatus::Failure(DkgFailure::Parties(BTreeMap::from([(
+            1,
+            DkgPartyError::BadShares(vec![2]),
+        )])));
+        assert!(!dkg_end.verify(&sig, &public_key));
+        dkg_end.status = DkgStatus::Success;
+        assert!(!dkg_end.verify(&sig, &public_key));
+    }
+}
@@ -0,0 +1,194 @@
+use bitcoin::{
+    hashes::Hash,
+    psbt::Psbt,
+    secp256k1::schnorr,
+    sighash::{Prevouts, SighashCache, TapSighashType},
+    taproot, TxOut,
+};
+use p256k1::point::Point;
+use std::collections::VecDeque;
+use tracing::info;
+
+use crate::{
+    common::TaprootTweak,
+    compute,
+    net::Packet,
+    state_machine::{
+        coordinator::{Coordinatable, Error as CoordinatorError},
+        OperationResult,
+    },
+};
+
+#[derive(thiserror::Error, Debug)]
+/// Errors from signing the taproot key spends in a PSBT
+pub enum Error {
+    #[error("input {0} has no previous output to compute its sighash")]
+    /// An input's previous output, which every key spend's sighash commits to, was missing
+    MissingUtxo(usize),
+    #[error("input {0} has a sighash type which isn't valid for taproot")]
+    /// An input had a sighash type which taproot doesn't allow
+    BadSighashType(usize),
+    #[error("failed to compute the sighash for input {0}: {1}")]
+    /// The sighash couldn't be computed, e.g. SIGHASH_SINGLE without a matching output
+    Sighash(usize, String),
+    #[error("no key spend is being signed")]
+    /// A signing result arrived when no key spend was being signed
+    NotSigning,
+    #[error("expected a taproot signature, got {0:?}")]
+    /// A signing round finished with something other than a taproot signature
+    UnexpectedResult(Box<OperationResult>),
+    #[error("bad schnorr signature {0}")]
+    /// The signature couldn't be turned into a BIP-340 signature
+    BadSignature(String),
+    #[error("coordinator error {0}")]
+    /// A signing round couldn't be started
+    Coordinator(#[from] CoordinatorError),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A taproot key spend of the group key which still needs a signature
+pub struct KeySpend {
+    /// the index of the input in the PSBT
+    pub input_index: usize,
+    /// the BIP-341 sighash to sign
+    pub sighash: [u8; 32],
+    /// the sighash type to put in the signature
+    pub sighash_type: TapSighashType,
+    /// how the group key is tweaked into the output key being spent
+    pub tweak: TaprootTweak,
+}
+
+/// Find the inputs of `psbt` which spend a taproot output of `group_key` by key path and aren't
+/// signed yet, and compute their sighashes
+pub fn key_spends(psbt: &Psbt, group_key: &Point) -> Result<Vec<KeySpend>, Error> {
+    let prevouts = psbt
+        .inputs
+        .iter()
+        .zip(&psbt.unsigned_tx.input)
+        .map(
+            |(input, txin)| match (&input.witness_utxo, &input.non_witness_utxo) {
+                (Some(txout), _) => Some(txout.clone()),
+                (None, Some(tx)) => tx.output.get(txin.previous_output.vout as usize).cloned(),
+                (None, None) => None,
+            },
+        )
+        .collect::<Vec<Option<TxOut>>>();
+
+    let mut cache = SighashCache::new(&psbt.unsigned_tx);
+    let mut spends = Vec::new();
+    for (input_index, input) in psbt.inputs.iter().enumerate() {
+        let Some(prevout) = &prevouts[input_index] else {
+            continue;
+        };
+        if input.tap_key_sig.is_some() || !prevout.script_pubkey.is_p2tr() {
+            continue;
+        }
+        let tweak = TaprootTweak::from(input.tap_merkle_root.map(|root| root.to_byte_array()));
+        let output_key = compute::tweaked_public_key(group_key, tweak);
+        if prevout.script_pubkey.as_bytes()[2..] != output_key.x().to_bytes() {
+            continue;
+        }
+
+        let sighash_type = match input.sighash_type {
+            Some(sighash_type) => sighash_type
+                .taproot_hash_ty()
+                .map_err(|_| Error::BadSighashType(input_index))?,
+            None => TapSighashType::Default,
+        };
+        let sighash = match sighash_type {
+            TapSighashType::AllPlusAnyoneCanPay
+            | TapSighashType::NonePlusAnyoneCanPay
+            | TapSighashType::SinglePlusAnyoneCanPay => cache.taproot_key_spend_signature_hash(
+                input_index,
+                &Prevouts::One(input_index, prevout),
+                sighash_type,
+            ),
+            _ => {
+                let all = prevouts
+                    .iter()
+                    .enumerate()
+                    .map(|(i, prevout)| prevout.as_ref().ok_or(Error::MissingUtxo(i)))
+                    .collect::<Result<Vec<&TxOut>, Error>>()?;
+                cache.taproot_key_spend_signature_hash(
+                    input_index,
+                    &Prevouts::All(&all),
+                    sighash_type,
+                )
+            }
+        }
+        .map_err(|e| Error::Sighash(input_index, e.to_string()))?;
+
+        spends.push(KeySpend {
+            input_index,
+            sighash: sighash.to_byte_array(),
+            sighash_type,
+            tweak,
+        });
+    }
+    Ok(spends)
+}
+
+/// Drives a coordinator through a signing round for each taproot key spend of the group key in a
+/// PSBT, one at a time, and inserts each signature into the PSBT as its round completes
+pub struct PsbtSigner {
+    /// the PSBT being signed
+    pub psbt: Psbt,
+    /// the key spends which haven't been signed yet
+    pub pending: VecDeque<KeySpend>,
+    in_flight: Option<KeySpend>,
+}
+
+impl PsbtSigner {
+    /// Find the key spends of `group_key` in `psbt` which need signing
+    pub fn new(psbt: Psbt, group_key: &Point) -> Result<Self, Error> {
+        let pending = key_spends(&psbt, group_key)?.into();
+        Ok(Self {
+            psbt,
+            pending,
+            in_flight: None,
+        })
+    }
+
+    /// Start a signing round on `coordinator` for the next key spend, returning None once every
+    /// key spend is signed
+    pub fn start_next_round<C: Coordinatable + ?Sized>(
+        &mut self,
+        coordinator: &mut C,
+    ) -> Result<Option<Packet>, Error> {
+        let Some(spend) = self.pending.front() else {
+            return Ok(None);
+        };
+        let packet = coordinator.start_signing_digest(&spend.sighash, true, spend.tweak)?;
+        info!(input_index = spend.input_index, "Signing PSBT key spend");
+        self.in_flight = self.pending.pop_front();
+        Ok(Some(packet))
+    }
+
+    /// Insert the signature from the signing round which just completed into the PSBT
+    pub fn insert_signature(&mut self, result: &OperationResult) -> Result<(), Error> {
+        let OperationResult::SignTaproot(proof) = result else {
+            return Err(Error::UnexpectedResult(Box::new(result.clone())));
+        };
+        let spend = self.in_flight.take().ok_or(Error::NotSigning)?;
+        let signature = schnorr::Signature::from_slice(&proof.to_bytes())
+            .map_err(|e| Error::BadSignature(e.to_string()))?;
+        self.psbt.inputs[spend.input_index].tap_key_sig = Some(taproot::Signature {
+            signature,
+            sighash_type: spend.sighash_type,
+        });
+        Ok(())
+    }
+
+    /// Put the key spend whose signing round failed back at the front of the queue, so the next
+    /// round retries it
+    pub fn retry(&mut self) {
+        if let Some(spend) = self.in_flight.take() {
+            self.pending.push_front(spend);
+        }
+    }
+
+    /// Whether every key spend has been signed
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty() && self.in_flight.is_none()
+    }
+}
@@ -0,0 +1,409 @@
+use hashbrown::HashMap;
+use p256k1::{
+    point::{Point, G},
+    scalar::Scalar,
+};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+use crate::{ct, util::hash_to_scalar};
+
+/// The number of bits in each encrypted chunk of a share
+pub const CHUNK_BITS: usize = 16;
+/// The number of chunks a share is split into, enough to hold any scalar
+pub const NUM_CHUNKS: usize = 256 / CHUNK_BITS;
+
+/// The number of baby steps when solving for a chunk, so a chunk takes at most
+/// 2^CHUNK_BITS / BABY_STEPS giant steps
+const BABY_STEPS: u32 = 1 << 10;
+/// The prefix hashed into the proof that a share was encrypted correctly
+const ENCRYPTION_PREFIX: &str = "WSTS/pvss/encryption";
+/// The prefix hashed into the proof that a chunk didn't decrypt
+const COMPLAINT_PREFIX: &str = "WSTS/pvss/complaint";
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from decrypting a verifiably encrypted share
+pub enum Error {
+    #[error("expected {NUM_CHUNKS} chunks, got {0}")]
+    /// The share wasn't split into the right number of chunks
+    BadChunkCount(usize),
+    #[error("chunk {0} doesn't decrypt to a {CHUNK_BITS} bit value")]
+    /// A chunk decrypted to a point whose discrete log is out of range, so the dealer cheated
+    ChunkOutOfRange(usize),
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A non-interactive proof that `Y1 = x*G` and `Y2 = x*H` for the same secret `x`
+pub struct DleqProof {
+    /// The challenge
+    pub c: Scalar,
+    /// The response
+    pub z: Scalar,
+}
+
+#[allow(non_snake_case)]
+impl DleqProof {
+    /// Prove knowledge of `x` where `Y1 = x*G` and `Y2 = x*H`, binding `context` into the proof
+    fn new<RNG: RngCore + CryptoRng>(
+        prefix: &str,
+        x: &Scalar,
+        H: &Point,
+        Y1: &Point,
+        Y2: &Point,
+        context: &[u8],
+        rng: &mut RNG,
+    ) -> Self {
+        let k = Scalar::random(rng);
+        let A1 = ct::mul_base(&k);
+        let A2 = ct::mul(&k, H);
+        let c = Self::challenge(prefix, H, Y1, Y2, &A1, &A2, context);
+        Self { c, z: k + c * x }
+    }
+
+    /// Compute the proof challenge
+    fn challenge(
+        prefix: &str,
+        H: &Point,
+        Y1: &Point,
+        Y2: &Point,
+        A1: &Point,
+        A2: &Point,
+        context: &[u8],
+    ) -> Scalar {
+        let mut hasher = Sha256::new();
+        hasher.update(prefix.as_bytes());
+        for point in [H, Y1, Y2, A1, A2] {
+            hasher.update(point.compress().as_bytes());
+        }
+        hasher.update(context);
+        hash_to_scalar(&mut hasher)
+    }
+
+    /// Verify the proof for `Y1 = x*G` and `Y2 = x*H`
+    fn verify(&self, prefix: &str, H: &Point, Y1: &Point, Y2: &Point, context: &[u8]) -> bool {
+        let A1 = self.z * G - self.c * Y1;
+        let A2 = self.z * H - self.c * Y2;
+        self.c == Self::challenge(prefix, H, Y1, Y2, &A1, &A2, context)
+    }
+}
+
+#[allow(non_snake_case)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A DKG private share encrypted to a signer's network key, which anyone can check against the
+/// dealer's polynomial commitment without decrypting it
+///
+/// The share is split into `NUM_CHUNKS` chunks of `CHUNK_BITS` bits, and each chunk `m` is
+/// encrypted with ElGamal in the exponent as `(r*G, m*G + r*P)`.  The recipient recovers `m*G`
+/// and solves for the small `m`.  The proof shows that the chunks recombine to the share which
+/// the commitment dictates, so a dealer can only cheat by encrypting a chunk which is too big
+/// to solve for, which the recipient can prove with a `Complaint`.
+pub struct EncryptedShare {
+    /// The ElGamal ciphertext `(R, C)` of each chunk, least significant first
+    pub chunks: Vec<(Point, Point)>,
+    /// The proof that the chunks recombine to the committed share
+    pub proof: DleqProof,
+}
+
+#[allow(non_snake_case)]
+impl EncryptedShare {
+    /// Encrypt `share` to `public_key`
+    pub fn new<RNG: RngCore + CryptoRng>(
+        share: &Scalar,
+        public_key: &Point,
+        rng: &mut RNG,
+    ) -> Self {
+        let bytes = share.to_bytes();
+        let mut rho = Scalar::from(0);
+        let mut chunks = Vec::with_capacity(NUM_CHUNKS);
+        for k in 0..NUM_CHUNKS {
+            let m = u32::from(u16::from_be_bytes([bytes[30 - 2 * k], bytes[31 - 2 * k]]));
+            let r = Scalar::random(rng);
+            chunks.push((
+                ct::mul_base(&r),
+                ct::mul_base(&Scalar::from(m)) + ct::mul(&r, public_key),
+            ));
+            rho += chunk_weight(k) * r;
+        }
+
+        let (R, D) = Self::combine(&chunks, &ct::mul_base(share));
+        let proof = DleqProof::new(
+            ENCRYPTION_PREFIX,
+            &rho,
+            public_key,
+            &R,
+            &D,
+            &chunks_bytes(&chunks),
+            rng,
+        );
+        Self { chunks, proof }
+    }
+
+    /// Check that this encrypts the share committed to by `commitment`, i.e. the dealer's
+    /// public polynomial evaluated at the recipient's key ID, to `public_key`
+    pub fn verify(&self, public_key: &Point, commitment: &Point) -> bool {
+        if self.chunks.len() != NUM_CHUNKS {
+            return false;
+        }
+        let (R, D) = Self::combine(&self.chunks, commitment);
+        self.proof.verify(
+            ENCRYPTION_PREFIX,
+            public_key,
+            &R,
+            &D,
+            &chunks_bytes(&self.chunks),
+        )
+    }
+
+    /// Decrypt the share with the recipient's network private key
+    pub fn decrypt(&self, private_key: &Scalar) -> Result<Scalar, Error> {
+        if self.chunks.len() != NUM_CHUNKS {
+            return Err(Error::BadChunkCount(self.chunks.len()));
+        }
+        let mut share = Scalar::from(0);
+        for (k, (R, C)) in self.chunks.iter().enumerate() {
+            let m =
+                solve_chunk(&(C - &ct::mul(private_key, R))).ok_or(Error::ChunkOutOfRange(k))?;
+            share += chunk_weight(k) * Scalar::from(m);
+        }
+        Ok(share)
+    }
+
+    /// Prove that a chunk of this share can't be decrypted, or None if every chunk can
+    pub fn complain<RNG: RngCore + CryptoRng>(
+        &self,
+        private_key: &Scalar,
+        rng: &mut RNG,
+    ) -> Option<Complaint> {
+        let chunk = match self.decrypt(private_key) {
+            Err(Error::ChunkOutOfRange(chunk)) => chunk,
+            _ => return None,
+        };
+        let (R, C) = &self.chunks[chunk];
+        let dh_key = ct::mul(private_key, R);
+        let proof = DleqProof::new(
+            COMPLAINT_PREFIX,
+            private_key,
+            R,
+            &ct::mul_base(private_key),
+            &dh_key,
+            C.compress().as_bytes(),
+            rng,
+        );
+        Some(Complaint {
+            chunk: chunk.try_into().unwrap(),
+            dh_key,
+            proof,
+        })
+    }
+
+    /// Combine the chunks into `(sum(w_k*R_k), sum(w_k*C_k) - commitment)`, which share the
+    /// discrete logs `rho` and `rho * log(public_key)` if the chunks recombine to the share
+    fn combine(chunks: &[(Point, Point)], commitment: &Point) -> (Point, Point) {
+        let weights = (0..chunks.len()).map(chunk_weight).collect::<Vec<Scalar>>();
+        let (R, C): (Vec<Point>, Vec<Point>) = chunks.iter().copied().unzip();
+        let multimult = |points| {
+            Point::multimult(weights.clone(), points).expect("there is one weight per chunk")
+        };
+        (multimult(R), multimult(C) - *commitment)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// Proof that a chunk of an `EncryptedShare` doesn't decrypt, which anyone holding the
+/// encrypted share and the recipient's public key can check without the recipient's help
+pub struct Complaint {
+    /// The index of the chunk which didn't decrypt
+    pub chunk: u32,
+    /// The Diffie-Hellman key which decrypts the chunk
+    pub dh_key: Point,
+    /// The proof that `dh_key` was computed with the recipient's private key
+    pub proof: DleqProof,
+}
+
+impl Complaint {
+    /// Check that the recipient with `public_key` really can't decrypt `share`
+    #[allow(non_snake_case)]
+    pub fn verify(&self, share: &EncryptedShare, public_key: &Point) -> bool {
+        let Some((R, C)) = usize::try_from(self.chunk)
+            .ok()
+            .and_then(|chunk| share.chunks.get(chunk))
+        else {
+            return false;
+        };
+        self.proof.verify(
+            COMPLAINT_PREFIX,
+            R,
+            public_key,
+            &self.dh_key,
+            C.compress().as_bytes(),
+        ) && solve_chunk(&(C - &self.dh_key)).is_none()
+    }
+}
+
+/// The scalar `2^(CHUNK_BITS * k)` which chunk `k` is weighted by
+fn chunk_weight(k: usize) -> Scalar {
+    let mut bytes = [0u8; 32];
+    bytes[31 - CHUNK_BITS / 8 * k] = 1;
+    Scalar::from(bytes)
+}
+
+/// Serialize the chunk ciphertexts, to bind them into the encryption proof
+#[allow(non_snake_case)]
+fn chunks_bytes(chunks: &[(Point, Point)]) -> Vec<u8> {
+    chunks
+        .iter()
+        .flat_map(|(R, C)| {
+            [
+                R.compress().as_bytes().to_vec(),
+                C.compress().as_bytes().to_vec(),
+            ]
+        })
+        .flatten()
+        .collect()
+}
+
+/// Solve `M = m*G` for `m < 2^CHUNK_BITS` with baby-step giant-step, or None if it's out of range
+#[allow(non_snake_case)]
+fn solve_chunk(M: &Point) -> Option<u32> {
+    static BABY_STEPS_TABLE: OnceLock<HashMap<Point, u32>> = OnceLock::new();
+    let table = BABY_STEPS_TABLE.get_or_init(|| {
+        let mut table = HashMap::with_capacity(BABY_STEPS as usize);
+        let mut point = Point::default();
+        for j in 0..BABY_STEPS {
+            table.insert(point, j);
+            point += G;
+        }
+        table
+    });
+
+    let giant_step = Scalar::from(BABY_STEPS) * G;
+    let mut point = *M;
+    for i in 0..(1u32 << CHUNK_BITS) / BABY_STEPS {
+        if let Some(j) = table.get(&point) {
+            return Some(i * BABY_STEPS + j);
+        }
+        point = point - giant_step;
+    }
+    None
+}
+
+#[cfg(test)]
+#[allow(non_snake_case)]
+/// Encrypt `share` as a cheating dealer would, moving value between the first two chunks so they
+/// still recombine to the share and pass verification, but chunk 0 is far too big to solve for
+pub(crate) fn undecryptable_share<RNG: RngCore + CryptoRng>(
+    share: &Scalar,
+    public_key: &Point,
+    rng: &mut RNG,
+) -> EncryptedShare {
+    let bytes = share.to_bytes();
+    let mut values = (0..NUM_CHUNKS)
+        .map(|k| {
+            Scalar::from(u32::from(u16::from_be_bytes([
+                bytes[30 - 2 * k],
+                bytes[31 - 2 * k],
+            ])))
+        })
+        .collect::<Vec<Scalar>>();
+    let big = Scalar::random(rng);
+    values[0] += big;
+    values[1] = values[1] - big * chunk_weight(1).invert();
+
+    let mut rho = Scalar::from(0);
+    let chunks = values
+        .iter()
+        .enumerate()
+        .map(|(k, m)| {
+            let r = Scalar::random(rng);
+            rho += chunk_weight(k) * r;
+            (r * G, m * G + r * public_key)
+        })
+        .collect::<Vec<_>>();
+    let (R, D) = EncryptedShare::combine(&chunks, &(share * G));
+    let proof = DleqProof::new(
+        ENCRYPTION_PREFIX,
+        &rho,
+        public_key,
+        &R,
+        &D,
+        &chunks_bytes(&chunks),
+        rng,
+    );
+    EncryptedShare { chunks, proof }
+}
+
+#[cfg(test)]
+mod test {
+    use p256k1::{point::G, scalar::Scalar};
+    use rand_core::OsRng;
+
+    use super::{undecryptable_share, Complaint, EncryptedShare, Error};
+
+    #[test]
+    fn encrypt_decrypt() {
+        let mut rng = OsRng;
+        let private_key = Scalar::random(&mut rng);
+        let public_key = private_key * G;
+
+        for share in [
+            Scalar::random(&mut rng),
+            Scalar::from(0),
+            Scalar::from(0) - Scalar::from(1),
+        ] {
+            let encrypted = EncryptedShare::new(&share, &public_key, &mut rng);
+            assert!(encrypted.verify(&public_key, &(share * G)));
+            assert_eq!(encrypted.decrypt(&private_key), Ok(share));
+            assert!(encrypted.complain(&private_key, &mut rng).is_none());
+
+            // the proof ties the ciphertext to both the commitment and the recipient
+            assert!(!encrypted.verify(&public_key, &((share + Scalar::from(1)) * G)));
+            assert!(!encrypted.verify(&(Scalar::random(&mut rng) * G), &(share * G)));
+
+            let mut tampered = encrypted.clone();
+            tampered.chunks[3].1 += G;
+            assert!(!tampered.verify(&public_key, &(share * G)));
+
+            let mut truncated = encrypted.clone();
+            truncated.chunks.pop();
+            assert!(!truncated.verify(&public_key, &(share * G)));
+            assert_eq!(
+                truncated.decrypt(&private_key),
+                Err(Error::BadChunkCount(15))
+            );
+        }
+    }
+
+    #[test]
+    fn complaint() {
+        let mut rng = OsRng;
+        let private_key = Scalar::random(&mut rng);
+        let public_key = private_key * G;
+        let share = Scalar::random(&mut rng);
+
+        let encrypted = undecryptable_share(&share, &public_key, &mut rng);
+        assert!(encrypted.verify(&public_key, &(share * G)));
+        assert_eq!(
+            encrypted.decrypt(&private_key),
+            Err(Error::ChunkOutOfRange(0))
+        );
+
+        let complaint = encrypted.complain(&private_key, &mut rng).unwrap();
+        assert_eq!(complaint.chunk, 0);
+        let json = serde_json::to_string(&complaint).unwrap();
+        let complaint: Complaint = serde_json::from_str(&json).unwrap();
+        assert!(complaint.verify(&encrypted, &public_key));
+        assert!(!complaint.verify(&encrypted, &(Scalar::random(&mut rng) * G)));
+
+        // the recipient can't frame an honest dealer, whose chunks all decrypt
+        let honest = EncryptedShare::new(&share, &public_key, &mut rng);
+        assert!(!complaint.verify(&honest, &public_key));
+        assert!(!Complaint {
+            dh_key: private_key * honest.chunks[0].0 + G,
+            ..complaint.clone()
+        }
+        .verify(&honest, &public_key));
+    }
+}
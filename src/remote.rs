@@ -0,0 +1,714 @@
+use core::marker::PhantomData;
+use hashbrown::HashMap;
+use num_traits::Zero;
+use p256k1::{
+    point::{Error as PointError, Point},
+    scalar::Scalar,
+};
+use rand_core::{CryptoRng, OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+use crate::{
+    common::{Nonce, PolyCommitment, PublicNonce, SignatureShare, TaprootTweak},
+    errors::DkgError,
+    net::DkgPartyError,
+    traits::Signer as SignerTrait,
+};
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from calling a keystore
+pub enum Error {
+    #[error("keystore RPC failed: {0}")]
+    /// The request couldn't be delivered, or the response couldn't be read
+    Rpc(String),
+    #[error("keystore error: {0}")]
+    /// The keystore refused or failed to carry out the request
+    Keystore(String),
+    #[error("unexpected keystore response {0}")]
+    /// The keystore answered with a response for a different request
+    UnexpectedResponse(String),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// What a keystore is asked to sign
+pub enum SignKind {
+    /// A message, as `traits::Signer::sign`
+    Message(Vec<u8>),
+    /// A message under the taproot tweaked group key, as `traits::Signer::sign_taproot`
+    Taproot(Vec<u8>, TaprootTweak),
+    /// A blinded challenge, as `traits::Signer::sign_blinded`
+    Blinded(Scalar),
+    /// A message under the group key tweaked by a scalar, as `traits::Signer::sign_with_tweak`
+    Tweaked(Vec<u8>, Scalar),
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A request to a keystore.  These are the only operations which need the private polynomials,
+/// key shares, or nonces, so a keystore can hold them without the host ever seeing them.
+pub enum Request {
+    /// Create the signer's polynomials
+    Init {
+        /// the signer ID
+        party_id: u32,
+        /// the key IDs owned by the signer
+        key_ids: Vec<u32>,
+        /// the total number of signers
+        num_signers: u32,
+        /// the total number of keys
+        num_keys: u32,
+        /// the threshold of the keys needed for a valid signature
+        threshold: u32,
+    },
+    /// Get the polynomial commitments
+    PolyCommitments,
+    /// Replace the polynomials with new random ones
+    ResetPolys,
+    /// Replace the polynomials with ones which reshare the key shares under a new threshold,
+    /// Lagrange weighted over the listed key IDs
+    ResharePolys(u32, Vec<u32>),
+    /// Get the private shares to send to the other signers
+    Shares,
+    /// Compute the key shares from the private shares sent by every signer
+    ComputeSecrets {
+        /// the private shares for each of the signer's keys, keyed by key ID then party ID
+        shares: HashMap<u32, HashMap<u32, Scalar>>,
+        /// the polynomial commitments of every party
+        polys: Vec<PolyCommitment>,
+    },
+    /// Generate nonces for a signing round
+    GenNonces {
+        /// auxiliary randomness from the host which is mixed into the nonces
+        aux_rand: Vec<u8>,
+        /// the signing round context which is mixed into the nonces
+        context: Vec<u8>,
+    },
+    /// Sign with the nonces from a `GenNonces` request, which can't be used again
+    Sign {
+        /// the ID of the nonces which the keystore returned
+        nonces_id: u64,
+        /// what to sign
+        kind: SignKind,
+        /// the IDs of the signers in the signing round
+        signer_ids: Vec<u32>,
+        /// the key IDs in the signing round
+        key_ids: Vec<u32>,
+        /// the public nonces of every signer in the signing round
+        nonces: Vec<PublicNonce>,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// A keystore's response to a `Request`
+pub enum Response {
+    /// The signer was created, and has this many parties in total
+    Initialized(u32),
+    /// The request was carried out
+    Done,
+    /// The polynomial commitments
+    PolyCommitments(Vec<PolyCommitment>),
+    /// The private shares, keyed by destination key ID then source party ID
+    Shares(HashMap<u32, HashMap<u32, Scalar>>),
+    /// The key shares were computed, giving this group key
+    GroupKey(Point),
+    /// The key shares couldn't be computed, for these reasons keyed by party ID
+    DkgFailed(HashMap<u32, DkgPartyError>),
+    /// Nonces were generated and stored in the keystore under an ID
+    Nonces {
+        /// the ID to sign with the nonces
+        nonces_id: u64,
+        /// the public nonces
+        nonces: Vec<PublicNonce>,
+    },
+    /// The signature shares
+    SignatureShares(Vec<SignatureShare>),
+    /// The request failed
+    Error(String),
+}
+
+/// The RPC interface to a keystore or HSM, e.g. over a unix socket or a PKCS#11 bridge.  Requests
+/// and responses can be serialized, so implementations only need to carry them.
+pub trait Keystore {
+    /// Send `request` to the keystore and wait for its response
+    fn call(&self, request: Request) -> Result<Response, Error>;
+}
+
+/// The keystore side of the RPC interface, which holds a signer and answers requests with it.
+/// It keeps every set of nonces until they're signed with, and never signs twice with the same
+/// nonces.
+pub struct KeystoreServer<Signer: SignerTrait> {
+    /// the signer, once it's been created by an `Init` request
+    pub signer: Option<Signer>,
+    nonces: HashMap<u64, Vec<Nonce>>,
+    next_nonces_id: u64,
+}
+
+impl<Signer: SignerTrait> Default for KeystoreServer<Signer> {
+    fn default() -> Self {
+        Self {
+            signer: None,
+            nonces: HashMap::new(),
+            next_nonces_id: 0,
+        }
+    }
+}
+
+impl<Signer: SignerTrait> KeystoreServer<Signer> {
+    /// Create a keystore which holds `signer`, e.g. loaded from the keystore's own storage
+    pub fn with_signer(signer: Signer) -> Self {
+        Self {
+            signer: Some(signer),
+            ..Default::default()
+        }
+    }
+
+    /// Carry out `request`
+    pub fn handle(&mut self, request: Request) -> Response {
+        let mut rng = OsRng;
+        if let Request::Init {
+            party_id,
+            key_ids,
+            num_signers,
+            num_keys,
+            threshold,
+        } = request
+        {
+            let signer = Signer::new(
+                party_id,
+                &key_ids,
+                num_signers,
+                num_keys,
+                threshold,
+                &mut rng,
+            );
+            let num_parties = signer.get_num_parties();
+            *self = Self::with_signer(signer);
+            return Response::Initialized(num_parties);
+        }
+
+        let Some(signer) = &mut self.signer else {
+            return Response::Error("no signer, send Init first".to_string());
+        };
+        match request {
+            Request::Init { .. } => unreachable!("Init was handled above"),
+            Request::PolyCommitments => {
+                Response::PolyCommitments(signer.get_poly_commitments(&mut rng))
+            }
+            Request::ResetPolys => {
+                signer.reset_polys(&mut rng);
+                Response::Done
+            }
+            Request::ResharePolys(threshold, key_ids) => {
+                signer.reshare_polys(threshold, &key_ids, &mut rng);
+                Response::Done
+            }
+            Request::Shares => Response::Shares(signer.get_shares()),
+            Request::ComputeSecrets { shares, polys } => {
+                let polys = polys.iter().collect::<Vec<&PolyCommitment>>();
+                match signer.compute_secrets(&shares, &polys) {
+                    Ok(()) => Response::GroupKey(signer.get_group_key()),
+                    Err(errors) => Response::DkgFailed(
+                        errors
+                            .iter()
+                            .map(|(party_id, error)| (*party_id, error.into()))
+                            .collect(),
+                    ),
+                }
+            }
+            Request::GenNonces { aux_rand, context } => {
+                let public_nonces = signer.gen_nonces_with_context(&aux_rand, &context, &mut rng);
+                let nonces_id = self.next_nonces_id;
+                self.next_nonces_id += 1;
+                self.nonces.insert(nonces_id, signer.get_nonces());
+                Response::Nonces {
+                    nonces_id,
+                    nonces: public_nonces,
+                }
+            }
+            Request::Sign {
+                nonces_id,
+                kind,
+                signer_ids,
+                key_ids,
+                nonces,
+            } => {
+                let Some(private_nonces) = self.nonces.remove(&nonces_id) else {
+                    return Response::Error(format!("no nonces with ID {nonces_id}"));
+                };
+                signer.set_nonces(&private_nonces);
+                let shares = match kind {
+                    SignKind::Message(msg) => signer.sign(&msg, &signer_ids, &key_ids, &nonces),
+                    SignKind::Taproot(msg, tweak) => {
+                        signer.sign_taproot(&msg, &signer_ids, &key_ids, &nonces, tweak)
+                    }
+                    SignKind::Blinded(challenge) => {
+                        signer.sign_blinded(&challenge, &signer_ids, &key_ids, &nonces)
+                    }
+                    SignKind::Tweaked(msg, tweak) => {
+                        signer.sign_with_tweak(&msg, &signer_ids, &key_ids, &nonces, &tweak)
+                    }
+                };
+                Response::SignatureShares(shares)
+            }
+        }
+    }
+}
+
+/// A keystore which runs a `KeystoreServer` in the same process, for tests and as a reference
+/// for real keystores.  Clones share the same server.
+pub struct LocalKeystore<Signer: SignerTrait> {
+    /// the server which answers requests
+    pub server: Arc<Mutex<KeystoreServer<Signer>>>,
+}
+
+impl<Signer: SignerTrait> Default for LocalKeystore<Signer> {
+    fn default() -> Self {
+        Self {
+            server: Arc::new(Mutex::new(KeystoreServer::default())),
+        }
+    }
+}
+
+impl<Signer: SignerTrait> Clone for LocalKeystore<Signer> {
+    fn clone(&self) -> Self {
+        Self {
+            server: self.server.clone(),
+        }
+    }
+}
+
+impl<Signer: SignerTrait> Keystore for LocalKeystore<Signer> {
+    fn call(&self, request: Request) -> Result<Response, Error> {
+        let mut server = self.server.lock().map_err(|e| Error::Rpc(e.to_string()))?;
+        Ok(server.handle(request))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// The state of a `RemoteSigner` which the host keeps; the keystore keeps everything private
+pub struct RemoteSignerState {
+    /// the signer ID
+    pub id: u32,
+    /// the key IDs owned by the signer
+    pub key_ids: Vec<u32>,
+    /// the total number of parties
+    pub num_parties: u32,
+    /// the group key from the last DKG
+    pub group_key: Point,
+    /// the ID of the nonces which the next signature will use
+    pub nonces_id: Option<u64>,
+}
+
+/// A `Signer` which delegates everything that needs private polynomials, key shares, or nonces to
+/// a keystore, so that none of them are ever in the host process.  `Signer` is the version which
+/// the keystore runs, and the keystore is created with `Default`, e.g. from a socket path in the
+/// environment.
+///
+/// The `Signer` trait can't return errors from most methods, so a failed call to the keystore is
+/// logged, and the method returns an empty result which the coordinator will reject; the error
+/// can be taken with `take_error`.  The private shares for other signers still pass through the
+/// host, which encrypts them for their recipients, and `get_private_keys` returns nothing.
+/// `get_nonces` returns an opaque handle to nonces held by the keystore rather than the nonces.
+pub struct RemoteSigner<Signer: SignerTrait, K: Keystore + Default> {
+    /// the keystore which holds the private state
+    pub keystore: K,
+    state: RemoteSignerState,
+    error: Mutex<Option<Error>>,
+    _signer: PhantomData<Signer>,
+}
+
+impl<Signer: SignerTrait, K: Keystore + Default> RemoteSigner<Signer, K> {
+    /// Take the error from the last call to the keystore which failed, if any
+    pub fn take_error(&self) -> Option<Error> {
+        self.error.lock().ok().and_then(|mut error| error.take())
+    }
+
+    /// Send `request` to the keystore, recording any error
+    fn call(&self, request: Request) -> Option<Response> {
+        let result = match self.keystore.call(request) {
+            Ok(Response::Error(reason)) => Err(Error::Keystore(reason)),
+            result => result,
+        };
+        match result {
+            Ok(response) => Some(response),
+            Err(e) => {
+                self.fail(e);
+                None
+            }
+        }
+    }
+
+    fn fail(&self, error: Error) {
+        warn!("Signer {} keystore call failed: {error}", self.state.id);
+        if let Ok(mut last) = self.error.lock() {
+            *last = Some(error);
+        }
+    }
+
+    fn unexpected(&self, response: Response) {
+        self.fail(Error::UnexpectedResponse(format!("{response:?}")));
+    }
+
+    fn sign_kind(
+        &self,
+        kind: SignKind,
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        let Some(nonces_id) = self.state.nonces_id else {
+            self.fail(Error::Keystore("no nonces to sign with".to_string()));
+            return Vec::new();
+        };
+        match self.call(Request::Sign {
+            nonces_id,
+            kind,
+            signer_ids: signer_ids.to_vec(),
+            key_ids: key_ids.to_vec(),
+            nonces: nonces.to_vec(),
+        }) {
+            Some(Response::SignatureShares(shares)) => shares,
+            Some(response) => {
+                self.unexpected(response);
+                Vec::new()
+            }
+            None => Vec::new(),
+        }
+    }
+}
+
+fn nonces_handle(nonces_id: u64) -> Nonce {
+    let mut bytes = [0u8; 32];
+    bytes[24..].copy_from_slice(&nonces_id.to_be_bytes());
+    Nonce {
+        d: Scalar::from(bytes),
+        e: Scalar::zero(),
+    }
+}
+
+fn nonces_id(handle: &Nonce) -> u64 {
+    let bytes = handle.d.to_bytes();
+    let mut id = [0u8; 8];
+    id.copy_from_slice(&bytes[24..]);
+    u64::from_be_bytes(id)
+}
+
+impl<Signer: SignerTrait, K: Keystore + Default> SignerTrait for RemoteSigner<Signer, K> {
+    type State = RemoteSignerState;
+
+    fn new<RNG: RngCore + CryptoRng>(
+        party_id: u32,
+        key_ids: &[u32],
+        num_signers: u32,
+        num_keys: u32,
+        threshold: u32,
+        _rng: &mut RNG,
+    ) -> Self {
+        let mut signer = Self::load(&RemoteSignerState {
+            id: party_id,
+            key_ids: key_ids.to_vec(),
+            num_parties: 0,
+            group_key: Point::new(),
+            nonces_id: None,
+        });
+        match signer.call(Request::Init {
+            party_id,
+            key_ids: key_ids.to_vec(),
+            num_signers,
+            num_keys,
+            threshold,
+        }) {
+            Some(Response::Initialized(num_parties)) => signer.state.num_parties = num_parties,
+            Some(response) => signer.unexpected(response),
+            None => (),
+        }
+        signer
+    }
+
+    fn load(state: &RemoteSignerState) -> Self {
+        Self {
+            keystore: K::default(),
+            state: state.clone(),
+            error: Mutex::new(None),
+            _signer: PhantomData,
+        }
+    }
+
+    fn save(&self) -> RemoteSignerState {
+        self.state.clone()
+    }
+
+    fn get_id(&self) -> u32 {
+        self.state.id
+    }
+
+    fn get_key_ids(&self) -> Vec<u32> {
+        self.state.key_ids.clone()
+    }
+
+    fn get_num_parties(&self) -> u32 {
+        self.state.num_parties
+    }
+
+    fn party_ids(signer_id: u32, key_ids: &[u32]) -> Vec<u32> {
+        Signer::party_ids(signer_id, key_ids)
+    }
+
+    fn get_group_key(&self) -> Point {
+        self.state.group_key
+    }
+
+    fn get_private_keys(&self) -> HashMap<u32, Scalar> {
+        HashMap::new()
+    }
+
+    fn get_poly_commitments<RNG: RngCore + CryptoRng>(
+        &self,
+        _rng: &mut RNG,
+    ) -> Vec<PolyCommitment> {
+        match self.call(Request::PolyCommitments) {
+            Some(Response::PolyCommitments(polys)) => polys,
+            Some(response) => {
+                self.unexpected(response);
+                Vec::new()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn reset_polys<RNG: RngCore + CryptoRng>(&mut self, _rng: &mut RNG) {
+        match self.call(Request::ResetPolys) {
+            Some(Response::Done) | None => (),
+            Some(response) => self.unexpected(response),
+        }
+    }
+
+    fn reshare_polys<RNG: RngCore + CryptoRng>(
+        &mut self,
+        threshold: u32,
+        key_ids: &[u32],
+        _rng: &mut RNG,
+    ) {
+        match self.call(Request::ResharePolys(threshold, key_ids.to_vec())) {
+            Some(Response::Done) | None => (),
+            Some(response) => self.unexpected(response),
+        }
+    }
+
+    fn get_shares(&self) -> HashMap<u32, HashMap<u32, Scalar>> {
+        match self.call(Request::Shares) {
+            Some(Response::Shares(shares)) => shares,
+            Some(response) => {
+                self.unexpected(response);
+                HashMap::new()
+            }
+            None => HashMap::new(),
+        }
+    }
+
+    fn compute_secrets(
+        &mut self,
+        shares: &HashMap<u32, HashMap<u32, Scalar>>,
+        polys: &[&PolyCommitment],
+    ) -> Result<(), HashMap<u32, DkgError>> {
+        let errors = match self.call(Request::ComputeSecrets {
+            shares: shares.clone(),
+            polys: polys.iter().map(|poly| (*poly).clone()).collect(),
+        }) {
+            Some(Response::GroupKey(group_key)) => {
+                self.state.group_key = group_key;
+                return Ok(());
+            }
+            Some(Response::DkgFailed(errors)) => errors,
+            Some(response) => {
+                self.unexpected(response);
+                HashMap::new()
+            }
+            None => HashMap::new(),
+        };
+
+        // a point error's cause doesn't survive the RPC, and neither does a failed call, so
+        // report them as point errors for every party
+        let mut errors: HashMap<u32, DkgError> = errors
+            .into_iter()
+            .map(|(party_id, error)| {
+                let error = match error {
+                    DkgPartyError::MissingShares(ids) => DkgError::MissingShares(ids),
+                    DkgPartyError::BadCommitments(ids) => DkgError::BadIds(ids),
+                    DkgPartyError::NotEnoughShares(ids) => DkgError::NotEnoughShares(ids),
+                    DkgPartyError::BadShares(ids) => DkgError::BadShares(ids),
+                    DkgPartyError::Point(_) => DkgError::Point(PointError::MultiMultFailed),
+                };
+                (party_id, error)
+            })
+            .collect();
+        if errors.is_empty() {
+            for party_id in Signer::party_ids(self.state.id, &self.state.key_ids) {
+                errors.insert(party_id, DkgError::Point(PointError::MultiMultFailed));
+            }
+        }
+        Err(errors)
+    }
+
+    fn gen_nonces_with_context<RNG: RngCore + CryptoRng>(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        _rng: &mut RNG,
+    ) -> Vec<PublicNonce> {
+        match self.call(Request::GenNonces {
+            aux_rand: aux_rand.to_vec(),
+            context: context.to_vec(),
+        }) {
+            Some(Response::Nonces { nonces_id, nonces }) => {
+                self.state.nonces_id = Some(nonces_id);
+                nonces
+            }
+            Some(response) => {
+                self.unexpected(response);
+                Vec::new()
+            }
+            None => Vec::new(),
+        }
+    }
+
+    fn get_nonces(&self) -> Vec<Nonce> {
+        self.state
+            .nonces_id
+            .map(|nonces_id| vec![nonces_handle(nonces_id)])
+            .unwrap_or_default()
+    }
+
+    fn set_nonces(&mut self, nonces: &[Nonce]) {
+        self.state.nonces_id = nonces.first().map(nonces_id);
+    }
+
+    fn compute_intermediate(
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> (Vec<Point>, Point) {
+        Signer::compute_intermediate(msg, signer_ids, key_ids, nonces)
+    }
+
+    fn sign(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        self.sign_kind(SignKind::Message(msg.to_vec()), signer_ids, key_ids, nonces)
+    }
+
+    fn sign_taproot(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: TaprootTweak,
+    ) -> Vec<SignatureShare> {
+        self.sign_kind(
+            SignKind::Taproot(msg.to_vec(), tweak),
+            signer_ids,
+            key_ids,
+            nonces,
+        )
+    }
+
+    fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        self.sign_kind(SignKind::Blinded(*challenge), signer_ids, key_ids, nonces)
+    }
+
+    fn sign_with_tweak(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: &Scalar,
+    ) -> Vec<SignatureShare> {
+        self.sign_kind(
+            SignKind::Tweaked(msg.to_vec(), *tweak),
+            signer_ids,
+            key_ids,
+            nonces,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use rand_core::OsRng;
+    use std::sync::{Arc, Mutex};
+
+    use super::{Error, KeystoreServer, LocalKeystore, RemoteSigner, RemoteSignerState};
+    use crate::{
+        traits::{Aggregator as AggregatorTrait, Signer as SignerTrait},
+        v2,
+    };
+
+    type Remote = RemoteSigner<v2::Signer, LocalKeystore<v2::Signer>>;
+
+    #[test]
+    fn nonces_are_single_use() {
+        let mut rng = OsRng;
+        let key_ids = [0, 1];
+        let mut signers = [v2::Signer::new(0, &key_ids, 1, 2, 2, &mut rng)];
+        let polys = v2::test_helpers::dkg(&mut signers, &mut rng).unwrap();
+        let [local] = signers;
+        let group_key = local.get_group_key();
+
+        let mut signer = Remote::load(&RemoteSignerState {
+            id: 0,
+            key_ids: key_ids.to_vec(),
+            num_parties: 1,
+            group_key,
+            nonces_id: None,
+        });
+        signer.keystore = LocalKeystore {
+            server: Arc::new(Mutex::new(KeystoreServer::with_signer(local))),
+        };
+        assert!(signer.get_private_keys().is_empty());
+
+        // the host only ever holds a handle to the nonces
+        let nonces = signer.gen_nonces(&mut rng);
+        let handle = signer.get_nonces();
+        assert!(!handle.contains(
+            &signer
+                .keystore
+                .server
+                .lock()
+                .unwrap()
+                .signer
+                .as_ref()
+                .unwrap()
+                .get_nonces()[0]
+        ));
+        signer.gen_nonces(&mut rng);
+        signer.set_nonces(&handle);
+
+        let shares = signer.sign(b"message", &[0], &key_ids, &nonces);
+        assert!(signer.take_error().is_none());
+        let mut aggregator = v2::Aggregator::new(2, 2);
+        aggregator.init(polys).unwrap();
+        let sig = aggregator
+            .sign(b"message", &nonces, &shares, &key_ids)
+            .unwrap();
+        assert!(sig.verify(&group_key, b"message"));
+
+        // the first signature used up the nonces, so the keystore refuses to sign again with them
+        assert!(signer.sign(b"message", &[0], &key_ids, &nonces).is_empty());
+        assert!(matches!(signer.take_error(), Some(Error::Keystore(_))));
+        assert!(signer.take_error().is_none());
+    }
+}
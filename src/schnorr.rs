@@ -6,7 +6,7 @@ use rand_core::{CryptoRng, RngCore};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use crate::util::hash_to_scalar;
+use crate::{ct, util::hash_to_scalar};
 
 #[allow(non_snake_case)]
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -25,11 +25,12 @@ impl ID {
     /// Construct a new schnorr ID which binds the passed `Scalar` `id` and `Scalar` `a`, with a zero-knowledge proof of ownership of `a`
     pub fn new<RNG: RngCore + CryptoRng>(id: &Scalar, a: &Scalar, rng: &mut RNG) -> Self {
         let k = Scalar::random(rng);
-        let c = Self::challenge(id, &(&k * &G), &(a * &G));
+        let kG = ct::mul_base(&k);
+        let c = Self::challenge(id, &kG, &ct::mul_base(a));
 
         Self {
             id: *id,
-            kG: &k * G,
+            kG,
             kca: &k + c * a,
         }
     }
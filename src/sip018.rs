@@ -0,0 +1,278 @@
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+use crate::{
+    common::TaprootTweak,
+    net::Packet,
+    state_machine::coordinator::{Coordinatable, Error as CoordinatorError},
+};
+
+/// The prefix of every SIP-018 structured data hash, "SIP018" in ASCII
+pub const PREFIX: &[u8] = b"SIP018";
+/// The chain ID of Stacks mainnet
+pub const CHAIN_ID_MAINNET: u32 = 0x00000001;
+/// The chain ID of Stacks testnet
+pub const CHAIN_ID_TESTNET: u32 = 0x80000000;
+
+/// The longest tuple field or contract name Clarity allows
+const MAX_NAME_LEN: usize = 128;
+
+#[derive(thiserror::Error, Debug)]
+/// Errors from hashing and signing structured data
+pub enum Error {
+    #[error("string-ascii value isn't ASCII: {0:?}")]
+    /// A string-ascii value had non-ASCII characters
+    NotAscii(String),
+    #[error("bad name {0:?}")]
+    /// A tuple field or contract name was empty or too long
+    BadName(String),
+    #[error("coordinator error {0}")]
+    /// The signing round couldn't be started
+    Coordinator(#[from] CoordinatorError),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// A Clarity value, which SIP-018 domains and messages are made of
+pub enum Value {
+    /// A signed 128-bit integer
+    Int(i128),
+    /// An unsigned 128-bit integer
+    UInt(u128),
+    /// A byte buffer
+    Buffer(Vec<u8>),
+    /// A boolean
+    Bool(bool),
+    /// A standard principal, as an address version and hash160
+    StandardPrincipal(u8, [u8; 20]),
+    /// A contract principal, as the deployer's address version and hash160, and the contract name
+    ContractPrincipal(u8, [u8; 20], String),
+    /// A successful response
+    Ok(Box<Value>),
+    /// An error response
+    Err(Box<Value>),
+    /// An empty optional
+    None,
+    /// An optional holding a value
+    Some(Box<Value>),
+    /// A list of values
+    List(Vec<Value>),
+    /// A tuple, whose fields are serialized in name order
+    Tuple(BTreeMap<String, Value>),
+    /// An ASCII string
+    StringAscii(String),
+    /// A UTF-8 string
+    StringUtf8(String),
+}
+
+impl Value {
+    /// Serialize the value in the Clarity consensus format
+    pub fn serialize(&self) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        self.serialize_into(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn serialize_into(&self, bytes: &mut Vec<u8>) -> Result<(), Error> {
+        match self {
+            Value::Int(i) => {
+                bytes.push(0x00);
+                bytes.extend(i.to_be_bytes());
+            }
+            Value::UInt(u) => {
+                bytes.push(0x01);
+                bytes.extend(u.to_be_bytes());
+            }
+            Value::Buffer(buffer) => {
+                bytes.push(0x02);
+                bytes.extend((buffer.len() as u32).to_be_bytes());
+                bytes.extend(buffer);
+            }
+            Value::Bool(true) => bytes.push(0x03),
+            Value::Bool(false) => bytes.push(0x04),
+            Value::StandardPrincipal(version, hash) => {
+                bytes.push(0x05);
+                bytes.push(*version);
+                bytes.extend(hash);
+            }
+            Value::ContractPrincipal(version, hash, name) => {
+                bytes.push(0x06);
+                bytes.push(*version);
+                bytes.extend(hash);
+                serialize_name(name, bytes)?;
+            }
+            Value::Ok(value) => {
+                bytes.push(0x07);
+                value.serialize_into(bytes)?;
+            }
+            Value::Err(value) => {
+                bytes.push(0x08);
+                value.serialize_into(bytes)?;
+            }
+            Value::None => bytes.push(0x09),
+            Value::Some(value) => {
+                bytes.push(0x0a);
+                value.serialize_into(bytes)?;
+            }
+            Value::List(values) => {
+                bytes.push(0x0b);
+                bytes.extend((values.len() as u32).to_be_bytes());
+                for value in values {
+                    value.serialize_into(bytes)?;
+                }
+            }
+            Value::Tuple(fields) => {
+                bytes.push(0x0c);
+                bytes.extend((fields.len() as u32).to_be_bytes());
+                for (name, value) in fields {
+                    serialize_name(name, bytes)?;
+                    value.serialize_into(bytes)?;
+                }
+            }
+            Value::StringAscii(s) => {
+                if !s.is_ascii() {
+                    return Err(Error::NotAscii(s.clone()));
+                }
+                bytes.push(0x0d);
+                bytes.extend((s.len() as u32).to_be_bytes());
+                bytes.extend(s.as_bytes());
+            }
+            Value::StringUtf8(s) => {
+                bytes.push(0x0e);
+                bytes.extend((s.len() as u32).to_be_bytes());
+                bytes.extend(s.as_bytes());
+            }
+        }
+        Ok(())
+    }
+}
+
+fn serialize_name(name: &str, bytes: &mut Vec<u8>) -> Result<(), Error> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !name.is_ascii() {
+        return Err(Error::BadName(name.to_string()));
+    }
+    bytes.push(name.len() as u8);
+    bytes.extend(name.as_bytes());
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// The SIP-018 domain which a message is signed for, so a signature for one app or chain can't
+/// be replayed on another
+pub struct Domain {
+    /// the name of the app
+    pub name: String,
+    /// the version of the app
+    pub version: String,
+    /// the chain ID, e.g. `CHAIN_ID_MAINNET`
+    pub chain_id: u32,
+}
+
+impl Domain {
+    /// The domain as the Clarity tuple which SIP-018 hashes
+    pub fn to_value(&self) -> Value {
+        Value::Tuple(BTreeMap::from([
+            ("name".to_string(), Value::StringAscii(self.name.clone())),
+            (
+                "version".to_string(),
+                Value::StringAscii(self.version.clone()),
+            ),
+            ("chain-id".to_string(), Value::UInt(self.chain_id.into())),
+        ]))
+    }
+}
+
+/// Compute the SIP-018 hash of `message` in `domain`, which is what gets signed
+pub fn message_hash(domain: &Domain, message: &Value) -> Result<[u8; 32], Error> {
+    let domain_hash = Sha256::digest(domain.to_value().serialize()?);
+    let message_hash = Sha256::digest(message.serialize()?);
+
+    let mut hasher = Sha256::new();
+    hasher.update(PREFIX);
+    hasher.update(domain_hash);
+    hasher.update(message_hash);
+    Ok(hasher.finalize().into())
+}
+
+/// Start a signing round over the SIP-018 hash of `message` in `domain`
+pub fn start_signing_round<C: Coordinatable + ?Sized>(
+    coordinator: &mut C,
+    domain: &Domain,
+    message: &Value,
+    is_taproot: bool,
+    tweak: TaprootTweak,
+) -> Result<Packet, Error> {
+    let hash = message_hash(domain, message)?;
+    Ok(coordinator.start_signing_digest(&hash, is_taproot, tweak)?)
+}
+
+#[cfg(test)]
+mod test {
+    use sha2::{Digest, Sha256};
+    use std::collections::BTreeMap;
+
+    use super::{message_hash, Domain, Error, Value, CHAIN_ID_MAINNET, CHAIN_ID_TESTNET, PREFIX};
+
+    #[test]
+    fn serialize() {
+        let mut uint = vec![0x01];
+        uint.extend([0; 15]);
+        uint.push(7);
+        assert_eq!(Value::UInt(7).serialize().unwrap(), uint);
+
+        let mut int = vec![0x00];
+        int.extend([0xff; 16]);
+        assert_eq!(Value::Int(-1).serialize().unwrap(), int);
+
+        assert_eq!(
+            Value::StringAscii("hi".to_string()).serialize().unwrap(),
+            [0x0d, 0, 0, 0, 2, b'h', b'i']
+        );
+        assert_eq!(
+            Value::Some(Box::new(Value::Bool(true)))
+                .serialize()
+                .unwrap(),
+            [0x0a, 0x03]
+        );
+
+        // tuple fields are serialized in name order, whatever order they were added in
+        let tuple = Value::Tuple(BTreeMap::from([
+            ("b".to_string(), Value::None),
+            ("a".to_string(), Value::Bool(false)),
+        ]));
+        assert_eq!(
+            tuple.serialize().unwrap(),
+            [0x0c, 0, 0, 0, 2, 1, b'a', 0x04, 1, b'b', 0x09]
+        );
+
+        assert!(matches!(
+            Value::StringAscii("héllo".to_string()).serialize(),
+            Err(Error::NotAscii(_))
+        ));
+        let bad_name = Value::Tuple(BTreeMap::from([("".to_string(), Value::None)]));
+        assert!(matches!(bad_name.serialize(), Err(Error::BadName(_))));
+    }
+
+    #[test]
+    fn hash() {
+        let domain = Domain {
+            name: "Test App".to_string(),
+            version: "1.0.0".to_string(),
+            chain_id: CHAIN_ID_MAINNET,
+        };
+        let message = Value::StringAscii("Hello World".to_string());
+
+        let mut hasher = Sha256::new();
+        hasher.update(PREFIX);
+        hasher.update(Sha256::digest(domain.to_value().serialize().unwrap()));
+        hasher.update(Sha256::digest(message.serialize().unwrap()));
+        let expected: [u8; 32] = hasher.finalize().into();
+        assert_eq!(message_hash(&domain, &message).unwrap(), expected);
+
+        // the same message in another domain hashes differently
+        let other_domain = Domain {
+            chain_id: CHAIN_ID_TESTNET,
+            ..domain
+        };
+        assert_ne!(message_hash(&other_domain, &message).unwrap(), expected);
+    }
+}
@@ -1,23 +1,44 @@
 use hashbrown::HashSet;
 use p256k1::{point::Point, scalar::Scalar};
-use std::collections::BTreeMap;
-use tracing::{debug, info};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Instant,
+};
+use tracing::{debug, info, info_span, warn, Span};
 
 use crate::{
-    common::{MerkleRoot, PolyCommitment, PublicNonce, Signature, SignatureShare},
+    audit::{AuditLog, Direction, DkgTranscript},
+    common::{PolyCommitment, PublicNonce, Signature, SignatureShare, TaprootTweak},
     compute,
+    errors::AggregatorError,
     net::{
-        DkgBegin, DkgPublicShares, Message, NonceRequest, NonceResponse, Packet, Signable,
-        SignatureShareRequest,
+        initial_seq, DkgBegin, DkgDecommit, DkgFailure, DkgPrivateBegin, DkgPublicShares,
+        DkgStatus, Message, NonceCommitment, NonceRequest, NonceResponse, NonceReveal, Packet,
+        SignatureShareRequest, ThresholdChangeBegin,
     },
     state_machine::{
-        coordinator::{Coordinatable, Error, State},
-        OperationResult, StateMachine,
+        coordinator::{
+            Coordinatable, CoordinatorConfig, CoordinatorEvent, Error, Misbehavior, NotReady,
+            ReadinessReport, RetryPolicy, SignerWeights, State,
+        },
+        elapsed_states,
+        signer::{SignerStatus, State as SignerState},
+        BanList, Metrics, NoopMetrics, OperationResult, Phase, RoundProgress, RoundType,
+        StateMachine,
     },
     taproot::SchnorrProof,
     traits::Aggregator as AggregatorTrait,
 };
 
+/// The DKG results from before an in-flight threshold change, so it can be rolled back
+struct ThresholdChange {
+    threshold: u32,
+    aggregate_public_key: Point,
+    party_polynomials: BTreeMap<u32, PolyCommitment>,
+    /// the signer whose keys are being re-dealt, if this is a recovery round
+    recover_signer_id: Option<u32>,
+}
+
 /// The coordinator for the FROST algorithm
 pub struct Coordinator<Aggregator: AggregatorTrait> {
     /// current DKG round ID
@@ -26,6 +47,8 @@ pub struct Coordinator<Aggregator: AggregatorTrait> {
     current_sign_id: u64,
     /// current signing iteration ID
     current_sign_iter_id: u64,
+    /// the epoch whose signer set the coordinator is running rounds with
+    pub epoch: u64,
     /// total number of signers
     pub total_signers: u32, // Assuming the signers cover all id:s in {1, 2, ..., total_signers}
     /// total number of keys
@@ -33,8 +56,12 @@ pub struct Coordinator<Aggregator: AggregatorTrait> {
     /// the threshold of the keys needed for a valid signature
     pub threshold: u32,
     dkg_public_shares: BTreeMap<u32, DkgPublicShares>,
+    dkg_private_share_signers: BTreeSet<u32>,
     party_polynomials: BTreeMap<u32, PolyCommitment>,
     public_nonces: BTreeMap<u32, NonceResponse>,
+    nonce_commitments: BTreeMap<u32, NonceCommitment>,
+    /// whether the signers which committed to nonces this nonce round were asked to reveal them
+    nonces_revealed: bool,
     signature_shares: BTreeMap<u32, Vec<SignatureShare>>,
     /// aggregate public key
     pub aggregate_public_key: Option<Point>,
@@ -42,14 +69,64 @@ pub struct Coordinator<Aggregator: AggregatorTrait> {
     schnorr_proof: Option<SchnorrProof>,
     /// key used to sign packet messages
     pub message_private_key: Scalar,
+    /// domain separation tag bound into every signed message (e.g. network magic or chain ID)
+    pub domain: Vec<u8>,
     /// which signers we're currently waiting on
     pub ids_to_await: HashSet<u32>,
+    /// the signers chosen to sign the current signing round, or None to ask every signer
+    pub signer_set: Option<HashSet<u32>>,
+    /// signers which failed to send nonces during the current signing round
+    pub unresponsive_signers: HashSet<u32>,
+    /// signers whose signature shares failed to verify during the current signing round
+    pub bad_sig_share_signers: HashSet<u32>,
+    /// signers which reported failure during the current DKG round
+    pub dkg_failures: BTreeMap<u32, DkgFailure>,
+    /// signers which aren't sent private shares during the current DKG round
+    dkg_excluded: HashSet<u32>,
+    /// signers whose messages are dropped, and which are never asked to sign
+    pub ban_list: BanList,
+    /// per-signer weights which must meet their own threshold, or None to only count keys
+    pub weights: Option<SignerWeights>,
+    /// whether signers commit to their nonces, and only reveal them once every signer has
+    /// committed
+    pub commit_reveal: bool,
+    /// whether to request signature shares as soon as the signers which sent nonces hold
+    /// enough keys, rather than waiting on every signer which was asked
+    pub early_nonce_quorum: bool,
+    /// whether to ask for new nonces as soon as a signer sends a bad signature share, rather
+    /// than failing the signing round
+    pub replace_bad_shares: bool,
+    /// how failed signing rounds are retried, or None to fail them straight away
+    pub retry_policy: Option<RetryPolicy>,
+    /// whether DKG rounds use Pedersen commitments, which hide each signer's polynomial until
+    /// every signer is bound to its own
+    pub pedersen_dkg: bool,
+    /// the number of signing iterations tried during the current signing round
+    sign_attempts: u32,
+    /// when a scheduled retry of the current signing round may ask for nonces
+    retry_at: Option<Instant>,
+    /// events from the messages processed since the last call to `take_events`
+    pub events: Vec<CoordinatorEvent>,
     /// the bytes that we're signing
     pub message: Vec<u8>,
+    /// whether `message` is a 32-byte digest which signers sign as-is
+    pub is_digest: bool,
     /// current state of the state machine
     pub state: State,
     /// Aggregator object
     aggregator: Aggregator,
+    threshold_change: Option<ThresholdChange>,
+    last_result: Option<OperationResult>,
+    /// the sequence number of the last packet the coordinator signed
+    pub seq: u64,
+    /// where counters and gauges are recorded
+    pub metrics: Box<dyn Metrics + Send>,
+    /// when the current round started
+    round_started: Option<Instant>,
+    /// the states the current round has been through, and when it entered each of them
+    state_history: Vec<(State, Instant)>,
+    /// the log of packets sent and received, or None to not keep one
+    pub audit_log: Option<AuditLog>,
 }
 
 impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
@@ -64,29 +141,275 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
             current_dkg_id: 0,
             current_sign_id: 0,
             current_sign_iter_id: 0,
+            epoch: 0,
             total_signers,
             total_keys,
             threshold,
             dkg_public_shares: Default::default(),
+            dkg_private_share_signers: Default::default(),
             party_polynomials: Default::default(),
             public_nonces: Default::default(),
+            nonce_commitments: Default::default(),
+            nonces_revealed: false,
             signature_shares: Default::default(),
             aggregate_public_key: None,
             signature: None,
             schnorr_proof: None,
             message: Default::default(),
+            is_digest: false,
             message_private_key,
+            domain: Vec::new(),
             ids_to_await: (0..total_signers).collect(),
+            signer_set: None,
+            unresponsive_signers: Default::default(),
+            bad_sig_share_signers: Default::default(),
+            dkg_failures: Default::default(),
+            dkg_excluded: Default::default(),
+            ban_list: Default::default(),
+            weights: None,
+            commit_reveal: false,
+            early_nonce_quorum: false,
+            replace_bad_shares: false,
+            retry_policy: None,
+            pedersen_dkg: false,
+            sign_attempts: 0,
+            retry_at: None,
+            events: Vec::new(),
             state: State::Idle,
             aggregator: Aggregator::new(total_keys, threshold),
+            threshold_change: None,
+            last_result: None,
+            seq: initial_seq(),
+            metrics: Box::new(NoopMetrics),
+            round_started: None,
+            state_history: Vec::new(),
+            audit_log: None,
+        }
+    }
+
+    /// Create a new coordinator from a config, after checking that it's valid
+    pub fn from_config(config: CoordinatorConfig) -> Result<Self, Error> {
+        config.validate()?;
+        let mut coordinator = Self::new(
+            config.total_signers,
+            config.total_keys,
+            config.threshold,
+            config.message_private_key,
+        )
+        .with_domain(&config.domain)
+        .with_ban_list(config.ban_list);
+        coordinator.weights = config.weights;
+        coordinator.early_nonce_quorum = config.early_nonce_quorum;
+        coordinator.replace_bad_shares = config.replace_bad_shares;
+        coordinator.retry_policy = config.retry_policy;
+        coordinator.pedersen_dkg = config.pedersen_dkg;
+        Ok(coordinator)
+    }
+
+    /// Set the domain separation tag used when signing outbound messages
+    pub fn with_domain(mut self, domain: &[u8]) -> Self {
+        self.domain = domain.to_vec();
+        self
+    }
+
+    /// Set the signers whose messages will be dropped, e.g. from a previously saved ban list
+    pub fn with_ban_list(mut self, ban_list: BanList) -> Self {
+        self.ban_list = ban_list;
+        self
+    }
+
+    /// Set per-signer weights, so DKG and signing rounds also need `weights.threshold` weight
+    pub fn with_weights(mut self, weights: SignerWeights) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    /// Have signers commit to their nonces before revealing them, which costs an extra round
+    /// trip but stops a signer which answers last from choosing its nonces based on the others
+    pub fn with_commit_reveal(mut self) -> Self {
+        self.commit_reveal = true;
+        self
+    }
+
+    /// Request signature shares as soon as the signers which sent nonces hold enough keys,
+    /// without waiting on the rest
+    pub fn with_early_nonce_quorum(mut self) -> Self {
+        self.early_nonce_quorum = true;
+        self
+    }
+
+    /// Ask for new nonces without a signer as soon as it sends a bad signature share, rather
+    /// than failing the signing round
+    pub fn with_bad_share_replacement(mut self) -> Self {
+        self.replace_bad_shares = true;
+        self
+    }
+
+    /// Retry failed signing rounds under `retry_policy`, rather than leaving it to the caller
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Have signers send Pedersen commitments during DKG, and only reveal their polynomial
+    /// commitments with their private shares, which costs an extra message per signer but stops a
+    /// signer which answers last from choosing its polynomial based on the others
+    pub fn with_pedersen_dkg(mut self) -> Self {
+        self.pedersen_dkg = true;
+        self
+    }
+
+    /// Take the events from the messages processed since the last call
+    pub fn take_events(&mut self) -> Vec<CoordinatorEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Set where counters and gauges are recorded
+    pub fn with_metrics(mut self, metrics: impl Metrics + Send + 'static) -> Self {
+        self.metrics = Box::new(metrics);
+        self
+    }
+
+    /// Keep a log of every packet processed and sent
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Switch to the signer set of `epoch`, which has `total_signers` signers holding
+    /// `total_keys` keys.  The group key belongs to the old signer set, so it's dropped and the
+    /// next round must be DKG, whose DkgBegin moves the signers to `epoch` as well.
+    pub fn start_epoch(
+        &mut self,
+        epoch: u64,
+        total_signers: u32,
+        total_keys: u32,
+    ) -> Result<(), Error> {
+        if self.state != State::Idle {
+            return Err(Error::BadStateChange(format!(
+                "{:?} to epoch {epoch}",
+                self.state
+            )));
+        }
+        CoordinatorConfig::new(
+            total_signers,
+            total_keys,
+            self.threshold,
+            self.message_private_key,
+        )
+        .validate()?;
+
+        info!(epoch, total_signers, total_keys, "Starting epoch");
+        self.epoch = epoch;
+        self.total_signers = total_signers;
+        self.total_keys = total_keys;
+        self.ids_to_await = (0..total_signers).collect();
+        self.aggregator = Aggregator::new(total_keys, self.threshold);
+        self.aggregate_public_key = None;
+        self.party_polynomials.clear();
+        Ok(())
+    }
+
+    /// A coordinator for signing round `sign_id` alone, which shares this coordinator's DKG
+    /// results and config but keeps its own signing state
+    pub(crate) fn fork_sign_round(&self, sign_id: u64) -> Self {
+        let mut round = Self::new(
+            self.total_signers,
+            self.total_keys,
+            self.threshold,
+            self.message_private_key,
+        )
+        .with_domain(&self.domain)
+        .with_ban_list(self.ban_list.clone());
+        round.weights = self.weights.clone();
+        round.commit_reveal = self.commit_reveal;
+        round.early_nonce_quorum = self.early_nonce_quorum;
+        round.replace_bad_shares = self.replace_bad_shares;
+        round.retry_policy = self.retry_policy.clone();
+        round.pedersen_dkg = self.pedersen_dkg;
+        round.epoch = self.epoch;
+        round.current_dkg_id = self.current_dkg_id;
+        // start_signing_round moves on to sign_id
+        round.current_sign_id = sign_id.wrapping_sub(1);
+        round.aggregate_public_key = self.aggregate_public_key;
+        round.party_polynomials = self.party_polynomials.clone();
+        round
+    }
+
+    /// Sign `msg` with the next sequence number
+    fn sign_packet(&mut self, msg: Message) -> Packet {
+        self.seq += 1;
+        let packet = Packet::new(msg, self.seq, &self.domain, &self.message_private_key).expect("");
+        if let Some(audit_log) = &mut self.audit_log {
+            audit_log.record(Direction::Outbound, &packet);
+        }
+        packet
+    }
+
+    /// Check that `signer_ids` carry enough weight, if there are signer weights
+    fn check_weight<'a>(&self, signer_ids: impl IntoIterator<Item = &'a u32>) -> Result<(), Error> {
+        match &self.weights {
+            Some(weights) => {
+                let weight = weights.weight(signer_ids);
+                if weight < weights.threshold {
+                    return Err(Error::InsufficientWeight(weight, weights.threshold));
+                }
+                Ok(())
+            }
+            None => Ok(()),
         }
     }
 
+    /// A span carrying the current round IDs, which every log line for the round is made in
+    fn span(&self) -> Span {
+        info_span!(
+            "coordinator",
+            dkg_id = self.current_dkg_id,
+            sign_id = self.current_sign_id,
+            sign_iter_id = self.current_sign_iter_id,
+        )
+    }
+
     /// Process the message inside the passed packet
     pub fn process_message(
         &mut self,
         packet: &Packet,
     ) -> Result<(Option<Packet>, Option<OperationResult>), Error> {
+        let _span = self.span().entered();
+        if let Some(audit_log) = &mut self.audit_log {
+            audit_log.record(Direction::Inbound, packet);
+        }
+        let message_type = packet.msg.message_type();
+        self.metrics.message_processed(message_type);
+        let (packet, result) = self.handle_message(packet).inspect_err(|_| {
+            self.metrics.round_failed(message_type.into());
+        })?;
+        if let Some(result) = &result {
+            if let Some(started) = self.round_started.take() {
+                let round = match result {
+                    OperationResult::Dkg(_) | OperationResult::ThresholdChange(_) => RoundType::Dkg,
+                    OperationResult::Sign(_) | OperationResult::SignTaproot(_) => RoundType::Sign,
+                };
+                self.metrics.round_completed(round, started.elapsed());
+            }
+            self.last_result = Some(result.clone());
+        }
+        Ok((packet, result))
+    }
+
+    fn handle_message(
+        &mut self,
+        packet: &Packet,
+    ) -> Result<(Option<Packet>, Option<OperationResult>), Error> {
+        if self.ban_list.drops(&packet.msg) {
+            debug!(
+                "Dropping {:?} from banned signer {:?}",
+                packet.msg.message_type(),
+                packet.msg.id().signer_id
+            );
+            return Ok((None, None));
+        }
+
         loop {
             match self.state {
                 State::Idle => {
@@ -98,6 +421,10 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
                     let packet = self.start_public_shares()?;
                     return Ok((Some(packet), None));
                 }
+                State::ThresholdChangeDistribute => {
+                    let packet = self.start_threshold_change_shares()?;
+                    return Ok((Some(packet), None));
+                }
                 State::DkgPublicGather => {
                     self.gather_public_shares(packet)?;
                     if self.state == State::DkgPublicGather {
@@ -115,6 +442,9 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
                         // We need more data
                         return Ok((None, None));
                     } else if self.state == State::Idle {
+                        if self.threshold_change.is_some() {
+                            return self.finish_threshold_change().map(|r| (None, Some(r)));
+                        }
                         // We are done with the DKG round! Return the operation result
                         return Ok((
                             None,
@@ -125,24 +455,46 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
                         ));
                     }
                 }
-                State::NonceRequest(is_taproot, merkle_root) => {
-                    let packet = self.request_nonces(is_taproot, merkle_root)?;
+                State::NonceRequest(is_taproot, tweak) => {
+                    // a retry waits out its backoff first, see poll_retry
+                    if self
+                        .retry_at
+                        .is_some_and(|retry_at| Instant::now() < retry_at)
+                    {
+                        return Ok((None, None));
+                    }
+                    let packet = self.request_nonces(is_taproot, tweak)?;
                     return Ok((Some(packet), None));
                 }
-                State::NonceGather(is_taproot, merkle_root) => {
-                    self.gather_nonces(packet, is_taproot, merkle_root)?;
-                    if self.state == State::NonceGather(is_taproot, merkle_root) {
+                State::NonceGather(is_taproot, tweak) => {
+                    if self.commit_reveal && !self.nonces_revealed {
+                        if let Some(packet) =
+                            self.gather_nonce_commitments(packet, is_taproot, tweak)?
+                        {
+                            return Ok((Some(packet), None));
+                        }
+                    } else {
+                        self.gather_nonces(packet, is_taproot, tweak)?;
+                    }
+                    if self.state == State::NonceGather(is_taproot, tweak) {
                         // We need more data
                         return Ok((None, None));
                     }
                 }
-                State::SigShareRequest(is_taproot, merkle_root) => {
-                    let packet = self.request_sig_shares(is_taproot, merkle_root)?;
+                State::SigShareRequest(is_taproot, tweak) => {
+                    let packet = self.request_sig_shares(is_taproot, tweak)?;
                     return Ok((Some(packet), None));
                 }
-                State::SigShareGather(is_taproot, merkle_root) => {
-                    self.gather_sig_shares(packet, is_taproot, merkle_root)?;
-                    if self.state == State::SigShareGather(is_taproot, merkle_root) {
+                State::SigShareGather(is_taproot, tweak) => {
+                    if let Err(err) = self.gather_sig_shares(packet, is_taproot) {
+                        if !matches!(err, Error::Aggregator(_))
+                            || !(self.replace_bad_signer(packet, &err, is_taproot, tweak)?
+                                || self.schedule_retry(&err, is_taproot, tweak)?)
+                        {
+                            return Err(err);
+                        }
+                    }
+                    if self.state == State::SigShareGather(is_taproot, tweak) {
                         // We need more data
                         return Ok((None, None));
                     } else if self.state == State::Idle {
@@ -178,61 +530,350 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
 
     /// Start a DKG round
     pub fn start_dkg_round(&mut self) -> Result<Packet, Error> {
+        self.start_dkg_round_excluding(&[])
+    }
+
+    /// Start a DKG round in which the `excluded` signers, e.g. ones which failed the last round,
+    /// are sent no private shares; they still deal shares to the others, but end up without keys
+    pub fn start_dkg_round_excluding(&mut self, excluded: &[u32]) -> Result<Packet, Error> {
+        self.abort_threshold_change();
+        self.dkg_excluded = excluded.iter().copied().collect();
         self.current_dkg_id = self.current_dkg_id.wrapping_add(1);
-        info!("Starting DKG round {}", self.current_dkg_id);
+        self.round_started = Some(Instant::now());
+        let _span = self.span().entered();
+        info!("Starting DKG round");
         self.move_to(State::DkgPublicDistribute)?;
         self.start_public_shares()
     }
 
-    /// Start a signing round
+    /// Start a threshold change round, in which signers reshare their keys so that the group key
+    /// stays the same but `threshold` keys are needed to sign
+    pub fn start_threshold_change_round(&mut self, threshold: u32) -> Result<Packet, Error> {
+        self.begin_threshold_change(threshold, None)?;
+        let _span = self.span().entered();
+        info!(threshold, "Starting threshold change");
+        self.start_threshold_change_shares()
+    }
+
+    /// Start a recovery round, in which the other signers re-deal fresh key shares to
+    /// `signer_id` after it lost its keys or they were compromised, keeping the group key and
+    /// threshold.  Its old key shares are no longer valid once the round completes.
+    pub fn start_recovery_round(&mut self, signer_id: u32) -> Result<Packet, Error> {
+        if signer_id >= self.total_signers {
+            return Err(Error::UnknownSigners(vec![signer_id]));
+        }
+
+        self.begin_threshold_change(self.threshold, Some(signer_id))?;
+        let _span = self.span().entered();
+        info!(signer_id, "Starting signer recovery");
+        self.start_threshold_change_shares()
+    }
+
+    /// Save the DKG results so the threshold change can be rolled back, and move to a new round
+    fn begin_threshold_change(
+        &mut self,
+        threshold: u32,
+        recover_signer_id: Option<u32>,
+    ) -> Result<(), Error> {
+        let Some(aggregate_public_key) = self.aggregate_public_key else {
+            return Err(Error::MissingAggregatePublicKey);
+        };
+        if threshold == 0 || threshold > self.total_keys {
+            return Err(Error::InvalidThreshold(threshold, self.total_keys));
+        }
+
+        self.move_to(State::ThresholdChangeDistribute)?;
+        self.threshold_change = Some(ThresholdChange {
+            threshold: self.threshold,
+            aggregate_public_key,
+            party_polynomials: self.party_polynomials.clone(),
+            recover_signer_id,
+        });
+        self.threshold = threshold;
+        self.dkg_excluded.clear();
+        self.current_dkg_id = self.current_dkg_id.wrapping_add(1);
+        self.round_started = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Restore the threshold and DKG results from before the in-flight threshold change, if any
+    fn abort_threshold_change(&mut self) {
+        if let Some(threshold_change) = self.threshold_change.take() {
+            info!("Aborting threshold change");
+            self.threshold = threshold_change.threshold;
+            self.aggregate_public_key = Some(threshold_change.aggregate_public_key);
+            self.party_polynomials = threshold_change.party_polynomials;
+        }
+    }
+
+    /// Keep the new threshold if every signer reshared its keys, otherwise roll back
+    fn finish_threshold_change(&mut self) -> Result<OperationResult, Error> {
+        if !self.dkg_failures.is_empty() {
+            self.abort_threshold_change();
+            return Err(Error::ThresholdChangeFailed(
+                self.dkg_failures.keys().copied().collect(),
+            ));
+        }
+
+        self.threshold_change = None;
+        self.aggregator = Aggregator::new(self.total_keys, self.threshold);
+        info!(threshold = self.threshold, "Threshold changed");
+        Ok(OperationResult::ThresholdChange(self.threshold))
+    }
+
+    /// Start a signing round, asking only the signers in `signer_set` for nonces if it's set
     pub fn start_signing_round(
         &mut self,
         is_taproot: bool,
-        merkle_root: Option<MerkleRoot>,
+        tweak: TaprootTweak,
+        signer_set: Option<HashSet<u32>>,
     ) -> Result<Packet, Error> {
         // We cannot sign if we haven't first set DKG (either manually or via DKG round).
         if self.aggregate_public_key.is_none() {
             return Err(Error::MissingAggregatePublicKey);
         }
+        if let Some(signer_set) = &signer_set {
+            let mut unknown = signer_set
+                .iter()
+                .copied()
+                .filter(|id| *id >= self.total_signers)
+                .collect::<Vec<u32>>();
+            if !unknown.is_empty() {
+                unknown.sort();
+                return Err(Error::UnknownSigners(unknown));
+            }
+            if signer_set.is_empty() {
+                return Err(Error::InsufficientSigners(0, self.threshold));
+            }
+        }
+        self.signer_set = signer_set;
         self.current_sign_id = self.current_sign_id.wrapping_add(1);
-        info!("Starting signing round {}", self.current_sign_id);
-        self.move_to(State::NonceRequest(is_taproot, merkle_root))?;
-        self.request_nonces(is_taproot, merkle_root)
+        self.sign_attempts = 0;
+        self.unresponsive_signers.clear();
+        self.bad_sig_share_signers.clear();
+        self.round_started = Some(Instant::now());
+        let _span = self.span().entered();
+        info!("Starting signing round");
+        self.move_to(State::NonceRequest(is_taproot, tweak))?;
+        self.request_nonces(is_taproot, tweak)
+    }
+
+    /// Give up on the signers which have not yet sent nonces, and request new nonces for the
+    /// next signing iteration from the signers which did respond plus any alternates which
+    /// have not yet failed to respond during this signing round
+    pub fn nonce_timeout(&mut self) -> Result<Packet, Error> {
+        let State::NonceGather(is_taproot, tweak) = self.state else {
+            return Err(Error::BadStateChange(format!(
+                "nonce timeout in {:?}",
+                self.state
+            )));
+        };
+
+        let _span = self.span().entered();
+        info!(waiting_on = ?self.ids_to_await, "Nonce round timed out");
+        self.unresponsive_signers.extend(self.ids_to_await.drain());
+        self.retry_nonces(is_taproot, tweak)?;
+        // the timeout has already been waited out, so any retry backoff is skipped
+        self.request_nonces(is_taproot, tweak)
+    }
+
+    /// Ask for nonces for a retry of the current signing round, once its backoff has passed at
+    /// `now`.  Returns None if no retry is due.
+    pub fn poll_retry(&mut self, now: Instant) -> Result<Option<Packet>, Error> {
+        let State::NonceRequest(is_taproot, tweak) = self.state else {
+            return Ok(None);
+        };
+        if self.retry_at.is_none_or(|retry_at| now < retry_at) {
+            return Ok(None);
+        }
+        let _span = self.span().entered();
+        self.request_nonces(is_taproot, tweak).map(Some)
+    }
+
+    /// If the signer which sent `packet` was just banned for a bad signature share, and the
+    /// other signers could still make up the threshold, start a new signing iteration without
+    /// it straight away.  The shares already gathered are dropped, since every signer has to
+    /// sign again over the new nonces.  Returns whether new nonces will be requested.
+    fn replace_bad_signer(
+        &mut self,
+        packet: &Packet,
+        err: &Error,
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<bool, Error> {
+        let Message::SignatureShareResponse(response) = &packet.msg else {
+            return Ok(false);
+        };
+        let signer_id = response.signer_id;
+        if !self.replace_bad_shares || !self.bad_sig_share_signers.contains(&signer_id) {
+            return Ok(false);
+        }
+        let remaining_keys = self
+            .public_nonces
+            .iter()
+            .filter(|(id, _)| **id != signer_id)
+            .map(|(_, nr)| nr.key_ids.len() as u32)
+            .sum::<u32>();
+        let has_alternates = (0..self.total_signers).any(|id| {
+            self.is_chosen(id)
+                && !self.unresponsive_signers.contains(&id)
+                && !self.ban_list.is_banned(id)
+                && !self.public_nonces.contains_key(&id)
+        });
+        if remaining_keys < self.threshold && !has_alternates {
+            return Ok(false);
+        }
+
+        self.current_sign_iter_id = self.current_sign_iter_id.wrapping_add(1);
+        info!(signer_id, reason = %err, "Replacing signer which sent a bad signature share");
+        self.events.push(CoordinatorEvent::SignerReplaced {
+            sign_id: self.current_sign_id,
+            sign_iter_id: self.current_sign_iter_id,
+            signer_id,
+            reason: err.to_string(),
+        });
+        self.move_to(State::NonceRequest(is_taproot, tweak))?;
+        Ok(true)
+    }
+
+    /// If the retry policy has attempts left, start a new signing iteration, in which every
+    /// signer which isn't banned is asked for nonces again once the backoff has passed, rather
+    /// than failing the signing round with `err`.  Returns whether a retry was scheduled.
+    fn schedule_retry(
+        &mut self,
+        err: &Error,
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<bool, Error> {
+        let Some(retry_policy) = &self.retry_policy else {
+            return Ok(false);
+        };
+        if self.sign_attempts >= retry_policy.max_attempts {
+            return Ok(false);
+        }
+        let delay = retry_policy.delay(self.sign_attempts);
+        let attempt = self.sign_attempts + 1;
+
+        self.current_sign_iter_id = self.current_sign_iter_id.wrapping_add(1);
+        self.unresponsive_signers.clear();
+        self.retry_at = Some(Instant::now() + delay);
+        info!(attempt, ?delay, reason = %err, "Retrying signing round");
+        self.events.push(CoordinatorEvent::SignRetry {
+            sign_id: self.current_sign_id,
+            sign_iter_id: self.current_sign_iter_id,
+            attempt,
+            reason: err.to_string(),
+            delay,
+        });
+        self.move_to(State::NonceRequest(is_taproot, tweak))?;
+        Ok(true)
+    }
+
+    /// Start a new nonce round without the unresponsive signers, or fail the signing round if
+    /// there is nobody left to ask and the responders don't hold enough keys
+    fn retry_nonces(&mut self, is_taproot: bool, tweak: TaprootTweak) -> Result<(), Error> {
+        let has_alternates = (0..self.total_signers).any(|id| {
+            self.is_chosen(id)
+                && !self.unresponsive_signers.contains(&id)
+                && !self.ban_list.is_banned(id)
+                && !self.public_nonces.contains_key(&id)
+        });
+        let num_keys = self.num_nonce_keys();
+        if !has_alternates {
+            let enough = if num_keys < self.threshold {
+                Err(Error::InsufficientSigners(num_keys, self.threshold))
+            } else {
+                self.check_weight(self.public_nonces.keys())
+            };
+            if let Err(err) = enough {
+                if self.schedule_retry(&err, is_taproot, tweak)? {
+                    return Ok(());
+                }
+                self.metrics.round_failed(RoundType::Sign);
+                return Err(err);
+            }
+        }
+
+        self.current_sign_iter_id = self.current_sign_iter_id.wrapping_add(1);
+        self.move_to(State::NonceRequest(is_taproot, tweak))
+    }
+
+    /// Was `signer_id` chosen to sign the current signing round?
+    fn is_chosen(&self, signer_id: u32) -> bool {
+        self.signer_set
+            .as_ref()
+            .is_none_or(|signer_set| signer_set.contains(&signer_id))
+    }
+
+    /// The number of keys held by the signers which have sent nonces this nonce round
+    fn num_nonce_keys(&self) -> u32 {
+        self.public_nonces
+            .values()
+            .map(|nr| nr.key_ids.len() as u32)
+            .sum::<u32>()
+    }
+
+    /// The signers which are sent private shares during the current DKG round
+    fn dkg_signer_ids(&self) -> Vec<u32> {
+        (0..self.total_signers)
+            .filter(|signer_id| !self.dkg_excluded.contains(signer_id))
+            .collect()
     }
 
     /// Ask signers to send DKG public shares
     pub fn start_public_shares(&mut self) -> Result<Packet, Error> {
         self.dkg_public_shares.clear();
+        self.dkg_private_share_signers.clear();
         self.party_polynomials.clear();
-        info!(
-            "DKG Round {}: Starting Public Share Distribution",
-            self.current_dkg_id,
-        );
+        self.dkg_failures.clear();
+        info!("Starting public share distribution");
         let dkg_begin = DkgBegin {
             dkg_id: self.current_dkg_id,
+            epoch: self.epoch,
+            threshold: self.threshold,
+            num_signers: self.total_signers,
+            num_keys: self.total_keys,
+            pedersen: self.pedersen_dkg,
         };
 
-        let dkg_begin_packet = Packet {
-            sig: dkg_begin.sign(&self.message_private_key).expect(""),
-            msg: Message::DkgBegin(dkg_begin),
-        };
+        let dkg_begin_packet = self.sign_packet(Message::DkgBegin(dkg_begin));
         self.move_to(State::DkgPublicGather)?;
         Ok(dkg_begin_packet)
     }
 
+    /// Ask signers to reshare their keys under the new threshold and send DKG public shares
+    pub fn start_threshold_change_shares(&mut self) -> Result<Packet, Error> {
+        self.dkg_public_shares.clear();
+        self.dkg_private_share_signers.clear();
+        self.party_polynomials.clear();
+        self.dkg_failures.clear();
+        info!("Starting threshold change public share distribution");
+        let threshold_change_begin = ThresholdChangeBegin {
+            dkg_id: self.current_dkg_id,
+            threshold: self.threshold,
+            recover_signer_id: self
+                .threshold_change
+                .as_ref()
+                .and_then(|threshold_change| threshold_change.recover_signer_id),
+        };
+
+        let threshold_change_begin_packet =
+            self.sign_packet(Message::ThresholdChangeBegin(threshold_change_begin));
+        self.move_to(State::DkgPublicGather)?;
+        Ok(threshold_change_begin_packet)
+    }
+
     /// Ask signers to send DKG private shares
     pub fn start_private_shares(&mut self) -> Result<Packet, Error> {
-        info!(
-            "DKG Round {}: Starting Private Share Distribution",
-            self.current_dkg_id
-        );
-        let dkg_begin = DkgBegin {
+        let signer_ids = self.dkg_signer_ids();
+        info!(?signer_ids, "Starting private share distribution");
+        let dkg_private_begin = DkgPrivateBegin {
             dkg_id: self.current_dkg_id,
+            signer_ids: signer_ids.clone(),
         };
-        let dkg_private_begin_msg = Packet {
-            sig: dkg_begin.sign(&self.message_private_key).expect(""),
-            msg: Message::DkgPrivateBegin(dkg_begin),
-        };
+        let dkg_private_begin_msg = self.sign_packet(Message::DkgPrivateBegin(dkg_private_begin));
+        // excluded signers have no keys to finish DKG with, so don't wait on them
+        self.ids_to_await = signer_ids.into_iter().collect();
         self.move_to(State::DkgEndGather)?;
         Ok(dkg_private_begin_msg)
     }
@@ -246,6 +887,16 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
                 ));
             }
 
+            let mut invalid_party_ids = dkg_public_shares.invalid_party_ids(self.threshold);
+            invalid_party_ids.extend(
+                dkg_public_shares
+                    .invalid_hiding_party_ids(self.threshold, self.is_pedersen_round()),
+            );
+            if !invalid_party_ids.is_empty() {
+                self.ban_list.ban(dkg_public_shares.signer_id);
+                return Err(Error::InvalidPolyCommitments(invalid_party_ids));
+            }
+
             self.ids_to_await.remove(&dkg_public_shares.signer_id);
 
             self.dkg_public_shares
@@ -260,14 +911,30 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
             );
         }
 
-        if self.ids_to_await.is_empty() {
+        if self.ids_to_await.is_empty() && self.is_pedersen_round() {
+            // the aggregate public key is only known once the signers decommit
+            self.aggregate_public_key = None;
+            self.move_to(State::DkgPrivateDistribute)?;
+            self.ids_to_await = (0..self.total_signers).collect();
+        } else if self.ids_to_await.is_empty() {
             // Calculate the aggregate public key
             let key = self
                 .party_polynomials
                 .iter()
                 .fold(Point::default(), |s, (_, comm)| s + comm.poly[0]);
 
-            info!("Aggregate public key: {}", key);
+            info!(%key, "Aggregate public key");
+            if let Some(threshold_change) = &self.threshold_change {
+                if key != threshold_change.aggregate_public_key {
+                    let expected = threshold_change.aggregate_public_key;
+                    self.abort_threshold_change();
+                    self.move_to(State::Idle)?;
+                    return Err(Error::ThresholdChangeKeyMismatch(
+                        Box::new(expected),
+                        Box::new(key),
+                    ));
+                }
+            }
             self.aggregate_public_key = Some(key);
             self.move_to(State::DkgPrivateDistribute)?;
             self.ids_to_await = (0..self.total_signers).collect();
@@ -280,11 +947,24 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
             "DKG Round {}: waiting for Dkg End from signers {:?}",
             self.current_dkg_id, self.ids_to_await
         );
+        if let Message::DkgPrivateShares(dkg_private_shares) = &packet.msg {
+            if dkg_private_shares.dkg_id == self.current_dkg_id {
+                self.dkg_private_share_signers
+                    .insert(dkg_private_shares.signer_id);
+            }
+            return Ok(());
+        }
+        if let Message::DkgDecommit(dkg_decommit) = &packet.msg {
+            return self.gather_decommit(dkg_decommit);
+        }
         if let Message::DkgEnd(dkg_end) = &packet.msg {
             if dkg_end.dkg_id != self.current_dkg_id {
                 return Err(Error::BadDkgId(dkg_end.dkg_id, self.current_dkg_id));
             }
             self.ids_to_await.remove(&dkg_end.signer_id);
+            if let DkgStatus::Failure(failure) = &dkg_end.status {
+                self.dkg_failures.insert(dkg_end.signer_id, failure.clone());
+            }
             debug!(
                 "DKG_End round {} from signer {}. Waiting on {:?}",
                 dkg_end.dkg_id, dkg_end.signer_id, self.ids_to_await
@@ -294,40 +974,226 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
         if self.ids_to_await.is_empty() {
             self.ids_to_await = (0..self.total_signers).collect();
             self.move_to(State::Idle)?;
+            if self.is_pedersen_round() {
+                self.finish_pedersen_dkg()?;
+            }
+
+            let succeeded = self
+                .dkg_signer_ids()
+                .into_iter()
+                .filter(|signer_id| !self.dkg_failures.contains_key(signer_id))
+                .collect::<Vec<u32>>();
+            if let Err(e) = self.check_weight(&succeeded) {
+                self.abort_threshold_change();
+                return Err(e);
+            }
         }
         Ok(())
     }
 
-    fn request_nonces(
-        &mut self,
-        is_taproot: bool,
-        merkle_root: Option<MerkleRoot>,
-    ) -> Result<Packet, Error> {
-        self.public_nonces.clear();
-        info!(
-            "Sign Round {} Nonce round {} Requesting Nonces",
-            self.current_sign_id, self.current_sign_iter_id,
+    /// Is the current DKG round using Pedersen commitments?  Threshold changes never do, since
+    /// the reshared polynomials are pinned to the existing group key anyway
+    fn is_pedersen_round(&self) -> bool {
+        self.pedersen_dkg && self.threshold_change.is_none()
+    }
+
+    /// Check the polynomial commitments a signer revealed in a Pedersen DKG round against the
+    /// Pedersen commitments it sent, and record them alongside its public shares
+    fn gather_decommit(&mut self, dkg_decommit: &DkgDecommit) -> Result<(), Error> {
+        if dkg_decommit.dkg_id != self.current_dkg_id {
+            return Err(Error::BadDkgId(dkg_decommit.dkg_id, self.current_dkg_id));
+        }
+        let Some(public_shares) = self.dkg_public_shares.get_mut(&dkg_decommit.signer_id) else {
+            return Ok(());
+        };
+        let hiding_comms = public_shares.hiding_comms.iter().cloned().collect();
+        let invalid_party_ids = dkg_decommit.invalid_party_ids(self.threshold, &hiding_comms);
+        if !invalid_party_ids.is_empty() {
+            self.ban_list.ban(dkg_decommit.signer_id);
+            return Err(Error::InvalidPolyCommitments(invalid_party_ids));
+        }
+
+        // keep the revealed commitments with the public shares, so the transcript carries them
+        public_shares.comms = dkg_decommit
+            .comms
+            .iter()
+            .map(|(party_id, comm, _)| (*party_id, comm.clone()))
+            .collect();
+        for (party_id, comm) in &public_shares.comms {
+            self.party_polynomials.insert(*party_id, comm.clone());
+        }
+        debug!(
+            "DKG round {} DkgDecommit from signer {}",
+            dkg_decommit.dkg_id, dkg_decommit.signer_id
         );
+        Ok(())
+    }
+
+    /// Compute the aggregate public key of a Pedersen DKG round from the revealed polynomial
+    /// commitments, once every signer has decommitted
+    fn finish_pedersen_dkg(&mut self) -> Result<(), Error> {
+        let missing = self
+            .dkg_public_shares
+            .iter()
+            .filter(|(_, public_shares)| {
+                public_shares.hiding_comms.len() != public_shares.comms.len()
+            })
+            .map(|(signer_id, _)| *signer_id)
+            .collect::<Vec<u32>>();
+        if !missing.is_empty() {
+            return Err(Error::MissingDecommits(missing));
+        }
+
+        let key = self
+            .party_polynomials
+            .values()
+            .fold(Point::default(), |s, comm| s + comm.poly[0]);
+        info!(%key, "Aggregate public key");
+        self.aggregate_public_key = Some(key);
+        Ok(())
+    }
+
+    fn request_nonces(&mut self, is_taproot: bool, tweak: TaprootTweak) -> Result<Packet, Error> {
+        self.public_nonces.clear();
+        self.nonce_commitments.clear();
+        self.nonces_revealed = false;
+        self.sign_attempts += 1;
+        self.retry_at = None;
+        info!("Requesting nonces");
         let nonce_request = NonceRequest {
             dkg_id: self.current_dkg_id,
             sign_id: self.current_sign_id,
             sign_iter_id: self.current_sign_iter_id,
+            signer_ids: self.signer_set.as_ref().map(|signer_set| {
+                let mut signer_ids = signer_set.iter().copied().collect::<Vec<u32>>();
+                signer_ids.sort();
+                signer_ids
+            }),
+            commit: self.commit_reveal,
         };
-        let nonce_request_msg = Packet {
-            sig: nonce_request.sign(&self.message_private_key).expect(""),
-            msg: Message::NonceRequest(nonce_request),
-        };
-        self.ids_to_await = (0..self.total_signers).collect();
-        self.move_to(State::NonceGather(is_taproot, merkle_root))?;
+        let nonce_request_msg = self.sign_packet(Message::NonceRequest(nonce_request));
+        self.ids_to_await = (0..self.total_signers)
+            .filter(|id| {
+                self.is_chosen(*id)
+                    && !self.unresponsive_signers.contains(id)
+                    && !self.ban_list.is_banned(*id)
+            })
+            .collect();
+        self.move_to(State::NonceGather(is_taproot, tweak))?;
         Ok(nonce_request_msg)
     }
 
+    /// Gather nonce commitments, and once every signer asked has committed or refused, ask the
+    /// ones which committed to reveal their nonces
+    fn gather_nonce_commitments(
+        &mut self,
+        packet: &Packet,
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<Option<Packet>, Error> {
+        match &packet.msg {
+            Message::SignatureShareReject(reject) => {
+                if reject.dkg_id != self.current_dkg_id
+                    || reject.sign_id != self.current_sign_id
+                    || reject.sign_iter_id != self.current_sign_iter_id
+                    || !self.ids_to_await.remove(&reject.signer_id)
+                {
+                    debug!(
+                        "Sign round {} nonce round {} ignoring stale SignatureShareReject from signer {}",
+                        reject.sign_id, reject.sign_iter_id, reject.signer_id
+                    );
+                    return Ok(None);
+                }
+                info!(
+                    signer_id = reject.signer_id,
+                    reason = %reject.reason,
+                    "Signer refused nonce request"
+                );
+                self.unresponsive_signers.insert(reject.signer_id);
+            }
+            Message::NonceCommitment(nonce_commitment) => {
+                if nonce_commitment.dkg_id != self.current_dkg_id {
+                    return Err(Error::BadDkgId(
+                        nonce_commitment.dkg_id,
+                        self.current_dkg_id,
+                    ));
+                }
+                if nonce_commitment.sign_id != self.current_sign_id {
+                    return Err(Error::BadSignId(
+                        nonce_commitment.sign_id,
+                        self.current_sign_id,
+                    ));
+                }
+                if nonce_commitment.sign_iter_id != self.current_sign_iter_id {
+                    return Err(Error::BadSignIterId(
+                        nonce_commitment.sign_iter_id,
+                        self.current_sign_iter_id,
+                    ));
+                }
+                if !self.ids_to_await.remove(&nonce_commitment.signer_id) {
+                    debug!(
+                        "Sign round {} nonce round {} ignoring NonceCommitment from signer {}",
+                        nonce_commitment.sign_id,
+                        nonce_commitment.sign_iter_id,
+                        nonce_commitment.signer_id
+                    );
+                    return Ok(None);
+                }
+                self.nonce_commitments
+                    .insert(nonce_commitment.signer_id, nonce_commitment.clone());
+            }
+            _ => return Ok(None),
+        }
+        if !self.ids_to_await.is_empty() {
+            return Ok(None);
+        }
+        if self.nonce_commitments.is_empty() {
+            self.retry_nonces(is_taproot, tweak)?;
+            return Ok(None);
+        }
+
+        info!("Requesting nonce reveals");
+        let signer_ids = self.nonce_commitments.keys().copied().collect::<Vec<u32>>();
+        self.ids_to_await = signer_ids.iter().copied().collect();
+        self.nonces_revealed = true;
+        let nonce_reveal = NonceReveal {
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+            sign_iter_id: self.current_sign_iter_id,
+            signer_ids,
+        };
+        Ok(Some(self.sign_packet(Message::NonceReveal(nonce_reveal))))
+    }
+
     fn gather_nonces(
         &mut self,
         packet: &Packet,
         is_taproot: bool,
-        merkle_root: Option<MerkleRoot>,
+        tweak: TaprootTweak,
     ) -> Result<(), Error> {
+        if let Message::SignatureShareReject(reject) = &packet.msg {
+            if reject.dkg_id != self.current_dkg_id
+                || reject.sign_id != self.current_sign_id
+                || reject.sign_iter_id != self.current_sign_iter_id
+                || !self.ids_to_await.remove(&reject.signer_id)
+            {
+                debug!(
+                    "Sign round {} nonce round {} ignoring stale SignatureShareReject from signer {}",
+                    reject.sign_id, reject.sign_iter_id, reject.signer_id
+                );
+                return Ok(());
+            }
+            info!(
+                signer_id = reject.signer_id,
+                reason = %reject.reason,
+                "Signer refused nonce request"
+            );
+            // don't ask the signer again this round, but it isn't at fault so don't ban it
+            self.unresponsive_signers.insert(reject.signer_id);
+            if self.ids_to_await.is_empty() && self.num_nonce_keys() < self.threshold {
+                return self.retry_nonces(is_taproot, tweak);
+            }
+        }
         if let Message::NonceResponse(nonce_response) = &packet.msg {
             if nonce_response.dkg_id != self.current_dkg_id {
                 return Err(Error::BadDkgId(nonce_response.dkg_id, self.current_dkg_id));
@@ -344,23 +1210,67 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
                     self.current_sign_iter_id,
                 ));
             }
-
-            self.public_nonces
-                .insert(nonce_response.signer_id, nonce_response.clone());
-            self.ids_to_await.remove(&nonce_response.signer_id);
-            debug!(
-                "Sign round {} nonce round {} NonceResponse from signer {}. Waiting on {:?}",
-                nonce_response.sign_id,
-                nonce_response.sign_iter_id,
-                nonce_response.signer_id,
-                self.ids_to_await
-            );
+            if !self.is_chosen(nonce_response.signer_id)
+                || self
+                    .unresponsive_signers
+                    .contains(&nonce_response.signer_id)
+            {
+                debug!(
+                    "Sign round {} nonce round {} ignoring NonceResponse from unchosen or unresponsive signer {}",
+                    nonce_response.sign_id, nonce_response.sign_iter_id, nonce_response.signer_id
+                );
+                return Ok(());
+            }
+            let nonce_commitment = self.nonce_commitments.get(&nonce_response.signer_id);
+            if self.commit_reveal && nonce_commitment.is_none() {
+                debug!(
+                    "Sign round {} nonce round {} ignoring NonceResponse from uncommitted signer {}",
+                    nonce_response.sign_id, nonce_response.sign_iter_id, nonce_response.signer_id
+                );
+                return Ok(());
+            }
+            if nonce_commitment.is_some_and(|nonce_commitment| {
+                nonce_commitment.commitment != nonce_response.commitment()
+            }) {
+                // the signer changed its nonces after seeing the other commitments, so treat it
+                // like a signer which never sent any
+                warn!(
+                    signer_id = nonce_response.signer_id,
+                    "NonceResponse doesn't match the signer's commitment"
+                );
+                self.ids_to_await.remove(&nonce_response.signer_id);
+                self.unresponsive_signers.insert(nonce_response.signer_id);
+                if self.ids_to_await.is_empty() && self.num_nonce_keys() < self.threshold {
+                    return self.retry_nonces(is_taproot, tweak);
+                }
+            } else {
+                self.public_nonces
+                    .insert(nonce_response.signer_id, nonce_response.clone());
+                self.ids_to_await.remove(&nonce_response.signer_id);
+                debug!(
+                    "Sign round {} nonce round {} NonceResponse from signer {}. Waiting on {:?}",
+                    nonce_response.sign_id,
+                    nonce_response.sign_iter_id,
+                    nonce_response.signer_id,
+                    self.ids_to_await
+                );
+            }
         }
-        if self.ids_to_await.is_empty() {
+        // with an early nonce quorum or signer weights, start signing as soon as the responders
+        // hold enough keys, and carry enough weight if signers are weighted, rather than waiting
+        // on everyone; a few signers holding many keys may be enough on their own
+        let enough_keys = self.num_nonce_keys() >= self.threshold;
+        let weight_met = self
+            .weights
+            .as_ref()
+            .is_none_or(|weights| weights.is_met(self.public_nonces.keys()));
+        let early = self.early_nonce_quorum || self.weights.is_some();
+        if self.ids_to_await.is_empty() || (early && enough_keys && weight_met) {
+            self.check_weight(self.public_nonces.keys())?;
             let aggregate_nonce = self.compute_aggregate_nonce();
-            info!("Aggregate nonce: {}", aggregate_nonce);
+            info!(%aggregate_nonce, "Aggregate nonce");
 
-            self.move_to(State::SigShareRequest(is_taproot, merkle_root))?;
+            self.move_to(State::SigShareRequest(is_taproot, tweak))?;
         }
         Ok(())
     }
@@ -368,15 +1278,14 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
     fn request_sig_shares(
         &mut self,
         is_taproot: bool,
-        merkle_root: Option<MerkleRoot>,
+        tweak: TaprootTweak,
     ) -> Result<Packet, Error> {
         self.signature_shares.clear();
-        info!(
-            "Sign Round {} Requesting Signature Shares",
-            self.current_sign_id,
-        );
-        let nonce_responses = (0..self.total_signers)
-            .map(|i| self.public_nonces[&i].clone())
+        info!("Requesting signature shares");
+        let nonce_responses = self
+            .public_nonces
+            .values()
+            .cloned()
             .collect::<Vec<NonceResponse>>();
         let sig_share_request = SignatureShareRequest {
             dkg_id: self.current_dkg_id,
@@ -384,25 +1293,74 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
             sign_iter_id: self.current_sign_iter_id,
             nonce_responses,
             message: self.message.clone(),
+            is_digest: self.is_digest,
             is_taproot,
-            merkle_root,
-        };
-        let sig_share_request_msg = Packet {
-            sig: sig_share_request.sign(&self.message_private_key).expect(""),
-            msg: Message::SignatureShareRequest(sig_share_request),
+            tweak,
         };
-        self.ids_to_await = (0..self.total_signers).collect();
-        self.move_to(State::SigShareGather(is_taproot, merkle_root))?;
+        let sig_share_request_msg =
+            self.sign_packet(Message::SignatureShareRequest(sig_share_request));
+        self.start_aggregation(is_taproot, tweak)?;
+        self.ids_to_await = self.public_nonces.keys().copied().collect();
+        self.move_to(State::SigShareGather(is_taproot, tweak))?;
 
         Ok(sig_share_request_msg)
     }
 
-    fn gather_sig_shares(
-        &mut self,
-        packet: &Packet,
-        is_taproot: bool,
-        merkle_root: Option<MerkleRoot>,
-    ) -> Result<(), Error> {
+    /// Get the aggregator ready to check the signature shares one at a time as they arrive
+    fn start_aggregation(&mut self, is_taproot: bool, tweak: TaprootTweak) -> Result<(), Error> {
+        let polys: Vec<PolyCommitment> = self.party_polynomials.values().cloned().collect();
+        self.aggregator.init(polys)?;
+
+        let tweak = if is_taproot {
+            let aggregate_public_key = self
+                .aggregate_public_key
+                .ok_or(Error::MissingAggregatePublicKey)?;
+            compute::taproot_tweak(&aggregate_public_key, tweak)
+        } else {
+            Scalar::from(0)
+        };
+        let nonces = self
+            .public_nonces
+            .values()
+            .flat_map(|nr| nr.nonces.clone())
+            .collect::<Vec<PublicNonce>>();
+        let key_ids = self
+            .public_nonces
+            .values()
+            .flat_map(|nr| nr.key_ids.iter())
+            .collect::<Vec<u32>>();
+
+        debug!(
+            "aggregator.start_sign({:?}, {:?}, {:?})",
+            self.message,
+            nonces.len(),
+            key_ids.len()
+        );
+        self.aggregator.start_sign(
+            &self.message,
+            &self.signing_party_ids(),
+            &nonces,
+            &key_ids,
+            &tweak,
+        )?;
+        Ok(())
+    }
+
+    fn gather_sig_shares(&mut self, packet: &Packet, is_taproot: bool) -> Result<(), Error> {
+        if let Message::SignatureShareReject(reject) = &packet.msg {
+            if reject.dkg_id != self.current_dkg_id {
+                return Err(Error::BadDkgId(reject.dkg_id, self.current_dkg_id));
+            }
+            if reject.sign_id != self.current_sign_id {
+                return Err(Error::BadSignId(reject.sign_id, self.current_sign_id));
+            }
+            if self.public_nonces.contains_key(&reject.signer_id) {
+                return Err(Error::SignatureShareRejected(
+                    reject.signer_id,
+                    reject.reason.clone(),
+                ));
+            }
+        }
         if let Message::SignatureShareResponse(sig_share_response) = &packet.msg {
             if sig_share_response.dkg_id != self.current_dkg_id {
                 return Err(Error::BadDkgId(
@@ -416,80 +1374,192 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
                     self.current_sign_id,
                 ));
             }
-            self.signature_shares.insert(
-                sig_share_response.signer_id,
-                sig_share_response.signature_shares.clone(),
-            );
-            self.ids_to_await.remove(&sig_share_response.signer_id);
-            debug!(
-                "Sign round {} SignatureShareResponse from signer {}. Waiting on {:?}",
-                sig_share_response.sign_id, sig_share_response.signer_id, self.ids_to_await
-            );
-        }
-        if self.ids_to_await.is_empty() {
-            // Calculate the aggregate signature
-            let polys: Vec<PolyCommitment> = self.party_polynomials.values().cloned().collect();
-
-            let nonce_responses = (0..self.total_signers)
-                .map(|i| self.public_nonces[&i].clone())
-                .collect::<Vec<NonceResponse>>();
-
-            let nonces = nonce_responses
-                .iter()
-                .flat_map(|nr| nr.nonces.clone())
-                .collect::<Vec<PublicNonce>>();
+            let signer_id = sig_share_response.signer_id;
+            let Some(nonce_response) = self.public_nonces.get(&signer_id) else {
+                debug!(
+                    "Sign round {} ignoring SignatureShareResponse from signer {} which sent no nonces",
+                    sig_share_response.sign_id, signer_id
+                );
+                return Ok(());
+            };
 
-            let key_ids = nonce_responses
+            // the signer must send one share for each of its nonces
+            let mut party_ids = Aggregator::party_ids(signer_id, &nonce_response.key_ids.to_vec());
+            let mut share_ids: Vec<u32> = sig_share_response
+                .signature_shares
                 .iter()
-                .flat_map(|nr| nr.key_ids.clone())
-                .collect::<Vec<u32>>();
+                .map(|share| share.id)
+                .collect();
+            party_ids.sort();
+            share_ids.sort();
+            if share_ids != party_ids {
+                let err = AggregatorError::BadPartySigs(party_ids);
+                return Err(self.record_bad_sig_shares(signer_id, err));
+            }
 
-            let shares = &self
-                .public_nonces
-                .iter()
-                .flat_map(|(i, _)| self.signature_shares[i].clone())
-                .collect::<Vec<SignatureShare>>();
+            // check each share as it arrives, so a bad one is caught without waiting for the rest
+            for share in &sig_share_response.signature_shares {
+                if let Err(err) = self.aggregator.add_share(share.clone()) {
+                    return Err(self.record_bad_sig_shares(signer_id, err));
+                }
+            }
 
+            self.signature_shares
+                .insert(signer_id, sig_share_response.signature_shares.clone());
+            self.ids_to_await.remove(&signer_id);
             debug!(
-                "aggregator.sign({:?}, {:?}, {:?})",
-                self.message,
-                nonces.len(),
-                shares.len()
+                "Sign round {} SignatureShareResponse from signer {}. Waiting on {:?}",
+                sig_share_response.sign_id, signer_id, self.ids_to_await
             );
+        }
 
-            self.aggregator.init(polys)?;
-
+        if let Some(signature) = self.aggregator.try_finalize()? {
             if is_taproot {
-                let schnorr_proof = self.aggregator.sign_taproot(
-                    &self.message,
-                    &nonces,
-                    shares,
-                    &key_ids,
-                    merkle_root,
-                )?;
+                let schnorr_proof = SchnorrProof::new(&signature);
                 info!("SchnorrProof ({}, {})", schnorr_proof.r, schnorr_proof.s);
                 self.schnorr_proof = Some(schnorr_proof);
             } else {
-                let signature = self
-                    .aggregator
-                    .sign(&self.message, &nonces, shares, &key_ids)?;
                 info!("Signature ({}, {})", signature.R, signature.z);
                 self.signature = Some(signature);
             }
-
             self.move_to(State::Idle)?;
         }
         Ok(())
     }
 
+    /// Remember and ban the signer whose signature shares the aggregator rejected
+    fn record_bad_sig_shares(&mut self, signer_id: u32, err: AggregatorError) -> Error {
+        if let AggregatorError::BadPartySigs(_) | AggregatorError::BadPartyKeys(_) = &err {
+            self.bad_sig_share_signers.insert(signer_id);
+            self.ban_list.ban(signer_id);
+        }
+        err.into()
+    }
+
+    /// Check the `statuses` reported by the signers, to see which of them are ready to sign and
+    /// whether they hold enough keys to sign before starting a signing round
+    pub fn readiness_report(&self, statuses: &[SignerStatus]) -> ReadinessReport {
+        let mut report = ReadinessReport::default();
+        for signer_id in 0..self.total_signers {
+            let Some(status) = statuses.iter().find(|status| status.signer_id == signer_id) else {
+                report.not_ready.insert(signer_id, NotReady::Missing);
+                continue;
+            };
+            let not_ready = if self.ban_list.is_banned(signer_id) {
+                Some(NotReady::Banned)
+            } else if !status.has_dkg_output {
+                Some(NotReady::MissingDkgOutput)
+            } else if status.dkg_id != self.current_dkg_id {
+                Some(NotReady::StaleDkg(status.dkg_id))
+            } else if !matches!(
+                status.state,
+                SignerState::Idle | SignerState::SignGather | SignerState::Signed
+            ) {
+                Some(NotReady::InDkg)
+            } else {
+                None
+            };
+
+            match not_ready {
+                Some(reason) => {
+                    report.not_ready.insert(signer_id, reason);
+                }
+                None => {
+                    report.ready.push(signer_id);
+                    report.ready_keys += status.key_ids.len() as u32;
+                }
+            }
+        }
+        report.ready_weight = self
+            .weights
+            .as_ref()
+            .map(|weights| weights.weight(&report.ready));
+        report.can_sign = report.ready_keys >= self.threshold
+            && self
+                .weights
+                .as_ref()
+                .is_none_or(|weights| weights.is_met(&report.ready));
+        report
+    }
+
+    /// A snapshot of how far the current round has got
+    pub fn round_progress(&self) -> RoundProgress<State> {
+        let gathering = matches!(
+            self.state,
+            State::DkgPublicGather
+                | State::DkgEndGather
+                | State::NonceGather(..)
+                | State::SigShareGather(..)
+        );
+        RoundProgress {
+            state: self.state.clone(),
+            dkg_id: self.current_dkg_id,
+            sign_id: self.current_sign_id,
+            sign_iter_id: self.current_sign_iter_id,
+            counts: Phase::ALL
+                .into_iter()
+                .map(|phase| (phase, self.participants(phase).len()))
+                .collect(),
+            missing: if gathering {
+                self.ids_to_await.iter().copied().collect()
+            } else {
+                BTreeSet::new()
+            },
+            elapsed: elapsed_states(&self.state_history, Instant::now()),
+        }
+    }
+
+    /// Report the signers which misbehaved during the current DKG or signing round
+    pub fn misbehavior_report(&self) -> Vec<Misbehavior> {
+        let mut report = vec![];
+        if !self.unresponsive_signers.is_empty() {
+            let mut signer_ids: Vec<u32> = self.unresponsive_signers.iter().copied().collect();
+            signer_ids.sort();
+            report.push(Misbehavior::MissingNonces(signer_ids));
+        }
+        if !self.bad_sig_share_signers.is_empty() {
+            let mut signer_ids: Vec<u32> = self.bad_sig_share_signers.iter().copied().collect();
+            signer_ids.sort();
+            report.push(Misbehavior::BadSigShares(signer_ids));
+        }
+        if !self.dkg_failures.is_empty() {
+            report.push(Misbehavior::DkgFailures(self.dkg_failures.clone()));
+        }
+        report
+    }
+
+    /// The transcript of the last finished DKG round, for auditors to check with
+    /// `audit::verify_transcript`, or None if no DKG round has finished
+    pub fn dkg_transcript(&self) -> Option<DkgTranscript> {
+        if matches!(
+            self.state,
+            State::DkgPublicDistribute
+                | State::DkgPublicGather
+                | State::DkgPrivateDistribute
+                | State::DkgEndGather
+        ) {
+            return None;
+        }
+        Some(DkgTranscript {
+            dkg_id: self.current_dkg_id,
+            threshold: self.threshold,
+            public_shares: self.dkg_public_shares.clone(),
+            failures: self.dkg_failures.clone(),
+            aggregate_public_key: self.aggregate_public_key?,
+        })
+    }
+
+    /// The IDs of the parties which sent nonces, in the same order as the nonces
+    fn signing_party_ids(&self) -> Vec<u32> {
+        self.public_nonces
+            .iter()
+            .flat_map(|(signer_id, pn)| Aggregator::party_ids(*signer_id, &pn.key_ids.to_vec()))
+            .collect()
+    }
+
     #[allow(non_snake_case)]
     fn compute_aggregate_nonce(&self) -> Point {
-        // XXX this needs to be key_ids for v1 and signer_ids for v2
-        let party_ids = self
-            .public_nonces
-            .values()
-            .flat_map(|pn| pn.key_ids.clone())
-            .collect::<Vec<u32>>();
+        let party_ids = self.signing_party_ids();
         let nonces = self
             .public_nonces
             .values()
@@ -504,40 +1574,17 @@ impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
 impl<Aggregator: AggregatorTrait> StateMachine<State, Error> for Coordinator<Aggregator> {
     fn move_to(&mut self, state: State) -> Result<(), Error> {
         self.can_move_to(&state)?;
+        if self.state == State::Idle {
+            self.state_history.clear();
+        }
+        self.state_history.push((state.clone(), Instant::now()));
         self.state = state;
         Ok(())
     }
 
     fn can_move_to(&self, state: &State) -> Result<(), Error> {
         let prev_state = &self.state;
-        let accepted = match state {
-            State::Idle => true,
-            State::DkgPublicDistribute => {
-                prev_state == &State::Idle
-                    || prev_state == &State::DkgPublicGather
-                    || prev_state == &State::DkgEndGather
-            }
-            State::DkgPublicGather => {
-                prev_state == &State::DkgPublicDistribute || prev_state == &State::DkgPublicGather
-            }
-            State::DkgPrivateDistribute => prev_state == &State::DkgPublicGather,
-            State::DkgEndGather => prev_state == &State::DkgPrivateDistribute,
-            State::NonceRequest(_, _) => {
-                prev_state == &State::Idle || prev_state == &State::DkgEndGather
-            }
-            State::NonceGather(is_taproot, merkle_root) => {
-                prev_state == &State::NonceRequest(*is_taproot, *merkle_root)
-                    || prev_state == &State::NonceGather(*is_taproot, *merkle_root)
-            }
-            State::SigShareRequest(is_taproot, merkle_root) => {
-                prev_state == &State::NonceGather(*is_taproot, *merkle_root)
-            }
-            State::SigShareGather(is_taproot, merkle_root) => {
-                prev_state == &State::SigShareRequest(*is_taproot, *merkle_root)
-                    || prev_state == &State::SigShareGather(*is_taproot, *merkle_root)
-            }
-        };
-        if accepted {
+        if prev_state.can_move_to(state) {
             debug!("state change from {:?} to {:?}", prev_state, state);
             Ok(())
         } else {
@@ -550,6 +1597,14 @@ impl<Aggregator: AggregatorTrait> StateMachine<State, Error> for Coordinator<Agg
 }
 
 impl<Aggregator: AggregatorTrait> Coordinatable for Coordinator<Aggregator> {
+    /// Process a single inbound message
+    fn process(
+        &mut self,
+        packet: &Packet,
+    ) -> Result<(Option<Packet>, Option<OperationResult>), Error> {
+        self.process_message(packet)
+    }
+
     /// Process inbound messages
     fn process_inbound_messages(
         &mut self,
@@ -574,6 +1629,16 @@ impl<Aggregator: AggregatorTrait> Coordinatable for Coordinator<Aggregator> {
         self.aggregate_public_key
     }
 
+    /// The signers which have submitted `phase` during the current round
+    fn participants(&self, phase: Phase) -> BTreeSet<u32> {
+        match phase {
+            Phase::DkgPublicShares => self.dkg_public_shares.keys().copied().collect(),
+            Phase::DkgPrivateShares => self.dkg_private_share_signers.clone(),
+            Phase::Nonces => self.public_nonces.keys().copied().collect(),
+            Phase::SignatureShares => self.signature_shares.keys().copied().collect(),
+        }
+    }
+
     /// Set the aggregate public key
     fn set_aggregate_public_key(&mut self, aggregate_public_key: Option<Point>) {
         self.aggregate_public_key = aggregate_public_key;
@@ -584,24 +1649,78 @@ impl<Aggregator: AggregatorTrait> Coordinatable for Coordinator<Aggregator> {
         self.start_dkg_round()
     }
 
+    /// Trigger a threshold change round
+    fn start_threshold_change(&mut self, threshold: u32) -> Result<Packet, Error> {
+        self.start_threshold_change_round(threshold)
+    }
+
+    fn start_recovery(&mut self, signer_id: u32) -> Result<Packet, Error> {
+        self.start_recovery_round(signer_id)
+    }
+
     // Trigger a signing round
     fn start_signing_message(
         &mut self,
         message: &[u8],
         is_taproot: bool,
-        merkle_root: Option<MerkleRoot>,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error> {
+        self.start_signing_message_with_signers(message, None, is_taproot, tweak)
+    }
+
+    // Trigger a signing round with only the chosen signers
+    fn start_signing_message_with_signers(
+        &mut self,
+        message: &[u8],
+        signer_set: Option<HashSet<u32>>,
+        is_taproot: bool,
+        tweak: TaprootTweak,
     ) -> Result<Packet, Error> {
         self.message = message.to_vec();
-        self.start_signing_round(is_taproot, merkle_root)
+        self.is_digest = false;
+        self.start_signing_round(is_taproot, tweak, signer_set)
+    }
+
+    // Trigger a signing round over a digest
+    fn start_signing_digest(
+        &mut self,
+        digest: &[u8; 32],
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error> {
+        self.message = digest.to_vec();
+        self.is_digest = true;
+        self.start_signing_round(is_taproot, tweak, None)
+    }
+
+    /// Request nonces again from the signers which are left
+    fn nonce_timeout(&mut self) -> Result<Packet, Error> {
+        Coordinator::nonce_timeout(self)
+    }
+
+    /// The result of the last operation which completed
+    fn result(&self) -> Option<OperationResult> {
+        self.last_result.clone()
     }
 
     // Reset internal state
     fn reset(&mut self) {
+        self.abort_threshold_change();
         self.state = State::Idle;
         self.dkg_public_shares.clear();
+        self.dkg_private_share_signers.clear();
         self.party_polynomials.clear();
         self.public_nonces.clear();
+        self.nonce_commitments.clear();
+        self.nonces_revealed = false;
         self.signature_shares.clear();
+        self.sign_attempts = 0;
+        self.retry_at = None;
+        self.signer_set = None;
+        self.unresponsive_signers.clear();
+        self.bad_sig_share_signers.clear();
+        self.dkg_failures.clear();
+        self.dkg_excluded.clear();
         self.ids_to_await = (0..self.total_signers).collect();
     }
 }
@@ -1,16 +1,27 @@
-use p256k1::point::Point;
+use hashbrown::HashSet;
+use p256k1::{point::Point, scalar::Scalar};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Duration,
+};
 
 use crate::{
-    common::MerkleRoot, errors::AggregatorError, net::Packet, state_machine::OperationResult,
+    common::TaprootTweak,
+    errors::{self, AggregatorError, ErrorContext},
+    net::{DkgFailure, Packet, RejectReason},
+    state_machine::{BanList, ConfigError, OperationResult, Phase},
 };
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// Coordinator states
 pub enum State {
     /// The coordinator is idle
     Idle,
     /// The coordinator is distributing public shares
     DkgPublicDistribute,
+    /// The coordinator is asking signers to reshare their keys under a new threshold
+    ThresholdChangeDistribute,
     /// The coordinator is gathering public shares
     DkgPublicGather,
     /// The coordinator is distributing private shares
@@ -18,13 +29,277 @@ pub enum State {
     /// The coordinator is gathering DKG End messages
     DkgEndGather,
     /// The coordinator is requesting nonces
-    NonceRequest(bool, Option<MerkleRoot>),
+    NonceRequest(bool, TaprootTweak),
     /// The coordinator is gathering nonces
-    NonceGather(bool, Option<MerkleRoot>),
+    NonceGather(bool, TaprootTweak),
     /// The coordinator is requesting signature shares
-    SigShareRequest(bool, Option<MerkleRoot>),
+    SigShareRequest(bool, TaprootTweak),
     /// The coordinator is gathering signature shares
-    SigShareGather(bool, Option<MerkleRoot>),
+    SigShareGather(bool, TaprootTweak),
+}
+
+impl State {
+    /// Whether a coordinator in this state may move to `next`
+    pub fn can_move_to(&self, next: &State) -> bool {
+        match next {
+            State::Idle => true,
+            State::DkgPublicDistribute => {
+                self == &State::Idle
+                    || self == &State::DkgPublicGather
+                    || self == &State::DkgEndGather
+            }
+            State::ThresholdChangeDistribute => self == &State::Idle,
+            State::DkgPublicGather => {
+                self == &State::DkgPublicDistribute
+                    || self == &State::ThresholdChangeDistribute
+                    || self == &State::DkgPublicGather
+            }
+            State::DkgPrivateDistribute => self == &State::DkgPublicGather,
+            State::DkgEndGather => self == &State::DkgPrivateDistribute,
+            State::NonceRequest(is_taproot, tweak) => {
+                self == &State::Idle
+                    || self == &State::DkgEndGather
+                    || self == &State::NonceGather(*is_taproot, *tweak)
+                    || self == &State::SigShareGather(*is_taproot, *tweak)
+            }
+            State::NonceGather(is_taproot, tweak) => {
+                self == &State::NonceRequest(*is_taproot, *tweak)
+                    || self == &State::NonceGather(*is_taproot, *tweak)
+            }
+            State::SigShareRequest(is_taproot, tweak) => {
+                self == &State::NonceGather(*is_taproot, *tweak)
+            }
+            State::SigShareGather(is_taproot, tweak) => {
+                self == &State::SigShareRequest(*is_taproot, *tweak)
+                    || self == &State::SigShareGather(*is_taproot, *tweak)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Signers which misbehaved during a protocol phase
+pub enum Misbehavior {
+    /// Signers which did not send nonces before timing out
+    MissingNonces(Vec<u32>),
+    /// Signers whose signature shares failed to verify
+    BadSigShares(Vec<u32>),
+    /// Signers which reported a DKG failure, and why it failed
+    DkgFailures(BTreeMap<u32, DkgFailure>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+/// Why a signer isn't ready to sign
+pub enum NotReady {
+    /// The signer didn't report its status
+    Missing,
+    /// The signer is banned
+    Banned,
+    /// The signer has no key shares from DKG
+    MissingDkgOutput,
+    /// The signer's key shares are from another DKG round
+    StaleDkg(u64),
+    /// The signer is in the middle of DKG
+    InDkg,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// Which signers are ready to sign, built from the statuses they reported
+pub struct ReadinessReport {
+    /// signers which are ready to sign
+    pub ready: Vec<u32>,
+    /// signers which aren't ready to sign, and why
+    pub not_ready: BTreeMap<u32, NotReady>,
+    /// the number of keys held by the ready signers
+    pub ready_keys: u32,
+    /// the weight of the ready signers, if the coordinator has signer weights
+    pub ready_weight: Option<u64>,
+    /// whether the ready signers hold enough keys, and carry enough weight, to meet the thresholds
+    pub can_sign: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+/// Arbitrary per-signer weights (e.g. stacked amounts), on top of the key threshold.  When set,
+/// signing rounds and DKG only succeed if the signers taking part carry at least `threshold` weight
+pub struct SignerWeights {
+    /// signer_id -> weight; signers which aren't listed weigh nothing
+    pub weights: BTreeMap<u32, u64>,
+    /// the total weight needed
+    pub threshold: u64,
+}
+
+impl SignerWeights {
+    /// The total weight of `signer_ids`
+    pub fn weight<'a>(&self, signer_ids: impl IntoIterator<Item = &'a u32>) -> u64 {
+        signer_ids
+            .into_iter()
+            .map(|signer_id| self.weights.get(signer_id).copied().unwrap_or(0))
+            .fold(0u64, |total, weight| total.saturating_add(weight))
+    }
+
+    /// The total weight of all signers
+    pub fn total(&self) -> u64 {
+        self.weight(self.weights.keys())
+    }
+
+    /// Do `signer_ids` carry enough weight?
+    pub fn is_met<'a>(&self, signer_ids: impl IntoIterator<Item = &'a u32>) -> bool {
+        self.weight(signer_ids) >= self.threshold
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// How a coordinator retries a signing round by itself, under a new signing iteration, when the
+/// signature shares don't aggregate or the signers which sent nonces don't hold enough keys
+pub struct RetryPolicy {
+    /// the most signing iterations to try, including the first
+    pub max_attempts: u32,
+    /// how long to wait before the first retry, which doubles for each retry after it
+    pub backoff: Duration,
+    /// the longest to wait before any retry
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// How long to wait before retry number `retry`, counting from one
+    pub fn delay(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.backoff.saturating_mul(factor).min(self.max_backoff)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Something which happened while processing a message, so embedding applications can react
+/// without parsing the outbound messages
+pub enum CoordinatorEvent {
+    /// A signing round failed and is being retried under a new signing iteration
+    SignRetry {
+        /// signing round ID
+        sign_id: u64,
+        /// the signing iteration ID of the retry
+        sign_iter_id: u64,
+        /// which attempt the retry is, counting the first try as attempt one
+        attempt: u32,
+        /// why the previous attempt failed
+        reason: String,
+        /// how long the coordinator waits before asking for nonces again
+        delay: Duration,
+    },
+    /// A signer sent a bad signature share, so new nonces were requested without it
+    SignerReplaced {
+        /// signing round ID
+        sign_id: u64,
+        /// the signing iteration ID of the new nonce request
+        sign_iter_id: u64,
+        /// the signer whose share was bad
+        signer_id: u32,
+        /// why the share was rejected
+        reason: String,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Everything needed to construct a coordinator, which can be built in code or loaded from a
+/// config file
+pub struct CoordinatorConfig {
+    /// the total number of signers
+    pub total_signers: u32,
+    /// the total number of keys
+    pub total_keys: u32,
+    /// the threshold of the keys needed for a valid signature
+    pub threshold: u32,
+    /// the private key used to sign messages sent over the network
+    pub message_private_key: Scalar,
+    /// domain separation tag bound into every signed message, empty by default
+    #[serde(default)]
+    pub domain: Vec<u8>,
+    /// signers whose messages are dropped, empty by default
+    #[serde(default)]
+    pub ban_list: BanList,
+    /// per-signer weights which must also meet their own threshold, none by default
+    #[serde(default)]
+    pub weights: Option<SignerWeights>,
+    /// whether to request signature shares as soon as the signers which sent nonces hold
+    /// enough keys, off by default
+    #[serde(default)]
+    pub early_nonce_quorum: bool,
+    /// whether to ask for new nonces as soon as a signer sends a bad signature share, off by
+    /// default
+    #[serde(default)]
+    pub replace_bad_shares: bool,
+    /// how failed signing rounds are retried, or None to leave retries to the caller
+    #[serde(default)]
+    pub retry_policy: Option<RetryPolicy>,
+    /// whether DKG rounds use Pedersen commitments, off by default
+    #[serde(default)]
+    pub pedersen_dkg: bool,
+}
+
+impl CoordinatorConfig {
+    /// Create a config with an empty domain and ban list
+    pub fn new(
+        total_signers: u32,
+        total_keys: u32,
+        threshold: u32,
+        message_private_key: Scalar,
+    ) -> Self {
+        Self {
+            total_signers,
+            total_keys,
+            threshold,
+            message_private_key,
+            domain: Vec::new(),
+            ban_list: BanList::default(),
+            weights: None,
+            early_nonce_quorum: false,
+            replace_bad_shares: false,
+            retry_policy: None,
+            pedersen_dkg: false,
+        }
+    }
+
+    /// Set the domain separation tag used when signing outbound messages
+    pub fn with_domain(mut self, domain: &[u8]) -> Self {
+        self.domain = domain.to_vec();
+        self
+    }
+
+    /// Set the signers whose messages will be dropped
+    pub fn with_ban_list(mut self, ban_list: BanList) -> Self {
+        self.ban_list = ban_list;
+        self
+    }
+
+    /// Set the per-signer weights
+    pub fn with_weights(mut self, weights: SignerWeights) -> Self {
+        self.weights = Some(weights);
+        self
+    }
+
+    /// Check that the thresholds and totals are consistent
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if let Some(weights) = &self.weights {
+            if weights.threshold == 0 || weights.threshold > weights.total() {
+                return Err(ConfigError::InvalidWeightThreshold(
+                    weights.threshold,
+                    weights.total(),
+                ));
+            }
+        }
+        if self.threshold == 0 || self.threshold > self.total_keys {
+            return Err(ConfigError::InvalidThreshold(
+                self.threshold,
+                self.total_keys,
+            ));
+        }
+        if self.total_signers == 0 || self.total_signers > self.total_keys {
+            return Err(ConfigError::InvalidTotalSigners(
+                self.total_signers,
+                self.total_keys,
+            ));
+        }
+        Ok(())
+    }
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -33,6 +308,9 @@ pub enum Error {
     /// A bad state change was made
     #[error("Bad State Change: {0}")]
     BadStateChange(String),
+    /// The config was invalid
+    #[error("Invalid config: {0}")]
+    Config(#[from] ConfigError),
     /// A bad dkg_id in received message
     #[error("Bad dkg_id: got {0} expected {1}")]
     BadDkgId(u64, u64),
@@ -45,6 +323,9 @@ pub enum Error {
     /// SignatureAggregator error
     #[error("Aggregator: {0}")]
     Aggregator(AggregatorError),
+    /// A signer refused to sign the message
+    #[error("Signer {0} refused to sign: {1}")]
+    SignatureShareRejected(u32, RejectReason),
     /// Schnorr proof failed to verify
     #[error("Schnorr Proof failed to verify")]
     SchnorrProofFailed,
@@ -57,6 +338,34 @@ pub enum Error {
     /// No signature set
     #[error("No signature set")]
     MissingSignature,
+    /// DKG polynomial commitments were malformed or failed to prove ownership
+    #[error("Invalid polynomial commitments for party IDs {0:?}")]
+    InvalidPolyCommitments(Vec<u32>),
+    /// Too few keys remain among the responsive signers to meet the threshold
+    #[error("Insufficient signers: {0} keys available, threshold {1}")]
+    InsufficientSigners(u32, u32),
+    /// The signers taking part don't carry enough weight to meet the weight threshold
+    #[error("Insufficient weight: {0} available, threshold {1}")]
+    InsufficientWeight(u64, u64),
+    /// A threshold change asked for a threshold which the total number of keys can't meet
+    #[error("Invalid threshold {0} for {1} keys")]
+    InvalidThreshold(u32, u32),
+    /// The reshared DKG commitments didn't add up to the existing group key
+    #[error("Threshold change would change the group key from {0} to {1}")]
+    ThresholdChangeKeyMismatch(Box<Point>, Box<Point>),
+    /// Signers reported failure during a threshold change, so the old threshold was kept
+    #[error("Threshold change failed for signers {0:?}")]
+    ThresholdChangeFailed(Vec<u32>),
+    /// A pipelined coordinator already has as many signing rounds in flight as it allows
+    #[error("Too many signing rounds in flight: at most {0}")]
+    TooManySignRounds(usize),
+    /// The signers chosen for a signing round included IDs which aren't in the signer set
+    #[error("Unknown signers {0:?} chosen to sign")]
+    UnknownSigners(Vec<u32>),
+    /// A Pedersen DKG round finished without the coordinator seeing these signers reveal their
+    /// polynomial commitments
+    #[error("Signers {0:?} never revealed their polynomial commitments")]
+    MissingDecommits(Vec<u32>),
 }
 
 impl From<AggregatorError> for Error {
@@ -66,28 +375,87 @@ impl From<AggregatorError> for Error {
 }
 
 /// Coordinatable trait for handling the coordination of DKG and sign messages
+///
+/// `frost::Coordinator` is one implementation; alternative coordinators (e.g. ROAST or pipelined
+/// signing) implement this trait so services can swap them in, including as a
+/// `Box<dyn Coordinatable>`
 pub trait Coordinatable {
+    /// Process a single inbound message, returning the next packet to send and the result of any
+    /// operation which completed
+    fn process(
+        &mut self,
+        packet: &Packet,
+    ) -> Result<(Option<Packet>, Option<OperationResult>), Error>;
     /// Process inbound messages
     fn process_inbound_messages(
         &mut self,
         packets: &[Packet],
     ) -> Result<(Vec<Packet>, Vec<OperationResult>), Error>;
+    /// Process inbound messages like `process_inbound_messages`, but on failure say which round
+    /// and sender the failing message belonged to
+    fn process_inbound_messages_with_context(
+        &mut self,
+        packets: &[Packet],
+    ) -> Result<(Vec<Packet>, Vec<OperationResult>), errors::Error> {
+        let mut outbound_packets = vec![];
+        let mut operation_results = vec![];
+        for packet in packets {
+            let (outbound_packet, operation_result) = self
+                .process(packet)
+                .map_err(|e| errors::Error::new(e, ErrorContext::from_message(&packet.msg)))?;
+            outbound_packets.extend(outbound_packet);
+            operation_results.extend(operation_result);
+        }
+        Ok((outbound_packets, operation_results))
+    }
     /// Retrieve the aggregate public key
     fn get_aggregate_public_key(&self) -> Option<Point>;
+    /// The signers which have submitted `phase` during the current round, for dashboards and
+    /// alerting on signers which are lagging
+    fn participants(&self, phase: Phase) -> BTreeSet<u32>;
     /// Set the aggregate public key
     fn set_aggregate_public_key(&mut self, aggregate_public_key: Option<Point>);
     /// Trigger a DKG round
     fn start_distributed_key_generation(&mut self) -> Result<Packet, Error>;
+    /// Trigger a threshold change round, which keeps the group key but requires `threshold` keys to sign
+    fn start_threshold_change(&mut self, threshold: u32) -> Result<Packet, Error>;
+    /// Trigger a recovery round, which re-deals fresh key shares to `signer_id` and invalidates
+    /// its old ones, keeping the group key and threshold
+    fn start_recovery(&mut self, signer_id: u32) -> Result<Packet, Error>;
     /// Trigger a signing round
     fn start_signing_message(
         &mut self,
         message: &[u8],
         is_taproot: bool,
-        merkle_root: Option<MerkleRoot>,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error>;
+    /// Trigger a signing round in which only the signers in `signer_set` are asked for nonces,
+    /// e.g. the lowest latency ones, or every signer if it's None
+    fn start_signing_message_with_signers(
+        &mut self,
+        message: &[u8],
+        signer_set: Option<HashSet<u32>>,
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error>;
+    /// Trigger a signing round over a 32-byte digest which the caller already hashed, e.g. a
+    /// sighash or Stacks block hash, which is signed as-is
+    fn start_signing_digest(
+        &mut self,
+        digest: &[u8; 32],
+        is_taproot: bool,
+        tweak: TaprootTweak,
     ) -> Result<Packet, Error>;
+    /// Stop waiting on the signers which haven't sent nonces, and request nonces again from the
+    /// ones which are left
+    fn nonce_timeout(&mut self) -> Result<Packet, Error>;
+    /// The result of the last DKG, threshold change, or signing round which completed
+    fn result(&self) -> Option<OperationResult>;
     /// Reset internal state
     fn reset(&mut self);
 }
 
 /// The coordinator for the FROST algorithm
 pub mod frost;
+/// A FROST coordinator which runs several signing rounds at once
+pub mod pipelined;
@@ -0,0 +1,335 @@
+use hashbrown::HashSet;
+use p256k1::point::Point;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Instant,
+};
+use tracing::{debug, info};
+
+use crate::{
+    common::TaprootTweak,
+    net::{Message, Packet},
+    state_machine::{
+        coordinator::{frost, Coordinatable, CoordinatorEvent, Error, State},
+        OperationResult, Phase,
+    },
+    traits::Aggregator as AggregatorTrait,
+};
+
+/// A coordinator which runs several signing rounds at once, so it can gather nonces for the next
+/// message while still aggregating signature shares for the previous one
+///
+/// DKG and threshold changes run on a single `frost::Coordinator`, which holds the DKG results.
+/// Each signing round gets its own `frost::Coordinator` sharing those results, and inbound
+/// packets are routed to it by sign ID.  Signers already keep a nonce session per sign ID, so
+/// they need no changes to take part.
+pub struct Coordinator<Aggregator: AggregatorTrait> {
+    /// the coordinator which runs DKG, and holds the DKG results and config for signing rounds
+    pub dkg: frost::Coordinator<Aggregator>,
+    /// the signing rounds in flight, indexed by sign ID
+    pub rounds: BTreeMap<u64, frost::Coordinator<Aggregator>>,
+    /// the most signing rounds which can be in flight at once
+    pub max_rounds: usize,
+    /// the sign ID of the last signing round started
+    current_sign_id: u64,
+    last_result: Option<OperationResult>,
+}
+
+impl<Aggregator: AggregatorTrait> Coordinator<Aggregator> {
+    /// Create a pipelined coordinator which runs DKG on `dkg`, and uses its config and DKG
+    /// results for up to two signing rounds at once
+    pub fn new(dkg: frost::Coordinator<Aggregator>) -> Self {
+        Self {
+            dkg,
+            rounds: Default::default(),
+            max_rounds: 2,
+            current_sign_id: 0,
+            last_result: None,
+        }
+    }
+
+    /// Set the most signing rounds which can be in flight at once
+    pub fn with_max_rounds(mut self, max_rounds: usize) -> Self {
+        self.max_rounds = max_rounds;
+        self
+    }
+
+    /// Process the message inside the passed packet, routing signing messages to their round
+    pub fn process_message(
+        &mut self,
+        packet: &Packet,
+    ) -> Result<(Option<Packet>, Option<OperationResult>), Error> {
+        let result = match &packet.msg {
+            Message::NonceResponse(_)
+            | Message::NonceCommitment(_)
+            | Message::SignatureShareResponse(_)
+            | Message::SignatureShareReject(_) => {
+                let sign_id = packet.msg.id().sign_id;
+                if !self.rounds.contains_key(&sign_id) {
+                    debug!(
+                        "Ignoring {:?} for sign round {} which isn't in flight",
+                        packet.msg.message_type(),
+                        sign_id
+                    );
+                    return Ok((None, None));
+                }
+                self.with_round(sign_id, |round| round.process_message(packet))
+            }
+            _ => self.dkg.process_message(packet),
+        }?;
+        if let Some(result) = &result.1 {
+            self.last_result = Some(result.clone());
+        }
+        Ok(result)
+    }
+
+    /// Start a signing round for `message`, unless `max_rounds` are already in flight
+    pub fn start_signing_round(
+        &mut self,
+        message: &[u8],
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error> {
+        self.start_round(message, false, is_taproot, tweak, None)
+    }
+
+    /// Start a signing round for a 32-byte `digest` which is signed as-is, unless `max_rounds`
+    /// are already in flight
+    pub fn start_signing_digest_round(
+        &mut self,
+        digest: &[u8; 32],
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error> {
+        self.start_round(digest, true, is_taproot, tweak, None)
+    }
+
+    fn start_round(
+        &mut self,
+        message: &[u8],
+        is_digest: bool,
+        is_taproot: bool,
+        tweak: TaprootTweak,
+        signer_set: Option<HashSet<u32>>,
+    ) -> Result<Packet, Error> {
+        if self.dkg.state != State::Idle {
+            return Err(Error::BadStateChange(format!(
+                "signing round in {:?}",
+                self.dkg.state
+            )));
+        }
+        if self.rounds.len() >= self.max_rounds {
+            return Err(Error::TooManySignRounds(self.max_rounds));
+        }
+
+        let sign_id = self.current_sign_id.wrapping_add(1);
+        self.rounds
+            .insert(sign_id, self.dkg.fork_sign_round(sign_id));
+        let packet = self.with_round(sign_id, |round| {
+            round.message = message.to_vec();
+            round.is_digest = is_digest;
+            round.start_signing_round(is_taproot, tweak, signer_set)
+        })?;
+        self.current_sign_id = sign_id;
+        info!(
+            sign_id,
+            in_flight = self.rounds.len(),
+            "Started pipelined signing round"
+        );
+        Ok(packet)
+    }
+
+    /// Give up on the signers which haven't sent nonces for signing round `sign_id`, and
+    /// request nonces again from the ones which are left
+    pub fn nonce_timeout(&mut self, sign_id: u64) -> Result<Packet, Error> {
+        if !self.rounds.contains_key(&sign_id) {
+            return Err(Error::BadSignId(sign_id, self.current_sign_id));
+        }
+        self.with_round(sign_id, |round| round.nonce_timeout())
+    }
+
+    /// Ask for nonces for every signing round whose retry is due at `now`
+    pub fn poll_retries(&mut self, now: Instant) -> Result<Vec<Packet>, Error> {
+        let sign_ids = self.rounds.keys().copied().collect::<Vec<u64>>();
+        let mut packets = vec![];
+        for sign_id in sign_ids {
+            if let Some(packet) = self.with_round(sign_id, |round| round.poll_retry(now))? {
+                packets.push(packet);
+            }
+        }
+        Ok(packets)
+    }
+
+    /// Take the events from every signing round since the last call
+    pub fn take_events(&mut self) -> Vec<CoordinatorEvent> {
+        self.dkg.take_events()
+    }
+
+    /// Abandon every signing round in flight, e.g. because the keys are about to change
+    pub fn abort_signing_rounds(&mut self) {
+        if !self.rounds.is_empty() {
+            info!(sign_ids = ?self.rounds.keys().collect::<Vec<_>>(), "Aborting signing rounds");
+            self.rounds.clear();
+        }
+    }
+
+    /// Run `f` on signing round `sign_id`, which must be in flight, lending it the sequence
+    /// number, metrics, audit log, ban list, and events shared by every round.  The round is
+    /// dropped once it completes or fails.
+    fn with_round<T>(
+        &mut self,
+        sign_id: u64,
+        f: impl FnOnce(&mut frost::Coordinator<Aggregator>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let round = self
+            .rounds
+            .get_mut(&sign_id)
+            .expect("signing round is in flight");
+        lend_shared(&mut self.dkg, round);
+        let result = f(round);
+        lend_shared(&mut self.dkg, round);
+        if result.is_err() || round.state == State::Idle {
+            self.rounds.remove(&sign_id);
+        }
+        result
+    }
+}
+
+/// Swap the state which every round shares between `dkg` and `round`; swapping again gives it back
+fn lend_shared<Aggregator: AggregatorTrait>(
+    dkg: &mut frost::Coordinator<Aggregator>,
+    round: &mut frost::Coordinator<Aggregator>,
+) {
+    std::mem::swap(&mut dkg.seq, &mut round.seq);
+    std::mem::swap(&mut dkg.metrics, &mut round.metrics);
+    std::mem::swap(&mut dkg.audit_log, &mut round.audit_log);
+    std::mem::swap(&mut dkg.ban_list, &mut round.ban_list);
+    std::mem::swap(&mut dkg.events, &mut round.events);
+}
+
+impl<Aggregator: AggregatorTrait> Coordinatable for Coordinator<Aggregator> {
+    /// Process a single inbound message
+    fn process(
+        &mut self,
+        packet: &Packet,
+    ) -> Result<(Option<Packet>, Option<OperationResult>), Error> {
+        self.process_message(packet)
+    }
+
+    /// Process inbound messages
+    fn process_inbound_messages(
+        &mut self,
+        packets: &[Packet],
+    ) -> Result<(Vec<Packet>, Vec<OperationResult>), Error> {
+        let mut outbound_packets = vec![];
+        let mut operation_results = vec![];
+        for packet in packets {
+            let (outbound_packet, operation_result) = self.process_message(packet)?;
+            if let Some(outbound_packet) = outbound_packet {
+                outbound_packets.push(outbound_packet);
+            }
+            if let Some(operation_result) = operation_result {
+                operation_results.push(operation_result);
+            }
+        }
+        Ok((outbound_packets, operation_results))
+    }
+
+    /// Retrieve the aggregate public key
+    fn get_aggregate_public_key(&self) -> Option<Point> {
+        self.dkg.aggregate_public_key
+    }
+
+    /// The signers which have submitted `phase` during the current DKG round, or the signing
+    /// round which was started last
+    fn participants(&self, phase: Phase) -> BTreeSet<u32> {
+        match phase {
+            Phase::DkgPublicShares | Phase::DkgPrivateShares => self.dkg.participants(phase),
+            Phase::Nonces | Phase::SignatureShares => self
+                .rounds
+                .get(&self.current_sign_id)
+                .map(|round| round.participants(phase))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Set the aggregate public key
+    fn set_aggregate_public_key(&mut self, aggregate_public_key: Option<Point>) {
+        self.dkg.aggregate_public_key = aggregate_public_key;
+    }
+
+    /// Trigger a DKG round, abandoning any signing rounds in flight
+    fn start_distributed_key_generation(&mut self) -> Result<Packet, Error> {
+        self.abort_signing_rounds();
+        self.dkg.start_dkg_round()
+    }
+
+    /// Trigger a threshold change round, abandoning any signing rounds in flight
+    fn start_threshold_change(&mut self, threshold: u32) -> Result<Packet, Error> {
+        self.abort_signing_rounds();
+        self.dkg.start_threshold_change_round(threshold)
+    }
+
+    /// Trigger a recovery round, abandoning any signing rounds in flight
+    fn start_recovery(&mut self, signer_id: u32) -> Result<Packet, Error> {
+        self.abort_signing_rounds();
+        self.dkg.start_recovery_round(signer_id)
+    }
+
+    /// Trigger a signing round
+    fn start_signing_message(
+        &mut self,
+        message: &[u8],
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error> {
+        self.start_signing_round(message, is_taproot, tweak)
+    }
+
+    /// Trigger a signing round with only the chosen signers
+    fn start_signing_message_with_signers(
+        &mut self,
+        message: &[u8],
+        signer_set: Option<HashSet<u32>>,
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error> {
+        self.start_round(message, false, is_taproot, tweak, signer_set)
+    }
+
+    /// Trigger a signing round over a digest
+    fn start_signing_digest(
+        &mut self,
+        digest: &[u8; 32],
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<Packet, Error> {
+        self.start_signing_digest_round(digest, is_taproot, tweak)
+    }
+
+    /// Request nonces again for the oldest signing round which is still gathering them
+    fn nonce_timeout(&mut self) -> Result<Packet, Error> {
+        let Some(sign_id) = self
+            .rounds
+            .iter()
+            .find(|(_, round)| matches!(round.state, State::NonceGather(..)))
+            .map(|(sign_id, _)| *sign_id)
+        else {
+            return Err(Error::BadStateChange(
+                "nonce timeout with no signing round gathering nonces".to_string(),
+            ));
+        };
+        Coordinator::nonce_timeout(self, sign_id)
+    }
+
+    /// The result of the last operation which completed
+    fn result(&self) -> Option<OperationResult> {
+        self.last_result.clone()
+    }
+
+    // Reset internal state
+    fn reset(&mut self) {
+        self.abort_signing_rounds();
+        self.dkg.reset();
+    }
+}
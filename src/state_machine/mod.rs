@@ -1,7 +1,19 @@
 use hashbrown::HashMap;
-use p256k1::{ecdsa, point::Point};
+use p256k1::{
+    ecdsa,
+    point::{Compressed, Point},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::{Duration, Instant},
+};
 
-use crate::{common::Signature, taproot::SchnorrProof};
+use crate::{
+    common::Signature,
+    net::{Message, MessageType},
+    taproot::SchnorrProof,
+};
 
 /// A generic state machine
 pub trait StateMachine<S, E> {
@@ -11,6 +23,7 @@ pub trait StateMachine<S, E> {
     fn can_move_to(&self, state: &S) -> Result<(), E>;
 }
 
+#[derive(Clone, Debug, PartialEq)]
 /// Result of a DKG or sign operation
 pub enum OperationResult {
     /// The DKG result
@@ -19,15 +32,453 @@ pub enum OperationResult {
     Sign(Signature),
     /// The sign taproot result
     SignTaproot(SchnorrProof),
+    /// The threshold change result, which is the new threshold
+    ThresholdChange(u32),
 }
 
-#[derive(Default, Clone, Debug)]
+#[derive(Default, Clone, Debug, Serialize, Deserialize)]
 /// Map of signer_id and key_id to the relevant ecdsa public keys
 pub struct PublicKeys {
     /// signer_id -> public key
+    #[serde(with = "public_key_map")]
     pub signers: HashMap<u32, ecdsa::PublicKey>,
-    /// key_id -> public key
+    /// one-based key_id -> public key; use `KeyId` to convert from the zero-based IDs which
+    /// signers use
+    #[serde(with = "public_key_map")]
     pub key_ids: HashMap<u32, ecdsa::PublicKey>,
+    /// public keys of the coordinators which may lead rounds, in failover order; if empty,
+    /// signers accept requests from any coordinator
+    #[serde(default, with = "public_key_vec")]
+    pub coordinators: Vec<ecdsa::PublicKey>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+/// A key ID as signers, aggregators and messages use it, which is zero-based.  `PublicKeys`
+/// registers keys under one-based IDs, so convert with `to_public` and `from_public` rather than
+/// adding or subtracting one by hand.
+pub struct KeyId(pub u32);
+
+impl KeyId {
+    /// The key ID registered as `id` in `PublicKeys::key_ids`, or None for the invalid ID zero
+    pub fn from_public(id: u32) -> Option<Self> {
+        id.checked_sub(1).map(KeyId)
+    }
+
+    /// The ID this key is registered under in `PublicKeys::key_ids`
+    pub fn to_public(self) -> u32 {
+        self.0 + 1
+    }
+}
+
+impl From<u32> for KeyId {
+    fn from(key_id: u32) -> Self {
+        KeyId(key_id)
+    }
+}
+
+impl From<KeyId> for u32 {
+    fn from(key_id: KeyId) -> Self {
+        key_id.0
+    }
+}
+
+/// ecdsa::PublicKey isn't serde-aware, so serialize maps of them with the keys in base58
+mod public_key_map {
+    use hashbrown::HashMap;
+    use p256k1::ecdsa;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        keys: &HashMap<u32, ecdsa::PublicKey>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        keys.iter()
+            .map(|(id, key)| (*id, key.to_string()))
+            .collect::<HashMap<u32, String>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<u32, ecdsa::PublicKey>, D::Error> {
+        HashMap::<u32, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(id, key)| {
+                ecdsa::PublicKey::try_from(key.as_str())
+                    .map(|key| (id, key))
+                    .map_err(|e| D::Error::custom(format!("{e:?}")))
+            })
+            .collect()
+    }
+}
+
+/// Serialize lists of ecdsa::PublicKey with the keys in base58
+mod public_key_vec {
+    use p256k1::ecdsa;
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        keys: &[ecdsa::PublicKey],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        keys.iter()
+            .map(|key| key.to_string())
+            .collect::<Vec<String>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<ecdsa::PublicKey>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|key| {
+                ecdsa::PublicKey::try_from(key.as_str())
+                    .map_err(|e| D::Error::custom(format!("{e:?}")))
+            })
+            .collect()
+    }
+}
+
+impl PublicKeys {
+    /// Create public keys for `total_signers` signers holding `total_keys` keys, checking them
+    /// with `validate`.  At least one coordinator must be given; build the struct directly to
+    /// let signers accept requests from any coordinator.
+    pub fn try_new(
+        total_signers: u32,
+        total_keys: u32,
+        signers: HashMap<u32, ecdsa::PublicKey>,
+        key_ids: HashMap<u32, ecdsa::PublicKey>,
+        coordinators: Vec<ecdsa::PublicKey>,
+    ) -> Result<Self, ConfigError> {
+        if coordinators.is_empty() {
+            return Err(ConfigError::NoCoordinators);
+        }
+        let public_keys = Self {
+            signers,
+            key_ids,
+            coordinators,
+        };
+        public_keys.validate(total_signers, total_keys)?;
+        Ok(public_keys)
+    }
+
+    /// Check that signer IDs `0..total_signers` and key IDs `0..total_keys` are all registered
+    /// and nothing else is, that no two signers share a public key, and that every key belongs
+    /// to a signer and every signer holds a key
+    pub fn validate(&self, total_signers: u32, total_keys: u32) -> Result<(), ConfigError> {
+        let signer_ids = self.signers.keys().copied().collect::<BTreeSet<u32>>();
+        if let Some(signer_id) = signer_ids.range(total_signers..).next() {
+            return Err(ConfigError::InvalidSignerId(*signer_id, total_signers));
+        }
+        if let Some(signer_id) = (0..total_signers).find(|id| !signer_ids.contains(id)) {
+            return Err(ConfigError::UnknownSigner(signer_id));
+        }
+
+        let mut owners = HashMap::new();
+        for signer_id in &signer_ids {
+            if let Some(other_id) = owners.insert(self.signers[signer_id].to_bytes(), *signer_id) {
+                return Err(ConfigError::SharedSignerKey(other_id, *signer_id));
+            }
+        }
+
+        let public_ids = self.key_ids.keys().copied().collect::<BTreeSet<u32>>();
+        for public_id in &public_ids {
+            let key_id = match KeyId::from_public(*public_id) {
+                Some(key_id) if key_id.0 < total_keys => key_id,
+                _ => return Err(ConfigError::InvalidPublicKeyId(*public_id, total_keys)),
+            };
+            if !owners.contains_key(&self.key_ids[public_id].to_bytes()) {
+                return Err(ConfigError::UnownedKeyId(key_id.0));
+            }
+        }
+        if let Some(key_id) =
+            (0..total_keys).find(|id| !public_ids.contains(&KeyId(*id).to_public()))
+        {
+            return Err(ConfigError::MissingKeyId(key_id));
+        }
+
+        let holders = self
+            .key_ids
+            .values()
+            .map(|key| owners[&key.to_bytes()])
+            .collect::<BTreeSet<u32>>();
+        if let Some(signer_id) = signer_ids.iter().find(|id| !holders.contains(*id)) {
+            return Err(ConfigError::SignerWithoutKeys(*signer_id));
+        }
+
+        Ok(())
+    }
+
+    /// The coordinator which leads the round `(dkg_id, sign_id)`, or None if no coordinators are
+    /// registered.  Leadership rotates through `coordinators` as either ID increases, so when a
+    /// leader dies the group can carry on by retrying with the next round ID.
+    pub fn leader(&self, dkg_id: u64, sign_id: u64) -> Option<&ecdsa::PublicKey> {
+        if self.coordinators.is_empty() {
+            return None;
+        }
+        let index = dkg_id.wrapping_add(sign_id) % self.coordinators.len() as u64;
+        self.coordinators.get(index as usize)
+    }
+
+    /// Whether `public_key` belongs to the leader of the round `(dkg_id, sign_id)`.  Any key is
+    /// accepted if no coordinators are registered.
+    pub fn is_leader(&self, dkg_id: u64, sign_id: u64, public_key: &ecdsa::PublicKey) -> bool {
+        self.leader(dkg_id, sign_id)
+            .is_none_or(|leader| leader.to_bytes() == public_key.to_bytes())
+    }
+
+    /// The key IDs which belong to `signer_id`, i.e. which are registered with its public key
+    pub fn signer_key_ids(&self, signer_id: u32) -> Option<BTreeSet<KeyId>> {
+        let signer_key = self.signers.get(&signer_id)?.to_bytes();
+        Some(
+            self.key_ids
+                .iter()
+                .filter(|(_, key)| key.to_bytes() == signer_key)
+                .filter_map(|(key_id, _)| KeyId::from_public(*key_id))
+                .collect(),
+        )
+    }
+
+    /// The public key registered for `key_id`
+    pub fn key(&self, key_id: KeyId) -> Option<&ecdsa::PublicKey> {
+        self.key_ids.get(&key_id.to_public())
+    }
+
+    /// Register `public_key` for `key_id`
+    pub fn insert_key(&mut self, key_id: KeyId, public_key: ecdsa::PublicKey) {
+        self.key_ids.insert(key_id.to_public(), public_key);
+    }
+
+    /// Decompress the public keys into curve points, skipping any which aren't valid points
+    pub fn decompress(&self) -> PublicPoints {
+        PublicPoints {
+            signers: decompress_keys(&self.signers),
+            key_ids: decompress_keys(&self.key_ids),
+        }
+    }
+}
+
+fn decompress_keys(keys: &HashMap<u32, ecdsa::PublicKey>) -> HashMap<u32, Point> {
+    keys.iter()
+        .filter_map(|(id, key)| {
+            Point::try_from(&Compressed::from(key.to_bytes()))
+                .ok()
+                .map(|point| (*id, point))
+        })
+        .collect()
+}
+
+#[derive(Default, Clone, Debug)]
+/// PublicKeys decompressed into curve points, so they can be used for ECDH without
+/// decompressing them on every message
+pub struct PublicPoints {
+    /// signer_id -> public key
+    pub signers: HashMap<u32, Point>,
+    /// key_id -> public key
+    pub key_ids: HashMap<u32, Point>,
+}
+
+#[derive(Default, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// Signers which are excluded from signing for misbehaving; serialize it to persist bans across
+/// restarts.  Banned signers still take part in DKG, since every signer's keys are dealt in each
+/// DKG round, so a round couldn't finish without them.
+pub struct BanList {
+    /// banned signer IDs
+    pub signer_ids: BTreeSet<u32>,
+}
+
+impl BanList {
+    /// Ban `signer_id`, returning true if it wasn't already banned
+    pub fn ban(&mut self, signer_id: u32) -> bool {
+        self.signer_ids.insert(signer_id)
+    }
+
+    /// Lift the ban on `signer_id`, returning true if it was banned
+    pub fn unban(&mut self, signer_id: u32) -> bool {
+        self.signer_ids.remove(&signer_id)
+    }
+
+    /// Is `signer_id` banned?
+    pub fn is_banned(&self, signer_id: u32) -> bool {
+        self.signer_ids.contains(&signer_id)
+    }
+
+    /// Should `message` be dropped, because it's part of a signing round and came from a banned
+    /// signer?
+    pub fn drops(&self, message: &Message) -> bool {
+        let id = message.id();
+        RoundType::from(id.message_type) == RoundType::Sign
+            && id
+                .signer_id
+                .is_some_and(|signer_id| self.is_banned(signer_id))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+/// The kind of round which a metric is recorded for
+pub enum RoundType {
+    /// A DKG or threshold change round
+    Dkg,
+    /// A signing round
+    Sign,
+}
+
+impl From<MessageType> for RoundType {
+    fn from(message_type: MessageType) -> Self {
+        match message_type {
+            MessageType::DkgBegin
+            | MessageType::DkgPublicShares
+            | MessageType::DkgPrivateBegin
+            | MessageType::DkgPrivateShares
+            | MessageType::DkgEnd
+            | MessageType::DkgEcho
+            | MessageType::DkgDecommit
+            | MessageType::ThresholdChangeBegin => RoundType::Dkg,
+            // unknown messages are ignored, so never fail a round
+            MessageType::Unknown => RoundType::Dkg,
+            MessageType::NonceRequest
+            | MessageType::NonceResponse
+            | MessageType::SignatureShareRequest
+            | MessageType::SignatureShareResponse
+            | MessageType::SignatureShareReject
+            | MessageType::NonceCommitment
+            | MessageType::NonceReveal => RoundType::Sign,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+/// A phase of a round in which each participating signer submits something, for asking which
+/// signers have taken part so far
+pub enum Phase {
+    /// signers send DKG public shares
+    DkgPublicShares,
+    /// signers send DKG private shares
+    DkgPrivateShares,
+    /// signers send nonces
+    Nonces,
+    /// signers send signature shares
+    SignatureShares,
+}
+
+impl Phase {
+    /// Every phase, in the order they happen
+    pub const ALL: [Phase; 4] = [
+        Phase::DkgPublicShares,
+        Phase::DkgPrivateShares,
+        Phase::Nonces,
+        Phase::SignatureShares,
+    ];
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// A snapshot of how far the current round has got, which monitoring agents can serialize and
+/// scrape without reaching into the state machine's fields
+pub struct RoundProgress<State> {
+    /// the current state
+    pub state: State,
+    /// DKG round ID
+    pub dkg_id: u64,
+    /// signing round ID
+    pub sign_id: u64,
+    /// signing round iteration ID
+    pub sign_iter_id: u64,
+    /// the number of signers which have submitted each phase of the current round
+    pub counts: BTreeMap<Phase, usize>,
+    /// the signers which are still being waited on in the current state
+    pub missing: BTreeSet<u32>,
+    /// each state the current round has been through, with how long it spent there; the last
+    /// entry is the current state, with how long it has been in it so far
+    pub elapsed: Vec<(State, Duration)>,
+}
+
+/// How long was spent in each of the timestamped `states`, up until `now` for the last one
+fn elapsed_states<State: Clone>(
+    states: &[(State, Instant)],
+    now: Instant,
+) -> Vec<(State, Duration)> {
+    states
+        .iter()
+        .enumerate()
+        .map(|(i, (state, entered))| {
+            let left = states.get(i + 1).map_or(now, |(_, left)| *left);
+            (state.clone(), left.duration_since(*entered))
+        })
+        .collect()
+}
+
+/// Hooks which signers and coordinators call to record counters and gauges, so they can be
+/// exported to Prometheus, statsd, etc. without wsts choosing the backend; every method does
+/// nothing by default
+pub trait Metrics {
+    /// A message of type `message_type` was processed
+    fn message_processed(&self, _message_type: MessageType) {}
+
+    /// A round finished successfully, `duration` after it started
+    fn round_completed(&self, _round: RoundType, _duration: Duration) {}
+
+    /// A round failed, or processing a message for it returned an error
+    fn round_failed(&self, _round: RoundType) {}
+
+    /// The number of signing rounds which a signer is holding private nonces for
+    fn nonce_pool_depth(&self, _depth: usize) {}
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+/// Metrics which aren't recorded anywhere
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq)]
+/// Errors from validating a signer or coordinator config
+pub enum ConfigError {
+    /// The threshold was zero or more than the total number of keys
+    #[error("Invalid threshold {0} for {1} keys")]
+    InvalidThreshold(u32, u32),
+    /// There were no signers, or more signers than keys
+    #[error("Invalid total signers {0} for {1} keys")]
+    InvalidTotalSigners(u32, u32),
+    /// The signer ID was not less than the total number of signers
+    #[error("Invalid signer ID {0} for {1} signers")]
+    InvalidSignerId(u32, u32),
+    /// The signer had no key IDs
+    #[error("No key IDs")]
+    NoKeyIds,
+    /// A key ID was not less than the total number of keys
+    #[error("Invalid key ID {0} for {1} keys")]
+    InvalidKeyId(u32, u32),
+    /// The public keys had no entry for the signer
+    #[error("No public key for signer ID {0}")]
+    UnknownSigner(u32),
+    /// The network private key didn't match the signer's public key
+    #[error("Network private key doesn't match the public key for signer ID {0}")]
+    NetworkKeyMismatch(u32),
+    /// The weight threshold was zero or more than the total weight
+    #[error("Invalid weight threshold {0} for total weight {1}")]
+    InvalidWeightThreshold(u64, u64),
+    /// The key IDs didn't match the ones registered to the signer in the public keys
+    #[error("Key IDs don't match the public keys for signer ID {0}")]
+    KeyIdMismatch(u32),
+    /// Two signers were registered with the same public key
+    #[error("Signer IDs {0} and {1} have the same public key")]
+    SharedSignerKey(u32, u32),
+    /// A one-based key ID in the public keys was zero or more than the total number of keys
+    #[error("Invalid public key ID {0} for {1} keys")]
+    InvalidPublicKeyId(u32, u32),
+    /// A key ID less than the total number of keys had no public key
+    #[error("No public key for key ID {0}")]
+    MissingKeyId(u32),
+    /// A key ID's public key didn't belong to any signer
+    #[error("Key ID {0} doesn't belong to any signer")]
+    UnownedKeyId(u32),
+    /// A signer held none of the key IDs
+    #[error("Signer ID {0} has no key IDs")]
+    SignerWithoutKeys(u32),
+    /// No coordinators were registered in the public keys
+    #[error("No coordinators")]
+    NoCoordinators,
 }
 
 /// State machine for a simple FROST coordinator
@@ -38,26 +489,142 @@ pub mod signer;
 
 #[cfg(test)]
 mod test {
-    use hashbrown::HashMap;
+    use hashbrown::{HashMap, HashSet};
     use p256k1::{ecdsa, point::Point, scalar::Scalar};
     use rand_core::OsRng;
-    use std::sync::atomic::{AtomicBool, Ordering};
+    use sha2::{Digest, Sha256};
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    };
+    use std::time::Duration;
     use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
     use crate::{
-        common::PolyCommitment,
-        net::{DkgPublicShares, DkgStatus, Message, Packet},
+        audit::{verify_transcript, DkgTranscript},
+        common::{Nonce, PolyCommitment, PublicNonce, SignatureShare, TaprootTweak},
+        compute,
+        errors::{ErrorContext, ErrorKind, TranscriptError},
+        net::{
+            BadPrivateShare, BadShareReason, DkgBegin, DkgEnd, DkgFailure, DkgPartyError,
+            DkgPrivateBegin, DkgPublicShares, DkgStatus, Message, MessageType, NonceRequest,
+            NonceResponse, Packet, RejectReason, SignatureShareRequest,
+        },
+        pvss::{self, EncryptedShare},
+        remote::{LocalKeystore, RemoteSigner},
         schnorr::ID,
+        sip018,
         state_machine::{
-            coordinator::{frost::Coordinator, Coordinatable, State as CoordinatorState},
-            signer::{SigningRound, State as SignerState},
-            OperationResult, PublicKeys, StateMachine,
+            coordinator::{
+                frost::Coordinator, pipelined::Coordinator as PipelinedCoordinator, Coordinatable,
+                CoordinatorConfig, CoordinatorEvent, Error as CoordinatorError, Misbehavior,
+                NotReady, RetryPolicy, SignerWeights, State as CoordinatorState,
+            },
+            signer::{
+                Error as SignerError, SignerConfig, SignerEvent, SigningRound,
+                State as SignerState, MAX_SIGN_SESSIONS,
+            },
+            BanList, ConfigError, KeyId, Metrics, OperationResult, Phase, PublicKeys,
+            RoundProgress, RoundType, StateMachine,
         },
-        traits::{Aggregator as AggregatorTrait, Signer as SignerTrait},
-        v1, v2,
+        store::{MemoryStore, StateStore},
+        traits::{Aggregator as AggregatorTrait, ChallengeHash, Signer as SignerTrait},
+        v1, v2, v3,
     };
 
-    static mut LOG_INIT: AtomicBool = AtomicBool::new(false);
+    static LOG_INIT: AtomicBool = AtomicBool::new(false);
+
+    #[test]
+    fn key_ids() {
+        assert_eq!(KeyId::from_public(0), None);
+        assert_eq!(KeyId::from_public(1), Some(KeyId(0)));
+        assert_eq!(KeyId(4).to_public(), 5);
+
+        let mut rng = OsRng;
+        let signer_key = ecdsa::PublicKey::new(&Scalar::random(&mut rng)).unwrap();
+        let other_key = ecdsa::PublicKey::new(&Scalar::random(&mut rng)).unwrap();
+        let mut public_keys = PublicKeys::default();
+        public_keys.signers.insert(0, signer_key);
+        public_keys.signers.insert(1, other_key);
+        public_keys.insert_key(KeyId(0), signer_key);
+        public_keys.insert_key(KeyId(1), signer_key);
+        public_keys.insert_key(KeyId(2), other_key);
+
+        assert_eq!(public_keys.key_ids.len(), 3);
+        assert!(!public_keys.key_ids.contains_key(&0));
+        assert_eq!(
+            public_keys.key(KeyId(2)).map(|key| key.to_bytes()),
+            Some(other_key.to_bytes())
+        );
+        assert_eq!(
+            public_keys.signer_key_ids(0),
+            Some(BTreeSet::from([KeyId(0), KeyId(1)]))
+        );
+        assert_eq!(public_keys.signer_key_ids(2), None);
+    }
+
+    #[test]
+    fn public_keys_validation() {
+        let mut rng = OsRng;
+        let keys = (0..3)
+            .map(|_| ecdsa::PublicKey::new(&Scalar::random(&mut rng)).unwrap())
+            .collect::<Vec<ecdsa::PublicKey>>();
+        let signers = HashMap::from([(0, keys[0]), (1, keys[1])]);
+        let key_ids = HashMap::from([
+            (KeyId(0).to_public(), keys[0]),
+            (KeyId(1).to_public(), keys[1]),
+            (KeyId(2).to_public(), keys[1]),
+        ]);
+        let check = |signers: HashMap<u32, ecdsa::PublicKey>,
+                     key_ids: HashMap<u32, ecdsa::PublicKey>,
+                     expected: ConfigError| {
+            match PublicKeys::try_new(2, 3, signers, key_ids, vec![keys[2]]) {
+                Err(e) => assert_eq!(e, expected),
+                Ok(_) => panic!("expected {expected:?}"),
+            }
+        };
+
+        let public_keys =
+            PublicKeys::try_new(2, 3, signers.clone(), key_ids.clone(), vec![keys[2]]).unwrap();
+        assert!(public_keys.validate(2, 3).is_ok());
+        assert_eq!(
+            PublicKeys::try_new(2, 3, signers.clone(), key_ids.clone(), vec![]).unwrap_err(),
+            ConfigError::NoCoordinators
+        );
+        assert_eq!(
+            public_keys.validate(1, 3).unwrap_err(),
+            ConfigError::InvalidSignerId(1, 1)
+        );
+        assert_eq!(
+            public_keys.validate(3, 3).unwrap_err(),
+            ConfigError::UnknownSigner(2)
+        );
+        assert_eq!(
+            public_keys.validate(2, 2).unwrap_err(),
+            ConfigError::InvalidPublicKeyId(3, 2)
+        );
+        assert_eq!(
+            public_keys.validate(2, 4).unwrap_err(),
+            ConfigError::MissingKeyId(3)
+        );
+
+        let mut shared = signers.clone();
+        shared.insert(1, keys[0]);
+        check(shared, key_ids.clone(), ConfigError::SharedSignerKey(0, 1));
+
+        let mut zero = key_ids.clone();
+        zero.insert(0, keys[0]);
+        check(signers.clone(), zero, ConfigError::InvalidPublicKeyId(0, 3));
+
+        let mut unowned = key_ids.clone();
+        unowned.insert(KeyId(2).to_public(), keys[2]);
+        check(signers.clone(), unowned, ConfigError::UnownedKeyId(2));
+
+        let mut keyless = key_ids.clone();
+        keyless.insert(KeyId(0).to_public(), keys[1]);
+        check(signers, keyless, ConfigError::SignerWithoutKeys(0));
+    }
 
     #[test]
     fn test_coordinator_state_machine_v1() {
@@ -271,21 +838,24 @@ mod test {
 
     fn setup<Aggregator: AggregatorTrait, Signer: SignerTrait>(
     ) -> (Coordinator<Aggregator>, Vec<SigningRound<Signer>>) {
-        unsafe {
-            if let Ok(false) =
-                LOG_INIT.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
-            {
-                tracing_subscriber::registry()
-                    .with(fmt::layer())
-                    .with(EnvFilter::from_default_env())
-                    .init()
-            }
+        setup_with::<Aggregator, Signer>(5, 3, 7)
+    }
+
+    fn setup_with<Aggregator: AggregatorTrait, Signer: SignerTrait>(
+        total_signers: u32,
+        keys_per_signer: u32,
+        threshold: u32,
+    ) -> (Coordinator<Aggregator>, Vec<SigningRound<Signer>>) {
+        if let Ok(false) =
+            LOG_INIT.compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+        {
+            tracing_subscriber::registry()
+                .with(fmt::layer())
+                .with(EnvFilter::from_default_env())
+                .init()
         }
 
         let mut rng = OsRng;
-        let total_signers = 5;
-        let threshold = total_signers / 10 + 7;
-        let keys_per_signer = 3;
         let total_keys = total_signers * keys_per_signer;
         let key_pairs = (0..total_signers)
             .map(|_| {
@@ -301,7 +871,7 @@ mod test {
         for (i, (_private_key, public_key)) in key_pairs.iter().enumerate() {
             let mut key_ids = Vec::new();
             for _ in 0..keys_per_signer {
-                key_ids_map.insert(key_id + 1, *public_key);
+                key_ids_map.insert(KeyId(key_id).to_public(), *public_key);
                 key_ids.push(key_id);
                 key_id += 1;
             }
@@ -311,43 +881,43 @@ mod test {
         let public_keys = PublicKeys {
             signers: signer_ids_map,
             key_ids: key_ids_map,
+            coordinators: Vec::new(),
         };
 
         let signing_rounds = key_pairs
             .iter()
             .enumerate()
             .map(|(signer_id, (private_key, _public_key))| {
-                SigningRound::<Signer>::new(
+                let config = SignerConfig::new(
                     threshold,
-                    total_signers,
-                    total_keys,
                     signer_id as u32,
                     signer_key_ids[&(signer_id as u32)].clone(),
                     *private_key,
                     public_keys.clone(),
-                )
+                );
+                SigningRound::<Signer>::from_config(config).unwrap()
             })
             .collect::<Vec<SigningRound<Signer>>>();
 
-        let coordinator =
-            Coordinator::<Aggregator>::new(total_signers, total_keys, threshold, key_pairs[0].0);
+        let config = CoordinatorConfig::new(total_signers, total_keys, threshold, key_pairs[0].0);
+        let coordinator = Coordinator::<Aggregator>::from_config(config).unwrap();
         (coordinator, signing_rounds)
     }
 
     /// Helper function for feeding messages back from the processor into the signing rounds and coordinator
     fn feedback_messages<Aggregator: AggregatorTrait, Signer: SignerTrait>(
         coordinator: &mut Coordinator<Aggregator>,
-        signing_rounds: &mut Vec<SigningRound<Signer>>,
+        signing_rounds: &mut [SigningRound<Signer>],
         messages: &[Packet],
     ) -> (Vec<Packet>, Vec<OperationResult>) {
         let mut inbound_messages = vec![];
         let mut feedback_messages = vec![];
-        for signing_round in signing_rounds.as_mut_slice() {
+        for signing_round in signing_rounds.iter_mut() {
             let outbound_messages = signing_round.process_inbound_messages(messages).unwrap();
             feedback_messages.extend_from_slice(outbound_messages.as_slice());
             inbound_messages.extend(outbound_messages);
         }
-        for signing_round in signing_rounds.as_mut_slice() {
+        for signing_round in signing_rounds.iter_mut() {
             let outbound_messages = signing_round
                 .process_inbound_messages(&feedback_messages)
                 .unwrap();
@@ -407,13 +977,13 @@ mod test {
         // We have started a signing round
         let msg = vec![1, 2, 3];
         let is_taproot = false;
-        let merkle_root = None;
+        let tweak = TaprootTweak::KeySpendNoScripts;
         let message = coordinator
-            .start_signing_message(&msg, is_taproot, merkle_root)
+            .start_signing_message(&msg, is_taproot, tweak)
             .unwrap();
         assert_eq!(
             coordinator.state,
-            CoordinatorState::NonceGather(is_taproot, merkle_root)
+            CoordinatorState::NonceGather(is_taproot, tweak)
         );
 
         // Send the message to all signers and gather responses by sharing with all other signers and coordinator
@@ -422,7 +992,7 @@ mod test {
         assert!(operation_results.is_empty());
         assert_eq!(
             coordinator.state,
-            CoordinatorState::SigShareGather(is_taproot, merkle_root)
+            CoordinatorState::SigShareGather(is_taproot, tweak)
         );
 
         assert_eq!(outbound_messages.len(), 1);
@@ -452,123 +1022,3452 @@ mod test {
         assert_eq!(coordinator.state, CoordinatorState::Idle);
     }
 
-    #[test]
-    fn dkg_public_share_v1() {
-        dkg_public_share::<v1::Signer>();
+    /// Helper function to run a full DKG round, returning the aggregate public key
+    fn run_dkg<Aggregator: AggregatorTrait, Signer: SignerTrait>(
+        coordinator: &mut Coordinator<Aggregator>,
+        signing_rounds: &mut [SigningRound<Signer>],
+    ) -> Point {
+        let message = coordinator.start_dkg_round().unwrap();
+        let (outbound_messages, _) = feedback_messages(coordinator, signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(coordinator, signing_rounds, &outbound_messages);
+        match operation_results[..] {
+            [OperationResult::Dkg(point)] => point,
+            _ => panic!("Expected Dkg Operation result"),
+        }
     }
 
     #[test]
-    fn dkg_public_share_v2() {
-        dkg_public_share::<v2::Signer>();
-    }
-
-    fn dkg_public_share<Signer: SignerTrait>() {
-        let mut rnd = OsRng;
-        let mut signing_round = SigningRound::<Signer>::new(
-            1,
-            1,
-            1,
-            1,
-            vec![1],
-            Default::default(),
-            Default::default(),
-        );
-        let public_share = DkgPublicShares {
-            dkg_id: 0,
-            signer_id: 0,
-            comms: vec![(
-                0,
-                PolyCommitment {
-                    id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
-                    poly: vec![],
-                },
-            )],
-        };
-        signing_round.dkg_public_share(&public_share).unwrap();
-        assert_eq!(1, signing_round.commitments.len())
+    fn verify_group_key_v1() {
+        verify_group_key::<v1::Aggregator, v1::Signer>();
     }
 
     #[test]
-    fn public_shares_done_v1() {
-        public_shares_done::<v1::Signer>();
+    fn verify_group_key_v2() {
+        verify_group_key::<v2::Aggregator, v2::Signer>();
     }
 
-    #[test]
-    fn public_shares_done_v2() {
-        public_shares_done::<v2::Signer>();
-    }
+    fn verify_group_key<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
 
-    fn public_shares_done<Signer: SignerTrait>() {
-        let mut rnd = OsRng;
-        let mut signing_round = SigningRound::<Signer>::new(
-            1,
-            1,
-            1,
-            1,
-            vec![1],
-            Default::default(),
-            Default::default(),
-        );
-        // publich_shares_done starts out as false
-        assert!(!signing_round.public_shares_done());
+        assert!(matches!(
+            signing_rounds[0].verify_group_key(None),
+            Err(SignerError::MissingCommitments)
+        ));
+        assert!(matches!(
+            signing_rounds[0].compute_aggregate_key(),
+            Err(SignerError::MissingCommitments)
+        ));
 
-        // meet the conditions for all public keys received
-        signing_round.state = SignerState::DkgPublicGather;
-        signing_round.commitments.insert(
-            1,
-            PolyCommitment {
-                id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
-                poly: vec![],
-            },
-        );
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
 
-        // public_shares_done should be true
-        assert!(signing_round.public_shares_done());
+        for signing_round in &signing_rounds {
+            assert_eq!(signing_round.compute_aggregate_key().unwrap(), group_key);
+            assert_eq!(signing_round.verify_group_key(None).unwrap(), group_key);
+            assert_eq!(
+                signing_round.verify_group_key(Some(&group_key)).unwrap(),
+                group_key
+            );
+        }
+
+        let wrong_key = group_key + group_key;
+        assert!(matches!(
+            signing_rounds[0].verify_group_key(Some(&wrong_key)),
+            Err(SignerError::GroupKeyMismatch(_, _))
+        ));
+
+        // corrupt a stored commitment, which should no longer match the signer's key
+        let comm = signing_rounds[1].commitments.values_mut().next().unwrap();
+        comm.poly[0] = comm.poly[0] + comm.poly[0];
+        assert!(matches!(
+            signing_rounds[1].verify_group_key(None),
+            Err(SignerError::GroupKeyMismatch(_, _))
+        ));
     }
 
     #[test]
-    fn can_dkg_end_v1() {
-        can_dkg_end::<v1::Signer>();
+    fn dkg_transcript_v1() {
+        dkg_transcript::<v1::Aggregator, v1::Signer>();
     }
 
     #[test]
-    fn can_dkg_end_v2() {
-        can_dkg_end::<v2::Signer>();
+    fn dkg_transcript_v2() {
+        dkg_transcript::<v2::Aggregator, v2::Signer>();
     }
 
-    fn can_dkg_end<Signer: SignerTrait>() {
-        let mut rnd = OsRng;
-        let mut signing_round = SigningRound::<Signer>::new(
-            1,
-            1,
-            1,
-            1,
-            vec![1],
-            Default::default(),
-            Default::default(),
+    fn dkg_transcript<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        assert!(coordinator.dkg_transcript().is_none());
+
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        let transcript = coordinator.dkg_transcript().unwrap();
+        assert_eq!(transcript.aggregate_public_key, group_key);
+        assert_eq!(transcript.succeeded(), vec![0, 1, 2, 3, 4]);
+
+        // auditors get the transcript serialized, and check it without the coordinator
+        let json = serde_json::to_string(&transcript).unwrap();
+        let transcript: DkgTranscript = serde_json::from_str(&json).unwrap();
+        assert_eq!(verify_transcript(&transcript), Ok(()));
+
+        let mut tampered = transcript.clone();
+        tampered.aggregate_public_key = group_key + group_key;
+        assert_eq!(
+            verify_transcript(&tampered),
+            Err(TranscriptError::KeyMismatch)
         );
-        // can_dkg_end starts out as false
-        assert!(!signing_round.can_dkg_end());
 
-        // meet the conditions for DKG_END
-        signing_round.state = SignerState::DkgPrivateGather;
-        signing_round.commitments.insert(
-            1,
-            PolyCommitment {
-                id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
-                poly: vec![],
-            },
+        // dropping a signer's shares changes the sum of the constant terms
+        let mut tampered = transcript.clone();
+        tampered.public_shares.remove(&4);
+        assert_eq!(
+            verify_transcript(&tampered),
+            Err(TranscriptError::KeyMismatch)
         );
-        let shares: HashMap<u32, Scalar> = HashMap::new();
-        signing_round.decrypted_shares.insert(1, shares);
 
-        // can_dkg_end should be true
-        assert!(signing_round.can_dkg_end());
+        let mut tampered = transcript.clone();
+        let shares = tampered.public_shares.get_mut(&1).unwrap();
+        shares.comms[0].1.poly[0] = group_key;
+        let party_id = shares.comms[0].0;
+        assert_eq!(
+            verify_transcript(&tampered),
+            Err(TranscriptError::InvalidPolyCommitments(1, vec![party_id]))
+        );
+
+        let mut tampered = transcript.clone();
+        let shares = tampered.public_shares.get(&0).unwrap().clone();
+        tampered.public_shares.insert(1, shares);
+        assert_eq!(
+            verify_transcript(&tampered),
+            Err(TranscriptError::SignerMismatch(1, 0))
+        );
+
+        let mut tampered = transcript.clone();
+        tampered.failures.insert(7, DkgFailure::Timeout);
+        assert_eq!(
+            verify_transcript(&tampered),
+            Err(TranscriptError::UnknownComplainant(7))
+        );
+
+        // a complaint from a signer in the round is part of a valid transcript
+        let mut complained = transcript.clone();
+        complained.failures.insert(2, DkgFailure::Timeout);
+        assert_eq!(verify_transcript(&complained), Ok(()));
+        assert_eq!(complained.succeeded(), vec![0, 1, 3, 4]);
+
+        // the transcript isn't available while the next DKG round is running
+        coordinator.start_dkg_round().unwrap();
+        assert!(coordinator.dkg_transcript().is_none());
     }
 
     #[test]
-    fn dkg_ended_v1() {
-        dkg_ended::<v1::Signer>();
+    fn participants_v1() {
+        participants::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn participants_v2() {
+        participants::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn participants<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let all_signers = (0..5).collect::<BTreeSet<u32>>();
+
+        // only signer 2 has sent its public shares so far
+        let message = coordinator.start_dkg_round().unwrap();
+        let public_shares = signing_rounds[2]
+            .process_inbound_messages(std::slice::from_ref(&message))
+            .unwrap();
+        coordinator
+            .process_inbound_messages(&public_shares)
+            .unwrap();
+        assert_eq!(
+            coordinator.participants(Phase::DkgPublicShares),
+            BTreeSet::from([2])
+        );
+        assert!(coordinator.participants(Phase::DkgPrivateShares).is_empty());
+        signing_rounds[0]
+            .process_inbound_messages(&[message])
+            .unwrap();
+        signing_rounds[0]
+            .process_inbound_messages(&public_shares)
+            .unwrap();
+        assert_eq!(
+            signing_rounds[0].participants(Phase::DkgPublicShares),
+            BTreeSet::from([2])
+        );
+
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        for phase in [Phase::DkgPublicShares, Phase::DkgPrivateShares] {
+            assert_eq!(coordinator.participants(phase), all_signers);
+            for signing_round in &signing_rounds {
+                assert_eq!(signing_round.participants(phase), all_signers);
+            }
+        }
+
+        let message = coordinator
+            .start_signing_message(b"message", false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let nonce_signers = coordinator.participants(Phase::Nonces);
+        assert!(!nonce_signers.is_empty());
+        assert!(coordinator.participants(Phase::SignatureShares).is_empty());
+
+        feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        assert_eq!(
+            coordinator.participants(Phase::SignatureShares),
+            nonce_signers
+        );
+        for signing_round in &signing_rounds {
+            assert_eq!(signing_round.participants(Phase::Nonces), nonce_signers);
+            assert!(signing_round
+                .participants(Phase::SignatureShares)
+                .is_empty());
+        }
+    }
+
+    #[test]
+    fn round_progress_v1() {
+        round_progress::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn round_progress_v2() {
+        round_progress::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn round_progress<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+
+        let message = coordinator.start_dkg_round().unwrap();
+        let public_shares = signing_rounds[2]
+            .process_inbound_messages(std::slice::from_ref(&message))
+            .unwrap();
+        coordinator
+            .process_inbound_messages(&public_shares)
+            .unwrap();
+        signing_rounds[0]
+            .process_inbound_messages(&[message])
+            .unwrap();
+        signing_rounds[0]
+            .process_inbound_messages(&public_shares)
+            .unwrap();
+
+        let progress = coordinator.round_progress();
+        assert_eq!(progress.state, CoordinatorState::DkgPublicGather);
+        assert_eq!(progress.dkg_id, 1);
+        assert_eq!(progress.counts[&Phase::DkgPublicShares], 1);
+        assert_eq!(progress.counts[&Phase::Nonces], 0);
+        assert_eq!(progress.missing, BTreeSet::from([0, 1, 3, 4]));
+        let states = progress
+            .elapsed
+            .iter()
+            .map(|(state, _)| state.clone())
+            .collect::<Vec<CoordinatorState>>();
+        assert_eq!(
+            states,
+            vec![
+                CoordinatorState::DkgPublicDistribute,
+                CoordinatorState::DkgPublicGather
+            ]
+        );
+
+        // the signer is waiting on the shares it hasn't seen, but not its own
+        let progress = signing_rounds[0].round_progress();
+        assert_eq!(progress.state, SignerState::DkgPublicGather);
+        assert_eq!(progress.counts[&Phase::DkgPublicShares], 1);
+        assert_eq!(progress.missing, BTreeSet::from([1, 3, 4]));
+
+        // monitoring agents get it as JSON
+        let json = serde_json::to_string(&progress).unwrap();
+        let decoded: RoundProgress<SignerState> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, progress);
+
+        // a finished round isn't waiting on anyone
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        let progress = coordinator.round_progress();
+        assert_eq!(progress.state, CoordinatorState::Idle);
+        assert!(progress.missing.is_empty());
+        assert_eq!(progress.counts[&Phase::DkgPrivateShares], 5);
+        assert_eq!(
+            progress.elapsed.last().map(|(state, _)| state.clone()),
+            Some(CoordinatorState::Idle)
+        );
+        assert!(signing_rounds[1].round_progress().missing.is_empty());
+    }
+
+    #[test]
+    fn coordinator_failover_v1() {
+        coordinator_failover::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn coordinator_failover_v2() {
+        coordinator_failover::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn coordinator_failover<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut primary, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let backup_private_key = Scalar::random(&mut OsRng);
+        let mut backup = Coordinator::<Aggregator>::new(5, 15, 7, backup_private_key);
+
+        let primary_key = ecdsa::PublicKey::new(&primary.message_private_key).unwrap();
+        let backup_key = ecdsa::PublicKey::new(&backup_private_key).unwrap();
+        for signing_round in signing_rounds.iter_mut() {
+            signing_round.public_keys.coordinators = vec![primary_key, backup_key];
+        }
+
+        // leadership rotates with the round IDs
+        let public_keys = &signing_rounds[0].public_keys;
+        assert!(public_keys.is_leader(0, 0, &primary_key));
+        assert!(public_keys.is_leader(1, 0, &backup_key));
+        assert!(public_keys.is_leader(1, 1, &primary_key));
+        assert!(!public_keys.is_leader(1, 1, &backup_key));
+        assert!(PublicKeys::default().is_leader(1, 1, &backup_key));
+
+        // the primary doesn't lead DKG round 1, so the signers ignore it
+        let message = primary.start_dkg_round().unwrap();
+        let (outbound_messages, operation_results) =
+            feedback_messages(&mut primary, &mut signing_rounds, &[message]);
+        assert!(outbound_messages.is_empty());
+        assert!(operation_results.is_empty());
+        assert!(signing_rounds
+            .iter()
+            .all(|signing_round| signing_round.state == SignerState::Idle));
+
+        // the backup leads DKG round 1, but the primary leads signing round (1, 1), so the
+        // backup gives up on it and carries on with signing round (1, 2)
+        let group_key = run_dkg(&mut backup, &mut signing_rounds);
+        let msg = b"the leader is dead, long live the leader".to_vec();
+        let message = backup
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut backup, &mut signing_rounds, &[message]);
+        assert!(outbound_messages.is_empty());
+
+        backup.state = CoordinatorState::Idle;
+        let message = backup
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut backup, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut backup, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
+
+    #[test]
+    fn epoch_transition_v1() {
+        epoch_transition::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn epoch_transition_v2() {
+        epoch_transition::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn epoch_transition<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let old_group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // signer 0 leaves in epoch 1, and everyone else moves down one signer ID
+        let old_keys = signing_rounds[0].public_keys.clone();
+        let mut new_keys = PublicKeys::default();
+        for signer_id in 1..5 {
+            let public_key = old_keys.signers[&signer_id];
+            new_keys.signers.insert(signer_id - 1, public_key);
+            for key_id in 0..3 {
+                new_keys.insert_key(KeyId((signer_id - 1) * 3 + key_id), public_key);
+            }
+        }
+        let mut signing_rounds = signing_rounds.split_off(1);
+        for signing_round in signing_rounds.iter_mut() {
+            signing_round.add_epoch(1, new_keys.clone());
+        }
+
+        // the coordinator can't change signer sets in the middle of a round
+        coordinator
+            .start_signing_message(b"msg", false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        assert!(matches!(
+            coordinator.start_epoch(1, 4, 12),
+            Err(CoordinatorError::BadStateChange(_))
+        ));
+        coordinator.state = CoordinatorState::Idle;
+
+        coordinator.start_epoch(1, 4, 12).unwrap();
+        assert!(coordinator.aggregate_public_key.is_none());
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        assert_ne!(group_key, old_group_key);
+        for (signer_id, signing_round) in signing_rounds.iter().enumerate() {
+            let signer_id = signer_id as u32;
+            assert_eq!(signing_round.epoch, 1);
+            assert_eq!(signing_round.signer_id, signer_id);
+            assert_eq!(signing_round.total_keys, 12);
+            assert_eq!(
+                signing_round.signer.get_key_ids(),
+                (signer_id * 3..signer_id * 3 + 3).collect::<Vec<u32>>()
+            );
+        }
+
+        let msg = b"new signer set, same signers".to_vec();
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // a signer can't move to an epoch it hasn't been told about
+        coordinator.start_epoch(2, 4, 12).unwrap();
+        let message = coordinator.start_dkg_round().unwrap();
+        assert!(matches!(
+            signing_rounds[0].process(&message.msg),
+            Err(SignerError::UnknownEpoch(2))
+        ));
+    }
+
+    #[test]
+    fn export_encrypted_v1() {
+        export_encrypted::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn export_encrypted_v2() {
+        export_encrypted::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn export_encrypted<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let signing_round = &signing_rounds[0];
+        let passphrase = b"correct horse battery staple";
+        // use a low iteration count so the test runs quickly in debug builds
+        let bytes = signing_round
+            .export_encrypted_with_iterations(passphrase, 1000)
+            .unwrap();
+
+        let restored = SigningRound::<Signer>::import_encrypted(
+            &bytes,
+            passphrase,
+            signing_round.network_private_key,
+            signing_round.public_keys.clone(),
+        )
+        .unwrap();
+        assert_eq!(restored.dkg_id, signing_round.dkg_id);
+        assert_eq!(restored.signer_id, signing_round.signer_id);
+        assert_eq!(
+            restored.signer.get_key_ids(),
+            signing_round.signer.get_key_ids()
+        );
+        assert_eq!(
+            restored.verify_group_key(Some(&group_key)).unwrap(),
+            group_key
+        );
+
+        assert!(matches!(
+            SigningRound::<Signer>::import_encrypted(
+                &bytes,
+                b"wrong passphrase",
+                signing_round.network_private_key,
+                signing_round.public_keys.clone(),
+            ),
+            Err(SignerError::Decryption)
+        ));
+
+        // the same blob can be kept in a store
+        let mut store = MemoryStore::default();
+        assert!(SigningRound::<Signer>::load_from_store(
+            &store,
+            "signer",
+            passphrase,
+            signing_round.network_private_key,
+            signing_round.public_keys.clone(),
+        )
+        .unwrap()
+        .is_none());
+        store.put("signer", &bytes).unwrap();
+        let restored = SigningRound::<Signer>::load_from_store(
+            &store,
+            "signer",
+            passphrase,
+            signing_round.network_private_key,
+            signing_round.public_keys.clone(),
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            restored.verify_group_key(Some(&group_key)).unwrap(),
+            group_key
+        );
+    }
+
+    #[test]
+    fn backup_recover_v1() {
+        backup_recover::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn backup_recover_v2() {
+        backup_recover::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn backup_recover<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let signing_round = &signing_rounds[0];
+        let network_private_key = signing_round.network_private_key;
+        let public_keys = signing_round.public_keys.clone();
+
+        assert!(matches!(
+            signing_round.backup(4, 3, &mut OsRng),
+            Err(SignerError::InvalidBackupThreshold(4, 3))
+        ));
+
+        let shares = signing_round.backup(3, 5, &mut OsRng).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        for subset in [&shares[..3], &shares[2..], &shares[1..4]] {
+            let restored =
+                SigningRound::<Signer>::recover(subset, network_private_key, public_keys.clone())
+                    .unwrap();
+            assert_eq!(restored.signer_id, signing_round.signer_id);
+            assert_eq!(
+                restored.signer.get_key_ids(),
+                signing_round.signer.get_key_ids()
+            );
+            assert_eq!(
+                restored.verify_group_key(Some(&group_key)).unwrap(),
+                group_key
+            );
+        }
+
+        assert!(matches!(
+            SigningRound::<Signer>::recover(&shares[..2], network_private_key, public_keys.clone()),
+            Err(SignerError::NotEnoughBackupShares(2, 3))
+        ));
+
+        let duplicated = vec![shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(matches!(
+            SigningRound::<Signer>::recover(&duplicated, network_private_key, public_keys.clone()),
+            Err(SignerError::InconsistentBackupShares)
+        ));
+
+        // shares from a different backup can't be mixed in
+        let other_shares = signing_round.backup(3, 5, &mut OsRng).unwrap();
+        let mixed = vec![
+            shares[0].clone(),
+            shares[1].clone(),
+            other_shares[2].clone(),
+        ];
+        assert!(matches!(
+            SigningRound::<Signer>::recover(&mixed, network_private_key, public_keys),
+            Err(SignerError::InconsistentBackupShares)
+        ));
+    }
+
+    #[test]
+    fn taproot_output_key_v1() {
+        taproot_output_key::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn taproot_output_key_v2() {
+        taproot_output_key::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn taproot_output_key<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let tweak = TaprootTweak::KeySpend {
+            merkle_root: compute::merkle_root("OP_1".as_bytes()),
+        };
+
+        assert!(matches!(
+            signing_rounds[0].taproot_output_key(tweak),
+            Err(SignerError::MissingCommitments)
+        ));
+
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        let output_key = signing_rounds[0].taproot_output_key(tweak).unwrap();
+        assert_eq!(
+            output_key,
+            compute::tweaked_public_key(&group_key, tweak).x()
+        );
+
+        let msg = vec![1, 2, 3];
+        let message = coordinator
+            .start_signing_message(&msg, true, tweak)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::SignTaproot(proof)] => assert!(proof.verify(&output_key, &msg)),
+            _ => panic!("Expected SignTaproot Operation result"),
+        }
+    }
+
+    #[test]
+    fn threshold_change_v1() {
+        threshold_change::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn threshold_change_v2() {
+        threshold_change::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn threshold_change<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+
+        assert!(matches!(
+            coordinator.start_threshold_change(4),
+            Err(CoordinatorError::MissingAggregatePublicKey)
+        ));
+
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        assert!(matches!(
+            coordinator.start_threshold_change(0),
+            Err(CoordinatorError::InvalidThreshold(0, 15))
+        ));
+        assert!(matches!(
+            coordinator.start_threshold_change(16),
+            Err(CoordinatorError::InvalidThreshold(16, 15))
+        ));
+
+        // lower then raise the threshold, each time signing with just enough signers
+        for threshold in [4, 10] {
+            let message = coordinator.start_threshold_change(threshold).unwrap();
+            let (outbound_messages, _) =
+                feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+            let (_, operation_results) =
+                feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+            match operation_results[..] {
+                [OperationResult::ThresholdChange(t)] => assert_eq!(t, threshold),
+                _ => panic!("Expected ThresholdChange Operation result"),
+            }
+            assert_eq!(coordinator.threshold, threshold);
+            assert_eq!(coordinator.aggregate_public_key, Some(group_key));
+            for signing_round in &signing_rounds {
+                assert_eq!(signing_round.threshold, threshold);
+                assert!(signing_round.threshold_change.is_none());
+                assert_eq!(
+                    signing_round.verify_group_key(Some(&group_key)).unwrap(),
+                    group_key
+                );
+            }
+
+            // each signer holds 3 keys
+            let num_signers = threshold.div_ceil(3) as usize;
+            let online = &mut signing_rounds[..num_signers];
+            let msg = vec![1, 2, 3];
+            let message = coordinator
+                .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+                .unwrap();
+            feedback_messages(&mut coordinator, online, &[message]);
+            let message = coordinator.nonce_timeout().unwrap();
+            let (outbound_messages, _) = feedback_messages(&mut coordinator, online, &[message]);
+            let (_, operation_results) =
+                feedback_messages(&mut coordinator, online, &outbound_messages);
+            match &operation_results[..] {
+                [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &msg)),
+                _ => panic!("Expected Signature Operation result"),
+            }
+
+            // one signer fewer can't meet the new threshold
+            let message = coordinator
+                .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+                .unwrap();
+            feedback_messages(&mut coordinator, &mut online[..num_signers - 1], &[message]);
+            let num_keys = 3 * (num_signers as u32 - 1);
+            assert!(matches!(
+                coordinator.nonce_timeout(),
+                Err(CoordinatorError::InsufficientSigners(n, t)) if n == num_keys && t == threshold
+            ));
+            coordinator.reset();
+        }
+    }
+
+    #[test]
+    fn bad_threshold_change_v1() {
+        bad_threshold_change::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn bad_threshold_change_v2() {
+        bad_threshold_change::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn bad_threshold_change<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        let threshold_change_begin = coordinator.start_threshold_change(4).unwrap();
+
+        let mut public_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            public_shares.extend(signing_round.process(&threshold_change_begin.msg).unwrap());
+        }
+
+        // signer 4 commits to a fresh secret rather than resharing its keys
+        let fresh = Signer::new(4, &[12, 13, 14], 5, 15, 4, &mut OsRng);
+        if let Message::DkgPublicShares(shares) = &mut public_shares[4] {
+            shares.comms = fresh
+                .get_poly_commitments(&mut OsRng)
+                .into_iter()
+                .map(|comm| (comm.id.id.get_u32(), comm))
+                .collect();
+        }
+        assert!(matches!(
+            signing_rounds[0].process(&public_shares[4]),
+            Err(SignerError::InvalidThresholdChange(4))
+        ));
+        assert!(signing_rounds[0].ban_list.is_banned(4));
+
+        let packets = public_shares
+            .into_iter()
+            .map(|msg| Packet {
+                msg,
+                seq: 0,
+                sig: vec![],
+            })
+            .collect::<Vec<Packet>>();
+        assert!(matches!(
+            coordinator.process_inbound_messages(&packets),
+            Err(CoordinatorError::ThresholdChangeKeyMismatch(..))
+        ));
+        assert_eq!(coordinator.threshold, 7);
+        assert_eq!(coordinator.aggregate_public_key, Some(group_key));
+        assert_eq!(coordinator.state, CoordinatorState::Idle);
+
+        // a fresh DKG round rolls the signers back before starting over
+        let dkg_begin = coordinator.start_dkg_round().unwrap();
+        signing_rounds[1].process(&dkg_begin.msg).unwrap();
+        assert_eq!(signing_rounds[1].threshold, 7);
+        assert!(signing_rounds[1].threshold_change.is_none());
+    }
+
+    #[test]
+    fn recover_signer_v1() {
+        recover_signer::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn recover_signer_v2() {
+        recover_signer::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn recover_signer<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        assert!(matches!(
+            coordinator.start_recovery(5),
+            Err(CoordinatorError::UnknownSigners(ids)) if ids == vec![5]
+        ));
+
+        // signer 2 loses its keys, and comes back with nothing but its config
+        let config = SignerConfig::new(
+            7,
+            2,
+            vec![6, 7, 8],
+            signing_rounds[2].network_private_key,
+            signing_rounds[2].public_keys.clone(),
+        );
+        let fresh = SigningRound::<Signer>::from_config(config).unwrap();
+        let old = std::mem::replace(&mut signing_rounds[2], fresh);
+
+        let message = coordinator.start_recovery(2).unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match operation_results[..] {
+            [OperationResult::ThresholdChange(t)] => assert_eq!(t, 7),
+            _ => panic!("Expected ThresholdChange Operation result"),
+        }
+        assert_eq!(coordinator.threshold, 7);
+        assert_eq!(coordinator.aggregate_public_key, Some(group_key));
+        for signing_round in &signing_rounds {
+            assert!(signing_round.threshold_change.is_none());
+            assert_eq!(
+                signing_round.verify_group_key(Some(&group_key)).unwrap(),
+                group_key
+            );
+        }
+
+        // the recovered signer can sign with just enough others
+        let msg = vec![1, 2, 3];
+        let online = &mut signing_rounds[1..4];
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        feedback_messages(&mut coordinator, online, &[message]);
+        let message = coordinator.nonce_timeout().unwrap();
+        let (outbound_messages, _) = feedback_messages(&mut coordinator, online, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, online, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // but its old key shares no longer work, even if whoever holds them catches up to the
+        // current DKG round
+        signing_rounds[2] = old;
+        signing_rounds[2].dkg_id = coordinator.current_dkg_id;
+        let online = &mut signing_rounds[1..4];
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        feedback_messages(&mut coordinator, online, &[message]);
+        let message = coordinator.nonce_timeout().unwrap();
+        let (outbound_messages, _) = feedback_messages(&mut coordinator, online, &[message]);
+        let responses = online
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&outbound_messages)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        assert!(matches!(
+            coordinator.process_inbound_messages(&responses),
+            Err(CoordinatorError::Aggregator(_))
+        ));
+    }
+
+    #[test]
+    fn dkg_params_mismatch_v1() {
+        dkg_params_mismatch::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn dkg_params_mismatch_v2() {
+        dkg_params_mismatch::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn dkg_params_mismatch<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let dkg_begin = coordinator.start_dkg_round().unwrap();
+        let Message::DkgBegin(dkg_begin) = dkg_begin.msg else {
+            panic!("Expected DkgBegin message");
+        };
+        assert_eq!(
+            (
+                dkg_begin.threshold,
+                dkg_begin.num_signers,
+                dkg_begin.num_keys
+            ),
+            (7, 5, 15)
+        );
+
+        // a signer configured differently from the coordinator refuses to take part
+        for (misconfigured, expected) in [
+            (
+                DkgBegin {
+                    dkg_id: dkg_begin.dkg_id + 1,
+                    threshold: 6,
+                    ..dkg_begin.clone()
+                },
+                ("threshold", 6, 7),
+            ),
+            (
+                DkgBegin {
+                    dkg_id: dkg_begin.dkg_id + 2,
+                    num_signers: 4,
+                    ..dkg_begin.clone()
+                },
+                ("num_signers", 4, 5),
+            ),
+            (
+                DkgBegin {
+                    dkg_id: dkg_begin.dkg_id + 3,
+                    num_keys: 16,
+                    ..dkg_begin.clone()
+                },
+                ("num_keys", 16, 15),
+            ),
+        ] {
+            match signing_rounds[0].process(&Message::DkgBegin(misconfigured)) {
+                Err(SignerError::DkgParamMismatch(param, sent, local)) => {
+                    assert_eq!((param, sent, local), expected)
+                }
+                _ => panic!("Expected DkgParamMismatch error"),
+            }
+            assert_eq!(signing_rounds[0].state, SignerState::Idle);
+        }
+
+        let out = signing_rounds[0]
+            .process(&Message::DkgBegin(dkg_begin))
+            .unwrap();
+        assert!(matches!(out[..], [Message::DkgPublicShares(_)]));
+    }
+
+    #[test]
+    fn concurrent_signing_rounds_v1() {
+        concurrent_signing_rounds::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn concurrent_signing_rounds_v2() {
+        concurrent_signing_rounds::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn concurrent_signing_rounds<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        let polys: Vec<PolyCommitment> = signing_rounds[0].commitments.values().cloned().collect();
+        let dkg_id = coordinator.current_dkg_id;
+
+        // start two signing rounds before either has been answered
+        let sign_ids = [1u64, 2u64];
+        let mut nonce_responses = HashMap::new();
+        for sign_id in sign_ids {
+            let nonce_request = Message::NonceRequest(NonceRequest {
+                dkg_id,
+                sign_id,
+                sign_iter_id: 0,
+                signer_ids: None,
+                commit: false,
+            });
+            let responses = signing_rounds
+                .iter_mut()
+                .flat_map(|signing_round| signing_round.process(&nonce_request).unwrap())
+                .filter_map(|msg| match msg {
+                    Message::NonceResponse(response) => Some(response),
+                    _ => None,
+                })
+                .collect::<Vec<NonceResponse>>();
+            nonce_responses.insert(sign_id, responses);
+        }
+        assert_eq!(signing_rounds[0].sessions.len(), sign_ids.len());
+
+        // answer the signing rounds in the opposite order from how they were started
+        for sign_id in sign_ids.iter().rev() {
+            let msg = format!("message {}", sign_id).into_bytes();
+            let request = SignatureShareRequest {
+                dkg_id,
+                sign_id: *sign_id,
+                sign_iter_id: 0,
+                nonce_responses: nonce_responses[sign_id].clone(),
+                message: msg.clone(),
+                is_digest: false,
+                is_taproot: false,
+                tweak: TaprootTweak::KeySpendNoScripts,
+            };
+            let shares = signing_rounds
+                .iter_mut()
+                .flat_map(|signing_round| {
+                    signing_round
+                        .process(&Message::SignatureShareRequest(request.clone()))
+                        .unwrap()
+                })
+                .flat_map(|msg| match msg {
+                    Message::SignatureShareResponse(response) => response.signature_shares,
+                    _ => vec![],
+                })
+                .collect::<Vec<SignatureShare>>();
+            let nonces = request
+                .nonce_responses
+                .iter()
+                .flat_map(|nr| nr.nonces.clone())
+                .collect::<Vec<PublicNonce>>();
+            let key_ids = request
+                .nonce_responses
+                .iter()
+                .flat_map(|nr| nr.key_ids.iter())
+                .collect::<Vec<u32>>();
+
+            let mut aggregator = Aggregator::new(coordinator.total_keys, coordinator.threshold);
+            aggregator.init(polys.clone()).unwrap();
+            let sig = aggregator.sign(&msg, &nonces, &shares, &key_ids).unwrap();
+            assert!(sig.verify(&group_key, &msg));
+        }
+
+        // each session's nonces are consumed by signing
+        assert!(signing_rounds[0].sessions.is_empty());
+    }
+
+    #[test]
+    fn session_limit_v1() {
+        session_limit::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn session_limit_v2() {
+        session_limit::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn session_limit<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        let dkg_id = coordinator.current_dkg_id;
+        let signing_round = &mut signing_rounds[0];
+
+        // rounds this signer is never asked to sign in don't keep their nonces past the limit
+        let mut nonce_responses = HashMap::new();
+        for sign_id in 0..MAX_SIGN_SESSIONS as u64 + 2 {
+            let nonce_request = Message::NonceRequest(NonceRequest {
+                dkg_id,
+                sign_id,
+                sign_iter_id: 0,
+                signer_ids: None,
+                commit: false,
+            });
+            let responses = signing_round
+                .process(&nonce_request)
+                .unwrap()
+                .into_iter()
+                .filter_map(|msg| match msg {
+                    Message::NonceResponse(response) => Some(response),
+                    _ => None,
+                })
+                .collect::<Vec<NonceResponse>>();
+            nonce_responses.insert(sign_id, responses);
+        }
+        assert_eq!(signing_round.sessions.len(), MAX_SIGN_SESSIONS);
+        assert_eq!(signing_round.sessions.keys().next(), Some(&2));
+
+        // the discarded nonces can't be signed with; they're gone, and so is the record of them
+        // once there are no live sessions below them
+        let request = SignatureShareRequest {
+            dkg_id,
+            sign_id: 0,
+            sign_iter_id: 0,
+            nonce_responses: nonce_responses[&0].clone(),
+            message: b"message".to_vec(),
+            is_digest: false,
+            is_taproot: false,
+            tweak: TaprootTweak::KeySpendNoScripts,
+        };
+        assert!(matches!(
+            signing_round.process(&Message::SignatureShareRequest(request.clone())),
+            Err(SignerError::UnknownSignId(0))
+        ));
+
+        // but the newest ones still can
+        let request = SignatureShareRequest {
+            sign_id: 2,
+            nonce_responses: nonce_responses[&2].clone(),
+            ..request
+        };
+        assert_eq!(
+            signing_round
+                .process(&Message::SignatureShareRequest(request))
+                .unwrap()
+                .len(),
+            1
+        );
+
+        // consumed nonces are only remembered back to the lowest live session, or the newest
+        // consumed round if that's lower
+        assert_eq!(
+            signing_round.consumed_nonces.iter().collect::<Vec<_>>(),
+            vec![&(2, 0)]
+        );
+        for sign_id in 3..MAX_SIGN_SESSIONS as u64 + 2 {
+            let request = SignatureShareRequest {
+                dkg_id,
+                sign_id,
+                sign_iter_id: 0,
+                nonce_responses: nonce_responses[&sign_id].clone(),
+                message: b"message".to_vec(),
+                is_digest: false,
+                is_taproot: false,
+                tweak: TaprootTweak::KeySpendNoScripts,
+            };
+            signing_round
+                .process(&Message::SignatureShareRequest(request))
+                .unwrap();
+        }
+        assert!(signing_round.sessions.is_empty());
+        assert_eq!(
+            signing_round.consumed_nonces.iter().collect::<Vec<_>>(),
+            vec![&(MAX_SIGN_SESSIONS as u64 + 1, 0)]
+        );
+    }
+
+    #[test]
+    fn stale_sign_requests_v1() {
+        stale_sign_requests::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn stale_sign_requests_v2() {
+        stale_sign_requests::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn stale_sign_requests<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        let dkg_id = coordinator.current_dkg_id;
+        let signing_round = &mut signing_rounds[0];
+
+        let nonce_request = Message::NonceRequest(NonceRequest {
+            dkg_id,
+            sign_id: 1,
+            sign_iter_id: 1,
+            signer_ids: None,
+            commit: false,
+        });
+        let nonce_responses = signing_round
+            .process(&nonce_request)
+            .unwrap()
+            .into_iter()
+            .filter_map(|msg| match msg {
+                Message::NonceResponse(response) => Some(response),
+                _ => None,
+            })
+            .collect::<Vec<NonceResponse>>();
+        let request = SignatureShareRequest {
+            dkg_id,
+            sign_id: 1,
+            sign_iter_id: 1,
+            nonce_responses,
+            message: b"message".to_vec(),
+            is_digest: false,
+            is_taproot: false,
+            tweak: TaprootTweak::KeySpendNoScripts,
+        };
+
+        let stale_dkg = SignatureShareRequest {
+            dkg_id: dkg_id - 1,
+            ..request.clone()
+        };
+        assert!(matches!(
+            signing_round.process(&Message::SignatureShareRequest(stale_dkg)),
+            Err(SignerError::BadDkgId(current, stale)) if current == dkg_id && stale == dkg_id - 1
+        ));
+
+        let stale_iter = SignatureShareRequest {
+            sign_iter_id: 0,
+            ..request.clone()
+        };
+        assert!(matches!(
+            signing_round.process(&Message::SignatureShareRequest(stale_iter)),
+            Err(SignerError::BadSignIterId(1, 0))
+        ));
+
+        let unknown_sign = SignatureShareRequest {
+            sign_id: 2,
+            ..request.clone()
+        };
+        assert!(matches!(
+            signing_round.process(&Message::SignatureShareRequest(unknown_sign)),
+            Err(SignerError::UnknownSignId(2))
+        ));
+
+        // the signer only signs over the nonces it sent
+        let mut other_nonces = request.clone();
+        other_nonces.nonce_responses[0].nonces[0] = PublicNonce {
+            D: Point::from(Scalar::from(1)),
+            E: Point::from(Scalar::from(2)),
+        };
+        assert!(matches!(
+            signing_round.process(&Message::SignatureShareRequest(other_nonces)),
+            Err(SignerError::NonceMismatch(1))
+        ));
+
+        // the rejected requests did not consume the nonces
+        assert_eq!(
+            signing_round
+                .process(&Message::SignatureShareRequest(request.clone()))
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(signing_round.sessions.is_empty());
+
+        // a replayed request can't reuse the nonces, even once fresh ones were made for the round
+        signing_round.process(&nonce_request).unwrap();
+        assert!(matches!(
+            signing_round.process(&Message::SignatureShareRequest(request)),
+            Err(SignerError::NonceReused(1, 1))
+        ));
+    }
+
+    #[test]
+    fn duplicate_signer_ids_v1() {
+        duplicate_signer_ids::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn duplicate_signer_ids_v2() {
+        duplicate_signer_ids::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn duplicate_signer_ids<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        let dkg_id = coordinator.current_dkg_id;
+        let signing_round = &mut signing_rounds[0];
+
+        let nonce_request = Message::NonceRequest(NonceRequest {
+            dkg_id,
+            sign_id: 1,
+            sign_iter_id: 1,
+            signer_ids: None,
+            commit: false,
+        });
+        let nonce_responses = signing_round
+            .process(&nonce_request)
+            .unwrap()
+            .into_iter()
+            .filter_map(|msg| match msg {
+                Message::NonceResponse(response) => Some(response),
+                _ => None,
+            })
+            .collect::<Vec<NonceResponse>>();
+
+        // the signer is listed once per key, but only answers once
+        let request = SignatureShareRequest {
+            dkg_id,
+            sign_id: 1,
+            sign_iter_id: 1,
+            nonce_responses: [
+                nonce_responses.clone(),
+                nonce_responses.clone(),
+                nonce_responses,
+            ]
+            .concat(),
+            message: b"message".to_vec(),
+            is_digest: false,
+            is_taproot: false,
+            tweak: TaprootTweak::KeySpendNoScripts,
+        };
+        let responses = signing_round
+            .process(&Message::SignatureShareRequest(request))
+            .unwrap();
+        match &responses[..] {
+            [Message::SignatureShareResponse(response)] => assert_eq!(response.signer_id, 0),
+            _ => panic!("Expected a single SignatureShareResponse"),
+        }
+        assert!(signing_round.sessions.is_empty());
+    }
+
+    #[test]
+    fn bad_nonce_responses_v1() {
+        bad_nonce_responses::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn bad_nonce_responses_v2() {
+        bad_nonce_responses::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn bad_nonce_responses<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        let dkg_id = coordinator.current_dkg_id;
+
+        let nonce_request = Message::NonceRequest(NonceRequest {
+            dkg_id,
+            sign_id: 1,
+            sign_iter_id: 1,
+            signer_ids: None,
+            commit: false,
+        });
+        let nonce_responses = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| signing_round.process(&nonce_request).unwrap())
+            .filter_map(|msg| match msg {
+                Message::NonceResponse(response) => Some(response),
+                _ => None,
+            })
+            .collect::<Vec<NonceResponse>>();
+        let request = SignatureShareRequest {
+            dkg_id,
+            sign_id: 1,
+            sign_iter_id: 1,
+            nonce_responses,
+            message: b"message".to_vec(),
+            is_digest: false,
+            is_taproot: false,
+            tweak: TaprootTweak::KeySpendNoScripts,
+        };
+
+        // signer 1 claims one of signer 2's keys
+        let mut stolen_key = request.clone();
+        let mut key_ids = request.nonce_responses[1].key_ids.to_vec();
+        key_ids[0] = request.nonce_responses[2].key_ids.to_vec()[0];
+        stolen_key.nonce_responses[1].key_ids = key_ids.into();
+        // signer 2 sends one nonce too few
+        let mut missing_nonce = request.clone();
+        missing_nonce.nonce_responses[2].nonces.pop();
+        // signer 3 repeats a key
+        let mut repeated_key = request.clone();
+        let mut key_ids = request.nonce_responses[3].key_ids.to_vec();
+        key_ids.push(key_ids[0]);
+        repeated_key.nonce_responses[3].key_ids = key_ids.into();
+
+        let signing_round = &mut signing_rounds[0];
+        for (bad_request, signer_id) in [(stolen_key, 1), (missing_nonce, 2), (repeated_key, 3)] {
+            let msgs = signing_round
+                .process(&Message::SignatureShareRequest(bad_request))
+                .unwrap();
+            match &msgs[..] {
+                [Message::SignatureShareReject(reject)] => assert_eq!(
+                    reject.reason,
+                    RejectReason::BadRequest(
+                        SignerError::InvalidNonceResponse(signer_id).to_string()
+                    )
+                ),
+                _ => panic!("Expected SignatureShareReject message"),
+            }
+        }
+
+        // a digest must be 32 bytes
+        let bad_digest = SignatureShareRequest {
+            is_digest: true,
+            ..request.clone()
+        };
+        match &signing_round
+            .process(&Message::SignatureShareRequest(bad_digest))
+            .unwrap()[..]
+        {
+            [Message::SignatureShareReject(reject)] => assert_eq!(
+                reject.reason,
+                RejectReason::BadRequest(SignerError::BadDigestLength(7).to_string())
+            ),
+            _ => panic!("Expected SignatureShareReject message"),
+        }
+
+        // the rejected requests did not consume the nonces
+        assert_eq!(
+            signing_round
+                .process(&Message::SignatureShareRequest(request))
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn sign_digest_v1() {
+        sign_digest::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn sign_digest_v2() {
+        sign_digest::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn sign_digest<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let digest: [u8; 32] = Sha256::digest(b"block header").into();
+        let message = coordinator
+            .start_signing_digest(&digest, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        match &outbound_messages[..] {
+            [packet] => match &packet.msg {
+                Message::SignatureShareRequest(request) => {
+                    assert!(request.is_digest);
+                    assert_eq!(request.message, digest);
+                }
+                _ => panic!("Expected SignatureShareRequest message"),
+            },
+            _ => panic!("Expected one outbound message"),
+        }
+
+        // the digest is signed as-is, so the signature verifies against it directly
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &digest)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // signing a message afterwards isn't treated as a digest
+        coordinator
+            .start_signing_message(b"message", false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        assert!(!coordinator.is_digest);
+    }
+
+    #[test]
+    fn sign_digest_remote_v1() {
+        sign_digest::<v1::Aggregator, RemoteSigner<v1::Signer, LocalKeystore<v1::Signer>>>();
+    }
+
+    #[test]
+    fn sign_digest_remote_v2() {
+        sign_digest::<v2::Aggregator, RemoteSigner<v2::Signer, LocalKeystore<v2::Signer>>>();
+    }
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    /// A challenge hash which is plain SHA-256 rather than the BIP-340 tagged hash
+    struct Sha256Challenge;
+
+    impl ChallengeHash for Sha256Challenge {
+        #[allow(non_snake_case)]
+        fn challenge(public_key: &Point, R: &Point, msg: &[u8]) -> Scalar {
+            let mut hasher = Sha256::new();
+            hasher.update(R.compress().as_bytes());
+            hasher.update(public_key.compress().as_bytes());
+            hasher.update(msg);
+            Scalar::from(<[u8; 32]>::from(hasher.finalize()))
+        }
+    }
+
+    #[test]
+    fn sign_challenge_hash_v1() {
+        sign_challenge_hash::<v1::Aggregator<Sha256Challenge>, v1::Signer<Sha256Challenge>>();
+    }
+
+    #[test]
+    fn sign_challenge_hash_v2() {
+        sign_challenge_hash::<v2::Aggregator<Sha256Challenge>, v2::Signer<Sha256Challenge>>();
+    }
+
+    fn sign_challenge_hash<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let msg = b"It was many and many a year ago".to_vec();
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+
+        // the signature only verifies with the challenge hash it was made with
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => {
+                assert!(sig.verify_with::<Sha256Challenge>(&group_key, &msg));
+                assert!(!sig.verify(&group_key, &msg));
+            }
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
+
+    #[test]
+    fn sign_structured_data_v1() {
+        sign_structured_data::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn sign_structured_data_v2() {
+        sign_structured_data::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn sign_structured_data<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let domain = sip018::Domain {
+            name: "Test App".to_string(),
+            version: "1.0.0".to_string(),
+            chain_id: sip018::CHAIN_ID_TESTNET,
+        };
+        let message = sip018::Value::Tuple(BTreeMap::from([
+            ("amount".to_string(), sip018::Value::UInt(100)),
+            ("memo".to_string(), sip018::Value::None),
+        ]));
+        let packet = sip018::start_signing_round(
+            &mut coordinator,
+            &domain,
+            &message,
+            false,
+            TaprootTweak::KeySpendNoScripts,
+        )
+        .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[packet]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+
+        let hash = sip018::message_hash(&domain, &message).unwrap();
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &hash)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
+
+    #[cfg(feature = "bitcoin")]
+    #[test]
+    fn sign_psbt_v1() {
+        sign_psbt::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[cfg(feature = "bitcoin")]
+    #[test]
+    fn sign_psbt_v2() {
+        sign_psbt::<v2::Aggregator, v2::Signer>();
+    }
+
+    #[cfg(feature = "bitcoin")]
+    fn sign_psbt<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        use bitcoin::{
+            absolute::LockTime,
+            key::{TweakedPublicKey, XOnlyPublicKey},
+            secp256k1::{Message as SecpMessage, Secp256k1},
+            transaction::Version,
+            Amount, OutPoint, Psbt, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Witness,
+        };
+
+        use crate::psbt::PsbtSigner;
+
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        let output_key = compute::tweaked_public_key(&group_key, TaprootTweak::KeySpendNoScripts);
+        let output_key = XOnlyPublicKey::from_slice(&output_key.x().to_bytes()).unwrap();
+        let ours =
+            ScriptBuf::new_p2tr_tweaked(TweakedPublicKey::dangerous_assume_tweaked(output_key));
+        let theirs = ScriptBuf::new_op_return([1, 2, 3]);
+
+        let txin = |vout| TxIn {
+            previous_output: OutPoint {
+                vout,
+                ..OutPoint::null()
+            },
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        };
+        let tx = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![txin(0), txin(1), txin(2)],
+            output: vec![TxOut {
+                value: Amount::from_sat(2000),
+                script_pubkey: theirs.clone(),
+            }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        for (input, script_pubkey) in psbt.inputs.iter_mut().zip([&ours, &theirs, &ours]) {
+            input.witness_utxo = Some(TxOut {
+                value: Amount::from_sat(1000),
+                script_pubkey: script_pubkey.clone(),
+            });
+        }
+
+        // only the key spends of the group key are signed
+        let mut psbt_signer = PsbtSigner::new(psbt, &group_key).unwrap();
+        let spends = psbt_signer.pending.clone();
+        assert_eq!(
+            spends
+                .iter()
+                .map(|spend| spend.input_index)
+                .collect::<Vec<usize>>(),
+            vec![0, 2]
+        );
+        while let Some(packet) = psbt_signer.start_next_round(&mut coordinator).unwrap() {
+            let (outbound_messages, _) =
+                feedback_messages(&mut coordinator, &mut signing_rounds, &[packet]);
+            let (_, operation_results) =
+                feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+            assert_eq!(operation_results.len(), 1);
+            psbt_signer.insert_signature(&operation_results[0]).unwrap();
+        }
+        assert!(psbt_signer.is_done());
+
+        let secp = Secp256k1::verification_only();
+        for spend in spends {
+            let sig = psbt_signer.psbt.inputs[spend.input_index]
+                .tap_key_sig
+                .unwrap();
+            let msg = SecpMessage::from_digest(spend.sighash);
+            secp.verify_schnorr(&sig.signature, &msg, &output_key)
+                .unwrap();
+        }
+        assert!(psbt_signer.psbt.inputs[1].tap_key_sig.is_none());
+    }
+
+    #[test]
+    fn nonce_timeout_v1() {
+        nonce_timeout::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn nonce_timeout_v2() {
+        nonce_timeout::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn nonce_timeout<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let msg = vec![1, 2, 3];
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+
+        // the last signer never answers the nonce request
+        let (online, offline) = signing_rounds.split_at_mut(4);
+        let (outbound_messages, operation_results) =
+            feedback_messages(&mut coordinator, online, &[message]);
+        assert!(outbound_messages.is_empty());
+        assert!(operation_results.is_empty());
+        assert_eq!(coordinator.ids_to_await, [4].into_iter().collect());
+
+        // retry with the signers which did respond
+        let message = coordinator.nonce_timeout().unwrap();
+        match &message.msg {
+            Message::NonceRequest(nonce_request) => assert_eq!(nonce_request.sign_iter_id, 1),
+            _ => panic!("Expected NonceRequest message"),
+        }
+        assert_eq!(coordinator.ids_to_await, (0..4).collect());
+
+        // a late response from the dropped signer is ignored
+        let late = offline[0]
+            .process_inbound_messages(std::slice::from_ref(&message))
+            .unwrap();
+        assert!(coordinator
+            .process_inbound_messages(&late)
+            .unwrap()
+            .0
+            .is_empty());
+        assert_eq!(coordinator.ids_to_await, (0..4).collect());
+        let (outbound_messages, _) = feedback_messages(&mut coordinator, online, &[message]);
+        assert_eq!(outbound_messages.len(), 1);
+        match &outbound_messages[0].msg {
+            Message::SignatureShareRequest(request) => {
+                assert_eq!(request.nonce_responses.len(), 4)
+            }
+            _ => panic!("Expected SignatureShareRequest message"),
+        }
+
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, online, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+        assert_eq!(coordinator.state, CoordinatorState::Idle);
+
+        // two signers hold too few keys to meet the threshold
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        feedback_messages(&mut coordinator, &mut online[..2], &[message]);
+        assert!(matches!(
+            coordinator.nonce_timeout(),
+            Err(CoordinatorError::InsufficientSigners(6, 7))
+        ));
+    }
+
+    #[test]
+    fn misbehavior_report_v1() {
+        misbehavior_report::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn misbehavior_report_v2() {
+        misbehavior_report::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn misbehavior_report<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+
+        // one signer reports a DKG failure
+        let message = coordinator.start_dkg_round().unwrap();
+        feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let dkg_ends = (0..coordinator.total_signers)
+            .map(|signer_id| {
+                let status = if signer_id == 0 {
+                    DkgStatus::Failure(DkgFailure::Parties(BTreeMap::from([(
+                        0,
+                        DkgPartyError::BadShares(vec![1]),
+                    )])))
+                } else {
+                    DkgStatus::Success
+                };
+                Packet {
+                    msg: Message::DkgEnd(DkgEnd {
+                        dkg_id: coordinator.current_dkg_id,
+                        signer_id,
+                        status,
+                    }),
+                    seq: 0,
+                    sig: vec![],
+                }
+            })
+            .collect::<Vec<Packet>>();
+        coordinator.process_inbound_messages(&dkg_ends).unwrap();
+        match &coordinator.misbehavior_report()[..] {
+            [Misbehavior::DkgFailures(failures)] => {
+                assert_eq!(failures.keys().copied().collect::<Vec<u32>>(), vec![0]);
+                assert_eq!(failures[&0].blamed_ids(), &[1]);
+            }
+            _ => panic!("Expected DkgFailures report"),
+        }
+
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        assert!(coordinator.misbehavior_report().is_empty());
+
+        // one signer never sends nonces, and another sends a bad signature share
+        let message = coordinator
+            .start_signing_message(&[1, 2, 3], false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let online = &mut signing_rounds[..4];
+        feedback_messages(&mut coordinator, online, &[message]);
+        let message = coordinator.nonce_timeout().unwrap();
+        let (outbound_messages, _) = feedback_messages(&mut coordinator, online, &[message]);
+        let mut responses = online
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&outbound_messages)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        match &mut responses[1].msg {
+            Message::SignatureShareResponse(response) => {
+                response.signature_shares[0].z_i += Scalar::from(1);
+            }
+            _ => panic!("Expected SignatureShareResponse message"),
+        }
+        assert!(matches!(
+            coordinator.process_inbound_messages(&responses),
+            Err(CoordinatorError::Aggregator(_))
+        ));
+
+        match &coordinator.misbehavior_report()[..] {
+            [Misbehavior::MissingNonces(missing), Misbehavior::BadSigShares(bad)] => {
+                assert_eq!(missing, &vec![4]);
+                assert_eq!(bad, &vec![1]);
+            }
+            report => panic!("Unexpected misbehavior report {:?}", report),
+        }
+    }
+
+    #[test]
+    fn ban_list_v1() {
+        ban_list::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn ban_list_v2() {
+        ban_list::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn ban_list<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // signer 1 sends a bad signature share and gets banned
+        let msg = vec![1, 2, 3];
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let mut responses = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&outbound_messages)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        if let Message::SignatureShareResponse(response) = &mut responses[1].msg {
+            response.signature_shares[0].z_i += Scalar::from(1);
+        }
+        assert!(coordinator.process_inbound_messages(&responses).is_err());
+        assert!(coordinator.ban_list.is_banned(1));
+
+        // the ban can be saved and restored
+        let restored = Coordinator::<Aggregator>::new(
+            coordinator.total_signers,
+            coordinator.total_keys,
+            coordinator.threshold,
+            coordinator.message_private_key,
+        )
+        .with_ban_list(coordinator.ban_list.clone());
+        assert_eq!(restored.ban_list, coordinator.ban_list);
+
+        // the next signing round goes ahead without the banned signer
+        coordinator.state = CoordinatorState::Idle;
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        assert!(!coordinator.ids_to_await.contains(&1));
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        match &outbound_messages[0].msg {
+            Message::SignatureShareRequest(request) => {
+                assert!(request.nonce_responses.iter().all(|nr| nr.signer_id != 1))
+            }
+            _ => panic!("Expected SignatureShareRequest message"),
+        }
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // signers drop messages from banned signers
+        let mut ban_list = BanList::default();
+        ban_list.ban(1);
+        let signing_round = &mut signing_rounds[0];
+        signing_round.ban_list = ban_list;
+        let nonce_response = Message::NonceResponse(NonceResponse {
+            dkg_id: signing_round.dkg_id,
+            sign_id: 1,
+            sign_iter_id: 1,
+            signer_id: 1,
+            key_ids: Default::default(),
+            nonces: vec![],
+        });
+        assert!(signing_round.process(&nonce_response).unwrap().is_empty());
+
+        // but banned signers still take part in DKG, so the next round can finish
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        assert!(coordinator.ban_list.is_banned(1));
+        assert!(signing_rounds[0].ban_list.is_banned(1));
+    }
+
+    #[test]
+    fn signing_policy_v1() {
+        signing_policy::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn signing_policy_v2() {
+        signing_policy::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn signing_policy<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // signer 2 only signs messages which start with its magic byte
+        signing_rounds[2].signing_policy = Some(Box::new(|request: &SignatureShareRequest| {
+            if request.message.first() == Some(&0x42) {
+                Ok(())
+            } else {
+                Err("unknown block".to_string())
+            }
+        }));
+
+        let message = coordinator
+            .start_signing_message(&[1, 2, 3], false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let responses = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&outbound_messages)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        match &responses[2].msg {
+            Message::SignatureShareReject(reject) => {
+                assert_eq!(reject.signer_id, 2);
+                assert_eq!(
+                    reject.reason,
+                    RejectReason::Policy("unknown block".to_string())
+                );
+            }
+            _ => panic!("Expected SignatureShareReject message"),
+        }
+        match coordinator.process_inbound_messages(&responses) {
+            Err(CoordinatorError::SignatureShareRejected(2, reason)) => {
+                assert_eq!(reason, RejectReason::Policy("unknown block".to_string()))
+            }
+            _ => panic!("Expected SignatureShareRejected error"),
+        }
+        assert!(!coordinator.ban_list.is_banned(2));
+
+        // a message the policy accepts is signed as usual
+        let msg = vec![0x42, 2, 3];
+        coordinator.state = CoordinatorState::Idle;
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
+
+    #[test]
+    fn missing_dkg_state_v1() {
+        missing_dkg_state::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn missing_dkg_state_v2() {
+        missing_dkg_state::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn missing_dkg_state<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // signer 4 restarted without its DKG output
+        let forget = |signing_round: &mut SigningRound<Signer>| {
+            signing_round.signer = Signer::new(
+                signing_round.signer_id,
+                &signing_round.signer.get_key_ids(),
+                signing_round.total_signers,
+                signing_round.total_keys,
+                signing_round.threshold,
+                &mut OsRng,
+            );
+        };
+        forget(&mut signing_rounds[4]);
+
+        let msg = b"missing dkg state";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let responses = signing_rounds[4]
+            .process_inbound_messages(std::slice::from_ref(&message))
+            .unwrap();
+        match &responses[..] {
+            [Packet {
+                msg: Message::SignatureShareReject(reject),
+                ..
+            }] => {
+                assert_eq!(reject.signer_id, 4);
+                assert_eq!(reject.reason, RejectReason::MissingDkgState);
+            }
+            _ => panic!("Expected SignatureShareReject message"),
+        }
+
+        // the coordinator moves on as soon as everyone has answered, without a nonce timeout
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        match &outbound_messages[..] {
+            [Packet {
+                msg: Message::SignatureShareRequest(request),
+                ..
+            }] => assert_eq!(request.nonce_responses.len(), 4),
+            _ => panic!("Expected SignatureShareRequest message"),
+        }
+        assert!(!coordinator.ban_list.is_banned(4));
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // once the rejecting signers leave too few keys, the round fails straight away
+        forget(&mut signing_rounds[3]);
+        forget(&mut signing_rounds[2]);
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let responses = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(std::slice::from_ref(&message))
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        assert!(matches!(
+            coordinator.process_inbound_messages(&responses),
+            Err(CoordinatorError::InsufficientSigners(6, 7))
+        ));
+    }
+
+    #[test]
+    fn pipelined_signing_v1() {
+        pipelined_signing::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn pipelined_signing_v2() {
+        pipelined_signing::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn pipelined_signing<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        fn process_all<Signer: SignerTrait>(
+            signing_rounds: &mut [SigningRound<Signer>],
+            packets: &[Packet],
+        ) -> Vec<Packet> {
+            signing_rounds
+                .iter_mut()
+                .flat_map(|signing_round| signing_round.process_inbound_messages(packets).unwrap())
+                .collect()
+        }
+
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let mut coordinator = PipelinedCoordinator::new(coordinator);
+        let group_key = run_dkg(&mut coordinator.dkg, &mut signing_rounds);
+        let msgs: [&[u8]; 3] = [b"first", b"second", b"third"];
+
+        // start two rounds back to back, before either has finished
+        let nonce_requests = msgs[..2]
+            .iter()
+            .map(|msg| {
+                coordinator
+                    .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        let nonce_responses = process_all(&mut signing_rounds, &nonce_requests);
+        let (share_requests, operation_results) = coordinator
+            .process_inbound_messages(&nonce_responses)
+            .unwrap();
+        assert_eq!(share_requests.len(), 2);
+        assert!(operation_results.is_empty());
+        assert!(matches!(
+            coordinator.start_signing_message(msgs[2], false, TaprootTweak::KeySpendNoScripts),
+            Err(CoordinatorError::TooManySignRounds(2))
+        ));
+
+        // the first round finishes, which makes room to gather nonces for the third while the
+        // second is still aggregating
+        let shares = process_all(&mut signing_rounds, &share_requests[..1]);
+        let (_, operation_results) = coordinator.process_inbound_messages(&shares).unwrap();
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msgs[0])),
+            _ => panic!("Expected Signature Operation result"),
+        }
+        let nonce_request = coordinator
+            .start_signing_message(msgs[2], false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        assert_eq!(
+            coordinator.rounds.keys().copied().collect::<Vec<u64>>(),
+            [2, 3]
+        );
+
+        let mut inbound = process_all(&mut signing_rounds, &share_requests[1..]);
+        inbound.extend(process_all(
+            &mut signing_rounds,
+            std::slice::from_ref(&nonce_request),
+        ));
+        let (last_share_request, operation_results) =
+            coordinator.process_inbound_messages(&inbound).unwrap();
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msgs[1])),
+            _ => panic!("Expected Signature Operation result"),
+        }
+        let shares = process_all(&mut signing_rounds, &last_share_request);
+        let (_, operation_results) = coordinator.process_inbound_messages(&shares).unwrap();
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msgs[2])),
+            _ => panic!("Expected Signature Operation result"),
+        }
+        assert!(coordinator.rounds.is_empty());
+
+        // every round signed its packets from the one sequence
+        let seqs = nonce_requests
+            .iter()
+            .chain(&share_requests)
+            .chain([&nonce_request])
+            .chain(&last_share_request)
+            .map(|packet| packet.seq)
+            .collect::<Vec<u64>>();
+        assert!(seqs.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn readiness_report_v1() {
+        readiness_report::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn readiness_report_v2() {
+        readiness_report::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn readiness_report<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+
+        // nobody can sign before DKG
+        let statuses: Vec<_> = signing_rounds.iter().map(|sr| sr.status()).collect();
+        let report = coordinator.readiness_report(&statuses);
+        assert!(report.ready.is_empty());
+        assert!(report
+            .not_ready
+            .values()
+            .all(|reason| *reason == NotReady::MissingDkgOutput));
+        assert!(!report.can_sign);
+
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        let statuses: Vec<_> = signing_rounds.iter().map(|sr| sr.status()).collect();
+        let report = coordinator.readiness_report(&statuses);
+        assert_eq!(report.ready, vec![0, 1, 2, 3, 4]);
+        assert_eq!(report.ready_keys, coordinator.total_keys);
+        assert!(report.can_sign);
+
+        // signers which hold unused nonces are still ready
+        let message = coordinator
+            .start_signing_message(&[1, 2, 3], false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        for signing_round in signing_rounds.iter_mut() {
+            signing_round
+                .process_inbound_messages(std::slice::from_ref(&message))
+                .unwrap();
+            assert_eq!(signing_round.status().pending_sessions, 1);
+        }
+
+        // a banned signer and one which doesn't report aren't ready, but the rest can still sign
+        coordinator.ban_list.ban(1);
+        let statuses: Vec<_> = signing_rounds[..4].iter().map(|sr| sr.status()).collect();
+        let report = coordinator.readiness_report(&statuses);
+        assert_eq!(report.ready, vec![0, 2, 3]);
+        assert_eq!(report.not_ready[&1], NotReady::Banned);
+        assert_eq!(report.not_ready[&4], NotReady::Missing);
+        assert_eq!(report.ready_keys, 9);
+        assert!(report.can_sign);
+
+        // signers left behind by a new DKG round have stale keys
+        coordinator.ban_list.unban(1);
+        coordinator.current_dkg_id += 1;
+        let report = coordinator.readiness_report(&statuses);
+        assert_eq!(
+            report.not_ready[&0],
+            NotReady::StaleDkg(coordinator.current_dkg_id - 1)
+        );
+        assert!(!report.can_sign);
+    }
+
+    #[test]
+    fn signer_events_v1() {
+        signer_events::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn signer_events_v2() {
+        signer_events::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn signer_events<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        for signing_round in signing_rounds.iter_mut() {
+            assert_eq!(
+                signing_round.take_events(),
+                vec![SignerEvent::DkgCompleted {
+                    dkg_id: coordinator.current_dkg_id,
+                    group_key
+                }]
+            );
+            assert!(signing_round.take_events().is_empty());
+        }
+
+        signing_rounds[2].signing_policy = Some(Box::new(|_: &SignatureShareRequest| {
+            Err("not now".to_string())
+        }));
+        let message = coordinator
+            .start_signing_message(&[1, 2, 3], false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let Message::SignatureShareRequest(request) = &outbound_messages[0].msg else {
+            panic!("Expected SignatureShareRequest message");
+        };
+        for signing_round in signing_rounds.iter_mut() {
+            signing_round
+                .process_inbound_messages(&outbound_messages)
+                .unwrap();
+        }
+        for (signer_id, signing_round) in signing_rounds.iter_mut().enumerate() {
+            match &signing_round.take_events()[..] {
+                [SignerEvent::RoundFailed { reason }] if signer_id == 2 => {
+                    assert!(reason.contains("not now"))
+                }
+                [SignerEvent::SharePosted {
+                    sign_id,
+                    sign_iter_id,
+                }] if signer_id != 2 => {
+                    assert_eq!(*sign_id, request.sign_id);
+                    assert_eq!(*sign_iter_id, request.sign_iter_id);
+                }
+                events => panic!("Unexpected events {:?} from signer {}", events, signer_id),
+            }
+        }
+    }
+
+    #[test]
+    fn dkg_excluding_v1() {
+        dkg_excluding::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn dkg_excluding_v2() {
+        dkg_excluding::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn dkg_excluding<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // re-run DKG without sending private shares to signer 4
+        let message = coordinator.start_dkg_round_excluding(&[4]).unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        match &outbound_messages[..] {
+            [Packet {
+                msg: Message::DkgPrivateBegin(dkg_private_begin),
+                ..
+            }] => assert_eq!(dkg_private_begin.signer_ids, [0, 1, 2, 3]),
+            _ => panic!("Expected DkgPrivateBegin message"),
+        }
+
+        let private_shares = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&outbound_messages)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        for packet in &private_shares {
+            if let Message::DkgPrivateShares(private_shares) = &packet.msg {
+                let dst_signer_ids = private_shares
+                    .shares
+                    .iter()
+                    .map(|(dst_signer_id, _)| *dst_signer_id)
+                    .collect::<Vec<u32>>();
+                assert_eq!(dst_signer_ids, [0, 1, 2, 3]);
+            }
+        }
+
+        // the excluded signer sends no DkgEnd, and has no keys to sign with
+        let dkg_ends = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&private_shares)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        assert_eq!(dkg_ends.len(), 4);
+        assert_eq!(signing_rounds[4].signer.get_group_key(), Point::default());
+        let (_, operation_results) = coordinator
+            .process_inbound_messages(&[private_shares, dkg_ends].concat())
+            .unwrap();
+        let group_key = match operation_results[..] {
+            [OperationResult::Dkg(point)] => point,
+            _ => panic!("Expected Dkg Operation result"),
+        };
+
+        // the other signers hold enough keys to sign without it
+        let msg = b"without signer 4";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
+
+    #[test]
+    fn out_of_order_dkg_v1() {
+        out_of_order_dkg::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn out_of_order_dkg_v2() {
+        out_of_order_dkg::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn out_of_order_dkg<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let dkg_begin = coordinator.start_dkg_round().unwrap().msg;
+        let dkg_id = coordinator.current_dkg_id;
+        let dkg_private_begin = Message::DkgPrivateBegin(DkgPrivateBegin {
+            dkg_id,
+            signer_ids: (0..5).collect(),
+        });
+
+        // every signer but the first starts DKG on time
+        let (late, others) = signing_rounds.split_at_mut(1);
+        let late = &mut late[0];
+        let mut public_shares = vec![];
+        for signing_round in others.iter_mut() {
+            public_shares.extend(signing_round.process(&dkg_begin).unwrap());
+        }
+        for signing_round in others.iter_mut() {
+            for msg in &public_shares {
+                assert!(signing_round.process(msg).unwrap().is_empty());
+            }
+        }
+
+        // the late signer sees the other public shares and the private begin before its own DkgBegin
+        for msg in public_shares.iter().chain([&dkg_private_begin]) {
+            assert!(late.process(msg).unwrap().is_empty());
+        }
+        assert!(late.commitments.is_empty());
+
+        let late_public_shares = late.process(&dkg_begin).unwrap();
+        assert_eq!(late.commitments.len(), others[0].commitments.len());
+
+        // once its own public shares arrive, the late signer replays the buffered private begin
+        let mut private_shares = vec![];
+        for signing_round in others.iter_mut() {
+            for msg in &late_public_shares {
+                assert!(signing_round.process(msg).unwrap().is_empty());
+            }
+        }
+        for msg in &late_public_shares {
+            private_shares.extend(late.process(msg).unwrap());
+        }
+        // the private shares and the echo of the public shares
+        assert_eq!(private_shares.len(), 2);
+        assert_eq!(late.state, SignerState::DkgPrivateGather);
+
+        // a private share arriving before its recipient is ready is also buffered
+        assert!(others[0].process(&private_shares[0]).unwrap().is_empty());
+        for signing_round in others.iter_mut() {
+            private_shares.extend(signing_round.process(&dkg_private_begin).unwrap());
+        }
+
+        let mut dkg_ends = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            for msg in &private_shares {
+                dkg_ends.extend(signing_round.process(msg).unwrap());
+            }
+        }
+        assert_eq!(dkg_ends.len(), signing_rounds.len());
+        for dkg_end in dkg_ends {
+            match dkg_end {
+                Message::DkgEnd(dkg_end) => assert!(matches!(dkg_end.status, DkgStatus::Success)),
+                _ => panic!("Expected DkgEnd message"),
+            }
+        }
+
+        let group_key = signing_rounds[0].verify_group_key(None).unwrap();
+        for signing_round in &signing_rounds {
+            assert_eq!(
+                signing_round.verify_group_key(Some(&group_key)).unwrap(),
+                group_key
+            );
+            assert!(signing_round.pending.is_empty());
+        }
+    }
+
+    #[test]
+    fn early_message_limits() {
+        let (mut coordinator, mut signing_rounds) = setup::<v2::Aggregator, v2::Signer>();
+        let dkg_begin = coordinator.start_dkg_round().unwrap().msg;
+        let dkg_id = coordinator.current_dkg_id;
+        let (late, others) = signing_rounds.split_at_mut(1);
+        let late = &mut late[0];
+        assert_eq!(late.dkg_id + 1, dkg_id);
+        let public_shares = others[0].process(&dkg_begin).unwrap();
+        let Message::DkgPublicShares(shares) = &public_shares[0] else {
+            panic!("Expected DkgPublicShares");
+        };
+
+        // only one message of each type from each sender is buffered for the next round
+        for _ in 0..3 {
+            assert!(late.process(&public_shares[0]).unwrap().is_empty());
+        }
+        assert_eq!(late.pending[&dkg_id].len(), 1);
+
+        // and messages for later rounds aren't buffered at all
+        let far_ahead = Message::DkgPublicShares(DkgPublicShares {
+            dkg_id: dkg_id + 1,
+            ..shares.clone()
+        });
+        assert!(late.process(&far_ahead).unwrap().is_empty());
+        assert_eq!(late.pending.len(), 1);
+
+        // the buffered message is still replayed once the round starts
+        late.process(&dkg_begin).unwrap();
+        assert!(late.pending.is_empty());
+        for (party_id, _) in &shares.comms {
+            assert!(late.commitments.contains_key(party_id));
+        }
+    }
+
+    #[test]
+    fn duplicate_messages_v1() {
+        duplicate_messages::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn duplicate_messages_v2() {
+        duplicate_messages::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn duplicate_messages<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let dkg_begin = coordinator.start_dkg_round().unwrap().msg;
+        let dkg_id = coordinator.current_dkg_id;
+        let dkg_private_begin = Message::DkgPrivateBegin(DkgPrivateBegin {
+            dkg_id,
+            signer_ids: (0..5).collect(),
+        });
+
+        let Message::DkgBegin(begin) = &dkg_begin else {
+            panic!("Expected DkgBegin");
+        };
+        let bad_dkg_begin = Message::DkgBegin(DkgBegin {
+            threshold: begin.threshold + 1,
+            ..begin.clone()
+        });
+
+        // a bad copy which arrives first doesn't stop the genuine message being processed
+        let mut public_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            assert!(signing_round.process(&bad_dkg_begin).is_err());
+            public_shares.extend(signing_round.process(&dkg_begin).unwrap());
+            assert!(signing_round.process(&dkg_begin).unwrap().is_empty());
+        }
+        assert_eq!(public_shares.len(), signing_rounds.len());
+
+        // every message is delivered twice
+        let mut private_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            for msg in public_shares.iter().chain(public_shares.iter()) {
+                assert!(signing_round.process(msg).unwrap().is_empty());
+            }
+            private_shares.extend(signing_round.process(&dkg_private_begin).unwrap());
+            assert!(signing_round
+                .process(&dkg_private_begin)
+                .unwrap()
+                .is_empty());
+        }
+        assert_eq!(private_shares.len(), 2 * signing_rounds.len());
+
+        let mut dkg_ends = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            for msg in private_shares.iter().chain(private_shares.iter()) {
+                dkg_ends.extend(signing_round.process(msg).unwrap());
+            }
+            assert!(signing_round.invalid_private_shares.is_empty());
+        }
+        assert_eq!(dkg_ends.len(), signing_rounds.len());
+        for dkg_end in dkg_ends {
+            match dkg_end {
+                Message::DkgEnd(dkg_end) => assert!(matches!(dkg_end.status, DkgStatus::Success)),
+                _ => panic!("Expected DkgEnd message"),
+            }
+        }
+
+        // a retransmitted nonce request does not replace the nonces already sent
+        let nonce_request = Message::NonceRequest(NonceRequest {
+            dkg_id,
+            sign_id: 1,
+            sign_iter_id: 1,
+            signer_ids: None,
+            commit: false,
+        });
+        let signing_round = &mut signing_rounds[0];
+        assert_eq!(signing_round.process(&nonce_request).unwrap().len(), 1);
+        let public_nonces = signing_round.sessions[&1].public_nonces.clone();
+        assert!(signing_round.process(&nonce_request).unwrap().is_empty());
+        assert_eq!(
+            signing_round.sessions[&1].public_nonces.len(),
+            public_nonces.len()
+        );
+        for (a, b) in signing_round.sessions[&1]
+            .public_nonces
+            .iter()
+            .zip(public_nonces.iter())
+        {
+            assert_eq!(a.D, b.D);
+            assert_eq!(a.E, b.E);
+        }
+    }
+
+    #[test]
+    fn invalid_private_shares_v1() {
+        invalid_private_shares::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn invalid_private_shares_v2() {
+        invalid_private_shares::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn invalid_private_shares<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let dkg_begin = coordinator.start_dkg_round().unwrap().msg;
+        let dkg_id = coordinator.current_dkg_id;
+        let dkg_private_begin = Message::DkgPrivateBegin(DkgPrivateBegin {
+            dkg_id,
+            signer_ids: (0..5).collect(),
+        });
+
+        let mut public_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            public_shares.extend(signing_round.process(&dkg_begin).unwrap());
+        }
+        let mut private_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            for msg in &public_shares {
+                signing_round.process(msg).unwrap();
+            }
+            private_shares.extend(signing_round.process(&dkg_private_begin).unwrap());
+        }
+
+        // signer 1 garbles its shares for signer 0, so none of them decrypt
+        let key_ids = signing_rounds[0].signer.get_key_ids();
+        let mut expected = BTreeSet::new();
+        for msg in private_shares.iter_mut() {
+            let Message::DkgPrivateShares(shares) = msg else {
+                continue;
+            };
+            if shares.signer_id != 1 {
+                continue;
+            }
+            let (_, encrypted) = shares
+                .shares
+                .iter_mut()
+                .find(|(dst_signer_id, _)| *dst_signer_id == 0)
+                .unwrap();
+            encrypted[0] ^= 1;
+            for src_party_id in &shares.src_party_ids {
+                for key_id in &key_ids {
+                    expected.insert(BadPrivateShare {
+                        src_party_id: *src_party_id,
+                        dst_key_id: *key_id,
+                        reason: BadShareReason::Decrypt,
+                    });
+                }
+            }
+        }
+
+        let signing_round = &mut signing_rounds[0];
+        let mut dkg_ends = vec![];
+        for msg in &private_shares {
+            dkg_ends.extend(signing_round.process(msg).unwrap());
+        }
+        match &dkg_ends[..] {
+            [Message::DkgEnd(DkgEnd {
+                status: DkgStatus::Failure(failure @ DkgFailure::InvalidPrivateShares(bad_shares)),
+                ..
+            })] => {
+                assert_eq!(bad_shares, &BTreeMap::from([(1, expected.clone())]));
+                let src_party_ids = expected
+                    .iter()
+                    .map(|bad_share| bad_share.src_party_id)
+                    .collect::<BTreeSet<u32>>();
+                assert_eq!(
+                    failure.blamed_ids(),
+                    src_party_ids.into_iter().collect::<Vec<u32>>()
+                );
+            }
+            _ => panic!("Expected DkgEnd failure with invalid private shares"),
+        }
+        assert!(signing_round.ban_list.is_banned(1));
+    }
+
+    #[test]
+    fn unowned_private_shares_v1() {
+        unowned_private_shares::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn unowned_private_shares_v2() {
+        unowned_private_shares::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn unowned_private_shares<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let dkg_begin = coordinator.start_dkg_round().unwrap().msg;
+        let dkg_private_begin = Message::DkgPrivateBegin(DkgPrivateBegin {
+            dkg_id: coordinator.current_dkg_id,
+            signer_ids: (0..5).collect(),
+        });
+
+        let mut public_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            public_shares.extend(signing_round.process(&dkg_begin).unwrap());
+        }
+        let mut private_shares = BTreeMap::new();
+        for signing_round in signing_rounds.iter_mut() {
+            for msg in &public_shares {
+                signing_round.process(msg).unwrap();
+            }
+            for msg in signing_round.process(&dkg_private_begin).unwrap() {
+                if let Message::DkgPrivateShares(shares) = msg {
+                    private_shares.insert(shares.signer_id, shares);
+                }
+            }
+        }
+
+        let signing_round = &mut signing_rounds[0];
+        let honest = Message::DkgPrivateShares(private_shares[&2].clone());
+        signing_round.process(&honest).unwrap();
+        let decrypted = signing_round.decrypted_shares.clone();
+
+        // signer 1 sends shares as though they were from signer 2's parties, which would replace
+        // the ones signer 2 sent
+        let mut forged = private_shares[&1].clone();
+        forged.src_party_ids = private_shares[&2].src_party_ids.clone();
+        let expected = forged
+            .src_party_ids
+            .iter()
+            .map(|src_id| src_id + 1)
+            .collect::<Vec<u32>>();
+        match signing_round.process(&Message::DkgPrivateShares(forged)) {
+            Err(SignerError::UnownedPartyIds(1, party_ids)) => assert_eq!(party_ids, expected),
+            _ => panic!("Expected UnownedPartyIds error"),
+        }
+        assert!(signing_round.ban_list.is_banned(1));
+        assert_eq!(signing_round.decrypted_shares, decrypted);
+    }
+
+    #[test]
+    fn verifiable_shares_v1() {
+        verifiable_shares::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn verifiable_shares_v2() {
+        verifiable_shares::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn verifiable_shares<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        // verifiable shares are slow to encrypt and decrypt, so use fewer keys than usual; signers
+        // using them can run DKG alongside signers which don't
+        let (mut coordinator, mut signing_rounds) = setup_with::<Aggregator, Signer>(4, 2, 5);
+        for signing_round in signing_rounds.iter_mut().take(3) {
+            signing_round.verifiable_shares = true;
+        }
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        let msg = b"verifiable shares";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        let (mut coordinator, signing_rounds) = setup_with::<Aggregator, Signer>(4, 2, 5);
+        let mut signing_rounds = signing_rounds
+            .into_iter()
+            .map(|signing_round| signing_round.with_verifiable_shares())
+            .collect::<Vec<_>>();
+        let dkg_begin = coordinator.start_dkg_round().unwrap().msg;
+        let dkg_private_begin = Message::DkgPrivateBegin(DkgPrivateBegin {
+            dkg_id: coordinator.current_dkg_id,
+            signer_ids: (0..4).collect(),
+        });
+        let mut public_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            public_shares.extend(signing_round.process(&dkg_begin).unwrap());
+        }
+        let mut private_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            for msg in &public_shares {
+                signing_round.process(msg).unwrap();
+            }
+            private_shares.extend(signing_round.process(&dkg_private_begin).unwrap());
+        }
+
+        // an observer with only the public shares and network keys can check every share
+        let commitments = public_shares
+            .iter()
+            .filter_map(|msg| match msg {
+                Message::DkgPublicShares(shares) => Some(shares.comms.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect::<BTreeMap<u32, PolyCommitment>>();
+        let public_points = signing_rounds[0].public_points.clone();
+        let key_ids = |signing_round: &SigningRound<Signer>| {
+            let mut key_ids = signing_round.signer.get_key_ids();
+            key_ids.sort();
+            key_ids
+        };
+        let observe = |private_shares: &[Message]| {
+            let mut invalid = BTreeMap::new();
+            for msg in private_shares {
+                let Message::DkgPrivateShares(shares) = msg else {
+                    continue;
+                };
+                assert!(shares.shares.is_empty());
+                for signing_round in &signing_rounds {
+                    let dst_signer_id = signing_round.signer_id;
+                    let bad_shares = shares.invalid_verifiable_shares(
+                        dst_signer_id,
+                        &key_ids(signing_round),
+                        &public_points.signers[&dst_signer_id],
+                        &commitments,
+                    );
+                    if !bad_shares.is_empty() {
+                        invalid.insert((shares.signer_id, dst_signer_id), bad_shares);
+                    }
+                }
+            }
+            invalid
+        };
+        assert!(observe(&private_shares).is_empty());
+
+        // signer 1 sends signer 0 a share which passes verification but can't be decrypted, and
+        // signer 2 sends signer 3 a share which doesn't match its commitment
+        let mut rng = OsRng;
+        let dst_key_ids = [key_ids(&signing_rounds[0]), key_ids(&signing_rounds[3])];
+        let mut cheats = vec![];
+        for msg in private_shares.iter_mut() {
+            let Message::DkgPrivateShares(shares) = msg else {
+                continue;
+            };
+            let (dst_signer_id, dst_key_ids) = match shares.signer_id {
+                1 => (0, &dst_key_ids[0]),
+                2 => (3, &dst_key_ids[1]),
+                _ => continue,
+            };
+            let src_party_id = shares.src_party_ids[0];
+            let dst_key_id = dst_key_ids[0];
+            let share = signing_rounds[shares.signer_id as usize]
+                .signer
+                .get_shares()[&src_party_id][&dst_key_id];
+            let (_, encrypted) = shares
+                .verifiable_shares
+                .iter_mut()
+                .find(|(signer_id, _)| *signer_id == dst_signer_id)
+                .unwrap();
+            encrypted[0] = if dst_signer_id == 0 {
+                pvss::undecryptable_share(&share, &public_points.signers[&dst_signer_id], &mut rng)
+            } else {
+                EncryptedShare::new(
+                    &(share + Scalar::from(1)),
+                    &public_points.signers[&dst_signer_id],
+                    &mut rng,
+                )
+            };
+            cheats.push((src_party_id, dst_key_id));
+        }
+        let bad_proof = BadPrivateShare {
+            src_party_id: cheats[1].0,
+            dst_key_id: cheats[1].1,
+            reason: BadShareReason::BadProof,
+        };
+        assert_eq!(
+            observe(&private_shares),
+            BTreeMap::from([((2, 3), vec![bad_proof.clone()])])
+        );
+
+        let mut dkg_ends = vec![];
+        for msg in &private_shares {
+            dkg_ends.extend(signing_rounds[3].process(msg).unwrap());
+        }
+        match &dkg_ends[..] {
+            [Message::DkgEnd(DkgEnd {
+                status: DkgStatus::Failure(DkgFailure::InvalidPrivateShares(bad_shares)),
+                ..
+            })] => assert_eq!(
+                bad_shares,
+                &BTreeMap::from([(2, BTreeSet::from([bad_proof]))])
+            ),
+            _ => panic!("Expected DkgEnd failure with invalid private shares"),
+        }
+
+        // signer 0's complaint convinces anyone holding signer 1's private shares
+        let mut dkg_ends = vec![];
+        for msg in &private_shares {
+            dkg_ends.extend(signing_rounds[0].process(msg).unwrap());
+        }
+        let dealer_shares = private_shares
+            .iter()
+            .find_map(|msg| match msg {
+                Message::DkgPrivateShares(shares) if shares.signer_id == 1 => Some(shares),
+                _ => None,
+            })
+            .unwrap();
+        match &dkg_ends[..] {
+            [Message::DkgEnd(DkgEnd {
+                status: DkgStatus::Failure(failure @ DkgFailure::ShareComplaints(complaints)),
+                ..
+            })] => {
+                assert_eq!(complaints.keys().copied().collect::<Vec<u32>>(), [1]);
+                let share_complaint = &complaints[&1][0];
+                assert_eq!(
+                    (share_complaint.src_party_id, share_complaint.dst_key_id),
+                    cheats[0]
+                );
+                assert!(share_complaint.verify(
+                    dealer_shares,
+                    0,
+                    &dst_key_ids[0],
+                    &public_points.signers[&0],
+                ));
+                // the complaint doesn't hold up against another signer's shares
+                assert!(!share_complaint.verify(
+                    dealer_shares,
+                    3,
+                    &dst_key_ids[1],
+                    &public_points.signers[&3],
+                ));
+                assert_eq!(failure.blamed_ids(), [cheats[0].0]);
+            }
+            _ => panic!("Expected DkgEnd failure with share complaints"),
+        }
+        assert!(signing_rounds[0].ban_list.is_banned(1));
+    }
+
+    #[test]
+    fn pedersen_dkg_v1() {
+        pedersen_dkg::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn pedersen_dkg_v2() {
+        pedersen_dkg::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn pedersen_dkg<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let mut coordinator = coordinator.with_pedersen_dkg();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+        for signing_round in &signing_rounds {
+            assert_eq!(
+                signing_round.verify_group_key(Some(&group_key)).unwrap(),
+                group_key
+            );
+        }
+        assert_eq!(
+            verify_transcript(&coordinator.dkg_transcript().unwrap()),
+            Ok(())
+        );
+
+        let msg = b"pedersen dkg";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // the public shares only carry Pedersen commitments, which hide the polynomials
+        let dkg_begin = coordinator.start_dkg_round().unwrap().msg;
+        assert!(matches!(
+            &dkg_begin,
+            Message::DkgBegin(DkgBegin { pedersen: true, .. })
+        ));
+        let dkg_private_begin = Message::DkgPrivateBegin(DkgPrivateBegin {
+            dkg_id: coordinator.current_dkg_id,
+            signer_ids: (0..5).collect(),
+        });
+        let mut public_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            public_shares.extend(signing_round.process(&dkg_begin).unwrap());
+        }
+        for msg in &public_shares {
+            let Message::DkgPublicShares(shares) = msg else {
+                panic!("Expected DkgPublicShares");
+            };
+            assert!(shares.comms.is_empty());
+            assert!(!shares.hiding_comms.is_empty());
+        }
+        for signing_round in signing_rounds.iter_mut() {
+            for msg in &public_shares {
+                signing_round.process(msg).unwrap();
+            }
+        }
+
+        // signer 2 switches polynomials after committing, so its shares don't match
+        let mut rng = OsRng;
+        signing_rounds[2].signer.reset_polys(&mut rng);
+        let mut private_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            private_shares.extend(signing_round.process(&dkg_private_begin).unwrap());
+        }
+        let decommit = |signer_id: u32| {
+            private_shares
+                .iter()
+                .find_map(|msg| match msg {
+                    Message::DkgDecommit(decommit) if decommit.signer_id == signer_id => {
+                        Some(decommit.clone())
+                    }
+                    _ => None,
+                })
+                .unwrap()
+        };
+
+        // signer 1 reveals a polynomial which doesn't open its Pedersen commitment
+        let mut bad_decommit = decommit(1);
+        let (party_id, comm, _) = &mut bad_decommit.comms[0];
+        let constant = comm.poly[0];
+        comm.poly[1] += constant;
+        let party_id = *party_id;
+        match signing_rounds[0].process(&Message::DkgDecommit(bad_decommit)) {
+            Err(SignerError::InvalidPolyCommitments(party_ids)) => {
+                assert_eq!(party_ids, vec![party_id])
+            }
+            result => panic!("Expected InvalidPolyCommitments, got {:?}", result),
+        }
+        assert!(signing_rounds[0].ban_list.is_banned(1));
+
+        let mut results = vec![];
+        for msg in &private_shares {
+            results.push(signing_rounds[3].process(msg));
+        }
+        let bad_shares = &signing_rounds[3].invalid_private_shares[&2];
+        assert!(!bad_shares.is_empty());
+        assert!(bad_shares
+            .iter()
+            .all(|bad_share| bad_share.reason == BadShareReason::BadHidingShare));
+        assert!(results
+            .iter()
+            .any(|result| matches!(result, Err(SignerError::InvalidPolyCommitments(_)))));
+        assert!(signing_rounds[3].ban_list.is_banned(2));
+        assert!(!signing_rounds[3].ban_list.is_banned(1));
+    }
+
+    #[test]
+    fn equivocated_public_shares_v1() {
+        equivocated_public_shares::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn equivocated_public_shares_v2() {
+        equivocated_public_shares::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn equivocated_public_shares<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let dkg_begin = coordinator.start_dkg_round().unwrap().msg;
+        let dkg_id = coordinator.current_dkg_id;
+        let dkg_private_begin = Message::DkgPrivateBegin(DkgPrivateBegin {
+            dkg_id,
+            signer_ids: (0..5).collect(),
+        });
+
+        let mut public_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            public_shares.extend(signing_round.process(&dkg_begin).unwrap());
+        }
+
+        // signer 0 shows signer 1 a different commitment than it shows everyone else
+        let mut equivocation = public_shares[0].clone();
+        if let Message::DkgPublicShares(shares) = &mut equivocation {
+            shares.comms[0].1.poly[1] += Point::from(Scalar::from(1));
+        }
+        for (i, signing_round) in signing_rounds.iter_mut().enumerate() {
+            for (j, msg) in public_shares.iter().enumerate() {
+                let msg = if i == 1 && j == 0 { &equivocation } else { msg };
+                assert!(signing_round.process(msg).unwrap().is_empty());
+            }
+        }
+
+        let mut private_shares = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            private_shares.extend(signing_round.process(&dkg_private_begin).unwrap());
+        }
+
+        let mut dkg_ends = vec![];
+        for signing_round in signing_rounds.iter_mut() {
+            for msg in &private_shares {
+                dkg_ends.extend(signing_round.process(msg).unwrap());
+            }
+        }
+        assert_eq!(dkg_ends.len(), signing_rounds.len());
+        for dkg_end in dkg_ends {
+            match dkg_end {
+                Message::DkgEnd(DkgEnd {
+                    signer_id,
+                    status: DkgStatus::Failure(failure),
+                    ..
+                }) => {
+                    let mismatched = if signer_id == 1 {
+                        vec![0, 2, 3, 4]
+                    } else {
+                        vec![1]
+                    };
+                    assert_eq!(failure, DkgFailure::EchoMismatch(mismatched));
+                }
+                _ => panic!("Expected failed DkgEnd message"),
+            }
+        }
+    }
+
+    #[test]
+    fn dkg_public_share_v1() {
+        dkg_public_share::<v1::Signer>();
+    }
+
+    #[test]
+    fn dkg_public_share_v2() {
+        dkg_public_share::<v2::Signer>();
+    }
+
+    fn dkg_public_share<Signer: SignerTrait>() {
+        let mut rnd = OsRng;
+        let public_key = ecdsa::PublicKey::new(&Scalar::random(&mut rnd)).unwrap();
+        let public_keys = PublicKeys {
+            signers: [(0, public_key)].into_iter().collect(),
+            key_ids: [(1, public_key)].into_iter().collect(),
+            coordinators: Vec::new(),
+        };
+        let mut signing_round =
+            SigningRound::<Signer>::new(1, 1, 1, 1, vec![1], Default::default(), public_keys);
+        let secret = Scalar::random(&mut rnd);
+        let public_share = DkgPublicShares {
+            dkg_id: 0,
+            signer_id: 0,
+            comms: vec![(
+                1,
+                PolyCommitment {
+                    id: ID::new(&Scalar::from(1), &secret, &mut rnd),
+                    poly: vec![Point::from(secret)],
+                },
+            )],
+            hiding_comms: vec![],
+        };
+        signing_round.dkg_public_share(&public_share).unwrap();
+        assert_eq!(1, signing_round.commitments.len());
+
+        // commitments with a bad proof, the wrong party ID, or the wrong degree are rejected
+        let mut bad_proof = public_share.clone();
+        bad_proof.comms[0].1.poly[0] += Point::from(Scalar::from(1));
+        let mut wrong_party = public_share.clone();
+        wrong_party.comms[0].0 = 2;
+        let mut wrong_degree = public_share.clone();
+        wrong_degree.comms[0].1.poly.push(Point::from(secret));
+        for (bad_share, party_id) in [(bad_proof, 1), (wrong_party, 2), (wrong_degree, 1)] {
+            match signing_round.dkg_public_share(&bad_share) {
+                Err(SignerError::InvalidPolyCommitments(party_ids)) => {
+                    assert_eq!(party_ids, vec![party_id])
+                }
+                _ => panic!("Expected InvalidPolyCommitments error"),
+            }
+        }
+
+        // a signer can't send commitments for another signer's party
+        let mut other_party = public_share.clone();
+        other_party.comms[0] = (
+            2,
+            PolyCommitment {
+                id: ID::new(&Scalar::from(2), &secret, &mut rnd),
+                poly: vec![Point::from(secret)],
+            },
+        );
+        match signing_round.dkg_public_share(&other_party) {
+            Err(SignerError::UnownedPartyIds(0, party_ids)) => assert_eq!(party_ids, vec![2]),
+            _ => panic!("Expected UnownedPartyIds error"),
+        }
+        let mut unknown_signer = public_share.clone();
+        unknown_signer.signer_id = 9;
+        assert!(matches!(
+            signing_round.dkg_public_share(&unknown_signer),
+            Err(SignerError::UnknownSigner(9))
+        ));
+
+        // a delayed share from a previous round is rejected
+        signing_round.commitments.clear();
+        signing_round.dkg_id = 1;
+        assert!(matches!(
+            signing_round.dkg_public_share(&public_share),
+            Err(SignerError::BadDkgId(1, 0))
+        ));
+        assert!(signing_round.commitments.is_empty());
+    }
+
+    #[test]
+    fn signer_subset_v1() {
+        signer_subset::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn signer_subset_v2() {
+        signer_subset::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn signer_subset<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // only the chosen signers are asked for nonces, and the others stay quiet
+        let msg = b"signer subset";
+        let chosen = [1, 3, 4].into_iter().collect::<HashSet<u32>>();
+        let message = coordinator
+            .start_signing_message_with_signers(
+                msg,
+                Some(chosen.clone()),
+                false,
+                TaprootTweak::KeySpendNoScripts,
+            )
+            .unwrap();
+        assert_eq!(coordinator.ids_to_await, chosen);
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        match &outbound_messages[..] {
+            [Packet {
+                msg: Message::SignatureShareRequest(request),
+                ..
+            }] => {
+                let signer_ids = request
+                    .nonce_responses
+                    .iter()
+                    .map(|nonce_response| nonce_response.signer_id)
+                    .collect::<HashSet<u32>>();
+                assert_eq!(signer_ids, chosen);
+            }
+            _ => panic!("Expected SignatureShareRequest message"),
+        }
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // signers outside the chosen set aren't asked to stand in for ones which time out
+        let message = coordinator
+            .start_signing_message_with_signers(
+                msg,
+                Some(chosen),
+                false,
+                TaprootTweak::KeySpendNoScripts,
+            )
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds[1..4], &[message]);
+        assert!(outbound_messages.is_empty());
+        assert!(matches!(
+            coordinator.nonce_timeout(),
+            Err(CoordinatorError::InsufficientSigners(6, 7))
+        ));
+
+        coordinator.reset();
+        assert!(matches!(
+            coordinator.start_signing_message_with_signers(
+                msg,
+                Some([2, 5, 9].into_iter().collect()),
+                false,
+                TaprootTweak::KeySpendNoScripts
+            ),
+            Err(CoordinatorError::UnknownSigners(unknown)) if unknown == vec![5, 9]
+        ));
+        assert!(matches!(
+            coordinator.start_signing_message_with_signers(
+                msg,
+                Some(HashSet::new()),
+                false,
+                TaprootTweak::KeySpendNoScripts
+            ),
+            Err(CoordinatorError::InsufficientSigners(0, 7))
+        ));
+    }
+
+    #[test]
+    fn early_nonce_quorum_v1() {
+        early_nonce_quorum::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn early_nonce_quorum_v2() {
+        early_nonce_quorum::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn early_nonce_quorum<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let mut coordinator = coordinator.with_early_nonce_quorum();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let msg = b"early nonce quorum";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let responses = signing_rounds
+            .iter_mut()
+            .map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(std::slice::from_ref(&message))
+                    .unwrap()
+            })
+            .collect::<Vec<Vec<Packet>>>();
+
+        // two signers hold 6 keys, which isn't enough for a threshold of 7, but three are
+        for response in &responses[..2] {
+            assert!(coordinator
+                .process_inbound_messages(response)
+                .unwrap()
+                .0
+                .is_empty());
+        }
+        let (outbound_messages, _) = coordinator.process_inbound_messages(&responses[2]).unwrap();
+        match &outbound_messages[..] {
+            [Packet {
+                msg: Message::SignatureShareRequest(request),
+                ..
+            }] => assert_eq!(request.nonce_responses.len(), 3),
+            _ => panic!("Expected SignatureShareRequest message"),
+        }
+
+        // the slower signers' nonces arrive too late and are ignored
+        for response in &responses[3..] {
+            assert!(coordinator
+                .process_inbound_messages(response)
+                .unwrap()
+                .0
+                .is_empty());
+        }
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
+
+    #[test]
+    fn commit_reveal_v1() {
+        commit_reveal::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn commit_reveal_v2() {
+        commit_reveal::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn commit_reveal<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let mut coordinator = coordinator.with_commit_reveal();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // signers only send commitments until everyone has committed
+        let msg = b"commit reveal";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let commitments = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(std::slice::from_ref(&message))
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        assert!(commitments
+            .iter()
+            .all(|packet| matches!(packet.msg, Message::NonceCommitment(_))));
+        let (reveals, _) = coordinator
+            .process_inbound_messages(&commitments[..4])
+            .unwrap();
+        assert!(reveals.is_empty());
+        let (reveals, _) = coordinator
+            .process_inbound_messages(&commitments[4..])
+            .unwrap();
+        match &reveals[..] {
+            [Packet {
+                msg: Message::NonceReveal(nonce_reveal),
+                ..
+            }] => assert_eq!(nonce_reveal.signer_ids, vec![0, 1, 2, 3, 4]),
+            _ => panic!("Expected NonceReveal message"),
+        }
+
+        // a signer which reveals different nonces than it committed to is left out
+        let mut responses = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| signing_round.process_inbound_messages(&reveals).unwrap())
+            .collect::<Vec<Packet>>();
+        let Message::NonceResponse(nonce_response) = &mut responses[4].msg else {
+            panic!("Expected NonceResponse message");
+        };
+        nonce_response.nonces[0] = PublicNonce::from(&Nonce::random(&mut OsRng));
+        let (outbound_messages, _) = coordinator.process_inbound_messages(&responses).unwrap();
+        match &outbound_messages[..] {
+            [Packet {
+                msg: Message::SignatureShareRequest(request),
+                ..
+            }] => assert_eq!(request.nonce_responses.len(), 4),
+            _ => panic!("Expected SignatureShareRequest message"),
+        }
+        assert!(coordinator.unresponsive_signers.contains(&4));
+
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
+
+    #[test]
+    fn error_context_v1() {
+        error_context::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn error_context_v2() {
+        error_context::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn error_context<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (mut coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        let dkg_id = coordinator.current_dkg_id;
+
+        let message = coordinator
+            .start_signing_message(b"context", false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let Message::NonceRequest(nonce_request) = &message.msg else {
+            panic!("Expected NonceRequest message");
+        };
+        let sign_id = nonce_request.sign_id;
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+
+        // a signer error names the round and the local signer
+        signing_rounds[2].dkg_id += 1;
+        let err = signing_rounds[2]
+            .process_inbound_messages_with_context(&outbound_messages)
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::Signer(SignerError::BadDkgId(..))
+        ));
+        assert_eq!(
+            err.context,
+            ErrorContext {
+                dkg_id: Some(dkg_id),
+                sign_id: Some(sign_id),
+                sign_iter_id: Some(0),
+                sender_id: None,
+                signer_id: Some(2),
+            }
+        );
+        assert_eq!(
+            err.context.to_string(),
+            format!("dkg_id {dkg_id}, sign_id {sign_id}, sign_iter_id 0, signer_id 2")
+        );
+
+        // a coordinator error names the round and the signer whose message failed
+        let responses = signing_rounds[1]
+            .process_inbound_messages(&outbound_messages)
+            .unwrap();
+        coordinator.current_dkg_id += 1;
+        let err = coordinator
+            .process_inbound_messages_with_context(&responses)
+            .unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::Coordinator(CoordinatorError::BadDkgId(..))
+        ));
+        assert_eq!(
+            err.context,
+            ErrorContext {
+                dkg_id: Some(dkg_id),
+                sign_id: Some(sign_id),
+                sign_iter_id: Some(0),
+                sender_id: Some(1),
+                signer_id: None,
+            }
+        );
+    }
+
+    #[test]
+    fn public_shares_done_v1() {
+        public_shares_done::<v1::Signer>();
+    }
+
+    #[test]
+    fn public_shares_done_v2() {
+        public_shares_done::<v2::Signer>();
+    }
+
+    fn public_shares_done<Signer: SignerTrait>() {
+        let mut rnd = OsRng;
+        let mut signing_round = SigningRound::<Signer>::new(
+            1,
+            1,
+            1,
+            1,
+            vec![1],
+            Default::default(),
+            Default::default(),
+        );
+        // publich_shares_done starts out as false
+        assert!(!signing_round.public_shares_done());
+
+        // meet the conditions for all public keys received
+        signing_round.state = SignerState::DkgPublicGather;
+        signing_round.commitments.insert(
+            1,
+            PolyCommitment {
+                id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
+                poly: vec![],
+            },
+        );
+
+        // public_shares_done should be true
+        assert!(signing_round.public_shares_done());
+    }
+
+    #[test]
+    fn can_dkg_end_v1() {
+        can_dkg_end::<v1::Signer>();
+    }
+
+    #[test]
+    fn can_dkg_end_v2() {
+        can_dkg_end::<v2::Signer>();
+    }
+
+    fn can_dkg_end<Signer: SignerTrait>() {
+        let mut rnd = OsRng;
+        let mut signing_round = SigningRound::<Signer>::new(
+            1,
+            1,
+            1,
+            1,
+            vec![1],
+            Default::default(),
+            Default::default(),
+        );
+        // can_dkg_end starts out as false
+        assert!(!signing_round.can_dkg_end());
+
+        // meet the conditions for DKG_END
+        signing_round.state = SignerState::DkgPrivateGather;
+        signing_round.commitments.insert(
+            1,
+            PolyCommitment {
+                id: ID::new(&Scalar::new(), &Scalar::new(), &mut rnd),
+                poly: vec![],
+            },
+        );
+        let shares: HashMap<u32, Scalar> = HashMap::new();
+        signing_round.decrypted_shares.insert(1, shares);
+        assert!(!signing_round.can_dkg_end());
+        signing_round.echoes.insert(1, [0; 32]);
+
+        // can_dkg_end should be true
+        assert!(signing_round.can_dkg_end());
+    }
+
+    #[test]
+    fn dkg_ended_v1() {
+        dkg_ended::<v1::Signer>();
     }
 
     #[test]
@@ -595,4 +4494,550 @@ mod test {
             panic!("Unexpected Error");
         }
     }
+
+    #[test]
+    fn config_v1() {
+        config::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn config_v2() {
+        config::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn config<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let private_keys = [Scalar::random(&mut OsRng), Scalar::random(&mut OsRng)];
+        let public_keys = private_keys
+            .iter()
+            .map(|private_key| ecdsa::PublicKey::new(private_key).unwrap())
+            .collect::<Vec<ecdsa::PublicKey>>();
+        let public_keys = PublicKeys {
+            signers: HashMap::from([(0, public_keys[0]), (1, public_keys[1])]),
+            key_ids: HashMap::from([
+                (1, public_keys[0]),
+                (2, public_keys[0]),
+                (3, public_keys[1]),
+            ]),
+            coordinators: Vec::new(),
+        };
+
+        // the totals default to the number of registered signers and keys, and the config
+        // survives a round trip through a config file
+        let config = SignerConfig::new(2, 0, vec![0, 1], private_keys[0], public_keys.clone())
+            .with_domain(b"testnet");
+        assert_eq!(config.total_signers, 2);
+        assert_eq!(config.total_keys, 3);
+        let json = serde_json::to_string(&config).unwrap();
+        let config: SignerConfig = serde_json::from_str(&json).unwrap();
+        let signing_round = SigningRound::<Signer>::from_config(config.clone()).unwrap();
+        assert_eq!(signing_round.domain, b"testnet".to_vec());
+        assert_eq!(signing_round.signer.get_key_ids(), vec![0, 1]);
+
+        // optional fields can be left out of a config file
+        let mut value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        value.as_object_mut().unwrap().remove("domain");
+        value.as_object_mut().unwrap().remove("ban_list");
+        let config: SignerConfig = serde_json::from_value(value).unwrap();
+        assert!(config.domain.is_empty());
+        assert!(config.validate().is_ok());
+
+        let check = |config: SignerConfig, expected: ConfigError| {
+            match SigningRound::<Signer>::from_config(config) {
+                Err(SignerError::Config(e)) => assert_eq!(e, expected),
+                _ => panic!("expected {expected:?}"),
+            }
+        };
+        check(
+            config.clone().with_total_keys(1),
+            ConfigError::InvalidThreshold(2, 1),
+        );
+        check(
+            config.clone().with_total_signers(0),
+            ConfigError::InvalidTotalSigners(0, 3),
+        );
+        check(
+            SignerConfig::new(2, 2, vec![2], private_keys[1], public_keys.clone()),
+            ConfigError::InvalidSignerId(2, 2),
+        );
+        check(
+            SignerConfig::new(2, 1, vec![], private_keys[1], public_keys.clone()),
+            ConfigError::NoKeyIds,
+        );
+        check(
+            SignerConfig::new(2, 1, vec![3], private_keys[1], public_keys.clone()),
+            ConfigError::InvalidKeyId(3, 3),
+        );
+        check(
+            SignerConfig::new(2, 1, vec![2], private_keys[0], public_keys.clone()),
+            ConfigError::NetworkKeyMismatch(1),
+        );
+        check(
+            SignerConfig::new(2, 1, vec![1, 2], private_keys[1], public_keys.clone()),
+            ConfigError::KeyIdMismatch(1),
+        );
+
+        let config = CoordinatorConfig::new(2, 3, 2, private_keys[0]).with_domain(b"testnet");
+        let json = serde_json::to_string(&config).unwrap();
+        let config: CoordinatorConfig = serde_json::from_str(&json).unwrap();
+        let coordinator = Coordinator::<Aggregator>::from_config(config.clone()).unwrap();
+        assert_eq!(coordinator.domain, b"testnet".to_vec());
+        assert!(matches!(
+            Coordinator::<Aggregator>::from_config(CoordinatorConfig::new(
+                4,
+                3,
+                2,
+                private_keys[0]
+            )),
+            Err(CoordinatorError::Config(ConfigError::InvalidTotalSigners(
+                4, 3
+            )))
+        ));
+        assert!(matches!(
+            Coordinator::<Aggregator>::from_config(CoordinatorConfig::new(
+                2,
+                3,
+                0,
+                private_keys[0]
+            )),
+            Err(CoordinatorError::Config(ConfigError::InvalidThreshold(
+                0, 3
+            )))
+        ));
+    }
+
+    #[test]
+    fn dyn_coordinator_v1() {
+        dyn_coordinator::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn dyn_coordinator_v2() {
+        dyn_coordinator::<v2::Aggregator, v2::Signer>();
+    }
+
+    /// Feed `packet` to the signers, and their responses back to each other and the coordinator,
+    /// until the coordinator finishes the operation
+    fn drive<Signer: SignerTrait>(
+        coordinator: &mut dyn Coordinatable,
+        signing_rounds: &mut [SigningRound<Signer>],
+        packet: Packet,
+    ) -> OperationResult {
+        let mut messages = vec![packet];
+        loop {
+            let mut inbound_messages = vec![];
+            let mut feedback_messages = vec![];
+            for signing_round in signing_rounds.iter_mut() {
+                let outbound_messages = signing_round.process_inbound_messages(&messages).unwrap();
+                feedback_messages.extend_from_slice(&outbound_messages);
+                inbound_messages.extend(outbound_messages);
+            }
+            for signing_round in signing_rounds.iter_mut() {
+                inbound_messages.extend(
+                    signing_round
+                        .process_inbound_messages(&feedback_messages)
+                        .unwrap(),
+                );
+            }
+            let (outbound_messages, mut results) = coordinator
+                .process_inbound_messages(&inbound_messages)
+                .unwrap();
+            if let Some(result) = results.pop() {
+                return result;
+            }
+            messages = outbound_messages;
+        }
+    }
+
+    fn dyn_coordinator<Aggregator: AggregatorTrait + 'static, Signer: SignerTrait>() {
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let mut coordinator: Box<dyn Coordinatable> = Box::new(coordinator);
+        assert!(coordinator.result().is_none());
+
+        let packet = coordinator.start_distributed_key_generation().unwrap();
+        let OperationResult::Dkg(group_key) =
+            drive(coordinator.as_mut(), &mut signing_rounds, packet)
+        else {
+            panic!("expected a DKG result");
+        };
+        assert_eq!(coordinator.get_aggregate_public_key(), Some(group_key));
+        assert_eq!(coordinator.result(), Some(OperationResult::Dkg(group_key)));
+
+        let msg = b"swappable coordinator";
+        let packet = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let result = drive(coordinator.as_mut(), &mut signing_rounds, packet);
+        let OperationResult::Sign(signature) = &result else {
+            panic!("expected a signature");
+        };
+        assert!(signature.verify(&group_key, msg));
+        assert_eq!(coordinator.result(), Some(result));
+    }
+
+    #[test]
+    fn weighted_signing_v1() {
+        weighted_signing::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn weighted_signing_v2() {
+        weighted_signing::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn weighted_signing<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let weights = SignerWeights {
+            weights: [(0, 100), (1, 100), (2, 1), (3, 50), (4, 1)].into(),
+            threshold: 250,
+        };
+        let mut coordinator = coordinator.with_weights(weights.clone());
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // signing starts as soon as the responders carry enough weight, without waiting on the
+        // last signer
+        let msg = vec![1, 2, 3];
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        assert_eq!(outbound_messages.len(), 1);
+        match &outbound_messages[0].msg {
+            Message::SignatureShareRequest(request) => {
+                assert_eq!(request.nonce_responses.len(), 4)
+            }
+            _ => panic!("Expected SignatureShareRequest message"),
+        }
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, &msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // the first three signers hold enough keys, but not enough weight
+        let message = coordinator
+            .start_signing_message(&msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        feedback_messages(&mut coordinator, &mut signing_rounds[..3], &[message]);
+        assert!(matches!(
+            coordinator.nonce_timeout(),
+            Err(CoordinatorError::InsufficientWeight(201, 250))
+        ));
+
+        let statuses = signing_rounds[..3]
+            .iter()
+            .map(|signing_round| signing_round.status())
+            .collect::<Vec<_>>();
+        let report = coordinator.readiness_report(&statuses);
+        assert_eq!(report.ready_keys, 9);
+        assert_eq!(report.ready_weight, Some(201));
+        assert!(!report.can_sign);
+
+        let config = CoordinatorConfig::new(5, 15, 7, Scalar::random(&mut OsRng)).with_weights(
+            SignerWeights {
+                threshold: 300,
+                ..weights
+            },
+        );
+        assert!(matches!(
+            Coordinator::<Aggregator>::from_config(config),
+            Err(CoordinatorError::Config(
+                ConfigError::InvalidWeightThreshold(300, 252)
+            ))
+        ));
+    }
+
+    #[derive(Default)]
+    struct MetricCounts {
+        messages: HashMap<MessageType, usize>,
+        completed: Vec<RoundType>,
+        failed: Vec<RoundType>,
+        nonce_pool_depth: usize,
+    }
+
+    #[derive(Default, Clone)]
+    struct CountingMetrics(Arc<Mutex<MetricCounts>>);
+
+    impl Metrics for CountingMetrics {
+        fn message_processed(&self, message_type: MessageType) {
+            *self
+                .0
+                .lock()
+                .unwrap()
+                .messages
+                .entry(message_type)
+                .or_default() += 1;
+        }
+
+        fn round_completed(&self, round: RoundType, _duration: Duration) {
+            self.0.lock().unwrap().completed.push(round);
+        }
+
+        fn round_failed(&self, round: RoundType) {
+            self.0.lock().unwrap().failed.push(round);
+        }
+
+        fn nonce_pool_depth(&self, depth: usize) {
+            self.0.lock().unwrap().nonce_pool_depth = depth;
+        }
+    }
+
+    #[test]
+    fn metrics_v1() {
+        metrics::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn metrics_v2() {
+        metrics::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn metrics<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let coordinator_metrics = CountingMetrics::default();
+        let signer_metrics = CountingMetrics::default();
+        let mut coordinator = coordinator.with_metrics(coordinator_metrics.clone());
+        signing_rounds[0].metrics = Box::new(signer_metrics.clone());
+
+        run_dkg(&mut coordinator, &mut signing_rounds);
+        {
+            let counts = coordinator_metrics.0.lock().unwrap();
+            assert_eq!(counts.messages[&MessageType::DkgPublicShares], 5);
+            assert_eq!(counts.messages[&MessageType::DkgEnd], 5);
+            assert_eq!(counts.completed, vec![RoundType::Dkg]);
+            let counts = signer_metrics.0.lock().unwrap();
+            assert_eq!(counts.messages[&MessageType::DkgBegin], 1);
+            assert_eq!(counts.completed, vec![RoundType::Dkg]);
+        }
+
+        let message = coordinator
+            .start_signing_message(b"metrics", false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        assert_eq!(signer_metrics.0.lock().unwrap().nonce_pool_depth, 1);
+
+        // a request for the wrong DKG round is an error in the signing round
+        let mut stale = outbound_messages[0].msg.clone();
+        let Message::SignatureShareRequest(request) = &mut stale else {
+            panic!("Expected SignatureShareRequest message");
+        };
+        request.dkg_id -= 1;
+        assert!(signing_rounds[0].process(&stale).is_err());
+        assert_eq!(
+            signer_metrics.0.lock().unwrap().failed,
+            vec![RoundType::Sign]
+        );
+
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        assert!(matches!(operation_results[..], [OperationResult::Sign(_)]));
+
+        let counts = coordinator_metrics.0.lock().unwrap();
+        assert_eq!(counts.messages[&MessageType::SignatureShareResponse], 5);
+        assert_eq!(counts.completed, vec![RoundType::Dkg, RoundType::Sign]);
+        assert!(counts.failed.is_empty());
+        let counts = signer_metrics.0.lock().unwrap();
+        assert_eq!(counts.completed, vec![RoundType::Dkg, RoundType::Sign]);
+        assert_eq!(counts.nonce_pool_depth, 0);
+    }
+
+    #[test]
+    fn one_key_per_signer_v3() {
+        let (mut coordinator, mut signing_rounds) =
+            setup_with::<v3::Aggregator, v3::Signer>(7, 1, 5);
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let msg = b"one key each";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let responses = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&outbound_messages)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+
+        // v3 signature shares don't repeat their key ID
+        for response in &responses {
+            let Message::SignatureShareResponse(response) = &response.msg else {
+                panic!("Expected SignatureShareResponse message");
+            };
+            assert!(response
+                .signature_shares
+                .iter()
+                .all(|share| share.key_ids.is_empty()));
+        }
+
+        let (_, operation_results) = coordinator.process_inbound_messages(&responses).unwrap();
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
+
+    #[test]
+    fn sign_retry_v1() {
+        sign_retry::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn sign_retry_v2() {
+        sign_retry::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn sign_retry<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let mut coordinator = coordinator.with_retry_policy(RetryPolicy {
+            max_attempts: 2,
+            backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        });
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        // signer 1 sends a bad signature share, so the round is retried without it
+        let msg = b"sign retry";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let mut responses = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&outbound_messages)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        if let Message::SignatureShareResponse(response) = &mut responses[1].msg {
+            response.signature_shares[0].z_i += Scalar::from(1);
+        }
+        let (outbound_messages, _) = coordinator.process_inbound_messages(&responses).unwrap();
+        match &outbound_messages[..] {
+            [Packet {
+                msg: Message::NonceRequest(request),
+                ..
+            }] => assert_eq!(request.sign_iter_id, 1),
+            _ => panic!("Expected NonceRequest message"),
+        }
+        match &coordinator.take_events()[..] {
+            [CoordinatorEvent::SignRetry {
+                sign_iter_id,
+                attempt,
+                ..
+            }] => assert_eq!((*sign_iter_id, *attempt), (1, 2)),
+            events => panic!("Expected SignRetry event, got {events:?}"),
+        }
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+
+        // when too few signers send nonces every time, the round fails once out of attempts
+        let mut message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        for attempt in 1..=2 {
+            let responses = signing_rounds[2..4]
+                .iter_mut()
+                .flat_map(|signing_round| {
+                    signing_round
+                        .process_inbound_messages(std::slice::from_ref(&message))
+                        .unwrap()
+                })
+                .collect::<Vec<Packet>>();
+            coordinator.process_inbound_messages(&responses).unwrap();
+            if attempt == 1 {
+                message = coordinator.nonce_timeout().unwrap();
+                assert!(matches!(
+                    &coordinator.take_events()[..],
+                    [CoordinatorEvent::SignRetry { attempt: 2, .. }]
+                ));
+            } else {
+                assert!(matches!(
+                    coordinator.nonce_timeout(),
+                    Err(CoordinatorError::InsufficientSigners(6, 7))
+                ));
+                assert!(coordinator.take_events().is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn bad_share_replacement_v1() {
+        bad_share_replacement::<v1::Aggregator, v1::Signer>();
+    }
+
+    #[test]
+    fn bad_share_replacement_v2() {
+        bad_share_replacement::<v2::Aggregator, v2::Signer>();
+    }
+
+    fn bad_share_replacement<Aggregator: AggregatorTrait, Signer: SignerTrait>() {
+        let (coordinator, mut signing_rounds) = setup::<Aggregator, Signer>();
+        let mut coordinator = coordinator.with_bad_share_replacement();
+        let group_key = run_dkg(&mut coordinator, &mut signing_rounds);
+
+        let msg = b"bad share replacement";
+        let message = coordinator
+            .start_signing_message(msg, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap();
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &[message]);
+        let mut responses = signing_rounds
+            .iter_mut()
+            .flat_map(|signing_round| {
+                signing_round
+                    .process_inbound_messages(&outbound_messages)
+                    .unwrap()
+            })
+            .collect::<Vec<Packet>>();
+        if let Message::SignatureShareResponse(response) = &mut responses[1].msg {
+            response.signature_shares[0].z_i += Scalar::from(1);
+        }
+
+        // new nonces are requested as soon as the bad share arrives, before the rest
+        let (outbound_messages, _) = coordinator
+            .process_inbound_messages(&responses[..2])
+            .unwrap();
+        match &outbound_messages[..] {
+            [Packet {
+                msg: Message::NonceRequest(request),
+                ..
+            }] => assert_eq!(request.sign_iter_id, 1),
+            _ => panic!("Expected NonceRequest message"),
+        }
+        assert!(coordinator.ban_list.is_banned(1));
+        assert!(!coordinator.ids_to_await.contains(&1));
+        assert!(matches!(
+            &coordinator.take_events()[..],
+            [CoordinatorEvent::SignerReplaced { signer_id: 1, .. }]
+        ));
+
+        // the shares for the abandoned iteration are ignored
+        let (stale, _) = coordinator
+            .process_inbound_messages(&responses[2..])
+            .unwrap();
+        assert!(stale.is_empty());
+
+        let (outbound_messages, _) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        let (_, operation_results) =
+            feedback_messages(&mut coordinator, &mut signing_rounds, &outbound_messages);
+        match &operation_results[..] {
+            [OperationResult::Sign(sig)] => assert!(sig.verify(&group_key, msg)),
+            _ => panic!("Expected Signature Operation result"),
+        }
+    }
 }
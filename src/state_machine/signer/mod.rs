@@ -1,24 +1,49 @@
 use hashbrown::{HashMap, HashSet};
-use p256k1::{
-    point::{Compressed, Point},
-    scalar::Scalar,
+use num_traits::Zero;
+use p256k1::{ecdsa, field, point::Point, scalar::Scalar};
+use polynomial::Polynomial;
+use rand_core::{CryptoRng, CryptoRngCore, OsRng, RngCore};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    time::Instant,
 };
-use rand_core::{CryptoRng, OsRng, RngCore};
-use std::collections::BTreeMap;
-use tracing::{debug, info, warn};
+use tracing::{debug, info, info_span, warn};
 
 use crate::{
-    common::{PolyCommitment, PublicNonce},
+    audit::{AuditLog, Direction},
+    common::{Nonce, PolyCommitment, PublicNonce, TaprootTweak},
+    compute,
+    errors::{self, ErrorContext, StoreError},
     net::{
-        DkgBegin, DkgEnd, DkgPrivateShares, DkgPublicShares, DkgStatus, Message, NonceRequest,
-        NonceResponse, Packet, Signable, SignatureShareRequest, SignatureShareResponse,
+        initial_seq, BadPrivateShare, BadShareReason, DkgBegin, DkgDecommit, DkgEcho, DkgEnd,
+        DkgFailure, DkgPrivateBegin, DkgPrivateShares, DkgPublicShares, DkgStatus, Message,
+        MessageId, MessageType, NonceCommitment, NonceRequest, NonceResponse, NonceReveal, Packet,
+        RejectReason, ShareComplaint, Signable, SignatureShareReject, SignatureShareRequest,
+        SignatureShareResponse, ThresholdChangeBegin,
     },
-    state_machine::{PublicKeys, StateMachine},
+    pvss::EncryptedShare,
+    state_machine::{
+        elapsed_states, BanList, ConfigError, KeyId, Metrics, NoopMetrics, Phase, PublicKeys,
+        PublicPoints, RoundProgress, RoundType, StateMachine,
+    },
+    store::StateStore,
     traits::Signer as SignerTrait,
-    util::{decrypt, encrypt, make_shared_secret},
+    util::{
+        decrypt, decrypt_with_passphrase, encrypt, encrypt_with_passphrase, make_share_key,
+        make_shared_secret, PASSPHRASE_KDF_ITERATIONS,
+    },
+    vss::VSS,
 };
 
-#[derive(Debug, PartialEq)]
+/// The most signing rounds a signer keeps nonces for at once; starting another discards the
+/// nonces of the one with the lowest sign_id
+pub const MAX_SIGN_SESSIONS: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 /// Signer states
 pub enum State {
     /// The signer is idle
@@ -37,6 +62,25 @@ pub enum State {
     Signed,
 }
 
+impl State {
+    /// Whether a signer in this state may move to `next`
+    pub fn can_move_to(&self, next: &State) -> bool {
+        match next {
+            State::Idle => true,
+            State::DkgPublicDistribute => {
+                self == &State::Idle
+                    || self == &State::DkgPublicGather
+                    || self == &State::DkgPrivateDistribute
+            }
+            State::DkgPublicGather => self == &State::DkgPublicDistribute,
+            State::DkgPrivateDistribute => self == &State::DkgPublicGather,
+            State::DkgPrivateGather => self == &State::DkgPrivateDistribute,
+            State::SignGather => self == &State::Idle,
+            State::Signed => self == &State::SignGather,
+        }
+    }
+}
+
 #[derive(thiserror::Error, Debug)]
 /// The error type for a signer
 pub enum Error {
@@ -46,18 +90,339 @@ pub enum Error {
     /// A DKG public share was invalid
     #[error("InvalidDkgPublicShares")]
     InvalidDkgPublicShares,
+    /// DKG polynomial commitments were malformed or failed to prove ownership
+    #[error("Invalid polynomial commitments for party IDs {0:?}")]
+    InvalidPolyCommitments(Vec<u32>),
+    /// A message came from a signer which is not in the public keys
+    #[error("Unknown signer {0}")]
+    UnknownSigner(u32),
+    /// A DKG share was addressed to a key ID which is not in the public keys
+    #[error("Unknown key ID {0}")]
+    UnknownKeyID(u32),
+    /// A signer sent DKG commitments for parties which belong to other signers
+    #[error("Signer {0} does not own party IDs {1:?}")]
+    UnownedPartyIds(u32, Vec<u32>),
+    /// Saved state could not be serialized or deserialized
+    #[error("Serialization error: {0}")]
+    Serialization(String),
+    /// Saved state could not be encrypted
+    #[error("Failed to encrypt saved state")]
+    Encryption,
+    /// Saved state could not be decrypted, e.g. because the passphrase was wrong
+    #[error("Failed to decrypt saved state")]
+    Decryption,
+    /// Saved state could not be read from or written to a store
+    #[error("Store error: {0}")]
+    Store(#[from] StoreError),
+    /// The requested backup threshold can't be met by the requested number of shares
+    #[error("Invalid backup threshold {0} for {1} shares")]
+    InvalidBackupThreshold(u32, u32),
+    /// Too few backup shares were passed to recover the saved state
+    #[error("Need {1} backup shares to recover, got {0}")]
+    NotEnoughBackupShares(usize, u32),
+    /// The backup shares were not all made by the same backup, or contained duplicates
+    #[error("Inconsistent backup shares")]
+    InconsistentBackupShares,
+    /// A threshold change asked for a threshold which the total number of keys can't meet
+    #[error("Invalid threshold {0} for {1} keys")]
+    InvalidThreshold(u32, u32),
+    /// A DkgBegin's threshold, number of signers, or number of keys didn't match this signer's
+    #[error("DKG {0} mismatch: coordinator sent {1}, signer has {2}")]
+    DkgParamMismatch(&'static str, u32, u32),
+    /// A signer's DKG commitments during a threshold change didn't reshare its existing key shares
+    #[error("Signer {0} sent commitments which don't reshare its key shares")]
+    InvalidThresholdChange(u32),
     /// A DKG private share was invalid
     #[error("InvalidDkgPrivateShares")]
     InvalidDkgPrivateShares(Vec<u32>),
-    /// A nonce response was invalid
-    #[error("InvalidNonceResponse")]
-    InvalidNonceResponse,
+    /// A nonce response had key IDs or nonces which don't match the signer's registered keys
+    #[error("Invalid NonceResponse from signer {0}")]
+    InvalidNonceResponse(u32),
     /// A signature share was invalid
     #[error("InvalidSignatureShare")]
     InvalidSignatureShare,
     /// A bad state change was made
     #[error("Bad State Change: {0}")]
     BadStateChange(String),
+    /// The config was invalid
+    #[error("Invalid config: {0}")]
+    Config(#[from] ConfigError),
+    /// There are no DKG commitments to compute the group key from
+    #[error("Missing DKG commitments")]
+    MissingCommitments,
+    /// The group key computed from the DKG commitments didn't match the expected key
+    #[error("Group key mismatch: computed {0} expected {1}")]
+    GroupKeyMismatch(Box<Point>, Box<Point>),
+    /// A message was for a different DKG round than the current one
+    #[error("Bad DKG ID: current {0} got {1}")]
+    BadDkgId(u64, u64),
+    /// A signature share request was for a signing round with no nonces
+    #[error("Unknown sign ID {0}")]
+    UnknownSignId(u64),
+    /// A signature share request was for a different iteration than the nonces were generated for
+    #[error("Bad sign iteration ID: expected {0} got {1}")]
+    BadSignIterId(u64, u64),
+    /// A signature share request was for nonces which were already used or discarded
+    #[error("Nonces for sign round {0} iteration {1} were already consumed")]
+    NonceReused(u64, u64),
+    /// A signature share request didn't carry the public nonces this signer sent for the round
+    #[error("Signature share request for sign round {0} doesn't carry this signer's nonces")]
+    NonceMismatch(u64),
+    /// A signature share request was for a digest which isn't 32 bytes
+    #[error("Digest must be 32 bytes, got {0}")]
+    BadDigestLength(usize),
+    /// A DkgBegin was for an epoch whose public keys haven't been added
+    #[error("Unknown epoch {0}")]
+    UnknownEpoch(u64),
+    /// A DkgBegin was for an epoch whose signer set doesn't include this signer's network key
+    #[error("Not a signer in epoch {0}")]
+    NotInEpoch(u64),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Something which happened while processing a message, so embedding applications can react
+/// without parsing the outbound messages
+pub enum SignerEvent {
+    /// DKG finished successfully
+    DkgCompleted {
+        /// DKG round ID
+        dkg_id: u64,
+        /// the group public key
+        group_key: Point,
+    },
+    /// The signer sent its signature shares
+    SharePosted {
+        /// signing round ID
+        sign_id: u64,
+        /// signing round iteration ID
+        sign_iter_id: u64,
+    },
+    /// A DKG or signing round failed on this signer's side
+    RoundFailed {
+        /// why the round failed
+        reason: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A snapshot of whether a signer is ready to sign, which it can report to the coordinator
+pub struct SignerStatus {
+    /// the Signer ID
+    pub signer_id: u32,
+    /// the key IDs the signer holds shares of
+    pub key_ids: Vec<u32>,
+    /// the DKG round ID which the key shares came from
+    pub dkg_id: u64,
+    /// whether DKG has finished, so there are key shares to sign with
+    pub has_dkg_output: bool,
+    /// the number of signing rounds holding nonces which haven't been used yet
+    pub pending_sessions: usize,
+    /// the current state of the signer
+    pub state: State,
+}
+
+#[derive(Clone, Debug)]
+/// The private nonce state for one in-flight signing round
+pub struct SignSession {
+    /// signing round iteration ID the nonces were generated for
+    pub sign_iter_id: u64,
+    /// private nonces which will be used to sign
+    nonces: Vec<Nonce>,
+    /// public nonces which were sent to the coordinator
+    pub public_nonces: Vec<PublicNonce>,
+    /// when the nonces were generated
+    started: Instant,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// One share of an M-of-K backup of a signer's saved state
+pub struct BackupShare {
+    /// the ID of this share
+    pub id: u32,
+    /// the number of shares needed to recover the saved state
+    pub threshold: u32,
+    /// this share of the random recovery key
+    pub key_share: Scalar,
+    /// the saved state, encrypted under the recovery key
+    pub encrypted_state: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+/// The state required to restore a SigningRound, e.g. after a restart
+pub struct SavedState<SignerState> {
+    /// current DKG round ID
+    pub dkg_id: u64,
+    /// the threshold of the keys needed for a valid signature
+    pub threshold: u32,
+    /// the total number of signers
+    pub total_signers: u32,
+    /// the total number of keys
+    pub total_keys: u32,
+    /// the Signer ID
+    pub signer_id: u32,
+    /// map of party_id to the polynomial commitment for that party
+    pub commitments: BTreeMap<u32, PolyCommitment>,
+    /// the saved state of the Signer object, which holds the private key shares
+    pub signer: SignerState,
+    /// signers whose messages are dropped
+    pub ban_list: BanList,
+}
+
+/// The state needed to check an in-flight threshold change, and to roll back if it fails
+pub struct ThresholdChange<SignerState> {
+    /// the state before the threshold change started
+    pub saved: SavedState<SignerState>,
+    /// map of signer_id to the sum of the constant terms that signer must commit to, which is
+    /// the Lagrange weighted sum of the public keys of its existing key shares, or None if this
+    /// signer is being recovered and has no key shares to check them against
+    pub expected_constants: Option<HashMap<u32, Point>>,
+}
+
+/// Decides whether a signer is willing to sign the message in a signature share request, e.g. to
+/// only sign block proposals which match the signer's local view
+pub trait SigningPolicy {
+    /// Check `request`, returning the reason for refusing to sign it if it isn't acceptable
+    fn check(&self, request: &SignatureShareRequest) -> Result<(), String>;
+}
+
+impl<F: Fn(&SignatureShareRequest) -> Result<(), String>> SigningPolicy for F {
+    fn check(&self, request: &SignatureShareRequest) -> Result<(), String> {
+        self(request)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+/// Everything needed to construct a SigningRound, which can be built in code or loaded from a
+/// config file
+pub struct SignerConfig {
+    /// the threshold of the keys needed for a valid signature
+    pub threshold: u32,
+    /// the total number of signers
+    pub total_signers: u32,
+    /// the total number of keys
+    pub total_keys: u32,
+    /// the Signer ID
+    pub signer_id: u32,
+    /// the key IDs owned by this signer
+    pub key_ids: Vec<u32>,
+    /// the private key used to sign messages sent over the network
+    pub network_private_key: Scalar,
+    /// the public keys for all signers and coordinator
+    pub public_keys: PublicKeys,
+    /// domain separation tag bound into every signed message, empty by default
+    #[serde(default)]
+    pub domain: Vec<u8>,
+    /// signers whose messages are dropped, empty by default
+    #[serde(default)]
+    pub ban_list: BanList,
+    /// send DKG private shares with publicly verifiable encryption, off by default
+    #[serde(default)]
+    pub verifiable_shares: bool,
+}
+
+impl SignerConfig {
+    /// Create a config whose total signers and keys are the number registered in `public_keys`
+    pub fn new(
+        threshold: u32,
+        signer_id: u32,
+        key_ids: Vec<u32>,
+        network_private_key: Scalar,
+        public_keys: PublicKeys,
+    ) -> Self {
+        Self {
+            threshold,
+            total_signers: public_keys.signers.len().try_into().unwrap_or(u32::MAX),
+            total_keys: public_keys.key_ids.len().try_into().unwrap_or(u32::MAX),
+            signer_id,
+            key_ids,
+            network_private_key,
+            public_keys,
+            domain: Vec::new(),
+            ban_list: BanList::default(),
+            verifiable_shares: false,
+        }
+    }
+
+    /// set the total number of signers
+    pub fn with_total_signers(mut self, total_signers: u32) -> Self {
+        self.total_signers = total_signers;
+        self
+    }
+
+    /// set the total number of keys
+    pub fn with_total_keys(mut self, total_keys: u32) -> Self {
+        self.total_keys = total_keys;
+        self
+    }
+
+    /// set the domain separation tag used when signing outbound messages
+    pub fn with_domain(mut self, domain: &[u8]) -> Self {
+        self.domain = domain.to_vec();
+        self
+    }
+
+    /// set the signers whose messages will be dropped
+    pub fn with_ban_list(mut self, ban_list: BanList) -> Self {
+        self.ban_list = ban_list;
+        self
+    }
+
+    /// Check that the config describes a signer which can take part in DKG and signing
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.threshold == 0 || self.threshold > self.total_keys {
+            return Err(ConfigError::InvalidThreshold(
+                self.threshold,
+                self.total_keys,
+            ));
+        }
+        if self.total_signers == 0 || self.total_signers > self.total_keys {
+            return Err(ConfigError::InvalidTotalSigners(
+                self.total_signers,
+                self.total_keys,
+            ));
+        }
+        if self.signer_id >= self.total_signers {
+            return Err(ConfigError::InvalidSignerId(
+                self.signer_id,
+                self.total_signers,
+            ));
+        }
+        if self.key_ids.is_empty() {
+            return Err(ConfigError::NoKeyIds);
+        }
+        if let Some(key_id) = self
+            .key_ids
+            .iter()
+            .find(|key_id| **key_id >= self.total_keys)
+        {
+            return Err(ConfigError::InvalidKeyId(*key_id, self.total_keys));
+        }
+
+        let public_key = self
+            .public_keys
+            .signers
+            .get(&self.signer_id)
+            .ok_or(ConfigError::UnknownSigner(self.signer_id))?;
+        let network_public_key = ecdsa::PublicKey::new(&self.network_private_key)
+            .map_err(|_| ConfigError::NetworkKeyMismatch(self.signer_id))?;
+        if network_public_key.to_bytes() != public_key.to_bytes() {
+            return Err(ConfigError::NetworkKeyMismatch(self.signer_id));
+        }
+
+        let registered_key_ids = self
+            .public_keys
+            .signer_key_ids(self.signer_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(u32::from)
+            .collect::<HashSet<u32>>();
+        if registered_key_ids != self.key_ids.iter().copied().collect() {
+            return Err(ConfigError::KeyIdMismatch(self.signer_id));
+        }
+
+        self.public_keys
+            .validate(self.total_signers, self.total_keys)
+    }
 }
 
 /// A state machine for a signing round
@@ -84,14 +449,68 @@ pub struct SigningRound<Signer: SignerTrait> {
     pub commitments: BTreeMap<u32, PolyCommitment>,
     /// map of decrypted DKG private shares
     pub decrypted_shares: HashMap<u32, HashMap<u32, Scalar>>,
-    /// invalid private shares
-    pub invalid_private_shares: Vec<u32>,
+    /// map of signer_id to the private shares from that signer which couldn't be decrypted
+    pub invalid_private_shares: BTreeMap<u32, BTreeSet<BadPrivateShare>>,
+    /// map of signer_id to the proofs that verifiable private shares from that signer couldn't be
+    /// decrypted
+    pub share_complaints: BTreeMap<u32, Vec<ShareComplaint>>,
+    /// map of signer_id to the hash of the DKG public shares that signer received
+    pub echoes: HashMap<u32, [u8; 32]>,
     /// public nonces for this signing round
     pub public_nonces: Vec<PublicNonce>,
+    /// in-flight signing rounds, indexed by sign_id
+    pub sessions: BTreeMap<u64, SignSession>,
+    /// the (sign_id, sign_iter_id) of sessions whose nonces have been used or discarded, so they
+    /// are never used to sign again; only kept back to the lowest live sign_id, since rounds
+    /// below that have no nonces left to reuse
+    pub consumed_nonces: BTreeSet<(u64, u64)>,
+    /// DKG messages which arrived before the state machine was ready for them, indexed by dkg_id
+    pub pending: BTreeMap<u64, Vec<Message>>,
+    /// DKG messages which have already been processed, so retransmissions can be ignored
+    pub processed: HashSet<MessageId>,
+    /// the signers whose nonces were in the last signature share request
+    pub nonce_signer_ids: BTreeSet<u32>,
     /// the private key used to sign messages sent over the network
     pub network_private_key: Scalar,
     /// the public keys for all signers and coordinator
     pub public_keys: PublicKeys,
+    /// the public keys decompressed into points, which must be kept in sync with public_keys
+    pub public_points: PublicPoints,
+    /// the epoch whose signer and key assignments are in public_keys
+    pub epoch: u64,
+    /// the public keys of each epoch which a DkgBegin may move the signer to
+    pub epochs: BTreeMap<u64, PublicKeys>,
+    /// domain separation tag bound into every signed message (e.g. network magic or chain ID)
+    pub domain: Vec<u8>,
+    /// signers whose messages are dropped
+    pub ban_list: BanList,
+    /// whether to send DKG private shares with publicly verifiable encryption, which anyone can
+    /// check against the commitments, instead of encrypting them to each signer
+    pub verifiable_shares: bool,
+    /// whether the current DKG round uses Pedersen commitments, as its DkgBegin asked
+    pub pedersen: bool,
+    /// map of party_id to the Pedersen commitment for that party, during a Pedersen DKG round
+    pub hiding_commitments: BTreeMap<u32, Vec<Point>>,
+    /// the in-flight threshold change, if any
+    pub threshold_change: Option<ThresholdChange<Signer::State>>,
+    /// the policy which decides which messages to sign, or None to sign anything
+    pub signing_policy: Option<Box<dyn SigningPolicy + Send>>,
+    /// events from processing messages which haven't been taken yet
+    pub events: Vec<SignerEvent>,
+    /// the sequence number of the last packet this signer signed
+    pub seq: u64,
+    /// where counters and gauges are recorded
+    pub metrics: Box<dyn Metrics + Send>,
+    /// a second source of randomness, e.g. a hardware RNG, which is mixed into every nonce
+    pub aux_rand: Option<Box<dyn CryptoRngCore + Send>>,
+    /// the polynomials blinding this signer's Pedersen commitments, indexed like commitments
+    blinding_polys: BTreeMap<u32, Polynomial<Scalar>>,
+    /// when the current DKG round started
+    dkg_started: Option<Instant>,
+    /// the states the current round has been through, and when it entered each of them
+    state_history: Vec<(State, Instant)>,
+    /// the log of packets sent and received, or None to not keep one
+    pub audit_log: Option<AuditLog>,
 }
 
 impl<Signer: SignerTrait> SigningRound<Signer> {
@@ -131,78 +550,591 @@ impl<Signer: SignerTrait> SigningRound<Signer> {
             state: State::Idle,
             commitments: BTreeMap::new(),
             decrypted_shares: HashMap::new(),
-            invalid_private_shares: Vec::new(),
+            invalid_private_shares: BTreeMap::new(),
+            share_complaints: BTreeMap::new(),
+            echoes: HashMap::new(),
             public_nonces: vec![],
+            sessions: BTreeMap::new(),
+            consumed_nonces: BTreeSet::new(),
+            pending: BTreeMap::new(),
+            processed: HashSet::new(),
+            nonce_signer_ids: BTreeSet::new(),
+            network_private_key,
+            public_points: public_keys.decompress(),
+            public_keys,
+            epoch: 0,
+            epochs: BTreeMap::new(),
+            domain: Vec::new(),
+            ban_list: BanList::default(),
+            verifiable_shares: false,
+            pedersen: false,
+            hiding_commitments: BTreeMap::new(),
+            threshold_change: None,
+            signing_policy: None,
+            events: Vec::new(),
+            seq: initial_seq(),
+            metrics: Box::new(NoopMetrics),
+            aux_rand: None,
+            blinding_polys: BTreeMap::new(),
+            dkg_started: None,
+            state_history: Vec::new(),
+            audit_log: None,
+        }
+    }
+
+    /// create a SigningRound from a config, after checking that it's valid
+    pub fn from_config(config: SignerConfig) -> Result<Self, Error> {
+        config.validate()?;
+        let mut signing_round = Self::new(
+            config.threshold,
+            config.total_signers,
+            config.total_keys,
+            config.signer_id,
+            config.key_ids,
+            config.network_private_key,
+            config.public_keys,
+        )
+        .with_domain(&config.domain)
+        .with_ban_list(config.ban_list);
+        signing_round.verifiable_shares = config.verifiable_shares;
+        Ok(signing_round)
+    }
+
+    /// set the domain separation tag used when signing outbound messages
+    pub fn with_domain(mut self, domain: &[u8]) -> Self {
+        self.domain = domain.to_vec();
+        self
+    }
+
+    /// Add the signer and key assignments for `epoch`, e.g. a future reward cycle, so a DkgBegin
+    /// for that epoch can move the signer to them without a restart
+    pub fn add_epoch(&mut self, epoch: u64, public_keys: PublicKeys) {
+        self.epochs.insert(epoch, public_keys);
+    }
+
+    /// set the signers whose messages will be dropped, e.g. from a previously saved ban list
+    pub fn with_ban_list(mut self, ban_list: BanList) -> Self {
+        self.ban_list = ban_list;
+        self
+    }
+
+    /// send DKG private shares with publicly verifiable encryption, so any observer can check
+    /// them against the commitments and complaints about them need no interaction
+    pub fn with_verifiable_shares(mut self) -> Self {
+        self.verifiable_shares = true;
+        self
+    }
+
+    /// set the policy which decides which messages to sign
+    pub fn with_signing_policy(mut self, policy: impl SigningPolicy + Send + 'static) -> Self {
+        self.signing_policy = Some(Box::new(policy));
+        self
+    }
+
+    /// set where counters and gauges are recorded
+    pub fn with_metrics(mut self, metrics: impl Metrics + Send + 'static) -> Self {
+        self.metrics = Box::new(metrics);
+        self
+    }
+
+    /// mix the output of `aux_rand` into every nonce, alongside the system RNG
+    pub fn with_aux_rand(mut self, aux_rand: impl CryptoRngCore + Send + 'static) -> Self {
+        self.aux_rand = Some(Box::new(aux_rand));
+        self
+    }
+
+    /// keep a log of the packets passed to and returned from process_inbound_messages
+    pub fn with_audit_log(mut self, audit_log: AuditLog) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// take the events from the messages processed since the last call
+    pub fn take_events(&mut self) -> Vec<SignerEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// report whether this signer is ready to sign
+    pub fn status(&self) -> SignerStatus {
+        SignerStatus {
+            signer_id: self.signer_id,
+            key_ids: self.signer.get_key_ids(),
+            dkg_id: self.dkg_id,
+            has_dkg_output: self.signer.get_group_key() != Point::zero(),
+            pending_sessions: self.sessions.len(),
+            state: self.state,
+        }
+    }
+
+    /// The signers which have submitted `phase` during the current round, as far as this signer
+    /// can see.  DKG shares are broadcast so every signer sees them (its own only if the network
+    /// delivers them back to it), but nonces are only seen
+    /// once the coordinator asks for signature shares, and other signers' signature shares are
+    /// never seen, so `Phase::SignatureShares` is always empty.
+    pub fn participants(&self, phase: Phase) -> BTreeSet<u32> {
+        let message_type = match phase {
+            Phase::DkgPublicShares => MessageType::DkgPublicShares,
+            Phase::DkgPrivateShares => MessageType::DkgPrivateShares,
+            Phase::Nonces => return self.nonce_signer_ids.clone(),
+            Phase::SignatureShares => return BTreeSet::new(),
+        };
+        self.processed
+            .iter()
+            .filter(|id| id.message_type == message_type && id.dkg_id == self.dkg_id)
+            .filter_map(|id| id.signer_id)
+            .collect()
+    }
+
+    /// A snapshot of how far the current round has got.  During DKG the signers still being
+    /// waited on are the ones this signer hasn't seen shares from yet.
+    pub fn round_progress(&self) -> RoundProgress<State> {
+        let waiting_on = match self.state {
+            State::DkgPublicDistribute | State::DkgPublicGather => Some(Phase::DkgPublicShares),
+            State::DkgPrivateDistribute | State::DkgPrivateGather => Some(Phase::DkgPrivateShares),
+            _ => None,
+        };
+        let missing = match waiting_on {
+            Some(phase) => {
+                let participants = self.participants(phase);
+                (0..self.total_signers)
+                    .filter(|signer_id| {
+                        *signer_id != self.signer_id && !participants.contains(signer_id)
+                    })
+                    .collect()
+            }
+            None => BTreeSet::new(),
+        };
+        RoundProgress {
+            state: self.state,
+            dkg_id: self.dkg_id,
+            sign_id: self.sign_id,
+            sign_iter_id: self.sign_iter_id,
+            counts: Phase::ALL
+                .into_iter()
+                .map(|phase| (phase, self.participants(phase).len()))
+                .collect(),
+            missing,
+            elapsed: elapsed_states(&self.state_history, Instant::now()),
+        }
+    }
+
+    /// save the state required to restore this SigningRound
+    pub fn save(&self) -> SavedState<Signer::State> {
+        SavedState {
+            dkg_id: self.dkg_id,
+            threshold: self.threshold,
+            total_signers: self.total_signers,
+            total_keys: self.total_keys,
+            signer_id: self.signer_id,
+            commitments: self.commitments.clone(),
+            signer: self.signer.save(),
+            ban_list: self.ban_list.clone(),
+        }
+    }
+
+    /// restore a SigningRound from saved state
+    pub fn load(
+        state: &SavedState<Signer::State>,
+        network_private_key: Scalar,
+        public_keys: PublicKeys,
+    ) -> Self {
+        let signer = Signer::load(&state.signer);
+        let mut signing_round = Self::new(
+            state.threshold,
+            state.total_signers,
+            state.total_keys,
+            state.signer_id,
+            signer.get_key_ids(),
             network_private_key,
             public_keys,
+        );
+        signing_round.dkg_id = state.dkg_id;
+        signing_round.commitments = state.commitments.clone();
+        signing_round.signer = signer;
+        signing_round.ban_list = state.ban_list.clone();
+        signing_round
+    }
+
+    /// serialize the saved state and encrypt it under a key derived from `passphrase`, so the
+    /// private key shares can be stored on disk or in backups
+    pub fn export_encrypted(&self, passphrase: &[u8]) -> Result<Vec<u8>, Error> {
+        self.export_encrypted_with_iterations(passphrase, PASSPHRASE_KDF_ITERATIONS)
+    }
+
+    /// like `export_encrypted`, but with an explicit number of key derivation iterations
+    pub fn export_encrypted_with_iterations(
+        &self,
+        passphrase: &[u8],
+        iterations: u32,
+    ) -> Result<Vec<u8>, Error> {
+        let plain =
+            serde_json::to_vec(&self.save()).map_err(|e| Error::Serialization(e.to_string()))?;
+        encrypt_with_passphrase(passphrase, &plain, iterations, &mut OsRng)
+            .map_err(|_| Error::Encryption)
+    }
+
+    /// decrypt and restore a SigningRound which was exported by `export_encrypted`
+    pub fn import_encrypted(
+        bytes: &[u8],
+        passphrase: &[u8],
+        network_private_key: Scalar,
+        public_keys: PublicKeys,
+    ) -> Result<Self, Error> {
+        let plain = decrypt_with_passphrase(passphrase, bytes).map_err(|_| Error::Decryption)?;
+        let state =
+            serde_json::from_slice(&plain).map_err(|e| Error::Serialization(e.to_string()))?;
+        Ok(Self::load(&state, network_private_key, public_keys))
+    }
+
+    /// encrypt the saved state under a key derived from `passphrase` and put it in `store` at
+    /// `key`
+    pub fn save_to_store<S: StateStore + ?Sized>(
+        &self,
+        store: &mut S,
+        key: &str,
+        passphrase: &[u8],
+    ) -> Result<(), Error> {
+        store.put(key, &self.export_encrypted(passphrase)?)?;
+        Ok(())
+    }
+
+    /// restore a SigningRound which `save_to_store` put in `store` at `key`, or None if there
+    /// isn't one
+    pub fn load_from_store<S: StateStore + ?Sized>(
+        store: &S,
+        key: &str,
+        passphrase: &[u8],
+        network_private_key: Scalar,
+        public_keys: PublicKeys,
+    ) -> Result<Option<Self>, Error> {
+        store
+            .get(key)?
+            .map(|bytes| {
+                Self::import_encrypted(&bytes, passphrase, network_private_key, public_keys)
+            })
+            .transpose()
+    }
+
+    /// encrypt the saved state under a random recovery key, then split that key into
+    /// `num_shares` backup shares using Shamir secret sharing, any `threshold` of which can
+    /// recover the state
+    pub fn backup<RNG: RngCore + CryptoRng>(
+        &self,
+        threshold: u32,
+        num_shares: u32,
+        rng: &mut RNG,
+    ) -> Result<Vec<BackupShare>, Error> {
+        if threshold == 0 || threshold > num_shares {
+            return Err(Error::InvalidBackupThreshold(threshold, num_shares));
+        }
+
+        let recovery_key = Scalar::random(rng);
+        let plain =
+            serde_json::to_vec(&self.save()).map_err(|e| Error::Serialization(e.to_string()))?;
+        let encrypted_state =
+            encrypt(&recovery_key.to_bytes(), &plain, rng).map_err(|_| Error::Encryption)?;
+
+        Ok(VSS::split(&recovery_key, threshold, num_shares, rng)
+            .into_iter()
+            .map(|(id, key_share)| BackupShare {
+                id,
+                threshold,
+                key_share,
+                encrypted_state: encrypted_state.clone(),
+            })
+            .collect())
+    }
+
+    /// restore a SigningRound from at least `threshold` shares made by `backup`
+    pub fn recover(
+        shares: &[BackupShare],
+        network_private_key: Scalar,
+        public_keys: PublicKeys,
+    ) -> Result<Self, Error> {
+        let Some(first) = shares.first() else {
+            return Err(Error::NotEnoughBackupShares(0, 1));
+        };
+
+        let ids: HashSet<u32> = shares.iter().map(|share| share.id).collect();
+        if ids.len() != shares.len()
+            || shares.iter().any(|share| {
+                share.threshold != first.threshold || share.encrypted_state != first.encrypted_state
+            })
+        {
+            return Err(Error::InconsistentBackupShares);
+        }
+        if shares.len() < first.threshold as usize {
+            return Err(Error::NotEnoughBackupShares(shares.len(), first.threshold));
+        }
+
+        let key_shares: Vec<(u32, Scalar)> = shares
+            .iter()
+            .map(|share| (share.id, share.key_share))
+            .collect();
+        let recovery_key = VSS::reconstruct(&key_shares);
+        let plain = decrypt(&recovery_key.to_bytes(), &first.encrypted_state)
+            .map_err(|_| Error::Decryption)?;
+        let state =
+            serde_json::from_slice(&plain).map_err(|e| Error::Serialization(e.to_string()))?;
+        Ok(Self::load(&state, network_private_key, public_keys))
+    }
+
+    /// ban a signer for sending provably bad DKG data
+    fn ban(&mut self, signer_id: u32) {
+        if self.ban_list.ban(signer_id) {
+            warn!("Signer {} banned signer {}", self.signer_id, signer_id);
+        }
+    }
+
+    /// restore the key shares, threshold and commitments from before the in-flight threshold
+    /// change, if any
+    fn abort_threshold_change(&mut self) {
+        if let Some(threshold_change) = self.threshold_change.take() {
+            warn!(
+                "Signer {} aborting threshold change in DKG round {}",
+                self.signer_id, self.dkg_id
+            );
+            self.threshold = threshold_change.saved.threshold;
+            self.commitments = threshold_change.saved.commitments;
+            self.signer = Signer::load(&threshold_change.saved.signer);
         }
     }
 
     fn reset<T: RngCore + CryptoRng>(&mut self, dkg_id: u64, rng: &mut T) {
+        self.abort_threshold_change();
         self.dkg_id = dkg_id;
         self.commitments.clear();
         self.decrypted_shares.clear();
         self.invalid_private_shares.clear();
+        self.share_complaints.clear();
+        self.pedersen = false;
+        self.hiding_commitments.clear();
+        self.blinding_polys.clear();
+        self.echoes.clear();
         self.public_nonces.clear();
+        self.sessions.clear();
+        self.consumed_nonces.clear();
+        self.pending.retain(|id, _| *id == dkg_id);
+        self.processed.retain(|id| id.dkg_id >= dkg_id);
+        self.nonce_signer_ids.clear();
         self.signer.reset_polys(rng);
+        self.dkg_started = Some(Instant::now());
+        self.metrics.nonce_pool_depth(0);
     }
 
-    ///
+    /// process the passed incoming packets, and return any signed outgoing packets needed in response
     pub fn process_inbound_messages(&mut self, messages: &[Packet]) -> Result<Vec<Packet>, Error> {
         let mut responses = vec![];
         for message in messages {
             // TODO: this code was swiped from frost-signer. Expose it there so we don't have duplicate code
             // See: https://github.com/stacks-network/stacks-blockchain/issues/3913
+            if let Some(audit_log) = &mut self.audit_log {
+                audit_log.record(Direction::Inbound, message);
+            }
+            if !self.is_from_leader(message) {
+                continue;
+            }
             let outbounds = self.process(&message.msg)?;
             for out in outbounds {
-                let msg = Packet {
-                    sig: match &out {
-                        Message::DkgBegin(msg) | Message::DkgPrivateBegin(msg) => msg
-                            .sign(&self.network_private_key)
-                            .expect("failed to sign DkgBegin")
-                            .to_vec(),
-                        Message::DkgEnd(msg) => msg
-                            .sign(&self.network_private_key)
-                            .expect("failed to sign DkgEnd")
-                            .to_vec(),
-                        Message::DkgPublicShares(msg) => msg
-                            .sign(&self.network_private_key)
-                            .expect("failed to sign DkgPublicShares")
-                            .to_vec(),
-                        Message::DkgPrivateShares(msg) => msg
-                            .sign(&self.network_private_key)
-                            .expect("failed to sign DkgPrivateShare")
-                            .to_vec(),
-                        Message::NonceRequest(msg) => msg
-                            .sign(&self.network_private_key)
-                            .expect("failed to sign NonceRequest")
-                            .to_vec(),
-                        Message::NonceResponse(msg) => msg
-                            .sign(&self.network_private_key)
-                            .expect("failed to sign NonceResponse")
-                            .to_vec(),
-                        Message::SignatureShareRequest(msg) => msg
-                            .sign(&self.network_private_key)
-                            .expect("failed to sign SignShareRequest")
-                            .to_vec(),
-                        Message::SignatureShareResponse(msg) => msg
-                            .sign(&self.network_private_key)
-                            .expect("failed to sign SignShareResponse")
-                            .to_vec(),
-                    },
-                    msg: out,
-                };
-                responses.push(msg);
+                self.seq += 1;
+                let packet = Packet::new(out, self.seq, &self.domain, &self.network_private_key)
+                    .expect("failed to sign packet");
+                if let Some(audit_log) = &mut self.audit_log {
+                    audit_log.record(Direction::Outbound, &packet);
+                }
+                responses.push(packet);
             }
         }
         Ok(responses)
     }
 
+    /// Process inbound messages like `process_inbound_messages`, but on failure say which round
+    /// and sender the failing message belonged to
+    pub fn process_inbound_messages_with_context(
+        &mut self,
+        messages: &[Packet],
+    ) -> Result<Vec<Packet>, errors::Error> {
+        let mut responses = vec![];
+        for message in messages {
+            let outbounds = self
+                .process_inbound_messages(std::slice::from_ref(message))
+                .map_err(|e| {
+                    let context =
+                        ErrorContext::from_message(&message.msg).with_signer_id(self.signer_id);
+                    errors::Error::new(e, context)
+                })?;
+            responses.extend(outbounds);
+        }
+        Ok(responses)
+    }
+
+    /// If coordinators are registered in the public keys, is this packet either from a signer or
+    /// signed by the leader of its round?  Requests from any other coordinator are dropped, so
+    /// signers follow one leader per round even while a failed coordinator is being replaced.
+    fn is_from_leader(&self, packet: &Packet) -> bool {
+        let id = packet.msg.id();
+        if id.signer_id.is_some() {
+            return true;
+        }
+        // a DkgBegin for a new epoch is led by one of that epoch's coordinators
+        let public_keys = match &packet.msg {
+            Message::DkgBegin(dkg_begin) => self
+                .epochs
+                .get(&dkg_begin.epoch)
+                .unwrap_or(&self.public_keys),
+            _ => &self.public_keys,
+        };
+        let Some(leader) = public_keys.leader(id.dkg_id, id.sign_id) else {
+            return true;
+        };
+        if packet.verify_with_domain(&self.domain, &packet.sig, leader) {
+            return true;
+        }
+        debug!(
+            "Signer {} dropping {:?} for round ({}, {}) which isn't from its leader",
+            self.signer_id, id.message_type, id.dkg_id, id.sign_id
+        );
+        false
+    }
+
     /// process the passed incoming message, and return any outgoing messages needed in response
     pub fn process(&mut self, message: &Message) -> Result<Vec<Message>, Error> {
+        let id = message.id();
+        let _span = info_span!(
+            "signer",
+            signer_id = self.signer_id,
+            dkg_id = id.dkg_id,
+            sign_id = id.sign_id,
+            sign_iter_id = id.sign_iter_id,
+            msg_type = ?id.message_type,
+        )
+        .entered();
+
+        if self.ban_list.drops(message) {
+            debug!(
+                "Signer {} dropping {:?} from banned signer {:?}",
+                self.signer_id, id.message_type, id.signer_id
+            );
+            return Ok(vec![]);
+        }
+
+        self.metrics.message_processed(id.message_type);
+        let result = self.process_inner(message);
+        if result.is_err() {
+            self.metrics.round_failed(id.message_type.into());
+        }
+        result
+    }
+
+    fn process_inner(&mut self, message: &Message) -> Result<Vec<Message>, Error> {
+        if self.is_early(message) {
+            self.buffer(message);
+            return Ok(vec![]);
+        }
+
+        let mut out = self.process_message(message)?;
+        out.extend(self.process_pending()?);
+        Ok(out)
+    }
+
+    /// Buffer an early DKG message until the state machine is ready for it.  Only messages for
+    /// this round or the next are kept, and at most one of each type from each sender, so peers
+    /// can't fill the buffer.
+    fn buffer(&mut self, message: &Message) {
+        let id = message.id();
+        if id.dkg_id > self.dkg_id.saturating_add(1) {
+            debug!(
+                "Signer {} dropping {:?} for DKG round {}, which is too far ahead of round {}",
+                self.signer_id, id.message_type, id.dkg_id, self.dkg_id
+            );
+            return;
+        }
+        let buffered = self.pending.entry(id.dkg_id).or_default();
+        if buffered.iter().any(|msg| {
+            let other = msg.id();
+            other.message_type == id.message_type && other.signer_id == id.signer_id
+        }) {
+            debug!(
+                "Signer {} dropping {:?} from {:?} for DKG round {}, which already has one buffered",
+                self.signer_id, id.message_type, id.signer_id, id.dkg_id
+            );
+            return;
+        }
+        debug!(
+            "Signer {} buffering early message for DKG round {} in state {:?}",
+            self.signer_id, id.dkg_id, self.state
+        );
+        buffered.push(message.clone());
+    }
+
+    /// is this a DKG message which arrived before the state machine is ready for it?
+    fn is_early(&self, message: &Message) -> bool {
+        let public_phase = matches!(
+            self.state,
+            State::DkgPublicDistribute | State::DkgPublicGather
+        );
+        match message {
+            Message::DkgPublicShares(msg) => msg.dkg_id > self.dkg_id,
+            Message::DkgEcho(msg) => msg.dkg_id > self.dkg_id,
+            Message::DkgPrivateBegin(msg) => {
+                msg.dkg_id > self.dkg_id || (msg.dkg_id == self.dkg_id && public_phase)
+            }
+            Message::DkgPrivateShares(DkgPrivateShares { dkg_id, .. })
+            | Message::DkgDecommit(DkgDecommit { dkg_id, .. }) => {
+                *dkg_id > self.dkg_id
+                    || (*dkg_id == self.dkg_id
+                        && (public_phase || self.state == State::DkgPrivateDistribute))
+            }
+            _ => false,
+        }
+    }
+
+    /// replay buffered messages for the current DKG round which the state machine can now handle
+    fn process_pending(&mut self) -> Result<Vec<Message>, Error> {
+        let mut out = vec![];
+        while let Some(messages) = self.pending.remove(&self.dkg_id) {
+            let (waiting, ready): (Vec<Message>, Vec<Message>) =
+                messages.into_iter().partition(|msg| self.is_early(msg));
+            if !waiting.is_empty() {
+                self.pending.insert(self.dkg_id, waiting);
+            }
+            if ready.is_empty() {
+                break;
+            }
+            for message in &ready {
+                out.extend(self.process_message(message)?);
+            }
+        }
+        Ok(out)
+    }
+
+    fn process_message(&mut self, message: &Message) -> Result<Vec<Message>, Error> {
+        // only messages which were handled successfully count as processed, so a bad copy which
+        // arrives first doesn't shadow the genuine one
+        let deduplicate = matches!(
+            message,
+            Message::DkgBegin(_)
+                | Message::DkgPrivateBegin(_)
+                | Message::DkgPublicShares(_)
+                | Message::DkgPrivateShares(_)
+                | Message::DkgEcho(_)
+                | Message::DkgDecommit(_)
+                | Message::ThresholdChangeBegin(_)
+        );
+        if deduplicate && self.processed.contains(&message.id()) {
+            debug!(
+                "Signer {} ignoring duplicate {:?}",
+                self.signer_id,
+                message.id()
+            );
+            return Ok(vec![]);
+        }
+
         let out_msgs = match message {
             Message::DkgBegin(dkg_begin) => self.dkg_begin(dkg_begin),
-            Message::DkgPrivateBegin(_) => self.dkg_private_begin(),
+            Message::DkgPrivateBegin(dkg_private_begin) => {
+                self.dkg_private_begin(dkg_private_begin)
+            }
             Message::DkgPublicShares(dkg_public_shares) => self.dkg_public_share(dkg_public_shares),
             Message::DkgPrivateShares(dkg_private_shares) => {
                 self.dkg_private_shares(dkg_private_shares)
@@ -211,11 +1143,20 @@ impl<Signer: SignerTrait> SigningRound<Signer> {
                 self.sign_share_request(sign_share_request)
             }
             Message::NonceRequest(nonce_request) => self.nonce_request(nonce_request),
+            Message::NonceReveal(nonce_reveal) => self.nonce_reveal(nonce_reveal),
+            Message::DkgEcho(dkg_echo) => self.dkg_echo(dkg_echo),
+            Message::DkgDecommit(dkg_decommit) => self.dkg_decommit(dkg_decommit),
+            Message::ThresholdChangeBegin(threshold_change_begin) => {
+                self.threshold_change_begin(threshold_change_begin)
+            }
             _ => Ok(vec![]), // TODO
         };
 
         match out_msgs {
             Ok(mut out) => {
+                if deduplicate {
+                    self.processed.insert(message.id());
+                }
                 if self.public_shares_done() {
                     debug!(
                         "public_shares_done==true. commitments {}",
@@ -240,47 +1181,147 @@ impl<Signer: SignerTrait> SigningRound<Signer> {
 
     /// DKG is done so compute secrets
     pub fn dkg_ended(&mut self) -> Result<Message, Error> {
-        let polys: Vec<PolyCommitment> = self.commitments.clone().into_values().collect();
+        let polys: Vec<&PolyCommitment> = self.commitments.values().collect();
 
-        let dkg_end = if self.invalid_private_shares.is_empty() {
-            match self.signer.compute_secrets(&self.decrypted_shares, &polys) {
-                Ok(()) => DkgEnd {
-                    dkg_id: self.dkg_id,
-                    signer_id: self.signer_id,
-                    status: DkgStatus::Success,
-                },
+        let public_shares_hash = self.public_shares_hash();
+        let mut echo_mismatches = self
+            .echoes
+            .iter()
+            .filter(|(_, hash)| **hash != public_shares_hash)
+            .map(|(signer_id, _)| *signer_id)
+            .collect::<Vec<u32>>();
+        echo_mismatches.sort();
+
+        let dkg_end = if !echo_mismatches.is_empty() {
+            DkgEnd {
+                dkg_id: self.dkg_id,
+                signer_id: self.signer_id,
+                status: DkgStatus::Failure(DkgFailure::EchoMismatch(echo_mismatches)),
+            }
+        } else if !self.share_complaints.is_empty() && self.invalid_private_shares.is_empty() {
+            DkgEnd {
+                dkg_id: self.dkg_id,
+                signer_id: self.signer_id,
+                status: DkgStatus::Failure(DkgFailure::ShareComplaints(
+                    self.share_complaints.clone(),
+                )),
+            }
+        } else if self.invalid_private_shares.is_empty() {
+            match self.signer.compute_secrets(&self.decrypted_shares, &polys) {
+                Ok(()) => DkgEnd {
+                    dkg_id: self.dkg_id,
+                    signer_id: self.signer_id,
+                    status: DkgStatus::Success,
+                },
                 Err(dkg_error_map) => DkgEnd {
                     dkg_id: self.dkg_id,
                     signer_id: self.signer_id,
-                    status: DkgStatus::Failure(format!("{:?}", dkg_error_map)),
+                    status: DkgStatus::Failure(DkgFailure::from(&dkg_error_map)),
                 },
             }
         } else {
             DkgEnd {
                 dkg_id: self.dkg_id,
                 signer_id: self.signer_id,
-                status: DkgStatus::Failure(format!("{:?}", self.invalid_private_shares)),
+                status: DkgStatus::Failure(DkgFailure::InvalidPrivateShares(
+                    self.invalid_private_shares.clone(),
+                )),
             }
         };
 
-        info!(
-            "Signer {} sending DkgEnd round {} status {:?}",
-            self.signer_id, self.dkg_id, dkg_end.status,
-        );
+        // keep the reshared keys only if this signer's side of the threshold change succeeded
+        if let DkgStatus::Failure(failure) = &dkg_end.status {
+            self.abort_threshold_change();
+            self.metrics.round_failed(RoundType::Dkg);
+            self.events.push(SignerEvent::RoundFailed {
+                reason: format!("DKG round {} failed: {}", self.dkg_id, failure),
+            });
+        } else {
+            self.threshold_change = None;
+            if let Some(started) = self.dkg_started.take() {
+                self.metrics
+                    .round_completed(RoundType::Dkg, started.elapsed());
+            }
+            self.events.push(SignerEvent::DkgCompleted {
+                dkg_id: self.dkg_id,
+                group_key: self.signer.get_group_key(),
+            });
+        }
+
+        // ban the signers whose private shares were bad, now that this round is finished with them
+        let bad_signer_ids = self
+            .invalid_private_shares
+            .keys()
+            .chain(self.share_complaints.keys())
+            .copied()
+            .collect::<Vec<u32>>();
+        for signer_id in bad_signer_ids {
+            self.ban(signer_id);
+        }
+
+        info!(status = ?dkg_end.status, "Sending DkgEnd");
 
         let dkg_end = Message::DkgEnd(dkg_end);
         Ok(dkg_end)
     }
 
+    /// compute the group key from the stored DKG commitments, by summing their constant terms
+    pub fn compute_aggregate_key(&self) -> Result<Point, Error> {
+        if self.commitments.is_empty() {
+            return Err(Error::MissingCommitments);
+        }
+
+        Ok(self
+            .commitments
+            .values()
+            .fold(Point::zero(), |s, comm| s + comm.poly[0]))
+    }
+
+    /// recompute the group key from the stored DKG commitments, and check that it matches the
+    /// signer's group key and, if passed, the `expected` key (e.g. the coordinator's copy)
+    pub fn verify_group_key(&self, expected: Option<&Point>) -> Result<Point, Error> {
+        let computed = self.compute_aggregate_key()?;
+
+        let group_key = self.signer.get_group_key();
+        if computed != group_key {
+            return Err(Error::GroupKeyMismatch(
+                Box::new(computed),
+                Box::new(group_key),
+            ));
+        }
+        if let Some(expected) = expected {
+            if computed != *expected {
+                return Err(Error::GroupKeyMismatch(
+                    Box::new(computed),
+                    Box::new(*expected),
+                ));
+            }
+        }
+
+        Ok(computed)
+    }
+
+    /// the BIP341 x-only taproot output key of the verified group key for `tweak`, which
+    /// is what `sign_taproot` signatures verify under
+    pub fn taproot_output_key(&self, tweak: TaprootTweak) -> Result<field::Element, Error> {
+        let group_key = self.verify_group_key(None)?;
+        Ok(compute::tweaked_public_key(&group_key, tweak).x())
+    }
+
     /// do we have all DkgPublicShares?
     pub fn public_shares_done(&self) -> bool {
+        // a Pedersen round only reveals the polynomial commitments with the private shares
+        let received = if self.pedersen {
+            self.hiding_commitments.len()
+        } else {
+            self.commitments.len()
+        };
         debug!(
             "public_shares_done state {:?} commitments {}",
-            self.state,
-            self.commitments.len(),
+            self.state, received,
         );
         self.state == State::DkgPublicGather
-            && self.commitments.len() == usize::try_from(self.signer.get_num_parties()).unwrap()
+            && received == usize::try_from(self.signer.get_num_parties()).unwrap()
     }
 
     /// do we have all DkgPublicShares and DkgPrivateShares?
@@ -295,101 +1336,416 @@ impl<Signer: SignerTrait> SigningRound<Signer> {
             && self.commitments.len() == usize::try_from(self.signer.get_num_parties()).unwrap()
             && self.decrypted_shares.len()
                 == usize::try_from(self.signer.get_num_parties()).unwrap()
+            && self.echoes.len() == usize::try_from(self.total_signers).unwrap()
+    }
+
+    /// hash every DKG polynomial commitment received, so signers can check they all saw the same set
+    ///
+    /// In a Pedersen round only the Pedersen commitments are hashed, since they pin down the
+    /// polynomial commitments revealed later.
+    pub fn public_shares_hash(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        hasher.update("DKG_PUBLIC_SHARES_ECHO".as_bytes());
+        hasher.update(self.dkg_id.to_be_bytes());
+        if self.pedersen {
+            for (party_id, hiding_comm) in &self.hiding_commitments {
+                hasher.update(party_id.to_be_bytes());
+                for c in hiding_comm {
+                    hasher.update(c.compress().as_bytes());
+                }
+            }
+            return hasher.finalize().into();
+        }
+        for (party_id, comm) in &self.commitments {
+            hasher.update(party_id.to_be_bytes());
+            hasher.update(comm.id.id.to_bytes());
+            hasher.update(comm.id.kG.compress().as_bytes());
+            hasher.update(comm.id.kca.to_bytes());
+            for a in &comm.poly {
+                hasher.update(a.compress().as_bytes());
+            }
+        }
+        hasher.finalize().into()
+    }
+
+    /// check that a nonce response carries exactly the sender's registered key IDs, and one
+    /// nonce per party those keys make up
+    fn validate_nonce_response(&self, nonce_response: &NonceResponse) -> Result<(), Error> {
+        let signer_id = nonce_response.signer_id;
+        let registered_key_ids = self
+            .public_keys
+            .signer_key_ids(signer_id)
+            .ok_or(Error::UnknownSigner(signer_id))?
+            .into_iter()
+            .map(u32::from)
+            .collect::<HashSet<u32>>();
+        let key_ids = nonce_response.key_ids.iter().collect::<HashSet<u32>>();
+        let num_parties = Signer::party_ids(signer_id, &nonce_response.key_ids.to_vec()).len();
+
+        if key_ids.len() != nonce_response.key_ids.len()
+            || key_ids != registered_key_ids
+            || nonce_response.nonces.len() != num_parties
+        {
+            warn!(
+                "Signer {} got bad NonceResponse from signer {}: key_ids {:?} nonces {}",
+                self.signer_id,
+                signer_id,
+                nonce_response.key_ids,
+                nonce_response.nonces.len()
+            );
+            return Err(Error::InvalidNonceResponse(signer_id));
+        }
+        Ok(())
     }
 
     fn nonce_request(&mut self, nonce_request: &NonceRequest) -> Result<Vec<Message>, Error> {
+        if nonce_request
+            .signer_ids
+            .as_ref()
+            .is_some_and(|signer_ids| !signer_ids.contains(&self.signer_id))
+        {
+            debug!(
+                "Signer {} wasn't chosen for sign round {} iteration {}",
+                self.signer_id, nonce_request.sign_id, nonce_request.sign_iter_id
+            );
+            return Ok(vec![]);
+        }
+        if self
+            .sessions
+            .get(&nonce_request.sign_id)
+            .is_some_and(|session| session.sign_iter_id == nonce_request.sign_iter_id)
+        {
+            debug!(
+                "Signer {} ignoring duplicate NonceRequest for sign round {} iteration {}",
+                self.signer_id, nonce_request.sign_id, nonce_request.sign_iter_id
+            );
+            return Ok(vec![]);
+        }
+        if nonce_request.dkg_id > self.dkg_id || self.signer.get_group_key() == Point::zero() {
+            return Ok(self.reject(
+                nonce_request.dkg_id,
+                nonce_request.sign_id,
+                nonce_request.sign_iter_id,
+                RejectReason::MissingDkgState,
+            ));
+        }
+
         let mut rng = OsRng;
         let mut msgs = vec![];
         let signer_id = self.signer_id;
         let key_ids = self.signer.get_key_ids();
-        let nonces = self.signer.gen_nonces(&mut rng);
+        let mut aux_rand = Vec::new();
+        if let Some(source) = &mut self.aux_rand {
+            aux_rand.resize(32, 0);
+            source.fill_bytes(&mut aux_rand);
+        }
+        // bind the nonces to this request, so different rounds get different nonces even if
+        // both sources of randomness repeat themselves
+        let mut hasher = Sha256::new();
+        hasher.update(&self.domain);
+        nonce_request.hash(&mut hasher);
+        let context = hasher.finalize();
+        let nonces = self
+            .signer
+            .gen_nonces_with_context(&aux_rand, &context, &mut rng);
+
+        self.sign_id = nonce_request.sign_id;
+        self.sign_iter_id = nonce_request.sign_iter_id;
+        self.public_nonces = nonces.clone();
+        self.start_session(
+            nonce_request.sign_id,
+            SignSession {
+                sign_iter_id: nonce_request.sign_iter_id,
+                nonces: self.signer.get_nonces(),
+                public_nonces: nonces.clone(),
+                started: Instant::now(),
+            },
+        );
+        self.metrics.nonce_pool_depth(self.sessions.len());
 
         let response = NonceResponse {
             dkg_id: nonce_request.dkg_id,
             sign_id: nonce_request.sign_id,
             sign_iter_id: nonce_request.sign_iter_id,
             signer_id,
-            key_ids,
+            key_ids: key_ids.into(),
             nonces,
         };
 
-        let response = Message::NonceResponse(response);
-
-        info!(
-            "Signer {} sending NonceResponse for DKG round {} sign round {} sign iteration {}",
-            signer_id, nonce_request.dkg_id, nonce_request.sign_id, nonce_request.sign_iter_id,
-        );
-        msgs.push(response);
+        // with commit-reveal, only send a hash of the nonces until the coordinator has every
+        // signer's commitment
+        if nonce_request.commit {
+            info!("Sending NonceCommitment");
+            msgs.push(Message::NonceCommitment(NonceCommitment {
+                dkg_id: response.dkg_id,
+                sign_id: response.sign_id,
+                sign_iter_id: response.sign_iter_id,
+                signer_id,
+                commitment: response.commitment(),
+            }));
+        } else {
+            info!("Sending NonceResponse");
+            msgs.push(Message::NonceResponse(response));
+        }
 
         Ok(msgs)
     }
 
+    /// Keep the nonces for a signing round, discarding those of the iteration it replaces, and of
+    /// the rounds with the lowest sign_ids if there are more than MAX_SIGN_SESSIONS, so rounds
+    /// this signer is never asked to sign in don't keep their nonces forever
+    fn start_session(&mut self, sign_id: u64, session: SignSession) {
+        if let Some(replaced) = self.sessions.insert(sign_id, session) {
+            self.consumed_nonces
+                .insert((sign_id, replaced.sign_iter_id));
+        }
+        while self.sessions.len() > MAX_SIGN_SESSIONS {
+            let Some((sign_id, evicted)) = self.sessions.pop_first() else {
+                break;
+            };
+            debug!(
+                "Signer {} discarding nonces for sign round {} iteration {}",
+                self.signer_id, sign_id, evicted.sign_iter_id
+            );
+            self.consumed_nonces.insert((sign_id, evicted.sign_iter_id));
+        }
+        self.prune_consumed_nonces();
+    }
+
+    /// Forget consumed nonces for rounds below both the lowest live session and the newest
+    /// consumed round.  Those rounds have no nonces left to sign with, and keeping the newest
+    /// means a replayed request for it is still refused explicitly.
+    fn prune_consumed_nonces(&mut self) {
+        let newest = self
+            .consumed_nonces
+            .last()
+            .map_or(u64::MAX, |(sign_id, _)| *sign_id);
+        let lowest_live = self.sessions.keys().next().copied().unwrap_or(u64::MAX);
+        let floor = newest.min(lowest_live);
+        self.consumed_nonces = self.consumed_nonces.split_off(&(floor, 0));
+    }
+
+    fn nonce_reveal(&mut self, nonce_reveal: &NonceReveal) -> Result<Vec<Message>, Error> {
+        if !nonce_reveal.signer_ids.contains(&self.signer_id) {
+            return Ok(vec![]);
+        }
+        let Some(session) = self.sessions.get(&nonce_reveal.sign_id) else {
+            return Err(Error::UnknownSignId(nonce_reveal.sign_id));
+        };
+        if session.sign_iter_id != nonce_reveal.sign_iter_id {
+            return Err(Error::BadSignIterId(
+                session.sign_iter_id,
+                nonce_reveal.sign_iter_id,
+            ));
+        }
+
+        info!("Revealing NonceResponse");
+        Ok(vec![Message::NonceResponse(NonceResponse {
+            dkg_id: nonce_reveal.dkg_id,
+            sign_id: nonce_reveal.sign_id,
+            sign_iter_id: nonce_reveal.sign_iter_id,
+            signer_id: self.signer_id,
+            key_ids: self.signer.get_key_ids().into(),
+            nonces: session.public_nonces.clone(),
+        })])
+    }
+
     fn sign_share_request(
         &mut self,
         sign_request: &SignatureShareRequest,
     ) -> Result<Vec<Message>, Error> {
-        let mut msgs = vec![];
-
         let signer_ids = sign_request
             .nonce_responses
             .iter()
             .map(|nr| nr.signer_id)
             .collect::<Vec<u32>>();
+        let is_signing = signer_ids.contains(&self.signer_id);
 
-        debug!("Got SignatureShareRequest for signer_ids {:?}", signer_ids);
+        if sign_request.dkg_id > self.dkg_id && is_signing {
+            return Ok(self.reject(
+                sign_request.dkg_id,
+                sign_request.sign_id,
+                sign_request.sign_iter_id,
+                RejectReason::MissingDkgState,
+            ));
+        }
+        if sign_request.dkg_id != self.dkg_id {
+            return Err(Error::BadDkgId(self.dkg_id, sign_request.dkg_id));
+        }
+        let validation = if sign_request.is_digest && sign_request.message.len() != 32 {
+            Err(Error::BadDigestLength(sign_request.message.len()))
+        } else {
+            sign_request
+                .nonce_responses
+                .iter()
+                .try_for_each(|nonce_response| self.validate_nonce_response(nonce_response))
+        };
+        if let Err(e) = validation {
+            if !is_signing {
+                return Err(e);
+            }
+            return Ok(self.reject(
+                sign_request.dkg_id,
+                sign_request.sign_id,
+                sign_request.sign_iter_id,
+                RejectReason::BadRequest(e.to_string()),
+            ));
+        }
 
-        for signer_id in &signer_ids {
-            if *signer_id == self.signer_id {
-                let key_ids: Vec<u32> = sign_request
-                    .nonce_responses
-                    .iter()
-                    .flat_map(|nr| nr.key_ids.iter().copied())
-                    .collect::<Vec<u32>>();
-                let nonces = sign_request
-                    .nonce_responses
-                    .iter()
-                    .flat_map(|nr| nr.nonces.clone())
-                    .collect::<Vec<PublicNonce>>();
-                let signature_shares = if sign_request.is_taproot {
-                    self.signer.sign_taproot(
-                        &sign_request.message,
-                        &signer_ids,
-                        &key_ids,
-                        &nonces,
-                        sign_request.merkle_root,
-                    )
-                } else {
-                    self.signer
-                        .sign(&sign_request.message, &signer_ids, &key_ids, &nonces)
-                };
+        let mut msgs = vec![];
 
-                let response = SignatureShareResponse {
-                    dkg_id: sign_request.dkg_id,
-                    sign_id: sign_request.sign_id,
-                    sign_iter_id: sign_request.sign_iter_id,
-                    signer_id: *signer_id,
-                    signature_shares,
-                };
+        debug!("Got SignatureShareRequest for signer_ids {:?}", signer_ids);
+        self.nonce_signer_ids = signer_ids.iter().copied().collect();
 
-                info!(
-                    "Signer {} sending SignatureShareResponse for DKG round {} sign round {} sign iteration {}",
-                    signer_id, self.dkg_id, self.sign_id, self.sign_iter_id,
-                );
+        if is_signing {
+            if let Some(Err(reason)) = self.signing_policy.as_ref().map(|p| p.check(sign_request)) {
+                // the nonces were committed to for this request, so don't keep them for another
+                if let Some(session) = self.sessions.remove(&sign_request.sign_id) {
+                    self.consumed_nonces
+                        .insert((sign_request.sign_id, session.sign_iter_id));
+                    self.prune_consumed_nonces();
+                }
+                self.metrics.nonce_pool_depth(self.sessions.len());
+                return Ok(self.reject(
+                    sign_request.dkg_id,
+                    sign_request.sign_id,
+                    sign_request.sign_iter_id,
+                    RejectReason::Policy(reason),
+                ));
+            }
+        }
 
-                let response = Message::SignatureShareResponse(response);
+        // respond once, however many times this signer appears in the request
+        if is_signing {
+            // each session's nonces are only ever used for a single signature
+            let round = (sign_request.sign_id, sign_request.sign_iter_id);
+            if self.consumed_nonces.contains(&round) {
+                warn!(
+                    "Signer {} refusing to reuse nonces for sign round {} iteration {}",
+                    self.signer_id, round.0, round.1
+                );
+                return Err(Error::NonceReused(round.0, round.1));
+            }
+            let Some(session) = self.sessions.remove(&sign_request.sign_id) else {
+                return Err(Error::UnknownSignId(sign_request.sign_id));
+            };
+            if session.sign_iter_id != sign_request.sign_iter_id {
+                let expected = session.sign_iter_id;
+                self.sessions.insert(sign_request.sign_id, session);
+                return Err(Error::BadSignIterId(expected, sign_request.sign_iter_id));
+            }
+            // only sign over the nonces this signer committed to
+            if !sign_request
+                .nonce_responses
+                .iter()
+                .any(|nr| nr.signer_id == self.signer_id && nr.nonces == session.public_nonces)
+            {
+                self.sessions.insert(sign_request.sign_id, session);
+                return Err(Error::NonceMismatch(sign_request.sign_id));
+            }
+            self.consumed_nonces.insert(round);
+            self.prune_consumed_nonces();
+            self.signer.set_nonces(&session.nonces);
+            self.metrics.nonce_pool_depth(self.sessions.len());
 
-                msgs.push(response);
+            let key_ids: Vec<u32> = sign_request
+                .nonce_responses
+                .iter()
+                .flat_map(|nr| nr.key_ids.iter())
+                .collect::<Vec<u32>>();
+            let nonces = sign_request
+                .nonce_responses
+                .iter()
+                .flat_map(|nr| nr.nonces.clone())
+                .collect::<Vec<PublicNonce>>();
+            let signature_shares = if sign_request.is_taproot {
+                self.signer.sign_taproot(
+                    &sign_request.message,
+                    &signer_ids,
+                    &key_ids,
+                    &nonces,
+                    sign_request.tweak,
+                )
             } else {
-                debug!("SignatureShareRequest for {} dropped.", signer_id);
-            }
+                self.signer
+                    .sign(&sign_request.message, &signer_ids, &key_ids, &nonces)
+            };
+
+            let response = SignatureShareResponse {
+                dkg_id: sign_request.dkg_id,
+                sign_id: sign_request.sign_id,
+                sign_iter_id: sign_request.sign_iter_id,
+                signer_id: self.signer_id,
+                signature_shares,
+            };
+
+            info!("Sending SignatureShareResponse");
+
+            let response = Message::SignatureShareResponse(response);
+
+            msgs.push(response);
+            self.metrics
+                .round_completed(RoundType::Sign, session.started.elapsed());
+            self.events.push(SignerEvent::SharePosted {
+                sign_id: sign_request.sign_id,
+                sign_iter_id: sign_request.sign_iter_id,
+            });
+        } else {
+            debug!(
+                "SignatureShareRequest for {:?} dropped by signer {}",
+                signer_ids, self.signer_id
+            );
         }
         Ok(msgs)
     }
 
+    /// refuse a signing round, telling the coordinator why so it needn't wait for a timeout
+    fn reject(
+        &mut self,
+        dkg_id: u64,
+        sign_id: u64,
+        sign_iter_id: u64,
+        reason: RejectReason,
+    ) -> Vec<Message> {
+        info!(%reason, "Refusing sign round {} iteration {}", sign_id, sign_iter_id);
+        self.metrics.round_failed(RoundType::Sign);
+        self.events.push(SignerEvent::RoundFailed {
+            reason: format!("Refused to sign in sign round {}: {}", sign_id, reason),
+        });
+        vec![Message::SignatureShareReject(SignatureShareReject {
+            dkg_id,
+            sign_id,
+            sign_iter_id,
+            signer_id: self.signer_id,
+            reason,
+        })]
+    }
+
     fn dkg_begin(&mut self, dkg_begin: &DkgBegin) -> Result<Vec<Message>, Error> {
         let mut rng = OsRng;
 
+        // roll back any threshold change before leaving its signer set or checking the threshold
+        self.abort_threshold_change();
+        if dkg_begin.epoch != self.epoch {
+            self.enter_epoch(dkg_begin.epoch)?;
+        }
+        // a key made with different parameters than the signers expect would be unusable, so
+        // refuse to take part rather than finding out when signing fails
+        for (param, sent, expected) in [
+            ("threshold", dkg_begin.threshold, self.threshold),
+            ("num_signers", dkg_begin.num_signers, self.total_signers),
+            ("num_keys", dkg_begin.num_keys, self.total_keys),
+        ] {
+            if sent != expected {
+                warn!(
+                    param,
+                    sent, expected, "DkgBegin doesn't match signer config"
+                );
+                return Err(Error::DkgParamMismatch(param, sent, expected));
+            }
+        }
         self.reset(dkg_begin.dkg_id, &mut rng);
+        self.pedersen = dkg_begin.pedersen;
         self.move_to(State::DkgPublicDistribute)?;
 
         //let _party_state = self.signer.save();
@@ -397,27 +1753,195 @@ impl<Signer: SignerTrait> SigningRound<Signer> {
         self.dkg_public_begin()
     }
 
+    /// Switch to the signer and key assignments of `epoch`, finding this signer's ID and key IDs
+    /// by its network key, and replacing the Signer with a fresh one which holds those keys
+    fn enter_epoch(&mut self, epoch: u64) -> Result<(), Error> {
+        let public_keys = self
+            .epochs
+            .get(&epoch)
+            .ok_or(Error::UnknownEpoch(epoch))?
+            .clone();
+        let network_public_key = ecdsa::PublicKey::new(&self.network_private_key)
+            .map_err(|_| Error::NotInEpoch(epoch))?
+            .to_bytes();
+        let signer_id = public_keys
+            .signers
+            .iter()
+            .find(|(_, key)| key.to_bytes() == network_public_key)
+            .map(|(signer_id, _)| *signer_id)
+            .ok_or(Error::NotInEpoch(epoch))?;
+        let key_ids = public_keys
+            .signer_key_ids(signer_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(u32::from)
+            .collect::<Vec<u32>>();
+        let total_signers = public_keys.signers.len().try_into().unwrap_or(u32::MAX);
+        let total_keys = public_keys.key_ids.len().try_into().unwrap_or(u32::MAX);
+        public_keys.validate(total_signers, total_keys)?;
+        if self.threshold > total_keys {
+            return Err(Error::InvalidThreshold(self.threshold, total_keys));
+        }
+
+        info!(epoch, signer_id, ?key_ids, "Entering epoch");
+        let mut rng = OsRng;
+        self.signer = Signer::new(
+            signer_id,
+            &key_ids,
+            total_signers,
+            total_keys,
+            self.threshold,
+            &mut rng,
+        );
+        self.signer_id = signer_id;
+        self.total_signers = total_signers;
+        self.total_keys = total_keys;
+        self.public_points = public_keys.decompress();
+        self.public_keys = public_keys;
+        self.epoch = epoch;
+        Ok(())
+    }
+
+    /// handle incoming ThresholdChangeBegin, by starting a DKG round in which every party
+    /// reshares its existing keys with a polynomial of the new degree
+    ///
+    /// When recovering a signer, the other signers reshare the group key from their own keys
+    /// alone, and the recovered signer drops whatever keys it has and deals nothing, so it ends
+    /// up with fresh key shares and its old ones are no longer valid.  The fresh shares are
+    /// encrypted to its network key, so a compromised network key must be rotated first.
+    pub fn threshold_change_begin(
+        &mut self,
+        threshold_change_begin: &ThresholdChangeBegin,
+    ) -> Result<Vec<Message>, Error> {
+        let mut rng = OsRng;
+        let threshold = threshold_change_begin.threshold;
+        let recover_signer_id = threshold_change_begin.recover_signer_id;
+
+        self.abort_threshold_change();
+        let recovering = recover_signer_id == Some(self.signer_id);
+        if !recovering {
+            self.verify_group_key(None)?;
+        }
+        if threshold == 0 || threshold > self.total_keys {
+            return Err(Error::InvalidThreshold(threshold, self.total_keys));
+        }
+
+        let lost_key_ids = recover_signer_id
+            .and_then(|signer_id| self.public_keys.signer_key_ids(signer_id))
+            .unwrap_or_default();
+        let key_ids: Vec<u32> = (0..self.total_keys)
+            .filter(|key_id| !lost_key_ids.contains(&KeyId(*key_id)))
+            .collect();
+        let remaining_keys = key_ids.len().try_into().unwrap_or(u32::MAX);
+        if threshold > remaining_keys {
+            return Err(Error::InvalidThreshold(threshold, remaining_keys));
+        }
+
+        let expected_constants = if recovering {
+            None
+        } else {
+            Some(self.expected_reshare_constants(&key_ids))
+        };
+        let saved = self.save();
+
+        if let Some(signer_id) = recover_signer_id {
+            info!(signer_id, "Recovering signer");
+        } else {
+            info!(from = self.threshold, to = threshold, "Changing threshold");
+        }
+
+        self.reset(threshold_change_begin.dkg_id, &mut rng);
+        self.threshold = threshold;
+        if recovering {
+            let own_key_ids = self
+                .public_keys
+                .signer_key_ids(self.signer_id)
+                .unwrap_or_default()
+                .into_iter()
+                .map(u32::from)
+                .collect::<Vec<u32>>();
+            self.signer = Signer::new(
+                self.signer_id,
+                &own_key_ids,
+                self.total_signers,
+                self.total_keys,
+                threshold,
+                &mut rng,
+            );
+        }
+        self.signer.reshare_polys(threshold, &key_ids, &mut rng);
+        self.threshold_change = Some(ThresholdChange {
+            saved,
+            expected_constants,
+        });
+        self.move_to(State::DkgPublicDistribute)?;
+
+        self.dkg_public_begin()
+    }
+
+    /// the sum of the constant terms each signer must commit to when resharing its keys over
+    /// `reshared_key_ids`, computed from the current DKG commitments
+    fn expected_reshare_constants(&self, reshared_key_ids: &[u32]) -> HashMap<u32, Point> {
+        let mut group_poly: Vec<Point> = vec![];
+        for comm in self.commitments.values() {
+            group_poly.resize(comm.poly.len().max(group_poly.len()), Point::zero());
+            for (a, b) in group_poly.iter_mut().zip(&comm.poly) {
+                *a += b;
+            }
+        }
+
+        let public_key = |key_id: u32| {
+            let x = compute::id(key_id);
+            group_poly
+                .iter()
+                .rev()
+                .fold(Point::zero(), |acc, a| x * acc + a)
+        };
+
+        self.public_keys
+            .signers
+            .keys()
+            .map(|signer_id| {
+                let key_ids = self
+                    .public_keys
+                    .signer_key_ids(*signer_id)
+                    .unwrap_or_default();
+                let constant = key_ids
+                    .into_iter()
+                    .filter(|KeyId(key_id)| reshared_key_ids.contains(key_id))
+                    .fold(Point::zero(), |s, KeyId(key_id)| {
+                        s + compute::lambda(key_id, reshared_key_ids) * public_key(key_id)
+                    });
+                (*signer_id, constant)
+            })
+            .collect()
+    }
+
     fn dkg_public_begin(&mut self) -> Result<Vec<Message>, Error> {
         let mut rng = OsRng;
         let mut msgs = vec![];
         let comms = self.signer.get_poly_commitments(&mut rng);
 
-        info!(
-            "Signer {} sending DkgPublicShares for round {}",
-            self.signer.get_id(),
-            self.dkg_id,
-        );
+        info!("Sending DkgPublicShares");
 
         let mut public_share = DkgPublicShares {
             dkg_id: self.dkg_id,
             signer_id: self.signer_id,
             comms: Vec::new(),
+            hiding_comms: Vec::new(),
         };
 
         for poly in &comms {
-            public_share
-                .comms
-                .push((poly.id.id.get_u32(), poly.clone()));
+            let party_id = poly.id.id.get_u32();
+            if self.pedersen {
+                let blinding = VSS::random_poly(self.threshold - 1, &mut rng);
+                public_share
+                    .hiding_comms
+                    .push((party_id, VSS::hiding_commitment(&poly.poly, &blinding)));
+                self.blinding_polys.insert(party_id, blinding);
+            } else {
+                public_share.comms.push((party_id, poly.clone()));
+            }
         }
 
         let public_share = Message::DkgPublicShares(public_share);
@@ -427,51 +1951,142 @@ impl<Signer: SignerTrait> SigningRound<Signer> {
         Ok(msgs)
     }
 
-    fn dkg_private_begin(&mut self) -> Result<Vec<Message>, Error> {
+    fn dkg_private_begin(
+        &mut self,
+        dkg_private_begin: &DkgPrivateBegin,
+    ) -> Result<Vec<Message>, Error> {
         let mut rng = OsRng;
         let mut msgs = vec![];
+        let shares = self.signer.get_shares();
+        let mut src_party_ids = shares.keys().copied().collect::<Vec<u32>>();
+        src_party_ids.sort();
         let mut private_shares = DkgPrivateShares {
             dkg_id: self.dkg_id,
             signer_id: self.signer_id,
+            src_party_ids,
             shares: Vec::new(),
+            verifiable_shares: Vec::new(),
         };
-        info!(
-            "Signer {} sending DkgPrivateShares for round {}",
-            self.signer.get_id(),
-            self.dkg_id,
-        );
+        // blinding shares can't be verifiably encrypted, so Pedersen rounds encrypt to each signer
+        let verifiable = self.verifiable_shares && !self.pedersen;
+        info!("Sending DkgPrivateShares");
 
-        debug!(
-            "Signer {} shares {:?}",
-            self.signer_id,
-            &self.signer.get_shares()
-        );
-        for (key_id, shares) in &self.signer.get_shares() {
+        // only send shares to the signers the coordinator listed, encrypting all of each
+        // signer's shares together
+        for dst_signer_id in &dkg_private_begin.signer_ids {
+            let Some(dst_key_ids) = self.public_keys.signer_key_ids(*dst_signer_id) else {
+                continue;
+            };
+            // already sorted, since KeyIds are ordered
+            let dst_key_ids = dst_key_ids.into_iter().map(u32::from).collect::<Vec<u32>>();
             debug!(
-                "Signer {} addding dkg private share for key_id {}",
-                self.signer_id, key_id
+                "Signer {} encrypting dkg private shares for signer {} key_ids {:?}",
+                self.signer_id, dst_signer_id, dst_key_ids
             );
-            // encrypt each share for the recipient
-            let mut encrypted_shares = HashMap::new();
 
-            for (dst_key_id, private_share) in shares {
-                debug!("encrypting dkg private share for key_id {}", dst_key_id + 1);
-                let compressed =
-                    Compressed::from(self.public_keys.key_ids[&(dst_key_id + 1)].to_bytes());
-                let dst_public_key = Point::try_from(&compressed).unwrap();
-                let shared_secret = make_shared_secret(&self.network_private_key, &dst_public_key);
-                let encrypted_share =
-                    encrypt(&shared_secret, &private_share.to_bytes(), &mut rng).unwrap();
+            let dst_public_key = self
+                .public_points
+                .signers
+                .get(dst_signer_id)
+                .ok_or(Error::UnknownSigner(*dst_signer_id))?;
 
-                encrypted_shares.insert(*dst_key_id, encrypted_share);
+            let mut plain = Vec::with_capacity(
+                self.share_len() * private_shares.src_party_ids.len() * dst_key_ids.len(),
+            );
+            let mut verifiable_shares = Vec::new();
+            for src_party_id in &private_shares.src_party_ids {
+                for dst_key_id in &dst_key_ids {
+                    let share = shares[src_party_id]
+                        .get(dst_key_id)
+                        .ok_or(Error::UnknownKeyID(*dst_key_id))?;
+                    if verifiable {
+                        verifiable_shares.push(EncryptedShare::new(
+                            share,
+                            dst_public_key,
+                            &mut rng,
+                        ));
+                    } else {
+                        plain.extend_from_slice(&share.to_bytes());
+                    }
+                    // each share is followed by its blinding share in a Pedersen round
+                    if let Some(blinding) = self
+                        .blinding_polys
+                        .get(&compute::id(*src_party_id).get_u32())
+                    {
+                        plain
+                            .extend_from_slice(&blinding.eval(compute::id(*dst_key_id)).to_bytes());
+                    }
+                }
+            }
+            if verifiable {
+                private_shares
+                    .verifiable_shares
+                    .push((*dst_signer_id, verifiable_shares));
+                continue;
             }
 
-            private_shares.shares.push((*key_id, encrypted_shares));
+            let shared_secret = make_shared_secret(&self.network_private_key, dst_public_key);
+            let share_key =
+                make_share_key(&shared_secret, self.dkg_id, self.signer_id, *dst_signer_id);
+            let encrypted_shares = encrypt(&share_key, &plain, &mut rng).unwrap();
+
+            private_shares
+                .shares
+                .push((*dst_signer_id, encrypted_shares));
         }
 
         let private_shares = Message::DkgPrivateShares(private_shares);
         msgs.push(private_shares);
 
+        // every signer is bound to its Pedersen commitments by now, so reveal what's behind them
+        if self.pedersen {
+            info!("Sending DkgDecommit");
+            let comms = self
+                .signer
+                .get_poly_commitments(&mut rng)
+                .into_iter()
+                .map(|comm| {
+                    let party_id = comm.id.id.get_u32();
+                    let blinding = self
+                        .blinding_polys
+                        .get(&party_id)
+                        .map(|blinding| blinding.data().to_vec())
+                        .unwrap_or_default();
+                    (party_id, comm, blinding)
+                })
+                .collect();
+            msgs.push(Message::DkgDecommit(DkgDecommit {
+                dkg_id: self.dkg_id,
+                signer_id: self.signer_id,
+                comms,
+            }));
+        }
+
+        // now that all public shares are in, tell everyone which ones we got
+        let public_shares_hash = self.public_shares_hash();
+        self.echoes.insert(self.signer_id, public_shares_hash);
+        msgs.push(Message::DkgEcho(DkgEcho {
+            dkg_id: self.dkg_id,
+            signer_id: self.signer_id,
+            public_shares_hash,
+        }));
+
+        if !dkg_private_begin.signer_ids.contains(&self.signer_id) {
+            // this signer gets no shares, so it can't finish DKG; drop the keys from any earlier
+            // round too, since the group key is changing
+            info!("Excluded from DKG private shares");
+            self.signer = Signer::new(
+                self.signer_id,
+                &self.signer.get_key_ids(),
+                self.total_signers,
+                self.total_keys,
+                self.threshold,
+                &mut rng,
+            );
+            self.move_to(State::Idle)?;
+            return Ok(msgs);
+        }
+
         self.move_to(State::DkgPrivateGather)?;
         Ok(msgs)
     }
@@ -481,54 +2096,306 @@ impl<Signer: SignerTrait> SigningRound<Signer> {
         &mut self,
         dkg_public_shares: &DkgPublicShares,
     ) -> Result<Vec<Message>, Error> {
+        if dkg_public_shares.dkg_id != self.dkg_id {
+            return Err(Error::BadDkgId(self.dkg_id, dkg_public_shares.dkg_id));
+        }
+
+        let invalid_party_ids = dkg_public_shares.invalid_party_ids(self.threshold);
+        if !invalid_party_ids.is_empty() {
+            warn!(
+                "Signer {} rejecting DkgPublicShares from signer {} with invalid commitments for parties {:?}",
+                self.signer_id, dkg_public_shares.signer_id, invalid_party_ids
+            );
+            self.ban(dkg_public_shares.signer_id);
+            return Err(Error::InvalidPolyCommitments(invalid_party_ids));
+        }
+        let invalid_party_ids =
+            dkg_public_shares.invalid_hiding_party_ids(self.threshold, self.pedersen);
+        if !invalid_party_ids.is_empty() {
+            warn!(
+                "Signer {} rejecting DkgPublicShares from signer {} with invalid Pedersen commitments for parties {:?}",
+                self.signer_id, dkg_public_shares.signer_id, invalid_party_ids
+            );
+            self.ban(dkg_public_shares.signer_id);
+            return Err(Error::InvalidPolyCommitments(invalid_party_ids));
+        }
+
+        let signer_id = dkg_public_shares.signer_id;
+        let party_ids = dkg_public_shares
+            .comms
+            .iter()
+            .map(|(party_id, _)| *party_id)
+            .chain(
+                dkg_public_shares
+                    .hiding_comms
+                    .iter()
+                    .map(|(party_id, _)| *party_id),
+            );
+        self.check_party_ids("DkgPublicShares", signer_id, party_ids)?;
+
+        if let Some(expected_constants) = self
+            .threshold_change
+            .as_ref()
+            .and_then(|threshold_change| threshold_change.expected_constants.as_ref())
+        {
+            let constant = dkg_public_shares
+                .comms
+                .iter()
+                .fold(Point::zero(), |s, (_, comm)| s + comm.poly[0]);
+            if expected_constants.get(&signer_id) != Some(&constant) {
+                warn!(
+                    "Signer {} rejecting DkgPublicShares from signer {} which don't reshare its keys",
+                    self.signer_id, signer_id
+                );
+                self.ban(signer_id);
+                return Err(Error::InvalidThresholdChange(signer_id));
+            }
+        }
+
         for (party_id, comm) in &dkg_public_shares.comms {
             self.commitments.insert(*party_id, comm.clone());
         }
+        for (party_id, hiding_comm) in &dkg_public_shares.hiding_comms {
+            self.hiding_commitments
+                .insert(*party_id, hiding_comm.clone());
+        }
         debug!(
             "received DkgPublicShares from signer {} {}/{}",
             dkg_public_shares.signer_id,
+            self.commitments.len().max(self.hiding_commitments.len()),
+            self.signer.get_num_parties(),
+        );
+        Ok(vec![])
+    }
+
+    /// check that `signer_id` owns every party in `party_ids` which it sent commitments for in
+    /// `message_type`, banning it if not
+    fn check_party_ids(
+        &mut self,
+        message_type: &str,
+        signer_id: u32,
+        party_ids: impl Iterator<Item = u32>,
+    ) -> Result<(), Error> {
+        let key_ids = self
+            .public_keys
+            .signer_key_ids(signer_id)
+            .ok_or(Error::UnknownSigner(signer_id))?
+            .into_iter()
+            .map(u32::from)
+            .collect::<Vec<u32>>();
+        let owned_party_ids: HashSet<u32> =
+            Signer::party_ids(signer_id, &key_ids).into_iter().collect();
+        let unowned_party_ids = party_ids
+            .filter(|party_id| !owned_party_ids.contains(party_id))
+            .collect::<Vec<u32>>();
+        if !unowned_party_ids.is_empty() {
+            warn!(
+                "Signer {} rejecting {} from signer {} for unowned parties {:?}",
+                self.signer_id, message_type, signer_id, unowned_party_ids
+            );
+            self.ban(signer_id);
+            return Err(Error::UnownedPartyIds(signer_id, unowned_party_ids));
+        }
+        Ok(())
+    }
+
+    /// handle incoming DkgDecommit, which reveals the polynomial commitments behind a signer's
+    /// Pedersen commitments
+    pub fn dkg_decommit(&mut self, dkg_decommit: &DkgDecommit) -> Result<Vec<Message>, Error> {
+        if dkg_decommit.dkg_id != self.dkg_id {
+            return Err(Error::BadDkgId(self.dkg_id, dkg_decommit.dkg_id));
+        }
+        if !self.pedersen {
+            debug!(
+                "Signer {} ignoring DkgDecommit from signer {} outside a Pedersen round",
+                self.signer_id, dkg_decommit.signer_id
+            );
+            return Ok(vec![]);
+        }
+
+        let signer_id = dkg_decommit.signer_id;
+        let party_ids = dkg_decommit.comms.iter().map(|(party_id, _, _)| *party_id);
+        self.check_party_ids("DkgDecommit", signer_id, party_ids)?;
+
+        let invalid_party_ids =
+            dkg_decommit.invalid_party_ids(self.threshold, &self.hiding_commitments);
+        if !invalid_party_ids.is_empty() {
+            warn!(
+                "Signer {} rejecting DkgDecommit from signer {} which doesn't open the Pedersen commitments for parties {:?}",
+                self.signer_id, signer_id, invalid_party_ids
+            );
+            self.ban(signer_id);
+            return Err(Error::InvalidPolyCommitments(invalid_party_ids));
+        }
+
+        for (party_id, comm, _) in &dkg_decommit.comms {
+            self.commitments.insert(*party_id, comm.clone());
+        }
+        debug!(
+            "received DkgDecommit from signer {} {}/{}",
+            signer_id,
             self.commitments.len(),
             self.signer.get_num_parties(),
         );
         Ok(vec![])
     }
 
+    /// the number of plaintext bytes per DKG private share: the share, and in a Pedersen round
+    /// its blinding share
+    fn share_len(&self) -> usize {
+        if self.pedersen {
+            64
+        } else {
+            32
+        }
+    }
+
+    /// handle incoming DkgEcho
+    pub fn dkg_echo(&mut self, dkg_echo: &DkgEcho) -> Result<Vec<Message>, Error> {
+        if dkg_echo.dkg_id != self.dkg_id {
+            return Err(Error::BadDkgId(self.dkg_id, dkg_echo.dkg_id));
+        }
+
+        self.echoes
+            .insert(dkg_echo.signer_id, dkg_echo.public_shares_hash);
+        debug!(
+            "received DkgEcho from signer {} {}/{}",
+            dkg_echo.signer_id,
+            self.echoes.len(),
+            self.total_signers,
+        );
+        Ok(vec![])
+    }
+
     /// handle incoming DkgPrivateShares
     pub fn dkg_private_shares(
         &mut self,
         dkg_private_shares: &DkgPrivateShares,
     ) -> Result<Vec<Message>, Error> {
+        if dkg_private_shares.dkg_id != self.dkg_id {
+            return Err(Error::BadDkgId(self.dkg_id, dkg_private_shares.dkg_id));
+        }
+
+        // the shares are stored by source party, so a signer may only send its own parties'
+        // shares, or it could replace an honest party's.  Commitments are indexed by the ID they
+        // were made for, one past the party ID.
+        let party_ids = dkg_private_shares
+            .src_party_ids
+            .iter()
+            .map(|src_id| compute::id(*src_id).get_u32());
+        self.check_party_ids("DkgPrivateShares", dkg_private_shares.signer_id, party_ids)?;
+
+        if dkg_private_shares
+            .verifiable_shares
+            .iter()
+            .any(|(dst_signer_id, _)| *dst_signer_id == self.signer_id)
+        {
+            return self.dkg_verifiable_private_shares(dkg_private_shares);
+        }
+
         // go ahead and decrypt here, since we know the signer_id and hence the pubkey of the sender
+        let public_key = self
+            .public_points
+            .signers
+            .get(&dkg_private_shares.signer_id)
+            .ok_or(Error::UnknownSigner(dkg_private_shares.signer_id))?;
+        let shared_secret = make_shared_secret(&self.network_private_key, public_key);
 
-        // make a HashSet of our key_ids so we can quickly query them
-        let key_ids: HashSet<u32> = self.signer.get_key_ids().into_iter().collect();
-        let compressed =
-            Compressed::from(self.public_keys.signers[&dkg_private_shares.signer_id].to_bytes());
-        let public_key = Point::try_from(&compressed).unwrap();
-        let shared_secret = make_shared_secret(&self.network_private_key, &public_key);
-
-        for (src_id, shares) in &dkg_private_shares.shares {
-            let mut decrypted_shares = HashMap::new();
-            for (dst_key_id, bytes) in shares {
-                if key_ids.contains(dst_key_id) {
-                    match decrypt(&shared_secret, bytes) {
-                        Ok(plain) => match Scalar::try_from(&plain[..]) {
-                            Ok(s) => {
-                                decrypted_shares.insert(*dst_key_id, s);
-                            }
-                            Err(e) => {
-                                warn!("Failed to parse Scalar for dkg private share from src_id {} to dst_id {}: {:?}", src_id, dst_key_id, e);
-                                self.invalid_private_shares.push(*src_id);
-                            }
-                        },
-                        Err(e) => {
-                            warn!("Failed to decrypt dkg private share from src_id {} to dst_id {}: {:?}", src_id, dst_key_id, e);
-                            self.invalid_private_shares.push(*src_id);
+        // our shares are laid out by source party, then by our key IDs in ascending order
+        let mut key_ids = self.signer.get_key_ids();
+        key_ids.sort();
+        let num_shares = dkg_private_shares.src_party_ids.len() * key_ids.len();
+        let share_len = self.share_len();
+        let plain = dkg_private_shares
+            .shares
+            .iter()
+            .find(|(dst_signer_id, _)| *dst_signer_id == self.signer_id)
+            .map(|(_, encrypted_shares)| {
+                let share_key = make_share_key(
+                    &shared_secret,
+                    dkg_private_shares.dkg_id,
+                    dkg_private_shares.signer_id,
+                    self.signer_id,
+                );
+                decrypt(&share_key, encrypted_shares)
+                    .map_err(|e| (BadShareReason::Decrypt, format!("{:?}", e)))
+                    .and_then(|plain| {
+                        if plain.len() == share_len * num_shares {
+                            Ok(plain)
+                        } else {
+                            Err((
+                                BadShareReason::BadLength,
+                                format!("{} bytes for {} shares", plain.len(), num_shares),
+                            ))
                         }
-                    }
+                    })
+            });
+
+        for src_id in &dkg_private_shares.src_party_ids {
+            self.decrypted_shares.insert(*src_id, HashMap::new());
+        }
+        let Some(plain) = plain else {
+            // no shares were sent to this signer, which compute_secrets will report
+            return Ok(vec![]);
+        };
+
+        let positions = dkg_private_shares
+            .src_party_ids
+            .iter()
+            .flat_map(|src_id| key_ids.iter().map(move |dst_key_id| (*src_id, *dst_key_id)))
+            .collect::<Vec<(u32, u32)>>();
+
+        // the shares share one decryption, but are parsed and checked against the Pedersen
+        // commitments independently, so with the rayon feature they are checked in parallel
+        #[cfg(feature = "rayon")]
+        let positions_iter = positions.par_iter().enumerate();
+        #[cfg(not(feature = "rayon"))]
+        let positions_iter = positions.iter().enumerate();
+
+        let pedersen = self.pedersen;
+        let hiding_commitments = &self.hiding_commitments;
+        let results: Vec<Result<Scalar, (BadShareReason, String)>> = positions_iter
+            .map(|(i, (src_id, dst_key_id))| {
+                let plain = plain.as_ref().map_err(Clone::clone)?;
+                let share = Scalar::try_from(&plain[share_len * i..share_len * i + 32])
+                    .map_err(|e| (BadShareReason::InvalidScalar, format!("{:?}", e)))?;
+                if pedersen {
+                    Self::check_hiding_share(
+                        hiding_commitments,
+                        &plain[share_len * i + 32..share_len * (i + 1)],
+                        *src_id,
+                        *dst_key_id,
+                        share,
+                    )
+                } else {
+                    Ok(share)
+                }
+            })
+            .collect();
+
+        for ((src_id, dst_key_id), result) in positions.into_iter().zip(results) {
+            match result {
+                Ok(s) => {
+                    self.decrypted_shares
+                        .entry(src_id)
+                        .or_default()
+                        .insert(dst_key_id, s);
+                }
+                Err((reason, e)) => {
+                    warn!(
+                        "{} ({}) for dkg private share from signer {} src_id {} to dst_id {}",
+                        reason, e, dkg_private_shares.signer_id, src_id, dst_key_id
+                    );
+                    self.invalid_private_shares
+                        .entry(dkg_private_shares.signer_id)
+                        .or_default()
+                        .insert(BadPrivateShare {
+                            src_party_id: src_id,
+                            dst_key_id,
+                            reason,
+                        });
                 }
             }
-            self.decrypted_shares.insert(*src_id, decrypted_shares);
         }
         debug!(
             "received DkgPrivateShares from signer {} {}/{}",
@@ -538,31 +2405,138 @@ impl<Signer: SignerTrait> SigningRound<Signer> {
         );
         Ok(vec![])
     }
+
+    /// check a share decrypted in a Pedersen round, and the `blinding_share` bytes which followed
+    /// it, against the dealer's Pedersen commitment
+    fn check_hiding_share(
+        hiding_commitments: &BTreeMap<u32, Vec<Point>>,
+        blinding_share: &[u8],
+        src_id: u32,
+        dst_key_id: u32,
+        share: Scalar,
+    ) -> Result<Scalar, (BadShareReason, String)> {
+        let blinding_share = Scalar::try_from(blinding_share)
+            .map_err(|e| (BadShareReason::InvalidScalar, format!("{:?}", e)))?;
+        // commitments are indexed by the ID they were made for, one past the party ID
+        let hiding_comm = hiding_commitments
+            .get(&compute::id(src_id).get_u32())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        if VSS::verify_hiding_share(
+            &compute::id(dst_key_id),
+            &share,
+            &blinding_share,
+            hiding_comm,
+        ) {
+            Ok(share)
+        } else {
+            Err((
+                BadShareReason::BadHidingShare,
+                "share and blinding share".to_string(),
+            ))
+        }
+    }
+
+    /// handle incoming DkgPrivateShares which were sent with publicly verifiable encryption,
+    /// checking each share against its dealer's commitment before decrypting it
+    fn dkg_verifiable_private_shares(
+        &mut self,
+        dkg_private_shares: &DkgPrivateShares,
+    ) -> Result<Vec<Message>, Error> {
+        let mut rng = OsRng;
+        let signer_id = dkg_private_shares.signer_id;
+        let mut key_ids = self.signer.get_key_ids();
+        key_ids.sort();
+        let public_key = self
+            .public_points
+            .signers
+            .get(&self.signer_id)
+            .ok_or(Error::UnknownSigner(self.signer_id))?;
+
+        let invalid_shares = dkg_private_shares.invalid_verifiable_shares(
+            self.signer_id,
+            &key_ids,
+            public_key,
+            &self.commitments,
+        );
+        for bad_share in &invalid_shares {
+            warn!(
+                "{} for dkg private share from signer {} src_id {} to dst_id {}",
+                bad_share.reason, signer_id, bad_share.src_party_id, bad_share.dst_key_id
+            );
+        }
+        if !invalid_shares.is_empty() {
+            self.invalid_private_shares
+                .entry(signer_id)
+                .or_default()
+                .extend(invalid_shares.iter().cloned());
+        }
+
+        for src_id in &dkg_private_shares.src_party_ids {
+            self.decrypted_shares.insert(*src_id, HashMap::new());
+            for dst_key_id in &key_ids {
+                let is_invalid = invalid_shares.iter().any(|bad_share| {
+                    bad_share.src_party_id == *src_id && bad_share.dst_key_id == *dst_key_id
+                });
+                let share = dkg_private_shares.verifiable_share(
+                    self.signer_id,
+                    &key_ids,
+                    *src_id,
+                    *dst_key_id,
+                );
+                let Some(share) = share.filter(|_| !is_invalid) else {
+                    continue;
+                };
+                match share.decrypt(&self.network_private_key) {
+                    Ok(s) => {
+                        self.decrypted_shares
+                            .entry(*src_id)
+                            .or_default()
+                            .insert(*dst_key_id, s);
+                    }
+                    Err(e) => {
+                        warn!(
+                            "{} for dkg private share from signer {} src_id {} to dst_id {}",
+                            e, signer_id, src_id, dst_key_id
+                        );
+                        if let Some(complaint) = share.complain(&self.network_private_key, &mut rng)
+                        {
+                            self.share_complaints.entry(signer_id).or_default().push(
+                                ShareComplaint {
+                                    src_party_id: *src_id,
+                                    dst_key_id: *dst_key_id,
+                                    complaint,
+                                },
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        debug!(
+            "received verifiable DkgPrivateShares from signer {} {}/{}",
+            signer_id,
+            self.decrypted_shares.len(),
+            self.signer.get_num_parties(),
+        );
+        Ok(vec![])
+    }
 }
 
 impl<Signer: SignerTrait> StateMachine<State, Error> for SigningRound<Signer> {
     fn move_to(&mut self, state: State) -> Result<(), Error> {
         self.can_move_to(&state)?;
+        if self.state == State::Idle {
+            self.state_history.clear();
+        }
+        self.state_history.push((state, Instant::now()));
         self.state = state;
         Ok(())
     }
 
     fn can_move_to(&self, state: &State) -> Result<(), Error> {
         let prev_state = &self.state;
-        let accepted = match state {
-            State::Idle => true,
-            State::DkgPublicDistribute => {
-                prev_state == &State::Idle
-                    || prev_state == &State::DkgPublicGather
-                    || prev_state == &State::DkgPrivateDistribute
-            }
-            State::DkgPublicGather => prev_state == &State::DkgPublicDistribute,
-            State::DkgPrivateDistribute => prev_state == &State::DkgPublicGather,
-            State::DkgPrivateGather => prev_state == &State::DkgPrivateDistribute,
-            State::SignGather => prev_state == &State::Idle,
-            State::Signed => prev_state == &State::SignGather,
-        };
-        if accepted {
+        if prev_state.can_move_to(state) {
             debug!("state change from {:?} to {:?}", prev_state, state);
             Ok(())
         } else {
@@ -0,0 +1,223 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::errors::StoreError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// One write in a batch
+pub enum Op {
+    /// Set a key to a value
+    Put(String, Vec<u8>),
+    /// Remove a key
+    Delete(String),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// Writes which a store applies all together or not at all
+pub struct Batch {
+    /// the writes, in the order they're applied
+    pub ops: Vec<Op>,
+}
+
+impl Batch {
+    /// Create an empty batch
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set `key` to `value`
+    pub fn put(mut self, key: &str, value: &[u8]) -> Self {
+        self.ops.push(Op::Put(key.to_string(), value.to_vec()));
+        self
+    }
+
+    /// Remove `key`
+    pub fn delete(mut self, key: &str) -> Self {
+        self.ops.push(Op::Delete(key.to_string()));
+        self
+    }
+}
+
+/// A key-value store for signer state, e.g. the encrypted blobs written by
+/// `SigningRound::save_to_store`.  Implement it to keep signer state in sled, SQLite, or a cloud
+/// KMS; stores only need to provide `get` and an atomic `write`.
+pub trait StateStore {
+    /// Get the value of `key`, or None if it isn't set
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StoreError>;
+
+    /// Apply every write in `batch`, or none of them if there's an error
+    fn write(&mut self, batch: Batch) -> Result<(), StoreError>;
+
+    /// Set `key` to `value`
+    fn put(&mut self, key: &str, value: &[u8]) -> Result<(), StoreError> {
+        self.write(Batch::new().put(key, value))
+    }
+
+    /// Remove `key`, which does nothing if it isn't set
+    fn delete(&mut self, key: &str) -> Result<(), StoreError> {
+        self.write(Batch::new().delete(key))
+    }
+}
+
+fn apply(values: &mut BTreeMap<String, Vec<u8>>, batch: Batch) {
+    for op in batch.ops {
+        match op {
+            Op::Put(key, value) => {
+                values.insert(key, value);
+            }
+            Op::Delete(key) => {
+                values.remove(&key);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// A store which keeps everything in memory, for tests and for signers which run DKG on every
+/// start
+pub struct MemoryStore {
+    /// the stored values
+    pub values: BTreeMap<String, Vec<u8>>,
+}
+
+impl StateStore for MemoryStore {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.values.get(key).cloned())
+    }
+
+    fn write(&mut self, batch: Batch) -> Result<(), StoreError> {
+        apply(&mut self.values, batch);
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+/// A store which keeps everything in a single JSON file, mapping keys to hex encoded values.
+/// Every batch rewrites a temporary file and renames it over the store, so a crash leaves either
+/// the old or the new contents.
+pub struct FileStore {
+    /// the path of the store
+    pub path: PathBuf,
+    values: BTreeMap<String, Vec<u8>>,
+}
+
+impl FileStore {
+    /// Open the store at `path`, which is created on the first write if it doesn't exist
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, StoreError> {
+        let path = path.as_ref().to_path_buf();
+        let values = match fs::read(&path) {
+            Ok(bytes) => {
+                let hex_values: BTreeMap<String, String> = serde_json::from_slice(&bytes)
+                    .map_err(|e| StoreError::Corrupt(e.to_string()))?;
+                hex_values
+                    .into_iter()
+                    .map(|(key, value)| {
+                        hex::decode(value)
+                            .map(|value| (key, value))
+                            .map_err(|e| StoreError::Corrupt(e.to_string()))
+                    })
+                    .collect::<Result<_, _>>()?
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(StoreError::Io(e.to_string())),
+        };
+        Ok(Self { path, values })
+    }
+
+    fn persist(&self, values: &BTreeMap<String, Vec<u8>>) -> Result<(), StoreError> {
+        let hex_values: BTreeMap<&String, String> = values
+            .iter()
+            .map(|(key, value)| (key, hex::encode(value)))
+            .collect();
+        let bytes = serde_json::to_vec(&hex_values).map_err(|e| StoreError::Io(e.to_string()))?;
+
+        let mut tmp = self.path.clone().into_os_string();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        let mut file = fs::File::create(&tmp).map_err(|e| StoreError::Io(e.to_string()))?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp, &self.path))
+            .map_err(|e| StoreError::Io(e.to_string()))
+    }
+}
+
+impl StateStore for FileStore {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StoreError> {
+        Ok(self.values.get(key).cloned())
+    }
+
+    fn write(&mut self, batch: Batch) -> Result<(), StoreError> {
+        let mut values = self.values.clone();
+        apply(&mut values, batch);
+        self.persist(&values)?;
+        self.values = values;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{env, fs, process};
+
+    use super::{Batch, FileStore, MemoryStore, StateStore};
+    use crate::errors::StoreError;
+
+    fn check_store<S: StateStore>(store: &mut S) {
+        assert_eq!(store.get("a").unwrap(), None);
+        store.put("a", b"1").unwrap();
+        store.put("b", b"2").unwrap();
+        assert_eq!(store.get("a").unwrap(), Some(b"1".to_vec()));
+
+        store
+            .write(Batch::new().delete("a").put("b", b"3").put("c", b"4"))
+            .unwrap();
+        assert_eq!(store.get("a").unwrap(), None);
+        assert_eq!(store.get("b").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(store.get("c").unwrap(), Some(b"4".to_vec()));
+
+        store.delete("c").unwrap();
+        store.delete("c").unwrap();
+        assert_eq!(store.get("c").unwrap(), None);
+    }
+
+    #[test]
+    fn memory_store() {
+        check_store(&mut MemoryStore::default());
+    }
+
+    #[test]
+    fn file_store() {
+        let dir = env::temp_dir().join(format!("wsts-store-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+
+        let mut store = FileStore::open(&path).unwrap();
+        check_store(&mut store);
+
+        // everything written is there after reopening
+        let store = FileStore::open(&path).unwrap();
+        assert_eq!(store.get("b").unwrap(), Some(b"3".to_vec()));
+        assert_eq!(store.get("c").unwrap(), None);
+
+        // a failed batch leaves the store as it was
+        let mut store = FileStore {
+            path: dir.join("missing").join("state.json"),
+            ..store
+        };
+        assert!(matches!(store.put("b", b"5"), Err(StoreError::Io(_))));
+        assert_eq!(store.get("b").unwrap(), Some(b"3".to_vec()));
+
+        fs::write(&path, b"not json").unwrap();
+        assert!(matches!(
+            FileStore::open(&path),
+            Err(StoreError::Corrupt(_))
+        ));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
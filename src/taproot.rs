@@ -4,11 +4,32 @@ use p256k1::{
     scalar::Scalar,
 };
 
-use crate::{common::Signature, compute};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    common::{Signature, TaprootTweak},
+    compute::{self, Bip340Challenge},
+    errors::SighashError,
+    traits::ChallengeHash,
+};
+
+/// The BIP-342 tapscript leaf version
+pub const TAPSCRIPT_LEAF_VERSION: u8 = 0xc0;
+
+/// The BIP-341 sighash type which signs everything, and is left out of the signature
+pub const SIGHASH_DEFAULT: u8 = 0x00;
+/// Sign all inputs and outputs
+pub const SIGHASH_ALL: u8 = 0x01;
+/// Sign all inputs and none of the outputs
+pub const SIGHASH_NONE: u8 = 0x02;
+/// Sign all inputs and the output at the same index as the input being signed
+pub const SIGHASH_SINGLE: u8 = 0x03;
+/// Combined with another sighash type to only sign the input being signed
+pub const SIGHASH_ANYONECANPAY: u8 = 0x80;
 
 /// A SchnorrProof in BIP-340 format
 #[allow(non_snake_case)]
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SchnorrProof {
     /// The schnorr public commitment (FROST Signature R)
     pub r: field::Element,
@@ -26,8 +47,13 @@ impl SchnorrProof {
     }
 
     /// Verify a BIP-340 schnorr proof
-    #[allow(non_snake_case)]
     pub fn verify(&self, public_key: &field::Element, msg: &[u8]) -> bool {
+        self.verify_with::<Bip340Challenge>(public_key, msg)
+    }
+
+    /// Verify a BIP-340 style schnorr proof, using the challenge hash `H`
+    #[allow(non_snake_case)]
+    pub fn verify_with<H: ChallengeHash>(&self, public_key: &field::Element, msg: &[u8]) -> bool {
         let Y = match Point::lift_x(public_key) {
             Ok(Y) => Y,
             Err(_) => return false,
@@ -36,12 +62,27 @@ impl SchnorrProof {
             Ok(R) => R,
             Err(_) => return false,
         };
-        let c = compute::challenge(&Y, &R, msg);
+        let c = H::challenge(&Y, &R, msg);
         let Rp = self.s * G - c * Y;
 
         Rp.has_even_y() && Rp.x() == self.r
     }
 
+    /// Verify a BIP-340 schnorr proof using libsecp256k1, the implementation which Bitcoin
+    /// consensus uses, rather than this crate's own arithmetic
+    #[cfg(feature = "with_secp256k1")]
+    pub fn verify_bip340(&self, public_key: &field::Element, msg: &[u8]) -> bool {
+        let secp = secp256k1::Secp256k1::verification_only();
+        let Ok(sig) = secp256k1::schnorr::Signature::from_slice(&self.to_bytes()) else {
+            return false;
+        };
+        let Ok(public_key) = secp256k1::XOnlyPublicKey::from_slice(&public_key.to_bytes()) else {
+            return false;
+        };
+
+        secp.verify_schnorr(&sig, msg, &public_key).is_ok()
+    }
+
     /// Serialize this proof into a 64-byte buffer
     pub fn to_bytes(&self) -> [u8; 64] {
         let mut bytes = [0u8; 64];
@@ -68,10 +109,334 @@ impl From<[u8; 64]> for SchnorrProof {
     }
 }
 
+impl TryFrom<&[u8]> for SchnorrProof {
+    type Error = core::array::TryFromSliceError;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let bytes: [u8; 64] = bytes.try_into()?;
+
+        Ok(Self::from(bytes))
+    }
+}
+
+/// A leaf of a taproot script tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TapLeaf {
+    /// The leaf version
+    pub version: u8,
+    /// The serialized script
+    pub script: Vec<u8>,
+}
+
+impl TapLeaf {
+    /// Construct a tapscript leaf for the serialized `script`
+    pub fn new(script: &[u8]) -> Self {
+        Self {
+            version: TAPSCRIPT_LEAF_VERSION,
+            script: script.to_vec(),
+        }
+    }
+
+    /// Compute the BIP-341 leaf hash
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = compute::tagged_hash("TapLeaf");
+
+        hasher.update([self.version]);
+        hasher.update(compact_size(self.script.len()));
+        hasher.update(&self.script);
+
+        hasher.finalize().into()
+    }
+}
+
+/// Compute the BIP-341 branch hash of two child nodes
+pub fn tap_branch(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = compute::tagged_hash("TapBranch");
+
+    if a <= b {
+        hasher.update(a);
+        hasher.update(b);
+    } else {
+        hasher.update(b);
+        hasher.update(a);
+    }
+
+    hasher.finalize().into()
+}
+
+/// Bitcoin's variable length integer encoding, used to prefix scripts
+pub(crate) fn compact_size(n: usize) -> Vec<u8> {
+    if n < 0xfd {
+        vec![n as u8]
+    } else if n <= 0xffff {
+        let mut bytes = vec![0xfd];
+        bytes.extend_from_slice(&(n as u16).to_le_bytes());
+        bytes
+    } else if n <= 0xffff_ffff {
+        let mut bytes = vec![0xfe];
+        bytes.extend_from_slice(&(n as u32).to_le_bytes());
+        bytes
+    } else {
+        let mut bytes = vec![0xff];
+        bytes.extend_from_slice(&(n as u64).to_le_bytes());
+        bytes
+    }
+}
+
+/// A taproot script tree, which is built by hashing adjacent pairs of nodes at each level
+/// until a single root remains
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TapTree {
+    /// The leaves of the tree
+    pub leaves: Vec<TapLeaf>,
+    /// The merkle path from each leaf to the root
+    paths: Vec<Vec<[u8; 32]>>,
+    /// The merkle root of the tree
+    root: [u8; 32],
+}
+
+impl TapTree {
+    /// Build a tree from the passed leaves, or return None if there are no leaves
+    pub fn new(leaves: Vec<TapLeaf>) -> Option<Self> {
+        if leaves.is_empty() {
+            return None;
+        }
+
+        let mut paths = vec![Vec::new(); leaves.len()];
+        let mut level: Vec<([u8; 32], Vec<usize>)> = leaves
+            .iter()
+            .enumerate()
+            .map(|(i, leaf)| (leaf.hash(), vec![i]))
+            .collect();
+
+        while level.len() > 1 {
+            let mut next = Vec::with_capacity(level.len().div_ceil(2));
+            let mut nodes = level.into_iter();
+            while let Some((left, mut left_leaves)) = nodes.next() {
+                match nodes.next() {
+                    Some((right, right_leaves)) => {
+                        for i in &left_leaves {
+                            paths[*i].push(right);
+                        }
+                        for i in &right_leaves {
+                            paths[*i].push(left);
+                        }
+                        left_leaves.extend(right_leaves);
+                        next.push((tap_branch(&left, &right), left_leaves));
+                    }
+                    None => next.push((left, left_leaves)),
+                }
+            }
+            level = next;
+        }
+
+        Some(Self {
+            leaves,
+            paths,
+            root: level[0].0,
+        })
+    }
+
+    /// The merkle root to pass to `sign_taproot`
+    pub fn merkle_root(&self) -> [u8; 32] {
+        self.root
+    }
+
+    /// The merkle path from the leaf at `index` to the root
+    pub fn merkle_path(&self, index: usize) -> Option<&[[u8; 32]]> {
+        self.paths.get(index).map(|path| path.as_slice())
+    }
+
+    /// The BIP-341 control block needed to spend the leaf at `index`, for a taproot output
+    /// with the passed internal key
+    pub fn control_block(&self, internal_key: &Point, index: usize) -> Option<Vec<u8>> {
+        let leaf = self.leaves.get(index)?;
+        let path = self.merkle_path(index)?;
+
+        let output_key =
+            compute::tweaked_public_key(internal_key, TaprootTweak::from(Some(self.root)));
+        let parity = u8::from(!output_key.has_even_y());
+
+        let mut control_block = Vec::with_capacity(33 + 32 * path.len());
+        control_block.push(leaf.version | parity);
+        control_block.extend_from_slice(&internal_key.x().to_bytes());
+        for node in path {
+            control_block.extend_from_slice(node);
+        }
+
+        Some(control_block)
+    }
+}
+
+/// A reference to a previous transaction output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct OutPoint {
+    /// The ID of the transaction, in the byte order it is serialized in
+    pub txid: [u8; 32],
+    /// The index of the output in the transaction
+    pub vout: u32,
+}
+
+/// A transaction input, without the scripts and witness which sighashes don't cover
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxIn {
+    /// The output being spent
+    pub previous_output: OutPoint,
+    /// The sequence number
+    pub sequence: u32,
+}
+
+/// A transaction output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TxOut {
+    /// The amount in satoshis
+    pub value: u64,
+    /// The serialized output script
+    pub script_pubkey: Vec<u8>,
+}
+
+/// The parts of a transaction which BIP-341 sighashes cover
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    /// The transaction version
+    pub version: i32,
+    /// The lock time
+    pub lock_time: u32,
+    /// The inputs
+    pub inputs: Vec<TxIn>,
+    /// The outputs
+    pub outputs: Vec<TxOut>,
+}
+
+impl TxOut {
+    fn hash_into(&self, hasher: &mut Sha256) {
+        hasher.update(self.value.to_le_bytes());
+        hasher.update(compact_size(self.script_pubkey.len()));
+        hasher.update(&self.script_pubkey);
+    }
+}
+
+/// Compute the BIP-341 sighash to sign with `sign_taproot` when spending input `input_index`
+/// of `tx` by key path.  `spent_outputs` are the outputs spent by every input, in order
+pub fn key_spend_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    spent_outputs: &[TxOut],
+    sighash_type: u8,
+) -> Result<[u8; 32], SighashError> {
+    sighash(tx, input_index, spent_outputs, sighash_type, None)
+}
+
+/// Compute the BIP-342 sighash to sign when spending input `input_index` of `tx` by script
+/// path through `leaf`, with no OP_CODESEPARATOR executed.  `spent_outputs` are the outputs
+/// spent by every input, in order
+pub fn script_spend_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    spent_outputs: &[TxOut],
+    sighash_type: u8,
+    leaf: &TapLeaf,
+) -> Result<[u8; 32], SighashError> {
+    sighash(tx, input_index, spent_outputs, sighash_type, Some(leaf))
+}
+
+/// Compute the BIP-341 signature message and hash it, with the BIP-342 extension when spending
+/// through `leaf`.  Annexes aren't supported
+fn sighash(
+    tx: &Transaction,
+    input_index: usize,
+    spent_outputs: &[TxOut],
+    sighash_type: u8,
+    leaf: Option<&TapLeaf>,
+) -> Result<[u8; 32], SighashError> {
+    if !matches!(sighash_type, 0x00..=0x03 | 0x81..=0x83) {
+        return Err(SighashError::BadSighashType(sighash_type));
+    }
+    if input_index >= tx.inputs.len() {
+        return Err(SighashError::BadInputIndex(input_index, tx.inputs.len()));
+    }
+    if spent_outputs.len() != tx.inputs.len() {
+        return Err(SighashError::BadSpentOutputs(
+            spent_outputs.len(),
+            tx.inputs.len(),
+        ));
+    }
+    let anyone_can_pay = sighash_type & SIGHASH_ANYONECANPAY != 0;
+    let output_type = sighash_type & 0x03;
+
+    let mut hasher = compute::tagged_hash("TapSighash");
+    // sighash epoch
+    hasher.update([0u8]);
+    hasher.update([sighash_type]);
+    hasher.update(tx.version.to_le_bytes());
+    hasher.update(tx.lock_time.to_le_bytes());
+
+    if !anyone_can_pay {
+        let mut prevouts = Sha256::new();
+        let mut amounts = Sha256::new();
+        let mut script_pubkeys = Sha256::new();
+        let mut sequences = Sha256::new();
+        for (input, spent_output) in tx.inputs.iter().zip(spent_outputs) {
+            prevouts.update(input.previous_output.txid);
+            prevouts.update(input.previous_output.vout.to_le_bytes());
+            amounts.update(spent_output.value.to_le_bytes());
+            script_pubkeys.update(compact_size(spent_output.script_pubkey.len()));
+            script_pubkeys.update(&spent_output.script_pubkey);
+            sequences.update(input.sequence.to_le_bytes());
+        }
+        hasher.update(prevouts.finalize());
+        hasher.update(amounts.finalize());
+        hasher.update(script_pubkeys.finalize());
+        hasher.update(sequences.finalize());
+    }
+
+    if output_type != SIGHASH_NONE && output_type != SIGHASH_SINGLE {
+        let mut outputs = Sha256::new();
+        for output in &tx.outputs {
+            output.hash_into(&mut outputs);
+        }
+        hasher.update(outputs.finalize());
+    }
+
+    // spend type, with the extension flag for script path spends and no annex
+    hasher.update([u8::from(leaf.is_some()) * 2]);
+
+    if anyone_can_pay {
+        let input = &tx.inputs[input_index];
+        let spent_output = &spent_outputs[input_index];
+        hasher.update(input.previous_output.txid);
+        hasher.update(input.previous_output.vout.to_le_bytes());
+        spent_output.hash_into(&mut hasher);
+        hasher.update(input.sequence.to_le_bytes());
+    } else {
+        hasher.update((input_index as u32).to_le_bytes());
+    }
+
+    if output_type == SIGHASH_SINGLE {
+        let output = tx
+            .outputs
+            .get(input_index)
+            .ok_or(SighashError::NoSingleOutput(input_index))?;
+        let mut single_output = Sha256::new();
+        output.hash_into(&mut single_output);
+        hasher.update(single_output.finalize());
+    }
+
+    if let Some(leaf) = leaf {
+        hasher.update(leaf.hash());
+        // key version
+        hasher.update([0u8]);
+        // no OP_CODESEPARATOR was executed
+        hasher.update(u32::MAX.to_le_bytes());
+    }
+
+    Ok(hasher.finalize().into())
+}
+
 /// Helper functions for tests
 pub mod test_helpers {
     use crate::{
-        common::{PolyCommitment, PublicNonce, SignatureShare},
+        common::{PolyCommitment, PublicNonce, SignatureShare, TaprootTweak},
         errors::DkgError,
         traits,
     };
@@ -97,10 +462,11 @@ pub mod test_helpers {
             }
         }
 
+        let polys = A.iter().collect::<Vec<&PolyCommitment>>();
         let mut secret_errors = HashMap::new();
         for signer in signers.iter_mut() {
-            if let Err(signer_secret_errors) = signer.compute_secrets(&private_shares, &A) {
-                secret_errors.extend(signer_secret_errors.into_iter());
+            if let Err(signer_secret_errors) = signer.compute_secrets(&private_shares, &polys) {
+                secret_errors.extend(signer_secret_errors);
             }
         }
 
@@ -129,12 +495,12 @@ pub mod test_helpers {
         msg: &[u8],
         signers: &mut [Signer],
         rng: &mut RNG,
-        merkle_root: Option<[u8; 32]>,
+        tweak: TaprootTweak,
     ) -> (Vec<PublicNonce>, Vec<SignatureShare>) {
         let (signer_ids, key_ids, nonces) = sign_params(signers, rng);
         let shares = signers
             .iter()
-            .flat_map(|s| s.sign_taproot(msg, &signer_ids, &key_ids, &nonces, merkle_root))
+            .flat_map(|s| s.sign_taproot(msg, &signer_ids, &key_ids, &nonces, tweak))
             .collect();
 
         (nonces, shares)
@@ -143,28 +509,288 @@ pub mod test_helpers {
 
 #[cfg(test)]
 mod test {
-    use super::{test_helpers, SchnorrProof};
+    use super::{
+        key_spend_sighash, script_spend_sighash, test_helpers, OutPoint, SchnorrProof, TapLeaf,
+        TapTree, Transaction, TxIn, TxOut, SIGHASH_DEFAULT, SIGHASH_SINGLE,
+    };
 
-    use crate::{compute, traits::Aggregator, traits::Signer, v1, v2};
+    use crate::{
+        common::TaprootTweak, compute, errors::SighashError, traits::Aggregator, traits::Signer,
+        v1, v2,
+    };
+    use p256k1::{field, point::Point, scalar::Scalar};
     use rand_core::OsRng;
 
+    #[test]
+    fn tap_tree_single_leaf() {
+        // BIP-341 wallet test vector
+        let script =
+            hex::decode("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac")
+                .unwrap();
+        let leaf = TapLeaf::new(&script);
+        assert_eq!(
+            hex::encode(leaf.hash()),
+            "c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b"
+        );
+
+        let tree = TapTree::new(vec![leaf.clone()]).unwrap();
+        assert_eq!(tree.merkle_root(), leaf.hash());
+        assert!(tree.merkle_path(0).unwrap().is_empty());
+        assert!(tree.merkle_path(1).is_none());
+        assert!(TapTree::new(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn tap_tree_control_block() {
+        let mut internal_key_bytes = [0u8; 32];
+        internal_key_bytes.copy_from_slice(
+            &hex::decode("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d")
+                .unwrap(),
+        );
+        let internal_key = Point::lift_x(&field::Element::from(internal_key_bytes)).unwrap();
+
+        let leaves = vec![
+            TapLeaf::new(&[0x51]),
+            TapLeaf::new(&[0x52]),
+            TapLeaf::new(&[0x53]),
+        ];
+        let tree = TapTree::new(leaves.clone()).unwrap();
+        assert_eq!(
+            hex::encode(tree.merkle_root()),
+            "609f09890e4348cc5bcc26e51c432c7830162715d64aab7c306f853c9da06a7b"
+        );
+        assert_eq!(
+            tree.merkle_path(0).unwrap(),
+            &[leaves[1].hash(), leaves[2].hash()]
+        );
+
+        assert_eq!(
+            hex::encode(tree.control_block(&internal_key, 0).unwrap()),
+            "c0d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d\
+             c276fef1386890619b80e10a4a328572d97493add269df1a15a7f89f8ae8ec09\
+             a8199db85e1f94b911a63ffece012bb8afc92131e59a614341db4ed2312a3c48"
+        );
+        assert_eq!(
+            hex::encode(tree.control_block(&internal_key, 2).unwrap()),
+            "c0d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d\
+             6496f0779f38b871013be71ee7dcce8fcdcc02afc4c688acb159fc5de2fba55e"
+        );
+        assert!(tree.control_block(&internal_key, 3).is_none());
+
+        // the control block uses the x-only internal key, so its negation gives the same result
+        assert_eq!(
+            tree.control_block(&-internal_key, 0),
+            tree.control_block(&internal_key, 0)
+        );
+    }
+
+    fn sighash_tx() -> (Transaction, Vec<TxOut>) {
+        let tx = Transaction {
+            version: 2,
+            lock_time: 500,
+            inputs: (0..3)
+                .map(|i| TxIn {
+                    previous_output: OutPoint {
+                        txid: [i as u8; 32],
+                        vout: i,
+                    },
+                    sequence: 0xffff_fffd - i,
+                })
+                .collect(),
+            outputs: (0..2)
+                .map(|i| TxOut {
+                    value: 1000 * (i + 1),
+                    script_pubkey: vec![0x6a, i as u8],
+                })
+                .collect(),
+        };
+        let spent_outputs = (0..3)
+            .map(|i| TxOut {
+                value: 5000 + i,
+                script_pubkey: [&[0x51, 0x20][..], &[i as u8; 32]].concat(),
+            })
+            .collect();
+        (tx, spent_outputs)
+    }
+
+    #[test]
+    fn sighash_errors() {
+        let (tx, spent_outputs) = sighash_tx();
+        assert_eq!(
+            key_spend_sighash(&tx, 0, &spent_outputs, 0x04),
+            Err(SighashError::BadSighashType(0x04))
+        );
+        assert_eq!(
+            key_spend_sighash(&tx, 3, &spent_outputs, SIGHASH_DEFAULT),
+            Err(SighashError::BadInputIndex(3, 3))
+        );
+        assert_eq!(
+            key_spend_sighash(&tx, 0, &spent_outputs[1..], SIGHASH_DEFAULT),
+            Err(SighashError::BadSpentOutputs(2, 3))
+        );
+        assert_eq!(
+            key_spend_sighash(&tx, 2, &spent_outputs, SIGHASH_SINGLE),
+            Err(SighashError::NoSingleOutput(2))
+        );
+
+        // the key and script paths sign different messages
+        let leaf = TapLeaf::new(&[0x51]);
+        assert_ne!(
+            key_spend_sighash(&tx, 0, &spent_outputs, SIGHASH_DEFAULT),
+            script_spend_sighash(&tx, 0, &spent_outputs, SIGHASH_DEFAULT, &leaf)
+        );
+    }
+
+    #[cfg(feature = "bitcoin")]
+    #[test]
+    fn sighash_matches_bitcoin() {
+        use bitcoin::{
+            absolute::LockTime,
+            hashes::Hash,
+            sighash::{Prevouts, SighashCache, TapSighashType},
+            taproot::{LeafVersion, TapLeafHash},
+            transaction::Version,
+            Amount, ScriptBuf, Sequence, Txid, Witness,
+        };
+
+        let (tx, spent_outputs) = sighash_tx();
+        let btc_tx = bitcoin::Transaction {
+            version: Version(tx.version),
+            lock_time: LockTime::from_consensus(tx.lock_time),
+            input: tx
+                .inputs
+                .iter()
+                .map(|input| bitcoin::TxIn {
+                    previous_output: bitcoin::OutPoint {
+                        txid: Txid::from_byte_array(input.previous_output.txid),
+                        vout: input.previous_output.vout,
+                    },
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence(input.sequence),
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: tx
+                .outputs
+                .iter()
+                .map(|output| bitcoin::TxOut {
+                    value: Amount::from_sat(output.value),
+                    script_pubkey: ScriptBuf::from_bytes(output.script_pubkey.clone()),
+                })
+                .collect(),
+        };
+        let btc_spent_outputs = spent_outputs
+            .iter()
+            .map(|output| bitcoin::TxOut {
+                value: Amount::from_sat(output.value),
+                script_pubkey: ScriptBuf::from_bytes(output.script_pubkey.clone()),
+            })
+            .collect::<Vec<bitcoin::TxOut>>();
+        let leaf = TapLeaf::new(&[0x20, 0x51, 0xac]);
+        let leaf_hash = TapLeafHash::from_script(
+            &ScriptBuf::from_bytes(leaf.script.clone()),
+            LeafVersion::TapScript,
+        );
+
+        let mut cache = SighashCache::new(&btc_tx);
+        for sighash_type in [0x00, 0x01, 0x02, 0x03, 0x81, 0x82, 0x83] {
+            let btc_sighash_type = TapSighashType::from_consensus_u8(sighash_type).unwrap();
+            for input_index in 0..2 {
+                let prevouts = Prevouts::All(&btc_spent_outputs);
+                let expected = cache
+                    .taproot_key_spend_signature_hash(input_index, &prevouts, btc_sighash_type)
+                    .unwrap();
+                assert_eq!(
+                    key_spend_sighash(&tx, input_index, &spent_outputs, sighash_type).unwrap(),
+                    expected.to_byte_array()
+                );
+
+                let expected = cache
+                    .taproot_script_spend_signature_hash(
+                        input_index,
+                        &prevouts,
+                        leaf_hash,
+                        btc_sighash_type,
+                    )
+                    .unwrap();
+                assert_eq!(
+                    script_spend_sighash(&tx, input_index, &spent_outputs, sighash_type, &leaf)
+                        .unwrap(),
+                    expected.to_byte_array()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn taproot_output_key() {
+        let mut internal_key_bytes = [0u8; 32];
+        internal_key_bytes.copy_from_slice(
+            &hex::decode("d6889cb081036e0faefa3a35157ad71086b123b2b144b649798b494c300a961d")
+                .unwrap(),
+        );
+        let internal_key = Point::lift_x(&field::Element::from(internal_key_bytes)).unwrap();
+        let script =
+            hex::decode("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac")
+                .unwrap();
+        let merkle_root = TapTree::new(vec![TapLeaf::new(&script)])
+            .unwrap()
+            .merkle_root();
+
+        let output_key =
+            compute::tweaked_public_key(&internal_key, TaprootTweak::KeySpend { merkle_root });
+        assert_eq!(
+            hex::encode(output_key.x().to_bytes()),
+            "445848438be58c8e370a743bb9f933baeaec7485ba7f4bb5d1b65e39e78acb13"
+        );
+
+        // BIP341 uses the x-only internal key, so an odd y key has the same output key
+        assert_eq!(
+            compute::tweaked_public_key(&-internal_key, TaprootTweak::KeySpend { merkle_root }),
+            output_key
+        );
+    }
+
+    #[test]
+    fn test_taproot_sign_verify_many_keys() {
+        // half of all group keys have odd y, which BIP341 negates before tweaking
+        for _ in 0..8 {
+            taproot_sign_verify_v1(TaprootTweak::KeySpendNoScripts);
+            taproot_sign_verify_v2(TaprootTweak::KeySpendNoScripts);
+        }
+    }
+
+    #[test]
+    fn test_taproot_sign_verify_v1_tap_tree() {
+        let tree = TapTree::new(vec![TapLeaf::new(&[0x51]), TapLeaf::new(&[0x52])]).unwrap();
+
+        taproot_sign_verify_v1(TaprootTweak::KeySpend {
+            merkle_root: tree.merkle_root(),
+        });
+    }
+
     #[test]
     #[allow(non_snake_case)]
     fn test_taproot_sign_verify_v1() {
         let script = "OP_1".as_bytes();
         let merkle_root = compute::merkle_root(script);
 
-        taproot_sign_verify_v1(Some(merkle_root));
+        taproot_sign_verify_v1(TaprootTweak::KeySpend { merkle_root });
     }
 
     #[test]
     #[allow(non_snake_case)]
     fn test_taproot_sign_verify_v1_no_merkle_root() {
-        taproot_sign_verify_v1(None);
+        taproot_sign_verify_v1(TaprootTweak::KeySpendNoScripts);
+    }
+
+    #[test]
+    fn test_taproot_sign_verify_v1_raw_tweak() {
+        taproot_sign_verify_v1(TaprootTweak::RawTweak(Scalar::random(&mut OsRng)));
     }
 
     #[allow(non_snake_case)]
-    fn taproot_sign_verify_v1(merkle_root: Option<[u8; 32]>) {
+    fn taproot_sign_verify_v1(tweak: TaprootTweak) {
         let mut rng = OsRng;
 
         // First create and verify a frost signature
@@ -194,9 +820,13 @@ mod test {
         let mut S = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
         let mut sig_agg = v1::Aggregator::new(N, T);
         sig_agg.init(A.clone()).expect("aggregator init failed");
-        let tweaked_public_key = compute::tweaked_public_key(&sig_agg.poly[0], merkle_root);
-        let (nonces, sig_shares) = test_helpers::sign(msg, &mut S, &mut rng, merkle_root);
-        let proof = match sig_agg.sign_taproot(msg, &nonces, &sig_shares, &[], merkle_root) {
+        let output_key = sig_agg.taproot_output_key(tweak).unwrap();
+        assert_eq!(
+            output_key,
+            compute::tweaked_public_key(&sig_agg.poly[0], tweak).x()
+        );
+        let (nonces, sig_shares) = test_helpers::sign(msg, &mut S, &mut rng, tweak);
+        let proof = match sig_agg.sign_taproot(msg, &nonces, &sig_shares, &[], tweak) {
             Err(e) => panic!("Aggregator sign failed: {:?}", e),
             Ok(proof) => proof,
         };
@@ -206,7 +836,13 @@ mod test {
         let proof_deser = SchnorrProof::from(proof_bytes);
 
         assert_eq!(proof, proof_deser);
-        assert!(proof_deser.verify(&tweaked_public_key.x(), msg));
+        assert!(proof_deser.verify(&output_key, msg));
+        #[cfg(feature = "with_secp256k1")]
+        assert!(proof_deser.verify_bip340(&output_key, msg));
+
+        let proof_slice = SchnorrProof::try_from(&proof_bytes[..]).unwrap();
+        assert_eq!(proof, proof_slice);
+        assert!(SchnorrProof::try_from(&proof_bytes[..63]).is_err());
     }
 
     #[test]
@@ -215,17 +851,22 @@ mod test {
         let script = "OP_1".as_bytes();
         let merkle_root = compute::merkle_root(script);
 
-        taproot_sign_verify_v2(Some(merkle_root));
+        taproot_sign_verify_v2(TaprootTweak::KeySpend { merkle_root });
     }
 
     #[test]
     #[allow(non_snake_case)]
     fn test_taproot_sign_verify_v2_no_merkle_root() {
-        taproot_sign_verify_v2(None);
+        taproot_sign_verify_v2(TaprootTweak::KeySpendNoScripts);
+    }
+
+    #[test]
+    fn test_taproot_sign_verify_v2_raw_tweak() {
+        taproot_sign_verify_v2(TaprootTweak::RawTweak(Scalar::random(&mut OsRng)));
     }
 
     #[allow(non_snake_case)]
-    fn taproot_sign_verify_v2(merkle_root: Option<[u8; 32]>) {
+    fn taproot_sign_verify_v2(tweak: TaprootTweak) {
         let mut rng = OsRng;
 
         // First create and verify a frost signature
@@ -257,9 +898,13 @@ mod test {
         let key_ids = S.iter().flat_map(|s| s.get_key_ids()).collect::<Vec<u32>>();
         let mut sig_agg = v2::Aggregator::new(Nk, T);
         sig_agg.init(A.clone()).expect("aggregator init failed");
-        let tweaked_public_key = compute::tweaked_public_key(&sig_agg.poly[0], merkle_root);
-        let (nonces, sig_shares) = test_helpers::sign(msg, &mut S, &mut rng, merkle_root);
-        let proof = match sig_agg.sign_taproot(msg, &nonces, &sig_shares, &key_ids, merkle_root) {
+        let output_key = sig_agg.taproot_output_key(tweak).unwrap();
+        assert_eq!(
+            output_key,
+            compute::tweaked_public_key(&sig_agg.poly[0], tweak).x()
+        );
+        let (nonces, sig_shares) = test_helpers::sign(msg, &mut S, &mut rng, tweak);
+        let proof = match sig_agg.sign_taproot(msg, &nonces, &sig_shares, &key_ids, tweak) {
             Err(e) => panic!("Aggregator sign failed: {:?}", e),
             Ok(proof) => proof,
         };
@@ -269,6 +914,12 @@ mod test {
         let proof_deser = SchnorrProof::from(proof_bytes);
 
         assert_eq!(proof, proof_deser);
-        assert!(proof_deser.verify(&tweaked_public_key.x(), msg));
+        assert!(proof_deser.verify(&output_key, msg));
+        #[cfg(feature = "with_secp256k1")]
+        assert!(proof_deser.verify_bip340(&output_key, msg));
+
+        let proof_slice = SchnorrProof::try_from(&proof_bytes[..]).unwrap();
+        assert_eq!(proof, proof_slice);
+        assert!(SchnorrProof::try_from(&proof_bytes[..63]).is_err());
     }
 }
@@ -0,0 +1,6 @@
+/// A reference model of the state machines' legal transitions, and generators of message
+/// interleavings, for model checking code which drives the state machines
+pub mod model;
+/// An in-process simulation of a coordinator and signers over a network which can drop,
+/// duplicate, reorder, and delay packets
+pub mod sim;
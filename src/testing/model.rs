@@ -0,0 +1,298 @@
+use core::fmt::Debug;
+use rand_core::RngCore;
+
+use crate::{
+    common::TaprootTweak,
+    state_machine::{coordinator::State as CoordinatorState, signer::State as SignerState},
+};
+
+/// A state machine state, with the transition rules which its state machine's `can_move_to`
+/// enforces
+pub trait Transitions: Clone + Debug + PartialEq {
+    /// Every state.  States which carry signing parameters are listed for an ordinary round, a
+    /// taproot round, and a taproot round with a merkle root.
+    fn states() -> Vec<Self>;
+
+    /// Whether a state machine in this state may move to `next`
+    fn can_move_to(&self, next: &Self) -> bool;
+
+    /// The states which a state machine in this state may move to
+    fn next_states(&self) -> Vec<Self> {
+        Self::states()
+            .into_iter()
+            .filter(|next| self.can_move_to(next))
+            .collect()
+    }
+}
+
+impl Transitions for CoordinatorState {
+    fn states() -> Vec<Self> {
+        let mut states = vec![
+            CoordinatorState::Idle,
+            CoordinatorState::DkgPublicDistribute,
+            CoordinatorState::ThresholdChangeDistribute,
+            CoordinatorState::DkgPublicGather,
+            CoordinatorState::DkgPrivateDistribute,
+            CoordinatorState::DkgEndGather,
+        ];
+        for (is_taproot, tweak) in [
+            (false, TaprootTweak::KeySpendNoScripts),
+            (true, TaprootTweak::KeySpendNoScripts),
+            (
+                true,
+                TaprootTweak::KeySpend {
+                    merkle_root: [0; 32],
+                },
+            ),
+        ] {
+            states.extend([
+                CoordinatorState::NonceRequest(is_taproot, tweak),
+                CoordinatorState::NonceGather(is_taproot, tweak),
+                CoordinatorState::SigShareRequest(is_taproot, tweak),
+                CoordinatorState::SigShareGather(is_taproot, tweak),
+            ]);
+        }
+        states
+    }
+
+    fn can_move_to(&self, next: &Self) -> bool {
+        CoordinatorState::can_move_to(self, next)
+    }
+}
+
+impl Transitions for SignerState {
+    fn states() -> Vec<Self> {
+        vec![
+            SignerState::Idle,
+            SignerState::DkgPublicDistribute,
+            SignerState::DkgPublicGather,
+            SignerState::DkgPrivateDistribute,
+            SignerState::DkgPrivateGather,
+            SignerState::SignGather,
+            SignerState::Signed,
+        ]
+    }
+
+    fn can_move_to(&self, next: &Self) -> bool {
+        SignerState::can_move_to(self, next)
+    }
+}
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("illegal transition {step} from {from} to {to}")]
+/// A transition which the state machine would reject
+pub struct BadTransition {
+    /// the index of the transition in the trace
+    pub step: usize,
+    /// the state being moved from
+    pub from: String,
+    /// the state being moved to
+    pub to: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A reference model of a state machine, which follows the same transition rules and records
+/// every state it passes through
+pub struct Model<S: Transitions> {
+    /// the current state
+    pub state: S,
+    /// every state which was moved to, in order
+    pub trace: Vec<S>,
+}
+
+impl<S: Transitions> Model<S> {
+    /// Create a model in `state`
+    pub fn new(state: S) -> Self {
+        Self {
+            state,
+            trace: Vec::new(),
+        }
+    }
+
+    /// Move to `next`, if the state machine would allow it
+    pub fn move_to(&mut self, next: S) -> Result<(), BadTransition> {
+        if !self.state.can_move_to(&next) {
+            return Err(BadTransition {
+                step: self.trace.len(),
+                from: format!("{:?}", self.state),
+                to: format!("{:?}", next),
+            });
+        }
+        self.trace.push(next.clone());
+        self.state = next;
+        Ok(())
+    }
+
+    /// Check that every move in `trace` from `initial` is legal, e.g. the states which an
+    /// integration observed its state machine pass through
+    pub fn check_trace(initial: S, trace: &[S]) -> Result<Self, BadTransition> {
+        let mut model = Self::new(initial);
+        for state in trace {
+            model.move_to(state.clone())?;
+        }
+        Ok(model)
+    }
+
+    /// Make `steps` random legal moves
+    pub fn random_walk<RNG: RngCore>(&mut self, steps: usize, rng: &mut RNG) {
+        for _ in 0..steps {
+            let next_states = self.state.next_states();
+            let next = next_states[random_index(next_states.len(), rng)].clone();
+            self.move_to(next).expect("next state is legal");
+        }
+    }
+}
+
+/// Merge `streams` into one in a random order, keeping the order within each stream, as a
+/// network whose links each deliver in order would
+pub fn interleave<T: Clone, RNG: RngCore>(streams: &[Vec<T>], rng: &mut RNG) -> Vec<T> {
+    let mut positions = vec![0; streams.len()];
+    let mut merged = Vec::with_capacity(streams.iter().map(Vec::len).sum());
+    loop {
+        let pending: Vec<usize> = (0..streams.len())
+            .filter(|i| positions[*i] < streams[*i].len())
+            .collect();
+        if pending.is_empty() {
+            return merged;
+        }
+        let i = pending[random_index(pending.len(), rng)];
+        merged.push(streams[i][positions[i]].clone());
+        positions[i] += 1;
+    }
+}
+
+/// Every way of merging `streams` into one which keeps the order within each stream.  The
+/// number of interleavings grows factorially, so this is only for exhaustively checking a few
+/// short streams.
+pub fn interleavings<T: Clone>(streams: &[Vec<T>]) -> Vec<Vec<T>> {
+    let mut merged = Vec::new();
+    extend_interleavings(
+        streams,
+        &mut vec![0; streams.len()],
+        &mut Vec::new(),
+        &mut merged,
+    );
+    merged
+}
+
+fn extend_interleavings<T: Clone>(
+    streams: &[Vec<T>],
+    positions: &mut [usize],
+    prefix: &mut Vec<T>,
+    merged: &mut Vec<Vec<T>>,
+) {
+    let mut done = true;
+    for i in 0..streams.len() {
+        if positions[i] < streams[i].len() {
+            done = false;
+            prefix.push(streams[i][positions[i]].clone());
+            positions[i] += 1;
+            extend_interleavings(streams, positions, prefix, merged);
+            positions[i] -= 1;
+            prefix.pop();
+        }
+    }
+    if done {
+        merged.push(prefix.clone());
+    }
+}
+
+/// Shuffle `items` into a random order, as a network which can reorder anything would
+pub fn shuffle<T, RNG: RngCore>(items: &mut [T], rng: &mut RNG) {
+    for i in (1..items.len()).rev() {
+        items.swap(i, random_index(i + 1, rng));
+    }
+}
+
+fn random_index<RNG: RngCore>(len: usize, rng: &mut RNG) -> usize {
+    (rng.next_u64() % len as u64) as usize
+}
+
+#[cfg(test)]
+mod test {
+    use rand_core::OsRng;
+
+    use super::{interleave, interleavings, shuffle, Model, Transitions};
+    use crate::{
+        common::TaprootTweak,
+        state_machine::{coordinator::State as CoordinatorState, signer::State as SignerState},
+    };
+
+    #[test]
+    fn transitions() {
+        let next_states = CoordinatorState::Idle.next_states();
+        assert!(next_states.contains(&CoordinatorState::DkgPublicDistribute));
+        assert!(next_states.contains(&CoordinatorState::NonceRequest(
+            true,
+            TaprootTweak::KeySpend {
+                merkle_root: [0; 32]
+            }
+        )));
+        assert!(!next_states.contains(&CoordinatorState::DkgEndGather));
+
+        // a signing round can't change its parameters part way through
+        assert!(
+            !CoordinatorState::NonceGather(false, TaprootTweak::KeySpendNoScripts).can_move_to(
+                &CoordinatorState::SigShareRequest(true, TaprootTweak::KeySpendNoScripts)
+            )
+        );
+
+        assert_eq!(
+            SignerState::SignGather.next_states(),
+            [SignerState::Idle, SignerState::Signed]
+        );
+    }
+
+    #[test]
+    fn check_trace() {
+        let dkg = [
+            CoordinatorState::DkgPublicDistribute,
+            CoordinatorState::DkgPublicGather,
+            CoordinatorState::DkgPrivateDistribute,
+            CoordinatorState::DkgEndGather,
+            CoordinatorState::Idle,
+        ];
+        let model = Model::check_trace(CoordinatorState::Idle, &dkg).unwrap();
+        assert_eq!(model.state, CoordinatorState::Idle);
+
+        let err = Model::check_trace(CoordinatorState::Idle, &dkg[1..]).unwrap_err();
+        assert_eq!(err.step, 0);
+        assert_eq!(err.to, "DkgPublicGather");
+    }
+
+    #[test]
+    fn random_walk() {
+        let mut model = Model::new(SignerState::Idle);
+        model.random_walk(100, &mut OsRng);
+        assert_eq!(model.trace.len(), 100);
+        assert!(Model::check_trace(SignerState::Idle, &model.trace).is_ok());
+
+        let mut model = Model::new(CoordinatorState::Idle);
+        model.random_walk(100, &mut OsRng);
+        assert!(Model::check_trace(CoordinatorState::Idle, &model.trace).is_ok());
+    }
+
+    #[test]
+    fn interleave_streams() {
+        let streams = vec![vec![1, 2, 3], vec![4, 5], vec![6]];
+        let merged = interleave(&streams, &mut OsRng);
+        assert_eq!(merged.len(), 6);
+        for stream in &streams {
+            let positions: Vec<usize> = stream
+                .iter()
+                .map(|x| merged.iter().position(|y| x == y).unwrap())
+                .collect();
+            assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        }
+
+        // 6! / (3! 2! 1!) ways to merge the streams
+        let all = interleavings(&streams);
+        assert_eq!(all.len(), 60);
+        assert!(all.contains(&merged));
+
+        let mut items: Vec<u32> = (0..10).collect();
+        shuffle(&mut items, &mut OsRng);
+        items.sort();
+        assert_eq!(items, (0..10).collect::<Vec<u32>>());
+    }
+}
@@ -0,0 +1,460 @@
+use hashbrown::HashMap;
+use p256k1::{ecdsa, point::Point, scalar::Scalar};
+use rand_core::{OsRng, RngCore};
+use std::collections::BTreeMap;
+use tracing::{debug, warn};
+
+use crate::{
+    common::TaprootTweak,
+    net::Packet,
+    state_machine::{
+        coordinator::{frost, Coordinatable, CoordinatorConfig, Error as CoordinatorError},
+        signer::{SignerConfig, SigningRound},
+        KeyId, OperationResult, PublicKeys,
+    },
+    traits::{Aggregator as AggregatorTrait, Signer as SignerTrait},
+    vectors::SeededRng,
+};
+
+#[derive(thiserror::Error, Debug)]
+/// Errors from running a simulation
+pub enum Error {
+    #[error("coordinator error {0}")]
+    /// The coordinator couldn't start a round
+    Coordinator(#[from] CoordinatorError),
+    #[error("no result after {0} attempts")]
+    /// Every attempt at the round timed out or failed
+    GaveUp(u32),
+    #[error("unexpected result {0:?}")]
+    /// The round finished with a result of the wrong kind
+    UnexpectedResult(Box<OperationResult>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// A node on the simulated network
+pub enum Node {
+    /// The coordinator
+    Coordinator,
+    /// The signer with the given ID
+    Signer(u32),
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+/// The faults which a link injects into the packets sent over it
+pub struct LinkFaults {
+    /// the chance of dropping each packet, from 0 to 1
+    pub loss: f64,
+    /// the chance of delivering each packet twice, from 0 to 1
+    pub duplication: f64,
+    /// whether packets may arrive in a different order than they were sent
+    pub reorder: bool,
+    /// the fewest ticks a packet takes to arrive
+    pub min_delay: u64,
+    /// the most ticks a packet takes to arrive
+    pub max_delay: u64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+/// Counts of what happened to the packets sent over the network
+pub struct NetworkStats {
+    /// packets sent, not counting duplicates
+    pub sent: u64,
+    /// packets dropped
+    pub dropped: u64,
+    /// extra copies of packets delivered
+    pub duplicated: u64,
+    /// packets delivered, counting duplicates
+    pub delivered: u64,
+}
+
+/// A simulated network with a clock, which delivers packets after a delay and injects the
+/// faults configured for each link.  A node's packets to itself are delivered right away.
+pub struct Network {
+    /// the faults on every link which has none of its own
+    pub default_faults: LinkFaults,
+    /// the faults on particular links, indexed by sender then receiver
+    pub links: HashMap<(Node, Node), LinkFaults>,
+    /// the current time in ticks
+    pub now: u64,
+    /// what happened to the packets sent so far
+    pub stats: NetworkStats,
+    rng: SeededRng,
+    seq: u64,
+    in_flight: BTreeMap<(u64, u64), (Node, Packet)>,
+    last_arrival: HashMap<(Node, Node), u64>,
+}
+
+impl Network {
+    /// Create a network without faults, whose faults are drawn from `seed` so a run can be
+    /// reproduced
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self {
+            default_faults: LinkFaults::default(),
+            links: HashMap::new(),
+            now: 0,
+            stats: NetworkStats::default(),
+            rng: SeededRng::new(seed),
+            seq: 0,
+            in_flight: BTreeMap::new(),
+            last_arrival: HashMap::new(),
+        }
+    }
+
+    /// Set the faults on every link which has none of its own
+    pub fn with_faults(mut self, faults: LinkFaults) -> Self {
+        self.default_faults = faults;
+        self
+    }
+
+    /// Set the faults on the link from `from` to `to`
+    pub fn set_link(&mut self, from: Node, to: Node, faults: LinkFaults) {
+        self.links.insert((from, to), faults);
+    }
+
+    /// Send `packet` from `from` to `to`
+    pub fn send(&mut self, from: Node, to: Node, packet: &Packet) {
+        self.stats.sent += 1;
+        if from == to {
+            self.enqueue(self.now, to, packet);
+            return;
+        }
+        let faults = self
+            .links
+            .get(&(from, to))
+            .unwrap_or(&self.default_faults)
+            .clone();
+        if self.chance(faults.loss) {
+            debug!(?from, ?to, "Dropping {:?}", packet.msg.message_type());
+            self.stats.dropped += 1;
+            return;
+        }
+        let copies = if self.chance(faults.duplication) {
+            self.stats.duplicated += 1;
+            2
+        } else {
+            1
+        };
+        for _ in 0..copies {
+            let spread = faults.max_delay.saturating_sub(faults.min_delay) + 1;
+            let mut arrival = self.now + faults.min_delay + self.rng.next_u64() % spread;
+            if !faults.reorder {
+                let last_arrival = self.last_arrival.entry((from, to)).or_default();
+                arrival = arrival.max(*last_arrival);
+                *last_arrival = arrival;
+            }
+            self.enqueue(arrival, to, packet);
+        }
+    }
+
+    /// Whether any packets are still in flight
+    pub fn is_idle(&self) -> bool {
+        self.in_flight.is_empty()
+    }
+
+    fn enqueue(&mut self, arrival: u64, to: Node, packet: &Packet) {
+        self.seq += 1;
+        self.in_flight
+            .insert((arrival, self.seq), (to, packet.clone()));
+    }
+
+    fn next_arrival(&self) -> Option<u64> {
+        self.in_flight.keys().next().map(|(arrival, _)| *arrival)
+    }
+
+    fn deliver_next(&mut self) -> Option<(Node, Packet)> {
+        let ((arrival, _), delivery) = self.in_flight.pop_first()?;
+        self.now = self.now.max(arrival);
+        self.stats.delivered += 1;
+        Some(delivery)
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && (self.rng.next_u64() as f64) < probability * u64::MAX as f64
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+/// How long a simulated integration waits before giving up on signers, and how often it retries
+pub struct RetryConfig {
+    /// the ticks to wait for nonces before calling `nonce_timeout`
+    pub nonce_timeout: u64,
+    /// the ticks to wait for a round to finish before resetting the coordinator and retrying
+    pub round_timeout: u64,
+    /// the most times to try a round
+    pub max_attempts: u32,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            nonce_timeout: 20,
+            round_timeout: 100,
+            max_attempts: 3,
+        }
+    }
+}
+
+/// An in-process run of a coordinator and its signers over a simulated network, for checking
+/// that timeout and retry settings still produce results when the network misbehaves
+pub struct Simulation<Aggregator: AggregatorTrait, Signer: SignerTrait> {
+    /// the coordinator
+    pub coordinator: frost::Coordinator<Aggregator>,
+    /// the signers, indexed by signer ID
+    pub signers: Vec<SigningRound<Signer>>,
+    /// the network between them
+    pub network: Network,
+    /// the timeout and retry settings
+    pub retry: RetryConfig,
+}
+
+impl<Aggregator: AggregatorTrait, Signer: SignerTrait> Simulation<Aggregator, Signer> {
+    /// Create `num_signers` signers with `keys_per_signer` keys each and a coordinator, which talk
+    /// over `network`
+    pub fn new(num_signers: u32, keys_per_signer: u32, threshold: u32, network: Network) -> Self {
+        let mut rng = OsRng;
+        let private_keys: Vec<Scalar> =
+            (0..num_signers).map(|_| Scalar::random(&mut rng)).collect();
+        let mut public_keys = PublicKeys::default();
+        let mut key_ids = Vec::new();
+        for (signer_id, private_key) in private_keys.iter().enumerate() {
+            let public_key = ecdsa::PublicKey::new(private_key).expect("nonzero private key");
+            public_keys.signers.insert(signer_id as u32, public_key);
+            let first_key_id = signer_id as u32 * keys_per_signer;
+            key_ids.push((first_key_id..first_key_id + keys_per_signer).collect::<Vec<u32>>());
+            for key_id in &key_ids[signer_id] {
+                public_keys.insert_key(KeyId(*key_id), public_key);
+            }
+        }
+
+        let signers = private_keys
+            .iter()
+            .zip(key_ids)
+            .enumerate()
+            .map(|(signer_id, (private_key, key_ids))| {
+                let config = SignerConfig::new(
+                    threshold,
+                    signer_id as u32,
+                    key_ids,
+                    *private_key,
+                    public_keys.clone(),
+                );
+                SigningRound::from_config(config).expect("valid signer config")
+            })
+            .collect();
+        let config = CoordinatorConfig::new(
+            num_signers,
+            num_signers * keys_per_signer,
+            threshold,
+            private_keys[0],
+        );
+        let coordinator =
+            frost::Coordinator::from_config(config).expect("valid coordinator config");
+
+        Self {
+            coordinator,
+            signers,
+            network,
+            retry: RetryConfig::default(),
+        }
+    }
+
+    /// Set the timeout and retry settings
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Run DKG, returning the group key
+    pub fn run_dkg(&mut self) -> Result<Point, Error> {
+        match self.run(|coordinator| coordinator.start_dkg_round())? {
+            OperationResult::Dkg(group_key) => Ok(group_key),
+            result => Err(Error::UnexpectedResult(Box::new(result))),
+        }
+    }
+
+    /// Sign `msg`, returning a `Sign` or `SignTaproot` result
+    pub fn sign(
+        &mut self,
+        msg: &[u8],
+        is_taproot: bool,
+        tweak: TaprootTweak,
+    ) -> Result<OperationResult, Error> {
+        self.run(|coordinator| coordinator.start_signing_message(msg, is_taproot, tweak))
+    }
+
+    /// Run a round started by `start` until the coordinator has a result, retrying it from
+    /// scratch whenever it times out or the coordinator fails
+    fn run(
+        &mut self,
+        start: impl Fn(&mut frost::Coordinator<Aggregator>) -> Result<Packet, CoordinatorError>,
+    ) -> Result<OperationResult, Error> {
+        for attempt in 1..=self.retry.max_attempts {
+            if attempt > 1 {
+                warn!(attempt, "Retrying round");
+                self.coordinator.reset();
+            }
+            let packet = start(&mut self.coordinator)?;
+            self.broadcast(Node::Coordinator, &packet);
+
+            let round_deadline = self.network.now + self.retry.round_timeout;
+            let mut nonce_deadline = Some(self.network.now + self.retry.nonce_timeout);
+            loop {
+                let deadline = nonce_deadline.unwrap_or(round_deadline).min(round_deadline);
+                match self.network.next_arrival() {
+                    Some(arrival) if arrival <= deadline => {
+                        let (to, packet) = self.network.deliver_next().expect("packet in flight");
+                        match self.deliver(to, &packet) {
+                            Ok(Some(result)) => return Ok(result),
+                            Ok(None) => {}
+                            Err(e) => {
+                                warn!("Coordinator failed: {e:?}");
+                                break;
+                            }
+                        }
+                    }
+                    _ if Some(deadline) == nonce_deadline && deadline < round_deadline => {
+                        self.network.now = deadline;
+                        // the coordinator refuses once it is past gathering nonces
+                        nonce_deadline = match self.coordinator.nonce_timeout() {
+                            Ok(packet) => {
+                                self.broadcast(Node::Coordinator, &packet);
+                                Some(deadline + self.retry.nonce_timeout)
+                            }
+                            Err(_) => None,
+                        };
+                    }
+                    _ => {
+                        self.network.now = round_deadline;
+                        warn!("Round timed out");
+                        break;
+                    }
+                }
+            }
+        }
+        Err(Error::GaveUp(self.retry.max_attempts))
+    }
+
+    /// Deliver `packet` to `to`, and send whatever it sends in response
+    fn deliver(
+        &mut self,
+        to: Node,
+        packet: &Packet,
+    ) -> Result<Option<OperationResult>, CoordinatorError> {
+        match to {
+            Node::Coordinator => {
+                let (outbound, result) = self.coordinator.process_message(packet)?;
+                if let Some(outbound) = outbound {
+                    self.broadcast(Node::Coordinator, &outbound);
+                }
+                Ok(result)
+            }
+            Node::Signer(signer_id) => {
+                let signer = &mut self.signers[signer_id as usize];
+                match signer.process_inbound_messages(std::slice::from_ref(packet)) {
+                    Ok(outbound) => {
+                        for packet in &outbound {
+                            self.broadcast(to, packet);
+                        }
+                    }
+                    Err(e) => debug!(signer_id, "Signer failed: {e:?}"),
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Send `packet` from `from` to every signer, and to the coordinator if a signer sent it
+    fn broadcast(&mut self, from: Node, packet: &Packet) {
+        for signer_id in 0..self.signers.len() as u32 {
+            self.network.send(from, Node::Signer(signer_id), packet);
+        }
+        if from != Node::Coordinator {
+            self.network.send(from, Node::Coordinator, packet);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Error, LinkFaults, Network, Node, RetryConfig, Simulation};
+    use crate::{
+        common::TaprootTweak,
+        state_machine::OperationResult,
+        traits::{Aggregator as AggregatorTrait, Signer as SignerTrait},
+        v1, v2,
+    };
+
+    const MSG: &[u8] = b"It was many and many a year ago";
+
+    fn sign<Aggregator: AggregatorTrait, Signer: SignerTrait>(faults: LinkFaults) {
+        let network = Network::new([1; 32]).with_faults(faults);
+        let mut sim = Simulation::<Aggregator, Signer>::new(5, 3, 7, network);
+        let group_key = sim.run_dkg().unwrap();
+        match sim
+            .sign(MSG, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap()
+        {
+            OperationResult::Sign(sig) => assert!(sig.verify(&group_key, MSG)),
+            result => panic!("Expected Sign result, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn sign_v1() {
+        sign::<v1::Aggregator, v1::Signer>(LinkFaults::default());
+    }
+
+    #[test]
+    fn sign_v2() {
+        sign::<v2::Aggregator, v2::Signer>(LinkFaults::default());
+    }
+
+    #[test]
+    fn duplicated_reordered_delayed() {
+        sign::<v2::Aggregator, v2::Signer>(LinkFaults {
+            duplication: 0.3,
+            reorder: true,
+            min_delay: 1,
+            max_delay: 10,
+            ..Default::default()
+        });
+    }
+
+    #[test]
+    fn lost_nonces() {
+        let mut sim = Simulation::<v2::Aggregator, v2::Signer>::new(5, 3, 7, Network::new([2; 32]));
+        let group_key = sim.run_dkg().unwrap();
+
+        // the coordinator never hears from signer 4, so it has to time out waiting for its nonce
+        let lost = LinkFaults {
+            loss: 1.0,
+            ..Default::default()
+        };
+        sim.network
+            .set_link(Node::Signer(4), Node::Coordinator, lost);
+        match sim
+            .sign(MSG, false, TaprootTweak::KeySpendNoScripts)
+            .unwrap()
+        {
+            OperationResult::Sign(sig) => assert!(sig.verify(&group_key, MSG)),
+            result => panic!("Expected Sign result, got {result:?}"),
+        }
+        assert!(sim.network.stats.dropped > 0);
+    }
+
+    #[test]
+    fn gives_up() {
+        let mut sim = Simulation::<v2::Aggregator, v2::Signer>::new(5, 3, 7, Network::new([3; 32]))
+            .with_retry(RetryConfig {
+                max_attempts: 2,
+                ..Default::default()
+            });
+        sim.run_dkg().unwrap();
+
+        sim.network.default_faults.loss = 1.0;
+        assert!(matches!(
+            sim.sign(MSG, false, TaprootTweak::KeySpendNoScripts),
+            Err(Error::GaveUp(2))
+        ));
+    }
+}
@@ -1,15 +1,21 @@
+use core::fmt::Debug;
 use hashbrown::HashMap;
-use p256k1::{point::Point, scalar::Scalar};
-use rand_core::{CryptoRng, RngCore};
+use p256k1::{field, point::Point, scalar::Scalar};
+use rand_core::{CryptoRng, CryptoRngCore, RngCore};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
 use crate::{
-    common::{MerkleRoot, PolyCommitment, PublicNonce, Signature, SignatureShare},
+    common::{Nonce, PolyCommitment, PublicNonce, Signature, SignatureShare, TaprootTweak},
     errors::{AggregatorError, DkgError},
     taproot::SchnorrProof,
+    v1, v2, v3,
 };
 
 /// A trait which provides a common `Signer` interface for `v1` and `v2`
 pub trait Signer {
+    /// The saved state required to reconstruct a `Signer`
+    type State: Serialize + DeserializeOwned;
+
     /// Create a new `Signer`
     fn new<RNG: RngCore + CryptoRng>(
         party_id: u32,
@@ -20,6 +26,12 @@ pub trait Signer {
         rng: &mut RNG,
     ) -> Self;
 
+    /// Load a `Signer` from the saved state
+    fn load(state: &Self::State) -> Self;
+
+    /// Save the state required to reconstruct the `Signer`
+    fn save(&self) -> Self::State;
+
     /// Get the signer ID for this signer
     fn get_id(&self) -> u32;
 
@@ -29,12 +41,35 @@ pub trait Signer {
     /// Get the total number of parties
     fn get_num_parties(&self) -> u32;
 
+    /// Get the DKG party IDs which the signer `signer_id` holding `key_ids` makes commitments for
+    fn party_ids(signer_id: u32, key_ids: &[u32]) -> Vec<u32>;
+
+    /// Get the aggregate group public key computed during DKG
+    fn get_group_key(&self) -> Point;
+
+    /// Get the private key shares computed during DKG, keyed by key ID
+    fn get_private_keys(&self) -> HashMap<u32, Scalar>;
+
     /// Get all poly commitments for this signer
     fn get_poly_commitments<RNG: RngCore + CryptoRng>(&self, rng: &mut RNG) -> Vec<PolyCommitment>;
 
     /// Reset all poly commitments for this signer
     fn reset_polys<RNG: RngCore + CryptoRng>(&mut self, rng: &mut RNG);
 
+    /// Replace all polynomials with random ones of degree `threshold - 1` which reshare this
+    /// signer's private keys, so a DKG round keeps the group key but changes the threshold
+    ///
+    /// The keys are Lagrange weighted over `key_ids`, the keys which the group key is
+    /// interpolated from: every key for a threshold change, or every key but a lost signer's when
+    /// recovering it.  Keys which aren't in `key_ids` aren't reshared, so a signer holding none of
+    /// them deals polynomials with a zero constant term.
+    fn reshare_polys<RNG: RngCore + CryptoRng>(
+        &mut self,
+        threshold: u32,
+        key_ids: &[u32],
+        rng: &mut RNG,
+    );
+
     /// Get all private shares for this signer
     fn get_shares(&self) -> HashMap<u32, HashMap<u32, Scalar>>;
 
@@ -42,11 +77,29 @@ pub trait Signer {
     fn compute_secrets(
         &mut self,
         shares: &HashMap<u32, HashMap<u32, Scalar>>,
-        polys: &[PolyCommitment],
+        polys: &[&PolyCommitment],
     ) -> Result<(), HashMap<u32, DkgError>>;
 
     /// Generate all nonces for this signer
-    fn gen_nonces<RNG: RngCore + CryptoRng>(&mut self, rng: &mut RNG) -> Vec<PublicNonce>;
+    fn gen_nonces<RNG: RngCore + CryptoRng>(&mut self, rng: &mut RNG) -> Vec<PublicNonce> {
+        self.gen_nonces_with_context(&[], &[], rng)
+    }
+
+    /// Generate all nonces for this signer as synthetic nonces, which mix the private keys,
+    /// `aux_rand` from a second source of randomness, and the round `context` in with `rng`'s
+    /// output, so a weak or backdoored `rng` alone doesn't leak the keys
+    fn gen_nonces_with_context<RNG: RngCore + CryptoRng>(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        rng: &mut RNG,
+    ) -> Vec<PublicNonce>;
+
+    /// Get the private nonces which will be used for the next signature
+    fn get_nonces(&self) -> Vec<Nonce>;
+
+    /// Replace the private nonces which will be used for the next signature
+    fn set_nonces(&mut self, nonces: &[Nonce]);
 
     /// Compute intermediate values
     fn compute_intermediate(
@@ -72,7 +125,28 @@ pub trait Signer {
         signer_ids: &[u32],
         key_ids: &[u32],
         nonces: &[PublicNonce],
-        merkle_root: Option<MerkleRoot>,
+        tweak: TaprootTweak,
+    ) -> Vec<SignatureShare>;
+
+    /// Sign the blinded `challenge` from a `blind::Blinding` using all this signer's keys,
+    /// without learning the message which is being signed
+    fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare>;
+
+    /// Sign `msg` using all this signer's keys, under the group public key tweaked by the
+    /// non-zero scalar `tweak`, e.g. to sign for a derived child key
+    fn sign_with_tweak(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: &Scalar,
     ) -> Vec<SignatureShare>;
 }
 
@@ -84,6 +158,17 @@ pub trait Aggregator {
     /// Initialize an Aggregator with the passed polynomial commitments
     fn init(&mut self, poly_comms: Vec<PolyCommitment>) -> Result<(), AggregatorError>;
 
+    /// Construct an Aggregator from the group public key and the public key of each of the
+    /// `num_keys` key IDs, so it can verify and aggregate signatures without the DKG commitments
+    fn from_public_keys(
+        num_keys: u32,
+        threshold: u32,
+        group_key: Point,
+        public_keys: HashMap<u32, Point>,
+    ) -> Result<Self, AggregatorError>
+    where
+        Self: Sized;
+
     /// Check and aggregate the signature shares into a `Signature`
     fn sign(
         &mut self,
@@ -100,6 +185,572 @@ pub trait Aggregator {
         nonces: &[PublicNonce],
         sig_shares: &[SignatureShare],
         key_ids: &[u32],
-        merkle_root: Option<MerkleRoot>,
+        tweak: TaprootTweak,
+    ) -> Result<SchnorrProof, AggregatorError>;
+
+    /// Check the blind signature shares and aggregate them into the blinded response, which the
+    /// requester unblinds using `blind::Blinding::unblind`
+    fn sign_blinded(
+        &mut self,
+        challenge: &Scalar,
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+    ) -> Result<Scalar, AggregatorError>;
+
+    /// The IDs of the parties which sign for `signer_id`'s `key_ids`, one for each of its nonces
+    fn party_ids(signer_id: u32, key_ids: &[u32]) -> Vec<u32>;
+
+    /// Start aggregating the signature shares for `msg` one at a time as they arrive, from the
+    /// parties `party_ids` which sent `nonces`, for the group key tweaked by `tweak` (zero for
+    /// no tweak)
+    fn start_sign(
+        &mut self,
+        msg: &[u8],
+        party_ids: &[u32],
+        nonces: &[PublicNonce],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<(), AggregatorError>;
+
+    /// Check a single signature share for the signature in progress, and keep it if it's valid
+    fn add_share(&mut self, share: SignatureShare) -> Result<(), AggregatorError>;
+
+    /// Aggregate the shares into the signature in progress once every party has sent a valid one,
+    /// or return None if some are still missing; a tweaked signature is BIP-340 style, i.e. it can
+    /// be turned into a `SchnorrProof`
+    fn try_finalize(&mut self) -> Result<Option<Signature>, AggregatorError>;
+
+    /// Get the BIP341 x-only taproot output key of the group public key for `tweak`, or
+    /// None if the aggregator hasn't been initialized
+    fn taproot_output_key(&self, tweak: TaprootTweak) -> Option<field::Element>;
+
+    /// Check and aggregate the signature shares into a `SchnorrProof` for the group public key
+    /// tweaked by the non-zero scalar `tweak`
+    fn sign_with_tweak(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: &Scalar,
     ) -> Result<SchnorrProof, AggregatorError>;
 }
+
+/// The hash which makes the schnorr challenge from the (tweaked) group key, aggregate nonce, and
+/// message.  Signers and aggregators take it as a type parameter, which defaults to BIP-340, so
+/// signatures can be made to verify on chains which hash the challenge some other way, e.g. with
+/// keccak256 for an EVM precompile.  The signers and aggregator of a group must use the same hash.
+#[allow(non_snake_case)]
+pub trait ChallengeHash: Clone + Debug + PartialEq + Eq + Send + Sync + 'static {
+    /// Compute the schnorr challenge for `msg` signed by `public_key` with nonce `R`
+    fn challenge(public_key: &Point, R: &Point, msg: &[u8]) -> Scalar;
+}
+
+/// An object safe version of `Signer`, so the WSTS version can be chosen at runtime and the signer
+/// stored as a `Box<dyn DynSigner>`.  Every `Signer` implements it.
+pub trait DynSigner {
+    /// Get the signer ID for this signer
+    fn get_id(&self) -> u32;
+
+    /// Get all key IDs for this signer
+    fn get_key_ids(&self) -> Vec<u32>;
+
+    /// Get the total number of parties
+    fn get_num_parties(&self) -> u32;
+
+    /// Get the DKG party IDs which the signer `signer_id` holding `key_ids` makes commitments for
+    fn party_ids(&self, signer_id: u32, key_ids: &[u32]) -> Vec<u32>;
+
+    /// Get the aggregate group public key computed during DKG
+    fn get_group_key(&self) -> Point;
+
+    /// Get the private key shares computed during DKG, keyed by key ID
+    fn get_private_keys(&self) -> HashMap<u32, Scalar>;
+
+    /// Get all poly commitments for this signer
+    fn get_poly_commitments(&self, rng: &mut dyn CryptoRngCore) -> Vec<PolyCommitment>;
+
+    /// Reset all poly commitments for this signer
+    fn reset_polys(&mut self, rng: &mut dyn CryptoRngCore);
+
+    /// Replace all polynomials with ones of degree `threshold - 1` which reshare the private keys,
+    /// Lagrange weighted over `key_ids`
+    fn reshare_polys(&mut self, threshold: u32, key_ids: &[u32], rng: &mut dyn CryptoRngCore);
+
+    /// Get all private shares for this signer
+    fn get_shares(&self) -> HashMap<u32, HashMap<u32, Scalar>>;
+
+    /// Compute all secrets for this signer
+    fn compute_secrets(
+        &mut self,
+        shares: &HashMap<u32, HashMap<u32, Scalar>>,
+        polys: &[&PolyCommitment],
+    ) -> Result<(), HashMap<u32, DkgError>>;
+
+    /// Generate all nonces for this signer
+    fn gen_nonces(&mut self, rng: &mut dyn CryptoRngCore) -> Vec<PublicNonce>;
+
+    /// Generate all nonces for this signer as synthetic nonces, which mix the private keys,
+    /// `aux_rand` and the round `context` in with `rng`'s output
+    fn gen_nonces_with_context(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        rng: &mut dyn CryptoRngCore,
+    ) -> Vec<PublicNonce>;
+
+    /// Get the private nonces which will be used for the next signature
+    fn get_nonces(&self) -> Vec<Nonce>;
+
+    /// Replace the private nonces which will be used for the next signature
+    fn set_nonces(&mut self, nonces: &[Nonce]);
+
+    /// Compute intermediate values
+    fn compute_intermediate(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> (Vec<Point>, Point);
+
+    /// Sign `msg` using all this signer's keys
+    fn sign(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare>;
+
+    /// Sign `msg` using all this signer's keys and a tweaked public key
+    fn sign_taproot(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: TaprootTweak,
+    ) -> Vec<SignatureShare>;
+
+    /// Sign the blinded `challenge` using all this signer's keys
+    fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare>;
+
+    /// Sign `msg` using all this signer's keys, under the group public key tweaked by `tweak`
+    fn sign_with_tweak(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: &Scalar,
+    ) -> Vec<SignatureShare>;
+}
+
+impl<T: Signer> DynSigner for T {
+    fn get_id(&self) -> u32 {
+        Signer::get_id(self)
+    }
+
+    fn get_key_ids(&self) -> Vec<u32> {
+        Signer::get_key_ids(self)
+    }
+
+    fn get_num_parties(&self) -> u32 {
+        Signer::get_num_parties(self)
+    }
+
+    fn party_ids(&self, signer_id: u32, key_ids: &[u32]) -> Vec<u32> {
+        T::party_ids(signer_id, key_ids)
+    }
+
+    fn get_group_key(&self) -> Point {
+        Signer::get_group_key(self)
+    }
+
+    fn get_private_keys(&self) -> HashMap<u32, Scalar> {
+        Signer::get_private_keys(self)
+    }
+
+    fn get_poly_commitments(&self, mut rng: &mut dyn CryptoRngCore) -> Vec<PolyCommitment> {
+        Signer::get_poly_commitments(self, &mut rng)
+    }
+
+    fn reset_polys(&mut self, mut rng: &mut dyn CryptoRngCore) {
+        Signer::reset_polys(self, &mut rng)
+    }
+
+    fn reshare_polys(&mut self, threshold: u32, key_ids: &[u32], mut rng: &mut dyn CryptoRngCore) {
+        Signer::reshare_polys(self, threshold, key_ids, &mut rng)
+    }
+
+    fn get_shares(&self) -> HashMap<u32, HashMap<u32, Scalar>> {
+        Signer::get_shares(self)
+    }
+
+    fn compute_secrets(
+        &mut self,
+        shares: &HashMap<u32, HashMap<u32, Scalar>>,
+        polys: &[&PolyCommitment],
+    ) -> Result<(), HashMap<u32, DkgError>> {
+        Signer::compute_secrets(self, shares, polys)
+    }
+
+    fn gen_nonces(&mut self, mut rng: &mut dyn CryptoRngCore) -> Vec<PublicNonce> {
+        Signer::gen_nonces(self, &mut rng)
+    }
+
+    fn gen_nonces_with_context(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        mut rng: &mut dyn CryptoRngCore,
+    ) -> Vec<PublicNonce> {
+        Signer::gen_nonces_with_context(self, aux_rand, context, &mut rng)
+    }
+
+    fn get_nonces(&self) -> Vec<Nonce> {
+        Signer::get_nonces(self)
+    }
+
+    fn set_nonces(&mut self, nonces: &[Nonce]) {
+        Signer::set_nonces(self, nonces)
+    }
+
+    fn compute_intermediate(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> (Vec<Point>, Point) {
+        T::compute_intermediate(msg, signer_ids, key_ids, nonces)
+    }
+
+    fn sign(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        Signer::sign(self, msg, signer_ids, key_ids, nonces)
+    }
+
+    fn sign_taproot(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: TaprootTweak,
+    ) -> Vec<SignatureShare> {
+        Signer::sign_taproot(self, msg, signer_ids, key_ids, nonces, tweak)
+    }
+
+    fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        Signer::sign_blinded(self, challenge, signer_ids, key_ids, nonces)
+    }
+
+    fn sign_with_tweak(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: &Scalar,
+    ) -> Vec<SignatureShare> {
+        Signer::sign_with_tweak(self, msg, signer_ids, key_ids, nonces, tweak)
+    }
+}
+
+/// An object safe version of `Aggregator`, so the WSTS version can be chosen at runtime and the
+/// aggregator stored as a `Box<dyn DynAggregator>`.  Every `Aggregator` implements it.
+pub trait DynAggregator {
+    /// Initialize an Aggregator with the passed polynomial commitments
+    fn init(&mut self, poly_comms: Vec<PolyCommitment>) -> Result<(), AggregatorError>;
+
+    /// Check and aggregate the signature shares into a `Signature`
+    fn sign(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+    ) -> Result<Signature, AggregatorError>;
+
+    /// Check and aggregate the signature shares into a `SchnorrProof`
+    fn sign_taproot(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: TaprootTweak,
+    ) -> Result<SchnorrProof, AggregatorError>;
+
+    /// Check the blind signature shares and aggregate them into the blinded response
+    fn sign_blinded(
+        &mut self,
+        challenge: &Scalar,
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+    ) -> Result<Scalar, AggregatorError>;
+
+    /// The IDs of the parties which sign for `signer_id`'s `key_ids`, one for each of its nonces
+    fn party_ids(&self, signer_id: u32, key_ids: &[u32]) -> Vec<u32>;
+
+    /// Start aggregating the signature shares for `msg` one at a time as they arrive
+    fn start_sign(
+        &mut self,
+        msg: &[u8],
+        party_ids: &[u32],
+        nonces: &[PublicNonce],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<(), AggregatorError>;
+
+    /// Check a single signature share for the signature in progress, and keep it if it's valid
+    fn add_share(&mut self, share: SignatureShare) -> Result<(), AggregatorError>;
+
+    /// Aggregate the shares into the signature in progress once every party has sent a valid one
+    fn try_finalize(&mut self) -> Result<Option<Signature>, AggregatorError>;
+
+    /// Get the BIP341 x-only taproot output key of the group public key for `tweak`
+    fn taproot_output_key(&self, tweak: TaprootTweak) -> Option<field::Element>;
+
+    /// Check and aggregate the signature shares into a `SchnorrProof` for the group public key
+    /// tweaked by `tweak`
+    fn sign_with_tweak(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<SchnorrProof, AggregatorError>;
+}
+
+impl<T: Aggregator> DynAggregator for T {
+    fn init(&mut self, poly_comms: Vec<PolyCommitment>) -> Result<(), AggregatorError> {
+        Aggregator::init(self, poly_comms)
+    }
+
+    fn sign(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+    ) -> Result<Signature, AggregatorError> {
+        Aggregator::sign(self, msg, nonces, sig_shares, key_ids)
+    }
+
+    fn sign_taproot(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: TaprootTweak,
+    ) -> Result<SchnorrProof, AggregatorError> {
+        Aggregator::sign_taproot(self, msg, nonces, sig_shares, key_ids, tweak)
+    }
+
+    fn sign_blinded(
+        &mut self,
+        challenge: &Scalar,
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+    ) -> Result<Scalar, AggregatorError> {
+        Aggregator::sign_blinded(self, challenge, nonces, sig_shares, key_ids)
+    }
+
+    fn party_ids(&self, signer_id: u32, key_ids: &[u32]) -> Vec<u32> {
+        T::party_ids(signer_id, key_ids)
+    }
+
+    fn start_sign(
+        &mut self,
+        msg: &[u8],
+        party_ids: &[u32],
+        nonces: &[PublicNonce],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<(), AggregatorError> {
+        Aggregator::start_sign(self, msg, party_ids, nonces, key_ids, tweak)
+    }
+
+    fn add_share(&mut self, share: SignatureShare) -> Result<(), AggregatorError> {
+        Aggregator::add_share(self, share)
+    }
+
+    fn try_finalize(&mut self) -> Result<Option<Signature>, AggregatorError> {
+        Aggregator::try_finalize(self)
+    }
+
+    fn taproot_output_key(&self, tweak: TaprootTweak) -> Option<field::Element> {
+        Aggregator::taproot_output_key(self, tweak)
+    }
+
+    fn sign_with_tweak(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<SchnorrProof, AggregatorError> {
+        Aggregator::sign_with_tweak(self, msg, nonces, sig_shares, key_ids, tweak)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// The WSTS versions, for choosing one at runtime
+pub enum Version {
+    /// `v1`, where each key is a separate FROST party
+    V1,
+    /// `v2`, where each signer is a single party holding all its keys
+    V2,
+    /// `v3`, where each signer holds exactly one key
+    V3,
+}
+
+impl Version {
+    /// Create a new signer of this version
+    pub fn new_signer(
+        &self,
+        signer_id: u32,
+        key_ids: &[u32],
+        num_signers: u32,
+        num_keys: u32,
+        threshold: u32,
+        rng: &mut dyn CryptoRngCore,
+    ) -> Box<dyn DynSigner + Send> {
+        let mut rng = rng;
+        match self {
+            Version::V1 => Box::new(<v1::Signer as Signer>::new(
+                signer_id,
+                key_ids,
+                num_signers,
+                num_keys,
+                threshold,
+                &mut rng,
+            )),
+            Version::V2 => Box::new(<v2::Signer as Signer>::new(
+                signer_id,
+                key_ids,
+                num_signers,
+                num_keys,
+                threshold,
+                &mut rng,
+            )),
+            Version::V3 => Box::new(<v3::Signer as Signer>::new(
+                signer_id,
+                key_ids,
+                num_signers,
+                num_keys,
+                threshold,
+                &mut rng,
+            )),
+        }
+    }
+
+    /// Create a new aggregator of this version
+    pub fn new_aggregator(&self, num_keys: u32, threshold: u32) -> Box<dyn DynAggregator + Send> {
+        match self {
+            Version::V1 => Box::new(<v1::Aggregator as Aggregator>::new(num_keys, threshold)),
+            Version::V2 => Box::new(<v2::Aggregator as Aggregator>::new(num_keys, threshold)),
+            Version::V3 => Box::new(<v3::Aggregator as Aggregator>::new(num_keys, threshold)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use hashbrown::HashMap;
+    use rand_core::OsRng;
+
+    use super::{DynAggregator, DynSigner, Version};
+    use crate::common::{PolyCommitment, PublicNonce, SignatureShare};
+
+    #[test]
+    fn dyn_sign() {
+        let two_keys_each = vec![vec![0, 1], vec![2, 3], vec![4, 5]];
+        let one_key_each = (0..6).map(|key_id| vec![key_id]).collect();
+        for (version, key_ids) in [
+            (Version::V1, two_keys_each.clone()),
+            (Version::V2, two_keys_each),
+            (Version::V3, one_key_each),
+        ] {
+            let (num_keys, threshold) = (6, 4);
+            let num_signers = key_ids.len() as u32;
+            let mut signers: Vec<Box<dyn DynSigner + Send>> = key_ids
+                .iter()
+                .enumerate()
+                .map(|(signer_id, key_ids)| {
+                    version.new_signer(
+                        signer_id as u32,
+                        key_ids,
+                        num_signers,
+                        num_keys,
+                        threshold,
+                        &mut OsRng,
+                    )
+                })
+                .collect();
+
+            let polys: Vec<PolyCommitment> = signers
+                .iter()
+                .flat_map(|signer| signer.get_poly_commitments(&mut OsRng))
+                .collect();
+            let mut shares = HashMap::new();
+            for signer in &signers {
+                shares.extend(signer.get_shares());
+            }
+            let comms = polys.iter().collect::<Vec<&PolyCommitment>>();
+            for signer in signers.iter_mut() {
+                signer.compute_secrets(&shares, &comms).unwrap();
+            }
+
+            let mut aggregator: Box<dyn DynAggregator + Send> =
+                version.new_aggregator(num_keys, threshold);
+            aggregator.init(polys).unwrap();
+
+            let msg = b"chosen at runtime";
+            // sign with just enough signers to hold the threshold of keys
+            let signers = &mut signers[..key_ids.len() * 2 / 3];
+            let signer_ids: Vec<u32> = signers.iter().map(|signer| signer.get_id()).collect();
+            let key_ids: Vec<u32> = signers
+                .iter()
+                .flat_map(|signer| signer.get_key_ids())
+                .collect();
+            let nonces: Vec<PublicNonce> = signers
+                .iter_mut()
+                .flat_map(|signer| signer.gen_nonces(&mut OsRng))
+                .collect();
+            let shares: Vec<SignatureShare> = signers
+                .iter()
+                .flat_map(|signer| signer.sign(msg, &signer_ids, &key_ids, &nonces))
+                .collect();
+
+            let sig = aggregator.sign(msg, &nonces, &shares, &key_ids).unwrap();
+            assert!(sig.verify(&signers[0].get_group_key(), msg));
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use libp2p::{
+    futures::StreamExt,
+    gossipsub::{self, IdentTopic, MessageAuthenticity, ValidationMode},
+    identity::Keypair,
+    noise,
+    swarm::SwarmEvent,
+    tcp, yamux, Swarm, SwarmBuilder,
+};
+use std::time::Duration;
+use tokio::{runtime::Handle, sync::mpsc};
+use tracing::{debug, warn};
+
+use crate::{
+    net::{Message, Packet, MAX_FRAME_LEN},
+    transport::{Error, Transport},
+};
+
+/// How long a connection with no open streams is kept before it's closed
+pub const IDLE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The gossipsub topics which packets are published on.  Messages which start a round, i.e.
+/// `DkgBegin`, `ThresholdChangeBegin` and `NonceRequest`, go on a control topic which every
+/// participant subscribes to.  Every other message goes on a topic for its round, keyed by its
+/// `dkg_id` and `sign_id`, which participants subscribe to when they see the round start and
+/// leave when the next round of the same kind starts.
+#[derive(Clone, Debug)]
+pub struct Topics {
+    /// the prefix of every topic, which keeps separate groups apart
+    pub prefix: String,
+    dkg: Option<IdentTopic>,
+    sign: Option<IdentTopic>,
+}
+
+impl Topics {
+    /// Create topics under `prefix`
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            dkg: None,
+            sign: None,
+        }
+    }
+
+    /// The topic which round starting messages are published on
+    pub fn control(&self) -> IdentTopic {
+        IdentTopic::new(format!("{}/control", self.prefix))
+    }
+
+    /// The topic for the round which `msg` belongs to
+    pub fn round(&self, msg: &Message) -> IdentTopic {
+        let id = msg.id();
+        if id.sign_id == 0 && !matches!(msg, Message::NonceRequest(_)) {
+            IdentTopic::new(format!("{}/dkg/{}", self.prefix, id.dkg_id))
+        } else {
+            IdentTopic::new(format!("{}/sign/{}/{}", self.prefix, id.dkg_id, id.sign_id))
+        }
+    }
+
+    /// The topic which `msg` is published on
+    pub fn publish(&self, msg: &Message) -> IdentTopic {
+        if starts_round(msg) {
+            self.control()
+        } else {
+            self.round(msg)
+        }
+    }
+
+    /// Follow the round which `msg` starts, if it starts one.  Returns the topic to subscribe to
+    /// and the topic of the previous round of the same kind to leave.
+    pub fn enter(&mut self, msg: &Message) -> Option<(IdentTopic, Option<IdentTopic>)> {
+        if !starts_round(msg) {
+            return None;
+        }
+        let topic = self.round(msg);
+        let current = match msg {
+            Message::NonceRequest(_) => &mut self.sign,
+            _ => &mut self.dkg,
+        };
+        if current.as_ref().map(IdentTopic::hash) == Some(topic.hash()) {
+            return None;
+        }
+        Some((topic.clone(), current.replace(topic)))
+    }
+}
+
+fn starts_round(msg: &Message) -> bool {
+    matches!(
+        msg,
+        Message::DkgBegin(_) | Message::ThresholdChangeBegin(_) | Message::NonceRequest(_)
+    )
+}
+
+/// Build a gossipsub swarm which connects over TCP with noise and yamux, and accepts packets up
+/// to `net::MAX_FRAME_LEN`
+pub fn tcp_swarm(keypair: Keypair) -> Result<Swarm<gossipsub::Behaviour>, Error> {
+    let config = gossipsub::ConfigBuilder::default()
+        .validation_mode(ValidationMode::Strict)
+        .max_transmit_size(MAX_FRAME_LEN)
+        .build()
+        .map_err(|e| Error::Gossip(e.to_string()))?;
+    let behaviour = gossipsub::Behaviour::new(MessageAuthenticity::Signed(keypair.clone()), config)
+        .map_err(|e| Error::Gossip(e.to_string()))?;
+    Ok(SwarmBuilder::with_existing_identity(keypair)
+        .with_tokio()
+        .with_tcp(
+            tcp::Config::default(),
+            noise::Config::new,
+            yamux::Config::default,
+        )
+        .map_err(|e| Error::Gossip(e.to_string()))?
+        .with_behaviour(|_| behaviour)
+        .map_err(|e| Error::Gossip(e.to_string()))?
+        .with_swarm_config(|c| c.with_idle_connection_timeout(IDLE_CONNECTION_TIMEOUT))
+        .build())
+}
+
+/// A transport which publishes packets over libp2p gossipsub.  The swarm is driven by a task on
+/// a tokio runtime, so packets can be sent and polled from synchronous code.  Unlike a relayer,
+/// gossipsub doesn't deliver a participant's own packets back to it.
+pub struct GossipTransport {
+    outbound: mpsc::UnboundedSender<Packet>,
+    inbound: mpsc::UnboundedReceiver<Packet>,
+}
+
+impl GossipTransport {
+    /// Subscribe `swarm` to the control topic under `prefix`, then drive it on `runtime` until
+    /// the transport is dropped.  The swarm should already be listening or dialing its peers.
+    pub fn spawn(
+        mut swarm: Swarm<gossipsub::Behaviour>,
+        prefix: &str,
+        runtime: &Handle,
+    ) -> Result<Self, Error> {
+        let topics = Topics::new(prefix);
+        swarm
+            .behaviour_mut()
+            .subscribe(&topics.control())
+            .map_err(|e| Error::Gossip(e.to_string()))?;
+
+        let (outbound, outbound_rx) = mpsc::unbounded_channel();
+        let (inbound_tx, inbound) = mpsc::unbounded_channel();
+        runtime.spawn(drive(swarm, topics, outbound_rx, inbound_tx));
+        Ok(Self { outbound, inbound })
+    }
+}
+
+impl Transport for GossipTransport {
+    fn send(&mut self, packet: &Packet) -> Result<(), Error> {
+        self.outbound
+            .send(packet.clone())
+            .map_err(|_| Error::Closed)
+    }
+
+    fn poll(&mut self) -> Result<Vec<Packet>, Error> {
+        let mut packets = Vec::new();
+        loop {
+            match self.inbound.try_recv() {
+                Ok(packet) => packets.push(packet),
+                Err(mpsc::error::TryRecvError::Empty) => return Ok(packets),
+                Err(mpsc::error::TryRecvError::Disconnected) => return Err(Error::Closed),
+            }
+        }
+    }
+}
+
+/// Move to the round which `msg` starts, if it starts one
+fn enter(swarm: &mut Swarm<gossipsub::Behaviour>, topics: &mut Topics, msg: &Message) {
+    let Some((topic, previous)) = topics.enter(msg) else {
+        return;
+    };
+    if let Some(previous) = previous {
+        if let Err(e) = swarm.behaviour_mut().unsubscribe(&previous) {
+            warn!("Failed to unsubscribe from {previous}: {e}");
+        }
+    }
+    if let Err(e) = swarm.behaviour_mut().subscribe(&topic) {
+        warn!("Failed to subscribe to {topic}: {e}");
+    }
+}
+
+async fn drive(
+    mut swarm: Swarm<gossipsub::Behaviour>,
+    mut topics: Topics,
+    mut outbound: mpsc::UnboundedReceiver<Packet>,
+    inbound: mpsc::UnboundedSender<Packet>,
+) {
+    loop {
+        tokio::select! {
+            packet = outbound.recv() => {
+                let Some(packet) = packet else {
+                    return;
+                };
+                enter(&mut swarm, &mut topics, &packet.msg);
+                let topic = topics.publish(&packet.msg);
+                let result = packet
+                    .encode(false)
+                    .map_err(|e| e.to_string())
+                    .and_then(|bytes| {
+                        swarm
+                            .behaviour_mut()
+                            .publish(topic.clone(), bytes)
+                            .map_err(|e| e.to_string())
+                    });
+                if let Err(e) = result {
+                    warn!("Failed to publish to {topic}: {e}");
+                }
+            }
+            event = swarm.select_next_some() => {
+                let SwarmEvent::Behaviour(gossipsub::Event::Message { message, .. }) = event else {
+                    continue;
+                };
+                let packet = match Packet::decode(&message.data) {
+                    Ok(packet) => packet,
+                    Err(e) => {
+                        warn!("Failed to decode packet from {}: {e}", message.topic);
+                        continue;
+                    }
+                };
+                debug!(topic = %message.topic, "Received packet");
+                enter(&mut swarm, &mut topics, &packet.msg);
+                if inbound.send(packet).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use libp2p::{futures::StreamExt, identity::Keypair, swarm::SwarmEvent, Multiaddr};
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
+    use tokio::runtime::Runtime;
+
+    use super::{tcp_swarm, GossipTransport, Topics};
+    use crate::{
+        net::{DkgBegin, DkgEnd, DkgStatus, Message, NonceRequest, Packet},
+        transport::Transport,
+    };
+
+    fn packet(msg: Message) -> Packet {
+        Packet {
+            msg,
+            seq: 0,
+            sig: vec![],
+        }
+    }
+
+    #[test]
+    fn topics() {
+        let mut topics = Topics::new("wsts");
+        let dkg_begin = Message::DkgBegin(DkgBegin {
+            dkg_id: 1,
+            epoch: 0,
+            threshold: 0,
+            num_signers: 0,
+            num_keys: 0,
+            pedersen: false,
+        });
+        let dkg_end = Message::DkgEnd(DkgEnd {
+            dkg_id: 1,
+            signer_id: 0,
+            status: DkgStatus::Success,
+        });
+        let nonce_request = Message::NonceRequest(NonceRequest {
+            dkg_id: 1,
+            sign_id: 0,
+            sign_iter_id: 0,
+            signer_ids: None,
+            commit: false,
+        });
+
+        assert_eq!(topics.publish(&dkg_begin).to_string(), "wsts/control");
+        assert_eq!(topics.publish(&dkg_end).to_string(), "wsts/dkg/1");
+        assert_eq!(topics.publish(&nonce_request).to_string(), "wsts/control");
+        assert_eq!(topics.round(&nonce_request).to_string(), "wsts/sign/1/0");
+
+        let (topic, previous) = topics.enter(&dkg_begin).unwrap();
+        assert_eq!(topic.to_string(), "wsts/dkg/1");
+        assert!(previous.is_none());
+        assert!(topics.enter(&dkg_begin).is_none());
+        assert!(topics.enter(&dkg_end).is_none());
+
+        // a signing round doesn't leave the DKG round, but the next DKG round does
+        assert!(topics.enter(&nonce_request).unwrap().1.is_none());
+        let (topic, previous) = topics
+            .enter(&Message::DkgBegin(DkgBegin {
+                dkg_id: 2,
+                epoch: 0,
+                threshold: 0,
+                num_signers: 0,
+                num_keys: 0,
+                pedersen: false,
+            }))
+            .unwrap();
+        assert_eq!(topic.to_string(), "wsts/dkg/2");
+        assert_eq!(previous.unwrap().to_string(), "wsts/dkg/1");
+    }
+
+    /// Send `packet` from `sender` until `receiver` gets a packet
+    fn deliver(
+        sender: &mut GossipTransport,
+        receiver: &mut GossipTransport,
+        packet: &Packet,
+    ) -> Packet {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(30) {
+            sender.send(packet).unwrap();
+            thread::sleep(Duration::from_millis(100));
+            if let Some(received) = receiver.poll().unwrap().pop() {
+                return received;
+            }
+        }
+        panic!("packet was never delivered");
+    }
+
+    #[test]
+    fn send_and_poll() {
+        let runtime = Runtime::new().unwrap();
+        // the swarms spawn their connection tasks on the runtime
+        let _guard = runtime.enter();
+
+        let mut alice = tcp_swarm(Keypair::generate_ed25519()).unwrap();
+        alice
+            .listen_on("/ip4/127.0.0.1/tcp/0".parse().unwrap())
+            .unwrap();
+        let address: Multiaddr = runtime.block_on(async {
+            loop {
+                if let SwarmEvent::NewListenAddr { address, .. } = alice.select_next_some().await {
+                    return address;
+                }
+            }
+        });
+        let mut bob = tcp_swarm(Keypair::generate_ed25519()).unwrap();
+        bob.dial(address).unwrap();
+
+        let mut alice = GossipTransport::spawn(alice, "wsts", runtime.handle()).unwrap();
+        let mut bob = GossipTransport::spawn(bob, "wsts", runtime.handle()).unwrap();
+
+        // the round is started on the control topic, and answered on the round's own topic
+        let received = deliver(
+            &mut alice,
+            &mut bob,
+            &packet(Message::DkgBegin(DkgBegin {
+                dkg_id: 1,
+                epoch: 0,
+                threshold: 0,
+                num_signers: 0,
+                num_keys: 0,
+                pedersen: false,
+            })),
+        );
+        assert!(matches!(
+            received.msg,
+            Message::DkgBegin(DkgBegin {
+                dkg_id: 1,
+                epoch: 0,
+                threshold: 0,
+                num_signers: 0,
+                num_keys: 0,
+                pedersen: false,
+            })
+        ));
+
+        let received = deliver(
+            &mut bob,
+            &mut alice,
+            &packet(Message::DkgEnd(DkgEnd {
+                dkg_id: 1,
+                signer_id: 0,
+                status: DkgStatus::Success,
+            })),
+        );
+        assert!(matches!(
+            received.msg,
+            Message::DkgEnd(DkgEnd { dkg_id: 1, .. })
+        ));
+
+        // gossipsub doesn't deliver a participant's own packets
+        assert!(alice
+            .poll()
+            .unwrap()
+            .iter()
+            .all(|packet| matches!(packet.msg, Message::DkgEnd(_))));
+    }
+}
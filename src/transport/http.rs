@@ -0,0 +1,285 @@
+use std::{io::Read, time::Duration};
+use tracing::{debug, warn};
+
+use crate::{
+    net::Packet,
+    transport::{Error, Transport},
+};
+
+/// How long a request to the relayer may take before it fails
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A transport which relays JSON encoded packets through an HTTP relayer.  Packets are sent by
+/// posting them to the relayer's URL, which appends them to a single log, and received by getting
+/// `?id=N` for each index in the log in turn, until the relayer has nothing at the next index.
+pub struct HttpRelay {
+    /// the URL of the relayer
+    pub url: String,
+    /// the index in the relayer's log of the next packet to receive
+    pub next_id: u64,
+    agent: ureq::Agent,
+}
+
+impl HttpRelay {
+    /// Create a transport which relays through `url`, starting at the beginning of its log
+    pub fn new(url: &str) -> Self {
+        Self::with_timeout(url, DEFAULT_TIMEOUT)
+    }
+
+    /// Create a transport whose requests to the relayer fail after `timeout`
+    pub fn with_timeout(url: &str, timeout: Duration) -> Self {
+        Self {
+            url: url.to_string(),
+            next_id: 0,
+            agent: ureq::AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+
+    /// Get the body of the entry at `id` in the relayer's log, or None if there isn't one yet
+    fn get(&self, id: u64) -> Result<Option<Vec<u8>>, Error> {
+        let response = match self
+            .agent
+            .get(&self.url)
+            .query("id", &id.to_string())
+            .call()
+        {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(ureq::Error::Status(status, _)) => return Err(Error::Status(status)),
+            Err(e) => return Err(Error::Http(e.to_string())),
+        };
+
+        let mut body = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut body)
+            .map_err(|e| Error::Http(e.to_string()))?;
+        if body.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(body))
+    }
+}
+
+impl Transport for HttpRelay {
+    fn send(&mut self, packet: &Packet) -> Result<(), Error> {
+        let body = serde_json::to_vec(packet).map_err(|e| Error::Serialization(e.to_string()))?;
+        match self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_bytes(&body)
+        {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, _)) => Err(Error::Status(status)),
+            Err(e) => Err(Error::Http(e.to_string())),
+        }
+    }
+
+    fn poll(&mut self) -> Result<Vec<Packet>, Error> {
+        let mut packets = Vec::new();
+        loop {
+            let body = match self.get(self.next_id) {
+                Ok(Some(body)) => body,
+                Ok(None) => break,
+                Err(e) if packets.is_empty() => return Err(e),
+                // keep what was received, and retry the failed entry on the next poll
+                Err(e) => {
+                    warn!(id = self.next_id, "Failed to get packet from relayer: {e}");
+                    break;
+                }
+            };
+            // an entry which can't be decoded never will be, so skip it rather than stall on it
+            match serde_json::from_slice(&body) {
+                Ok(packet) => {
+                    debug!(id = self.next_id, "Received packet from relayer");
+                    packets.push(packet);
+                }
+                Err(e) => warn!(id = self.next_id, "Skipping undecodable packet: {e}"),
+            }
+            self.next_id += 1;
+        }
+        Ok(packets)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use super::HttpRelay;
+    use crate::{
+        net::{DkgBegin, Message, NonceRequest, Packet},
+        transport::{Error, Transport},
+    };
+
+    const UNAVAILABLE: &[u8] = b"unavailable";
+
+    /// Run a relayer which keeps its log in memory, serving one request per connection
+    fn relayer() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let log = Arc::new(Mutex::new(Vec::<Vec<u8>>::new()));
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = header.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut log = log.lock().unwrap();
+                let (status, body) = if request_line.starts_with("POST") {
+                    log.push(body);
+                    ("200 OK", Vec::new())
+                } else if request_line.contains("/bad") {
+                    ("500 Internal Server Error", Vec::new())
+                } else {
+                    let id: usize = request_line
+                        .split("id=")
+                        .nth(1)
+                        .and_then(|rest| rest.split(' ').next())
+                        .unwrap()
+                        .parse()
+                        .unwrap();
+                    match log.get(id) {
+                        // an entry the relayer fails to serve
+                        Some(body) if body == UNAVAILABLE => {
+                            ("503 Service Unavailable", Vec::new())
+                        }
+                        body => ("200 OK", body.cloned().unwrap_or_default()),
+                    }
+                };
+                write!(
+                    stream,
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .unwrap();
+                stream.write_all(&body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn send_and_poll() {
+        let url = relayer();
+        let mut alice = HttpRelay::new(&url);
+        let mut bob = HttpRelay::new(&url);
+        assert!(bob.poll().unwrap().is_empty());
+
+        let packets = [
+            Packet {
+                msg: Message::DkgBegin(DkgBegin {
+                    dkg_id: 1,
+                    epoch: 0,
+                    threshold: 0,
+                    num_signers: 0,
+                    num_keys: 0,
+                    pedersen: false,
+                }),
+                seq: 0,
+                sig: vec![1, 2, 3],
+            },
+            Packet {
+                msg: Message::NonceRequest(NonceRequest {
+                    dkg_id: 1,
+                    sign_id: 2,
+                    sign_iter_id: 3,
+                    signer_ids: None,
+                    commit: false,
+                }),
+                seq: 1,
+                sig: vec![],
+            },
+        ];
+        for packet in &packets {
+            alice.send(packet).unwrap();
+        }
+
+        let received = bob.poll().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(bob.next_id, 2);
+        for (received, sent) in received.iter().zip(&packets) {
+            assert_eq!(
+                serde_json::to_vec(received).unwrap(),
+                serde_json::to_vec(sent).unwrap()
+            );
+        }
+        assert!(bob.poll().unwrap().is_empty());
+
+        // the sender sees its own packets in the relayer's log too
+        assert_eq!(alice.poll().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn relayer_errors() {
+        let mut relay = HttpRelay::new(&format!("{}bad", relayer()));
+        assert!(matches!(relay.poll(), Err(Error::Status(500))));
+
+        let mut relay = HttpRelay::new("http://127.0.0.1:1/");
+        assert!(matches!(relay.poll(), Err(Error::Http(_))));
+    }
+
+    #[test]
+    fn bad_entries() {
+        let url = relayer();
+        let mut relay = HttpRelay::new(&url);
+        let packet = |seq| Packet {
+            msg: Message::DkgBegin(DkgBegin {
+                dkg_id: 1,
+                epoch: 0,
+                threshold: 0,
+                num_signers: 0,
+                num_keys: 0,
+                pedersen: false,
+            }),
+            seq,
+            sig: vec![],
+        };
+        let post = |body: &[u8]| {
+            ureq::post(&url).send_bytes(body).unwrap();
+        };
+
+        // an undecodable entry in the middle of the log is skipped
+        relay.send(&packet(0)).unwrap();
+        post(b"not a packet");
+        relay.send(&packet(1)).unwrap();
+        let received = relay.poll().unwrap();
+        assert_eq!(
+            received.iter().map(|p| p.seq).collect::<Vec<_>>(),
+            vec![0, 1]
+        );
+        assert_eq!(relay.next_id, 3);
+
+        // an entry which can't be fetched keeps the packets before it, and is retried
+        relay.send(&packet(2)).unwrap();
+        post(UNAVAILABLE);
+        relay.send(&packet(3)).unwrap();
+        let received = relay.poll().unwrap();
+        assert_eq!(received.iter().map(|p| p.seq).collect::<Vec<_>>(), vec![2]);
+        assert_eq!(relay.next_id, 4);
+        assert!(matches!(relay.poll(), Err(Error::Status(503))));
+        assert_eq!(relay.next_id, 4);
+    }
+}
@@ -0,0 +1,38 @@
+use crate::net::Packet;
+
+/// Publish and subscribe to packets over libp2p gossipsub, without a central relay
+#[cfg(feature = "gossipsub")]
+pub mod gossip;
+/// Post and poll packets through an HTTP relayer, as stacks-signer nodes do
+#[cfg(feature = "http-relay")]
+pub mod http;
+
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+/// Errors from sending or receiving packets
+pub enum Error {
+    #[error("serialization error {0}")]
+    /// A packet could not be serialized or deserialized
+    Serialization(String),
+    #[error("HTTP error {0}")]
+    /// A request to the relayer failed
+    Http(String),
+    #[error("relayer returned status {0}")]
+    /// The relayer answered a request with an unexpected status code
+    Status(u16),
+    #[error("gossip error {0}")]
+    /// The gossip network could not be set up
+    Gossip(String),
+    #[error("transport is closed")]
+    /// The task which drives the network has stopped
+    Closed,
+}
+
+/// Moves packets between the participants in the protocol, so a state machine can be driven the
+/// same way whatever network it runs over
+pub trait Transport {
+    /// Send a packet to every other participant
+    fn send(&mut self, packet: &Packet) -> Result<(), Error>;
+
+    /// Receive the packets which have arrived since the last poll, in the order they arrived
+    fn poll(&mut self) -> Result<Vec<Packet>, Error>;
+}
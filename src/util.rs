@@ -1,11 +1,22 @@
 use aes_gcm::{aead::Aead, Aes256Gcm, Error as AesGcmError, KeyInit, Nonce};
+use hkdf::Hkdf;
 use p256k1::{point::Point, scalar::Scalar};
+use pbkdf2::pbkdf2_hmac;
 use rand_core::{CryptoRng, RngCore};
 use sha2::{Digest, Sha256};
 
 /// Size of the AES-GCM nonce
 pub const AES_GCM_NONCE_SIZE: usize = 12;
 
+/// Number of PBKDF2-HMAC-SHA256 iterations used to derive a key from a passphrase
+pub const PASSPHRASE_KDF_ITERATIONS: u32 = 600_000;
+
+/// Size of the random salt used to derive a key from a passphrase
+pub const PASSPHRASE_SALT_SIZE: usize = 16;
+
+/// HKDF salt used to derive the keys which encrypt DKG private shares
+pub const SHARE_KEY_SALT: &str = "WSTS/DKG_PRIVATE_SHARE_KEY";
+
 #[allow(dead_code)]
 /// Digest the hasher to a Scalar
 pub fn hash_to_scalar(hasher: &mut Sha256) -> Scalar {
@@ -20,7 +31,7 @@ pub fn hash_to_scalar(hasher: &mut Sha256) -> Scalar {
 /// Do a Diffie-Hellman key exchange to create a shared secret from the passed private and public keys
 pub fn make_shared_secret(private_key: &Scalar, public_key: &Point) -> [u8; 32] {
     let mut hasher = Sha256::new();
-    let shared_key = private_key * public_key;
+    let shared_key = crate::ct::mul(private_key, public_key);
 
     hasher.update("DH_SHARED_SECRET_KEY/".as_bytes());
     hasher.update(shared_key.compress().as_bytes());
@@ -32,6 +43,28 @@ pub fn make_shared_secret(private_key: &Scalar, public_key: &Point) -> [u8; 32]
     bytes
 }
 
+/// Derive the key which encrypts the DKG private shares sent from signer `signer_id` to signer
+/// `dst_signer_id` in DKG round `dkg_id`, from the ECDH `shared_secret` of the two signers.
+/// Binding the round and both ends into the HKDF info means no two ciphertexts, in either
+/// direction or in different rounds, are encrypted under the same key.
+pub fn make_share_key(
+    shared_secret: &[u8; 32],
+    dkg_id: u64,
+    signer_id: u32,
+    dst_signer_id: u32,
+) -> [u8; 32] {
+    let mut info = Vec::with_capacity(16);
+    info.extend_from_slice(&dkg_id.to_be_bytes());
+    info.extend_from_slice(&signer_id.to_be_bytes());
+    info.extend_from_slice(&dst_signer_id.to_be_bytes());
+
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(SHARE_KEY_SALT.as_bytes()), shared_secret)
+        .expand(&info, &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
 /// Encrypt the passed data using the key
 pub fn encrypt<RNG: RngCore + CryptoRng>(
     key: &[u8; 32],
@@ -64,6 +97,50 @@ pub fn decrypt(key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>, AesGcmError> {
     cipher.decrypt(nonce, cipher_vec.as_ref())
 }
 
+/// Derive an encryption key from the passed passphrase and salt
+fn passphrase_key(passphrase: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(passphrase, salt, iterations, &mut key);
+    key
+}
+
+/// Encrypt the passed data using a key derived from the passphrase; the KDF iterations and salt
+/// are prepended so the key can be derived again when decrypting
+pub fn encrypt_with_passphrase<RNG: RngCore + CryptoRng>(
+    passphrase: &[u8],
+    data: &[u8],
+    iterations: u32,
+    rng: &mut RNG,
+) -> Result<Vec<u8>, AesGcmError> {
+    let mut salt = [0u8; PASSPHRASE_SALT_SIZE];
+
+    rng.fill_bytes(&mut salt);
+
+    let key = passphrase_key(passphrase, &salt, iterations);
+    let mut bytes = Vec::new();
+
+    bytes.extend_from_slice(&iterations.to_be_bytes());
+    bytes.extend_from_slice(&salt);
+    bytes.extend_from_slice(&encrypt(&key, data, rng)?);
+
+    Ok(bytes)
+}
+
+/// Decrypt data which was encrypted by `encrypt_with_passphrase`
+pub fn decrypt_with_passphrase(passphrase: &[u8], data: &[u8]) -> Result<Vec<u8>, AesGcmError> {
+    let header_size = 4 + PASSPHRASE_SALT_SIZE;
+    if data.len() < header_size + AES_GCM_NONCE_SIZE {
+        return Err(AesGcmError);
+    }
+
+    let mut iterations = [0u8; 4];
+    iterations.copy_from_slice(&data[..4]);
+    let salt = &data[4..header_size];
+    let key = passphrase_key(passphrase, salt, u32::from_be_bytes(iterations));
+
+    decrypt(&key, &data[header_size..])
+}
+
 #[cfg(test)]
 mod test {
     use p256k1::{point::Point, scalar::Scalar};
@@ -108,4 +185,47 @@ mod test {
 
         assert_eq!(msg.as_bytes(), &plain);
     }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn test_share_key() {
+        let mut rng = OsRng;
+
+        let x = Scalar::random(&mut rng);
+        let y = Scalar::random(&mut rng);
+
+        let X = Point::from(x);
+        let Y = Point::from(y);
+
+        let xy = make_shared_secret(&x, &Y);
+        let yx = make_shared_secret(&y, &X);
+
+        // both ends derive the same key for the same context
+        let key = make_share_key(&xy, 1, 0, 2);
+        assert_eq!(key, make_share_key(&yx, 1, 0, 2));
+        assert_ne!(key, xy);
+
+        // but a different round, direction, or recipient gets a different key
+        assert_ne!(key, make_share_key(&xy, 2, 0, 2));
+        assert_ne!(key, make_share_key(&xy, 1, 2, 0));
+        assert_ne!(key, make_share_key(&xy, 1, 0, 3));
+
+        let cipher = encrypt(&key, b"share", &mut rng).unwrap();
+        assert!(decrypt(&make_share_key(&yx, 2, 0, 2), &cipher).is_err());
+        assert_eq!(decrypt(&key, &cipher).unwrap(), b"share");
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_with_passphrase() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago, in a kingdom by the sea...";
+
+        let cipher =
+            encrypt_with_passphrase(b"passphrase", msg.as_bytes(), 1000, &mut rng).unwrap();
+        let plain = decrypt_with_passphrase(b"passphrase", &cipher).unwrap();
+
+        assert_eq!(msg.as_bytes(), &plain);
+        assert!(decrypt_with_passphrase(b"wrong passphrase", &cipher).is_err());
+        assert!(decrypt_with_passphrase(b"passphrase", &cipher[..20]).is_err());
+    }
 }
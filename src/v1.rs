@@ -1,20 +1,30 @@
+use core::marker::PhantomData;
 use hashbrown::HashMap;
 use num_traits::{One, Zero};
 use p256k1::{
+    field,
     point::{Point, G},
     scalar::Scalar,
 };
 use polynomial::Polynomial;
 use rand_core::{CryptoRng, RngCore};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    common::{CheckPrivateShares, Nonce, PolyCommitment, PublicNonce, Signature, SignatureShare},
-    compute,
+    blind,
+    common::{
+        CheckPrivateShares, Nonce, PendingSignature, PolyCommitment, PublicNonce, Signature,
+        SignatureShare, TaprootTweak,
+    },
+    compute::{self, Bip340Challenge},
+    ct,
     errors::{AggregatorError, DkgError},
     schnorr::ID,
     taproot::SchnorrProof,
-    traits,
+    traits::{self, ChallengeHash},
+    v2,
     vss::VSS,
 };
 
@@ -29,23 +39,42 @@ pub struct PartyState {
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 /// A FROST party, which encapsulates a single polynomial, nonce, and key
-pub struct Party {
+pub struct Party<H: ChallengeHash = Bip340Challenge> {
     /// The ID
     pub id: u32,
     /// The public key
     pub public_key: Point,
     /// The polynomial used for Lagrange interpolation
     pub f: Polynomial<Scalar>,
-    n: u32,
-    private_key: Scalar,
+    pub(crate) n: u32,
+    pub(crate) private_key: Scalar,
     /// The aggregate group public key
     pub group_key: Point,
-    nonce: Nonce,
+    pub(crate) nonce: Nonce,
+    lambdas: compute::LagrangeCache,
+    challenge_hash: PhantomData<H>,
 }
 
 impl Party {
     /// Construct a random Party with the passed ID and parameters
     pub fn new<RNG: RngCore + CryptoRng>(id: u32, n: u32, t: u32, rng: &mut RNG) -> Self {
+        Self::with_challenge_hash(id, n, t, rng)
+    }
+
+    /// Load a party from `state`
+    pub fn load(id: u32, n: u32, group_key: &Point, state: &PartyState) -> Self {
+        Self::load_with_challenge_hash(id, n, group_key, state)
+    }
+}
+
+impl<H: ChallengeHash> Party<H> {
+    /// Construct a random Party with the passed ID and parameters, which uses the challenge hash `H`
+    pub fn with_challenge_hash<RNG: RngCore + CryptoRng>(
+        id: u32,
+        n: u32,
+        t: u32,
+        rng: &mut RNG,
+    ) -> Self {
         Self {
             id,
             n,
@@ -54,19 +83,28 @@ impl Party {
             public_key: Point::zero(),
             group_key: Point::zero(),
             nonce: Nonce::zero(),
+            lambdas: Default::default(),
+            challenge_hash: PhantomData,
         }
     }
 
-    /// Load a party from `state`
-    pub fn load(id: u32, n: u32, group_key: &Point, state: &PartyState) -> Self {
+    /// Load a party from `state`, which uses the challenge hash `H`
+    pub fn load_with_challenge_hash(
+        id: u32,
+        n: u32,
+        group_key: &Point,
+        state: &PartyState,
+    ) -> Self {
         Self {
             id,
             n,
             f: state.polynomial.clone(),
             private_key: state.private_key,
-            public_key: &state.private_key * G,
+            public_key: ct::mul_base(&state.private_key),
             group_key: *group_key,
             nonce: Nonce::zero(),
+            lambdas: Default::default(),
+            challenge_hash: PhantomData,
         }
     }
 
@@ -80,7 +118,20 @@ impl Party {
 
     /// Generate and store a private nonce for a signing round
     pub fn gen_nonce<RNG: RngCore + CryptoRng>(&mut self, rng: &mut RNG) -> PublicNonce {
-        self.nonce = Nonce::random(rng);
+        self.gen_nonce_with_context(&[], &[], rng)
+    }
+
+    /// Generate and store a synthetic private nonce for a signing round, which mixes the private
+    /// key, `aux_rand` and the round `context` in with `rng`'s output
+    pub fn gen_nonce_with_context<RNG: RngCore + CryptoRng>(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        rng: &mut RNG,
+    ) -> PublicNonce {
+        let mut secret = self.id.to_be_bytes().to_vec();
+        secret.extend_from_slice(&self.private_key.to_bytes());
+        self.nonce = Nonce::synthetic(&secret, aux_rand, context, rng);
 
         PublicNonce::from(&self.nonce)
     }
@@ -90,7 +141,7 @@ impl Party {
         PolyCommitment {
             id: ID::new(&self.id(), &self.f.data()[0], rng),
             poly: (0..self.f.data().len())
-                .map(|i| &self.f.data()[i] * G)
+                .map(|i| ct::mul_base(&self.f.data()[i]))
                 .collect(),
         }
     }
@@ -101,6 +152,22 @@ impl Party {
         self.f = VSS::random_poly(t.try_into().unwrap(), rng);
     }
 
+    /// Make a new polynomial of degree `t - 1` whose constant term is this party's Lagrange
+    /// weighted share of the group secret, so the sum over all parties is the group secret
+    pub fn reshare_poly<RNG: RngCore + CryptoRng>(
+        &mut self,
+        t: u32,
+        key_ids: &[u32],
+        rng: &mut RNG,
+    ) {
+        let secret = if key_ids.contains(&self.id) {
+            compute::lambda(self.id, key_ids) * self.private_key
+        } else {
+            Scalar::zero()
+        };
+        self.f = VSS::secret_poly(&secret, t - 1, rng);
+    }
+
     /// Get the shares of this party's private polynomial for all parties
     pub fn get_shares(&self) -> HashMap<u32, Scalar> {
         let mut shares = HashMap::new();
@@ -114,7 +181,7 @@ impl Party {
     pub fn compute_secret(
         &mut self,
         shares: HashMap<u32, Scalar>,
-        comms: &[PolyCommitment],
+        comms: &[&PolyCommitment],
     ) -> Result<(), DkgError> {
         let mut missing_shares = Vec::new();
         for i in 0..self.n {
@@ -148,7 +215,7 @@ impl Party {
             let mut bad_shares = Vec::new();
             for (i, s) in shares.iter() {
                 let comm = &comms[usize::try_from(*i).unwrap()];
-                if s * G != compute::poly(&self.id(), &comm.poly)? {
+                if ct::mul_base(s) != compute::poly(&self.id(), &comm.poly)? {
                     bad_shares.push(*i);
                 }
             }
@@ -161,7 +228,7 @@ impl Party {
             self.private_key += s;
             self.group_key += comm.poly[0];
         }
-        self.public_key = self.private_key * G;
+        self.public_key = ct::mul_base(&self.private_key);
 
         Ok(())
     }
@@ -175,14 +242,32 @@ impl Party {
     pub fn sign(&self, msg: &[u8], signers: &[u32], nonces: &[PublicNonce]) -> SignatureShare {
         let (_, aggregate_nonce) = compute::intermediate(msg, signers, nonces);
         let mut z = &self.nonce.d + &self.nonce.e * compute::binding(&self.id(), nonces, msg);
-        z += compute::challenge(&self.group_key, &aggregate_nonce, msg)
+        z += H::challenge(&self.group_key, &aggregate_nonce, msg)
             * &self.private_key
-            * compute::lambda(self.id, signers);
+            * self.lambdas.lambda(self.id, signers);
+
+        SignatureShare {
+            id: self.id,
+            z_i: z,
+            key_ids: vec![self.id].into(),
+        }
+    }
+
+    /// Sign the blinded `challenge` with this party's share of the group private key, using the set of `signers` and corresponding `nonces`
+    pub fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        signers: &[u32],
+        nonces: &[PublicNonce],
+    ) -> SignatureShare {
+        let mut z = &self.nonce.d
+            + &self.nonce.e * compute::binding(&self.id(), nonces, blind::BINDING_MSG);
+        z += challenge * &self.private_key * self.lambdas.lambda(self.id, signers);
 
         SignatureShare {
             id: self.id,
             z_i: z,
-            key_ids: vec![self.id],
+            key_ids: vec![self.id].into(),
         }
     }
 
@@ -212,9 +297,9 @@ impl Party {
         }
 
         let tweaked_public_key = self.group_key + tweak * G;
-        let mut cx = compute::challenge(&tweaked_public_key, aggregate_nonce, msg)
+        let mut cx = H::challenge(&tweaked_public_key, aggregate_nonce, msg)
             * &self.private_key
-            * compute::lambda(self.id, signers);
+            * self.lambdas.lambda(self.id, signers);
 
         if tweak != &Scalar::zero() && !tweaked_public_key.has_even_y() {
             cx = -cx;
@@ -225,22 +310,70 @@ impl Party {
         SignatureShare {
             id: self.id,
             z_i: z,
-            key_ids: vec![self.id],
+            key_ids: vec![self.id].into(),
         }
     }
 }
 
 /// The group signature aggregator
-pub struct Aggregator {
+pub struct Aggregator<H: ChallengeHash = Bip340Challenge> {
     /// The total number of keys
     pub num_keys: u32,
     /// The threshold of signers needed to construct a valid signature
     pub threshold: u32,
-    /// The aggregate group polynomial; poly[0] is the group public key
+    /// The aggregate group polynomial; poly[0] is the group public key, and it holds nothing
+    /// else if the aggregator was constructed from public keys
     pub poly: Vec<Point>,
+    /// The public key of each key ID
+    pub public_keys: HashMap<u32, Point>,
+    pending: Option<PendingSignature<H>>,
 }
 
-impl Aggregator {
+impl<H: ChallengeHash> Aggregator<H> {
+    /// Check the party signatures one at a time against their binding nonces `Rs`, to find the
+    /// bad ones; each share must be for exactly its own key, since every key is a separate party
+    #[allow(non_snake_case)]
+    fn check_shares(
+        &self,
+        sig_shares: &[SignatureShare],
+        Rs: &[Point],
+        nonce_sign: &Scalar,
+        challenge: &Scalar,
+        signers: &[u32],
+    ) -> Result<(), AggregatorError> {
+        let mut bad_party_keys = Vec::new();
+        let mut bad_party_sigs = Vec::new();
+
+        for (sig_share, R_i) in sig_shares.iter().zip(Rs) {
+            if !sig_share.key_ids.iter().eq([sig_share.id]) {
+                bad_party_sigs.push(sig_share.id);
+                continue;
+            }
+            match compute::verify_share_with_public_keys(
+                sig_share,
+                &(nonce_sign * R_i),
+                &self.public_keys,
+                challenge,
+                signers,
+            ) {
+                Some(true) => {}
+                Some(false) => bad_party_sigs.push(sig_share.id),
+                None => {
+                    bad_party_keys.push(sig_share.id);
+                    bad_party_sigs.push(sig_share.id);
+                }
+            }
+        }
+
+        if bad_party_sigs.is_empty() {
+            Ok(())
+        } else if !bad_party_keys.is_empty() {
+            Err(AggregatorError::BadPartyKeys(bad_party_keys))
+        } else {
+            Err(AggregatorError::BadPartySigs(bad_party_sigs))
+        }
+    }
+
     #[allow(non_snake_case)]
     /// Check and aggregate the party signatures using a tweak
     pub fn sign_with_tweak(
@@ -255,13 +388,10 @@ impl Aggregator {
         }
 
         let signers: Vec<u32> = sig_shares.iter().map(|ss| ss.id).collect();
-        let (Rs, R) = compute::intermediate(msg, &signers, nonces);
-        let mut z = Scalar::zero();
-        let mut bad_party_keys = Vec::new();
-        let mut bad_party_sigs = Vec::new();
+        let R = compute::aggregate_nonce(msg, &signers, nonces)?;
         let aggregate_public_key = self.poly[0];
         let tweaked_public_key = aggregate_public_key + tweak * G;
-        let c = compute::challenge(&tweaked_public_key, &R, msg);
+        let c = H::challenge(&tweaked_public_key, &R, msg);
         let mut r_sign = Scalar::one();
         let mut cx_sign = Scalar::one();
         if tweak != &Scalar::zero() {
@@ -273,53 +403,90 @@ impl Aggregator {
             }
         }
 
-        for i in 0..sig_shares.len() {
-            let id = compute::id(sig_shares[i].id);
-            let public_key = match compute::poly(&id, &self.poly) {
-                Ok(p) => p,
-                Err(_) => {
-                    bad_party_keys.push(sig_shares[i].id);
-                    Point::zero()
-                }
-            };
-
-            let z_i = sig_shares[i].z_i;
-
-            if z_i * G
-                != r_sign * Rs[i]
-                    + cx_sign * (compute::lambda(sig_shares[i].id, &signers) * c * public_key)
-            {
-                bad_party_sigs.push(sig_shares[i].id);
-            }
+        let challenge = cx_sign * c;
+        let mut z = sig_shares
+            .iter()
+            .fold(Scalar::zero(), |z, sig_share| z + sig_share.z_i);
 
-            z += z_i;
+        // check all the shares at once, and only check them one at a time if that fails
+        if !compute::check_aggregate(&z, &R, &r_sign, &aggregate_public_key, &challenge)? {
+            let (Rs, _) = compute::intermediate(msg, &signers, nonces);
+            self.check_shares(sig_shares, &Rs, &r_sign, &challenge, &signers)?;
         }
 
         if tweak != &Scalar::zero() {
-            z += cx_sign * c * tweak;
+            z += challenge * tweak;
         }
 
-        if bad_party_sigs.is_empty() {
-            let sig = Signature { R, z };
-            Ok((tweaked_public_key, sig))
-        } else if !bad_party_keys.is_empty() {
-            Err(AggregatorError::BadPartyKeys(bad_party_keys))
-        } else {
-            Err(AggregatorError::BadPartySigs(bad_party_sigs))
-        }
+        let sig = Signature { R, z };
+        Ok((tweaked_public_key, sig))
     }
 }
 
-impl traits::Aggregator for Aggregator {
+impl Aggregator {
+    /// Construct an Aggregator with the passed parameters
+    pub fn new(num_keys: u32, threshold: u32) -> Self {
+        <Self as traits::Aggregator>::new(num_keys, threshold)
+    }
+
+    /// Construct an Aggregator from the group public key and the public key of each key ID
+    pub fn from_public_keys(
+        num_keys: u32,
+        threshold: u32,
+        group_key: Point,
+        public_keys: HashMap<u32, Point>,
+    ) -> Result<Self, AggregatorError> {
+        <Self as traits::Aggregator>::from_public_keys(num_keys, threshold, group_key, public_keys)
+    }
+}
+
+impl<H: ChallengeHash> traits::Aggregator for Aggregator<H> {
     /// Construct an Aggregator with the passed parameters
     fn new(num_keys: u32, threshold: u32) -> Self {
         Self {
             num_keys,
             threshold,
             poly: Default::default(),
+            public_keys: Default::default(),
+            pending: None,
         }
     }
 
+    /// Construct an Aggregator from the group public key and the public key of each key ID
+    fn from_public_keys(
+        num_keys: u32,
+        threshold: u32,
+        group_key: Point,
+        public_keys: HashMap<u32, Point>,
+    ) -> Result<Self, AggregatorError> {
+        let mut bad_keys: Vec<u32> = (0..num_keys)
+            .filter(|key_id| !public_keys.contains_key(key_id))
+            .chain(
+                public_keys
+                    .keys()
+                    .copied()
+                    .filter(|key_id| *key_id >= num_keys),
+            )
+            .collect();
+        if !bad_keys.is_empty() {
+            bad_keys.sort();
+            return Err(AggregatorError::BadPartyKeys(bad_keys));
+        }
+
+        let key_ids: Vec<u32> = (0..threshold).collect();
+        if compute::interpolate_public_key(&public_keys, &key_ids)? != group_key {
+            return Err(AggregatorError::BadGroupKey);
+        }
+
+        Ok(Self {
+            num_keys,
+            threshold,
+            poly: vec![group_key],
+            public_keys,
+            pending: None,
+        })
+    }
+
     /// Initialize the Aggregator polynomial
     fn init(&mut self, comms: Vec<PolyCommitment>) -> Result<(), AggregatorError> {
         let len = self.num_keys.try_into().unwrap();
@@ -327,9 +494,10 @@ impl traits::Aggregator for Aggregator {
             return Err(AggregatorError::BadPolyCommitmentLen(len, comms.len()));
         }
 
+        let threshold: usize = self.threshold.try_into().unwrap();
         let mut bad_poly_commitments = Vec::new();
         for comm in &comms {
-            if !comm.verify() {
+            if comm.poly.len() != threshold || !comm.verify() {
                 bad_poly_commitments.push(comm.id.id);
             }
         }
@@ -346,6 +514,7 @@ impl traits::Aggregator for Aggregator {
             }
         }
 
+        self.public_keys = compute::public_keys(self.num_keys, &poly)?;
         self.poly = poly;
 
         Ok(())
@@ -361,7 +530,7 @@ impl traits::Aggregator for Aggregator {
     ) -> Result<Signature, AggregatorError> {
         let (key, sig) = self.sign_with_tweak(msg, nonces, sig_shares, &Scalar::zero())?;
 
-        if sig.verify(&key, msg) {
+        if sig.verify_with::<H>(&key, msg) {
             Ok(sig)
         } else {
             Err(AggregatorError::BadGroupSig)
@@ -369,19 +538,108 @@ impl traits::Aggregator for Aggregator {
     }
 
     /// Check and aggregate the party signatures using a merke root to make a tweak
+    #[allow(non_snake_case)]
+    fn sign_blinded(
+        &mut self,
+        challenge: &Scalar,
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        _key_ids: &[u32],
+    ) -> Result<Scalar, AggregatorError> {
+        if nonces.len() != sig_shares.len() {
+            return Err(AggregatorError::BadNonceLen(nonces.len(), sig_shares.len()));
+        }
+
+        let signers: Vec<u32> = sig_shares.iter().map(|ss| ss.id).collect();
+        let R = compute::aggregate_nonce(blind::BINDING_MSG, &signers, nonces)?;
+        let z = sig_shares
+            .iter()
+            .fold(Scalar::zero(), |z, sig_share| z + sig_share.z_i);
+
+        // check all the shares at once, and only check them one at a time if that fails
+        if !compute::check_aggregate(&z, &R, &Scalar::one(), &self.poly[0], challenge)? {
+            let (Rs, _) = compute::intermediate(blind::BINDING_MSG, &signers, nonces);
+            self.check_shares(sig_shares, &Rs, &Scalar::one(), challenge, &signers)?;
+        }
+
+        Ok(z)
+    }
+
+    fn party_ids(_signer_id: u32, key_ids: &[u32]) -> Vec<u32> {
+        key_ids.to_vec()
+    }
+
+    fn start_sign(
+        &mut self,
+        msg: &[u8],
+        party_ids: &[u32],
+        nonces: &[PublicNonce],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<(), AggregatorError> {
+        self.pending = Some(PendingSignature::new(
+            msg,
+            party_ids,
+            nonces,
+            key_ids,
+            &self.poly[0],
+            tweak,
+        )?);
+        Ok(())
+    }
+
+    fn add_share(&mut self, share: SignatureShare) -> Result<(), AggregatorError> {
+        if !share.key_ids.iter().eq([share.id]) {
+            return Err(AggregatorError::BadPartySigs(vec![share.id]));
+        }
+        self.pending
+            .as_mut()
+            .ok_or(AggregatorError::NoPendingSignature)?
+            .add_share(share, &self.public_keys)
+    }
+
+    fn try_finalize(&mut self) -> Result<Option<Signature>, AggregatorError> {
+        let sig = self
+            .pending
+            .as_ref()
+            .ok_or(AggregatorError::NoPendingSignature)?
+            .finalize()?;
+        if sig.is_some() {
+            self.pending = None;
+        }
+        Ok(sig)
+    }
+
+    fn taproot_output_key(&self, tweak: TaprootTweak) -> Option<field::Element> {
+        self.poly
+            .first()
+            .map(|key| compute::tweaked_public_key(key, tweak).x())
+    }
+
     fn sign_taproot(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: TaprootTweak,
+    ) -> Result<SchnorrProof, AggregatorError> {
+        let tweak = compute::taproot_tweak(&self.poly[0], tweak);
+        traits::Aggregator::sign_with_tweak(self, msg, nonces, sig_shares, key_ids, &tweak)
+    }
+
+    fn sign_with_tweak(
         &mut self,
         msg: &[u8],
         nonces: &[PublicNonce],
         sig_shares: &[SignatureShare],
         _key_ids: &[u32],
-        merkle_root: Option<[u8; 32]>,
+        tweak: &Scalar,
     ) -> Result<SchnorrProof, AggregatorError> {
-        let tweak = compute::tweak(&self.poly[0], merkle_root);
-        let (key, sig) = self.sign_with_tweak(msg, nonces, sig_shares, &tweak)?;
+        let (key, sig) = self.sign_with_tweak(msg, nonces, sig_shares, tweak)?;
         let proof = SchnorrProof::new(&sig);
 
-        if proof.verify(&key.x(), msg) {
+        if proof.verify_with::<H>(&key.x(), msg) {
             Ok(proof)
         } else {
             Err(AggregatorError::BadGroupSig)
@@ -404,7 +662,7 @@ pub struct SignerState {
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 /// A set of encapsulated FROST parties
-pub struct Signer {
+pub struct Signer<H: ChallengeHash = Bip340Challenge> {
     /// The associated signer ID
     id: u32,
     /// The total number of keys
@@ -412,7 +670,7 @@ pub struct Signer {
     /// The aggregate group public key
     group_key: Point,
     /// The parties which this object encapsulates
-    parties: Vec<Party>,
+    parties: Vec<Party<H>>,
 }
 
 impl Signer {
@@ -423,10 +681,29 @@ impl Signer {
         num_keys: u32,
         threshold: u32,
         rng: &mut RNG,
+    ) -> Self {
+        Self::with_challenge_hash(id, key_ids, num_keys, threshold, rng)
+    }
+
+    /// Load a Signer from the saved state
+    pub fn load(state: &SignerState) -> Self {
+        Self::load_with_challenge_hash(state)
+    }
+}
+
+impl<H: ChallengeHash> Signer<H> {
+    /// Construct a random Signer with the passed IDs and parameters, which uses the challenge
+    /// hash `H`
+    pub fn with_challenge_hash<RNG: RngCore + CryptoRng>(
+        id: u32,
+        key_ids: &[u32],
+        num_keys: u32,
+        threshold: u32,
+        rng: &mut RNG,
     ) -> Self {
         let parties = key_ids
             .iter()
-            .map(|id| Party::new(*id, num_keys, threshold, rng))
+            .map(|id| Party::with_challenge_hash(*id, num_keys, threshold, rng))
             .collect();
         Signer {
             id,
@@ -436,13 +713,16 @@ impl Signer {
         }
     }
 
-    /// Load a Signer from the saved state
-    pub fn load(state: &SignerState) -> Self {
-        let parties = state
+    /// Load a Signer from the saved state, which uses the challenge hash `H`
+    pub fn load_with_challenge_hash(state: &SignerState) -> Self {
+        let mut parties: Vec<Party<H>> = state
             .parties
             .iter()
-            .map(|(id, ps)| Party::load(*id, state.num_keys, &state.group_key, ps))
+            .map(|(id, ps)| {
+                Party::load_with_challenge_hash(*id, state.num_keys, &state.group_key, ps)
+            })
             .collect();
+        parties.sort_by_key(|p| p.id);
 
         Self {
             id: state.id,
@@ -469,7 +749,66 @@ impl Signer {
     }
 }
 
-impl traits::Signer for Signer {
+impl SignerState {
+    /// Convert to the state of a v2 party holding the same keys, so a signer can move from v1
+    /// to v2 without a new DKG.  Polynomials are only used during DKG and are replaced at the
+    /// start of the next one, so the party gets a fresh random polynomial
+    pub fn to_v2<RNG: RngCore + CryptoRng>(
+        &self,
+        num_signers: u32,
+        threshold: u32,
+        rng: &mut RNG,
+    ) -> v2::PartyState {
+        let mut key_ids: Vec<u32> = self.parties.keys().copied().collect();
+        key_ids.sort();
+
+        v2::PartyState {
+            party_id: self.id,
+            key_ids,
+            num_keys: self.num_keys,
+            num_parties: num_signers,
+            threshold,
+            polynomial: VSS::random_poly(threshold - 1, rng),
+            private_keys: self
+                .parties
+                .iter()
+                .map(|(key_id, party)| (*key_id, party.private_key))
+                .collect(),
+            group_key: self.group_key,
+        }
+    }
+
+    /// Convert from the state of a v2 party, so a signer can move from v2 to v1 without a new
+    /// DKG.  Each key's party gets a fresh random polynomial, as in `to_v2`
+    pub fn from_v2<RNG: RngCore + CryptoRng>(state: &v2::PartyState, rng: &mut RNG) -> Self {
+        let parties = state
+            .key_ids
+            .iter()
+            .map(|key_id| {
+                let party = PartyState {
+                    private_key: state
+                        .private_keys
+                        .get(key_id)
+                        .copied()
+                        .unwrap_or_else(Scalar::zero),
+                    polynomial: VSS::random_poly(state.threshold - 1, rng),
+                };
+                (*key_id, party)
+            })
+            .collect();
+
+        Self {
+            id: state.party_id,
+            num_keys: state.num_keys,
+            group_key: state.group_key,
+            parties,
+        }
+    }
+}
+
+impl<H: ChallengeHash> traits::Signer for Signer<H> {
+    type State = SignerState;
+
     fn new<RNG: RngCore + CryptoRng>(
         party_id: u32,
         key_ids: &[u32],
@@ -478,7 +817,15 @@ impl traits::Signer for Signer {
         threshold: u32,
         rng: &mut RNG,
     ) -> Self {
-        Signer::new(party_id, key_ids, num_keys, threshold, rng)
+        Self::with_challenge_hash(party_id, key_ids, num_keys, threshold, rng)
+    }
+
+    fn load(state: &SignerState) -> Self {
+        Self::load_with_challenge_hash(state)
+    }
+
+    fn save(&self) -> SignerState {
+        Signer::save(self)
     }
 
     fn get_id(&self) -> u32 {
@@ -493,6 +840,25 @@ impl traits::Signer for Signer {
         self.num_keys
     }
 
+    fn party_ids(_signer_id: u32, key_ids: &[u32]) -> Vec<u32> {
+        // each key is a separate party
+        key_ids
+            .iter()
+            .map(|key_id| compute::id(*key_id).get_u32())
+            .collect()
+    }
+
+    fn get_group_key(&self) -> Point {
+        self.group_key
+    }
+
+    fn get_private_keys(&self) -> HashMap<u32, Scalar> {
+        self.parties
+            .iter()
+            .map(|party| (party.id, party.private_key))
+            .collect()
+    }
+
     fn get_poly_commitments<RNG: RngCore + CryptoRng>(&self, rng: &mut RNG) -> Vec<PolyCommitment> {
         self.parties
             .iter()
@@ -506,6 +872,17 @@ impl traits::Signer for Signer {
         }
     }
 
+    fn reshare_polys<RNG: RngCore + CryptoRng>(
+        &mut self,
+        threshold: u32,
+        key_ids: &[u32],
+        rng: &mut RNG,
+    ) {
+        for party in self.parties.iter_mut() {
+            party.reshare_poly(threshold, key_ids, rng);
+        }
+    }
+
     fn get_shares(&self) -> HashMap<u32, HashMap<u32, Scalar>> {
         let mut shares = HashMap::new();
         for party in &self.parties {
@@ -517,29 +894,58 @@ impl traits::Signer for Signer {
     fn compute_secrets(
         &mut self,
         private_shares: &HashMap<u32, HashMap<u32, Scalar>>,
-        polys: &[PolyCommitment],
+        polys: &[&PolyCommitment],
     ) -> Result<(), HashMap<u32, DkgError>> {
-        let mut dkg_errors = HashMap::new();
-        for party in &mut self.parties {
-            // go through the shares, looking for this party's
-            let mut key_shares = HashMap::with_capacity(polys.len());
-            for (signer_id, signer_shares) in private_shares.iter() {
-                key_shares.insert(*signer_id, signer_shares[&party.id]);
-            }
-            if let Err(e) = party.compute_secret(key_shares, polys) {
-                dkg_errors.insert(party.id, e);
-            }
-        }
+        // each party only checks its own shares, so with the rayon feature they run in parallel
+        #[cfg(feature = "rayon")]
+        let parties = self.parties.par_iter_mut();
+        #[cfg(not(feature = "rayon"))]
+        let parties = self.parties.iter_mut();
+
+        let dkg_errors: HashMap<u32, DkgError> = parties
+            .filter_map(|party| {
+                // go through the shares, looking for this party's
+                let mut key_shares = HashMap::with_capacity(polys.len());
+                for (signer_id, signer_shares) in private_shares.iter() {
+                    key_shares.insert(*signer_id, signer_shares[&party.id]);
+                }
+                party
+                    .compute_secret(key_shares, polys)
+                    .err()
+                    .map(|e| (party.id, e))
+            })
+            .collect::<Vec<(u32, DkgError)>>()
+            .into_iter()
+            .collect();
 
         if dkg_errors.is_empty() {
+            self.group_key = polys.iter().fold(Point::zero(), |s, comm| s + comm.poly[0]);
             Ok(())
         } else {
             Err(dkg_errors)
         }
     }
 
-    fn gen_nonces<RNG: RngCore + CryptoRng>(&mut self, rng: &mut RNG) -> Vec<PublicNonce> {
-        self.parties.iter_mut().map(|p| p.gen_nonce(rng)).collect()
+    fn gen_nonces_with_context<RNG: RngCore + CryptoRng>(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        rng: &mut RNG,
+    ) -> Vec<PublicNonce> {
+        self.parties
+            .iter_mut()
+            .map(|p| p.gen_nonce_with_context(aux_rand, context, rng))
+            .collect()
+    }
+
+    fn get_nonces(&self) -> Vec<Nonce> {
+        self.parties.iter().map(|p| p.nonce.clone()).collect()
+    }
+
+    fn set_nonces(&mut self, nonces: &[Nonce]) {
+        for (party, nonce) in self.parties.iter_mut().zip(nonces) {
+            party.nonce = nonce.clone();
+        }
     }
 
     fn compute_intermediate(
@@ -566,18 +972,42 @@ impl traits::Signer for Signer {
     }
 
     fn sign_taproot(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: TaprootTweak,
+    ) -> Vec<SignatureShare> {
+        let tweak = compute::taproot_tweak(&self.parties[0].group_key, tweak);
+        self.sign_with_tweak(msg, signer_ids, key_ids, nonces, &tweak)
+    }
+
+    fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        _signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        self.parties
+            .iter()
+            .map(|p| p.sign_blinded(challenge, key_ids, nonces))
+            .collect()
+    }
+
+    fn sign_with_tweak(
         &self,
         msg: &[u8],
         _signer_ids: &[u32],
         key_ids: &[u32],
         nonces: &[PublicNonce],
-        merkle_root: Option<[u8; 32]>,
+        tweak: &Scalar,
     ) -> Vec<SignatureShare> {
         let aggregate_nonce = compute::aggregate_nonce(msg, key_ids, nonces).unwrap();
-        let tweak = compute::tweak(&self.parties[0].group_key, merkle_root);
         self.parties
             .iter()
-            .map(|p| p.sign_precomputed_with_tweak(msg, key_ids, nonces, &aggregate_nonce, &tweak))
+            .map(|p| p.sign_precomputed_with_tweak(msg, key_ids, nonces, &aggregate_nonce, tweak))
             .collect()
     }
 }
@@ -609,10 +1039,11 @@ pub mod test_helpers {
             }
         }
 
+        let polys = comms.iter().collect::<Vec<&PolyCommitment>>();
         let mut secret_errors = HashMap::new();
         for signer in signers.iter_mut() {
-            if let Err(signer_secret_errors) = signer.compute_secrets(&private_shares, &comms) {
-                secret_errors.extend(signer_secret_errors.into_iter());
+            if let Err(signer_secret_errors) = signer.compute_secrets(&private_shares, &polys) {
+                secret_errors.extend(signer_secret_errors);
             }
         }
 
@@ -642,10 +1073,15 @@ pub mod test_helpers {
 
 #[cfg(test)]
 mod tests {
+    use crate::common::{Nonce, PublicNonce};
+    use crate::compute;
+    use crate::errors::{AggregatorError, DkgError};
     use crate::traits::{Aggregator, Signer};
-    use crate::v1;
+    use crate::vectors::SeededRng;
+    use crate::{v1, v2};
 
     use num_traits::Zero;
+    use p256k1::{point::G, scalar::Scalar};
     use rand_core::OsRng;
 
     #[test]
@@ -684,6 +1120,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn synthetic_nonces() {
+        // a broken RNG which gives every host the same output
+        let rng = || SeededRng::new([7; 32]);
+        let secret = Scalar::from(1).to_bytes();
+        let other_secret = Scalar::from(2).to_bytes();
+
+        let nonce = Nonce::synthetic(&secret, &[], b"round 1", &mut rng());
+        assert_eq!(
+            Nonce::synthetic(&secret, &[], b"round 1", &mut rng()),
+            nonce
+        );
+        assert_ne!(nonce.d, nonce.e);
+
+        // the nonce still differs with the private key, the round, or the auxiliary randomness
+        assert_ne!(
+            Nonce::synthetic(&other_secret, &[], b"round 1", &mut rng()),
+            nonce
+        );
+        assert_ne!(
+            Nonce::synthetic(&secret, &[], b"round 2", &mut rng()),
+            nonce
+        );
+        assert_ne!(
+            Nonce::synthetic(&secret, &[1; 32], b"round 1", &mut rng()),
+            nonce
+        );
+        assert_ne!(
+            Nonce::synthetic(&secret, b"round", b" 1", &mut rng()),
+            Nonce::synthetic(&secret, b"round ", b"1", &mut rng())
+        );
+
+        // parties with the same RNG get different nonces
+        let mut signer = v1::Signer::new(1, &[1, 2], 10, 7, &mut OsRng);
+        let nonces = signer.gen_nonces_with_context(&[], b"round 1", &mut rng());
+        assert_ne!(nonces[0], nonces[1]);
+    }
+
     #[test]
     fn signer_save_load() {
         let mut rng = OsRng;
@@ -733,10 +1207,329 @@ mod tests {
             let mut sig_agg = v1::Aggregator::new(N, T);
             sig_agg.init(comms.clone()).expect("aggregator init failed");
 
-            let (nonces, sig_shares) = v1::test_helpers::sign(msg, &mut signers, &mut rng);
+            let (nonces, mut sig_shares) = v1::test_helpers::sign(msg, &mut signers, &mut rng);
             if let Err(e) = sig_agg.sign(msg, &nonces, &sig_shares, &[]) {
                 panic!("Aggregator sign failed: {:?}", e);
             }
+
+            // a bad share fails the batch check, and is then found on its own
+            sig_shares[1].z_i += Scalar::from(1);
+            match sig_agg.sign(msg, &nonces, &sig_shares, &[]) {
+                Err(AggregatorError::BadPartySigs(ids)) => assert_eq!(ids, [sig_shares[1].id]),
+                _ => panic!("Expected BadPartySigs"),
+            }
+
+            // an aggregator built from just the public keys checks shares the same way
+            let mut key_agg = v1::Aggregator::from_public_keys(
+                N,
+                T,
+                sig_agg.poly[0],
+                sig_agg.public_keys.clone(),
+            )
+            .expect("aggregator from public keys failed");
+            match key_agg.sign(msg, &nonces, &sig_shares, &[]) {
+                Err(AggregatorError::BadPartySigs(ids)) => assert_eq!(ids, [sig_shares[1].id]),
+                _ => panic!("Expected BadPartySigs"),
+            }
+            sig_shares[1].z_i = sig_shares[1].z_i - Scalar::from(1);
+            if let Err(e) = key_agg.sign(msg, &nonces, &sig_shares, &[]) {
+                panic!("Aggregator sign failed: {:?}", e);
+            }
+
+            let mut public_keys = sig_agg.public_keys.clone();
+            public_keys.remove(&0);
+            assert!(matches!(
+                v1::Aggregator::from_public_keys(N, T, sig_agg.poly[0], public_keys),
+                Err(AggregatorError::BadPartyKeys(ids)) if ids == [0]
+            ));
+            let mut public_keys = sig_agg.public_keys.clone();
+            public_keys.insert(0, sig_agg.poly[0]);
+            assert!(matches!(
+                v1::Aggregator::from_public_keys(N, T, sig_agg.poly[0], public_keys),
+                Err(AggregatorError::BadGroupKey)
+            ));
         }
     }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn aggregator_add_share() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let N: u32 = 10;
+        let T: u32 = 7;
+        let party_key_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let mut signers: Vec<v1::Signer> = party_key_ids
+            .iter()
+            .enumerate()
+            .map(|(id, ids)| v1::Signer::new(id.try_into().unwrap(), ids, N, T, &mut rng))
+            .collect();
+
+        let comms = match v1::test_helpers::dkg(&mut signers, &mut rng) {
+            Ok(comms) => comms,
+            Err(secret_errors) => {
+                panic!("Got secret errors from DKG: {:?}", secret_errors);
+            }
+        };
+
+        let mut signers = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
+        let mut sig_agg = v1::Aggregator::new(N, T);
+        sig_agg.init(comms).expect("aggregator init failed");
+
+        let (nonces, sig_shares) = v1::test_helpers::sign(msg, &mut signers, &mut rng);
+        let key_ids: Vec<u32> = sig_shares.iter().map(|share| share.id).collect();
+        let party_ids: Vec<u32> = sig_shares.iter().map(|share| share.id).collect();
+
+        assert!(matches!(
+            sig_agg.add_share(sig_shares[0].clone()),
+            Err(AggregatorError::NoPendingSignature)
+        ));
+        sig_agg
+            .start_sign(msg, &party_ids, &nonces, &key_ids, &Scalar::from(0))
+            .expect("start_sign failed");
+
+        // a bad share is rejected as soon as it's added
+        let mut bad_share = sig_shares[0].clone();
+        bad_share.z_i += Scalar::from(1);
+        match sig_agg.add_share(bad_share) {
+            Err(AggregatorError::BadPartySigs(ids)) => assert_eq!(ids, [sig_shares[0].id]),
+            _ => panic!("Expected BadPartySigs"),
+        }
+
+        let (last_share, shares) = sig_shares.split_last().unwrap();
+        for share in shares {
+            sig_agg.add_share(share.clone()).expect("add_share failed");
+            assert!(sig_agg.try_finalize().unwrap().is_none());
+        }
+        sig_agg
+            .add_share(last_share.clone())
+            .expect("add_share failed");
+
+        let sig = sig_agg
+            .try_finalize()
+            .expect("try_finalize failed")
+            .expect("signature should be complete");
+        assert!(sig.verify(&sig_agg.poly[0], msg));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn group_secret() {
+        let mut rng = OsRng;
+        let N: u32 = 10;
+        let T: u32 = 7;
+        let signer_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let mut signers: Vec<v1::Signer> = signer_ids
+            .iter()
+            .enumerate()
+            .map(|(id, ids)| v1::Signer::new(id.try_into().unwrap(), ids, N, T, &mut rng))
+            .collect();
+
+        if let Err(secret_errors) = v1::test_helpers::dkg(&mut signers, &mut rng) {
+            panic!("Got secret errors from DKG: {:?}", secret_errors);
+        }
+        let group_key = signers[0].get_group_key();
+
+        // signers [0,1,3] who have T keys
+        let secret = compute::group_secret(
+            &[signers[0].clone(), signers[1].clone(), signers[3].clone()],
+            T,
+        )
+        .unwrap();
+        assert_eq!(secret * G, group_key);
+
+        let secret = compute::group_secret(&signers, T).unwrap();
+        assert_eq!(secret * G, group_key);
+
+        // signers [0,1] only have 5 keys
+        assert!(matches!(
+            compute::group_secret(&signers[..2], T),
+            Err(DkgError::NotEnoughShares(_))
+        ));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn verify_share() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let N: u32 = 10;
+        let T: u32 = 7;
+        let signer_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let mut signers: Vec<v1::Signer> = signer_ids
+            .iter()
+            .enumerate()
+            .map(|(id, ids)| v1::Signer::new(id.try_into().unwrap(), ids, N, T, &mut rng))
+            .collect();
+
+        let comms = match v1::test_helpers::dkg(&mut signers, &mut rng) {
+            Ok(comms) => comms,
+            Err(secret_errors) => {
+                panic!("Got secret errors from DKG: {:?}", secret_errors);
+            }
+        };
+        let mut sig_agg = v1::Aggregator::new(N, T);
+        sig_agg.init(comms).expect("aggregator init failed");
+
+        // signers [0,1,3] who have T keys
+        let mut signers = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
+        let key_ids: Vec<u32> = signers.iter().flat_map(|s| s.get_key_ids()).collect();
+        let (nonces, sig_shares) = v1::test_helpers::sign(msg, &mut signers, &mut rng);
+        let (Rs, R) = compute::intermediate(msg, &key_ids, &nonces);
+
+        for (share, R_i) in sig_shares.iter().zip(&Rs) {
+            assert!(compute::verify_share(share, R_i, &R, &sig_agg.poly, msg, &key_ids).unwrap());
+            assert!(
+                !compute::verify_share(share, R_i, &R, &sig_agg.poly, b"other", &key_ids).unwrap()
+            );
+
+            let mut bad_share = share.clone();
+            bad_share.z_i += Scalar::from(1);
+            assert!(
+                !compute::verify_share(&bad_share, R_i, &R, &sig_agg.poly, msg, &key_ids).unwrap()
+            );
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn sign_with_tweak() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let N: u32 = 10;
+        let T: u32 = 7;
+        let signer_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let mut signers: Vec<v1::Signer> = signer_ids
+            .iter()
+            .enumerate()
+            .map(|(id, ids)| v1::Signer::new(id.try_into().unwrap(), ids, N, T, &mut rng))
+            .collect();
+
+        let comms = match v1::test_helpers::dkg(&mut signers, &mut rng) {
+            Ok(comms) => comms,
+            Err(secret_errors) => {
+                panic!("Got secret errors from DKG: {:?}", secret_errors);
+            }
+        };
+        let group_key = signers[0].get_group_key();
+
+        // signers [0,1,3] who have T keys
+        let mut signers = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
+        let signer_ids: Vec<u32> = signers.iter().map(|s| s.get_id()).collect();
+        let key_ids: Vec<u32> = signers.iter().flat_map(|s| s.get_key_ids()).collect();
+        let mut sig_agg = v1::Aggregator::new(N, T);
+        sig_agg.init(comms).expect("aggregator init failed");
+
+        // sign for several child keys, so both parities of the tweaked key are covered
+        for _ in 0..8 {
+            let tweak = Scalar::random(&mut rng);
+            let nonces: Vec<PublicNonce> = signers
+                .iter_mut()
+                .flat_map(|s| s.gen_nonces(&mut rng))
+                .collect();
+            let sig_shares: Vec<v1::SignatureShare> = signers
+                .iter()
+                .flat_map(|s| s.sign_with_tweak(msg, &signer_ids, &key_ids, &nonces, &tweak))
+                .collect();
+
+            let proof = Aggregator::sign_with_tweak(
+                &mut sig_agg,
+                msg,
+                &nonces,
+                &sig_shares,
+                &key_ids,
+                &tweak,
+            )
+            .expect("aggregator sign_with_tweak failed");
+
+            let child_key = group_key + tweak * G;
+            assert!(proof.verify(&child_key.x(), msg));
+            assert!(!proof.verify(&group_key.x(), msg));
+        }
+    }
+
+    #[test]
+    #[allow(non_snake_case)]
+    fn convert_v2() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let N: u32 = 10;
+        let T: u32 = 7;
+        let signer_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let Ns = signer_ids.len().try_into().unwrap();
+        let mut signers: Vec<v1::Signer> = signer_ids
+            .iter()
+            .enumerate()
+            .map(|(id, ids)| v1::Signer::new(id.try_into().unwrap(), ids, N, T, &mut rng))
+            .collect();
+        let comms = v1::test_helpers::dkg(&mut signers, &mut rng).unwrap();
+        let group_key = signers[0].get_group_key();
+
+        // the converted parties sign for the same group key without another DKG
+        let states: Vec<v2::PartyState> = signers
+            .iter()
+            .map(|signer| signer.save().to_v2(Ns, T, &mut rng))
+            .collect();
+        let parties: Vec<v2::Party> = states.iter().map(v2::Party::load).collect();
+        assert!(parties.iter().all(|p| p.get_group_key() == group_key));
+
+        let mut sig_agg = v2::Aggregator::new(N, T);
+        sig_agg.init(comms.clone()).expect("aggregator init failed");
+        let mut signing_parties = [parties[0].clone(), parties[1].clone(), parties[3].clone()];
+        let (nonces, sig_shares, key_ids) =
+            v2::test_helpers::sign(msg, &mut signing_parties, &mut rng);
+        let sig = sig_agg.sign(msg, &nonces, &sig_shares, &key_ids).unwrap();
+        assert!(sig.verify(&group_key, msg));
+
+        // and converting back gives the v1 signers their original keys
+        let signers_back: Vec<v1::Signer> = parties
+            .iter()
+            .map(|party| v1::Signer::load(&v1::SignerState::from_v2(&party.save(), &mut rng)))
+            .collect();
+        for (signer, signer_back) in signers.iter().zip(&signers_back) {
+            assert_eq!(signer.get_key_ids(), signer_back.get_key_ids());
+            assert_eq!(signer.get_private_keys(), signer_back.get_private_keys());
+        }
+
+        let mut sig_agg = v1::Aggregator::new(N, T);
+        sig_agg.init(comms).expect("aggregator init failed");
+        let mut signing_signers = [
+            signers_back[0].clone(),
+            signers_back[1].clone(),
+            signers_back[3].clone(),
+        ];
+        let (nonces, sig_shares) = v1::test_helpers::sign(msg, &mut signing_signers, &mut rng);
+        let sig = sig_agg.sign(msg, &nonces, &sig_shares, &[]).unwrap();
+        assert!(sig.verify(&group_key, msg));
+    }
 }
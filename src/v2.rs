@@ -1,20 +1,29 @@
+use core::marker::PhantomData;
 use hashbrown::{HashMap, HashSet};
 use num_traits::{One, Zero};
 use p256k1::{
+    field,
     point::{Point, G},
     scalar::Scalar,
 };
 use polynomial::Polynomial;
 use rand_core::{CryptoRng, RngCore};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    common::{Nonce, PolyCommitment, PublicNonce, Signature, SignatureShare},
-    compute,
+    blind,
+    common::{
+        CheckPrivateShares, Nonce, PendingSignature, PolyCommitment, PublicNonce, Signature,
+        SignatureShare, TaprootTweak,
+    },
+    compute::{self, Bip340Challenge},
+    ct,
     errors::{AggregatorError, DkgError},
     schnorr::ID,
     taproot::SchnorrProof,
-    traits,
+    traits::{self, ChallengeHash},
     vss::VSS,
 };
 
@@ -46,7 +55,7 @@ pub struct PartyState {
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 /// A WSTS party, which encapsulates a single polynomial, nonce, and one private key per key ID
-pub struct Party {
+pub struct Party<H: ChallengeHash = Bip340Challenge> {
     /// The party ID
     pub party_id: u32,
     /// The key IDs for this party
@@ -59,6 +68,8 @@ pub struct Party {
     private_keys: PrivKeyMap,
     group_key: Point,
     nonce: Nonce,
+    lambdas: compute::LagrangeCache,
+    challenge_hash: PhantomData<H>,
 }
 
 impl Party {
@@ -70,6 +81,26 @@ impl Party {
         num_keys: u32,
         threshold: u32,
         rng: &mut RNG,
+    ) -> Self {
+        Self::with_challenge_hash(party_id, key_ids, num_parties, num_keys, threshold, rng)
+    }
+
+    /// Load a party from `state`
+    pub fn load(state: &PartyState) -> Self {
+        Self::load_with_challenge_hash(state)
+    }
+}
+
+impl<H: ChallengeHash> Party<H> {
+    /// Construct a random Party with the passed party ID, key IDs, and parameters, which uses
+    /// the challenge hash `H`
+    pub fn with_challenge_hash<RNG: RngCore + CryptoRng>(
+        party_id: u32,
+        key_ids: &[u32],
+        num_parties: u32,
+        num_keys: u32,
+        threshold: u32,
+        rng: &mut RNG,
     ) -> Self {
         Self {
             party_id,
@@ -81,11 +112,13 @@ impl Party {
             private_keys: PrivKeyMap::new(),
             group_key: Point::zero(),
             nonce: Nonce::zero(),
+            lambdas: Default::default(),
+            challenge_hash: PhantomData,
         }
     }
 
-    /// Load a party from `state`
-    pub fn load(state: &PartyState) -> Self {
+    /// Load a party from `state`, which uses the challenge hash `H`
+    pub fn load_with_challenge_hash(state: &PartyState) -> Self {
         Self {
             party_id: state.party_id,
             key_ids: state.key_ids.clone(),
@@ -96,6 +129,8 @@ impl Party {
             private_keys: state.private_keys.clone(),
             group_key: state.group_key,
             nonce: Nonce::zero(),
+            lambdas: Default::default(),
+            challenge_hash: PhantomData,
         }
     }
 
@@ -115,7 +150,25 @@ impl Party {
 
     /// Generate and store a private nonce for a signing round
     pub fn gen_nonce<RNG: RngCore + CryptoRng>(&mut self, rng: &mut RNG) -> PublicNonce {
-        self.nonce = Nonce::random(rng);
+        self.gen_nonce_with_context(&[], &[], rng)
+    }
+
+    /// Generate and store a synthetic private nonce for a signing round, which mixes the private
+    /// keys, `aux_rand` and the round `context` in with `rng`'s output
+    pub fn gen_nonce_with_context<RNG: RngCore + CryptoRng>(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        rng: &mut RNG,
+    ) -> PublicNonce {
+        let mut key_ids = self.private_keys.keys().collect::<Vec<&u32>>();
+        key_ids.sort();
+        let mut secret = self.party_id.to_be_bytes().to_vec();
+        for key_id in key_ids {
+            secret.extend_from_slice(&key_id.to_be_bytes());
+            secret.extend_from_slice(&self.private_keys[key_id].to_bytes());
+        }
+        self.nonce = Nonce::synthetic(&secret, aux_rand, context, rng);
 
         PublicNonce::from(&self.nonce)
     }
@@ -125,7 +178,7 @@ impl Party {
         PolyCommitment {
             id: ID::new(&self.id(), &self.f.data()[0], rng),
             poly: (0..self.f.data().len())
-                .map(|i| &self.f.data()[i] * G)
+                .map(|i| ct::mul_base(&self.f.data()[i]))
                 .collect(),
         }
     }
@@ -143,7 +196,7 @@ impl Party {
     pub fn compute_secret(
         &mut self,
         shares: &HashMap<u32, HashMap<u32, Scalar>>,
-        comms: &[PolyCommitment],
+        comms: &[&PolyCommitment],
     ) -> Result<(), DkgError> {
         let mut missing_shares = Vec::new();
         for key_id in &self.key_ids {
@@ -155,13 +208,20 @@ impl Party {
             return Err(DkgError::MissingShares(missing_shares));
         }
 
-        let mut bad_ids = Vec::new();
-        for (i, comm) in comms.iter().enumerate() {
-            if !comm.verify() {
-                bad_ids.push(i.try_into().unwrap());
-            }
-            self.group_key += comm.poly[0];
-        }
+        self.group_key = comms.iter().fold(Point::zero(), |s, comm| s + comm.poly[0]);
+
+        // the commitments and each key's shares are checked independently, so with the rayon
+        // feature they are checked in parallel
+        #[cfg(feature = "rayon")]
+        let comms_iter = comms.par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let comms_iter = comms.iter();
+
+        let bad_ids: Vec<u32> = comms_iter
+            .enumerate()
+            .filter(|(_, comm)| !comm.verify())
+            .map(|(i, _)| i.try_into().unwrap())
+            .collect();
         if !bad_ids.is_empty() {
             return Err(DkgError::BadIds(bad_ids));
         }
@@ -177,15 +237,15 @@ impl Party {
             return Err(DkgError::NotEnoughShares(not_enough_shares));
         }
 
-        let mut bad_shares = Vec::new();
-        for key_id in &self.key_ids {
-            for (sender, s) in &shares[key_id] {
-                let comm = &comms[usize::try_from(*sender).unwrap()];
-                if s * G != compute::poly(&compute::id(*key_id), &comm.poly)? {
-                    bad_shares.push(*sender);
-                }
-            }
-        }
+        #[cfg(feature = "rayon")]
+        let key_ids_iter = self.key_ids.par_iter();
+        #[cfg(not(feature = "rayon"))]
+        let key_ids_iter = self.key_ids.iter();
+
+        let bad_shares = key_ids_iter
+            .map(|key_id| self.bad_shares(*key_id, &shares[key_id], comms))
+            .collect::<Result<Vec<Vec<u32>>, DkgError>>()?
+            .concat();
         if !bad_shares.is_empty() {
             return Err(DkgError::BadShares(bad_shares));
         }
@@ -202,6 +262,32 @@ impl Party {
         Ok(())
     }
 
+    /// The senders whose `shares` for `key_id` don't match their polynomial commitments; the
+    /// shares are checked as a batch with a single multi-exponentiation, and only checked one by
+    /// one to find the bad ones if the batch fails
+    fn bad_shares(
+        &self,
+        key_id: u32,
+        shares: &HashMap<u32, Scalar>,
+        comms: &[&PolyCommitment],
+    ) -> Result<Vec<u32>, DkgError> {
+        if (0..self.num_parties).all(|sender| shares.contains_key(&sender)) {
+            let mut check_shares = CheckPrivateShares::new(compute::id(key_id), shares, comms);
+            if Point::multimult_trait(&mut check_shares)? == Point::zero() {
+                return Ok(vec![]);
+            }
+        }
+
+        let mut bad_shares = Vec::new();
+        for (sender, s) in shares {
+            let comm = &comms[usize::try_from(*sender).unwrap()];
+            if ct::mul_base(s) != compute::poly(&compute::id(key_id), &comm.poly)? {
+                bad_shares.push(*sender);
+            }
+        }
+        Ok(bad_shares)
+    }
+
     /// Compute a Scalar from this party's ID
     pub fn id(&self) -> Scalar {
         compute::id(self.party_id)
@@ -218,6 +304,26 @@ impl Party {
         self.sign_with_tweak(msg, party_ids, key_ids, nonces, &Scalar::from(0))
     }
 
+    /// Sign the blinded `challenge` with this party's shares of the group private key, using the set of `key_ids` and corresponding `nonces`
+    pub fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> SignatureShare {
+        let mut z = &self.nonce.d
+            + &self.nonce.e * compute::binding(&self.id(), nonces, blind::BINDING_MSG);
+        for key_id in self.key_ids.iter() {
+            z += challenge * &self.private_keys[key_id] * self.lambdas.lambda(*key_id, key_ids);
+        }
+
+        SignatureShare {
+            id: self.party_id,
+            z_i: z,
+            key_ids: self.key_ids.clone().into(),
+        }
+    }
+
     /// Sign `msg` with this party's shares of the group private key, using the set of `party_ids`, `key_ids` and corresponding `nonces` with a tweaked public key
     #[allow(non_snake_case)]
     pub fn sign_with_tweak(
@@ -229,8 +335,8 @@ impl Party {
         tweak: &Scalar,
     ) -> SignatureShare {
         let tweaked_public_key = self.group_key + tweak * G;
-        let (_, R) = compute::intermediate(msg, party_ids, nonces);
-        let c = compute::challenge(&tweaked_public_key, &R, msg);
+        let R = compute::aggregate_nonce(msg, party_ids, nonces).unwrap();
+        let c = H::challenge(&tweaked_public_key, &R, msg);
         let mut r = &self.nonce.d + &self.nonce.e * compute::binding(&self.id(), nonces, msg);
         if tweak != &Scalar::zero() && !R.has_even_y() {
             r = -r;
@@ -238,7 +344,7 @@ impl Party {
 
         let mut cx = Scalar::zero();
         for key_id in self.key_ids.iter() {
-            cx += c * &self.private_keys[key_id] * compute::lambda(*key_id, key_ids);
+            cx += c * &self.private_keys[key_id] * self.lambdas.lambda(*key_id, key_ids);
         }
 
         if tweak != &Scalar::zero() && !tweaked_public_key.has_even_y() {
@@ -250,22 +356,66 @@ impl Party {
         SignatureShare {
             id: self.party_id,
             z_i: z,
-            key_ids: self.key_ids.clone(),
+            key_ids: self.key_ids.clone().into(),
         }
     }
 }
 
 /// The group signature aggregator
-pub struct Aggregator {
+pub struct Aggregator<H: ChallengeHash = Bip340Challenge> {
     /// The total number of keys
     pub num_keys: u32,
     /// The threshold of signing keys needed to construct a valid signature
     pub threshold: u32,
-    /// The aggregate group polynomial; poly[0] is the group public key
+    /// The aggregate group polynomial; poly[0] is the group public key, and it holds nothing
+    /// else if the aggregator was constructed from public keys
     pub poly: Vec<Point>,
+    /// The public key of each key ID
+    pub public_keys: HashMap<u32, Point>,
+    pending: Option<PendingSignature<H>>,
 }
 
-impl Aggregator {
+impl<H: ChallengeHash> Aggregator<H> {
+    /// Check the party signatures one at a time against their binding nonces `Rs`, to find the
+    /// bad ones
+    #[allow(non_snake_case)]
+    fn check_shares(
+        &self,
+        sig_shares: &[SignatureShare],
+        Rs: &[Point],
+        nonce_sign: &Scalar,
+        challenge: &Scalar,
+        key_ids: &[u32],
+    ) -> Result<(), AggregatorError> {
+        let mut bad_party_keys = Vec::new();
+        let mut bad_party_sigs = Vec::new();
+
+        for (sig_share, R_i) in sig_shares.iter().zip(Rs) {
+            match compute::verify_share_with_public_keys(
+                sig_share,
+                &(nonce_sign * R_i),
+                &self.public_keys,
+                challenge,
+                key_ids,
+            ) {
+                Some(true) => {}
+                Some(false) => bad_party_sigs.push(sig_share.id),
+                None => {
+                    bad_party_keys.push(sig_share.id);
+                    bad_party_sigs.push(sig_share.id);
+                }
+            }
+        }
+
+        if bad_party_sigs.is_empty() {
+            Ok(())
+        } else if !bad_party_keys.is_empty() {
+            Err(AggregatorError::BadPartyKeys(bad_party_keys))
+        } else {
+            Err(AggregatorError::BadPartySigs(bad_party_sigs))
+        }
+    }
+
     /// Check and aggregate the party signatures
     #[allow(non_snake_case)]
     pub fn sign_with_tweak(
@@ -281,13 +431,10 @@ impl Aggregator {
         }
 
         let party_ids: Vec<u32> = sig_shares.iter().map(|ss| ss.id).collect();
-        let (Rs, R) = compute::intermediate(msg, &party_ids, nonces);
-        let mut z = Scalar::zero();
-        let mut bad_party_keys = Vec::new();
-        let mut bad_party_sigs = Vec::new();
+        let R = compute::aggregate_nonce(msg, &party_ids, nonces)?;
         let aggregate_public_key = self.poly[0];
         let tweaked_public_key = aggregate_public_key + tweak * G;
-        let c = compute::challenge(&tweaked_public_key, &R, msg);
+        let c = H::challenge(&tweaked_public_key, &R, msg);
         let mut r_sign = Scalar::one();
         let mut cx_sign = Scalar::one();
         if tweak != &Scalar::zero() {
@@ -299,58 +446,94 @@ impl Aggregator {
             }
         }
 
-        for i in 0..sig_shares.len() {
-            let z_i = sig_shares[i].z_i;
-            let mut cx = Point::zero();
-
-            for key_id in &sig_shares[i].key_ids {
-                let kid = compute::id(*key_id);
-                let public_key = match compute::poly(&kid, &self.poly) {
-                    Ok(p) => p,
-                    Err(_) => {
-                        bad_party_keys.push(sig_shares[i].id);
-                        Point::zero()
-                    }
-                };
+        let challenge = cx_sign * c;
+        let mut z = sig_shares
+            .iter()
+            .fold(Scalar::zero(), |z, sig_share| z + sig_share.z_i);
 
-                cx += compute::lambda(*key_id, key_ids) * c * public_key;
-            }
+        // check all the shares at once, and only check them one at a time if that fails
+        if !compute::check_aggregate(&z, &R, &r_sign, &aggregate_public_key, &challenge)? {
+            let (Rs, _) = compute::intermediate(msg, &party_ids, nonces);
+            self.check_shares(sig_shares, &Rs, &r_sign, &challenge, key_ids)?;
+        }
 
-            if z_i * G != (r_sign * Rs[i] + cx_sign * cx) {
-                bad_party_sigs.push(sig_shares[i].id);
-            }
+        z += challenge * tweak;
 
-            z += z_i;
-        }
+        let sig = Signature { R, z };
+        Ok((tweaked_public_key, sig))
+    }
+}
 
-        z += cx_sign * c * tweak;
+impl Aggregator {
+    /// Construct an Aggregator with the passed parameters
+    pub fn new(num_keys: u32, threshold: u32) -> Self {
+        <Self as traits::Aggregator>::new(num_keys, threshold)
+    }
 
-        if bad_party_sigs.is_empty() {
-            let sig = Signature { R, z };
-            Ok((tweaked_public_key, sig))
-        } else if !bad_party_keys.is_empty() {
-            Err(AggregatorError::BadPartyKeys(bad_party_keys))
-        } else {
-            Err(AggregatorError::BadPartySigs(bad_party_sigs))
-        }
+    /// Construct an Aggregator from the group public key and the public key of each key ID
+    pub fn from_public_keys(
+        num_keys: u32,
+        threshold: u32,
+        group_key: Point,
+        public_keys: HashMap<u32, Point>,
+    ) -> Result<Self, AggregatorError> {
+        <Self as traits::Aggregator>::from_public_keys(num_keys, threshold, group_key, public_keys)
     }
 }
 
-impl traits::Aggregator for Aggregator {
+impl<H: ChallengeHash> traits::Aggregator for Aggregator<H> {
     /// Construct an Aggregator with the passed parameters
     fn new(num_keys: u32, threshold: u32) -> Self {
         Self {
             num_keys,
             threshold,
             poly: Default::default(),
+            public_keys: Default::default(),
+            pending: None,
+        }
+    }
+
+    /// Construct an Aggregator from the group public key and the public key of each key ID
+    fn from_public_keys(
+        num_keys: u32,
+        threshold: u32,
+        group_key: Point,
+        public_keys: HashMap<u32, Point>,
+    ) -> Result<Self, AggregatorError> {
+        let mut bad_keys: Vec<u32> = (0..num_keys)
+            .filter(|key_id| !public_keys.contains_key(key_id))
+            .chain(
+                public_keys
+                    .keys()
+                    .copied()
+                    .filter(|key_id| *key_id >= num_keys),
+            )
+            .collect();
+        if !bad_keys.is_empty() {
+            bad_keys.sort();
+            return Err(AggregatorError::BadPartyKeys(bad_keys));
+        }
+
+        let key_ids: Vec<u32> = (0..threshold).collect();
+        if compute::interpolate_public_key(&public_keys, &key_ids)? != group_key {
+            return Err(AggregatorError::BadGroupKey);
         }
+
+        Ok(Self {
+            num_keys,
+            threshold,
+            poly: vec![group_key],
+            public_keys,
+            pending: None,
+        })
     }
 
     /// Initialize the Aggregator polynomial
     fn init(&mut self, comms: Vec<PolyCommitment>) -> Result<(), AggregatorError> {
+        let threshold: usize = self.threshold.try_into().unwrap();
         let mut bad_poly_commitments = Vec::new();
         for comm in &comms {
-            if !comm.verify() {
+            if comm.poly.len() != threshold || !comm.verify() {
                 bad_poly_commitments.push(comm.id.id);
             }
         }
@@ -367,6 +550,7 @@ impl traits::Aggregator for Aggregator {
             }
         }
 
+        self.public_keys = compute::public_keys(self.num_keys, &poly)?;
         self.poly = poly;
 
         Ok(())
@@ -382,7 +566,7 @@ impl traits::Aggregator for Aggregator {
     ) -> Result<Signature, AggregatorError> {
         let (key, sig) = self.sign_with_tweak(msg, nonces, sig_shares, key_ids, &Scalar::zero())?;
 
-        if sig.verify(&key, msg) {
+        if sig.verify_with::<H>(&key, msg) {
             Ok(sig)
         } else {
             Err(AggregatorError::BadGroupSig)
@@ -390,19 +574,105 @@ impl traits::Aggregator for Aggregator {
     }
 
     /// Check and aggregate the party signatures
+    #[allow(non_snake_case)]
+    fn sign_blinded(
+        &mut self,
+        challenge: &Scalar,
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+    ) -> Result<Scalar, AggregatorError> {
+        if nonces.len() != sig_shares.len() {
+            return Err(AggregatorError::BadNonceLen(nonces.len(), sig_shares.len()));
+        }
+
+        let party_ids: Vec<u32> = sig_shares.iter().map(|ss| ss.id).collect();
+        let R = compute::aggregate_nonce(blind::BINDING_MSG, &party_ids, nonces)?;
+        let z = sig_shares
+            .iter()
+            .fold(Scalar::zero(), |z, sig_share| z + sig_share.z_i);
+
+        // check all the shares at once, and only check them one at a time if that fails
+        if !compute::check_aggregate(&z, &R, &Scalar::one(), &self.poly[0], challenge)? {
+            let (Rs, _) = compute::intermediate(blind::BINDING_MSG, &party_ids, nonces);
+            self.check_shares(sig_shares, &Rs, &Scalar::one(), challenge, key_ids)?;
+        }
+
+        Ok(z)
+    }
+
+    fn party_ids(signer_id: u32, _key_ids: &[u32]) -> Vec<u32> {
+        vec![signer_id]
+    }
+
+    fn start_sign(
+        &mut self,
+        msg: &[u8],
+        party_ids: &[u32],
+        nonces: &[PublicNonce],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<(), AggregatorError> {
+        self.pending = Some(PendingSignature::new(
+            msg,
+            party_ids,
+            nonces,
+            key_ids,
+            &self.poly[0],
+            tweak,
+        )?);
+        Ok(())
+    }
+
+    fn add_share(&mut self, share: SignatureShare) -> Result<(), AggregatorError> {
+        self.pending
+            .as_mut()
+            .ok_or(AggregatorError::NoPendingSignature)?
+            .add_share(share, &self.public_keys)
+    }
+
+    fn try_finalize(&mut self) -> Result<Option<Signature>, AggregatorError> {
+        let sig = self
+            .pending
+            .as_ref()
+            .ok_or(AggregatorError::NoPendingSignature)?
+            .finalize()?;
+        if sig.is_some() {
+            self.pending = None;
+        }
+        Ok(sig)
+    }
+
+    fn taproot_output_key(&self, tweak: TaprootTweak) -> Option<field::Element> {
+        self.poly
+            .first()
+            .map(|key| compute::tweaked_public_key(key, tweak).x())
+    }
+
     fn sign_taproot(
         &mut self,
         msg: &[u8],
         nonces: &[PublicNonce],
         sig_shares: &[SignatureShare],
         key_ids: &[u32],
-        merkle_root: Option<[u8; 32]>,
+        tweak: TaprootTweak,
+    ) -> Result<SchnorrProof, AggregatorError> {
+        let tweak = compute::taproot_tweak(&self.poly[0], tweak);
+        traits::Aggregator::sign_with_tweak(self, msg, nonces, sig_shares, key_ids, &tweak)
+    }
+
+    fn sign_with_tweak(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: &Scalar,
     ) -> Result<SchnorrProof, AggregatorError> {
-        let tweak = compute::tweak(&self.poly[0], merkle_root);
-        let (key, sig) = self.sign_with_tweak(msg, nonces, sig_shares, key_ids, &tweak)?;
+        let (key, sig) = self.sign_with_tweak(msg, nonces, sig_shares, key_ids, tweak)?;
         let proof = SchnorrProof::new(&sig);
 
-        if proof.verify(&key.x(), msg) {
+        if proof.verify_with::<H>(&key.x(), msg) {
             Ok(proof)
         } else {
             Err(AggregatorError::BadGroupSig)
@@ -413,9 +683,11 @@ impl traits::Aggregator for Aggregator {
 /// Typedef so we can use the same tokens for v1 and v2
 pub type SignerState = PartyState;
 /// Typedef so we can use the same tokens for v1 and v2
-pub type Signer = Party;
+pub type Signer<H = Bip340Challenge> = Party<H>;
+
+impl<H: ChallengeHash> traits::Signer for Party<H> {
+    type State = PartyState;
 
-impl traits::Signer for Party {
     fn new<RNG: RngCore + CryptoRng>(
         party_id: u32,
         key_ids: &[u32],
@@ -424,7 +696,15 @@ impl traits::Signer for Party {
         threshold: u32,
         rng: &mut RNG,
     ) -> Self {
-        Party::new(party_id, key_ids, num_signers, num_keys, threshold, rng)
+        Self::with_challenge_hash(party_id, key_ids, num_signers, num_keys, threshold, rng)
+    }
+
+    fn load(state: &PartyState) -> Self {
+        Self::load_with_challenge_hash(state)
+    }
+
+    fn save(&self) -> PartyState {
+        Party::save(self)
     }
 
     fn get_id(&self) -> u32 {
@@ -439,6 +719,18 @@ impl traits::Signer for Party {
         self.num_parties
     }
 
+    fn party_ids(signer_id: u32, _key_ids: &[u32]) -> Vec<u32> {
+        vec![compute::id(signer_id).get_u32()]
+    }
+
+    fn get_group_key(&self) -> Point {
+        self.group_key
+    }
+
+    fn get_private_keys(&self) -> HashMap<u32, Scalar> {
+        self.private_keys.clone()
+    }
+
     fn get_poly_commitments<RNG: RngCore + CryptoRng>(&self, rng: &mut RNG) -> Vec<PolyCommitment> {
         vec![self.get_poly_commitment(rng)]
     }
@@ -447,6 +739,23 @@ impl traits::Signer for Party {
         self.f = VSS::random_poly(self.threshold - 1, rng);
     }
 
+    fn reshare_polys<RNG: RngCore + CryptoRng>(
+        &mut self,
+        threshold: u32,
+        key_ids: &[u32],
+        rng: &mut RNG,
+    ) {
+        let secret = self
+            .private_keys
+            .iter()
+            .filter(|(key_id, _)| key_ids.contains(key_id))
+            .fold(Scalar::zero(), |s, (key_id, private_key)| {
+                s + compute::lambda(*key_id, key_ids) * private_key
+            });
+        self.threshold = threshold;
+        self.f = VSS::secret_poly(&secret, threshold - 1, rng);
+    }
+
     fn get_shares(&self) -> HashMap<u32, HashMap<u32, Scalar>> {
         let mut shares = HashMap::new();
 
@@ -458,7 +767,7 @@ impl traits::Signer for Party {
     fn compute_secrets(
         &mut self,
         private_shares: &HashMap<u32, HashMap<u32, Scalar>>,
-        polys: &[PolyCommitment],
+        polys: &[&PolyCommitment],
     ) -> Result<(), HashMap<u32, DkgError>> {
         // go through the shares, looking for this party's
         let mut key_shares = HashMap::new();
@@ -480,8 +789,23 @@ impl traits::Signer for Party {
         }
     }
 
-    fn gen_nonces<RNG: RngCore + CryptoRng>(&mut self, rng: &mut RNG) -> Vec<PublicNonce> {
-        vec![self.gen_nonce(rng)]
+    fn gen_nonces_with_context<RNG: RngCore + CryptoRng>(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        rng: &mut RNG,
+    ) -> Vec<PublicNonce> {
+        vec![self.gen_nonce_with_context(aux_rand, context, rng)]
+    }
+
+    fn get_nonces(&self) -> Vec<Nonce> {
+        vec![self.nonce.clone()]
+    }
+
+    fn set_nonces(&mut self, nonces: &[Nonce]) {
+        if let Some(nonce) = nonces.first() {
+            self.nonce = nonce.clone();
+        }
     }
 
     fn compute_intermediate(
@@ -509,11 +833,32 @@ impl traits::Signer for Party {
         signer_ids: &[u32],
         key_ids: &[u32],
         nonces: &[PublicNonce],
-        merkle_root: Option<[u8; 32]>,
+        tweak: TaprootTweak,
     ) -> Vec<SignatureShare> {
-        let tweak = compute::tweak(&self.group_key, merkle_root);
+        let tweak = compute::taproot_tweak(&self.group_key, tweak);
         vec![self.sign_with_tweak(msg, signer_ids, key_ids, nonces, &tweak)]
     }
+
+    fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        _signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        vec![self.sign_blinded(challenge, key_ids, nonces)]
+    }
+
+    fn sign_with_tweak(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: &Scalar,
+    ) -> Vec<SignatureShare> {
+        vec![self.sign_with_tweak(msg, signer_ids, key_ids, nonces, tweak)]
+    }
 }
 
 /// Helper functions for tests
@@ -542,6 +887,7 @@ pub mod test_helpers {
 
         // each party collects its shares from the broadcasts
         // maybe this should collect into a hashmap first?
+        let comms = polys.iter().collect::<Vec<&PolyCommitment>>();
         let mut secret_errors = HashMap::new();
         for party in signers.iter_mut() {
             let mut party_shares = HashMap::new();
@@ -555,7 +901,7 @@ pub mod test_helpers {
                 party_shares.insert(key_id, key_shares);
             }
 
-            if let Err(secret_error) = party.compute_secret(&party_shares, &polys) {
+            if let Err(secret_error) = party.compute_secret(&party_shares, &comms) {
                 secret_errors.insert(party.party_id, secret_error);
             }
         }
@@ -587,8 +933,15 @@ pub mod test_helpers {
 
 #[cfg(test)]
 mod tests {
-    use crate::{traits::Aggregator, v2};
-
+    use crate::{
+        common::{self, PublicNonce},
+        compute,
+        errors::{AggregatorError, DkgError},
+        traits::{Aggregator, Signer},
+        v2,
+    };
+
+    use p256k1::{point::G, scalar::Scalar};
     use rand_core::OsRng;
 
     #[test]
@@ -641,10 +994,276 @@ mod tests {
 
             sig_agg.init(comms.clone()).expect("aggregator init failed");
 
-            let (nonces, sig_shares, key_ids) = v2::test_helpers::sign(msg, &mut signers, &mut rng);
+            let (nonces, mut sig_shares, key_ids) =
+                v2::test_helpers::sign(msg, &mut signers, &mut rng);
             if let Err(e) = sig_agg.sign(msg, &nonces, &sig_shares, &key_ids) {
                 panic!("Aggregator sign failed: {:?}", e);
             }
+
+            // a bad share fails the batch check, and is then found on its own
+            sig_shares[1].z_i += Scalar::from(1);
+            match sig_agg.sign(msg, &nonces, &sig_shares, &key_ids) {
+                Err(AggregatorError::BadPartySigs(ids)) => assert_eq!(ids, [sig_shares[1].id]),
+                _ => panic!("Expected BadPartySigs"),
+            }
+
+            // an aggregator built from just the public keys checks shares the same way
+            let mut key_agg = v2::Aggregator::from_public_keys(
+                Nk,
+                T,
+                sig_agg.poly[0],
+                sig_agg.public_keys.clone(),
+            )
+            .expect("aggregator from public keys failed");
+            match key_agg.sign(msg, &nonces, &sig_shares, &key_ids) {
+                Err(AggregatorError::BadPartySigs(ids)) => assert_eq!(ids, [sig_shares[1].id]),
+                _ => panic!("Expected BadPartySigs"),
+            }
+            sig_shares[1].z_i = sig_shares[1].z_i - Scalar::from(1);
+            if let Err(e) = key_agg.sign(msg, &nonces, &sig_shares, &key_ids) {
+                panic!("Aggregator sign failed: {:?}", e);
+            }
+
+            let mut public_keys = sig_agg.public_keys.clone();
+            public_keys.remove(&0);
+            assert!(matches!(
+                v2::Aggregator::from_public_keys(Nk, T, sig_agg.poly[0], public_keys),
+                Err(AggregatorError::BadPartyKeys(ids)) if ids == [0]
+            ));
+            let mut public_keys = sig_agg.public_keys.clone();
+            public_keys.insert(0, sig_agg.poly[0]);
+            assert!(matches!(
+                v2::Aggregator::from_public_keys(Nk, T, sig_agg.poly[0], public_keys),
+                Err(AggregatorError::BadGroupKey)
+            ));
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn aggregator_add_share() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let Nk: u32 = 10;
+        let T: u32 = 7;
+        let party_key_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let Np = party_key_ids.len().try_into().unwrap();
+        let mut signers: Vec<v2::Party> = party_key_ids
+            .iter()
+            .enumerate()
+            .map(|(pid, pkids)| v2::Party::new(pid.try_into().unwrap(), pkids, Np, Nk, T, &mut rng))
+            .collect();
+
+        let comms = match v2::test_helpers::dkg(&mut signers, &mut rng) {
+            Ok(comms) => comms,
+            Err(secret_errors) => {
+                panic!("Got secret errors from DKG: {:?}", secret_errors);
+            }
+        };
+
+        let mut signers = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
+        let mut sig_agg = v2::Aggregator::new(Nk, T);
+        sig_agg.init(comms).expect("aggregator init failed");
+
+        let (nonces, sig_shares, key_ids) = v2::test_helpers::sign(msg, &mut signers, &mut rng);
+        let party_ids: Vec<u32> = sig_shares.iter().map(|share| share.id).collect();
+
+        assert!(matches!(
+            sig_agg.add_share(sig_shares[0].clone()),
+            Err(AggregatorError::NoPendingSignature)
+        ));
+        sig_agg
+            .start_sign(msg, &party_ids, &nonces, &key_ids, &Scalar::from(0))
+            .expect("start_sign failed");
+
+        // a bad share is rejected as soon as it's added
+        let mut bad_share = sig_shares[0].clone();
+        bad_share.z_i += Scalar::from(1);
+        match sig_agg.add_share(bad_share) {
+            Err(AggregatorError::BadPartySigs(ids)) => assert_eq!(ids, [sig_shares[0].id]),
+            _ => panic!("Expected BadPartySigs"),
+        }
+
+        let (last_share, shares) = sig_shares.split_last().unwrap();
+        for share in shares {
+            sig_agg.add_share(share.clone()).expect("add_share failed");
+            assert!(sig_agg.try_finalize().unwrap().is_none());
+        }
+        sig_agg
+            .add_share(last_share.clone())
+            .expect("add_share failed");
+
+        let sig = sig_agg
+            .try_finalize()
+            .expect("try_finalize failed")
+            .expect("signature should be complete");
+        assert!(sig.verify(&sig_agg.poly[0], msg));
+        assert!(common::verify(msg, &sig, &sig_agg.poly[0]));
+        assert!(!common::verify(b"other message", &sig, &sig_agg.poly[0]));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn group_secret() {
+        let mut rng = OsRng;
+        let Nk: u32 = 10;
+        let T: u32 = 7;
+        let party_key_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let Np = party_key_ids.len().try_into().unwrap();
+        let mut signers: Vec<v2::Party> = party_key_ids
+            .iter()
+            .enumerate()
+            .map(|(pid, pkids)| v2::Party::new(pid.try_into().unwrap(), pkids, Np, Nk, T, &mut rng))
+            .collect();
+
+        if let Err(secret_errors) = v2::test_helpers::dkg(&mut signers, &mut rng) {
+            panic!("Got secret errors from DKG: {:?}", secret_errors);
+        }
+        let group_key = signers[0].get_group_key();
+
+        // signers [0,1,3] who have T keys
+        let secret = compute::group_secret(
+            &[signers[0].clone(), signers[1].clone(), signers[3].clone()],
+            T,
+        )
+        .unwrap();
+        assert_eq!(secret * G, group_key);
+
+        let secret = compute::group_secret(&signers, T).unwrap();
+        assert_eq!(secret * G, group_key);
+
+        // signers [0,1] only have 5 keys
+        assert!(matches!(
+            compute::group_secret(&signers[..2], T),
+            Err(DkgError::NotEnoughShares(_))
+        ));
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn verify_share() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let Nk: u32 = 10;
+        let T: u32 = 7;
+        let party_key_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let Np = party_key_ids.len().try_into().unwrap();
+        let mut signers: Vec<v2::Party> = party_key_ids
+            .iter()
+            .enumerate()
+            .map(|(pid, pkids)| v2::Party::new(pid.try_into().unwrap(), pkids, Np, Nk, T, &mut rng))
+            .collect();
+
+        let comms = match v2::test_helpers::dkg(&mut signers, &mut rng) {
+            Ok(comms) => comms,
+            Err(secret_errors) => {
+                panic!("Got secret errors from DKG: {:?}", secret_errors);
+            }
+        };
+        let mut sig_agg = v2::Aggregator::new(Nk, T);
+        sig_agg.init(comms).expect("aggregator init failed");
+
+        // signers [0,1,3] who have T keys
+        let mut signers = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
+        let party_ids: Vec<u32> = signers.iter().map(|s| s.party_id).collect();
+        let (nonces, sig_shares, key_ids) = v2::test_helpers::sign(msg, &mut signers, &mut rng);
+        let (Rs, R) = compute::intermediate(msg, &party_ids, &nonces);
+
+        for (share, R_i) in sig_shares.iter().zip(&Rs) {
+            assert!(compute::verify_share(share, R_i, &R, &sig_agg.poly, msg, &key_ids).unwrap());
+            assert!(
+                !compute::verify_share(share, R_i, &R, &sig_agg.poly, b"other", &key_ids).unwrap()
+            );
+
+            let mut bad_share = share.clone();
+            bad_share.z_i += Scalar::from(1);
+            assert!(
+                !compute::verify_share(&bad_share, R_i, &R, &sig_agg.poly, msg, &key_ids).unwrap()
+            );
+        }
+    }
+
+    #[allow(non_snake_case)]
+    #[test]
+    fn sign_with_tweak() {
+        let mut rng = OsRng;
+        let msg = "It was many and many a year ago".as_bytes();
+        let Nk: u32 = 10;
+        let T: u32 = 7;
+        let party_key_ids: Vec<Vec<u32>> = [
+            [0, 1, 2].to_vec(),
+            [3, 4].to_vec(),
+            [5, 6, 7].to_vec(),
+            [8, 9].to_vec(),
+        ]
+        .to_vec();
+        let Np = party_key_ids.len().try_into().unwrap();
+        let mut signers: Vec<v2::Party> = party_key_ids
+            .iter()
+            .enumerate()
+            .map(|(pid, pkids)| v2::Party::new(pid.try_into().unwrap(), pkids, Np, Nk, T, &mut rng))
+            .collect();
+
+        let comms = match v2::test_helpers::dkg(&mut signers, &mut rng) {
+            Ok(comms) => comms,
+            Err(secret_errors) => {
+                panic!("Got secret errors from DKG: {:?}", secret_errors);
+            }
+        };
+        let group_key = signers[0].get_group_key();
+
+        // signers [0,1,3] who have T keys
+        let mut signers = [signers[0].clone(), signers[1].clone(), signers[3].clone()].to_vec();
+        let party_ids: Vec<u32> = signers.iter().map(|s| s.party_id).collect();
+        let key_ids: Vec<u32> = signers.iter().flat_map(|s| s.key_ids.clone()).collect();
+        let mut sig_agg = v2::Aggregator::new(Nk, T);
+        sig_agg.init(comms).expect("aggregator init failed");
+
+        // sign for several child keys, so both parities of the tweaked key are covered
+        for _ in 0..8 {
+            let tweak = Scalar::random(&mut rng);
+            let nonces: Vec<PublicNonce> = signers
+                .iter_mut()
+                .flat_map(|s| s.gen_nonces(&mut rng))
+                .collect();
+            let sig_shares: Vec<v2::SignatureShare> = signers
+                .iter()
+                .flat_map(|s| {
+                    Signer::sign_with_tweak(s, msg, &party_ids, &key_ids, &nonces, &tweak)
+                })
+                .collect();
+
+            let proof = Aggregator::sign_with_tweak(
+                &mut sig_agg,
+                msg,
+                &nonces,
+                &sig_shares,
+                &key_ids,
+                &tweak,
+            )
+            .expect("aggregator sign_with_tweak failed");
+
+            let child_key = group_key + tweak * G;
+            assert!(proof.verify(&child_key.x(), msg));
+            assert!(!proof.verify(&group_key.x(), msg));
         }
     }
 }
@@ -0,0 +1,416 @@
+use hashbrown::HashMap;
+use p256k1::{field, point::Point, scalar::Scalar};
+use rand_core::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    common::{
+        KeyIdRanges, Nonce, PolyCommitment, PublicNonce, Signature, SignatureShare, TaprootTweak,
+    },
+    compute::{self, Bip340Challenge},
+    errors::{AggregatorError, DkgError},
+    taproot::SchnorrProof,
+    traits::{self, Aggregator as AggregatorTrait, ChallengeHash},
+    v1,
+};
+
+/// Drop the key IDs from a share, since a v3 share's only key ID is its party ID
+fn trim(share: SignatureShare) -> SignatureShare {
+    SignatureShare {
+        key_ids: KeyIdRanges::default(),
+        ..share
+    }
+}
+
+/// Put back the key IDs which `trim` dropped, so the shares can be checked by the v1 aggregator
+fn untrim(shares: &[SignatureShare]) -> Vec<SignatureShare> {
+    shares.iter().cloned().map(untrim_one).collect()
+}
+
+fn untrim_one(mut share: SignatureShare) -> SignatureShare {
+    if share.key_ids.is_empty() {
+        share.key_ids = vec![share.id].into();
+    }
+    share
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+/// The saved state required to construct a Signer
+pub struct SignerState {
+    /// The associated ID
+    id: u32,
+    /// The signer's only key ID
+    key_id: u32,
+    /// The total number of keys
+    num_keys: u32,
+    /// The aggregate group public key
+    group_key: Point,
+    /// The state of the party for the signer's key
+    party: v1::PartyState,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+/// A signer which holds exactly one key, so it is a single FROST party with none of the per-key
+/// bookkeeping of a v1 signer
+pub struct Signer<H: ChallengeHash = Bip340Challenge> {
+    /// The associated signer ID
+    id: u32,
+    /// The party for the signer's key
+    party: v1::Party<H>,
+}
+
+impl Signer {
+    /// Construct a random Signer with the passed ID, key ID, and parameters
+    pub fn new<RNG: RngCore + CryptoRng>(
+        id: u32,
+        key_id: u32,
+        num_keys: u32,
+        threshold: u32,
+        rng: &mut RNG,
+    ) -> Self {
+        Self::with_challenge_hash(id, key_id, num_keys, threshold, rng)
+    }
+
+    /// Load a Signer from the saved state
+    pub fn load(state: &SignerState) -> Self {
+        Self::load_with_challenge_hash(state)
+    }
+}
+
+impl<H: ChallengeHash> Signer<H> {
+    /// Construct a random Signer with the passed ID, key ID, and parameters, which uses the
+    /// challenge hash `H`
+    pub fn with_challenge_hash<RNG: RngCore + CryptoRng>(
+        id: u32,
+        key_id: u32,
+        num_keys: u32,
+        threshold: u32,
+        rng: &mut RNG,
+    ) -> Self {
+        Self {
+            id,
+            party: v1::Party::with_challenge_hash(key_id, num_keys, threshold, rng),
+        }
+    }
+
+    /// Load a Signer from the saved state, which uses the challenge hash `H`
+    pub fn load_with_challenge_hash(state: &SignerState) -> Self {
+        Self {
+            id: state.id,
+            party: v1::Party::load_with_challenge_hash(
+                state.key_id,
+                state.num_keys,
+                &state.group_key,
+                &state.party,
+            ),
+        }
+    }
+
+    /// Save the state required to reconstruct the signer
+    pub fn save(&self) -> SignerState {
+        SignerState {
+            id: self.id,
+            key_id: self.party.id,
+            num_keys: self.party.n,
+            group_key: self.party.group_key,
+            party: self.party.save(),
+        }
+    }
+}
+
+impl<H: ChallengeHash> traits::Signer for Signer<H> {
+    type State = SignerState;
+
+    fn new<RNG: RngCore + CryptoRng>(
+        party_id: u32,
+        key_ids: &[u32],
+        _num_signers: u32,
+        num_keys: u32,
+        threshold: u32,
+        rng: &mut RNG,
+    ) -> Self {
+        assert_eq!(key_ids.len(), 1, "v3 signers hold exactly one key");
+        Self::with_challenge_hash(party_id, key_ids[0], num_keys, threshold, rng)
+    }
+
+    fn load(state: &SignerState) -> Self {
+        Self::load_with_challenge_hash(state)
+    }
+
+    fn save(&self) -> SignerState {
+        Signer::save(self)
+    }
+
+    fn get_id(&self) -> u32 {
+        self.id
+    }
+
+    fn get_key_ids(&self) -> Vec<u32> {
+        vec![self.party.id]
+    }
+
+    fn get_num_parties(&self) -> u32 {
+        self.party.n
+    }
+
+    fn party_ids(signer_id: u32, key_ids: &[u32]) -> Vec<u32> {
+        <v1::Signer as traits::Signer>::party_ids(signer_id, key_ids)
+    }
+
+    fn get_group_key(&self) -> Point {
+        self.party.group_key
+    }
+
+    fn get_private_keys(&self) -> HashMap<u32, Scalar> {
+        HashMap::from([(self.party.id, self.party.private_key)])
+    }
+
+    fn get_poly_commitments<RNG: RngCore + CryptoRng>(&self, rng: &mut RNG) -> Vec<PolyCommitment> {
+        vec![self.party.get_poly_commitment(rng)]
+    }
+
+    fn reset_polys<RNG: RngCore + CryptoRng>(&mut self, rng: &mut RNG) {
+        self.party.reset_poly(rng);
+    }
+
+    fn reshare_polys<RNG: RngCore + CryptoRng>(
+        &mut self,
+        threshold: u32,
+        key_ids: &[u32],
+        rng: &mut RNG,
+    ) {
+        self.party.reshare_poly(threshold, key_ids, rng);
+    }
+
+    fn get_shares(&self) -> HashMap<u32, HashMap<u32, Scalar>> {
+        HashMap::from([(self.party.id, self.party.get_shares())])
+    }
+
+    fn compute_secrets(
+        &mut self,
+        private_shares: &HashMap<u32, HashMap<u32, Scalar>>,
+        polys: &[&PolyCommitment],
+    ) -> Result<(), HashMap<u32, DkgError>> {
+        let key_shares = private_shares
+            .iter()
+            .map(|(signer_id, signer_shares)| (*signer_id, signer_shares[&self.party.id]))
+            .collect();
+        self.party
+            .compute_secret(key_shares, polys)
+            .map_err(|e| HashMap::from([(self.party.id, e)]))
+    }
+
+    fn gen_nonces_with_context<RNG: RngCore + CryptoRng>(
+        &mut self,
+        aux_rand: &[u8],
+        context: &[u8],
+        rng: &mut RNG,
+    ) -> Vec<PublicNonce> {
+        vec![self.party.gen_nonce_with_context(aux_rand, context, rng)]
+    }
+
+    fn get_nonces(&self) -> Vec<Nonce> {
+        vec![self.party.nonce.clone()]
+    }
+
+    fn set_nonces(&mut self, nonces: &[Nonce]) {
+        if let Some(nonce) = nonces.first() {
+            self.party.nonce = nonce.clone();
+        }
+    }
+
+    fn compute_intermediate(
+        msg: &[u8],
+        _signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> (Vec<Point>, Point) {
+        compute::intermediate(msg, key_ids, nonces)
+    }
+
+    fn sign(
+        &self,
+        msg: &[u8],
+        _signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        let aggregate_nonce = compute::aggregate_nonce(msg, key_ids, nonces).unwrap();
+        vec![trim(self.party.sign_precomputed(
+            msg,
+            key_ids,
+            nonces,
+            &aggregate_nonce,
+        ))]
+    }
+
+    fn sign_taproot(
+        &self,
+        msg: &[u8],
+        signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: TaprootTweak,
+    ) -> Vec<SignatureShare> {
+        let tweak = compute::taproot_tweak(&self.party.group_key, tweak);
+        traits::Signer::sign_with_tweak(self, msg, signer_ids, key_ids, nonces, &tweak)
+    }
+
+    fn sign_blinded(
+        &self,
+        challenge: &Scalar,
+        _signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+    ) -> Vec<SignatureShare> {
+        vec![trim(self.party.sign_blinded(challenge, key_ids, nonces))]
+    }
+
+    fn sign_with_tweak(
+        &self,
+        msg: &[u8],
+        _signer_ids: &[u32],
+        key_ids: &[u32],
+        nonces: &[PublicNonce],
+        tweak: &Scalar,
+    ) -> Vec<SignatureShare> {
+        let aggregate_nonce = compute::aggregate_nonce(msg, key_ids, nonces).unwrap();
+        vec![trim(self.party.sign_precomputed_with_tweak(
+            msg,
+            key_ids,
+            nonces,
+            &aggregate_nonce,
+            tweak,
+        ))]
+    }
+}
+
+/// The group signature aggregator, which accepts the trimmed shares from v3 signers
+pub struct Aggregator<H: ChallengeHash = Bip340Challenge>(v1::Aggregator<H>);
+
+impl Aggregator {
+    /// Construct an Aggregator with the passed parameters
+    pub fn new(num_keys: u32, threshold: u32) -> Self {
+        <Self as traits::Aggregator>::new(num_keys, threshold)
+    }
+
+    /// Construct an Aggregator from the group public key and the public key of each key ID
+    pub fn from_public_keys(
+        num_keys: u32,
+        threshold: u32,
+        group_key: Point,
+        public_keys: HashMap<u32, Point>,
+    ) -> Result<Self, AggregatorError> {
+        <Self as traits::Aggregator>::from_public_keys(num_keys, threshold, group_key, public_keys)
+    }
+}
+
+impl<H: ChallengeHash> traits::Aggregator for Aggregator<H> {
+    fn new(num_keys: u32, threshold: u32) -> Self {
+        Self(<v1::Aggregator<H> as traits::Aggregator>::new(
+            num_keys, threshold,
+        ))
+    }
+
+    fn init(&mut self, poly_comms: Vec<PolyCommitment>) -> Result<(), AggregatorError> {
+        self.0.init(poly_comms)
+    }
+
+    fn from_public_keys(
+        num_keys: u32,
+        threshold: u32,
+        group_key: Point,
+        public_keys: HashMap<u32, Point>,
+    ) -> Result<Self, AggregatorError> {
+        <v1::Aggregator<H> as traits::Aggregator>::from_public_keys(
+            num_keys,
+            threshold,
+            group_key,
+            public_keys,
+        )
+        .map(Self)
+    }
+
+    fn sign(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+    ) -> Result<Signature, AggregatorError> {
+        AggregatorTrait::sign(&mut self.0, msg, nonces, &untrim(sig_shares), key_ids)
+    }
+
+    fn sign_taproot(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: TaprootTweak,
+    ) -> Result<SchnorrProof, AggregatorError> {
+        AggregatorTrait::sign_taproot(
+            &mut self.0,
+            msg,
+            nonces,
+            &untrim(sig_shares),
+            key_ids,
+            tweak,
+        )
+    }
+
+    fn sign_blinded(
+        &mut self,
+        challenge: &Scalar,
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+    ) -> Result<Scalar, AggregatorError> {
+        AggregatorTrait::sign_blinded(&mut self.0, challenge, nonces, &untrim(sig_shares), key_ids)
+    }
+
+    fn party_ids(signer_id: u32, key_ids: &[u32]) -> Vec<u32> {
+        v1::Aggregator::<H>::party_ids(signer_id, key_ids)
+    }
+
+    fn start_sign(
+        &mut self,
+        msg: &[u8],
+        party_ids: &[u32],
+        nonces: &[PublicNonce],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<(), AggregatorError> {
+        self.0.start_sign(msg, party_ids, nonces, key_ids, tweak)
+    }
+
+    fn add_share(&mut self, share: SignatureShare) -> Result<(), AggregatorError> {
+        self.0.add_share(untrim_one(share))
+    }
+
+    fn try_finalize(&mut self) -> Result<Option<Signature>, AggregatorError> {
+        self.0.try_finalize()
+    }
+
+    fn taproot_output_key(&self, tweak: TaprootTweak) -> Option<field::Element> {
+        self.0.taproot_output_key(tweak)
+    }
+
+    fn sign_with_tweak(
+        &mut self,
+        msg: &[u8],
+        nonces: &[PublicNonce],
+        sig_shares: &[SignatureShare],
+        key_ids: &[u32],
+        tweak: &Scalar,
+    ) -> Result<SchnorrProof, AggregatorError> {
+        AggregatorTrait::sign_with_tweak(
+            &mut self.0,
+            msg,
+            nonces,
+            &untrim(sig_shares),
+            key_ids,
+            tweak,
+        )
+    }
+}
@@ -0,0 +1,481 @@
+use hashbrown::HashMap;
+use p256k1::{point::Point, scalar::Scalar};
+use rand_core::{impls, CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+use crate::{
+    common::{PolyCommitment, PublicNonce, SignatureShare},
+    compute,
+    errors::AggregatorError,
+    traits::{Aggregator, Signer, Version},
+    v1, v2, v3,
+};
+
+#[derive(thiserror::Error, Debug, Clone)]
+/// Errors from generating and validating test vectors
+pub enum Error {
+    #[error("bad params {0}")]
+    /// The params couldn't be used to run DKG and sign
+    BadParams(String),
+    #[error("bad hex {0:?}")]
+    /// The seed or message wasn't valid hex
+    BadHex(String),
+    #[error("dkg failed for key IDs {0:?}")]
+    /// DKG failed, which can't happen with honest signers
+    Dkg(Vec<u32>),
+    #[error("aggregator error {0:?}")]
+    /// The signature shares couldn't be aggregated
+    Aggregator(AggregatorError),
+    #[error("test vector doesn't match at {0}")]
+    /// A value in the test vector didn't match the one computed from its params
+    Mismatch(&'static str),
+}
+
+/// A deterministic RNG which hashes its seed with a block counter, so a test vector can be
+/// regenerated from its seed.  It must never be used for real keys or nonces.
+pub struct SeededRng {
+    seed: [u8; 32],
+    counter: u64,
+    block: [u8; 32],
+    pos: usize,
+}
+
+impl SeededRng {
+    /// Create an RNG which outputs the bytes derived from `seed`
+    pub fn new(seed: [u8; 32]) -> Self {
+        Self {
+            seed,
+            counter: 0,
+            block: [0; 32],
+            pos: 32,
+        }
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for byte in dest {
+            if self.pos == self.block.len() {
+                let mut hasher = Sha256::new();
+                hasher.update(self.seed);
+                hasher.update(self.counter.to_be_bytes());
+                self.block = hasher.finalize().into();
+                self.counter += 1;
+                self.pos = 0;
+            }
+            *byte = self.block[self.pos];
+            self.pos += 1;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for SeededRng {}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// The inputs which a test vector is generated from
+pub struct Params {
+    /// the WSTS version
+    pub version: Version,
+    /// the hex encoded 32-byte seed of the `SeededRng` which makes every random value
+    pub seed: String,
+    /// the total number of keys
+    pub num_keys: u32,
+    /// the threshold of keys needed to sign
+    pub threshold: u32,
+    /// the key IDs of each signer, indexed by signer ID
+    pub key_ids: Vec<Vec<u32>>,
+    /// the signers which take part in the signing round
+    pub signer_ids: Vec<u32>,
+    /// the hex encoded message to sign
+    pub message: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+/// A party's polynomial commitment with its schnorr proof of the constant term
+pub struct PolyVector {
+    /// the party ID as a scalar
+    pub id: String,
+    /// the schnorr proof's public nonce
+    pub kG: String,
+    /// the schnorr proof's response
+    pub kca: String,
+    /// the commitments to the polynomial coefficients, constant term first
+    pub poly: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// The DKG transcript
+pub struct DkgVector {
+    /// the polynomial commitment of every party
+    pub polys: Vec<PolyVector>,
+    /// the private shares sent by each party, indexed by party ID then destination key ID
+    pub shares: BTreeMap<u32, BTreeMap<u32, String>>,
+    /// the private key of every key ID which resulted
+    pub private_keys: BTreeMap<u32, String>,
+    /// the group public key
+    pub group_key: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+/// A public nonce
+pub struct NonceVector {
+    /// the commitment to the first nonce value
+    pub D: String,
+    /// the commitment to the second nonce value
+    pub E: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A signature share
+pub struct ShareVector {
+    /// the party ID
+    pub id: u32,
+    /// the key IDs the share signs for
+    pub key_ids: Vec<u32>,
+    /// the share
+    pub z: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[allow(non_snake_case)]
+/// The signing round transcript, with the intermediate values every implementation computes
+pub struct SignVector {
+    /// the public nonce of every party which signs
+    pub nonces: Vec<NonceVector>,
+    /// the binding value of every party which signs
+    pub bindings: Vec<String>,
+    /// each party's nonce commitment after binding, `D + rho * E`
+    pub party_nonces: Vec<String>,
+    /// the aggregate nonce
+    pub R: String,
+    /// the schnorr challenge
+    pub challenge: String,
+    /// the signature share of every party which signs
+    pub shares: Vec<ShareVector>,
+    /// the aggregate signature's nonce
+    pub signature_R: String,
+    /// the aggregate signature's response
+    pub signature_z: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+/// A test vector covering a DKG round and a signing round, with every point compressed and every
+/// scalar big-endian, both hex encoded
+pub struct TestVector {
+    /// the inputs the vector was generated from
+    pub params: Params,
+    /// the DKG transcript
+    pub dkg: DkgVector,
+    /// the signing round transcript
+    pub sign: SignVector,
+}
+
+impl TestVector {
+    /// Generate the test vector for `params`
+    pub fn generate(params: Params) -> Result<Self, Error> {
+        match params.version {
+            Version::V1 => generate::<v1::Signer, v1::Aggregator>(params),
+            Version::V2 => generate::<v2::Signer, v2::Aggregator>(params),
+            Version::V3 => generate::<v3::Signer, v3::Aggregator>(params),
+        }
+    }
+
+    /// Check that every value in the vector matches the one generated from its params
+    pub fn validate(&self) -> Result<(), Error> {
+        let expected = Self::generate(self.params.clone())?;
+        let dkg = [
+            (self.dkg.polys == expected.dkg.polys, "dkg.polys"),
+            (self.dkg.shares == expected.dkg.shares, "dkg.shares"),
+            (
+                self.dkg.private_keys == expected.dkg.private_keys,
+                "dkg.private_keys",
+            ),
+            (
+                self.dkg.group_key == expected.dkg.group_key,
+                "dkg.group_key",
+            ),
+        ];
+        let sign = [
+            (self.sign.nonces == expected.sign.nonces, "sign.nonces"),
+            (
+                self.sign.bindings == expected.sign.bindings,
+                "sign.bindings",
+            ),
+            (
+                self.sign.party_nonces == expected.sign.party_nonces,
+                "sign.party_nonces",
+            ),
+            (self.sign.R == expected.sign.R, "sign.R"),
+            (
+                self.sign.challenge == expected.sign.challenge,
+                "sign.challenge",
+            ),
+            (self.sign.shares == expected.sign.shares, "sign.shares"),
+            (
+                self.sign.signature_R == expected.sign.signature_R,
+                "sign.signature_R",
+            ),
+            (
+                self.sign.signature_z == expected.sign.signature_z,
+                "sign.signature_z",
+            ),
+        ];
+        match dkg.into_iter().chain(sign).find(|(matches, _)| !matches) {
+            Some((_, field)) => Err(Error::Mismatch(field)),
+            None => Ok(()),
+        }
+    }
+}
+
+fn point_hex(point: &Point) -> String {
+    hex::encode(point.compress().as_bytes())
+}
+
+fn scalar_hex(scalar: &Scalar) -> String {
+    hex::encode(scalar.to_bytes())
+}
+
+#[allow(non_snake_case)]
+fn generate<S: Signer, A: Aggregator>(params: Params) -> Result<TestVector, Error> {
+    let seed: [u8; 32] = hex::decode(&params.seed)
+        .ok()
+        .and_then(|seed| seed.try_into().ok())
+        .ok_or_else(|| Error::BadHex(params.seed.clone()))?;
+    let msg = hex::decode(&params.message).map_err(|_| Error::BadHex(params.message.clone()))?;
+    let num_signers = params.key_ids.len() as u32;
+    let num_keys = params.key_ids.iter().map(Vec::len).sum::<usize>() as u32;
+    if num_keys != params.num_keys || params.threshold == 0 || params.threshold > num_keys {
+        return Err(Error::BadParams(format!(
+            "{} keys with threshold {}",
+            num_keys, params.threshold
+        )));
+    }
+    if params.signer_ids.iter().any(|id| *id >= num_signers) {
+        return Err(Error::BadParams(format!(
+            "signer IDs {:?}",
+            params.signer_ids
+        )));
+    }
+    let mut rng = SeededRng::new(seed);
+
+    let mut signers: Vec<S> = params
+        .key_ids
+        .iter()
+        .enumerate()
+        .map(|(signer_id, key_ids)| {
+            S::new(
+                signer_id as u32,
+                key_ids,
+                num_signers,
+                params.num_keys,
+                params.threshold,
+                &mut rng,
+            )
+        })
+        .collect();
+    let polys: Vec<PolyCommitment> = signers
+        .iter()
+        .flat_map(|signer| signer.get_poly_commitments(&mut rng))
+        .collect();
+    let mut shares = HashMap::new();
+    for signer in &signers {
+        shares.extend(signer.get_shares());
+    }
+    let comms = polys.iter().collect::<Vec<&PolyCommitment>>();
+    for signer in signers.iter_mut() {
+        signer
+            .compute_secrets(&shares, &comms)
+            .map_err(|errors| Error::Dkg(errors.keys().copied().collect()))?;
+    }
+    let group_key = signers[0].get_group_key();
+
+    let dkg = DkgVector {
+        polys: polys
+            .iter()
+            .map(|comm| PolyVector {
+                id: scalar_hex(&comm.id.id),
+                kG: point_hex(&comm.id.kG),
+                kca: scalar_hex(&comm.id.kca),
+                poly: comm.poly.iter().map(point_hex).collect(),
+            })
+            .collect(),
+        shares: shares
+            .iter()
+            .map(|(party_id, shares)| {
+                let shares = shares
+                    .iter()
+                    .map(|(key_id, share)| (*key_id, scalar_hex(share)))
+                    .collect();
+                (*party_id, shares)
+            })
+            .collect(),
+        private_keys: signers
+            .iter()
+            .flat_map(|signer| signer.get_private_keys())
+            .map(|(key_id, key)| (key_id, scalar_hex(&key)))
+            .collect(),
+        group_key: point_hex(&group_key),
+    };
+
+    let mut signing_set: Vec<&mut S> = signers
+        .iter_mut()
+        .filter(|signer| params.signer_ids.contains(&signer.get_id()))
+        .collect();
+    let key_ids: Vec<u32> = signing_set
+        .iter()
+        .flat_map(|signer| signer.get_key_ids())
+        .collect();
+    let signer_ids: Vec<u32> = signing_set.iter().map(|signer| signer.get_id()).collect();
+    let party_ids: Vec<u32> = signing_set
+        .iter()
+        .flat_map(|signer| S::party_ids(signer.get_id(), &signer.get_key_ids()))
+        .collect();
+    let nonces: Vec<PublicNonce> = signing_set
+        .iter_mut()
+        .flat_map(|signer| signer.gen_nonces(&mut rng))
+        .collect();
+    let (party_nonces, R) = S::compute_intermediate(&msg, &signer_ids, &key_ids, &nonces);
+    let sig_shares: Vec<SignatureShare> = signing_set
+        .iter()
+        .flat_map(|signer| signer.sign(&msg, &signer_ids, &key_ids, &nonces))
+        .collect();
+
+    let mut aggregator = A::new(params.num_keys, params.threshold);
+    aggregator.init(polys).map_err(Error::Aggregator)?;
+    let sig = aggregator
+        .sign(&msg, &nonces, &sig_shares, &key_ids)
+        .map_err(Error::Aggregator)?;
+
+    let sign = SignVector {
+        nonces: nonces
+            .iter()
+            .map(|nonce| NonceVector {
+                D: point_hex(&nonce.D),
+                E: point_hex(&nonce.E),
+            })
+            .collect(),
+        bindings: compute::bindings(&msg, &party_ids, &nonces)
+            .iter()
+            .map(scalar_hex)
+            .collect(),
+        party_nonces: party_nonces.iter().map(point_hex).collect(),
+        R: point_hex(&R),
+        challenge: scalar_hex(&compute::challenge(&group_key, &R, &msg)),
+        shares: sig_shares
+            .iter()
+            .map(|share| ShareVector {
+                id: share.id,
+                key_ids: share.key_ids.to_vec(),
+                z: scalar_hex(&share.z_i),
+            })
+            .collect(),
+        signature_R: point_hex(&sig.R),
+        signature_z: scalar_hex(&sig.z),
+    };
+
+    Ok(TestVector { params, dkg, sign })
+}
+
+#[cfg(test)]
+mod test {
+    use rand_core::RngCore;
+
+    use super::{Error, Params, SeededRng, TestVector};
+    use crate::traits::Version;
+
+    fn params(version: Version, key_ids: Vec<Vec<u32>>, signer_ids: Vec<u32>) -> Params {
+        Params {
+            version,
+            seed: hex::encode([7; 32]),
+            num_keys: key_ids.iter().map(Vec::len).sum::<usize>() as u32,
+            threshold: 3,
+            key_ids,
+            signer_ids,
+            message: hex::encode("It was many and many a year ago"),
+        }
+    }
+
+    #[test]
+    fn seeded_rng() {
+        let mut bytes = [[0; 40]; 2];
+        SeededRng::new([1; 32]).fill_bytes(&mut bytes[0]);
+        SeededRng::new([1; 32]).fill_bytes(&mut bytes[1]);
+        assert_eq!(bytes[0], bytes[1]);
+        SeededRng::new([2; 32]).fill_bytes(&mut bytes[1]);
+        assert_ne!(bytes[0], bytes[1]);
+    }
+
+    #[test]
+    fn generate_and_validate() {
+        for params in [
+            params(Version::V1, vec![vec![0, 1], vec![2], vec![3]], vec![0, 2]),
+            params(Version::V2, vec![vec![0, 1], vec![2], vec![3]], vec![0, 2]),
+            params(
+                Version::V3,
+                vec![vec![0], vec![1], vec![2], vec![3]],
+                vec![0, 1, 3],
+            ),
+        ] {
+            let vector = TestVector::generate(params.clone()).unwrap();
+            assert_eq!(TestVector::generate(params).unwrap(), vector);
+
+            let json = serde_json::to_string_pretty(&vector).unwrap();
+            let vector: TestVector = serde_json::from_str(&json).unwrap();
+            assert!(vector.validate().is_ok());
+
+            let mut tampered = vector.clone();
+            tampered.sign.bindings[0] = tampered.sign.challenge.clone();
+            assert!(matches!(
+                tampered.validate(),
+                Err(Error::Mismatch("sign.bindings"))
+            ));
+
+            let mut tampered = vector;
+            tampered.params.seed = hex::encode([8; 32]);
+            assert!(matches!(
+                tampered.validate(),
+                Err(Error::Mismatch("dkg.polys"))
+            ));
+        }
+    }
+
+    #[test]
+    fn bad_params() {
+        let mut bad_seed = params(Version::V1, vec![vec![0, 1], vec![2, 3]], vec![0, 1]);
+        bad_seed.seed = "00".to_string();
+        assert!(matches!(
+            TestVector::generate(bad_seed),
+            Err(Error::BadHex(_))
+        ));
+
+        let mut bad_threshold = params(Version::V2, vec![vec![0, 1], vec![2, 3]], vec![0, 1]);
+        bad_threshold.threshold = 5;
+        assert!(matches!(
+            TestVector::generate(bad_threshold),
+            Err(Error::BadParams(_))
+        ));
+
+        let bad_signer = params(Version::V2, vec![vec![0, 1], vec![2, 3]], vec![0, 2]);
+        assert!(matches!(
+            TestVector::generate(bad_signer),
+            Err(Error::BadParams(_))
+        ));
+    }
+}
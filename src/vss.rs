@@ -1,6 +1,18 @@
-use p256k1::scalar::Scalar;
+use num_traits::Zero;
+use p256k1::{
+    field,
+    point::{Point, G},
+    scalar::Scalar,
+};
 use polynomial::Polynomial;
 use rand_core::{CryptoRng, RngCore};
+use sha2::Digest;
+use std::sync::OnceLock;
+
+use crate::{compute, ct};
+
+/// The tag hashed, along with a counter, to find the second generator of Pedersen commitments
+const PEDERSEN_GENERATOR_TAG: &str = "WSTS/pedersen/generator";
 
 /// A verifiable secret share algorithm
 pub struct VSS {}
@@ -11,4 +23,152 @@ impl VSS {
         let params: Vec<Scalar> = (0..n + 1).map(|_| Scalar::random(rng)).collect();
         Polynomial::new(params)
     }
+
+    /// Construct a random polynomial of the passed degree `n` whose constant term is `secret`
+    pub fn secret_poly<RNG: RngCore + CryptoRng>(
+        secret: &Scalar,
+        n: u32,
+        rng: &mut RNG,
+    ) -> Polynomial<Scalar> {
+        let mut params: Vec<Scalar> = (0..n + 1).map(|_| Scalar::random(rng)).collect();
+        params[0] = *secret;
+        Polynomial::new(params)
+    }
+
+    /// Split `secret` into `n` shares, any `t` of which can reconstruct it
+    pub fn split<RNG: RngCore + CryptoRng>(
+        secret: &Scalar,
+        t: u32,
+        n: u32,
+        rng: &mut RNG,
+    ) -> Vec<(u32, Scalar)> {
+        let poly = Self::secret_poly(secret, t - 1, rng);
+
+        (0..n).map(|i| (i, poly.eval(compute::id(i)))).collect()
+    }
+
+    /// Reconstruct a secret from shares made by `split`; at least `t` distinct shares must be passed
+    pub fn reconstruct(shares: &[(u32, Scalar)]) -> Scalar {
+        let ids: Vec<u32> = shares.iter().map(|(id, _)| *id).collect();
+        shares.iter().fold(Scalar::zero(), |s, (id, share)| {
+            s + compute::lambda(*id, &ids) * share
+        })
+    }
+
+    /// The second generator `H` of Pedersen commitments, found by hashing to the curve so that
+    /// nobody knows its discrete log with respect to `G`
+    pub fn pedersen_generator() -> Point {
+        static GENERATOR: OnceLock<Point> = OnceLock::new();
+        *GENERATOR.get_or_init(|| {
+            (0u32..)
+                .find_map(|counter| {
+                    let mut hasher = compute::tagged_hash(PEDERSEN_GENERATOR_TAG);
+                    hasher.update(counter.to_be_bytes());
+                    let x: [u8; 32] = hasher.finalize().into();
+                    Point::lift_x(&field::Element::from(x)).ok()
+                })
+                .expect("half of all x coordinates are on the curve")
+        })
+    }
+
+    /// Hide the Feldman commitment `comm` to a polynomial behind the `blinding` polynomial, giving
+    /// the Pedersen commitment `a_k*G + b_k*H` to each coefficient
+    pub fn hiding_commitment(comm: &[Point], blinding: &Polynomial<Scalar>) -> Vec<Point> {
+        let h = Self::pedersen_generator();
+        comm.iter()
+            .zip(blinding.data())
+            .map(|(a, b)| a + ct::mul(b, &h))
+            .collect()
+    }
+
+    /// Check that `share` and `blinding_share`, dealt to `id`, match the Pedersen commitment
+    /// `hiding_comm`
+    pub fn verify_hiding_share(
+        id: &Scalar,
+        share: &Scalar,
+        blinding_share: &Scalar,
+        hiding_comm: &[Point],
+    ) -> bool {
+        match compute::poly(id, hiding_comm) {
+            Ok(expected) => expected == share * G + blinding_share * Self::pedersen_generator(),
+            Err(_) => false,
+        }
+    }
+
+    /// Check that the Feldman commitment `comm` and the `blinding` coefficients open the Pedersen
+    /// commitment `hiding_comm`, which pins `comm` down once `hiding_comm` has been agreed on
+    pub fn verify_opening(hiding_comm: &[Point], comm: &[Point], blinding: &[Scalar]) -> bool {
+        let h = Self::pedersen_generator();
+        hiding_comm.len() == comm.len()
+            && comm.len() == blinding.len()
+            && hiding_comm
+                .iter()
+                .zip(comm.iter().zip(blinding))
+                .all(|(c, (a, b))| *c == a + b * h)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use p256k1::scalar::Scalar;
+    use rand_core::OsRng;
+
+    use super::*;
+
+    #[test]
+    fn split_reconstruct() {
+        let mut rng = OsRng;
+        let secret = Scalar::random(&mut rng);
+        let shares = VSS::split(&secret, 3, 5, &mut rng);
+
+        assert_eq!(shares.len(), 5);
+        assert_eq!(VSS::reconstruct(&shares[..3]), secret);
+        assert_eq!(VSS::reconstruct(&shares[2..]), secret);
+        assert_eq!(VSS::reconstruct(&shares), secret);
+        assert_ne!(VSS::reconstruct(&shares[..2]), secret);
+    }
+
+    #[test]
+    fn hiding_commitment() {
+        let mut rng = OsRng;
+        let h = VSS::pedersen_generator();
+        assert_eq!(h, VSS::pedersen_generator());
+        assert_ne!(h, G);
+
+        let poly = VSS::random_poly(2, &mut rng);
+        let blinding = VSS::random_poly(2, &mut rng);
+        let comm = poly.data().iter().map(|a| a * G).collect::<Vec<Point>>();
+        let hiding_comm = VSS::hiding_commitment(&comm, &blinding);
+
+        let id = compute::id(4);
+        let (share, blinding_share) = (poly.eval(id), blinding.eval(id));
+        assert!(VSS::verify_hiding_share(
+            &id,
+            &share,
+            &blinding_share,
+            &hiding_comm
+        ));
+        assert!(!VSS::verify_hiding_share(
+            &id,
+            &(share + Scalar::from(1)),
+            &blinding_share,
+            &hiding_comm
+        ));
+        assert!(!VSS::verify_hiding_share(
+            &compute::id(5),
+            &share,
+            &blinding_share,
+            &hiding_comm
+        ));
+
+        assert!(VSS::verify_opening(&hiding_comm, &comm, blinding.data()));
+        let mut other = comm.clone();
+        other[1] += h;
+        assert!(!VSS::verify_opening(&hiding_comm, &other, blinding.data()));
+        assert!(!VSS::verify_opening(
+            &hiding_comm,
+            &comm[1..],
+            &blinding.data()[1..]
+        ));
+    }
 }
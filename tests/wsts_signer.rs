@@ -0,0 +1,114 @@
+use p256k1::{ecdsa, scalar::Scalar};
+use rand_core::OsRng;
+use std::{
+    env, fs,
+    net::{TcpListener, TcpStream},
+    process::{Child, Command},
+    thread,
+    time::{Duration, Instant},
+};
+
+use wsts::{
+    net::{DkgBegin, Message, Packet},
+    state_machine::{signer::SignerConfig, KeyId, PublicKeys},
+};
+
+const DOMAIN: &[u8] = b"wsts-signer-test";
+
+/// Kills the signer when the test finishes, even if it fails
+struct Signer(Child);
+
+impl Drop for Signer {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+fn dkg_begin(dkg_id: u64) -> Message {
+    Message::DkgBegin(DkgBegin {
+        dkg_id,
+        threshold: 1,
+        num_signers: 1,
+        num_keys: 1,
+        pedersen: false,
+        epoch: 0,
+    })
+}
+
+/// Wait for the signer to connect, failing if it exits or takes too long
+fn accept(listener: &TcpListener, signer: &mut Signer) -> TcpStream {
+    listener.set_nonblocking(true).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(60);
+    loop {
+        if let Ok((stream, _)) = listener.accept() {
+            stream.set_nonblocking(false).unwrap();
+            stream
+                .set_read_timeout(Some(Duration::from_secs(60)))
+                .unwrap();
+            return stream;
+        }
+        if let Some(status) = signer.0.try_wait().unwrap() {
+            panic!("wsts-signer exited with {status}");
+        }
+        assert!(Instant::now() < deadline, "wsts-signer didn't connect");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn forged_packets_are_dropped() {
+    let mut rng = OsRng;
+    let signer_key = Scalar::random(&mut rng);
+    let signer_public_key = ecdsa::PublicKey::new(&signer_key).unwrap();
+    let coordinator_key = Scalar::random(&mut rng);
+    let forger_key = Scalar::random(&mut rng);
+    let public_keys = PublicKeys {
+        signers: [(0, signer_public_key)].into_iter().collect(),
+        key_ids: [(KeyId(0).to_public(), signer_public_key)]
+            .into_iter()
+            .collect(),
+        coordinators: vec![ecdsa::PublicKey::new(&coordinator_key).unwrap()],
+    };
+    let mut signer_config = SignerConfig::new(1, 0, vec![0], signer_key, public_keys);
+    signer_config.domain = DOMAIN.to_vec();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let config = serde_json::json!({
+        "coordinator": listener.local_addr().unwrap().to_string(),
+        "version": "V2",
+        "signer": signer_config,
+        "reconnect_secs": 1,
+    });
+    let config_path = env::temp_dir().join(format!("wsts-signer-{}.json", std::process::id()));
+    fs::write(&config_path, serde_json::to_vec(&config).unwrap()).unwrap();
+
+    let mut signer = Signer(
+        Command::new(env!("CARGO_BIN_EXE_wsts-signer"))
+            .arg(&config_path)
+            .spawn()
+            .unwrap(),
+    );
+    let mut stream = accept(&listener, &mut signer);
+    fs::remove_file(&config_path).unwrap();
+
+    // a DkgBegin signed by a key which isn't the coordinator's, then genuine ones, with a replay
+    // of an earlier one which would otherwise restart its round
+    let forged = Packet::new(dkg_begin(1), 1, DOMAIN, &forger_key).unwrap();
+    let first = Packet::new(dkg_begin(2), 2, DOMAIN, &coordinator_key).unwrap();
+    let second = Packet::new(dkg_begin(3), 3, DOMAIN, &coordinator_key).unwrap();
+    let third = Packet::new(dkg_begin(4), 4, DOMAIN, &coordinator_key).unwrap();
+    for packet in [&forged, &first, &second, &first, &third] {
+        packet.write_frame(&mut stream, false).unwrap();
+    }
+
+    // only the genuine packets are answered, each once
+    let mut answered = vec![];
+    while answered.last() != Some(&4) {
+        let packet = Packet::read_frame(&mut stream).unwrap();
+        if let Message::DkgPublicShares(shares) = &packet.msg {
+            answered.push(shares.dkg_id);
+        }
+    }
+    assert_eq!(answered, vec![2, 3, 4]);
+}